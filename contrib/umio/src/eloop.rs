@@ -132,26 +132,33 @@ where
                         }
 
                         timeouts.append(&mut pending);
+
+                        // Wait for the earliest timeout, waking early if a new one is pushed in the meantime
+                        if let Some(wait) = timeouts
+                            .peek()
+                            .and_then(|next| next.when.checked_duration_since(Instant::now()))
+                        {
+                            let (mut pending, _) = cvar.wait_timeout(pending, wait).unwrap();
+
+                            timeouts.append(&mut pending);
+                        }
                     }
 
-                    while let Some(timeout) = timeouts.pop() {
-                        let Some(token) = Weak::upgrade(&timeout.token) else {
-                            continue;
-                        };
+                    while let Some(timeout) = timeouts.peek() {
+                        if timeout.when > Instant::now() {
+                            break;
+                        }
 
-                        match timeout.when.checked_duration_since(Instant::now()) {
-                            Some(wait) => {
-                                std::thread::sleep(wait);
-                                elapsed.push_back(token);
-                                break;
-                            }
-                            None => elapsed.push_back(token),
+                        if let Some(token) = timeouts.pop().and_then(|timeout| Weak::upgrade(&timeout.token)) {
+                            elapsed.push_back(token);
                         }
                     }
 
-                    let mut finished = finished.lock().unwrap();
-                    finished.append(&mut elapsed);
-                    waker.wake().unwrap();
+                    if !elapsed.is_empty() {
+                        let mut finished = finished.lock().unwrap();
+                        finished.append(&mut elapsed);
+                        waker.wake().unwrap();
+                    }
                 }
             })
         };
@@ -167,13 +174,18 @@ where
 
     #[instrument(skip(self))]
     fn next(&mut self) -> Option<T> {
-        let token = self.finished.lock().unwrap().pop_front()?;
+        loop {
+            let token = self.finished.lock().unwrap().pop_front()?;
 
-        let token = if self.remove(&token) { Arc::into_inner(token) } else { None };
+            // Skip over timeouts that were removed after they had already elapsed
+            if self.remove(&token) {
+                let token = Arc::into_inner(token);
 
-        tracing::trace!(?token, "next timeout");
+                tracing::trace!(?token, "next timeout");
 
-        token
+                return token;
+            }
+        }
     }
 
     #[instrument(skip(self))]
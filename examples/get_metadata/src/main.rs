@@ -249,11 +249,16 @@ async fn main() {
                         tracing::info!("Connected To Peer: {info:?}");
                         IUberMessage::Control(Box::new(ControlMessage::PeerConnected(info)))
                     }
-                    Ok(PeerManagerOutputMessage::PeerRemoved(info)) => {
-                        tracing::info!("We Removed Peer {info:?} From The Peer Manager");
+                    Ok(PeerManagerOutputMessage::PeerRemoved(info, reason)) => {
+                        tracing::info!("We Removed Peer {info:?} From The Peer Manager: {reason}");
                         IUberMessage::Control(Box::new(ControlMessage::PeerDisconnected(info)))
                     }
                     Ok(PeerManagerOutputMessage::SentMessage(_, _)) => todo!(),
+                    Ok(PeerManagerOutputMessage::PeerParked(_) | PeerManagerOutputMessage::PeerRevived(_)) => continue,
+                    Ok(PeerManagerOutputMessage::ProtocolViolation(info, violation)) => {
+                        tracing::warn!("Peer {info:?} Violated The Protocol: {violation:?}");
+                        continue;
+                    }
                     Ok(PeerManagerOutputMessage::ReceivedMessage(info, message)) => match message {
                         PeerWireProtocolMessage::BitsExtension(message) => match message {
                             BitsExtensionMessage::Extended(extended) => {
@@ -269,8 +274,8 @@ async fn main() {
                         },
                         _ => unimplemented!(),
                     },
-                    Ok(PeerManagerOutputMessage::PeerDisconnect(info)) => {
-                        tracing::info!("Peer {info:?} Disconnected From Us");
+                    Ok(PeerManagerOutputMessage::PeerDisconnect(info, reason)) => {
+                        tracing::info!("Peer {info:?} Disconnected From Us: {reason}");
                         IUberMessage::Control(Box::new(ControlMessage::PeerDisconnected(info)))
                     }
                     Err(e) => {
@@ -412,12 +412,12 @@ async fn handle_peer_manager_messages(
                 _ => None,
             },
             Ok(PeerManagerOutputMessage::PeerAdded(peer_info)) => Some(Either::Left(PeerSelectionState::NewPeer(peer_info))),
-            Ok(PeerManagerOutputMessage::PeerRemoved(peer_info)) => {
-                println!("Removed Peer {peer_info:?} From The Peer Manager");
+            Ok(PeerManagerOutputMessage::PeerRemoved(peer_info, reason)) => {
+                println!("Removed Peer {peer_info:?} From The Peer Manager: {reason}");
                 Some(Either::Left(PeerSelectionState::RemovedPeer(peer_info)))
             }
-            Ok(PeerManagerOutputMessage::PeerDisconnect(peer_info)) => {
-                println!("Peer {peer_info:?} Disconnected From Us");
+            Ok(PeerManagerOutputMessage::PeerDisconnect(peer_info, reason)) => {
+                println!("Peer {peer_info:?} Disconnected From Us: {reason}");
                 Some(Either::Left(PeerSelectionState::RemovedPeer(peer_info)))
             }
             Err(PeerManagerOutputError::PeerError(peer_info, error)) => {
@@ -425,7 +425,13 @@ async fn handle_peer_manager_messages(
                 Some(Either::Left(PeerSelectionState::RemovedPeer(peer_info)))
             }
 
-            Err(_) | Ok(PeerManagerOutputMessage::SentMessage(_, _)) => None,
+            Err(_)
+            | Ok(
+                PeerManagerOutputMessage::SentMessage(_, _)
+                | PeerManagerOutputMessage::ProtocolViolation(_, _)
+                | PeerManagerOutputMessage::PeerParked(_)
+                | PeerManagerOutputMessage::PeerRevived(_),
+            ) => None,
         };
 
         if let Some(message) = opt_message {
@@ -35,6 +35,11 @@ pub trait BRefAccess: Sized {
 
     /// Attempt to access the bencode as an `BDictAccess`.
     fn dict(&self) -> Option<&dyn BDictAccess<Self::BKey, Self::BType>>;
+
+    /// Byte offset of the bencode within the buffer it was decoded from, if any.
+    fn pos(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// Trait for extended read access to some bencode type.
@@ -80,6 +85,10 @@ where
     fn dict(&self) -> Option<&dyn BDictAccess<Self::BKey, Self::BType>> {
         (*self).dict()
     }
+
+    fn pos(&self) -> Option<usize> {
+        (*self).pos()
+    }
 }
 
 impl<'a: 'b, 'b, T> BRefAccessExt<'a> for &'b T
@@ -2,6 +2,7 @@
 use crate::access::bencode::{BRefAccess, BRefAccessExt};
 use crate::access::dict::BDictAccess;
 use crate::access::list::BListAccess;
+use crate::error::BencodePath;
 use crate::BencodeConvertError;
 
 /// Trait for extended casting of bencode objects and converting conversion errors into application specific errors.
@@ -12,10 +13,9 @@ pub trait BConvertExt: BConvert {
         B: BRefAccessExt<'a>,
         E: AsRef<[u8]>,
     {
-        bencode.bytes_ext().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "Bytes".to_owned(),
-        }))
+        bencode
+            .bytes_ext()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "Bytes")))
     }
 
     /// See `BConvert::convert_str`.
@@ -24,10 +24,9 @@ pub trait BConvertExt: BConvert {
         B: BRefAccessExt<'a>,
         E: AsRef<[u8]>,
     {
-        bencode.str_ext().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "UTF-8 Bytes".to_owned(),
-        }))
+        bencode
+            .str_ext()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "UTF-8 Bytes")))
     }
 
     /// See `BConvert::lookup_and_convert_bytes`.
@@ -40,7 +39,11 @@ pub trait BConvertExt: BConvert {
         B: BRefAccessExt<'a>,
         K2: AsRef<[u8]>,
     {
-        self.convert_bytes_ext(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .bytes_ext()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "Bytes").in_key(&key)))
     }
 
     /// See `BConvert::lookup_and_convert_str`.
@@ -53,7 +56,11 @@ pub trait BConvertExt: BConvert {
         B: BRefAccessExt<'a>,
         K2: AsRef<[u8]>,
     {
-        self.convert_str_ext(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .str_ext()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "UTF-8 Bytes").in_key(&key)))
     }
 }
 
@@ -73,10 +80,9 @@ pub trait BConvert {
         B: BRefAccess,
         E: AsRef<[u8]>,
     {
-        bencode.int().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "Integer".to_owned(),
-        }))
+        bencode
+            .int()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "Integer")))
     }
 
     /// Attempt to convert the given bencode value into bytes.
@@ -87,10 +93,9 @@ pub trait BConvert {
         B: BRefAccess,
         E: AsRef<[u8]>,
     {
-        bencode.bytes().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "Bytes".to_owned(),
-        }))
+        bencode
+            .bytes()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "Bytes")))
     }
 
     /// Attempt to convert the given bencode value into a UTF-8 string.
@@ -101,10 +106,9 @@ pub trait BConvert {
         B: BRefAccess,
         E: AsRef<[u8]>,
     {
-        bencode.str().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "UTF-8 Bytes".to_owned(),
-        }))
+        bencode
+            .str()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "UTF-8 Bytes")))
     }
 
     /// Attempt to convert the given bencode value into a list.
@@ -115,10 +119,9 @@ pub trait BConvert {
         B: BRefAccess,
         E: AsRef<[u8]>,
     {
-        bencode.list().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "List".to_owned(),
-        }))
+        bencode
+            .list()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "List")))
     }
 
     /// Attempt to convert the given bencode value into a dictionary.
@@ -129,10 +132,9 @@ pub trait BConvert {
         B: BRefAccess,
         E: AsRef<[u8]>,
     {
-        bencode.dict().ok_or(self.handle_error(BencodeConvertError::WrongType {
-            key: error_key.as_ref().to_owned(),
-            expected_type: "Dictionary".to_owned(),
-        }))
+        bencode
+            .dict()
+            .ok_or_else(|| self.handle_error(wrong_type(&bencode, error_key, "Dictionary")))
     }
 
     /// Look up a value in a dictionary of bencoded values using the given key.
@@ -145,7 +147,10 @@ pub trait BConvert {
 
         match dictionary.lookup(key_ref) {
             Some(n) => Ok(n),
-            None => Err(self.handle_error(BencodeConvertError::MissingKey { key: key_ref.to_owned() })),
+            None => Err(self.handle_error(BencodeConvertError::MissingKey {
+                key: key_ref.to_owned(),
+                path: BencodePath::new().with_key(key_ref),
+            })),
         }
     }
 
@@ -155,7 +160,11 @@ pub trait BConvert {
         B: BRefAccess,
         K2: AsRef<[u8]>,
     {
-        self.convert_int(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .int()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "Integer").in_key(&key)))
     }
 
     /// Combines a lookup operation on the given key with a conversion of the value, if found, to a series of bytes.
@@ -168,7 +177,11 @@ pub trait BConvert {
         B: BRefAccess,
         K2: AsRef<[u8]>,
     {
-        self.convert_bytes(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .bytes()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "Bytes").in_key(&key)))
     }
 
     /// Combines a lookup operation on the given key with a conversion of the value, if found, to a UTF-8 string.
@@ -181,7 +194,11 @@ pub trait BConvert {
         B: BRefAccess,
         K2: AsRef<[u8]>,
     {
-        self.convert_str(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .str()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "UTF-8 Bytes").in_key(&key)))
     }
 
     /// Combines a lookup operation on the given key with a conversion of the value, if found, to a list.
@@ -194,7 +211,11 @@ pub trait BConvert {
         B: BRefAccess,
         K2: AsRef<[u8]>,
     {
-        self.convert_list(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .list()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "List").in_key(&key)))
     }
 
     /// Combines a lookup operation on the given key with a conversion of the value, if found, to a dictionary.
@@ -207,6 +228,89 @@ pub trait BConvert {
         B: BRefAccess,
         K2: AsRef<[u8]>,
     {
-        self.convert_dict(self.lookup(dictionary, &key)?, &key)
+        let value = self.lookup(dictionary, &key)?;
+
+        value
+            .dict()
+            .ok_or_else(|| self.handle_error(wrong_type(value, &key, "Dictionary").in_key(&key)))
+    }
+}
+
+/// Conversion error for a value of the wrong type, it is only nested within a key when looked up.
+fn wrong_type<B, E>(bencode: &B, error_key: E, expected_type: &str) -> BencodeConvertError
+where
+    B: BRefAccess,
+    E: AsRef<[u8]>,
+{
+    BencodeConvertError::WrongType {
+        key: error_key.as_ref().to_owned(),
+        expected_type: expected_type.to_owned(),
+        path: BencodePath::new(),
+        pos: bencode.pos(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access::bencode::BRefAccess;
+    use crate::access::convert::BConvert;
+    use crate::reference::bencode_ref::BencodeRef;
+    use crate::reference::decode_opt::BDecodeOpt;
+    use crate::BencodeConvertError;
+
+    struct Convert;
+
+    impl BConvert for Convert {
+        type Error = BencodeConvertError;
+
+        fn handle_error(&self, error: BencodeConvertError) -> BencodeConvertError {
+            error
+        }
+    }
+
+    /* cSpell:disable */
+    const FILES: &[u8] = b"d5:filesld6:lengthi1eed6:length1:1eee";
+    /* cSpell:enable */
+
+    #[test]
+    fn positive_convert_error_path() {
+        let bencode = BencodeRef::decode(FILES, BDecodeOpt::default()).unwrap();
+        let files = bencode.dict().unwrap().lookup(b"files").unwrap().list().unwrap();
+
+        let err = Convert
+            .lookup_and_convert_int(files.get(1).unwrap().dict().unwrap(), "length")
+            .unwrap_err()
+            .in_index(1)
+            .in_key("files");
+
+        assert_eq!(Some(31), err.pos());
+        assert_eq!(
+            "Wrong Type In Bencode For files[1].length At 31 Expected Type Integer",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn negative_convert_missing_key_path() {
+        let bencode = BencodeRef::decode(FILES, BDecodeOpt::default()).unwrap();
+        let files = bencode.dict().unwrap().lookup(b"files").unwrap().list().unwrap();
+
+        let err = Convert
+            .lookup(files.get(0).unwrap().dict().unwrap(), "path")
+            .unwrap_err()
+            .in_index(0)
+            .in_key("files");
+
+        assert_eq!(None, err.pos());
+        assert_eq!("Missing Key In Bencode For files[0].path", err.to_string());
+    }
+
+    #[test]
+    fn negative_convert_without_lookup() {
+        let bencode = BencodeRef::decode(FILES, BDecodeOpt::default()).unwrap();
+
+        let err = Convert.convert_list(&bencode, "root").map(|_| ()).unwrap_err();
+
+        assert_eq!("Wrong Type In Bencode For root At 0 Expected Type List", err.to_string());
     }
 }
@@ -1,6 +1,8 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 
+use bytes::Bytes;
+
 /// Trait for working with generic map data structures.
 pub trait BDictAccess<K, V> {
     /// Convert the dictionary to an unordered list of key/value pairs.
@@ -17,6 +19,20 @@ pub trait BDictAccess<K, V> {
 
     /// Remove a value from the dictionary and return it.
     fn remove(&mut self, key: &[u8]) -> Option<V>;
+
+    /// Iterate over the key/value pairs in the order they were inserted, which for decoded
+    /// dictionaries is the order they appeared in on the wire.
+    ///
+    /// Dictionaries that do not remember their insertion order iterate in sorted order.
+    fn iter_raw(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_>;
+
+    /// Iterate over the key/value pairs in the order of their keys, as required for encoding.
+    fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_>;
+
+    /// Whether or not the insertion order of the keys is also their sorted order.
+    ///
+    /// For decoded dictionaries, this tells whether the source was in canonical form.
+    fn is_sorted(&self) -> bool;
 }
 
 impl<'a, V> BDictAccess<&'a [u8], V> for BTreeMap<&'a [u8], V> {
@@ -39,6 +55,18 @@ impl<'a, V> BDictAccess<&'a [u8], V> for BTreeMap<&'a [u8], V> {
     fn remove(&mut self, key: &[u8]) -> Option<V> {
         self.remove(key)
     }
+
+    fn iter_raw(&self) -> Box<dyn Iterator<Item = (&&'a [u8], &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&&'a [u8], &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn is_sorted(&self) -> bool {
+        true
+    }
 }
 
 impl<'a, V> BDictAccess<Cow<'a, [u8]>, V> for BTreeMap<Cow<'a, [u8]>, V> {
@@ -61,4 +89,50 @@ impl<'a, V> BDictAccess<Cow<'a, [u8]>, V> for BTreeMap<Cow<'a, [u8]>, V> {
     fn remove(&mut self, key: &[u8]) -> Option<V> {
         self.remove(key)
     }
+
+    fn iter_raw(&self) -> Box<dyn Iterator<Item = (&Cow<'a, [u8]>, &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&Cow<'a, [u8]>, &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn is_sorted(&self) -> bool {
+        true
+    }
+}
+
+impl<V> BDictAccess<Bytes, V> for BTreeMap<Bytes, V> {
+    fn to_list(&self) -> Vec<(&Bytes, &V)> {
+        self.iter().collect()
+    }
+
+    fn lookup(&self, key: &[u8]) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn lookup_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn insert(&mut self, key: Bytes, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn iter_raw(&self) -> Box<dyn Iterator<Item = (&Bytes, &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&Bytes, &V)> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn is_sorted(&self) -> bool {
+        true
+    }
 }
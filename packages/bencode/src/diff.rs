@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::access::bencode::{BRefAccess, RefKind};
+use crate::access::dict::BDictAccess;
+use crate::access::list::BListAccess;
+use crate::error::BencodePath;
+use crate::mutable::encode;
+
+/// Single difference between two bencode values, as found by `diff`.
+///
+/// Values are given in their encoded form, so that values of any bencode type can be compared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BencodeChange {
+    /// Dictionary key or list element only present in the new value.
+    Added { path: BencodePath, value: Vec<u8> },
+    /// Dictionary key or list element only present in the old value.
+    Removed { path: BencodePath, value: Vec<u8> },
+    /// Value present in both, but with a different type, or a different integer or bytes.
+    Changed { path: BencodePath, old: Vec<u8>, new: Vec<u8> },
+}
+
+impl BencodeChange {
+    /// Path of the value that was added, removed or changed.
+    #[must_use]
+    pub fn path(&self) -> &BencodePath {
+        match self {
+            BencodeChange::Added { path, .. } | BencodeChange::Removed { path, .. } | BencodeChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for BencodeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BencodeChange::Added { path, value } => write!(f, "+ {path}: {}", String::from_utf8_lossy(value)),
+            BencodeChange::Removed { path, value } => write!(f, "- {path}: {}", String::from_utf8_lossy(value)),
+            BencodeChange::Changed { path, old, new } => write!(
+                f,
+                "~ {path}: {} -> {}",
+                String::from_utf8_lossy(old),
+                String::from_utf8_lossy(new)
+            ),
+        }
+    }
+}
+
+/// Structural diff of two bencode values, listing every key that was added, removed, or changed.
+///
+/// Dictionaries and lists are compared recursively, so a change deep within them is reported at its
+/// full path (for example `info.files[3].length`) rather than as a change of the whole container. Keys
+/// are compared in sorted order, and list elements by their index, so changes are listed in the order
+/// their paths appear in the encoded values.
+pub fn diff<A, B>(old: &A, new: &B) -> Vec<BencodeChange>
+where
+    A: BRefAccess,
+    A::BKey: AsRef<[u8]>,
+    B: BRefAccess,
+    B::BKey: AsRef<[u8]>,
+{
+    let mut changes = Vec::new();
+
+    diff_values(old, new, &BencodePath::new(), &mut changes);
+
+    changes
+}
+
+fn diff_values<A, B>(old: &A, new: &B, path: &BencodePath, changes: &mut Vec<BencodeChange>)
+where
+    A: BRefAccess,
+    A::BKey: AsRef<[u8]>,
+    B: BRefAccess,
+    B::BKey: AsRef<[u8]>,
+{
+    match (old.kind(), new.kind()) {
+        (RefKind::Dict(old_dict), RefKind::Dict(new_dict)) => diff_dicts(old_dict, new_dict, path, changes),
+        (RefKind::List(old_list), RefKind::List(new_list)) => diff_lists(old_list, new_list, path, changes),
+        (RefKind::Int(old_int), RefKind::Int(new_int)) if old_int == new_int => (),
+        (RefKind::Bytes(old_bytes), RefKind::Bytes(new_bytes)) if old_bytes == new_bytes => (),
+        _ => changes.push(BencodeChange::Changed {
+            path: path.clone(),
+            old: encoded(old),
+            new: encoded(new),
+        }),
+    }
+}
+
+fn diff_dicts<K, V, L, W>(
+    old: &dyn BDictAccess<K, V>,
+    new: &dyn BDictAccess<L, W>,
+    path: &BencodePath,
+    changes: &mut Vec<BencodeChange>,
+) where
+    K: AsRef<[u8]>,
+    V: BRefAccess,
+    V::BKey: AsRef<[u8]>,
+    L: AsRef<[u8]>,
+    W: BRefAccess,
+    W::BKey: AsRef<[u8]>,
+{
+    let mut old_entries = old.iter_sorted().peekable();
+    let mut new_entries = new.iter_sorted().peekable();
+
+    loop {
+        let order = match (old_entries.peek(), new_entries.peek()) {
+            (Some((old_key, _)), Some((new_key, _))) => old_key.as_ref().cmp(new_key.as_ref()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+
+        match order {
+            Ordering::Less => {
+                let (key, value) = old_entries.next().unwrap();
+
+                changes.push(BencodeChange::Removed {
+                    path: path.clone().with_key(key),
+                    value: encoded(value),
+                });
+            }
+            Ordering::Greater => {
+                let (key, value) = new_entries.next().unwrap();
+
+                changes.push(BencodeChange::Added {
+                    path: path.clone().with_key(key),
+                    value: encoded(value),
+                });
+            }
+            Ordering::Equal => {
+                let (key, old_value) = old_entries.next().unwrap();
+                let (_, new_value) = new_entries.next().unwrap();
+
+                diff_values(old_value, new_value, &path.clone().with_key(key), changes);
+            }
+        }
+    }
+}
+
+fn diff_lists<V, W>(old: &dyn BListAccess<V>, new: &dyn BListAccess<W>, path: &BencodePath, changes: &mut Vec<BencodeChange>)
+where
+    V: BRefAccess,
+    V::BKey: AsRef<[u8]>,
+    W: BRefAccess,
+    W::BKey: AsRef<[u8]>,
+{
+    for index in 0..std::cmp::max(old.len(), new.len()) {
+        let index_path = path.clone().with_index(index);
+
+        match (old.get(index), new.get(index)) {
+            (Some(old_value), Some(new_value)) => diff_values(old_value, new_value, &index_path, changes),
+            (Some(old_value), None) => changes.push(BencodeChange::Removed {
+                path: index_path,
+                value: encoded(old_value),
+            }),
+            (None, Some(new_value)) => changes.push(BencodeChange::Added {
+                path: index_path,
+                value: encoded(new_value),
+            }),
+            (None, None) => (),
+        }
+    }
+}
+
+fn encoded<T>(value: &T) -> Vec<u8>
+where
+    T: BRefAccess,
+    T::BKey: AsRef<[u8]>,
+{
+    let mut bytes = Vec::new();
+    encode::encode(value, &mut bytes);
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, BencodeChange};
+    use crate::{ben_bytes, ben_int, ben_list, ben_map, BDecodeOpt, BencodePath, BencodeRef};
+
+    #[test]
+    fn positive_diff_nested_changes() {
+        let old = ben_map! {
+            "announce" => ben_bytes!("udp://a"),
+            "info" => ben_map! {
+                "files" => ben_list!(ben_int!(1), ben_int!(2)),
+                "name" => ben_bytes!("old")
+            }
+        };
+        let new = ben_map! {
+            "comment" => ben_bytes!("hi"),
+            "info" => ben_map! {
+                "files" => ben_list!(ben_int!(1), ben_int!(3), ben_int!(4)),
+                "name" => ben_int!(5)
+            }
+        };
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            vec![
+                BencodeChange::Removed {
+                    path: BencodePath::new().with_key("announce"),
+                    value: b"7:udp://a".to_vec(),
+                },
+                BencodeChange::Added {
+                    path: BencodePath::new().with_key("comment"),
+                    value: b"2:hi".to_vec(),
+                },
+                BencodeChange::Changed {
+                    path: BencodePath::new().with_key("info").with_key("files").with_index(1),
+                    old: b"i2e".to_vec(),
+                    new: b"i3e".to_vec(),
+                },
+                BencodeChange::Added {
+                    path: BencodePath::new().with_key("info").with_key("files").with_index(2),
+                    value: b"i4e".to_vec(),
+                },
+                BencodeChange::Changed {
+                    path: BencodePath::new().with_key("info").with_key("name"),
+                    old: b"3:old".to_vec(),
+                    new: b"i5e".to_vec(),
+                },
+            ],
+            changes
+        );
+        assert_eq!("~ info.name: 3:old -> i5e", changes[4].to_string());
+    }
+
+    #[test]
+    fn negative_diff_equal_values() {
+        let bytes = b"d1:ai1e1:bl1:cee"; // cspell:disable-line
+        let decoded = BencodeRef::decode(bytes, BDecodeOpt::default()).unwrap();
+        let built = ben_map! {
+            "a" => ben_int!(1),
+            "b" => ben_list!(ben_bytes!("c"))
+        };
+
+        assert!(diff(&decoded, &built).is_empty());
+    }
+}
@@ -1,40 +1,197 @@
+use std::fmt;
+
 use thiserror::Error;
 
+/// Path to a value within nested bencode, displayed as dictionary keys separated by dots
+/// and list indices in brackets, for example `info.files[3].length`.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BencodePath {
+    segments: Vec<PathSegment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum PathSegment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+impl BencodePath {
+    /// Create a new `BencodePath` referring to the root bencode object.
+    #[must_use]
+    pub fn new() -> BencodePath {
+        BencodePath::default()
+    }
+
+    /// Whether the path refers to the root bencode object.
+    #[must_use]
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Append the given dictionary key to the path.
+    #[must_use]
+    pub fn with_key<K>(mut self, key: K) -> BencodePath
+    where
+        K: AsRef<[u8]>,
+    {
+        self.segments.push(PathSegment::Key(key.as_ref().to_owned()));
+        self
+    }
+
+    /// Append the given list index to the path.
+    #[must_use]
+    pub fn with_index(mut self, index: usize) -> BencodePath {
+        self.segments.push(PathSegment::Index(index));
+        self
+    }
+
+    fn prepend(&mut self, segment: PathSegment) {
+        self.segments.insert(0, segment);
+    }
+}
+
+impl fmt::Display for BencodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str("<root>");
+        }
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if index == 0 => write!(f, "{}", String::from_utf8_lossy(key))?,
+                PathSegment::Key(key) => write!(f, ".{}", String::from_utf8_lossy(key))?,
+                PathSegment::Index(list_index) => write!(f, "[{list_index}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BencodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+fn in_path(path: &BencodePath) -> String {
+    if path.is_root() {
+        String::new()
+    } else {
+        format!(" In {path}")
+    }
+}
+
+fn at_pos(pos: Option<usize>) -> String {
+    pos.map(|pos| format!(" At {pos}")).unwrap_or_default()
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug)]
 pub enum BencodeParseError {
-    #[error("Incomplete Number Of Bytes At {pos}")]
-    BytesEmpty { pos: usize },
+    #[error("Incomplete Number Of Bytes At {pos}{}", in_path(.path))]
+    BytesEmpty { pos: usize, path: BencodePath },
 
-    #[error("Invalid Byte Found At {pos}")]
-    InvalidByte { pos: usize },
+    #[error("Invalid Byte Found At {pos}{}", in_path(.path))]
+    InvalidByte { pos: usize, path: BencodePath },
 
-    #[error("Invalid Integer Found With No Delimiter At {pos}")]
-    InvalidIntNoDelimiter { pos: usize },
+    #[error("Invalid Integer Found With No Delimiter At {pos}{}", in_path(.path))]
+    InvalidIntNoDelimiter { pos: usize, path: BencodePath },
 
-    #[error("Invalid Integer Found As Negative Zero At {pos}")]
-    InvalidIntNegativeZero { pos: usize },
+    #[error("Invalid Integer Found As Negative Zero At {pos}{}", in_path(.path))]
+    InvalidIntNegativeZero { pos: usize, path: BencodePath },
 
-    #[error("Invalid Integer Found With Zero Padding At {pos}")]
-    InvalidIntZeroPadding { pos: usize },
+    #[error("Invalid Integer Found With Zero Padding At {pos}{}", in_path(.path))]
+    InvalidIntZeroPadding { pos: usize, path: BencodePath },
 
-    #[error("Invalid Integer Found To Fail Parsing At {pos}")]
-    InvalidIntParseError { pos: usize },
+    #[error("Invalid Integer Found To Fail Parsing At {pos}{}", in_path(.path))]
+    InvalidIntParseError { pos: usize, path: BencodePath },
 
-    #[error("Invalid Dictionary Key Ordering Found At {pos} For Key {key:?}")]
-    InvalidKeyOrdering { pos: usize, key: Vec<u8> },
+    #[error("Invalid Dictionary Key Ordering Found At {pos} For Key {key:?}{}", in_path(.path))]
+    InvalidKeyOrdering { pos: usize, key: Vec<u8>, path: BencodePath },
 
-    #[error("Invalid Dictionary Key Found At {pos} For Key {key:?}")]
-    InvalidKeyDuplicates { pos: usize, key: Vec<u8> },
+    #[error("Invalid Dictionary Key Found At {pos} For Key {key:?}{}", in_path(.path))]
+    InvalidKeyDuplicates { pos: usize, key: Vec<u8>, path: BencodePath },
 
-    #[error("Invalid Byte Length Found As Negative At {pos}")]
-    InvalidLengthNegative { pos: usize },
+    #[error("Invalid Byte Length Found As Negative At {pos}{}", in_path(.path))]
+    InvalidLengthNegative { pos: usize, path: BencodePath },
 
-    #[error("Invalid Byte Length Found To Overflow Buffer Length At {pos}")]
-    InvalidLengthOverflow { pos: usize },
+    #[error("Invalid Byte Length Found To Overflow Buffer Length At {pos}{}", in_path(.path))]
+    InvalidLengthOverflow { pos: usize, path: BencodePath },
 
-    #[error("Invalid Recursion Limit Exceeded At {pos} For Limit {max}")]
-    InvalidRecursionExceeded { pos: usize, max: usize },
+    #[error("Invalid Recursion Limit Exceeded At {pos} For Limit {max}{}", in_path(.path))]
+    InvalidRecursionExceeded { pos: usize, max: usize, path: BencodePath },
+}
+
+impl BencodeParseError {
+    /// Byte offset within the decoded buffer where the error occurred.
+    #[must_use]
+    pub fn pos(&self) -> usize {
+        match *self {
+            BencodeParseError::BytesEmpty { pos, .. }
+            | BencodeParseError::InvalidByte { pos, .. }
+            | BencodeParseError::InvalidIntNoDelimiter { pos, .. }
+            | BencodeParseError::InvalidIntNegativeZero { pos, .. }
+            | BencodeParseError::InvalidIntZeroPadding { pos, .. }
+            | BencodeParseError::InvalidIntParseError { pos, .. }
+            | BencodeParseError::InvalidKeyOrdering { pos, .. }
+            | BencodeParseError::InvalidKeyDuplicates { pos, .. }
+            | BencodeParseError::InvalidLengthNegative { pos, .. }
+            | BencodeParseError::InvalidLengthOverflow { pos, .. }
+            | BencodeParseError::InvalidRecursionExceeded { pos, .. } => pos,
+        }
+    }
+
+    /// Path to the value that was being decoded when the error occurred.
+    #[must_use]
+    pub fn path(&self) -> &BencodePath {
+        match self {
+            BencodeParseError::BytesEmpty { path, .. }
+            | BencodeParseError::InvalidByte { path, .. }
+            | BencodeParseError::InvalidIntNoDelimiter { path, .. }
+            | BencodeParseError::InvalidIntNegativeZero { path, .. }
+            | BencodeParseError::InvalidIntZeroPadding { path, .. }
+            | BencodeParseError::InvalidIntParseError { path, .. }
+            | BencodeParseError::InvalidKeyOrdering { path, .. }
+            | BencodeParseError::InvalidKeyDuplicates { path, .. }
+            | BencodeParseError::InvalidLengthNegative { path, .. }
+            | BencodeParseError::InvalidLengthOverflow { path, .. }
+            | BencodeParseError::InvalidRecursionExceeded { path, .. } => path,
+        }
+    }
+
+    /// Nest the error within the value of the given dictionary key.
+    #[must_use]
+    pub fn in_key<K>(mut self, key: K) -> BencodeParseError
+    where
+        K: AsRef<[u8]>,
+    {
+        self.path_mut().prepend(PathSegment::Key(key.as_ref().to_owned()));
+        self
+    }
+
+    /// Nest the error within the given list index.
+    #[must_use]
+    pub fn in_index(mut self, index: usize) -> BencodeParseError {
+        self.path_mut().prepend(PathSegment::Index(index));
+        self
+    }
+
+    fn path_mut(&mut self) -> &mut BencodePath {
+        match self {
+            BencodeParseError::BytesEmpty { path, .. }
+            | BencodeParseError::InvalidByte { path, .. }
+            | BencodeParseError::InvalidIntNoDelimiter { path, .. }
+            | BencodeParseError::InvalidIntNegativeZero { path, .. }
+            | BencodeParseError::InvalidIntZeroPadding { path, .. }
+            | BencodeParseError::InvalidIntParseError { path, .. }
+            | BencodeParseError::InvalidKeyOrdering { path, .. }
+            | BencodeParseError::InvalidKeyDuplicates { path, .. }
+            | BencodeParseError::InvalidLengthNegative { path, .. }
+            | BencodeParseError::InvalidLengthOverflow { path, .. }
+            | BencodeParseError::InvalidRecursionExceeded { path, .. } => path,
+        }
+    }
 }
 
 pub type BencodeParseResult<T> = Result<T, BencodeParseError>;
@@ -42,11 +199,103 @@ pub type BencodeParseResult<T> = Result<T, BencodeParseError>;
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug)]
 pub enum BencodeConvertError {
-    #[error("Missing Key In Bencode For {key:?}")]
-    MissingKey { key: Vec<u8> },
+    #[error("Missing Key In Bencode For {path}")]
+    MissingKey { key: Vec<u8>, path: BencodePath },
+
+    #[error("Wrong Type In Bencode For {}{} Expected Type {expected_type}", wrong_type_path(.key, .path), at_pos(*.pos))]
+    WrongType {
+        key: Vec<u8>,
+        expected_type: String,
+        path: BencodePath,
+        pos: Option<usize>,
+    },
+}
+
+/// Values converted without a lookup are referred to by their error key, until nested.
+fn wrong_type_path(key: &[u8], path: &BencodePath) -> String {
+    if path.is_root() {
+        String::from_utf8_lossy(key).into_owned()
+    } else {
+        path.to_string()
+    }
+}
+
+impl BencodeConvertError {
+    /// Path to the value that failed to convert, or the missing key.
+    #[must_use]
+    pub fn path(&self) -> &BencodePath {
+        match self {
+            BencodeConvertError::MissingKey { path, .. } | BencodeConvertError::WrongType { path, .. } => path,
+        }
+    }
+
+    /// Byte offset of the value that failed to convert, if it was decoded from a buffer.
+    #[must_use]
+    pub fn pos(&self) -> Option<usize> {
+        match *self {
+            BencodeConvertError::MissingKey { .. } => None,
+            BencodeConvertError::WrongType { pos, .. } => pos,
+        }
+    }
+
+    /// Nest the error within the value of the given dictionary key.
+    #[must_use]
+    pub fn in_key<K>(mut self, key: K) -> BencodeConvertError
+    where
+        K: AsRef<[u8]>,
+    {
+        self.path_mut().prepend(PathSegment::Key(key.as_ref().to_owned()));
+        self
+    }
 
-    #[error("Wrong Type In Bencode For {key:?} Expected Type {expected_type}")]
-    WrongType { key: Vec<u8>, expected_type: String },
+    /// Nest the error within the given list index.
+    #[must_use]
+    pub fn in_index(mut self, index: usize) -> BencodeConvertError {
+        self.path_mut().prepend(PathSegment::Index(index));
+        self
+    }
+
+    fn path_mut(&mut self) -> &mut BencodePath {
+        match self {
+            BencodeConvertError::MissingKey { path, .. } | BencodeConvertError::WrongType { path, .. } => path,
+        }
+    }
 }
 
 pub type BencodeConvertResult<T> = Result<T, BencodeConvertError>;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug)]
+pub enum BencodeEncodeError {
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Encoded Length {len} Exceeds The Maximum Length {max}")]
+    MaxLengthExceeded { len: u64, max: u64 },
+
+    #[error("Duplicate Dictionary Key {key:?} Found{}", in_path(.path))]
+    DuplicateKey { key: Vec<u8>, path: BencodePath },
+}
+
+impl BencodeEncodeError {
+    /// Nest the error within the value of the given dictionary key.
+    pub(crate) fn in_key<K>(mut self, key: K) -> BencodeEncodeError
+    where
+        K: AsRef<[u8]>,
+    {
+        if let BencodeEncodeError::DuplicateKey { path, .. } = &mut self {
+            path.prepend(PathSegment::Key(key.as_ref().to_owned()));
+        }
+        self
+    }
+
+    /// Nest the error within the given list index.
+    pub(crate) fn in_index(mut self, index: usize) -> BencodeEncodeError {
+        if let BencodeEncodeError::DuplicateKey { path, .. } = &mut self {
+            path.prepend(PathSegment::Index(index));
+        }
+        self
+    }
+}
+
+pub type BencodeEncodeResult<T> = Result<T, BencodeEncodeError>;
@@ -37,6 +37,7 @@
 
 mod access;
 mod cow;
+mod diff;
 mod error;
 mod mutable;
 mod reference;
@@ -62,8 +63,15 @@ pub use crate::access::bencode::{BMutAccess, BRefAccess, MutKind, RefKind};
 pub use crate::access::convert::BConvert;
 pub use crate::access::dict::BDictAccess;
 pub use crate::access::list::BListAccess;
-pub use crate::error::{BencodeConvertError, BencodeConvertResult, BencodeParseError, BencodeParseResult};
+pub use crate::diff::{diff, BencodeChange};
+pub use crate::error::{
+    BencodeConvertError, BencodeConvertResult, BencodeEncodeError, BencodeEncodeResult, BencodeParseError, BencodeParseResult,
+    BencodePath,
+};
 pub use crate::mutable::bencode_mut::BencodeMut;
+pub use crate::mutable::encode::encode_canonical;
+pub use crate::mutable::entry::BencodeMutEntry;
+pub use crate::reference::bencode_bytes::BencodeBytes;
 pub use crate::reference::bencode_ref::BencodeRef;
 pub use crate::reference::decode_opt::BDecodeOpt;
 
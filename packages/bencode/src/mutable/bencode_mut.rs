@@ -1,11 +1,15 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::io::Write;
 use std::str;
 
 use crate::access::bencode::{BMutAccess, BRefAccess, MutKind, RefKind};
 use crate::access::dict::BDictAccess;
 use crate::access::list::BListAccess;
+use crate::cow::BCowConvert;
+use crate::error::{BencodeEncodeError, BencodeEncodeResult};
 use crate::mutable::encode;
+use crate::mutable::entry::BencodeMutEntry;
 
 /// Bencode object that holds references to the underlying data.
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
@@ -55,7 +59,48 @@ impl<'a> BencodeMut<'a> {
         BencodeMut::new(Inner::Dict(BTreeMap::new()))
     }
 
+    /// Get the entry for the given key, if the `BencodeMut` is a dictionary.
+    ///
+    /// Nested structures can be built up without scoping each level:
+    /// `root.entry("info")?.or_insert_dict().entry("files")?.or_insert_list()`.
+    pub fn entry<'k, K>(&mut self, key: K) -> Option<BencodeMutEntry<'_, 'a>>
+    where
+        'k: 'a,
+        K: BCowConvert<'k>,
+    {
+        match self.inner {
+            Inner::Dict(ref mut n) => Some(BencodeMutEntry::new(n.entry(key.convert()))),
+            _ => None,
+        }
+    }
+
+    /// Lookup the value at the given path of dictionary keys.
+    ///
+    /// An empty path yields this `BencodeMut`.
+    pub fn get_path<P>(&self, path: P) -> Option<&BencodeMut<'a>>
+    where
+        P: IntoIterator,
+        P::Item: AsRef<[u8]>,
+    {
+        path.into_iter()
+            .try_fold(self, |bencode, key| bencode.dict()?.lookup(key.as_ref()))
+    }
+
+    /// Lookup the mutable value at the given path of dictionary keys.
+    ///
+    /// An empty path yields this `BencodeMut`.
+    pub fn get_path_mut<P>(&mut self, path: P) -> Option<&mut BencodeMut<'a>>
+    where
+        P: IntoIterator,
+        P::Item: AsRef<[u8]>,
+    {
+        path.into_iter()
+            .try_fold(self, |bencode, key| bencode.dict_mut()?.lookup_mut(key.as_ref()))
+    }
+
     /// Encode the `BencodeMut` into a buffer representing the bencode.
+    ///
+    /// Dictionaries keep their keys sorted, so the encoding is always canonical, see `encode_canonical`.
     #[must_use]
     pub fn encode(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
@@ -64,6 +109,83 @@ impl<'a> BencodeMut<'a> {
 
         buffer
     }
+
+    /// Number of bytes in the encoding of the `BencodeMut`, without encoding it.
+    #[must_use]
+    pub fn encoded_len(&self) -> u64 {
+        encode::encoded_len(self)
+    }
+
+    /// Encode the `BencodeMut` into the writer, a chunk at a time, returning the number of bytes written.
+    ///
+    /// Unlike `encode`, the whole encoding is never held in memory, so large structures can be
+    /// streamed directly to files or sockets.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the encoding is longer than `max_len`, in which case nothing
+    /// is written, or if writing to the writer fails.
+    pub fn encode_to_writer<W>(&self, writer: &mut W, max_len: Option<u64>) -> BencodeEncodeResult<u64>
+    where
+        W: Write + ?Sized,
+    {
+        let len = self.checked_encoded_len(max_len)?;
+
+        let mut encoder = encode::StreamEncoder::new(self);
+        let mut buffer = Vec::with_capacity(encode::CHUNK_LEN);
+        loop {
+            buffer.clear();
+            encoder.fill(&mut buffer);
+
+            if buffer.is_empty() {
+                return Ok(len);
+            }
+            writer.write_all(&buffer)?;
+        }
+    }
+
+    /// Encode the `BencodeMut` into the asynchronous writer, a chunk at a time, returning the number of bytes written.
+    ///
+    /// See `encode_to_writer`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the encoding is longer than `max_len`, in which case nothing
+    /// is written, or if writing to the writer fails.
+    #[cfg(feature = "async")]
+    pub async fn encode_to_async_writer<W>(&self, writer: &mut W, max_len: Option<u64>) -> BencodeEncodeResult<u64>
+    where
+        W: futures::io::AsyncWrite + Unpin + ?Sized,
+    {
+        use futures::io::AsyncWriteExt as _;
+
+        let len = self.checked_encoded_len(max_len)?;
+
+        let mut encoder = encode::StreamEncoder::new(self);
+        let mut buffer = Vec::with_capacity(encode::CHUNK_LEN);
+        loop {
+            buffer.clear();
+            encoder.fill(&mut buffer);
+
+            if buffer.is_empty() {
+                return Ok(len);
+            }
+            writer.write_all(&buffer).await?;
+        }
+    }
+
+    fn checked_encoded_len(&self, max_len: Option<u64>) -> BencodeEncodeResult<u64> {
+        let len = self.encoded_len();
+
+        match max_len {
+            Some(max) if len > max => Err(BencodeEncodeError::MaxLengthExceeded { len, max }),
+            _ => Ok(len),
+        }
+    }
+
+    pub(crate) fn inner(&self) -> &Inner<'a> {
+        &self.inner
+    }
 }
 
 impl<'a> BRefAccess for BencodeMut<'a> {
@@ -164,8 +286,35 @@ impl<'a> BMutAccess for BencodeMut<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::access::bencode::BMutAccess;
+    use std::borrow::Cow;
+
+    use crate::access::bencode::{BMutAccess, BRefAccess};
+    use crate::error::BencodeEncodeError;
     use crate::mutable::bencode_mut::BencodeMut;
+    use crate::mutable::encode::CHUNK_LEN;
+
+    fn large_bencode() -> BencodeMut<'static> {
+        let mut bencode_dict = BencodeMut::new_dict();
+        {
+            let dict = bencode_dict.dict_mut().unwrap();
+            dict.insert(
+                (&b"layers"[..]).into(),
+                BencodeMut::new_bytes(Cow::Owned(vec![7u8; 3 * CHUNK_LEN + 5])),
+            );
+            dict.insert((&b"count"[..]).into(), BencodeMut::new_int(-120));
+
+            let mut bencode_list = BencodeMut::new_list();
+            bencode_list.list_mut().unwrap().push(BencodeMut::new_int(0));
+            bencode_list.list_mut().unwrap().push(BencodeMut::new_dict());
+            bencode_list
+                .list_mut()
+                .unwrap()
+                .push(BencodeMut::new_bytes((&b""[..]).into()));
+            dict.insert((&b"a"[..]).into(), bencode_list);
+        }
+
+        bencode_dict
+    }
 
     #[test]
     fn positive_int_encode() {
@@ -226,4 +375,95 @@ mod test {
         let dict_bytes = b"d3:asd6:asdasde"; // cspell:disable-line
         assert_eq!(&dict_bytes[..], &bencode_dict.encode()[..]);
     }
+
+    #[test]
+    fn positive_entry_builds_nested_dicts() {
+        let mut bencode_dict = BencodeMut::new_dict();
+
+        bencode_dict
+            .entry("info")
+            .unwrap()
+            .or_insert_dict()
+            .entry("files")
+            .unwrap()
+            .or_insert_list()
+            .list_mut()
+            .unwrap()
+            .push(BencodeMut::new_int(1));
+
+        // Existing values are kept
+        bencode_dict.entry("info").unwrap().or_insert(BencodeMut::new_int(0));
+
+        let dict_bytes = b"d4:infod5:filesli1eeee"; // cspell:disable-line
+        assert_eq!(&dict_bytes[..], &bencode_dict.encode()[..]);
+    }
+
+    #[test]
+    fn negative_entry_not_dict() {
+        let mut bencode_list = BencodeMut::new_list();
+
+        assert!(bencode_list.entry("key").is_none());
+    }
+
+    #[test]
+    fn positive_get_path_mut() {
+        let mut bencode_dict = BencodeMut::new_dict();
+        bencode_dict
+            .entry("info")
+            .unwrap()
+            .or_insert_dict()
+            .entry("length")
+            .unwrap()
+            .or_insert(BencodeMut::new_int(5));
+
+        *bencode_dict.get_path_mut(["info", "length"]).unwrap() = BencodeMut::new_int(10);
+
+        assert_eq!(Some(10), bencode_dict.get_path(["info", "length"]).unwrap().int());
+        assert!(bencode_dict.get_path(["info", "missing"]).is_none());
+        assert!(bencode_dict.get_path(["info", "length", "nested"]).is_none());
+    }
+
+    #[test]
+    fn positive_encoded_len_matches_encode() {
+        let bencode = large_bencode();
+
+        assert_eq!(bencode.encode().len() as u64, bencode.encoded_len());
+    }
+
+    #[test]
+    fn positive_encode_to_writer_matches_encode() {
+        let bencode = large_bencode();
+
+        let mut buffer = Vec::new();
+        let written = bencode.encode_to_writer(&mut buffer, None).unwrap();
+
+        assert_eq!(bencode.encode(), buffer);
+        assert_eq!(buffer.len() as u64, written);
+    }
+
+    #[test]
+    fn negative_encode_to_writer_max_len_exceeded() {
+        let bencode = large_bencode();
+        let len = bencode.encoded_len();
+
+        let mut buffer = Vec::new();
+        let error = bencode.encode_to_writer(&mut buffer, Some(len - 1)).unwrap_err();
+
+        assert!(matches!(error, BencodeEncodeError::MaxLengthExceeded { len: l, max } if l == len && max == len - 1));
+        assert!(buffer.is_empty());
+
+        assert_eq!(len, bencode.encode_to_writer(&mut buffer, Some(len)).unwrap());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn positive_encode_to_async_writer_matches_encode() {
+        let bencode = large_bencode();
+
+        let mut buffer = Vec::new();
+        let written = futures::executor::block_on(bencode.encode_to_async_writer(&mut buffer, None)).unwrap();
+
+        assert_eq!(bencode.encode(), buffer);
+        assert_eq!(buffer.len() as u64, written);
+    }
 }
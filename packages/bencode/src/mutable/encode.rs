@@ -1,8 +1,17 @@
+use std::borrow::Cow;
+use std::collections::btree_map;
+use std::io::Write as _;
 use std::iter::Extend;
+use std::slice;
 
 use crate::access::bencode::{BRefAccess, RefKind};
 use crate::access::dict::BDictAccess;
 use crate::access::list::BListAccess;
+use crate::error::{BencodeEncodeError, BencodeEncodeResult, BencodePath};
+use crate::mutable::bencode_mut::{BencodeMut, Inner};
+
+/// Number of bytes buffered by the `StreamEncoder` before they are written out.
+pub const CHUNK_LEN: usize = 16 * 1024;
 
 pub fn encode<T>(val: T, bytes: &mut Vec<u8>)
 where
@@ -65,3 +74,297 @@ where
     }
     bytes.push(crate::BEN_END);
 }
+
+/// Encode the given bencode in canonical form, with the keys of every dictionary sorted and integers without
+/// zero padding, whatever order the dictionaries of the bencode iterate their keys in.
+///
+/// Useful for producing spec-clean torrents and DHT messages from any `BRefAccess`, such as a `BencodeRef`
+/// decoded from non-canonical bencode, as the dictionaries of a `BencodeMut` are always encoded sorted.
+///
+/// # Errors
+///
+/// It would return an error if any dictionary holds the same key more than once.
+pub fn encode_canonical<T>(val: &T) -> BencodeEncodeResult<Vec<u8>>
+where
+    T: BRefAccess,
+    T::BKey: AsRef<[u8]>,
+{
+    let mut bytes = Vec::new();
+
+    encode_canonical_into(val, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn encode_canonical_into<T>(val: &T, bytes: &mut Vec<u8>) -> BencodeEncodeResult<()>
+where
+    T: BRefAccess,
+    T::BKey: AsRef<[u8]>,
+{
+    match val.kind() {
+        RefKind::Int(n) => encode_int(n, bytes),
+        RefKind::Bytes(n) => encode_bytes(n, bytes),
+        RefKind::List(n) => {
+            bytes.push(crate::LIST_START);
+            for (index, item) in n.into_iter().enumerate() {
+                encode_canonical_into(item, bytes).map_err(|err| err.in_index(index))?;
+            }
+            bytes.push(crate::BEN_END);
+        }
+        RefKind::Dict(n) => {
+            let mut sort_dict = n.to_list();
+            sort_dict.sort_by(|&(a, _), &(b, _)| a.as_ref().cmp(b.as_ref()));
+
+            // Sorted keys that are the same end up next to each other
+            if let Some(pair) = sort_dict.windows(2).find(|pair| pair[0].0.as_ref() == pair[1].0.as_ref()) {
+                return Err(BencodeEncodeError::DuplicateKey {
+                    key: pair[0].0.as_ref().to_vec(),
+                    path: BencodePath::new(),
+                });
+            }
+
+            bytes.push(crate::DICT_START);
+            for (key, value) in sort_dict {
+                encode_bytes(key.as_ref(), bytes);
+                encode_canonical_into(value, bytes).map_err(|err| err.in_key(key))?;
+            }
+            bytes.push(crate::BEN_END);
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of bytes in the encoding of the given `BencodeMut`.
+pub fn encoded_len(val: &BencodeMut<'_>) -> u64 {
+    match val.inner() {
+        Inner::Int(n) => 2 + u64::from(*n < 0) + decimal_len(n.unsigned_abs()),
+        Inner::Bytes(n) => bytes_encoded_len(n),
+        Inner::List(n) => 2 + n.iter().map(encoded_len).sum::<u64>(),
+        Inner::Dict(n) => {
+            2 + n
+                .iter()
+                .map(|(key, value)| bytes_encoded_len(key) + encoded_len(value))
+                .sum::<u64>()
+        }
+    }
+}
+
+fn bytes_encoded_len(bytes: &[u8]) -> u64 {
+    let len = bytes.len() as u64;
+
+    decimal_len(len) + 1 + len
+}
+
+fn decimal_len(n: u64) -> u64 {
+    n.checked_ilog10().map_or(1, |digits| u64::from(digits) + 1)
+}
+
+/// Encodes a `BencodeMut` in chunks of about `CHUNK_LEN` bytes, without recursion, so
+/// that the chunks can be written out without holding the whole encoding in memory.
+pub struct StreamEncoder<'b, 'a> {
+    stack: Vec<Frame<'b, 'a>>,
+}
+
+enum Frame<'b, 'a> {
+    Value(&'b BencodeMut<'a>),
+    Payload(&'b [u8]),
+    List(slice::Iter<'b, BencodeMut<'a>>),
+    Dict(btree_map::Iter<'b, Cow<'a, [u8]>, BencodeMut<'a>>),
+}
+
+impl<'b, 'a> StreamEncoder<'b, 'a> {
+    pub fn new(val: &'b BencodeMut<'a>) -> StreamEncoder<'b, 'a> {
+        StreamEncoder {
+            stack: vec![Frame::Value(val)],
+        }
+    }
+
+    /// Encode into the buffer until it holds at least `CHUNK_LEN` bytes, or the encoding is done.
+    ///
+    /// Leaves the buffer untouched once the whole value has been encoded.
+    pub fn fill(&mut self, buffer: &mut Vec<u8>) {
+        while buffer.len() < CHUNK_LEN {
+            let Some(frame) = self.stack.pop() else {
+                return;
+            };
+
+            match frame {
+                Frame::Value(val) => match val.inner() {
+                    Inner::Int(n) => encode_int(*n, buffer),
+                    Inner::Bytes(n) => self.push_bytes(n, buffer),
+                    Inner::List(n) => {
+                        buffer.push(crate::LIST_START);
+                        self.stack.push(Frame::List(n.iter()));
+                    }
+                    Inner::Dict(n) => {
+                        // Keys of a `BencodeMut` dictionary are already kept sorted
+                        buffer.push(crate::DICT_START);
+                        self.stack.push(Frame::Dict(n.iter()));
+                    }
+                },
+                Frame::Payload(bytes) => {
+                    let (chunk, rest) = bytes.split_at(std::cmp::min(bytes.len(), CHUNK_LEN - buffer.len()));
+
+                    buffer.extend_from_slice(chunk);
+                    if !rest.is_empty() {
+                        self.stack.push(Frame::Payload(rest));
+                    }
+                }
+                Frame::List(mut iter) => match iter.next() {
+                    Some(val) => {
+                        self.stack.push(Frame::List(iter));
+                        self.stack.push(Frame::Value(val));
+                    }
+                    None => buffer.push(crate::BEN_END),
+                },
+                Frame::Dict(mut iter) => match iter.next() {
+                    Some((key, val)) => {
+                        self.stack.push(Frame::Dict(iter));
+                        self.stack.push(Frame::Value(val));
+                        self.push_bytes(key, buffer);
+                    }
+                    None => buffer.push(crate::BEN_END),
+                },
+            }
+        }
+    }
+
+    fn push_bytes(&mut self, bytes: &'b [u8], buffer: &mut Vec<u8>) {
+        write!(buffer, "{}", bytes.len()).expect("bip_bencode: Failed To Write Into A Vec");
+        buffer.push(crate::BYTE_LEN_END);
+
+        self.stack.push(Frame::Payload(bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access::bencode::{BRefAccess, RefKind};
+    use crate::access::dict::BDictAccess;
+    use crate::access::list::BListAccess;
+    use crate::error::BencodeEncodeError;
+    use crate::mutable::encode::encode_canonical;
+    use crate::reference::bencode_ref::BencodeRef;
+    use crate::reference::decode_opt::BDecodeOpt;
+    use crate::{ben_bytes, ben_int, ben_list, ben_map};
+
+    /// Bencode whose dictionaries keep every pair they are given, as a third party `BRefAccess` might.
+    enum PairsBencode {
+        Int(i64),
+        Dict(Vec<(Vec<u8>, PairsBencode)>),
+    }
+
+    impl BDictAccess<Vec<u8>, PairsBencode> for Vec<(Vec<u8>, PairsBencode)> {
+        fn to_list(&self) -> Vec<(&Vec<u8>, &PairsBencode)> {
+            self.iter().map(|(key, value)| (key, value)).collect()
+        }
+
+        fn lookup(&self, key: &[u8]) -> Option<&PairsBencode> {
+            self.iter().find(|(k, _)| k == key).map(|(_, value)| value)
+        }
+
+        fn lookup_mut(&mut self, key: &[u8]) -> Option<&mut PairsBencode> {
+            self.iter_mut().find(|(k, _)| k == key).map(|(_, value)| value)
+        }
+
+        fn insert(&mut self, key: Vec<u8>, value: PairsBencode) -> Option<PairsBencode> {
+            self.push((key, value));
+            None
+        }
+
+        fn remove(&mut self, key: &[u8]) -> Option<PairsBencode> {
+            let index = self.iter().position(|(k, _)| k == key)?;
+            Some(Vec::remove(self, index).1)
+        }
+
+        fn iter_raw(&self) -> Box<dyn Iterator<Item = (&Vec<u8>, &PairsBencode)> + '_> {
+            Box::new(self.iter().map(|(key, value)| (key, value)))
+        }
+
+        fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&Vec<u8>, &PairsBencode)> + '_> {
+            self.iter_raw()
+        }
+
+        fn is_sorted(&self) -> bool {
+            false
+        }
+    }
+
+    impl BRefAccess for PairsBencode {
+        type BKey = Vec<u8>;
+        type BType = PairsBencode;
+
+        fn kind(&self) -> RefKind<'_, Vec<u8>, PairsBencode> {
+            match self {
+                PairsBencode::Int(n) => RefKind::Int(*n),
+                PairsBencode::Dict(n) => RefKind::Dict(n),
+            }
+        }
+
+        fn str(&self) -> Option<&str> {
+            None
+        }
+
+        fn int(&self) -> Option<i64> {
+            match self {
+                PairsBencode::Int(n) => Some(*n),
+                PairsBencode::Dict(_) => None,
+            }
+        }
+
+        fn bytes(&self) -> Option<&[u8]> {
+            None
+        }
+
+        fn list(&self) -> Option<&dyn BListAccess<PairsBencode>> {
+            None
+        }
+
+        fn dict(&self) -> Option<&dyn BDictAccess<Vec<u8>, PairsBencode>> {
+            match self {
+                PairsBencode::Int(_) => None,
+                PairsBencode::Dict(n) => Some(n),
+            }
+        }
+    }
+
+    #[test]
+    fn positive_encode_canonical_unsorted_ref() {
+        let bytes = b"l4:spamd1:zi-3e1:ad1:ci0e1:bi12eeee"; // cspell:disable-line
+        let bencode = BencodeRef::decode(&bytes[..], BDecodeOpt::default()).unwrap();
+
+        let encoded = encode_canonical(&bencode).unwrap();
+
+        assert_eq!(&b"l4:spamd1:ad1:bi12e1:ci0ee1:zi-3eee"[..], &encoded[..]); // cspell:disable-line
+    }
+
+    #[test]
+    fn positive_encode_canonical_matches_encode() {
+        let bencode = ben_map! {
+            "z" => ben_list!(ben_int!(-1), ben_bytes!("")),
+            "a" => ben_map!{ "y" => ben_int!(0), "x" => ben_bytes!("x") }
+        };
+
+        assert_eq!(bencode.encode(), encode_canonical(&bencode).unwrap());
+    }
+
+    #[test]
+    fn negative_encode_canonical_duplicate_key() {
+        let bencode = PairsBencode::Dict(vec![
+            (b"a".to_vec(), PairsBencode::Int(1)),
+            (
+                b"b".to_vec(),
+                PairsBencode::Dict(vec![
+                    (b"d".to_vec(), PairsBencode::Int(2)),
+                    (b"c".to_vec(), PairsBencode::Int(3)),
+                    (b"d".to_vec(), PairsBencode::Int(4)),
+                ]),
+            ),
+        ]);
+
+        let error = encode_canonical(&bencode).unwrap_err();
+
+        assert!(matches!(&error, BencodeEncodeError::DuplicateKey { key, path } if key == b"d" && path.to_string() == "b"));
+    }
+}
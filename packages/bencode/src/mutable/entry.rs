@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+use std::collections::btree_map;
+
+use crate::mutable::bencode_mut::BencodeMut;
+
+/// View into a single key of a `BencodeMut` dictionary, which may or may not be present.
+#[allow(clippy::module_name_repetitions)]
+pub struct BencodeMutEntry<'b, 'a> {
+    entry: btree_map::Entry<'b, Cow<'a, [u8]>, BencodeMut<'a>>,
+}
+
+impl<'b, 'a> BencodeMutEntry<'b, 'a> {
+    pub(crate) fn new(entry: btree_map::Entry<'b, Cow<'a, [u8]>, BencodeMut<'a>>) -> BencodeMutEntry<'b, 'a> {
+        BencodeMutEntry { entry }
+    }
+
+    /// Key of the entry.
+    #[must_use]
+    pub fn key(&self) -> &[u8] {
+        self.entry.key()
+    }
+
+    /// Insert the given value if the key is not present, returning the value for the key.
+    #[allow(clippy::must_use_candidate)]
+    pub fn or_insert(self, default: BencodeMut<'a>) -> &'b mut BencodeMut<'a> {
+        self.entry.or_insert(default)
+    }
+
+    /// Insert the value returned by the given function if the key is not present, returning the value for the key.
+    pub fn or_insert_with<F>(self, default: F) -> &'b mut BencodeMut<'a>
+    where
+        F: FnOnce() -> BencodeMut<'a>,
+    {
+        self.entry.or_insert_with(default)
+    }
+
+    /// Insert an empty dictionary if the key is not present, returning the value for the key.
+    pub fn or_insert_dict(self) -> &'b mut BencodeMut<'a> {
+        self.or_insert_with(BencodeMut::new_dict)
+    }
+
+    /// Insert an empty list if the key is not present, returning the value for the key.
+    pub fn or_insert_list(self) -> &'b mut BencodeMut<'a> {
+        self.or_insert_with(BencodeMut::new_list)
+    }
+
+    /// Modify the value in place if the key is present.
+    #[must_use]
+    pub fn and_modify<F>(self, f: F) -> BencodeMutEntry<'b, 'a>
+    where
+        F: FnOnce(&mut BencodeMut<'a>),
+    {
+        BencodeMutEntry::new(self.entry.and_modify(f))
+    }
+}
@@ -1,2 +1,3 @@
 pub mod bencode_mut;
-mod encode;
+pub mod encode;
+pub mod entry;
@@ -0,0 +1,192 @@
+use std::str;
+
+use bytes::Bytes;
+
+use crate::access::bencode::{BRefAccess, RefKind};
+use crate::access::dict::BDictAccess;
+use crate::access::list::BListAccess;
+use crate::error::BencodeParseResult;
+use crate::reference::bencode_ref::BencodeRef;
+use crate::reference::decode_opt::BDecodeOpt;
+use crate::reference::wire_dict::WireDict;
+
+/// Bencode object that holds reference counted slices of the underlying data.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+enum Inner {
+    /// Bencode Integer.
+    Int(i64, Bytes),
+    /// Bencode Bytes.
+    Bytes(Bytes, Bytes),
+    /// Bencode List.
+    List(Vec<BencodeBytes>, Bytes),
+    /// Bencode Dictionary.
+    Dict(WireDict<Bytes, BencodeBytes>, Bytes),
+}
+
+/// `BencodeBytes` object that shares ownership of some `Bytes` buffer.
+///
+/// Unlike `BencodeRef`, this type carries no lifetime, so it can be freely moved
+/// across tasks or stored alongside the buffer it was decoded from.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct BencodeBytes {
+    inner: Inner,
+}
+
+impl BencodeBytes {
+    /// Decode the given bytes into a `BencodeBytes` using the given decode options.
+    ///
+    /// No data is copied, all values are slices of the given buffer.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn decode(bytes: &Bytes, opts: BDecodeOpt) -> BencodeParseResult<BencodeBytes> {
+        let bencode = BencodeRef::decode(bytes, opts)?;
+
+        Ok(BencodeBytes::from_ref(&bencode, bytes, bytes))
+    }
+
+    /// Get the current bencode byte representation.
+    #[must_use]
+    pub fn buffer(&self) -> &Bytes {
+        #[allow(clippy::match_same_arms)]
+        match self.inner {
+            Inner::Int(_, ref buffer) => buffer,
+            Inner::Bytes(_, ref buffer) => buffer,
+            Inner::List(_, ref buffer) => buffer,
+            Inner::Dict(_, ref buffer) => buffer,
+        }
+    }
+
+    /// Builds a `BencodeBytes` from a `BencodeRef` whose slices all point into `root`, where `bytes` holds the same data.
+    fn from_ref(bencode: &BencodeRef<'_>, root: &[u8], bytes: &Bytes) -> BencodeBytes {
+        let buffer = slice_from(root, bytes, bencode.buffer());
+
+        let inner = match bencode.kind() {
+            RefKind::Int(n) => Inner::Int(n, buffer),
+            RefKind::Bytes(n) => Inner::Bytes(slice_from(root, bytes, n), buffer),
+            RefKind::List(list) => Inner::List(
+                list.into_iter()
+                    .map(|value| BencodeBytes::from_ref(value, root, bytes))
+                    .collect(),
+                buffer,
+            ),
+            RefKind::Dict(dict) => Inner::Dict(
+                dict.iter_raw()
+                    .map(|(key, value)| (slice_from(root, bytes, key), BencodeBytes::from_ref(value, root, bytes)))
+                    .collect(),
+                buffer,
+            ),
+        };
+
+        BencodeBytes { inner }
+    }
+}
+
+/// Slice `bytes` at the same position that `sub` occupies within `root`.
+fn slice_from(root: &[u8], bytes: &Bytes, sub: &[u8]) -> Bytes {
+    let start = sub.as_ptr() as usize - root.as_ptr() as usize;
+
+    bytes.slice(start..start + sub.len())
+}
+
+impl<'a> From<BencodeRef<'a>> for BencodeBytes {
+    /// Copies the buffer backing the `BencodeRef` once, so that the result no longer borrows from it.
+    fn from(bencode: BencodeRef<'a>) -> BencodeBytes {
+        let root = bencode.buffer();
+
+        BencodeBytes::from_ref(&bencode, root, &Bytes::copy_from_slice(root))
+    }
+}
+
+impl BRefAccess for BencodeBytes {
+    type BKey = Bytes;
+    type BType = BencodeBytes;
+
+    fn kind(&self) -> RefKind<'_, Bytes, BencodeBytes> {
+        match self.inner {
+            Inner::Int(n, _) => RefKind::Int(n),
+            Inner::Bytes(ref n, _) => RefKind::Bytes(n),
+            Inner::List(ref n, _) => RefKind::List(n),
+            Inner::Dict(ref n, _) => RefKind::Dict(n),
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        str::from_utf8(self.bytes()?).ok()
+    }
+
+    fn int(&self) -> Option<i64> {
+        match self.inner {
+            Inner::Int(n, _) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn bytes(&self) -> Option<&[u8]> {
+        match self.inner {
+            Inner::Bytes(ref n, _) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&dyn BListAccess<BencodeBytes>> {
+        match self.inner {
+            Inner::List(ref n, _) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn dict(&self) -> Option<&dyn BDictAccess<Bytes, BencodeBytes>> {
+        match self.inner {
+            Inner::Dict(ref n, _) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::access::bencode::BRefAccess;
+    use crate::reference::bencode_bytes::BencodeBytes;
+    use crate::reference::bencode_ref::BencodeRef;
+    use crate::reference::decode_opt::BDecodeOpt;
+
+    #[test]
+    fn positive_decode_shares_buffer() {
+        let dict_bytes = Bytes::from_static(b"d3:asdl3:qweee"); // cspell:disable-line
+        let bencode = BencodeBytes::decode(&dict_bytes, BDecodeOpt::default()).unwrap();
+
+        let bencode_dict = bencode.dict().unwrap();
+        /* cspell:disable-next-line */
+        let bencode_list = bencode_dict.lookup(&b"asd"[..]).unwrap();
+        let bencode_bytes = bencode_list.list().unwrap().get(0).unwrap();
+
+        assert_eq!(b"l3:qwee", &bencode_list.buffer()[..]); // cspell:disable-line
+        assert_eq!(Some("qwe"), bencode_bytes.str());
+        assert_eq!(dict_bytes.as_ptr(), bencode.buffer().as_ptr());
+        assert_eq!(dict_bytes[9..].as_ptr(), bencode_bytes.bytes().unwrap().as_ptr());
+    }
+
+    #[test]
+    fn positive_from_bencode_ref() {
+        let dict_bytes = b"d3:asdi-500e3:qwel3:zxcee"; // cspell:disable-line
+        let bencode_ref = BencodeRef::decode(&dict_bytes[..], BDecodeOpt::default()).unwrap();
+
+        let bencode: BencodeBytes = bencode_ref.into();
+        let bencode_dict = bencode.dict().unwrap();
+
+        assert_eq!(&dict_bytes[..], &bencode.buffer()[..]);
+        /* cspell:disable-next-line */
+        assert_eq!(Some(-500), bencode_dict.lookup(&b"asd"[..]).unwrap().int());
+        /* cspell:disable-next-line */
+        let bencode_list = bencode_dict.lookup(&b"qwe"[..]).unwrap().list().unwrap();
+        assert_eq!(Some("zxc"), bencode_list.get(0).unwrap().str()); // cspell:disable-line
+    }
+
+    #[test]
+    fn negative_decode_trailing_bytes() {
+        let int_bytes = Bytes::from_static(b"i-500ee"); // cspell:disable-line
+
+        assert!(BencodeBytes::decode(&int_bytes, BDecodeOpt::default()).is_err());
+    }
+}
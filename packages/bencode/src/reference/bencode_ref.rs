@@ -1,12 +1,12 @@
-use std::collections::BTreeMap;
 use std::str;
 
 use crate::access::bencode::{BRefAccess, BRefAccessExt, RefKind};
 use crate::access::dict::BDictAccess;
 use crate::access::list::BListAccess;
-use crate::error::{BencodeParseError, BencodeParseResult};
+use crate::error::{BencodeParseError, BencodeParseResult, BencodePath};
 use crate::reference::decode;
 use crate::reference::decode_opt::BDecodeOpt;
+use crate::reference::wire_dict::WireDict;
 
 /// Bencode object that holds references to the underlying data.
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
@@ -18,22 +18,38 @@ pub enum Inner<'a> {
     /// Bencode List.
     List(Vec<BencodeRef<'a>>, &'a [u8]),
     /// Bencode Dictionary.
-    Dict(BTreeMap<&'a [u8], BencodeRef<'a>>, &'a [u8]),
-}
-
-impl<'a> From<Inner<'a>> for BencodeRef<'a> {
-    fn from(val: Inner<'a>) -> Self {
-        BencodeRef { inner: val }
-    }
+    Dict(WireDict<&'a [u8], BencodeRef<'a>>, &'a [u8]),
 }
 
 /// `BencodeRef` object that stores references to some buffer.
-#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+///
+/// Equality and hashing only consider the bencode, not where it was found in the buffer.
+#[derive(Debug, Clone)]
 pub struct BencodeRef<'a> {
     inner: Inner<'a>,
+    pos: usize,
+}
+
+impl PartialEq for BencodeRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for BencodeRef<'_> {}
+
+impl std::hash::Hash for BencodeRef<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
 }
 
 impl<'a> BencodeRef<'a> {
+    /// Create a new `BencodeRef` found at the given position of the decoded buffer.
+    pub(crate) fn new(inner: Inner<'a>, pos: usize) -> BencodeRef<'a> {
+        BencodeRef { inner, pos }
+    }
+
     /// Decode the given bytes into a `BencodeRef` using the given decode options.
     #[allow(clippy::missing_errors_doc)]
     pub fn decode(bytes: &'a [u8], opts: BDecodeOpt) -> BencodeParseResult<BencodeRef<'a>> {
@@ -41,7 +57,10 @@ impl<'a> BencodeRef<'a> {
         let (bencode, end_pos) = decode::decode(bytes, 0, opts, 0)?;
 
         if end_pos != bytes.len() && opts.enforce_full_decode() {
-            return Err(BencodeParseError::BytesEmpty { pos: end_pos });
+            return Err(BencodeParseError::BytesEmpty {
+                pos: end_pos,
+                path: BencodePath::new(),
+            });
         }
 
         Ok(bencode)
@@ -101,6 +120,10 @@ impl<'a> BRefAccess for BencodeRef<'a> {
             _ => None,
         }
     }
+
+    fn pos(&self) -> Option<usize> {
+        Some(self.pos)
+    }
 }
 
 impl<'a> BRefAccessExt<'a> for BencodeRef<'a> {
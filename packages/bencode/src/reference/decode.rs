@@ -1,36 +1,43 @@
-use std::collections::btree_map::Entry;
-use std::collections::BTreeMap;
 use std::str;
 
-use crate::error::{BencodeParseError, BencodeParseResult};
+use crate::access::dict::BDictAccess;
+use crate::error::{BencodeParseError, BencodeParseResult, BencodePath};
 use crate::reference::bencode_ref::{BencodeRef, Inner};
 use crate::reference::decode_opt::BDecodeOpt;
+use crate::reference::wire_dict::WireDict;
 
 pub fn decode(bytes: &[u8], pos: usize, opts: BDecodeOpt, depth: usize) -> BencodeParseResult<(BencodeRef<'_>, usize)> {
     if depth >= opts.max_recursion() {
-        return Err(BencodeParseError::InvalidRecursionExceeded { pos, max: depth });
+        return Err(BencodeParseError::InvalidRecursionExceeded {
+            pos,
+            max: depth,
+            path: BencodePath::new(),
+        });
     }
     let curr_byte = peek_byte(bytes, pos)?;
 
     match curr_byte {
         crate::INT_START => {
             let (bencode, next_pos) = decode_int(bytes, pos + 1, crate::BEN_END)?;
-            Ok((Inner::Int(bencode, &bytes[pos..next_pos]).into(), next_pos))
+            Ok((BencodeRef::new(Inner::Int(bencode, &bytes[pos..next_pos]), pos), next_pos))
         }
         crate::LIST_START => {
             let (bencode, next_pos) = decode_list(bytes, pos + 1, opts, depth)?;
-            Ok((Inner::List(bencode, &bytes[pos..next_pos]).into(), next_pos))
+            Ok((BencodeRef::new(Inner::List(bencode, &bytes[pos..next_pos]), pos), next_pos))
         }
         crate::DICT_START => {
             let (bencode, next_pos) = decode_dict(bytes, pos + 1, opts, depth)?;
-            Ok((Inner::Dict(bencode, &bytes[pos..next_pos]).into(), next_pos))
+            Ok((BencodeRef::new(Inner::Dict(bencode, &bytes[pos..next_pos]), pos), next_pos))
         }
         crate::BYTE_LEN_LOW..=crate::BYTE_LEN_HIGH => {
             let (bencode, next_pos) = decode_bytes(bytes, pos)?;
             // Include the length digit, don't increment position
-            Ok((Inner::Bytes(bencode, &bytes[pos..next_pos]).into(), next_pos))
+            Ok((BencodeRef::new(Inner::Bytes(bencode, &bytes[pos..next_pos]), pos), next_pos))
         }
-        _ => Err(BencodeParseError::InvalidByte { pos }),
+        _ => Err(BencodeParseError::InvalidByte {
+            pos,
+            path: BencodePath::new(),
+        }),
     }
 }
 
@@ -38,24 +45,36 @@ fn decode_int(bytes: &[u8], pos: usize, delim: u8) -> BencodeParseResult<(i64, u
     let (_, begin_decode) = bytes.split_at(pos);
 
     let Some(relative_end_pos) = begin_decode.iter().position(|n| *n == delim) else {
-        return Err(BencodeParseError::InvalidIntNoDelimiter { pos });
+        return Err(BencodeParseError::InvalidIntNoDelimiter {
+            pos,
+            path: BencodePath::new(),
+        });
     };
     let int_byte_slice = &begin_decode[..relative_end_pos];
 
     if int_byte_slice.len() > 1 {
         // Negative zero is not allowed (this would not be caught when converting)
         if int_byte_slice[0] == b'-' && int_byte_slice[1] == b'0' {
-            return Err(BencodeParseError::InvalidIntNegativeZero { pos });
+            return Err(BencodeParseError::InvalidIntNegativeZero {
+                pos,
+                path: BencodePath::new(),
+            });
         }
 
         // Zero padding is illegal, and unspecified for key lengths (we disallow both)
         if int_byte_slice[0] == b'0' {
-            return Err(BencodeParseError::InvalidIntZeroPadding { pos });
+            return Err(BencodeParseError::InvalidIntZeroPadding {
+                pos,
+                path: BencodePath::new(),
+            });
         }
     }
 
     let Ok(int_str) = str::from_utf8(int_byte_slice) else {
-        return Err(BencodeParseError::InvalidIntParseError { pos });
+        return Err(BencodeParseError::InvalidIntParseError {
+            pos,
+            path: BencodePath::new(),
+        });
     };
 
     // Position of end of integer type, next byte is the start of the next value
@@ -63,7 +82,10 @@ fn decode_int(bytes: &[u8], pos: usize, delim: u8) -> BencodeParseResult<(i64, u
     let next_pos = absolute_end_pos + 1;
     match int_str.parse::<i64>() {
         Ok(n) => Ok((n, next_pos)),
-        Err(_) => Err(BencodeParseError::InvalidIntParseError { pos }),
+        Err(_) => Err(BencodeParseError::InvalidIntParseError {
+            pos,
+            path: BencodePath::new(),
+        }),
     }
 }
 
@@ -73,14 +95,23 @@ fn decode_bytes(bytes: &[u8], pos: usize) -> BencodeParseResult<(&[u8], usize)>
     let (num_bytes, start_pos) = decode_int(bytes, pos, crate::BYTE_LEN_END)?;
 
     if num_bytes < 0 {
-        return Err(BencodeParseError::InvalidLengthNegative { pos });
+        return Err(BencodeParseError::InvalidLengthNegative {
+            pos,
+            path: BencodePath::new(),
+        });
     }
 
     // Use usize::try_from to handle potential overflow
-    let num_bytes = usize::try_from(num_bytes).map_err(|_| BencodeParseError::InvalidLengthOverflow { pos })?;
+    let num_bytes = usize::try_from(num_bytes).map_err(|_| BencodeParseError::InvalidLengthOverflow {
+        pos,
+        path: BencodePath::new(),
+    })?;
 
     if num_bytes > bytes[start_pos..].len() {
-        return Err(BencodeParseError::InvalidLengthOverflow { pos });
+        return Err(BencodeParseError::InvalidLengthOverflow {
+            pos,
+            path: BencodePath::new(),
+        });
     }
 
     let next_pos = start_pos + num_bytes;
@@ -94,7 +125,7 @@ fn decode_list(bytes: &[u8], pos: usize, opts: BDecodeOpt, depth: usize) -> Benc
     let mut curr_byte = peek_byte(bytes, curr_pos)?;
 
     while curr_byte != crate::BEN_END {
-        let (bencode, next_pos) = decode(bytes, curr_pos, opts, depth + 1)?;
+        let (bencode, next_pos) = decode(bytes, curr_pos, opts, depth + 1).map_err(|err| err.in_index(bencode_list.len()))?;
 
         bencode_list.push(bencode);
 
@@ -111,8 +142,8 @@ fn decode_dict(
     pos: usize,
     opts: BDecodeOpt,
     depth: usize,
-) -> BencodeParseResult<(BTreeMap<&[u8], BencodeRef<'_>>, usize)> {
-    let mut bencode_dict = BTreeMap::new();
+) -> BencodeParseResult<(WireDict<&[u8], BencodeRef<'_>>, usize)> {
+    let mut bencode_dict = WireDict::new();
 
     let mut curr_pos = pos;
     let mut curr_byte = peek_byte(bytes, curr_pos)?;
@@ -121,27 +152,27 @@ fn decode_dict(
         let (key_bytes, next_pos) = decode_bytes(bytes, curr_pos)?;
 
         // Spec says that the keys must be in alphabetical order
-        match (bencode_dict.keys().last(), opts.check_key_sort()) {
+        match (bencode_dict.last_key(), opts.check_key_sort()) {
             (Some(last_key), true) if key_bytes < *last_key => {
                 return Err(BencodeParseError::InvalidKeyOrdering {
                     pos: curr_pos,
                     key: key_bytes.to_vec(),
+                    path: BencodePath::new(),
                 })
             }
             _ => (),
         };
         curr_pos = next_pos;
 
-        let (value, next_pos) = decode(bytes, curr_pos, opts, depth + 1)?;
-        match bencode_dict.entry(key_bytes) {
-            Entry::Vacant(n) => n.insert(value),
-            Entry::Occupied(_) => {
-                return Err(BencodeParseError::InvalidKeyDuplicates {
-                    pos: curr_pos,
-                    key: key_bytes.to_vec(),
-                })
-            }
-        };
+        let (value, next_pos) = decode(bytes, curr_pos, opts, depth + 1).map_err(|err| err.in_key(key_bytes))?;
+        if bencode_dict.contains_key(key_bytes) {
+            return Err(BencodeParseError::InvalidKeyDuplicates {
+                pos: curr_pos,
+                key: key_bytes.to_vec(),
+                path: BencodePath::new(),
+            });
+        }
+        bencode_dict.insert(key_bytes, value);
 
         curr_pos = next_pos;
         curr_byte = peek_byte(bytes, curr_pos)?;
@@ -152,7 +183,10 @@ fn decode_dict(
 }
 
 fn peek_byte(bytes: &[u8], pos: usize) -> BencodeParseResult<u8> {
-    bytes.get(pos).copied().ok_or(BencodeParseError::BytesEmpty { pos })
+    bytes.get(pos).copied().ok_or(BencodeParseError::BytesEmpty {
+        pos,
+        path: BencodePath::new(),
+    })
 }
 
 #[cfg(test)]
@@ -188,6 +222,7 @@ mod tests {
     const DICT_UNORDERED_KEYS: &[u8] = b"d5:z_key5:value5:a_key5:valuee";
     const DICT_DUP_KEYS_SAME_DATA: &[u8] = b"d5:a_keyi0e5:a_keyi0ee";
     const DICT_DUP_KEYS_DIFF_DATA: &[u8] = b"d5:a_keyi0e5:a_key7:a_valuee";
+    const NESTED_INT_NEGATIVE_ZERO: &[u8] = b"d4:infod5:filesld6:lengthi1eed6:lengthi-0eeeee";
     /* cSpell:enable */
 
     #[test]
@@ -308,13 +343,33 @@ mod tests {
     }
 
     #[test]
-    #[should_panic = "InvalidByte { pos: 0 }"]
+    fn positive_decode_dict_unordered_keys_raw_order() {
+        let bencode = BencodeRef::decode(DICT_UNORDERED_KEYS, BDecodeOpt::default()).unwrap();
+        let ben_dict = bencode.dict().unwrap();
+
+        let raw_keys: Vec<&[u8]> = ben_dict.iter_raw().map(|(key, _)| *key).collect();
+        let sorted_keys: Vec<&[u8]> = ben_dict.iter_sorted().map(|(key, _)| *key).collect();
+
+        assert_eq!(vec![&b"z_key"[..], &b"a_key"[..]], raw_keys);
+        assert_eq!(vec![&b"a_key"[..], &b"z_key"[..]], sorted_keys);
+        assert!(!ben_dict.is_sorted());
+    }
+
+    #[test]
+    fn positive_decode_dict_sorted_keys() {
+        let bencode = BencodeRef::decode(DICTIONARY, BDecodeOpt::default()).unwrap();
+
+        assert!(bencode.dict().unwrap().is_sorted());
+    }
+
+    #[test]
+    #[should_panic = "InvalidByte { pos: 0, path: <root> }"]
     fn negative_decode_bytes_neg_len() {
         BencodeRef::decode(BYTES_NEG_LEN, BDecodeOpt::default()).unwrap();
     }
 
     #[test]
-    #[should_panic = "BytesEmpty { pos: 20 }"]
+    #[should_panic = "BytesEmpty { pos: 20, path: <root> }"]
     fn negative_decode_bytes_extra() {
         BencodeRef::decode(BYTES_EXTRA, BDecodeOpt::default()).unwrap();
     }
@@ -327,50 +382,62 @@ mod tests {
     }
 
     #[test]
-    #[should_panic = "InvalidIntParseError { pos: 1 }"]
+    #[should_panic = "InvalidIntParseError { pos: 1, path: <root> }"]
     fn negative_decode_int_nan() {
         super::decode_int(INT_NAN, 1, crate::BEN_END).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidIntZeroPadding { pos: 1 }"]
+    #[should_panic = "InvalidIntZeroPadding { pos: 1, path: <root> }"]
     fn negative_decode_int_leading_zero() {
         super::decode_int(INT_LEADING_ZERO, 1, crate::BEN_END).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidIntZeroPadding { pos: 1 }"]
+    #[should_panic = "InvalidIntZeroPadding { pos: 1, path: <root> }"]
     fn negative_decode_int_double_zero() {
         super::decode_int(INT_DOUBLE_ZERO, 1, crate::BEN_END).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidIntNegativeZero { pos: 1 }"]
+    #[should_panic = "InvalidIntNegativeZero { pos: 1, path: <root> }"]
     fn negative_decode_int_negative_zero() {
         super::decode_int(INT_NEGATIVE_ZERO, 1, crate::BEN_END).unwrap();
     }
 
     #[test]
-    #[should_panic = " InvalidIntParseError { pos: 1 }"]
+    #[should_panic = " InvalidIntParseError { pos: 1, path: <root> }"]
     fn negative_decode_int_double_negative() {
         super::decode_int(INT_DOUBLE_NEGATIVE, 1, crate::BEN_END).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidKeyOrdering { pos: 15, key: [97, 95, 107, 101, 121] }"]
+    #[should_panic = "InvalidKeyOrdering { pos: 15, key: [97, 95, 107, 101, 121], path: <root> }"]
     fn negative_decode_dict_unordered_keys() {
         BencodeRef::decode(DICT_UNORDERED_KEYS, BDecodeOpt::new(5, true, true)).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidKeyDuplicates { pos: 18, key: [97, 95, 107, 101, 121] }"]
+    #[should_panic = "InvalidKeyDuplicates { pos: 18, key: [97, 95, 107, 101, 121], path: <root> }"]
     fn negative_decode_dict_dup_keys_same_data() {
         BencodeRef::decode(DICT_DUP_KEYS_SAME_DATA, BDecodeOpt::default()).unwrap();
     }
 
     #[test]
-    #[should_panic = "InvalidKeyDuplicates { pos: 18, key: [97, 95, 107, 101, 121] }"]
+    #[should_panic = "InvalidKeyDuplicates { pos: 18, key: [97, 95, 107, 101, 121], path: <root> }"]
     fn negative_decode_dict_dup_keys_diff_data() {
         BencodeRef::decode(DICT_DUP_KEYS_DIFF_DATA, BDecodeOpt::default()).unwrap();
     }
+
+    #[test]
+    fn negative_decode_nested_error_path() {
+        let err = BencodeRef::decode(NESTED_INT_NEGATIVE_ZERO, BDecodeOpt::default()).unwrap_err();
+
+        assert_eq!(39, err.pos());
+        assert_eq!("info.files[1].length", err.path().to_string());
+        assert_eq!(
+            "Invalid Integer Found As Negative Zero At 39 In info.files[1].length",
+            err.to_string()
+        );
+    }
 }
@@ -1,3 +1,5 @@
+pub mod bencode_bytes;
 pub mod bencode_ref;
 pub mod decode;
 pub mod decode_opt;
+pub mod wire_dict;
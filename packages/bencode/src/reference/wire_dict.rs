@@ -0,0 +1,174 @@
+use std::borrow::Borrow;
+
+use crate::access::dict::BDictAccess;
+
+/// Dictionary that remembers the order its keys were inserted in, which for decoded
+/// bencode is the order they appeared in on the wire.
+///
+/// Entries are stored once, in insertion order, alongside an index of their positions
+/// sorted by key, so both orders can be iterated without copying any entries.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct WireDict<K, V> {
+    entries: Vec<(K, V)>,
+    // Positions within entries, sorted by key
+    sorted: Vec<usize>,
+}
+
+impl<K, V> WireDict<K, V>
+where
+    K: Borrow<[u8]>,
+{
+    pub fn new() -> WireDict<K, V> {
+        WireDict {
+            entries: Vec::new(),
+            sorted: Vec::new(),
+        }
+    }
+
+    /// Key that was inserted last, if any.
+    pub fn last_key(&self) -> Option<&K> {
+        self.entries.last().map(|(key, _)| key)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.search(key).is_ok()
+    }
+
+    /// Position within the sorted index that holds the given key, or that it would be inserted at.
+    fn search(&self, key: &[u8]) -> Result<usize, usize> {
+        self.sorted
+            .binary_search_by(|&position| self.entries[position].0.borrow().cmp(key))
+    }
+}
+
+impl<K, V> Default for WireDict<K, V>
+where
+    K: Borrow<[u8]>,
+{
+    fn default() -> WireDict<K, V> {
+        WireDict::new()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for WireDict<K, V>
+where
+    K: Borrow<[u8]>,
+{
+    fn from_iter<I>(iter: I) -> WireDict<K, V>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut dict = WireDict::new();
+        for (key, value) in iter {
+            dict.insert(key, value);
+        }
+
+        dict
+    }
+}
+
+impl<K, V> BDictAccess<K, V> for WireDict<K, V>
+where
+    K: Borrow<[u8]>,
+{
+    fn to_list(&self) -> Vec<(&K, &V)> {
+        self.iter_sorted().collect()
+    }
+
+    fn lookup(&self, key: &[u8]) -> Option<&V> {
+        let index = self.search(key).ok()?;
+
+        Some(&self.entries[self.sorted[index]].1)
+    }
+
+    fn lookup_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        let index = self.search(key).ok()?;
+
+        Some(&mut self.entries[self.sorted[index]].1)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.search(key.borrow()) {
+            Ok(index) => Some(std::mem::replace(&mut self.entries[self.sorted[index]].1, value)),
+            Err(index) => {
+                self.sorted.insert(index, self.entries.len());
+                self.entries.push((key, value));
+
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let index = self.search(key).ok()?;
+        let position = self.sorted.remove(index);
+
+        for other in &mut self.sorted {
+            if *other > position {
+                *other -= 1;
+            }
+        }
+
+        Some(self.entries.remove(position).1)
+    }
+
+    fn iter_raw(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+        Box::new(self.entries.iter().map(|(key, value)| (key, value)))
+    }
+
+    fn iter_sorted(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+        Box::new(self.sorted.iter().map(|&position| {
+            let (key, value) = &self.entries[position];
+
+            (key, value)
+        }))
+    }
+
+    fn is_sorted(&self) -> bool {
+        self.sorted.iter().enumerate().all(|(index, &position)| index == position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access::dict::BDictAccess;
+    use crate::reference::wire_dict::WireDict;
+
+    /* cSpell:disable */
+    fn unsorted() -> WireDict<&'static [u8], i64> {
+        [(&b"zxc"[..], 0), (&b"asd"[..], 1), (&b"qwe"[..], 2)].into_iter().collect()
+    }
+    /* cSpell:enable */
+
+    #[test]
+    fn positive_iter_raw_and_sorted() {
+        let dict = unsorted();
+
+        let raw: Vec<_> = dict.iter_raw().map(|(_, &value)| value).collect();
+        let sorted: Vec<_> = dict.iter_sorted().map(|(_, &value)| value).collect();
+
+        assert_eq!(vec![0, 1, 2], raw);
+        assert_eq!(vec![1, 2, 0], sorted);
+        assert!(!dict.is_sorted());
+    }
+
+    #[test]
+    fn positive_insert_existing_keeps_position() {
+        let mut dict = unsorted();
+
+        /* cspell:disable-next-line */
+        assert_eq!(Some(0), dict.insert(&b"zxc"[..], 5));
+        assert_eq!(Some(&5), dict.lookup(b"zxc")); // cspell:disable-line
+        assert_eq!(Some(&b"qwe"[..]), dict.last_key().copied()); // cspell:disable-line
+    }
+
+    #[test]
+    fn positive_remove_restores_sorted() {
+        let mut dict = unsorted();
+
+        assert_eq!(Some(0), dict.remove(b"zxc")); // cspell:disable-line
+        assert_eq!(None, dict.lookup(b"zxc")); // cspell:disable-line
+        assert_eq!(Some(&2), dict.lookup(b"qwe")); // cspell:disable-line
+        assert!(dict.is_sorted());
+    }
+}
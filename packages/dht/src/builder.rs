@@ -1,20 +1,43 @@
 use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use futures::channel::mpsc;
-use futures::SinkExt as _;
+use futures::channel::{mpsc, oneshot};
+use futures::{SinkExt as _, Stream};
+use rand::seq::SliceRandom as _;
 use tokio::net::UdpSocket;
 use tokio::task::JoinSet;
-use util::bt::InfoHash;
+use util::bt::{InfoHash, NodeId};
 use util::net;
 
+use crate::error::BootstrapError;
 use crate::handshaker_trait::HandshakerTrait;
+use crate::metrics::{DhtMetrics, SharedMetrics};
 use crate::router::Router;
-use crate::worker::{self, DhtEvent, OneshotTask, ShutdownCause};
+use crate::routing::node::{NodeInfo, NodeStatus};
+use crate::routing::table::RoutingConfig;
+use crate::routing::{bucket, table};
+use crate::snapshot::RoutingSnapshot;
+use crate::stats::DhtStats;
+use crate::storage::{MemoryStorage, PeerStorage, SharedStorage, StorageConfig, StorageStats};
+use crate::worker::blacklist::{BlacklistConfig, BlacklistEntry};
+use crate::worker::cache::LookupCacheConfig;
+use crate::worker::limiter::RateLimitConfig;
+use crate::worker::lookup::{AnnouncePort, LookupConfig};
+use crate::worker::sweep::SweepConfig;
+use crate::worker::{self, DhtEvent, IncomingQuery, OneshotTask, ShutdownCause};
+
+const QUERY_CHANNEL_CAPACITY: usize = 256;
+
+const DEFAULT_BOOTSTRAP_MIN_GOOD_NODES: usize = bucket::MAX_BUCKET_SIZE;
+const DEFAULT_BOOTSTRAP_TIMEOUT_SECS: u64 = 60;
 
 /// Maintains a Distributed Hash (Routing) Table.
 pub struct MainlineDht {
+    node_id: NodeId,
+    local_addr: SocketAddr,
+    bootstrap_readiness: (usize, Duration),
     main_task_sender: mpsc::Sender<OneshotTask>,
     _tasks: JoinSet<()>,
 }
@@ -31,11 +54,27 @@ impl MainlineDht {
         let kill_sock = send_sock.clone();
         let kill_addr = send_sock.local_addr()?;
 
+        // TODO: Utilize the security extension.
+        let node_id = builder.node_id.unwrap_or_else(table::random_node_id);
+        let storage = builder
+            .storage
+            .unwrap_or_else(|| SharedStorage::new(Arc::new(MemoryStorage::new(builder.storage_config))));
+
         let (main_task_sender, tasks) = worker::start_mainline_dht(
             &send_sock,
             recv_sock,
+            node_id,
             builder.read_only,
             builder.ext_addr,
+            builder.lookup_config,
+            builder.lookup_cache_config,
+            builder.announce_port,
+            builder.storage_config,
+            storage,
+            builder.rate_limit_config,
+            builder.routing_config,
+            builder.blacklist_config,
+            builder.metrics,
             handshaker,
             kill_sock,
             kill_addr,
@@ -54,24 +93,92 @@ impl MainlineDht {
         }
 
         Ok(MainlineDht {
+            node_id,
+            local_addr: kill_addr,
+            bootstrap_readiness: builder.bootstrap_readiness,
             main_task_sender,
             _tasks: tasks,
         })
     }
 
+    /// Id of our node, used by remote nodes to place us in their routing table.
+    #[must_use]
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// Local address that the DHT is bound to.
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Wait until the routing table holds enough good nodes for the DHT to be usable, returning their number.
+    ///
+    /// The number of good nodes and how long to wait for them are set with `DhtBuilder::set_bootstrap_readiness`,
+    /// so that resolving a magnet link, for example, can be delayed until lookups are likely to find peers.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the timeout is reached first, or if the DHT has shutdown.
+    pub async fn bootstrapped(&self) -> Result<usize, BootstrapError> {
+        let (min_good_nodes, timeout) = self.bootstrap_readiness;
+        let (send, recv) = oneshot::channel();
+
+        if let Err(e) = self
+            .main_task_sender
+            .clone()
+            .send(OneshotTask::WaitBootstrapped(min_good_nodes, send))
+            .await
+        {
+            tracing::warn!("bip_dht: MainlineDht failed to send a wait bootstrapped message..., {e}");
+        }
+
+        match tokio::time::timeout(timeout, recv).await {
+            Ok(Ok(good_nodes)) => Ok(good_nodes),
+            Ok(Err(_)) => Err(BootstrapError::Shutdown),
+            Err(_) => {
+                let good_nodes = self
+                    .routing_table()
+                    .await
+                    .iter()
+                    .filter(|info| info.status() == NodeStatus::Good)
+                    .count();
+
+                Err(BootstrapError::Timeout {
+                    good_nodes,
+                    min_good_nodes,
+                })
+            }
+        }
+    }
+
     /// Perform a search for the given `InfoHash` with an optional announce on the closest nodes.
     ///
     ///
     /// Announcing will place your contact information in the DHT so others performing lookups
     /// for the `InfoHash` will be able to find your contact information and initiate a handshake.
     ///
+    /// Searches that do not announce reuse the peers found by a recent lookup for the `InfoHash`,
+    /// if there is one, see `LookupCacheConfig`.
+    ///
     /// If the initial bootstrap has not finished, the search will be queued and executed once
     /// the bootstrap has completed.
     pub async fn search(&self, hash: InfoHash, announce: bool) {
+        self.start_lookup(hash, announce, true).await;
+    }
+
+    /// Perform a search for the given `InfoHash`, as with `search`, but always run a new lookup
+    /// rather than reusing the peers found by a recent lookup.
+    pub async fn search_uncached(&self, hash: InfoHash, announce: bool) {
+        self.start_lookup(hash, announce, false).await;
+    }
+
+    async fn start_lookup(&self, hash: InfoHash, announce: bool, use_cache: bool) {
         if self
             .main_task_sender
             .clone()
-            .send(OneshotTask::StartLookup(hash, announce))
+            .send(OneshotTask::StartLookup(hash, announce, use_cache))
             .await
             .is_err()
         {
@@ -79,6 +186,162 @@ impl MainlineDht {
         }
     }
 
+    /// Perform a bulk `find_node` sweep over the given targets, rate limited by the given `SweepConfig`.
+    ///
+    /// Each target is queried against the closest known nodes in our routing table, with any nodes
+    /// found being added to the routing table. A `DhtEvent::SweepCompleted` event is sent once the
+    /// sweep has finished waiting for responses.
+    ///
+    /// If the initial bootstrap has not finished, the sweep will be queued and executed once
+    /// the bootstrap has completed.
+    pub async fn sweep(&self, targets: Vec<NodeId>, config: SweepConfig) {
+        if self
+            .main_task_sender
+            .clone()
+            .send(OneshotTask::StartSweep(targets, config))
+            .await
+            .is_err()
+        {
+            tracing::warn!("bip_dht: MainlineDht failed to send a start sweep message...");
+        }
+    }
+
+    /// Perform an iterative `find_node` lookup for the nodes closest to the given target.
+    ///
+    /// The returned stream yields the closest nodes that responded to us, closest first, and
+    /// ends once the lookup has finished. Nodes found along the way are added to the routing
+    /// table. The stream ends without yielding anything if the DHT has shutdown.
+    ///
+    /// If the initial bootstrap has not finished, the lookup will be queued and executed once
+    /// the bootstrap has completed.
+    pub async fn find_closest_nodes(&self, target: NodeId) -> impl Stream<Item = NodeInfo> {
+        let (send, recv) = mpsc::channel(bucket::MAX_BUCKET_SIZE);
+
+        if let Err(e) = self
+            .main_task_sender
+            .clone()
+            .send(OneshotTask::StartClosest(target, send))
+            .await
+        {
+            tracing::warn!("bip_dht: MainlineDht failed to send a start closest nodes message..., {e}");
+        }
+
+        recv
+    }
+
+    /// Snapshot of the good and questionable nodes currently in our routing table.
+    ///
+    /// Returns an empty list if the DHT has shutdown.
+    pub async fn routing_table(&self) -> Vec<NodeInfo> {
+        let (send, recv) = oneshot::channel();
+
+        if let Err(e) = self.main_task_sender.clone().send(OneshotTask::RoutingTable(send)).await {
+            tracing::warn!("bip_dht: MainlineDht failed to send a routing table message..., {e}");
+        }
+
+        recv.await.unwrap_or_default()
+    }
+
+    /// Random sample of up to `max_nodes` good nodes from our routing table, to share with cooperating DHTs.
+    ///
+    /// Returns an empty snapshot if the DHT has shutdown.
+    pub async fn export_nodes(&self, max_nodes: usize) -> RoutingSnapshot {
+        let good_nodes: Vec<NodeInfo> = self
+            .routing_table()
+            .await
+            .into_iter()
+            .filter(|info| info.status() == NodeStatus::Good)
+            .collect();
+
+        RoutingSnapshot::new(
+            good_nodes
+                .choose_multiple(&mut rand::thread_rng(), max_nodes)
+                .map(|info| (info.id(), info.addr())),
+        )
+    }
+
+    /// Merge a snapshot exported by a trusted DHT into our routing table.
+    ///
+    /// Every node in the snapshot is sent a query, and only those that respond are added to our routing
+    /// table, so stale snapshots do no harm. A `DhtEvent::ImportCompleted` event is sent once the import
+    /// has finished waiting for responses. Imports run even while the initial bootstrap is in progress.
+    pub async fn import_nodes(&self, snapshot: RoutingSnapshot) {
+        if self
+            .main_task_sender
+            .clone()
+            .send(OneshotTask::StartImport(snapshot.nodes().to_vec()))
+            .await
+            .is_err()
+        {
+            tracing::warn!("bip_dht: MainlineDht failed to send a start import message...");
+        }
+    }
+
+    /// Number of `InfoHash`(s) and peers currently stored on behalf of remote nodes.
+    ///
+    /// Returns empty statistics if the DHT has shutdown.
+    pub async fn storage_stats(&self) -> StorageStats {
+        let (send, recv) = oneshot::channel();
+
+        if let Err(e) = self.main_task_sender.clone().send(OneshotTask::StorageStats(send)).await {
+            tracing::warn!("bip_dht: MainlineDht failed to send a storage stats message..., {e}");
+        }
+
+        recv.await.unwrap_or_default()
+    }
+
+    /// Snapshot of the nodes that misbehaved recently, and whether they are blacklisted.
+    ///
+    /// Blacklisted nodes are not added to our routing table or sent queries, see `BlacklistConfig`.
+    /// Returns an empty list if the DHT has shutdown.
+    pub async fn blacklist(&self) -> Vec<BlacklistEntry> {
+        let (send, recv) = oneshot::channel();
+
+        if let Err(e) = self.main_task_sender.clone().send(OneshotTask::Blacklist(send)).await {
+            tracing::warn!("bip_dht: MainlineDht failed to send a blacklist message..., {e}");
+        }
+
+        recv.await.unwrap_or_default()
+    }
+
+    /// Forget the misbehavior of every node, removing them all from the blacklist.
+    pub async fn clear_blacklist(&self) {
+        if let Err(e) = self.main_task_sender.clone().send(OneshotTask::ClearBlacklist).await {
+            tracing::warn!("bip_dht: MainlineDht failed to send a clear blacklist message..., {e}");
+        }
+    }
+
+    /// Snapshot of the routing table and storage statistics for this DHT.
+    ///
+    /// When running several DHTs in one process, their statistics can be summed
+    /// into a combined view. Returns empty statistics if the DHT has shutdown.
+    pub async fn stats(&self) -> DhtStats {
+        let nodes = self.routing_table().await.len();
+        let storage = self.storage_stats().await;
+
+        DhtStats::new(nodes, storage)
+    }
+
+    /// A Receiver which will receive the queries that remote nodes send to us.
+    ///
+    /// Queries are dropped for this receiver if it falls behind, so that monitoring never stalls
+    /// the DHT. Queries are reported even when the DHT is read only and does not respond to them.
+    #[must_use]
+    pub async fn queries(&self) -> mpsc::Receiver<IncomingQuery> {
+        let (send, recv) = mpsc::channel(QUERY_CHANNEL_CAPACITY);
+
+        if let Err(e) = self
+            .main_task_sender
+            .clone()
+            .send(OneshotTask::RegisterQuerySender(send))
+            .await
+        {
+            tracing::warn!("bip_dht: MainlineDht failed to send a register query sender message..., {e}");
+        }
+
+        recv
+    }
+
     /// An event Receiver which will receive events occurring within the DHT.
     ///
     /// It is important to at least monitor the DHT for shutdown events as any calls
@@ -124,7 +387,18 @@ pub struct DhtBuilder {
     routers: HashSet<Router>,
     read_only: bool,
     src_addr: SocketAddr,
+    node_id: Option<NodeId>,
     ext_addr: Option<SocketAddr>,
+    lookup_config: LookupConfig,
+    lookup_cache_config: LookupCacheConfig,
+    announce_port: AnnouncePort,
+    storage_config: StorageConfig,
+    storage: Option<SharedStorage>,
+    rate_limit_config: RateLimitConfig,
+    routing_config: RoutingConfig,
+    blacklist_config: BlacklistConfig,
+    bootstrap_readiness: (usize, Duration),
+    metrics: SharedMetrics,
 }
 
 impl DhtBuilder {
@@ -138,7 +412,21 @@ impl DhtBuilder {
             routers: HashSet::new(),
             read_only: true,
             src_addr: net::default_route_v4(),
+            node_id: None,
             ext_addr: None,
+            lookup_config: LookupConfig::default(),
+            lookup_cache_config: LookupCacheConfig::default(),
+            announce_port: AnnouncePort::default(),
+            storage_config: StorageConfig::default(),
+            storage: None,
+            rate_limit_config: RateLimitConfig::default(),
+            routing_config: RoutingConfig::default(),
+            blacklist_config: BlacklistConfig::default(),
+            bootstrap_readiness: (
+                DEFAULT_BOOTSTRAP_MIN_GOOD_NODES,
+                Duration::from_secs(DEFAULT_BOOTSTRAP_TIMEOUT_SECS),
+            ),
+            metrics: SharedMetrics::default(),
         }
     }
 
@@ -214,6 +502,126 @@ impl DhtBuilder {
         self
     }
 
+    /// Provide the DHT with the id of our node.
+    ///
+    /// If this is not supplied a random id will be generated. Useful when running
+    /// several DHTs in one process, each with their own id and routing table.
+    #[must_use]
+    pub fn set_node_id(mut self, node_id: NodeId) -> DhtBuilder {
+        self.node_id = Some(node_id);
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for iterative lookups.
+    ///
+    /// Controls the number of concurrent queries and the bounds placed on
+    /// adaptive query timeouts.
+    #[must_use]
+    pub fn set_lookup_config(mut self, config: LookupConfig) -> DhtBuilder {
+        self.lookup_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for caching the results of recent lookups.
+    ///
+    /// Controls for how long, and for how many `InfoHash`(s), searches reuse the peers found by
+    /// a recent lookup rather than running a new one.
+    #[must_use]
+    pub fn set_lookup_cache_config(mut self, config: LookupCacheConfig) -> DhtBuilder {
+        self.lookup_cache_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the port to announce after a lookup for an `InfoHash`.
+    ///
+    /// Defaults to the port that the handshaker is listening on.
+    #[must_use]
+    pub fn set_announce_port(mut self, port: AnnouncePort) -> DhtBuilder {
+        self.announce_port = port;
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for storing peers announced by remote nodes.
+    ///
+    /// Controls how long announced peers are kept, how many are kept, and how
+    /// many are handed out in response to a `get_peers` request.
+    #[must_use]
+    pub fn set_storage_config(mut self, config: StorageConfig) -> DhtBuilder {
+        self.storage_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the backend used for storing peers announced by remote nodes.
+    ///
+    /// Defaults to a `MemoryStorage` created from the storage configuration. When a backend is
+    /// provided, the storage configuration only controls how many peers are handed out in
+    /// response to a `get_peers` request.
+    #[must_use]
+    pub fn set_peer_storage(mut self, storage: Arc<dyn PeerStorage>) -> DhtBuilder {
+        self.storage = Some(SharedStorage::new(storage));
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for limiting the rate of outgoing queries.
+    ///
+    /// Controls how many queries are sent across all nodes and to a single node, and
+    /// how long a response to a query is accepted for.
+    #[must_use]
+    pub fn set_rate_limit_config(mut self, config: RateLimitConfig) -> DhtBuilder {
+        self.rate_limit_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for splitting the buckets of its routing table.
+    ///
+    /// Controls how deep the routing table grows around our own node id.
+    #[must_use]
+    pub fn set_routing_config(mut self, config: RoutingConfig) -> DhtBuilder {
+        self.routing_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the configuration used for blacklisting misbehaving nodes.
+    ///
+    /// Controls how many malformed messages, unknown transaction ids, or spoofed responses a
+    /// node may send before it is blacklisted, and how long its misbehavior is remembered.
+    #[must_use]
+    pub fn set_blacklist_config(mut self, config: BlacklistConfig) -> DhtBuilder {
+        self.blacklist_config = config;
+
+        self
+    }
+
+    /// Provide the DHT with the number of good nodes its routing table must hold to be considered
+    /// bootstrapped, and how long `MainlineDht::bootstrapped` waits for them.
+    ///
+    /// Defaults to a full bucket of good nodes, waited for up to a minute.
+    #[must_use]
+    pub fn set_bootstrap_readiness(mut self, min_good_nodes: usize, timeout: Duration) -> DhtBuilder {
+        self.bootstrap_readiness = (min_good_nodes, timeout);
+
+        self
+    }
+
+    /// Provide the DHT with hooks that will be called to report its metrics.
+    ///
+    /// Allows query counts, response latencies, errors, and the size of the routing table
+    /// and announce storage to be exported to a monitoring system.
+    #[must_use]
+    pub fn set_metrics(mut self, metrics: Arc<dyn DhtMetrics>) -> DhtBuilder {
+        self.metrics = SharedMetrics::new(metrics);
+
+        self
+    }
+
     /// Start a mainline DHT with the current configuration.
     ///
     /// # Errors
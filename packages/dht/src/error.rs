@@ -1,4 +1,4 @@
-use bencode::BencodeConvertError;
+use bencode::{BencodeConvertError, BencodeParseError};
 use thiserror::Error;
 
 use crate::message::error::ErrorMessage;
@@ -19,3 +19,37 @@ pub enum DhtError {
     #[error("Node Sent Us An Invalid Request Message With Code {msg:?} And Message {msg}")]
     InvalidRequest { msg: ErrorMessage<'static> },
 }
+
+/// Error returned while waiting for the DHT to bootstrap, see `MainlineDht::bootstrapped`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BootstrapError {
+    #[error("Routing Table Held {good_nodes} Of {min_good_nodes} Good Nodes Before Timing Out")]
+    Timeout { good_nodes: usize, min_good_nodes: usize },
+    #[error("DHT Shut Down Before Bootstrapping")]
+    Shutdown,
+}
+
+/// Error returned when reading a `RoutingSnapshot` from bytes.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Bencode error: {0}")]
+    Bencode(#[from] BencodeParseError),
+    #[error("Snapshot Does Not Hold Valid Compact Node Info")]
+    InvalidNodes,
+}
+
+impl DhtError {
+    /// Nest a bencode error within the value of the given dictionary key, see `BencodePath`.
+    #[must_use]
+    pub fn in_key<K>(self, key: K) -> DhtError
+    where
+        K: AsRef<[u8]>,
+    {
+        match self {
+            DhtError::Bencode(err) => DhtError::Bencode(err.in_key(key)),
+            other => other,
+        }
+    }
+}
@@ -18,9 +18,12 @@ mod builder;
 mod error;
 pub mod handshaker_trait;
 pub mod message;
+mod metrics;
 mod router;
 mod routing;
 mod security;
+mod snapshot;
+mod stats;
 mod storage;
 mod token;
 mod transaction;
@@ -28,8 +31,21 @@ mod worker;
 
 pub use handshake::Handshaker;
 /// Test
-pub use util::bt::{InfoHash, PeerId};
+pub use util::bt::{InfoHash, NodeId, PeerId};
 
 pub use crate::builder::{DhtBuilder, MainlineDht};
+pub use crate::error::{BootstrapError, SnapshotError};
+pub use crate::metrics::DhtMetrics;
 pub use crate::router::Router;
-pub use crate::worker::{DhtEvent, ShutdownCause};
+pub use crate::routing::node::{NodeInfo, NodeStatus};
+pub use crate::routing::table::RoutingConfig;
+pub use crate::snapshot::RoutingSnapshot;
+pub use crate::stats::DhtStats;
+pub use crate::storage::{LruStorage, MemoryStorage, PeerStorage, StorageConfig, StorageStats};
+pub use crate::worker::blacklist::{BlacklistConfig, BlacklistEntry, Misbehavior};
+pub use crate::worker::cache::LookupCacheConfig;
+pub use crate::worker::import::ImportStats;
+pub use crate::worker::limiter::RateLimitConfig;
+pub use crate::worker::lookup::{AnnouncePort, LookupConfig, LookupStats};
+pub use crate::worker::sweep::{SweepConfig, SweepStats};
+pub use crate::worker::{DhtEvent, IncomingQuery, QueryKind, ShutdownCause};
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::hash::Hash;
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
 use bencode::{BListAccess, BRefAccess};
 use util::bt::{self, NodeId};
@@ -13,8 +14,60 @@ use util::sha::ShaHash;
 // of bytes but instead offer to write the nodes into a provided buffer.
 
 const BYTES_PER_COMPACT_IP: usize = 6;
+const BYTES_PER_COMPACT_IPV6: usize = 18;
 const BYTES_PER_COMPACT_NODE_INFO: usize = 26;
+const BYTES_PER_COMPACT_NODE_INFO_V6: usize = 38;
 
+/// Socket address of a single address family, as found in compact node info.
+pub trait CompactAddr: Copy + Into<SocketAddr> {
+    /// Number of bytes used by the compact form of the address.
+    const BYTES_PER_COMPACT_ADDR: usize;
+
+    /// Read the address from its compact form.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the number of bytes is not `BYTES_PER_COMPACT_ADDR`.
+    fn from_compact_bytes(bytes: &[u8]) -> Self;
+}
+
+impl CompactAddr for SocketAddrV4 {
+    const BYTES_PER_COMPACT_ADDR: usize = BYTES_PER_COMPACT_IP;
+
+    fn from_compact_bytes(bytes: &[u8]) -> SocketAddrV4 {
+        socket_v4_from_bytes_be(bytes).unwrap()
+    }
+}
+
+impl CompactAddr for SocketAddrV6 {
+    const BYTES_PER_COMPACT_ADDR: usize = BYTES_PER_COMPACT_IPV6;
+
+    fn from_compact_bytes(bytes: &[u8]) -> SocketAddrV6 {
+        socket_v6_from_bytes_be(bytes).unwrap()
+    }
+}
+
+/// Compact node info for a single address family.
+///
+/// Allows code handling the `nodes` and `nodes6` fields of a message to be generic over the address family.
+pub trait CompactNodes<'a>: Copy {
+    /// Address family of the nodes.
+    type Addr: CompactAddr;
+
+    /// Raw bytes of the compact node info.
+    fn nodes(&self) -> &'a [u8];
+
+    /// Iterate over the `(NodeId, address)` pairs of the compact node info.
+    fn iter(&self) -> CompactNodeInfoIter<'a, Self::Addr> {
+        CompactNodeInfoIter {
+            nodes: self.nodes(),
+            pos: 0,
+            _addr: PhantomData,
+        }
+    }
+}
+
+/// Compact node info for IPv4 nodes, 26 bytes per node.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct CompactNodeInfo<'a> {
     nodes: &'a [u8],
@@ -27,14 +80,7 @@ impl<'a> CompactNodeInfo<'a> {
     ///
     /// This function will return an error if the byte array is the wrong length.
     pub fn new(nodes: &'a [u8]) -> LengthResult<CompactNodeInfo<'a>> {
-        if nodes.len() % BYTES_PER_COMPACT_NODE_INFO != 0 {
-            Err(Error::new(
-                LengthErrorKind::LengthMultipleExpected,
-                BYTES_PER_COMPACT_NODE_INFO,
-            ))
-        } else {
-            Ok(CompactNodeInfo { nodes })
-        }
+        validate_compact_nodes(nodes, BYTES_PER_COMPACT_NODE_INFO).map(|nodes| CompactNodeInfo { nodes })
     }
 
     #[must_use]
@@ -43,36 +89,85 @@ impl<'a> CompactNodeInfo<'a> {
     }
 }
 
+impl<'a> CompactNodes<'a> for CompactNodeInfo<'a> {
+    type Addr = SocketAddrV4;
+
+    fn nodes(&self) -> &'a [u8] {
+        self.nodes
+    }
+}
+
 impl<'a> IntoIterator for CompactNodeInfo<'a> {
     type Item = (NodeId, SocketAddrV4);
-    type IntoIter = CompactNodeInfoIter<'a>;
+    type IntoIter = CompactNodeInfoIter<'a, SocketAddrV4>;
 
     fn into_iter(self) -> Self::IntoIter {
-        CompactNodeInfoIter {
-            nodes: self.nodes,
-            pos: 0,
-        }
+        self.iter()
     }
 }
 
+/// Compact node info for IPv6 nodes, 38 bytes per node, as sent in `nodes6` fields.
+#[allow(clippy::module_name_repetitions)]
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub struct CompactNodeInfoIter<'a> {
+pub struct CompactNodeInfoV6<'a> {
+    nodes: &'a [u8],
+}
+
+impl<'a> CompactNodeInfoV6<'a> {
+    /// Make a new `CompactNodeInfoV6` from bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the byte array is the wrong length.
+    pub fn new(nodes: &'a [u8]) -> LengthResult<CompactNodeInfoV6<'a>> {
+        validate_compact_nodes(nodes, BYTES_PER_COMPACT_NODE_INFO_V6).map(|nodes| CompactNodeInfoV6 { nodes })
+    }
+
+    #[must_use]
+    pub fn nodes(&self) -> &'a [u8] {
+        self.nodes
+    }
+}
+
+impl<'a> CompactNodes<'a> for CompactNodeInfoV6<'a> {
+    type Addr = SocketAddrV6;
+
+    fn nodes(&self) -> &'a [u8] {
+        self.nodes
+    }
+}
+
+impl<'a> IntoIterator for CompactNodeInfoV6<'a> {
+    type Item = (NodeId, SocketAddrV6);
+    type IntoIter = CompactNodeInfoIter<'a, SocketAddrV6>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CompactNodeInfoIter<'a, A = SocketAddrV4> {
     nodes: &'a [u8],
     pos: usize,
+    _addr: PhantomData<A>,
 }
 
 #[allow(clippy::copy_iterator)]
-impl<'a> Iterator for CompactNodeInfoIter<'a> {
-    type Item = (NodeId, SocketAddrV4);
+impl<A> Iterator for CompactNodeInfoIter<'_, A>
+where
+    A: CompactAddr,
+{
+    type Item = (NodeId, A);
 
-    fn next(&mut self) -> Option<(NodeId, SocketAddrV4)> {
+    fn next(&mut self) -> Option<(NodeId, A)> {
         if self.pos == self.nodes.len() {
             None
         } else {
-            let compact_info_offset = self.pos + BYTES_PER_COMPACT_NODE_INFO;
+            let compact_info_offset = self.pos + bt::NODE_ID_LEN + A::BYTES_PER_COMPACT_ADDR;
             let compact_info = &self.nodes[self.pos..compact_info_offset];
 
-            self.pos += BYTES_PER_COMPACT_NODE_INFO;
+            self.pos = compact_info_offset;
 
             Some(parts_from_compact_info(compact_info))
         }
@@ -174,13 +269,24 @@ where
 
 // ----------------------------------------------------------------------------//
 
-/// Panics if the size of `compact_info` is less than `BYTES_PER_COMPACT_NODE_INFO`.
-fn parts_from_compact_info(compact_info: &[u8]) -> (NodeId, SocketAddrV4) {
+#[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` is newer than our minimum supported rust version
+fn validate_compact_nodes(nodes: &[u8], bytes_per_node: usize) -> LengthResult<&[u8]> {
+    if nodes.len() % bytes_per_node == 0 {
+        Ok(nodes)
+    } else {
+        Err(Error::new(LengthErrorKind::LengthMultipleExpected, bytes_per_node))
+    }
+}
+
+/// Panics if the size of `compact_info` is not that of a node id followed by a compact address.
+fn parts_from_compact_info<A>(compact_info: &[u8]) -> (NodeId, A)
+where
+    A: CompactAddr,
+{
     // Use unwrap here because we know these can never fail, but they aren't statically guaranteed
     let node_id = ShaHash::from_hash(&compact_info[0..bt::NODE_ID_LEN]).unwrap();
 
-    let compact_ip_offset = bt::NODE_ID_LEN + BYTES_PER_COMPACT_IP;
-    let socket = socket_v4_from_bytes_be(&compact_info[bt::NODE_ID_LEN..compact_ip_offset]).unwrap();
+    let socket = A::from_compact_bytes(&compact_info[bt::NODE_ID_LEN..]);
 
     (node_id, socket)
 }
@@ -202,15 +308,35 @@ fn socket_v4_from_bytes_be(bytes: &[u8]) -> LengthResult<SocketAddrV4> {
     }
 }
 
+fn socket_v6_from_bytes_be(bytes: &[u8]) -> LengthResult<SocketAddrV6> {
+    if bytes.len() == BYTES_PER_COMPACT_IPV6 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&bytes[0..16]);
+
+        let port = u16::from_be_bytes([bytes[16], bytes[17]]);
+
+        Ok(SocketAddrV6::new(Ipv6Addr::from(octets), port, 0, 0))
+    } else {
+        Err(Error::new(LengthErrorKind::LengthExpected, BYTES_PER_COMPACT_IPV6))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
     use bencode::{ben_bytes, ben_list, BRefAccess, BencodeMut, BencodeRef};
     use util::bt::NodeId;
     use util::sha::ShaHash;
 
-    use crate::message::compact_info::{CompactNodeInfo, CompactValueInfo};
+    use crate::message::compact_info::{CompactNodeInfo, CompactNodeInfoV6, CompactNodes, CompactValueInfo};
+
+    fn collect_socket_addrs<'a, N>(nodes: N) -> Vec<SocketAddr>
+    where
+        N: CompactNodes<'a>,
+    {
+        nodes.iter().map(|(_, addr)| addr.into()).collect()
+    }
 
     #[test]
     fn positive_compact_nodes_empty() {
@@ -252,6 +378,43 @@ mod tests {
         assert_eq!(collected_info[1].1, SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 2), 240));
     }
 
+    #[test]
+    fn positive_compact_nodes_v6_one() {
+        let mut bytes = vec![1u8; 20];
+        bytes.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x1a, 0xe1]);
+        let compact_node = CompactNodeInfoV6::new(&bytes[..]).unwrap();
+
+        let collected_info: Vec<(NodeId, SocketAddrV6)> = compact_node.into_iter().collect();
+        assert_eq!(collected_info.len(), 1);
+
+        assert_eq!(collected_info[0].0, ShaHash::from_hash(&bytes[0..20]).unwrap());
+        assert_eq!(
+            collected_info[0].1,
+            SocketAddrV6::new(Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 1), 6881, 0, 0)
+        );
+    }
+
+    #[test]
+    fn negative_compact_nodes_v6_wrong_length() {
+        let bytes = [1u8; 26];
+
+        assert!(CompactNodeInfoV6::new(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn positive_compact_nodes_generic_over_family() {
+        let mut v4_bytes = vec![1u8; 20];
+        v4_bytes.extend_from_slice(&[192, 168, 0, 1, 0, 240]);
+        let mut v6_bytes = vec![2u8; 20];
+        v6_bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 240]);
+
+        let v4_addrs = collect_socket_addrs(CompactNodeInfo::new(&v4_bytes[..]).unwrap());
+        let v6_addrs = collect_socket_addrs(CompactNodeInfoV6::new(&v6_bytes[..]).unwrap());
+
+        assert_eq!(v4_addrs, vec![SocketAddr::from((Ipv4Addr::new(192, 168, 0, 1), 240))]);
+        assert_eq!(v6_addrs, vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 240))]);
+    }
+
     #[test]
     fn positive_compact_values_empty() {
         let bencode_values = Vec::new();
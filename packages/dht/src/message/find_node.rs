@@ -1,18 +1,104 @@
-use bencode::{ben_bytes, ben_map, BConvert, BDictAccess, BRefAccess};
+use std::net::SocketAddr;
+
+use bencode::inner::BCowConvert;
+use bencode::{ben_bytes, ben_list, ben_map, BConvert, BDictAccess, BMutAccess, BRefAccess};
 use util::bt::NodeId;
 
 use crate::error::DhtError;
 use crate::message;
-use crate::message::compact_info::CompactNodeInfo;
+use crate::message::compact_info::{CompactNodeInfo, CompactNodeInfoV6};
 use crate::message::request::{self, RequestValidate};
 use crate::message::response::ResponseValidate;
 
+const WANT_KEY: &str = "want";
+const WANT_V4_KEY: &str = "n4";
+const WANT_V6_KEY: &str = "n6";
+
+/// Address families of the nodes wanted in a `find_node` response, sent in the `want` field (BEP 32).
+///
+/// An empty `Want` leaves the choice to the responder, which returns nodes of the family of the requester's address.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Want {
+    v4: bool,
+    v6: bool,
+}
+
+impl Want {
+    /// Want IPv4 nodes only.
+    pub const V4: Want = Want { v4: true, v6: false };
+    /// Want IPv6 nodes only.
+    pub const V6: Want = Want { v4: false, v6: true };
+    /// Want both IPv4 and IPv6 nodes.
+    pub const BOTH: Want = Want { v4: true, v6: true };
+
+    /// Address families reachable from a socket bound to the given local address.
+    ///
+    /// A socket bound to the unspecified IPv6 address is dual-stack, so it reaches both families.
+    #[must_use]
+    pub fn reachable_from(local_addr: SocketAddr) -> Want {
+        match local_addr {
+            SocketAddr::V4(_) => Want::V4,
+            SocketAddr::V6(v6_addr) if v6_addr.ip().is_unspecified() => Want::BOTH,
+            SocketAddr::V6(_) => Want::V6,
+        }
+    }
+
+    /// Resolve an empty `Want` to the family of the requester's address, as the responder would.
+    #[must_use]
+    pub fn or_family_of(self, addr: SocketAddr) -> Want {
+        match (self.is_empty(), addr) {
+            (false, _) => self,
+            (true, SocketAddr::V4(_)) => Want::V4,
+            (true, SocketAddr::V6(_)) => Want::V6,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        !self.v4 && !self.v6
+    }
+
+    /// Whether IPv4 nodes are wanted, sent in the `nodes` field.
+    #[must_use]
+    pub fn v4(&self) -> bool {
+        self.v4
+    }
+
+    /// Whether IPv6 nodes are wanted, sent in the `nodes6` field.
+    #[must_use]
+    pub fn v6(&self) -> bool {
+        self.v6
+    }
+
+    /// Read the `want` field from the request arguments; it is optional, and unknown families are ignored.
+    fn from_parts<B>(validate: &RequestValidate<'_>, rqst_root: &dyn BDictAccess<B::BKey, B>) -> Want
+    where
+        B: BRefAccess,
+    {
+        let Ok(families) = validate.lookup_and_convert_list(rqst_root, WANT_KEY) else {
+            return Want::default();
+        };
+
+        let mut want = Want::default();
+        for family in (0..families.len()).filter_map(|index| families.get(index).and_then(BRefAccess::bytes)) {
+            if family == WANT_V4_KEY.as_bytes() {
+                want.v4 = true;
+            } else if family == WANT_V6_KEY.as_bytes() {
+                want.v6 = true;
+            }
+        }
+
+        want
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FindNodeRequest<'a> {
     trans_id: &'a [u8],
     node_id: NodeId,
     target_id: NodeId,
+    want: Want,
 }
 
 impl<'a> FindNodeRequest<'a> {
@@ -22,9 +108,18 @@ impl<'a> FindNodeRequest<'a> {
             trans_id,
             node_id,
             target_id,
+            want: Want::default(),
         }
     }
 
+    /// Ask for nodes of the given address families, instead of those of the family we send the request from.
+    #[must_use]
+    pub fn with_want(mut self, want: Want) -> FindNodeRequest<'a> {
+        self.want = want;
+
+        self
+    }
+
     /// Create a `FindNodeRequest` from parts.
     ///
     /// The `target_key` argument is provided for cases where, due to forward compatibility,
@@ -49,7 +144,9 @@ impl<'a> FindNodeRequest<'a> {
         let target_id_bytes = validate.lookup_and_convert_bytes(rqst_root, target_key)?;
         let target_id = validate.validate_node_id(target_id_bytes)?;
 
-        Ok(FindNodeRequest::new(trans_id, node_id, target_id))
+        let want = Want::from_parts(&validate, rqst_root);
+
+        Ok(FindNodeRequest::new(trans_id, node_id, target_id).with_want(want))
     }
 
     #[must_use]
@@ -67,17 +164,45 @@ impl<'a> FindNodeRequest<'a> {
         self.target_id
     }
 
+    #[must_use]
+    pub fn want(&self) -> Want {
+        self.want
+    }
+
+    /// Returns the encode of this [`FindNodeRequest`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if unable to get the bencoded dictionary.
     #[must_use]
     pub fn encode(&self) -> Vec<u8> {
+        let mut request_args = ben_map! {
+            message::NODE_ID_KEY => ben_bytes!(self.node_id.as_ref()),
+            message::TARGET_ID_KEY => ben_bytes!(self.target_id.as_ref())
+        };
+
+        if !self.want.is_empty() {
+            let mut families = ben_list!();
+            let families_list = families.list_mut().unwrap();
+            if self.want.v4() {
+                families_list.push(ben_bytes!(WANT_V4_KEY));
+            }
+            if self.want.v6() {
+                families_list.push(ben_bytes!(WANT_V6_KEY));
+            }
+
+            request_args
+                .dict_mut()
+                .unwrap()
+                .insert(BCowConvert::convert(WANT_KEY), families);
+        }
+
         (ben_map! {
             //message::CLIENT_TYPE_KEY => ben_bytes!(dht::CLIENT_IDENTIFICATION),
             message::TRANSACTION_ID_KEY => ben_bytes!(self.trans_id),
             message::MESSAGE_TYPE_KEY => ben_bytes!(message::REQUEST_TYPE_KEY),
             message::REQUEST_TYPE_KEY => ben_bytes!(request::FIND_NODE_TYPE_KEY),
-            request::REQUEST_ARGS_KEY => ben_map!{
-                message::NODE_ID_KEY => ben_bytes!(self.node_id.as_ref()),
-                message::TARGET_ID_KEY => ben_bytes!(self.target_id.as_ref())
-            }
+            request::REQUEST_ARGS_KEY => request_args
         })
         .encode()
     }
@@ -89,6 +214,7 @@ pub struct FindNodeResponse<'a> {
     trans_id: &'a [u8],
     node_id: NodeId,
     nodes: CompactNodeInfo<'a>,
+    nodes6: Option<CompactNodeInfoV6<'a>>,
 }
 
 impl<'a> FindNodeResponse<'a> {
@@ -105,9 +231,22 @@ impl<'a> FindNodeResponse<'a> {
             trans_id,
             node_id,
             nodes: compact_nodes,
+            nodes6: None,
         })
     }
 
+    /// Attach IPv6 nodes to the `FindNodeResponse`, sent in the `nodes6` field.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unable to validate the nodes.
+    pub fn with_nodes6(mut self, nodes6: &'a [u8]) -> Result<FindNodeResponse<'a>, DhtError> {
+        let validate = ResponseValidate::new(self.trans_id);
+        self.nodes6 = Some(validate.validate_nodes6(nodes6)?);
+
+        Ok(self)
+    }
+
     /// Create a new `FindNodeResponse` from parts.
     ///
     /// # Errors
@@ -122,9 +261,20 @@ impl<'a> FindNodeResponse<'a> {
         let node_id_bytes = validate.lookup_and_convert_bytes(rsp_root, message::NODE_ID_KEY)?;
         let node_id = validate.validate_node_id(node_id_bytes)?;
 
-        let nodes = validate.lookup_and_convert_bytes(rsp_root, message::NODES_KEY)?;
+        let opt_nodes6 = validate.lookup_and_convert_bytes(rsp_root, message::NODES6_KEY).ok();
+
+        // Nodes only asked for IPv6 nodes may leave out the nodes field
+        let nodes = match (validate.lookup_and_convert_bytes(rsp_root, message::NODES_KEY), opt_nodes6) {
+            (Ok(nodes), _) => nodes,
+            (Err(_), Some(_)) => &[],
+            (Err(err), None) => return Err(err),
+        };
+        let response = FindNodeResponse::new(trans_id, node_id, nodes)?;
 
-        FindNodeResponse::new(trans_id, node_id, nodes)
+        match opt_nodes6 {
+            Some(nodes6) => response.with_nodes6(nodes6),
+            None => Ok(response),
+        }
     }
 
     #[must_use]
@@ -142,17 +292,145 @@ impl<'a> FindNodeResponse<'a> {
         self.nodes
     }
 
+    #[must_use]
+    pub fn nodes6(&self) -> Option<CompactNodeInfoV6<'a>> {
+        self.nodes6
+    }
+
+    /// Returns the encode of this [`FindNodeResponse`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if unable to get the bencoded dictionary.
     #[must_use]
     pub fn encode(&self) -> Vec<u8> {
+        let mut response_args = ben_map! {
+            message::NODE_ID_KEY => ben_bytes!(self.node_id.as_ref()),
+            message::NODES_KEY => ben_bytes!(self.nodes.nodes())
+        };
+
+        if let Some(nodes6) = self.nodes6 {
+            response_args
+                .dict_mut()
+                .unwrap()
+                .insert(BCowConvert::convert(message::NODES6_KEY), ben_bytes!(nodes6.nodes()));
+        }
+
         (ben_map! {
             //message::CLIENT_TYPE_KEY => ben_bytes!(dht::CLIENT_IDENTIFICATION),
             message::TRANSACTION_ID_KEY => ben_bytes!(self.trans_id),
             message::MESSAGE_TYPE_KEY => ben_bytes!(message::RESPONSE_TYPE_KEY),
-            message::RESPONSE_TYPE_KEY => ben_map!{
-                message::NODE_ID_KEY => ben_bytes!(self.node_id.as_ref()),
-                message::NODES_KEY => ben_bytes!(self.nodes.nodes())
-            }
+            message::RESPONSE_TYPE_KEY => response_args
         })
         .encode()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bencode::{BConvert, BDecodeOpt, BencodeRef};
+    use util::bt::NodeId;
+
+    use super::{FindNodeRequest, FindNodeResponse, Want};
+    use crate::message;
+    use crate::message::compact_info::CompactNodes;
+    use crate::message::request::RequestType;
+    use crate::message::response::{ExpectedResponse, ResponseValidate};
+    use crate::message::MessageType;
+
+    #[test]
+    fn positive_find_node_request_want_round_trip() {
+        let node_id: NodeId = [5u8; 20].into();
+        let target_id: NodeId = [6u8; 20].into();
+
+        let encoded = FindNodeRequest::new(b"aa", node_id, target_id).with_want(Want::BOTH).encode();
+        let bencode = BencodeRef::decode(&encoded, BDecodeOpt::default()).unwrap();
+
+        let Ok(MessageType::Request(RequestType::FindNode(request))) =
+            MessageType::<BencodeRef<'_>>::new(&bencode, |_| ExpectedResponse::None)
+        else {
+            panic!("bip_dht: Expected a find node request...");
+        };
+
+        assert_eq!(Want::BOTH, request.want());
+        assert_eq!(Want::BOTH, request.want().or_family_of("1.2.3.4:6881".parse().unwrap()));
+    }
+
+    #[test]
+    fn positive_find_node_request_ignores_unknown_want() {
+        let bytes =
+            b"d1:ad2:id20:aaaaaaaaaaaaaaaaaaaa6:target20:bbbbbbbbbbbbbbbbbbbb4:wantl2:n92:n6ee1:q9:find_node1:t2:aa1:y1:qe"; // cspell:disable-line
+        let bencode = BencodeRef::decode(bytes, BDecodeOpt::default()).unwrap();
+
+        let Ok(MessageType::Request(RequestType::FindNode(request))) =
+            MessageType::<BencodeRef<'_>>::new(&bencode, |_| ExpectedResponse::None)
+        else {
+            panic!("bip_dht: Expected a find node request...");
+        };
+
+        assert_eq!(Want::V6, request.want());
+        assert_eq!(Want::V4, Want::default().or_family_of("1.2.3.4:6881".parse().unwrap()));
+        assert_eq!(Want::BOTH, Want::reachable_from("[::]:6881".parse().unwrap()));
+    }
+
+    #[test]
+    fn positive_find_node_response_nodes6_only() {
+        let bytes = [
+            &b"d1:rd2:id20:aaaaaaaaaaaaaaaaaaaa6:nodes638:"[..], // cspell:disable-line
+            &[2u8; 38][..],
+            &b"e1:t2:aa1:y1:re"[..],
+        ]
+        .concat();
+        let bencode = BencodeRef::decode(&bytes, BDecodeOpt::default()).unwrap();
+        let validate = ResponseValidate::new(b"aa");
+        let root = validate.convert_dict(&bencode, message::ROOT_ID_KEY).unwrap();
+        let rsp_root = validate.lookup_and_convert_dict(root, message::RESPONSE_TYPE_KEY).unwrap();
+
+        let response = FindNodeResponse::from_parts::<BencodeRef<'_>>(rsp_root, b"aa").unwrap();
+
+        assert_eq!(0, response.nodes().iter().count());
+        assert_eq!(1, response.nodes6().unwrap().iter().count());
+    }
+
+    #[test]
+    fn positive_find_node_response_nodes6_round_trip() {
+        let node_id: NodeId = [5u8; 20].into();
+        let nodes = [1u8; 26];
+        let nodes6 = [2u8; 38];
+
+        let encoded = FindNodeResponse::new(b"aa", node_id, &nodes)
+            .unwrap()
+            .with_nodes6(&nodes6)
+            .unwrap()
+            .encode();
+
+        let bencode = BencodeRef::decode(&encoded, BDecodeOpt::default()).unwrap();
+        let validate = ResponseValidate::new(b"aa");
+        let root = validate.convert_dict(&bencode, message::ROOT_ID_KEY).unwrap();
+        let rsp_root = validate.lookup_and_convert_dict(root, message::RESPONSE_TYPE_KEY).unwrap();
+
+        let response = FindNodeResponse::from_parts::<BencodeRef<'_>>(rsp_root, b"aa").unwrap();
+
+        assert_eq!(1, response.nodes().iter().count());
+        assert_eq!(1, response.nodes6().unwrap().iter().count());
+    }
+
+    #[test]
+    fn negative_find_node_response_nodes6_wrong_length() {
+        let node_id: NodeId = [5u8; 20].into();
+
+        let response = FindNodeResponse::new(b"aa", node_id, &[]).unwrap();
+
+        assert!(response.with_nodes6(&[2u8; 26]).is_err());
+    }
+
+    #[test]
+    fn negative_find_node_request_error_path() {
+        let bytes = b"d1:ad2:id20:aaaaaaaaaaaaaaaaaaaae1:q9:find_node1:t2:aa1:y1:qe";
+        let bencode = BencodeRef::decode(bytes, BDecodeOpt::default()).unwrap();
+
+        let err = MessageType::<BencodeRef<'_>>::new(&bencode, |_| ExpectedResponse::None).unwrap_err();
+
+        assert_eq!("Bencode error: Missing Key In Bencode For a.target", err.to_string());
+    }
+}
@@ -33,6 +33,7 @@ const ROOT_ID_KEY: &str = "root";
 // Keys common across message types
 const NODE_ID_KEY: &str = "id";
 const NODES_KEY: &str = "nodes";
+const NODES6_KEY: &str = "nodes6";
 const VALUES_KEY: &str = "values";
 const TARGET_ID_KEY: &str = "target";
 const INFO_HASH_KEY: &str = "info_hash";
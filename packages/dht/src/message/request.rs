@@ -108,19 +108,22 @@ impl<'a> RequestType<'a> {
 
         match rqst_type {
             PING_TYPE_KEY => {
-                let ping_rqst = PingRequest::from_parts(rqst_root, trans_id)?;
+                let ping_rqst = PingRequest::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(REQUEST_ARGS_KEY))?;
                 Ok(RequestType::Ping(ping_rqst))
             }
             FIND_NODE_TYPE_KEY => {
-                let find_node_rqst = FindNodeRequest::from_parts(rqst_root, trans_id, message::TARGET_ID_KEY)?;
+                let find_node_rqst = FindNodeRequest::from_parts(rqst_root, trans_id, message::TARGET_ID_KEY)
+                    .map_err(|err| err.in_key(REQUEST_ARGS_KEY))?;
                 Ok(RequestType::FindNode(find_node_rqst))
             }
             GET_PEERS_TYPE_KEY => {
-                let get_peers_rqst = GetPeersRequest::from_parts(rqst_root, trans_id)?;
+                let get_peers_rqst =
+                    GetPeersRequest::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(REQUEST_ARGS_KEY))?;
                 Ok(RequestType::GetPeers(get_peers_rqst))
             }
             ANNOUNCE_PEER_TYPE_KEY => {
-                let announce_peer_rqst = AnnouncePeerRequest::from_parts(rqst_root, trans_id)?;
+                let announce_peer_rqst =
+                    AnnouncePeerRequest::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(REQUEST_ARGS_KEY))?;
                 Ok(RequestType::AnnouncePeer(announce_peer_rqst))
             }
             // GET_DATA_TYPE_KEY => {
@@ -133,7 +136,8 @@ impl<'a> RequestType<'a> {
             // },
             unknown => {
                 if let Some(target_key) = forward_compatible_find_node(rqst_root) {
-                    let find_node_rqst = FindNodeRequest::from_parts(rqst_root, trans_id, target_key)?;
+                    let find_node_rqst = FindNodeRequest::from_parts(rqst_root, trans_id, target_key)
+                        .map_err(|err| err.in_key(REQUEST_ARGS_KEY))?;
                     Ok(RequestType::FindNode(find_node_rqst))
                 } else {
                     let error_message = ErrorMessage::new(
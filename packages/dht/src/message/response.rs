@@ -4,7 +4,7 @@ use util::bt::NodeId;
 
 use crate::error::DhtError;
 use crate::message::announce_peer::AnnouncePeerResponse;
-use crate::message::compact_info::{CompactNodeInfo, CompactValueInfo};
+use crate::message::compact_info::{CompactNodeInfo, CompactNodeInfoV6, CompactValueInfo};
 use crate::message::find_node::FindNodeResponse;
 use crate::message::get_peers::GetPeersResponse;
 use crate::message::ping::PingResponse;
@@ -55,6 +55,22 @@ impl<'a> ResponseValidate<'a> {
         })
     }
 
+    /// Validate and deserialize bytes into a `CompactNodeInfoV6`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if to generate the `CompactNodeInfoV6`.
+    pub fn validate_nodes6<'b>(&self, nodes: &'b [u8]) -> Result<CompactNodeInfoV6<'b>, DhtError> {
+        CompactNodeInfoV6::new(nodes).map_err(|_| DhtError::InvalidResponse {
+            details: format!(
+                "TID {:?} Found Nodes6 Structure With {} Number Of Bytes Instead \
+                                  Of Correct Multiple",
+                self.trans_id,
+                nodes.len()
+            ),
+        })
+    }
+
     /// Validate and deserialize bytes into a `CompactValueInfo`
     ///
     /// # Errors
@@ -143,19 +159,22 @@ where
 
         match rsp_type {
             ExpectedResponse::Ping => {
-                let ping_rsp = PingResponse::from_parts(rqst_root, trans_id)?;
+                let ping_rsp = PingResponse::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(RESPONSE_ARGS_KEY))?;
                 Ok(ResponseType::Ping(ping_rsp))
             }
             ExpectedResponse::FindNode => {
-                let find_node_rsp = FindNodeResponse::from_parts(rqst_root, trans_id)?;
+                let find_node_rsp =
+                    FindNodeResponse::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(RESPONSE_ARGS_KEY))?;
                 Ok(ResponseType::FindNode(find_node_rsp))
             }
             ExpectedResponse::GetPeers => {
-                let get_peers_rsp = GetPeersResponse::<B>::from_parts(rqst_root, trans_id)?;
+                let get_peers_rsp =
+                    GetPeersResponse::<B>::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(RESPONSE_ARGS_KEY))?;
                 Ok(ResponseType::GetPeers(get_peers_rsp))
             }
             ExpectedResponse::AnnouncePeer => {
-                let announce_peer_rsp = AnnouncePeerResponse::from_parts(rqst_root, trans_id)?;
+                let announce_peer_rsp =
+                    AnnouncePeerResponse::from_parts(rqst_root, trans_id).map_err(|err| err.in_key(RESPONSE_ARGS_KEY))?;
                 Ok(ResponseType::AnnouncePeer(announce_peer_rsp))
             }
             ExpectedResponse::GetData => {
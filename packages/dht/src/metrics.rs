@@ -0,0 +1,77 @@
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::message::error::ErrorCode;
+use crate::storage::StorageStats;
+use crate::worker::QueryKind;
+
+/// Hooks called by a `MainlineDht` as it runs, for exporting metrics to Prometheus, OpenTelemetry, etc.
+///
+/// Every method has an empty default implementation, so implementors only need to override the
+/// metrics they are interested in. Methods are called from within the DHT workers, so they should
+/// return quickly, for example by incrementing an atomic counter.
+#[allow(clippy::module_name_repetitions)]
+pub trait DhtMetrics: Send + Sync {
+    /// A query of the given kind was received from a remote node.
+    fn query_received(&self, _kind: QueryKind) {}
+
+    /// A query of the given kind was sent to a remote node.
+    fn query_sent(&self, _kind: QueryKind) {}
+
+    /// A query of the given kind was dropped, because the rate limit for the remote node was exceeded.
+    fn query_dropped(&self, _kind: QueryKind) {}
+
+    /// A response to one of our queries was received, the given amount of time after the query was sent.
+    fn response_received(&self, _latency: Duration) {}
+
+    /// One of our queries was not responded to before the response timeout.
+    fn query_timed_out(&self) {}
+
+    /// An error message with the given code was received from a remote node.
+    fn error_received(&self, _code: ErrorCode) {}
+
+    /// An error message with the given code was sent to a remote node.
+    fn error_sent(&self, _code: ErrorCode) {}
+
+    /// Number of good and questionable nodes in our routing table, reported whenever the table is refreshed.
+    fn routing_table_size(&self, _nodes: usize) {}
+
+    /// Announces stored on behalf of remote nodes, reported whenever the routing table is refreshed.
+    fn stored_announces(&self, _stats: StorageStats) {}
+}
+
+/// `DhtMetrics` that discards every metric.
+struct NoMetrics;
+
+impl DhtMetrics for NoMetrics {}
+
+/// `DhtMetrics` shared between the workers of a `MainlineDht`.
+#[derive(Clone)]
+pub struct SharedMetrics(Arc<dyn DhtMetrics>);
+
+impl SharedMetrics {
+    pub fn new(metrics: Arc<dyn DhtMetrics>) -> SharedMetrics {
+        SharedMetrics(metrics)
+    }
+}
+
+impl Default for SharedMetrics {
+    fn default() -> SharedMetrics {
+        SharedMetrics(Arc::new(NoMetrics))
+    }
+}
+
+impl Deref for SharedMetrics {
+    type Target = dyn DhtMetrics;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl std::fmt::Debug for SharedMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedMetrics").finish_non_exhaustive()
+    }
+}
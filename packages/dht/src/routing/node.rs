@@ -33,11 +33,43 @@ const MAX_REFRESH_REQUESTS: usize = 2;
 #[allow(clippy::module_name_repetitions)]
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd)]
 pub enum NodeStatus {
+    /// Node has failed to respond to multiple requests.
     Bad,
+    /// Node has not been heard from recently.
     Questionable,
+    /// Node has responded to us recently.
     Good,
 }
 
+/// Snapshot of a node in our routing table.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NodeInfo {
+    id: NodeId,
+    addr: SocketAddr,
+    status: NodeStatus,
+}
+
+impl NodeInfo {
+    /// Id of the node.
+    #[must_use]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Address of the node.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Status of the node at the time of the snapshot.
+    #[must_use]
+    pub fn status(&self) -> NodeStatus {
+        self.status
+    }
+}
+
 /// Node participating in the dht.
 pub struct Node {
     id: NodeId,
@@ -145,6 +177,29 @@ impl Node {
         encoded
     }
 
+    /// Encode the node as compact IPv6 node info, as sent in `nodes6` fields.
+    pub fn encode6(&self) -> [u8; 38] {
+        let mut encoded = [0u8; 38];
+
+        encoded[..20].copy_from_slice(self.id.as_ref());
+        match self.addr {
+            SocketAddr::V6(v6) => encoded[20..36].copy_from_slice(&v6.ip().octets()),
+            SocketAddr::V4(_) => panic!("bip_dht: Cannot encode a SocketAddrV4 as IPv6 node info..."),
+        }
+        encoded[36..].copy_from_slice(&self.addr.port().to_be_bytes());
+
+        encoded
+    }
+
+    /// Snapshot of the current state of the node.
+    pub fn info(&self) -> NodeInfo {
+        NodeInfo {
+            id: self.id,
+            addr: self.addr,
+            status: self.status(),
+        }
+    }
+
     /// Current status of the node.
     pub fn status(&self) -> NodeStatus {
         let curr_time = Utc::now();
@@ -6,10 +6,65 @@ use util::bt::NodeId;
 use util::sha::{self, ShaHash, XorRep};
 
 use crate::routing::bucket::{self, Bucket};
-use crate::routing::node::{Node, NodeStatus};
+use crate::routing::node::{Node, NodeInfo, NodeStatus};
 
 pub const MAX_BUCKETS: usize = sha::SHA_HASH_LEN * 8;
 
+/// Configures how the routing table of a `MainlineDht` splits its buckets.
+///
+/// Only the bucket whose range contains our own node id is ever split, so the table holds many
+/// nodes close to us and progressively fewer nodes further away. Nodes are kept in the full
+/// buckets further away only if existing nodes go bad.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RoutingConfig {
+    max_depth: usize,
+    deep_split: bool,
+}
+
+impl RoutingConfig {
+    /// Sets the maximum number of buckets in the routing table, which is one more than the number of
+    /// leading bits of our node id that the buckets distinguish between.
+    ///
+    /// Lower depths bound the size of the routing table. Values are clamped to between one and 160.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> RoutingConfig {
+        self.max_depth = max_depth.clamp(1, MAX_BUCKETS);
+        self
+    }
+
+    /// Sets whether adding a node to the full bucket containing our own node id may split that bucket
+    /// repeatedly, until there is room for the node, rather than only once.
+    ///
+    /// Deep splits keep every node close to us, which speeds up lookups for the queries we serve.
+    #[must_use]
+    pub fn with_deep_split(mut self, deep_split: bool) -> RoutingConfig {
+        self.deep_split = deep_split;
+        self
+    }
+
+    /// Gets the maximum number of buckets in the routing table.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Gets whether the bucket containing our own node id may be split repeatedly.
+    #[must_use]
+    pub fn deep_split(&self) -> bool {
+        self.deep_split
+    }
+}
+
+impl Default for RoutingConfig {
+    fn default() -> RoutingConfig {
+        RoutingConfig {
+            max_depth: MAX_BUCKETS,
+            deep_split: true,
+        }
+    }
+}
+
 /// Routing table containing a table of routing nodes as well
 /// as the id of the local node participating in the dht.
 #[allow(clippy::module_name_repetitions)]
@@ -18,6 +73,7 @@ pub struct RoutingTable {
     // of the last bucket in the buckets array.
     buckets: Vec<Bucket>,
     node_id: NodeId,
+    config: RoutingConfig,
 }
 
 impl RoutingTable {
@@ -25,7 +81,18 @@ impl RoutingTable {
     pub fn new(node_id: NodeId) -> RoutingTable {
         let buckets = vec![Bucket::new()];
 
-        RoutingTable { buckets, node_id }
+        RoutingTable {
+            buckets,
+            node_id,
+            config: RoutingConfig::default(),
+        }
+    }
+
+    /// Split buckets according to the given `RoutingConfig`.
+    #[must_use]
+    pub fn with_config(mut self, config: RoutingConfig) -> RoutingTable {
+        self.config = config;
+        self
     }
 
     /// Return the node id of the `RoutingTable`.
@@ -47,6 +114,18 @@ impl RoutingTable {
         Buckets::new(&self.buckets)
     }
 
+    /// Snapshot of every good or questionable node in the `RoutingTable`.
+    pub fn node_infos(&self) -> Vec<NodeInfo> {
+        self.buckets()
+            .filter_map(|contents| match contents {
+                BucketContents::Empty => None,
+                BucketContents::Sorted(bucket) | BucketContents::Assorted(bucket) => Some(bucket),
+            })
+            .flat_map(Bucket::pingable_nodes)
+            .map(Node::info)
+            .collect()
+    }
+
     /// Find an instance of the target node in the `RoutingTable`, if it exists.
     pub fn find_node(&self, node: &Node) -> Option<&Node> {
         let bucket_index = leading_bit_count(self.node_id, node.id());
@@ -81,21 +160,18 @@ impl RoutingTable {
 
         // Should not add a node that has the same id as us
         if num_same_bits != MAX_BUCKETS {
-            self.bucket_node(node, num_same_bits);
+            self.bucket_node(node, num_same_bits, true);
         }
     }
 
     /// Recursively tries to place the node into some bucket.
-    fn bucket_node(&mut self, node: &Node, num_same_bits: usize) {
+    fn bucket_node(&mut self, node: &Node, num_same_bits: usize, can_split: bool) {
         let bucket_index = bucket_placement(num_same_bits, self.buckets.len());
 
-        // Try to place in correct bucket
-        if !self.buckets[bucket_index].add_node(node.clone()) {
-            // Bucket was full, try to split it
-            if self.split_bucket(bucket_index) {
-                // Bucket split successfully, try to add again
-                self.bucket_node(node, num_same_bits);
-            }
+        // Try to place in correct bucket, if the bucket was full try to split it
+        if !self.buckets[bucket_index].add_node(node.clone()) && can_split && self.split_bucket(bucket_index) {
+            // Bucket split successfully, try to add again
+            self.bucket_node(node, num_same_bits, self.config.deep_split);
         }
     }
 
@@ -103,7 +179,7 @@ impl RoutingTable {
     ///
     /// Returns false if the split cannot be performed.
     fn split_bucket(&mut self, bucket_index: usize) -> bool {
-        if !can_split_bucket(self.buckets.len(), bucket_index) {
+        if !can_split_bucket(self.buckets.len(), bucket_index, self.config.max_depth) {
             return false;
         }
 
@@ -127,8 +203,10 @@ impl RoutingTable {
 }
 
 /// Returns true if the bucket can be split.
-fn can_split_bucket(num_buckets: usize, bucket_index: usize) -> bool {
-    bucket_index == num_buckets - 1 && bucket_index != MAX_BUCKETS - 1
+///
+/// Only the last bucket, which contains our own node id, is split.
+fn can_split_bucket(num_buckets: usize, bucket_index: usize, max_depth: usize) -> bool {
+    bucket_index == num_buckets - 1 && num_buckets < max_depth.min(MAX_BUCKETS)
 }
 
 /// Generates a random `NodeId`.
@@ -440,8 +518,8 @@ mod tests {
     use util::test as bip_test;
 
     use crate::routing::bucket;
-    use crate::routing::node::Node;
-    use crate::routing::table::{self, BucketContents, RoutingTable};
+    use crate::routing::node::{Node, NodeStatus};
+    use crate::routing::table::{self, BucketContents, RoutingConfig, RoutingTable};
 
     // TODO: Move into bip_util crate
     fn flip_id_bit_at_index(node_id: NodeId, index: usize) -> NodeId {
@@ -609,6 +687,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn positive_node_infos_excludes_placeholders() {
+        let table_id = [1u8; bt::NODE_ID_LEN];
+        let mut table = RoutingTable::new(table_id.into());
+
+        assert!(table.node_infos().is_empty());
+
+        let node_id = flip_id_bit_at_index(table_id.into(), 0);
+        let node_addr = bip_test::dummy_socket_addr_v4();
+        table.add_node(&Node::as_good(node_id, node_addr));
+
+        let infos = table.node_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].id(), node_id);
+        assert_eq!(infos[0].addr(), node_addr);
+        assert_eq!(infos[0].status(), NodeStatus::Good);
+    }
+
     #[test]
     fn negative_node_id_equal_table_id() {
         let table_id = [1u8; bt::NODE_ID_LEN];
@@ -621,4 +717,48 @@ mod tests {
 
         assert_eq!(table.closest_nodes(table_id.into()).count(), 0);
     }
+
+    #[test]
+    fn positive_max_depth_limits_buckets() {
+        let table_id = [1u8; bt::NODE_ID_LEN];
+        let mut table = RoutingTable::new(table_id.into()).with_config(RoutingConfig::default().with_max_depth(3));
+
+        #[allow(clippy::cast_possible_truncation)]
+        let block_addrs = bip_test::dummy_block_socket_addrs(bucket::MAX_BUCKET_SIZE as u16);
+        for bit_flip_index in 0..table::MAX_BUCKETS {
+            for &addr in &block_addrs {
+                let bucket_node_id = flip_id_bit_at_index(table_id.into(), bit_flip_index);
+
+                table.add_node(&Node::as_good(bucket_node_id, addr));
+            }
+        }
+
+        assert_eq!(table.buckets.len(), 3);
+        // Home bucket holds the closest nodes it had room for
+        assert_eq!(table.buckets[2].pingable_nodes().count(), bucket::MAX_BUCKET_SIZE);
+    }
+
+    #[test]
+    fn positive_deep_split_home_bucket() {
+        let table_id = [1u8; bt::NODE_ID_LEN];
+        let node_id = flip_id_bit_at_index(table_id.into(), 5);
+
+        #[allow(clippy::cast_possible_truncation)]
+        let block_addrs = bip_test::dummy_block_socket_addrs((bucket::MAX_BUCKET_SIZE + 1) as u16);
+
+        let mut deep_table = RoutingTable::new(table_id.into());
+        let mut shallow_table = RoutingTable::new(table_id.into()).with_config(RoutingConfig::default().with_deep_split(false));
+        for &addr in &block_addrs {
+            deep_table.add_node(&Node::as_good(node_id, addr));
+            shallow_table.add_node(&Node::as_good(node_id, addr));
+        }
+
+        // Split until the nodes sharing five bits with us have their own bucket
+        assert_eq!(deep_table.buckets.len(), 7);
+        assert_eq!(deep_table.buckets[5].pingable_nodes().count(), bucket::MAX_BUCKET_SIZE);
+
+        // Split only once for the overflowing node
+        assert_eq!(shallow_table.buckets.len(), 2);
+        assert_eq!(shallow_table.buckets[1].pingable_nodes().count(), bucket::MAX_BUCKET_SIZE);
+    }
 }
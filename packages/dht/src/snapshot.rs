@@ -0,0 +1,132 @@
+use std::net::SocketAddr;
+
+use bencode::{ben_bytes, ben_map, BDecodeOpt, BRefAccess, BencodeRef};
+use util::bt::NodeId;
+
+use crate::error::SnapshotError;
+use crate::message::compact_info::{CompactNodeInfo, CompactNodeInfoV6, CompactNodes};
+use crate::routing::node::Node;
+
+const NODES_KEY: &str = "nodes";
+const NODES6_KEY: &str = "nodes6";
+
+/// Sample of known good nodes, exchanged out-of-band between cooperating DHTs.
+///
+/// Exported with `MainlineDht::export_nodes` and imported with `MainlineDht::import_nodes`, so that a
+/// new node in a fleet can warm up from the routing tables of its trusted peers instead of from scratch.
+/// Encoded as a dictionary holding the compact node info of the nodes, as in `find_node` responses.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct RoutingSnapshot {
+    nodes: Vec<(NodeId, SocketAddr)>,
+}
+
+impl RoutingSnapshot {
+    /// Create a `RoutingSnapshot` of the given nodes.
+    pub fn new<I>(nodes: I) -> RoutingSnapshot
+    where
+        I: IntoIterator<Item = (NodeId, SocketAddr)>,
+    {
+        RoutingSnapshot {
+            nodes: nodes.into_iter().collect(),
+        }
+    }
+
+    /// Read a `RoutingSnapshot` from the bytes written by `RoutingSnapshot::to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the bytes are not bencoded, or do not hold valid compact node info.
+    pub fn from_bytes(bytes: &[u8]) -> Result<RoutingSnapshot, SnapshotError> {
+        let bencode = BencodeRef::decode(bytes, BDecodeOpt::default())?;
+        let dict = bencode.dict().ok_or(SnapshotError::InvalidNodes)?;
+
+        let nodes_bytes = |key: &str| match dict.lookup(key.as_bytes()) {
+            Some(value) => value.bytes().map(Some).ok_or(SnapshotError::InvalidNodes),
+            None => Ok(None),
+        };
+
+        let mut nodes = Vec::new();
+        if let Some(bytes) = nodes_bytes(NODES_KEY)? {
+            let compact = CompactNodeInfo::new(bytes).map_err(|_| SnapshotError::InvalidNodes)?;
+            nodes.extend(compact.iter().map(|(id, addr)| (id, addr.into())));
+        }
+        if let Some(bytes) = nodes_bytes(NODES6_KEY)? {
+            let compact = CompactNodeInfoV6::new(bytes).map_err(|_| SnapshotError::InvalidNodes)?;
+            nodes.extend(compact.iter().map(|(id, addr)| (id, addr.into())));
+        }
+
+        Ok(RoutingSnapshot { nodes })
+    }
+
+    /// Write the `RoutingSnapshot` as bytes, to be read back with `RoutingSnapshot::from_bytes`.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v4_bytes = Vec::new();
+        let mut v6_bytes = Vec::new();
+
+        for &(id, addr) in &self.nodes {
+            let node = Node::as_questionable(id, addr);
+
+            match addr {
+                SocketAddr::V4(_) => v4_bytes.extend_from_slice(&node.encode()),
+                SocketAddr::V6(_) => v6_bytes.extend_from_slice(&node.encode6()),
+            }
+        }
+
+        (ben_map! {
+            NODES_KEY => ben_bytes!(v4_bytes),
+            NODES6_KEY => ben_bytes!(v6_bytes)
+        })
+        .encode()
+    }
+
+    /// Ids and addresses of the nodes in the snapshot.
+    #[must_use]
+    pub fn nodes(&self) -> &[(NodeId, SocketAddr)] {
+        &self.nodes
+    }
+
+    /// Number of nodes in the snapshot.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the snapshot holds no nodes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use util::bt::{self, NodeId};
+
+    use crate::error::SnapshotError;
+    use crate::snapshot::RoutingSnapshot;
+
+    #[test]
+    fn positive_snapshot_round_trip_both_families() {
+        let v4_addr: SocketAddr = "192.168.0.1:6881".parse().unwrap();
+        let v6_addr: SocketAddr = "[2001:db8::1]:6882".parse().unwrap();
+        let snapshot = RoutingSnapshot::new([
+            (NodeId::from([1u8; bt::NODE_ID_LEN]), v4_addr),
+            (NodeId::from([2u8; bt::NODE_ID_LEN]), v6_addr),
+        ]);
+
+        let read = RoutingSnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+
+        assert_eq!(snapshot, read);
+    }
+
+    #[test]
+    fn negative_snapshot_truncated_nodes() {
+        let bytes = b"d5:nodes3:abc6:nodes60:e";
+
+        assert!(matches!(RoutingSnapshot::from_bytes(bytes), Err(SnapshotError::InvalidNodes)));
+    }
+}
@@ -0,0 +1,104 @@
+use std::iter::Sum;
+use std::ops::Add;
+
+use crate::storage::StorageStats;
+
+/// Snapshot of the state of one or more `MainlineDht` instances.
+///
+/// Statistics for several instances running in the same process can be combined
+/// with `+` or by summing an iterator of `DhtStats`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct DhtStats {
+    instances: usize,
+    nodes: usize,
+    info_hashes: usize,
+    peers: usize,
+}
+
+impl DhtStats {
+    pub(crate) fn new(nodes: usize, storage: StorageStats) -> DhtStats {
+        DhtStats {
+            instances: 1,
+            nodes,
+            info_hashes: storage.info_hashes(),
+            peers: storage.peers(),
+        }
+    }
+
+    /// Number of DHT instances these statistics were gathered from.
+    #[must_use]
+    pub fn instances(&self) -> usize {
+        self.instances
+    }
+
+    /// Number of good and questionable nodes across all routing tables.
+    #[must_use]
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Number of `InfoHash`(s) with at least one stored peer, counted per instance.
+    #[must_use]
+    pub fn info_hashes(&self) -> usize {
+        self.info_hashes
+    }
+
+    /// Number of peers stored on behalf of remote nodes, counted per instance.
+    #[must_use]
+    pub fn peers(&self) -> usize {
+        self.peers
+    }
+}
+
+impl Add for DhtStats {
+    type Output = DhtStats;
+
+    fn add(self, rhs: DhtStats) -> DhtStats {
+        DhtStats {
+            instances: self.instances + rhs.instances,
+            nodes: self.nodes + rhs.nodes,
+            info_hashes: self.info_hashes + rhs.info_hashes,
+            peers: self.peers + rhs.peers,
+        }
+    }
+}
+
+impl Sum for DhtStats {
+    fn sum<I>(iter: I) -> DhtStats
+    where
+        I: Iterator<Item = DhtStats>,
+    {
+        iter.fold(DhtStats::default(), Add::add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::DhtStats;
+    use crate::storage::StorageStats;
+
+    #[test]
+    fn positive_sum_empty_is_default() {
+        let combined: DhtStats = std::iter::empty().sum();
+
+        assert_eq!(combined, DhtStats::default());
+        assert_eq!(combined.instances(), 0);
+    }
+
+    #[test]
+    fn positive_sum_combines_instances() {
+        let combined: DhtStats = vec![
+            DhtStats::new(8, StorageStats::default()),
+            DhtStats::new(5, StorageStats::default()),
+            DhtStats::new(0, StorageStats::default()),
+        ]
+        .into_iter()
+        .sum();
+
+        assert_eq!(combined.instances(), 3);
+        assert_eq!(combined.nodes(), 13);
+        assert_eq!(combined.info_hashes(), 0);
+        assert_eq!(combined.peers(), 0);
+    }
+}
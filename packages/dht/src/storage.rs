@@ -1,23 +1,245 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom as _;
 use util::bt::InfoHash;
 
-const MAX_ITEMS_STORED: usize = 500;
+const DEFAULT_TTL_SECS: u64 = 30 * 60;
+const DEFAULT_MAX_PEERS: usize = 2000;
+const DEFAULT_MAX_PEERS_PER_INFO_HASH: usize = 200;
+const DEFAULT_MAX_VALUES_PER_RESPONSE: usize = 50;
 
-/// Manages storage and expiration of contact information for a number of `InfoHash`(s).
+/// Configures how peers announced to us by remote nodes are stored and served.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct StorageConfig {
+    ttl: Duration,
+    max_peers: usize,
+    max_peers_per_info_hash: usize,
+    max_values_per_response: usize,
+}
+
+impl StorageConfig {
+    /// Sets how long an announced peer is kept before it expires, unless it is announced again.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> StorageConfig {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of peers stored across all `InfoHash`(s).
+    ///
+    /// Announces received while at this limit are rejected by a `MemoryStorage`, and evict the
+    /// least recently announced peer of an `LruStorage`.
+    #[must_use]
+    pub fn with_max_peers(mut self, max_peers: usize) -> StorageConfig {
+        self.max_peers = max_peers;
+        self
+    }
+
+    /// Sets the maximum number of peers stored for a single `InfoHash`.
+    ///
+    /// Announces for an `InfoHash` at this limit replace its oldest peer. A value of zero is treated as one.
+    #[must_use]
+    pub fn with_max_peers_per_info_hash(mut self, max_peers: usize) -> StorageConfig {
+        self.max_peers_per_info_hash = max_peers.max(1);
+        self
+    }
+
+    /// Sets the maximum number of peers, randomly sampled, returned in a single `get_peers` response.
+    #[must_use]
+    pub fn with_max_values_per_response(mut self, max_values: usize) -> StorageConfig {
+        self.max_values_per_response = max_values;
+        self
+    }
+
+    /// Gets how long an announced peer is kept.
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Gets the maximum number of peers stored across all `InfoHash`(s).
+    #[must_use]
+    pub fn max_peers(&self) -> usize {
+        self.max_peers
+    }
+
+    /// Gets the maximum number of peers stored for a single `InfoHash`.
+    #[must_use]
+    pub fn max_peers_per_info_hash(&self) -> usize {
+        self.max_peers_per_info_hash
+    }
+
+    /// Gets the maximum number of peers returned in a single `get_peers` response.
+    #[must_use]
+    pub fn max_values_per_response(&self) -> usize {
+        self.max_values_per_response
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> StorageConfig {
+        StorageConfig {
+            ttl: Duration::from_secs(DEFAULT_TTL_SECS),
+            max_peers: DEFAULT_MAX_PEERS,
+            max_peers_per_info_hash: DEFAULT_MAX_PEERS_PER_INFO_HASH,
+            max_values_per_response: DEFAULT_MAX_VALUES_PER_RESPONSE,
+        }
+    }
+}
+
+/// Snapshot of the peers currently stored on behalf of remote nodes.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct StorageStats {
+    info_hashes: usize,
+    peers: usize,
+}
+
+impl StorageStats {
+    /// Create a new `StorageStats`, for reporting the contents of a custom `PeerStorage`.
+    #[must_use]
+    pub fn new(info_hashes: usize, peers: usize) -> StorageStats {
+        StorageStats { info_hashes, peers }
+    }
+
+    /// Number of `InfoHash`(s) with at least one stored peer.
+    #[must_use]
+    pub fn info_hashes(&self) -> usize {
+        self.info_hashes
+    }
+
+    /// Number of peers stored across all `InfoHash`(s).
+    #[must_use]
+    pub fn peers(&self) -> usize {
+        self.peers
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Backend storing the peers announced to us by remote nodes, so they can be served in `get_peers` responses.
+///
+/// Implementations must honor the same expiry semantics as the default in-memory storage: a peer expires
+/// once the configured ttl has elapsed since it was last announced, announcing an already stored peer renews
+/// it, and expired peers are never sampled or counted in the stats. Methods are called from within the DHT
+/// workers, so backends talking to an external store should avoid blocking for long.
+#[allow(clippy::module_name_repetitions)]
+pub trait PeerStorage: Send + Sync {
+    /// Store, or renew, the given peer for the `InfoHash`.
+    ///
+    /// Returns false if the peer could not be stored, in which case the remote node is sent an error.
+    fn add_peer(&self, info_hash: InfoHash, address: SocketAddr) -> bool;
+
+    /// Returns up to `count` randomly chosen peers for the given `InfoHash`.
+    fn sample_peers(&self, info_hash: &InfoHash, count: usize) -> Vec<SocketAddr>;
+
+    /// Returns the number of `InfoHash`(s) and peers currently stored.
+    fn stats(&self) -> StorageStats;
+}
+
+/// `PeerStorage` keeping peers in memory, rejecting new peers once the storage is full.
+///
+/// This is the storage used when no other is provided to the `DhtBuilder`.
+#[allow(clippy::module_name_repetitions)]
+pub struct MemoryStorage(Mutex<AnnounceStorage>);
+
+impl MemoryStorage {
+    /// Create a new `MemoryStorage` with the given configuration.
+    #[must_use]
+    pub fn new(config: StorageConfig) -> MemoryStorage {
+        MemoryStorage(Mutex::new(AnnounceStorage::new(config, false)))
+    }
+}
+
+impl PeerStorage for MemoryStorage {
+    fn add_peer(&self, info_hash: InfoHash, address: SocketAddr) -> bool {
+        self.0.lock().unwrap().add_item(info_hash, address)
+    }
+
+    fn sample_peers(&self, info_hash: &InfoHash, count: usize) -> Vec<SocketAddr> {
+        self.0.lock().unwrap().sample_items(info_hash, count)
+    }
+
+    fn stats(&self) -> StorageStats {
+        self.0.lock().unwrap().stats()
+    }
+}
+
+/// `PeerStorage` keeping peers in memory, evicting the least recently announced peer once the storage is full.
+///
+/// Unlike `MemoryStorage`, announces are never rejected, so fresh peers always displace stale ones.
 #[allow(clippy::module_name_repetitions)]
-pub struct AnnounceStorage {
+pub struct LruStorage(Mutex<AnnounceStorage>);
+
+impl LruStorage {
+    /// Create a new `LruStorage` with the given configuration.
+    #[must_use]
+    pub fn new(config: StorageConfig) -> LruStorage {
+        LruStorage(Mutex::new(AnnounceStorage::new(config, true)))
+    }
+}
+
+impl PeerStorage for LruStorage {
+    fn add_peer(&self, info_hash: InfoHash, address: SocketAddr) -> bool {
+        self.0.lock().unwrap().add_item(info_hash, address)
+    }
+
+    fn sample_peers(&self, info_hash: &InfoHash, count: usize) -> Vec<SocketAddr> {
+        self.0.lock().unwrap().sample_items(info_hash, count)
+    }
+
+    fn stats(&self) -> StorageStats {
+        self.0.lock().unwrap().stats()
+    }
+}
+
+/// `PeerStorage` shared between the workers of a `MainlineDht`.
+#[derive(Clone)]
+pub struct SharedStorage(Arc<dyn PeerStorage>);
+
+impl SharedStorage {
+    pub fn new(storage: Arc<dyn PeerStorage>) -> SharedStorage {
+        SharedStorage(storage)
+    }
+}
+
+impl Deref for SharedStorage {
+    type Target = dyn PeerStorage;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl std::fmt::Debug for SharedStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedStorage").finish_non_exhaustive()
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Manages storage and expiration of contact information for a number of `InfoHash`(s).
+struct AnnounceStorage {
+    config: StorageConfig,
+    evict_when_full: bool,
     storage: HashMap<InfoHash, Vec<AnnounceItem>>,
     expires: Vec<ItemExpiration>,
 }
 
 impl AnnounceStorage {
-    /// Create a new `AnnounceStorage` object.
-    pub fn new() -> AnnounceStorage {
+    /// Create a new `AnnounceStorage` object, evicting the least recently announced contact when full if requested.
+    fn new(config: StorageConfig, evict_when_full: bool) -> AnnounceStorage {
         AnnounceStorage {
+            config,
+            evict_when_full,
             storage: HashMap::new(),
             expires: Vec::new(),
         }
@@ -31,7 +253,7 @@ impl AnnounceStorage {
     fn add(&mut self, info_hash: InfoHash, address: SocketAddr, curr_time: DateTime<Utc>) -> bool {
         // Clear out any old contacts that we have stored
         self.remove_expired_items(curr_time);
-        let item = AnnounceItem::new(info_hash, address);
+        let item = AnnounceItem::new(info_hash, address, curr_time);
         let item_expiration = item.expiration();
 
         // Check if we already have the item and want to update it's expiration
@@ -51,25 +273,30 @@ impl AnnounceStorage {
         }
     }
 
-    /// Invoke the closure once for each contact for the given `InfoHash`.
-    pub fn find_items<F>(&mut self, info_hash: &InfoHash, item_func: F)
-    where
-        F: FnMut(SocketAddr),
-    {
-        self.find(info_hash, item_func, Utc::now());
+    /// Returns up to `count` randomly chosen contacts for the given `InfoHash`.
+    pub fn sample_items(&mut self, info_hash: &InfoHash, count: usize) -> Vec<SocketAddr> {
+        self.sample(info_hash, count, Utc::now())
     }
 
-    fn find<F>(&mut self, info_hash: &InfoHash, mut item_func: F, curr_time: DateTime<Utc>)
-    where
-        F: FnMut(SocketAddr),
-    {
+    fn sample(&mut self, info_hash: &InfoHash, count: usize, curr_time: DateTime<Utc>) -> Vec<SocketAddr> {
         // Clear out any old contacts that we have stored
         self.remove_expired_items(curr_time);
 
-        if let Some(items) = self.storage.get(info_hash) {
-            for item in items {
-                item_func(item.address());
-            }
+        self.storage.get(info_hash).map_or_else(Vec::new, |items| {
+            items
+                .choose_multiple(&mut rand::thread_rng(), count)
+                .map(AnnounceItem::address)
+                .collect()
+        })
+    }
+
+    /// Returns the number of `InfoHash`(s) and contacts currently stored.
+    pub fn stats(&mut self) -> StorageStats {
+        self.remove_expired_items(Utc::now());
+
+        StorageStats {
+            info_hashes: self.storage.len(),
+            peers: self.expires.len(),
         }
     }
 
@@ -79,34 +306,66 @@ impl AnnounceStorage {
     fn insert_contact(&mut self, item: AnnounceItem) -> Option<bool> {
         let item_info_hash = item.info_hash();
 
-        // Check if the contact is already in our list
-        let already_in_list = if let Some(items) = self.storage.get_mut(&item_info_hash) {
-            items.iter().any(|a| a == &item)
+        // Check if the contact is already in our list, and how many contacts the info hash has
+        let (already_in_list, num_items) = if let Some(items) = self.storage.get_mut(&item_info_hash) {
+            (items.iter().any(|a| a == &item), items.len())
         } else {
-            false
+            (false, 0)
         };
 
-        // Check if we need to insert it into the list and if we have room
-        match (already_in_list, self.expires.len() < MAX_ITEMS_STORED) {
-            (false, true) => {
-                // Place it into the appropriate list
-                match self.storage.entry(item_info_hash) {
-                    Entry::Occupied(mut occ) => occ.get_mut().push(item),
-                    Entry::Vacant(vac) => {
-                        vac.insert(vec![item]);
-                    }
-                };
-
-                Some(false)
+        if already_in_list {
+            return Some(true);
+        }
+
+        // Make room by evicting the oldest contact for a full info hash, this way
+        // a single popular info hash can never starve the others of storage.
+        if num_items >= self.config.max_peers_per_info_hash() {
+            self.remove_oldest_item(item_info_hash);
+        } else if self.expires.len() >= self.config.max_peers() {
+            if !self.evict_when_full || self.expires.is_empty() {
+                return None;
+            }
+
+            // Expirations are kept in announce order, so the head is the least recently announced
+            let oldest_info_hash = self.expires[0].info_hash();
+            self.remove_oldest_item(oldest_info_hash);
+        }
+
+        // Place it into the appropriate list
+        match self.storage.entry(item_info_hash) {
+            Entry::Occupied(mut occ) => occ.get_mut().push(item),
+            Entry::Vacant(vac) => {
+                vac.insert(vec![item]);
             }
-            (false, false) => None,
-            (true, false | true) => Some(true),
+        }
+
+        Some(false)
+    }
+
+    /// Removes the contact for the given `InfoHash` which is closest to expiring.
+    fn remove_oldest_item(&mut self, info_hash: InfoHash) {
+        let Some(index) = self.expires.iter().position(|i| i.info_hash() == info_hash) else {
+            return;
+        };
+        let item_expiration = self.expires.remove(index);
+
+        let remove_info_hash = if let Some(items) = self.storage.get_mut(&info_hash) {
+            items.retain(|a| a.expiration() != item_expiration);
+
+            items.is_empty()
+        } else {
+            false
+        };
+
+        if remove_info_hash {
+            self.storage.remove(&info_hash);
         }
     }
 
     /// Prunes all expired items from the internal list.
     fn remove_expired_items(&mut self, curr_time: DateTime<Utc>) {
-        let num_expired_items = self.expires.iter().take_while(|i| i.is_expired(curr_time)).count();
+        let ttl = self.config.ttl();
+        let num_expired_items = self.expires.iter().take_while(|i| i.is_expired(curr_time, ttl)).count();
 
         // Remove the numbers of expired elements from the head of the list
         for item_expiration in self.expires.drain(0..num_expired_items) {
@@ -138,9 +397,9 @@ struct AnnounceItem {
 }
 
 impl AnnounceItem {
-    pub fn new(info_hash: InfoHash, address: SocketAddr) -> AnnounceItem {
+    pub fn new(info_hash: InfoHash, address: SocketAddr, inserted: DateTime<Utc>) -> AnnounceItem {
         AnnounceItem {
-            expiration: ItemExpiration::new(info_hash, address),
+            expiration: ItemExpiration::new(info_hash, address, inserted),
         }
     }
 
@@ -159,8 +418,6 @@ impl AnnounceItem {
 
 // ----------------------------------------------------------------------------//
 
-const EXPIRATION_TIME_HOURS: i64 = 24;
-
 #[derive(Debug, Clone)]
 struct ItemExpiration {
     address: SocketAddr,
@@ -169,16 +426,16 @@ struct ItemExpiration {
 }
 
 impl ItemExpiration {
-    pub fn new(info_hash: InfoHash, address: SocketAddr) -> ItemExpiration {
+    pub fn new(info_hash: InfoHash, address: SocketAddr, inserted: DateTime<Utc>) -> ItemExpiration {
         ItemExpiration {
             address,
-            inserted: Utc::now(),
+            inserted,
             info_hash,
         }
     }
 
-    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
-        now - self.inserted >= Duration::hours(EXPIRATION_TIME_HOURS)
+    pub fn is_expired(&self, now: DateTime<Utc>, ttl: Duration) -> bool {
+        (now - self.inserted).to_std().is_ok_and(|age| age >= ttl)
     }
 
     pub fn info_hash(&self) -> InfoHash {
@@ -203,18 +460,30 @@ mod tests {
     use chrono::Duration;
     use util::{bt, test as bip_test};
 
-    use crate::storage::{self, AnnounceStorage};
+    use crate::storage::{AnnounceStorage, LruStorage, MemoryStorage, PeerStorage, StorageConfig, StorageStats};
+
+    const MAX_ITEMS_STORED: usize = 500;
+
+    /// Config where a single info hash is able to fill up the whole storage.
+    fn test_config() -> StorageConfig {
+        StorageConfig::default()
+            .with_max_peers(MAX_ITEMS_STORED)
+            .with_max_peers_per_info_hash(MAX_ITEMS_STORED)
+    }
+
+    fn expiration_time() -> Duration {
+        Duration::from_std(test_config().ttl()).unwrap()
+    }
 
     #[test]
     fn positive_add_and_retrieve_contact() {
-        let mut announce_store = AnnounceStorage::new();
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
         let info_hash = [0u8; bt::INFO_HASH_LEN].into();
         let sock_addr = bip_test::dummy_socket_addr_v4();
 
         assert!(announce_store.add_item(info_hash, sock_addr));
 
-        let mut items = Vec::new();
-        announce_store.find_items(&info_hash, |a| items.push(a));
+        let items = announce_store.sample_items(&info_hash, usize::MAX);
         assert_eq!(items.len(), 1);
 
         assert_eq!(items[0], sock_addr);
@@ -222,18 +491,17 @@ mod tests {
 
     #[test]
     fn positive_add_and_retrieve_contacts() {
-        let mut announce_store = AnnounceStorage::new();
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
         let info_hash = [0u8; bt::INFO_HASH_LEN].into();
         #[allow(clippy::cast_possible_truncation)]
-        let sock_addrs = bip_test::dummy_block_socket_addrs(storage::MAX_ITEMS_STORED as u16);
+        let sock_addrs = bip_test::dummy_block_socket_addrs(MAX_ITEMS_STORED as u16);
 
         for sock_addr in &sock_addrs {
             assert!(announce_store.add_item(info_hash, *sock_addr));
         }
 
-        let mut items = Vec::new();
-        announce_store.find_items(&info_hash, |a| items.push(a));
-        assert_eq!(items.len(), storage::MAX_ITEMS_STORED);
+        let items = announce_store.sample_items(&info_hash, usize::MAX);
+        assert_eq!(items.len(), MAX_ITEMS_STORED);
 
         for item in &items {
             assert!(sock_addrs.iter().any(|s| s == item));
@@ -242,12 +510,12 @@ mod tests {
 
     #[test]
     fn positive_renew_contacts() {
-        let mut announce_store = AnnounceStorage::new();
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
         let info_hash = [0u8; bt::INFO_HASH_LEN].into();
         #[allow(clippy::cast_possible_truncation)]
-        let sock_addrs = bip_test::dummy_block_socket_addrs((storage::MAX_ITEMS_STORED + 1) as u16);
+        let sock_addrs = bip_test::dummy_block_socket_addrs((MAX_ITEMS_STORED + 1) as u16);
 
-        for sock_addr in sock_addrs.iter().take(storage::MAX_ITEMS_STORED) {
+        for sock_addr in sock_addrs.iter().take(MAX_ITEMS_STORED) {
             assert!(announce_store.add_item(info_hash, *sock_addr));
         }
 
@@ -256,26 +524,24 @@ mod tests {
 
         // Returns false because it wasn't added
         assert!(!announce_store.add_item(other_info_hash, sock_addrs[sock_addrs.len() - 1]));
-        // Closure not invoked because it wasn't added
-        let mut times_invoked = 0;
-        announce_store.find_items(&other_info_hash, |_| times_invoked += 1);
-        assert_eq!(times_invoked, 0);
+        // Nothing returned because it wasn't added
+        assert!(announce_store.sample_items(&other_info_hash, usize::MAX).is_empty());
 
         // Try to add all of the initial nodes again (renew)
-        for sock_addr in sock_addrs.iter().take(storage::MAX_ITEMS_STORED) {
+        for sock_addr in sock_addrs.iter().take(MAX_ITEMS_STORED) {
             assert!(announce_store.add_item(info_hash, *sock_addr));
         }
     }
 
     #[test]
     fn positive_full_storage_expire_one_infohash() {
-        let mut announce_store = AnnounceStorage::new();
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
         let info_hash = [0u8; bt::INFO_HASH_LEN].into();
         #[allow(clippy::cast_possible_truncation)]
-        let sock_addrs = bip_test::dummy_block_socket_addrs((storage::MAX_ITEMS_STORED + 1) as u16);
+        let sock_addrs = bip_test::dummy_block_socket_addrs((MAX_ITEMS_STORED + 1) as u16);
 
         // Fill up the announce storage completely
-        for sock_addr in sock_addrs.iter().take(storage::MAX_ITEMS_STORED) {
+        for sock_addr in sock_addrs.iter().take(MAX_ITEMS_STORED) {
             assert!(announce_store.add_item(info_hash, *sock_addr));
         }
 
@@ -284,35 +550,35 @@ mod tests {
 
         // Returned false because it wasn't added
         assert!(!announce_store.add_item(other_info_hash, sock_addrs[sock_addrs.len() - 1]));
-        // Closure not invoked because it wasn't added
-        let mut times_invoked = 0;
-        announce_store.find_items(&other_info_hash, |_| times_invoked += 1);
-        assert_eq!(times_invoked, 0);
+        // Nothing returned because it wasn't added
+        assert!(announce_store.sample_items(&other_info_hash, usize::MAX).is_empty());
 
         // Try to add a new item into the storage mocking the current time
-        let mock_current_time = bip_test::travel_into_future(Duration::hours(storage::EXPIRATION_TIME_HOURS));
+        let mock_current_time = bip_test::travel_into_future(expiration_time());
         assert!(announce_store.add(other_info_hash, sock_addrs[sock_addrs.len() - 1], mock_current_time));
-        // Closure invoked because it was added
-        announce_store.find_items(&other_info_hash, |_| times_invoked += 1);
-        assert_eq!(times_invoked, 1);
+        // Returned because it was added
+        assert_eq!(
+            announce_store.sample(&other_info_hash, usize::MAX, mock_current_time).len(),
+            1
+        );
     }
 
     #[test]
     fn positive_full_storage_expire_two_infohash() {
-        let mut announce_store = AnnounceStorage::new();
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
         let info_hash_one = [0u8; bt::INFO_HASH_LEN].into();
         let info_hash_two = [1u8; bt::INFO_HASH_LEN].into();
         #[allow(clippy::cast_possible_truncation)]
-        let sock_addrs = bip_test::dummy_block_socket_addrs((storage::MAX_ITEMS_STORED + 1) as u16);
+        let sock_addrs = bip_test::dummy_block_socket_addrs((MAX_ITEMS_STORED + 1) as u16);
 
         // Fill up first info hash
-        let num_contacts_first = storage::MAX_ITEMS_STORED / 2;
+        let num_contacts_first = MAX_ITEMS_STORED / 2;
         for sock_addr in sock_addrs.iter().take(num_contacts_first) {
             assert!(announce_store.add_item(info_hash_one, *sock_addr));
         }
 
         // Fill up second info hash
-        let num_contacts_second = storage::MAX_ITEMS_STORED - num_contacts_first;
+        let num_contacts_second = MAX_ITEMS_STORED - num_contacts_first;
         for sock_addr in sock_addrs.iter().skip(num_contacts_first).take(num_contacts_second) {
             assert!(announce_store.add_item(info_hash_two, *sock_addr));
         }
@@ -320,16 +586,101 @@ mod tests {
         // Try to add a third info hash with a contact
         let info_hash_three = [2u8; bt::INFO_HASH_LEN].into();
         assert!(!announce_store.add_item(info_hash_three, sock_addrs[sock_addrs.len() - 1]));
-        // Closure not invoked because it was not added
-        let mut times_invoked = 0;
-        announce_store.find_items(&info_hash_three, |_| times_invoked += 1);
-        assert_eq!(times_invoked, 0);
+        // Nothing returned because it was not added
+        assert!(announce_store.sample_items(&info_hash_three, usize::MAX).is_empty());
 
         // Try to add a new item into the storage mocking the current time
-        let mock_current_time = bip_test::travel_into_future(Duration::hours(storage::EXPIRATION_TIME_HOURS));
+        let mock_current_time = bip_test::travel_into_future(expiration_time());
         assert!(announce_store.add(info_hash_three, sock_addrs[sock_addrs.len() - 1], mock_current_time));
-        // Closure invoked because it was added
-        announce_store.find_items(&info_hash_three, |_| times_invoked += 1);
-        assert_eq!(times_invoked, 1);
+        // Returned because it was added
+        assert_eq!(
+            announce_store.sample(&info_hash_three, usize::MAX, mock_current_time).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn positive_full_info_hash_evicts_oldest() {
+        let config = StorageConfig::default().with_max_peers_per_info_hash(2);
+        let mut announce_store = AnnounceStorage::new(config, false);
+        let info_hash = [0u8; bt::INFO_HASH_LEN].into();
+        let sock_addrs = bip_test::dummy_block_socket_addrs(3);
+
+        for sock_addr in &sock_addrs {
+            assert!(announce_store.add_item(info_hash, *sock_addr));
+        }
+
+        let mut items = announce_store.sample_items(&info_hash, usize::MAX);
+        items.sort();
+        let mut expected = sock_addrs[1..].to_vec();
+        expected.sort();
+
+        assert_eq!(items, expected);
+        assert_eq!(announce_store.stats().peers(), 2);
+    }
+
+    #[test]
+    fn positive_sample_limited_to_count() {
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
+        let info_hash = [0u8; bt::INFO_HASH_LEN].into();
+        let sock_addrs = bip_test::dummy_block_socket_addrs(20);
+
+        for sock_addr in &sock_addrs {
+            assert!(announce_store.add_item(info_hash, *sock_addr));
+        }
+
+        let mut items = announce_store.sample_items(&info_hash, 5);
+        items.sort();
+        items.dedup();
+
+        assert_eq!(items.len(), 5);
+    }
+
+    #[test]
+    fn positive_stats_exclude_expired() {
+        let mut announce_store = AnnounceStorage::new(test_config(), false);
+        let sock_addrs = bip_test::dummy_block_socket_addrs(3);
+
+        assert!(announce_store.add(
+            [1u8; bt::INFO_HASH_LEN].into(),
+            sock_addrs[2],
+            bip_test::travel_into_past(expiration_time())
+        ));
+        assert!(announce_store.add_item([0u8; bt::INFO_HASH_LEN].into(), sock_addrs[0]));
+        assert!(announce_store.add_item([0u8; bt::INFO_HASH_LEN].into(), sock_addrs[1]));
+
+        let stats = announce_store.stats();
+        assert_eq!(stats.info_hashes(), 1);
+        assert_eq!(stats.peers(), 2);
+    }
+
+    #[test]
+    fn positive_lru_full_storage_evicts_least_recent() {
+        let config = StorageConfig::default().with_max_peers(2);
+        let lru_store = LruStorage::new(config);
+        let info_hashes: Vec<_> = (0..3u8).map(|i| [i; bt::INFO_HASH_LEN].into()).collect();
+        let sock_addrs = bip_test::dummy_block_socket_addrs(3);
+
+        assert!(lru_store.add_peer(info_hashes[0], sock_addrs[0]));
+        assert!(lru_store.add_peer(info_hashes[1], sock_addrs[1]));
+        // Renew the first peer, so the second becomes the least recently announced
+        assert!(lru_store.add_peer(info_hashes[0], sock_addrs[0]));
+        assert!(lru_store.add_peer(info_hashes[2], sock_addrs[2]));
+
+        assert_eq!(lru_store.sample_peers(&info_hashes[0], usize::MAX), vec![sock_addrs[0]]);
+        assert!(lru_store.sample_peers(&info_hashes[1], usize::MAX).is_empty());
+        assert_eq!(lru_store.sample_peers(&info_hashes[2], usize::MAX), vec![sock_addrs[2]]);
+        assert_eq!(lru_store.stats(), StorageStats::new(2, 2));
+    }
+
+    #[test]
+    fn negative_memory_full_storage_rejects() {
+        let config = StorageConfig::default().with_max_peers(1);
+        let memory_store = MemoryStorage::new(config);
+        let sock_addrs = bip_test::dummy_block_socket_addrs(2);
+
+        assert!(memory_store.add_peer([0u8; bt::INFO_HASH_LEN].into(), sock_addrs[0]));
+        assert!(!memory_store.add_peer([1u8; bt::INFO_HASH_LEN].into(), sock_addrs[1]));
+        assert_eq!(memory_store.stats(), StorageStats::new(1, 1));
     }
 }
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_STRIKES: u32 = 3;
+const DEFAULT_DECAY_SECS: u64 = 30 * 60;
+const DEFAULT_MAX_ENTRIES: usize = 4096;
+
+/// Configures how nodes that misbehave are blacklisted.
+///
+/// Each misbehavior counts as a strike against the address of the node. Once a node reaches the
+/// maximum number of strikes it is blacklisted, so it is no longer added to our routing table or
+/// sent queries. Strikes, and so the blacklisting, are forgotten once the node has behaved for the
+/// decay period.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct BlacklistConfig {
+    max_strikes: u32,
+    decay: Duration,
+    max_entries: usize,
+}
+
+impl BlacklistConfig {
+    /// Sets the number of strikes at which a node is blacklisted.
+    ///
+    /// A value of zero disables the blacklist.
+    #[must_use]
+    pub fn with_max_strikes(mut self, max_strikes: u32) -> BlacklistConfig {
+        self.max_strikes = max_strikes;
+        self
+    }
+
+    /// Sets how long after its latest strike the strikes against a node are forgotten.
+    ///
+    /// A decay of zero disables the blacklist.
+    #[must_use]
+    pub fn with_decay(mut self, decay: Duration) -> BlacklistConfig {
+        self.decay = decay;
+        self
+    }
+
+    /// Sets the maximum number of nodes that strikes are tracked for.
+    ///
+    /// Once full, the node whose latest strike is the oldest is forgotten.
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> BlacklistConfig {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Gets the number of strikes at which a node is blacklisted.
+    #[must_use]
+    pub fn max_strikes(&self) -> u32 {
+        self.max_strikes
+    }
+
+    /// Gets how long after its latest strike the strikes against a node are forgotten.
+    #[must_use]
+    pub fn decay(&self) -> Duration {
+        self.decay
+    }
+
+    /// Gets the maximum number of nodes that strikes are tracked for.
+    #[must_use]
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.max_strikes != 0 && !self.decay.is_zero() && self.max_entries != 0
+    }
+}
+
+impl Default for BlacklistConfig {
+    fn default() -> BlacklistConfig {
+        BlacklistConfig {
+            max_strikes: DEFAULT_MAX_STRIKES,
+            decay: Duration::from_secs(DEFAULT_DECAY_SECS),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Way in which a remote node misbehaved.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Misbehavior {
+    /// Node sent a message that could not be parsed.
+    MalformedMessage,
+    /// Node sent a response with a transaction id that we have no outstanding query for.
+    UnknownTransaction,
+    /// Node sent a response to a query that we sent to a different address.
+    SpoofedResponse,
+}
+
+/// Strikes recorded against a remote node, for inspecting the blacklist.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlacklistEntry {
+    addr: SocketAddr,
+    strikes: u32,
+    misbehavior: Misbehavior,
+    blacklisted: bool,
+    expires_in: Duration,
+}
+
+impl BlacklistEntry {
+    /// Address of the node.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Number of strikes recorded against the node.
+    #[must_use]
+    pub fn strikes(&self) -> u32 {
+        self.strikes
+    }
+
+    /// Latest misbehavior of the node.
+    #[must_use]
+    pub fn misbehavior(&self) -> Misbehavior {
+        self.misbehavior
+    }
+
+    /// Whether the node has reached the maximum number of strikes and is blacklisted.
+    #[must_use]
+    pub fn is_blacklisted(&self) -> bool {
+        self.blacklisted
+    }
+
+    /// Time until the strikes against the node are forgotten.
+    #[must_use]
+    pub fn expires_in(&self) -> Duration {
+        self.expires_in
+    }
+}
+
+struct Strikes {
+    count: u32,
+    misbehavior: Misbehavior,
+    latest: Instant,
+}
+
+/// Tracks strikes against misbehaving nodes, blacklisting those with too many.
+pub struct Blacklist {
+    config: BlacklistConfig,
+    nodes: HashMap<SocketAddr, Strikes>,
+}
+
+impl Blacklist {
+    pub fn new(config: BlacklistConfig) -> Blacklist {
+        Blacklist {
+            config,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Record a strike against the node at the given address, returns true if it is now blacklisted.
+    pub fn strike(&mut self, addr: SocketAddr, misbehavior: Misbehavior) -> bool {
+        self.strike_at(addr, misbehavior, Instant::now())
+    }
+
+    fn strike_at(&mut self, addr: SocketAddr, misbehavior: Misbehavior, now: Instant) -> bool {
+        if !self.config.is_enabled() {
+            return false;
+        }
+
+        self.remove_expired(now);
+        if !self.nodes.contains_key(&addr) && self.nodes.len() >= self.config.max_entries {
+            let oldest = self
+                .nodes
+                .iter()
+                .min_by_key(|(_, strikes)| strikes.latest)
+                .map(|(addr, _)| *addr);
+
+            if let Some(oldest) = oldest {
+                self.nodes.remove(&oldest);
+            }
+        }
+
+        let strikes = self.nodes.entry(addr).or_insert(Strikes {
+            count: 0,
+            misbehavior,
+            latest: now,
+        });
+        strikes.count = strikes.count.saturating_add(1);
+        strikes.misbehavior = misbehavior;
+        strikes.latest = now;
+
+        strikes.count >= self.config.max_strikes
+    }
+
+    /// Whether the node at the given address is blacklisted.
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.contains_at(addr, Instant::now())
+    }
+
+    fn contains_at(&self, addr: &SocketAddr, now: Instant) -> bool {
+        self.config.is_enabled()
+            && self
+                .nodes
+                .get(addr)
+                .is_some_and(|strikes| strikes.count >= self.config.max_strikes && !self.is_expired(strikes, now))
+    }
+
+    /// Snapshot of the nodes that strikes are currently recorded against.
+    pub fn entries(&mut self) -> Vec<BlacklistEntry> {
+        self.entries_at(Instant::now())
+    }
+
+    fn entries_at(&mut self, now: Instant) -> Vec<BlacklistEntry> {
+        self.remove_expired(now);
+
+        self.nodes
+            .iter()
+            .map(|(addr, strikes)| BlacklistEntry {
+                addr: *addr,
+                strikes: strikes.count,
+                misbehavior: strikes.misbehavior,
+                blacklisted: strikes.count >= self.config.max_strikes,
+                expires_in: (strikes.latest + self.config.decay).saturating_duration_since(now),
+            })
+            .collect()
+    }
+
+    /// Forget the strikes against every node.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    fn is_expired(&self, strikes: &Strikes, now: Instant) -> bool {
+        now.saturating_duration_since(strikes.latest) >= self.config.decay
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let decay = self.config.decay;
+
+        self.nodes
+            .retain(|_, strikes| now.saturating_duration_since(strikes.latest) < decay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    use super::{Blacklist, BlacklistConfig, Misbehavior};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn positive_blacklist_after_max_strikes() {
+        let mut blacklist = Blacklist::new(BlacklistConfig::default().with_max_strikes(2));
+        let now = Instant::now();
+
+        assert!(!blacklist.strike_at(addr(1), Misbehavior::MalformedMessage, now));
+        assert!(!blacklist.contains_at(&addr(1), now));
+
+        assert!(blacklist.strike_at(addr(1), Misbehavior::SpoofedResponse, now));
+        assert!(blacklist.contains_at(&addr(1), now));
+        assert!(!blacklist.contains_at(&addr(2), now));
+
+        let entries = blacklist.entries_at(now);
+        assert_eq!(1, entries.len());
+        assert_eq!(2, entries[0].strikes());
+        assert_eq!(Misbehavior::SpoofedResponse, entries[0].misbehavior());
+        assert!(entries[0].is_blacklisted());
+    }
+
+    #[test]
+    fn positive_clear_blacklist() {
+        let mut blacklist = Blacklist::new(BlacklistConfig::default().with_max_strikes(1));
+        let now = Instant::now();
+
+        blacklist.strike_at(addr(1), Misbehavior::UnknownTransaction, now);
+        blacklist.clear();
+
+        assert!(!blacklist.contains_at(&addr(1), now));
+        assert!(blacklist.entries_at(now).is_empty());
+    }
+
+    #[test]
+    fn positive_evict_oldest_strikes() {
+        let config = BlacklistConfig::default().with_max_strikes(1).with_max_entries(2);
+        let mut blacklist = Blacklist::new(config);
+        let now = Instant::now();
+
+        for port in 1..=3 {
+            blacklist.strike_at(
+                addr(port),
+                Misbehavior::MalformedMessage,
+                now + Duration::from_secs(port.into()),
+            );
+        }
+
+        assert!(!blacklist.contains_at(&addr(1), now));
+        assert!(blacklist.contains_at(&addr(2), now));
+        assert!(blacklist.contains_at(&addr(3), now));
+    }
+
+    #[test]
+    fn negative_strikes_decay() {
+        let config = BlacklistConfig::default()
+            .with_max_strikes(2)
+            .with_decay(Duration::from_secs(10));
+        let mut blacklist = Blacklist::new(config);
+        let now = Instant::now();
+
+        blacklist.strike_at(addr(1), Misbehavior::MalformedMessage, now);
+        assert!(!blacklist.strike_at(addr(1), Misbehavior::MalformedMessage, now + Duration::from_secs(10)));
+
+        assert!(blacklist.strike_at(addr(1), Misbehavior::MalformedMessage, now + Duration::from_secs(15)));
+        assert!(!blacklist.contains_at(&addr(1), now + Duration::from_secs(25)));
+    }
+
+    #[test]
+    fn negative_disabled_blacklist() {
+        let mut blacklist = Blacklist::new(BlacklistConfig::default().with_max_strikes(0));
+        let now = Instant::now();
+
+        assert!(!blacklist.strike_at(addr(1), Misbehavior::MalformedMessage, now));
+        assert!(!blacklist.contains_at(&addr(1), now));
+        assert!(blacklist.entries_at(now).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::net::SocketAddrV4;
+use std::time::{Duration, Instant};
+
+use util::bt::InfoHash;
+
+use crate::worker::lookup::LookupStats;
+
+const DEFAULT_TTL_SECS: u64 = 2 * 60;
+const DEFAULT_MAX_ENTRIES: usize = 64;
+
+/// Configures how the results of recent lookups are cached.
+///
+/// Searching again for an `InfoHash` that was looked up recently, without announcing, hands the
+/// peers found by that lookup to the handshaker instead of running a new lookup. This makes retries,
+/// such as when resolving a magnet link, cheap for both us and the DHT.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct LookupCacheConfig {
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl LookupCacheConfig {
+    /// Sets how long the results of a completed lookup are reused for.
+    ///
+    /// A ttl of zero disables the cache.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> LookupCacheConfig {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of `InfoHash`(s) whose lookup results are cached.
+    ///
+    /// Once full, the results of the oldest lookup are replaced. A value of zero disables the cache.
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> LookupCacheConfig {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Gets how long the results of a completed lookup are reused for.
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Gets the maximum number of `InfoHash`(s) whose lookup results are cached.
+    #[must_use]
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_entries != 0
+    }
+}
+
+impl Default for LookupCacheConfig {
+    fn default() -> LookupCacheConfig {
+        LookupCacheConfig {
+            ttl: Duration::from_secs(DEFAULT_TTL_SECS),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+struct CachedLookup {
+    peers: Vec<SocketAddrV4>,
+    stats: LookupStats,
+    completed: Instant,
+}
+
+/// Caches the peers found by recent lookups.
+#[allow(clippy::module_name_repetitions)]
+pub struct LookupCache {
+    config: LookupCacheConfig,
+    // Peers found so far by lookups that are still running
+    searching: HashMap<InfoHash, Vec<SocketAddrV4>>,
+    completed: HashMap<InfoHash, CachedLookup>,
+}
+
+impl LookupCache {
+    pub fn new(config: LookupCacheConfig) -> LookupCache {
+        LookupCache {
+            config,
+            searching: HashMap::new(),
+            completed: HashMap::new(),
+        }
+    }
+
+    /// Record the peers found by a running lookup for the given `InfoHash`.
+    pub fn add_peers(&mut self, info_hash: InfoHash, peers: &[SocketAddrV4]) {
+        if !self.config.is_enabled() {
+            return;
+        }
+
+        let found = self.searching.entry(info_hash).or_default();
+        for peer in peers {
+            if !found.contains(peer) {
+                found.push(*peer);
+            }
+        }
+    }
+
+    /// Cache the peers found by the lookup for the given `InfoHash`, which has completed.
+    pub fn complete(&mut self, info_hash: InfoHash, stats: LookupStats) {
+        self.complete_at(info_hash, stats, Instant::now());
+    }
+
+    fn complete_at(&mut self, info_hash: InfoHash, stats: LookupStats, now: Instant) {
+        let peers = self.searching.remove(&info_hash).unwrap_or_default();
+        if !self.config.is_enabled() {
+            return;
+        }
+
+        self.remove_expired(now);
+        if !self.completed.contains_key(&info_hash) && self.completed.len() >= self.config.max_entries {
+            let oldest = self
+                .completed
+                .iter()
+                .min_by_key(|(_, lookup)| lookup.completed)
+                .map(|(info_hash, _)| *info_hash);
+
+            if let Some(oldest) = oldest {
+                self.completed.remove(&oldest);
+            }
+        }
+
+        self.completed.insert(
+            info_hash,
+            CachedLookup {
+                peers,
+                stats,
+                completed: now,
+            },
+        );
+    }
+
+    /// Peers found by a recent lookup for the given `InfoHash`, along with the statistics of that lookup.
+    pub fn get(&mut self, info_hash: &InfoHash) -> Option<(Vec<SocketAddrV4>, LookupStats)> {
+        self.get_at(info_hash, Instant::now())
+    }
+
+    fn get_at(&mut self, info_hash: &InfoHash, now: Instant) -> Option<(Vec<SocketAddrV4>, LookupStats)> {
+        self.remove_expired(now);
+
+        self.completed
+            .get(info_hash)
+            .map(|lookup| (lookup.peers.clone(), lookup.stats))
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let ttl = self.config.ttl;
+
+        self.completed
+            .retain(|_, lookup| now.saturating_duration_since(lookup.completed) < ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use util::bt::{self, InfoHash};
+
+    use super::{LookupCache, LookupCacheConfig};
+    use crate::worker::lookup::LookupStats;
+
+    fn info_hash(byte: u8) -> InfoHash {
+        [byte; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_cache_completed_lookup() {
+        let mut cache = LookupCache::new(LookupCacheConfig::default());
+        let peer = "1.2.3.4:5".parse().unwrap();
+        let now = Instant::now();
+
+        cache.add_peers(info_hash(0), &[peer, peer]);
+        assert!(cache.get_at(&info_hash(0), now).is_none());
+
+        cache.complete_at(info_hash(0), LookupStats::default(), now);
+        let (peers, _) = cache.get_at(&info_hash(0), now + Duration::from_secs(1)).unwrap();
+
+        assert_eq!(vec![peer], peers);
+    }
+
+    #[test]
+    fn positive_evict_oldest_lookup() {
+        let mut cache = LookupCache::new(LookupCacheConfig::default().with_max_entries(2));
+        let now = Instant::now();
+
+        for (offset, byte) in [0, 1, 2].into_iter().enumerate() {
+            cache.complete_at(
+                info_hash(byte),
+                LookupStats::default(),
+                now + Duration::from_secs(offset as u64),
+            );
+        }
+
+        assert!(cache.get_at(&info_hash(0), now).is_none());
+        assert!(cache.get_at(&info_hash(1), now).is_some());
+        assert!(cache.get_at(&info_hash(2), now).is_some());
+    }
+
+    #[test]
+    fn negative_expired_lookup() {
+        let mut cache = LookupCache::new(LookupCacheConfig::default().with_ttl(Duration::from_secs(10)));
+        let now = Instant::now();
+
+        cache.complete_at(info_hash(0), LookupStats::default(), now);
+
+        assert!(cache.get_at(&info_hash(0), now + Duration::from_secs(10)).is_none());
+    }
+
+    #[test]
+    fn negative_disabled_cache() {
+        let mut cache = LookupCache::new(LookupCacheConfig::default().with_ttl(Duration::ZERO));
+        let now = Instant::now();
+
+        cache.add_peers(info_hash(0), &["1.2.3.4:5".parse().unwrap()]);
+        cache.complete_at(info_hash(0), LookupStats::default(), now);
+
+        assert!(cache.get_at(&info_hash(0), now).is_none());
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use futures::channel::mpsc;
+use futures::SinkExt as _;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Instant};
+use util::bt::NodeId;
+
+use crate::message::find_node::{FindNodeRequest, Want};
+use crate::routing::bucket;
+use crate::routing::node::{Node, NodeInfo};
+use crate::routing::table::RoutingTable;
+use crate::transaction::{MIDGenerator, TransactionID};
+use crate::worker::lookup::{self, Distance, NodeProgress, RttEstimator};
+use crate::worker::ScheduledTaskCheck;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClosestStatus {
+    Searching,
+    Completed,
+    Failed,
+}
+
+/// Iterative `find_node` lookup for the nodes closest to an arbitrary target.
+///
+/// Once the closest nodes have all responded, or there is nobody left to query, the
+/// closest responding nodes are sent to the results channel, which is then closed.
+#[allow(clippy::module_name_repetitions)]
+pub struct TableClosest {
+    table_id: NodeId,
+    target_id: NodeId,
+    alpha: usize,
+    want: Want,
+    id_generator: Mutex<MIDGenerator>,
+    rtt_estimator: Arc<Mutex<RttEstimator>>,
+    active_queries: Mutex<HashMap<TransactionID, (Node, Instant)>>,
+    all_sorted_nodes: Mutex<Vec<(Distance, Node, NodeProgress)>>,
+    results: Mutex<Option<mpsc::Sender<NodeInfo>>>,
+    // Dropping the lookup aborts any pending timeout checks
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl TableClosest {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        table_id: NodeId,
+        target_id: NodeId,
+        id_generator: MIDGenerator,
+        alpha: usize,
+        rtt_estimator: Arc<Mutex<RttEstimator>>,
+        table: &RoutingTable,
+        want: Want,
+        results: mpsc::Sender<NodeInfo>,
+    ) -> TableClosest {
+        let all_sorted_nodes = Mutex::new(Vec::with_capacity(bucket::MAX_BUCKET_SIZE));
+
+        for node in table.closest_nodes(target_id).take(bucket::MAX_BUCKET_SIZE) {
+            lookup::insert_sorted_node(&all_sorted_nodes, target_id, node.clone());
+        }
+
+        TableClosest {
+            table_id,
+            target_id,
+            alpha,
+            want,
+            id_generator: Mutex::new(id_generator),
+            rtt_estimator,
+            active_queries: Mutex::new(HashMap::with_capacity(alpha)),
+            all_sorted_nodes,
+            results: Mutex::new(Some(results)),
+            tasks: Mutex::default(),
+        }
+    }
+
+    /// Record a response to one of our queries, containing the given nodes.
+    pub async fn recv_response(
+        &self,
+        trans_id: TransactionID,
+        nodes: Vec<Node>,
+        table: Arc<RwLock<RoutingTable>>,
+        out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>,
+    ) -> ClosestStatus {
+        let Some((node, sent)) = self.active_queries.lock().unwrap().remove(&trans_id) else {
+            tracing::warn!("bip_dht: Received expired/unsolicited node response for an active closest nodes lookup...");
+            return ClosestStatus::Searching;
+        };
+
+        self.rtt_estimator.lock().unwrap().sample(sent.elapsed());
+        lookup::set_node_progress(&self.all_sorted_nodes, self.target_id, &node, NodeProgress::Responded);
+
+        for node in nodes.into_iter().filter(|node| node.id() != self.table_id) {
+            lookup::insert_sorted_node(&self.all_sorted_nodes, self.target_id, node);
+        }
+
+        self.start_request_round(table, out, scheduled_task_sender).await
+    }
+
+    /// Record that one of our queries did not receive a response in time.
+    pub async fn recv_timeout(
+        &self,
+        trans_id: TransactionID,
+        table: Arc<RwLock<RoutingTable>>,
+        out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>,
+    ) -> ClosestStatus {
+        let Some((node, _)) = self.active_queries.lock().unwrap().remove(&trans_id) else {
+            return ClosestStatus::Searching;
+        };
+
+        lookup::set_node_progress(&self.all_sorted_nodes, self.target_id, &node, NodeProgress::TimedOut);
+
+        self.start_request_round(table, out, scheduled_task_sender).await
+    }
+
+    /// Fill any free query slots with the closest nodes we have not queried yet.
+    pub async fn start_request_round(
+        &self,
+        table: Arc<RwLock<RoutingTable>>,
+        mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>,
+    ) -> ClosestStatus {
+        if lookup::closest_nodes_stable(&self.all_sorted_nodes) {
+            return ClosestStatus::Completed;
+        }
+
+        let free_slots = self.alpha.saturating_sub(self.active_queries.lock().unwrap().len());
+        let pick_nodes = lookup::pick_closest_unqueried(&self.all_sorted_nodes, free_slots);
+        let timeout = self.rtt_estimator.lock().unwrap().timeout();
+
+        for node in pick_nodes {
+            let trans_id = self.id_generator.lock().unwrap().generate();
+
+            let find_node_msg = FindNodeRequest::new(trans_id.as_ref(), self.table_id, self.target_id)
+                .with_want(self.want)
+                .encode();
+            if out.send((find_node_msg, node.addr())).await.is_err() {
+                tracing::error!("bip_dht: Could not send a closest nodes lookup message through the channel...");
+                return ClosestStatus::Failed;
+            }
+
+            if let Some(n) = table.read().unwrap().find_node(&node) {
+                n.local_request();
+            }
+            self.active_queries.lock().unwrap().insert(trans_id, (node, Instant::now()));
+
+            // Schedule a timeout check
+            let mut this_scheduled_task_sender = scheduled_task_sender.clone();
+            self.tasks.lock().unwrap().spawn(async move {
+                sleep(timeout).await;
+
+                if this_scheduled_task_sender
+                    .send(ScheduledTaskCheck::ClosestTimeout(trans_id))
+                    .await
+                    .is_err()
+                {
+                    tracing::debug!("bip_dht: Failed to send a closest nodes timeout to the scheduled channel...");
+                }
+            });
+        }
+
+        if self.active_queries.lock().unwrap().is_empty() {
+            ClosestStatus::Completed
+        } else {
+            ClosestStatus::Searching
+        }
+    }
+
+    /// Send the closest nodes that responded to us, closest first, and close the results channel.
+    ///
+    /// A dual-stack node may have responded on both of its addresses, it is only sent once.
+    pub fn finish(&self) {
+        let Some(mut results) = self.results.lock().unwrap().take() else {
+            return;
+        };
+
+        let mut sent_ids = HashSet::with_capacity(bucket::MAX_BUCKET_SIZE);
+        for (_, node, _) in self
+            .all_sorted_nodes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|&(_, node, progress)| *progress == NodeProgress::Responded && sent_ids.insert(node.id()))
+            .take(bucket::MAX_BUCKET_SIZE)
+        {
+            if results.try_send(Node::as_good(node.id(), node.addr()).info()).is_err() {
+                tracing::warn!("bip_dht: Failed to send a closest node, receiver was dropped...");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use bencode::{BDecodeOpt, BencodeRef};
+    use futures::channel::mpsc;
+    use futures::StreamExt as _;
+    use util::bt::{self, NodeId};
+    use util::test as bip_test;
+
+    use crate::message::find_node::Want;
+    use crate::message::request::RequestType;
+    use crate::message::response::ExpectedResponse;
+    use crate::message::MessageType;
+    use crate::routing::bucket;
+    use crate::routing::node::Node;
+    use crate::routing::table::RoutingTable;
+    use crate::transaction::{AIDGenerator, TransactionID};
+    use crate::worker::closest::{ClosestStatus, TableClosest};
+    use crate::worker::lookup::{LookupConfig, RttEstimator};
+
+    fn sent_queries(out_recv: &mut mpsc::Receiver<(Vec<u8>, SocketAddr)>) -> Vec<(TransactionID, SocketAddr)> {
+        let mut queries = Vec::new();
+        while let Ok((message, addr)) = out_recv.try_recv() {
+            let bencode = BencodeRef::decode(&message, BDecodeOpt::default()).unwrap();
+
+            let Ok(MessageType::Request(RequestType::FindNode(request))) =
+                MessageType::<BencodeRef<'_>>::new(&bencode, |_| ExpectedResponse::None)
+            else {
+                panic!("bip_dht: Expected a find node request...");
+            };
+
+            queries.push((TransactionID::from_bytes(request.transaction_id()).unwrap(), addr));
+        }
+
+        queries
+    }
+
+    #[tokio::test]
+    async fn positive_closest_nodes_converge() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let target: NodeId = [0xFFu8; bt::NODE_ID_LEN].into();
+        let addrs = bip_test::dummy_block_socket_addrs(3);
+
+        let mut table = RoutingTable::new(node_id);
+        table.add_node(&Node::as_good([1u8; bt::NODE_ID_LEN].into(), addrs[0]));
+        let table = Arc::new(RwLock::new(table));
+
+        let (out, mut out_recv) = mpsc::channel(16);
+        let (scheduled, _scheduled_recv) = mpsc::channel(16);
+        let (results, results_recv) = mpsc::channel(bucket::MAX_BUCKET_SIZE);
+        let rtt_estimator = Arc::new(Mutex::new(RttEstimator::new(LookupConfig::default())));
+
+        let closest = TableClosest::new(
+            node_id,
+            target,
+            AIDGenerator::new().generate(),
+            4,
+            rtt_estimator,
+            &table.read().unwrap(),
+            Want::V4,
+            results,
+        );
+
+        let status = closest.start_request_round(table.clone(), out.clone(), &scheduled).await;
+        assert_eq!(ClosestStatus::Searching, status);
+
+        let queries = sent_queries(&mut out_recv);
+        assert_eq!(vec![addrs[0]], queries.iter().map(|(_, addr)| *addr).collect::<Vec<_>>());
+
+        // First node knows about two nodes closer to the target, and about us
+        let closer = vec![
+            Node::as_questionable([0xF0u8; bt::NODE_ID_LEN].into(), addrs[1]),
+            Node::as_questionable([0xFEu8; bt::NODE_ID_LEN].into(), addrs[2]),
+            Node::as_questionable(node_id, "127.0.0.1:1".parse().unwrap()),
+        ];
+        let status = closest
+            .recv_response(queries[0].0, closer, table.clone(), out.clone(), &scheduled)
+            .await;
+        assert_eq!(ClosestStatus::Searching, status);
+
+        let queries = sent_queries(&mut out_recv);
+        assert_eq!(2, queries.len());
+
+        let status = closest
+            .recv_timeout(queries[0].0, table.clone(), out.clone(), &scheduled)
+            .await;
+        assert_eq!(ClosestStatus::Searching, status);
+
+        let status = closest
+            .recv_response(queries[1].0, Vec::new(), table.clone(), out.clone(), &scheduled)
+            .await;
+        assert_eq!(ClosestStatus::Completed, status);
+
+        closest.finish();
+
+        let found: Vec<SocketAddr> = results_recv.map(|info| info.addr()).collect().await;
+        let responded_addr = queries[1].1;
+
+        assert_eq!(vec![responded_addr, addrs[0]], found);
+    }
+
+    #[tokio::test]
+    async fn positive_empty_table_completes() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let table = Arc::new(RwLock::new(RoutingTable::new(node_id)));
+
+        let (out, _out_recv) = mpsc::channel(16);
+        let (scheduled, _scheduled_recv) = mpsc::channel(16);
+        let (results, results_recv) = mpsc::channel(bucket::MAX_BUCKET_SIZE);
+        let rtt_estimator = Arc::new(Mutex::new(RttEstimator::new(LookupConfig::default())));
+
+        let closest = TableClosest::new(
+            node_id,
+            [1u8; bt::NODE_ID_LEN].into(),
+            AIDGenerator::new().generate(),
+            4,
+            rtt_estimator,
+            &table.read().unwrap(),
+            Want::V4,
+            results,
+        );
+
+        assert_eq!(
+            ClosestStatus::Completed,
+            closest.start_request_round(table, out, &scheduled).await
+        );
+
+        closest.finish();
+
+        assert_eq!(0, results_recv.count().await);
+    }
+
+    #[tokio::test]
+    async fn positive_mixed_family_nodes_merged() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let target: NodeId = [0xFFu8; bt::NODE_ID_LEN].into();
+        let addrs = bip_test::dummy_block_socket_addrs(1);
+        let (v4_addr, v6_addr): (SocketAddr, SocketAddr) = ("127.0.0.1:6881".parse().unwrap(), "[::1]:6881".parse().unwrap());
+
+        let mut table = RoutingTable::new(node_id);
+        table.add_node(&Node::as_good([1u8; bt::NODE_ID_LEN].into(), addrs[0]));
+        let table = Arc::new(RwLock::new(table));
+
+        let (out, mut out_recv) = mpsc::channel(16);
+        let (scheduled, _scheduled_recv) = mpsc::channel(16);
+        let (results, results_recv) = mpsc::channel(bucket::MAX_BUCKET_SIZE);
+        let rtt_estimator = Arc::new(Mutex::new(RttEstimator::new(LookupConfig::default())));
+
+        let closest = TableClosest::new(
+            node_id,
+            target,
+            AIDGenerator::new().generate(),
+            4,
+            rtt_estimator,
+            &table.read().unwrap(),
+            Want::BOTH,
+            results,
+        );
+        closest.start_request_round(table.clone(), out.clone(), &scheduled).await;
+        let queries = sent_queries(&mut out_recv);
+
+        // Closer node is dual-stack, reported on both of its addresses
+        let closer_id: NodeId = [0xF0u8; bt::NODE_ID_LEN].into();
+        let closer = vec![
+            Node::as_questionable(closer_id, v4_addr),
+            Node::as_questionable(closer_id, v6_addr),
+        ];
+        closest
+            .recv_response(queries[0].0, closer, table.clone(), out.clone(), &scheduled)
+            .await;
+
+        let queries = sent_queries(&mut out_recv);
+        let mut queried: Vec<SocketAddr> = queries.iter().map(|(_, addr)| *addr).collect();
+        queried.sort();
+        assert_eq!(vec![v4_addr, v6_addr], queried);
+
+        for (trans_id, _) in queries {
+            closest
+                .recv_response(trans_id, Vec::new(), table.clone(), out.clone(), &scheduled)
+                .await;
+        }
+        closest.finish();
+
+        let found: Vec<NodeId> = results_recv.map(|info| info.id()).collect().await;
+
+        assert_eq!(vec![closer_id, [1u8; bt::NODE_ID_LEN].into()], found);
+    }
+}
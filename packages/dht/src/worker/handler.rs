@@ -3,37 +3,47 @@ use std::convert::AsRef;
 use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 
 use bencode::{ben_bytes, BDecodeOpt, BencodeMut, BencodeRef};
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 use futures::future::BoxFuture;
 use futures::{FutureExt, SinkExt, StreamExt as _};
 use tokio::net::UdpSocket;
 use tokio::task::JoinSet;
-use util::bt::InfoHash;
+use util::bt::{InfoHash, NodeId};
 use util::convert;
 use util::net::IpAddr;
 
+use crate::error::DhtError;
 use crate::handshaker_trait::HandshakerTrait;
 use crate::message::announce_peer::{AnnouncePeerResponse, ConnectPort};
-use crate::message::compact_info::{CompactNodeInfo, CompactValueInfo};
+use crate::message::compact_info::{CompactNodeInfo, CompactNodes, CompactValueInfo};
 use crate::message::error::{ErrorCode, ErrorMessage};
-use crate::message::find_node::FindNodeResponse;
+use crate::message::find_node::{FindNodeResponse, Want};
 use crate::message::get_peers::{CompactInfoType, GetPeersResponse};
 use crate::message::ping::PingResponse;
 use crate::message::request::RequestType;
 use crate::message::response::{ExpectedResponse, ResponseType};
 use crate::message::MessageType;
+use crate::metrics::SharedMetrics;
 use crate::router::Router;
-use crate::routing::node::{Node, NodeStatus};
+use crate::routing::node::{Node, NodeInfo, NodeStatus};
 use crate::routing::table::{BucketContents, RoutingTable};
-use crate::storage::AnnounceStorage;
+use crate::storage::{SharedStorage, StorageConfig, StorageStats};
 use crate::token::{Token, TokenStore};
 use crate::transaction::{AIDGenerator, ActionID, TransactionID};
+use crate::worker::blacklist::{Blacklist, BlacklistEntry, Misbehavior};
 use crate::worker::bootstrap::{BootstrapStatus, TableBootstrap};
-use crate::worker::lookup::{LookupStatus, TableLookup};
+use crate::worker::cache::{LookupCache, LookupCacheConfig};
+use crate::worker::closest::{ClosestStatus, TableClosest};
+use crate::worker::import::TableImport;
+use crate::worker::limiter::{self, QueryLimiter};
+use crate::worker::lookup::{AnnouncePort, LookupConfig, LookupStats, LookupStatus, RttEstimator, TableLookup};
+use crate::worker::readiness::BootstrapWaiters;
 use crate::worker::refresh::{RefreshStatus, TableRefresh};
-use crate::worker::{DhtEvent, OneshotTask, ScheduledTaskCheck, ShutdownCause};
+use crate::worker::sweep::{SweepConfig, TableSweep};
+use crate::worker::{DhtEvent, IncomingQuery, OneshotTask, QueryKind, ScheduledTaskCheck, ShutdownCause};
 
 const MAX_BOOTSTRAP_ATTEMPTS: usize = 3;
 const BOOTSTRAP_GOOD_NODE_THRESHOLD: usize = 10;
@@ -45,10 +55,19 @@ enum Task {
 
 /// Spawns a DHT handler that maintains our routing table and executes our actions on the DHT.
 #[allow(clippy::module_name_repetitions)]
+#[allow(clippy::too_many_arguments)]
 pub fn create_dht_handler<H>(
     table: RoutingTable,
     out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
     read_only: bool,
+    lookup_config: LookupConfig,
+    lookup_cache_config: LookupCacheConfig,
+    announce_port: AnnouncePort,
+    storage_config: StorageConfig,
+    storage: SharedStorage,
+    query_limiter: Arc<Mutex<QueryLimiter>>,
+    blacklist: Arc<Mutex<Blacklist>>,
+    metrics: SharedMetrics,
     handshaker: H,
     kill_sock: Arc<UdpSocket>,
     kill_addr: SocketAddr,
@@ -70,7 +89,16 @@ where
         main_task_sender.clone(),
         scheduled_task_sender,
         read_only,
+        lookup_config,
+        lookup_cache_config,
+        announce_port,
+        storage_config,
+        storage,
+        query_limiter,
+        blacklist,
+        metrics,
         handshaker,
+        Want::reachable_from(kill_addr),
     );
 
     let mut tasks = JoinSet::new();
@@ -109,14 +137,24 @@ enum TableAction {
     ///
     /// Includes number of bootstrap attempts.
     Bootstrap(Arc<TableBootstrap>, Arc<AtomicUsize>),
+    /// Sweep action.
+    Sweep(Arc<TableSweep>),
+    /// Import action.
+    Import(Arc<TableImport>),
+    /// Closest nodes lookup action.
+    Closest(Arc<TableClosest>),
 }
 
 /// Actions that we want to perform on our `RoutingTable` after bootstrapping finishes.
 enum PostBootstrapAction {
     /// Future lookup action.
-    Lookup(InfoHash, bool),
+    Lookup(InfoHash, bool, bool),
     /// Future refresh action.
     Refresh(Box<TableRefresh>, TransactionID),
+    /// Future sweep action.
+    Sweep(Vec<NodeId>, SweepConfig),
+    /// Future closest nodes lookup action.
+    Closest(NodeId, mpsc::Sender<NodeInfo>),
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -131,28 +169,51 @@ pub struct DhtHandler<H> {
 
     read_only: bool,
     bootstrapping: AtomicBool,
+    // Address families our socket is able to reach, so those we want nodes of
+    want: Want,
+
+    lookup_config: LookupConfig,
+    announce_port: AnnouncePort,
+    rtt_estimator: Arc<Mutex<RttEstimator>>,
+    lookup_cache: Mutex<LookupCache>,
 
     token_store: Mutex<TokenStore>,
+    query_limiter: Arc<Mutex<QueryLimiter>>,
+    blacklist: Arc<Mutex<Blacklist>>,
+    metrics: SharedMetrics,
     aid_generator: Mutex<AIDGenerator>,
-    active_stores: Mutex<AnnounceStorage>,
+    storage_config: StorageConfig,
+    active_stores: SharedStorage,
 
     // If future actions is not empty, that means we are still bootstrapping
     // since we will always spin up a table refresh action after bootstrapping.
     future_actions: Mutex<Vec<PostBootstrapAction>>,
     event_notifiers: Mutex<Vec<mpsc::Sender<DhtEvent>>>,
+    query_notifiers: Mutex<Vec<mpsc::Sender<IncomingQuery>>>,
+    bootstrap_waiters: Mutex<BootstrapWaiters>,
 }
 
 impl<H> DhtHandler<H>
 where
     H: HandshakerTrait + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         table: RoutingTable,
         out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         main_task_sender: mpsc::Sender<OneshotTask>,
         scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
         read_only: bool,
+        lookup_config: LookupConfig,
+        lookup_cache_config: LookupCacheConfig,
+        announce_port: AnnouncePort,
+        storage_config: StorageConfig,
+        storage: SharedStorage,
+        query_limiter: Arc<Mutex<QueryLimiter>>,
+        blacklist: Arc<Mutex<Blacklist>>,
+        metrics: SharedMetrics,
         handshaker: H,
+        want: Want,
     ) -> DhtHandler<H> {
         let mut aid_generator = AIDGenerator::new();
 
@@ -164,15 +225,26 @@ where
 
         DhtHandler {
             read_only,
+            want,
             handshaker: futures::lock::Mutex::new(handshaker),
             out_channel: out,
             token_store: Mutex::new(TokenStore::new()),
+            query_limiter,
+            blacklist,
+            metrics,
             aid_generator: Mutex::new(aid_generator),
             bootstrapping: AtomicBool::default(),
+            lookup_config,
+            announce_port,
+            rtt_estimator: Arc::new(Mutex::new(RttEstimator::new(lookup_config))),
+            lookup_cache: Mutex::new(LookupCache::new(lookup_cache_config)),
             routing_table: Arc::new(RwLock::new(table)),
-            active_stores: Mutex::new(AnnounceStorage::new()),
+            storage_config,
+            active_stores: storage,
             future_actions: Mutex::new(future_actions),
             event_notifiers: Mutex::default(),
+            query_notifiers: Mutex::default(),
+            bootstrap_waiters: Mutex::default(),
             table_actions: Mutex::new(HashMap::new()),
             main_task_sender,
             scheduled_task_sender,
@@ -187,16 +259,49 @@ where
             OneshotTask::RegisterSender(send) => {
                 self.handle_register_sender(send);
             }
+            OneshotTask::RegisterQuerySender(send) => {
+                self.handle_register_query_sender(send);
+            }
+            OneshotTask::RoutingTable(send) => {
+                self.handle_routing_table(send);
+            }
+            OneshotTask::StorageStats(send) => {
+                self.handle_storage_stats(send);
+            }
+            OneshotTask::Blacklist(send) => {
+                self.handle_blacklist(send);
+            }
+            OneshotTask::ClearBlacklist => {
+                self.blacklist.lock().unwrap().clear();
+            }
+            OneshotTask::WaitBootstrapped(min_good_nodes, send) => {
+                self.bootstrap_waiters.lock().unwrap().push(min_good_nodes, send);
+            }
             OneshotTask::StartBootstrap(routers, nodes) => {
                 self.handle_start_bootstrap(routers, nodes).await;
             }
-            OneshotTask::StartLookup(info_hash, should_announce) => {
-                self.handle_start_lookup(info_hash, should_announce).await;
+            OneshotTask::StartLookup(info_hash, should_announce, use_cache) => {
+                self.handle_start_lookup(info_hash, should_announce, use_cache).await;
+            }
+            OneshotTask::StartSweep(targets, config) => {
+                self.handle_start_sweep(targets, config);
+            }
+            OneshotTask::StartClosest(target, results) => {
+                self.handle_start_closest(target, results).await;
+            }
+            OneshotTask::StartImport(nodes) => {
+                Box::pin(self.handle_start_import(nodes)).await;
             }
             OneshotTask::Shutdown(cause) => {
                 self.handle_shutdown(cause);
             }
         }
+
+        // Any task may have added good nodes to the routing table
+        self.bootstrap_waiters
+            .lock()
+            .unwrap()
+            .notify(&self.routing_table.read().unwrap());
     }
 
     #[allow(clippy::too_many_lines)]
@@ -204,9 +309,34 @@ where
         // Parse the buffer as a bencoded message
         let Ok(bencode) = BencodeRef::decode(buffer, BDecodeOpt::default()) else {
             tracing::warn!("bip_dht: Received invalid bencode data...");
+            self.strike_node(addr, Misbehavior::MalformedMessage);
             return;
         };
 
+        // Only accept responses to queries we sent to that same address, so spoofed responses never reach our routing table
+        if let Some(trans_id) = limiter::response_transaction_id(&bencode) {
+            let misbehavior = {
+                let mut query_limiter = self.query_limiter.lock().unwrap();
+
+                if query_limiter.recv_response(trans_id, addr, Instant::now()) {
+                    None
+                } else if query_limiter.sent_elsewhere(trans_id, addr) {
+                    Some(Misbehavior::SpoofedResponse)
+                } else {
+                    Some(Misbehavior::UnknownTransaction)
+                }
+            };
+
+            if let Some(misbehavior) = misbehavior {
+                tracing::warn!(
+                    "bip_dht: Received a response from {} that does not match an outstanding query...",
+                    addr
+                );
+                self.strike_node(addr, misbehavior);
+                return;
+            }
+        }
+
         // Parse the bencode as a message
         // Check to make sure we issued the transaction id (or that it is still valid)
         let message = MessageType::<BencodeRef<'_>>::new(&bencode, |trans| {
@@ -222,10 +352,19 @@ where
 
             match table_action {
                 TableAction::Lookup(_) => ExpectedResponse::GetPeers,
-                TableAction::Refresh(_) | TableAction::Bootstrap(_, _) => ExpectedResponse::FindNode,
+                TableAction::Refresh(_)
+                | TableAction::Bootstrap(_, _)
+                | TableAction::Sweep(_)
+                | TableAction::Closest(_)
+                | TableAction::Import(_) => ExpectedResponse::FindNode,
             }
         });
 
+        // Let any monitors know about the request, even if we are not going to respond to it
+        if let Ok(MessageType::Request(request)) = &message {
+            self.broadcast_incoming_query(request, addr);
+        }
+
         // Do not process requests if we are read only
         // TODO: Add read only flags to messages we send it we are read only!
         // Also, check for read only flags on responses we get before adding nodes
@@ -276,14 +415,15 @@ where
                         n.remote_request();
                     }
 
-                    // Grab the closest nodes
-                    let mut closest_nodes_bytes = Vec::with_capacity(26 * 8);
-                    for node in routing_table.closest_nodes(f.target_id()).take(8) {
-                        closest_nodes_bytes.extend_from_slice(&node.encode());
-                    }
+                    // Grab the closest nodes of each address family the node wants
+                    let want = f.want().or_family_of(addr);
+                    let (closest_nodes_bytes, closest_nodes6_bytes) = closest_compact_nodes(&routing_table, f.target_id(), want);
 
-                    let find_node_rsp =
+                    let mut find_node_rsp =
                         FindNodeResponse::new(f.transaction_id(), routing_table.node_id(), &closest_nodes_bytes).unwrap();
+                    if want.v6() {
+                        find_node_rsp = find_node_rsp.with_nodes6(&closest_nodes6_bytes).unwrap();
+                    }
                     find_node_rsp.encode()
                 };
 
@@ -305,10 +445,11 @@ where
                     }
 
                     // TODO: Move socket address serialization code into bip_util
-                    // TODO: Check what the maximum number of values we can give without overflowing a udp packet
-                    // Also, if we aren't going to give all of the contacts, we may want to shuffle which ones we give
-                    let mut contact_info_bytes = Vec::with_capacity(6 * 20);
-                    self.active_stores.lock().unwrap().find_items(&g.info_hash(), |addr| {
+                    let contacts = self
+                        .active_stores
+                        .sample_peers(&g.info_hash(), self.storage_config.max_values_per_response());
+                    let mut contact_info_bytes = Vec::with_capacity(6 * contacts.len());
+                    for addr in &contacts {
                         let mut bytes = [0u8; 6];
                         let port = addr.port();
 
@@ -319,8 +460,8 @@ where
                                 }
                             }
                             SocketAddr::V6(_) => {
-                                tracing::error!("AnnounceStorage contained an IPv6 Address...");
-                                return;
+                                tracing::error!("PeerStorage contained an IPv6 Address...");
+                                continue;
                             }
                         };
 
@@ -328,7 +469,7 @@ where
                         bytes[5] = (port & 0x00FF) as u8;
 
                         contact_info_bytes.extend_from_slice(&bytes);
-                    });
+                    }
                     // Grab the bencoded list (ugh, we really have to do this, better apis I say!!!)
                     let mut contact_info_bencode = Vec::with_capacity(contact_info_bytes.len() / 6);
                     for chunk_index in 0..(contact_info_bytes.len() / 6) {
@@ -402,22 +543,24 @@ where
                     if !is_valid {
                         // Node gave us an invalid token
                         tracing::warn!("bip_dht: Remote node sent us an invalid token for an AnnounceRequest...");
+                        self.metrics.error_sent(ErrorCode::ProtocolError);
                         ErrorMessage::new(
                             a.transaction_id().to_vec(),
                             ErrorCode::ProtocolError,
                             "Received An Invalid Token".to_owned(),
                         )
                         .encode()
-                    } else if self.active_stores.lock().unwrap().add_item(a.info_hash(), connect_addr) {
+                    } else if self.active_stores.add_peer(a.info_hash(), connect_addr) {
                         // Node successfully stored the value with us, send an announce response
                         AnnouncePeerResponse::new(a.transaction_id(), routing_table.node_id()).encode()
                     } else {
                         // Node unsuccessfully stored the value with us, send them an error message
                         // TODO: Spec doesn't actually say what error message to send, or even if we should send one...
                         tracing::warn!(
-                            "bip_dht: AnnounceStorage failed to store contact information because it \
+                            "bip_dht: PeerStorage failed to store contact information because it \
                            is full..."
                         );
+                        self.metrics.error_sent(ErrorCode::ServerError);
                         ErrorMessage::new(
                             a.transaction_id().to_vec(),
                             ErrorCode::ServerError,
@@ -439,12 +582,15 @@ where
 
                 let opt_bootstrap = {
                     let mut routing_table = self.routing_table.write().unwrap();
+                    let blacklist = self.blacklist.lock().unwrap();
 
-                    // Add the payload nodes as questionable
-                    for (id, v4_addr) in f.nodes() {
-                        let sock_addr = SocketAddr::V4(v4_addr);
-
-                        routing_table.add_node(&Node::as_questionable(id, sock_addr));
+                    // Add the payload nodes as questionable, only taking those of the families we are able to reach
+                    let mut num_nodes = 0;
+                    if self.want.v4() {
+                        num_nodes += add_questionable_nodes(&mut routing_table, &blacklist, f.nodes());
+                    }
+                    if let (Some(nodes6), true) = (f.nodes6(), self.want.v6()) {
+                        num_nodes += add_questionable_nodes(&mut routing_table, &blacklist, nodes6);
                     }
 
                     // Match the response action id with our current actions
@@ -452,12 +598,28 @@ where
 
                     match table_action {
                         Some(TableAction::Refresh(_)) => {
-                            routing_table.add_node(&node);
+                            add_node(&mut routing_table, &blacklist, &node);
+                            None
+                        }
+                        Some(TableAction::Sweep(sweep)) => {
+                            add_node(&mut routing_table, &blacklist, &node);
+                            sweep.recv_response(num_nodes);
+                            None
+                        }
+                        Some(TableAction::Closest(_)) => {
+                            add_node(&mut routing_table, &blacklist, &node);
+                            None
+                        }
+                        Some(TableAction::Import(import)) => {
+                            add_node(&mut routing_table, &blacklist, &node);
+                            if import.recv_response() {
+                                self.handle_check_import_timeout(trans_id);
+                            }
                             None
                         }
                         Some(TableAction::Bootstrap(bootstrap, attempts)) => {
                             if !bootstrap.is_router(&node.addr()) {
-                                routing_table.add_node(&node);
+                                add_node(&mut routing_table, &blacklist, &node);
                             }
                             Some((bootstrap, attempts))
                         }
@@ -475,6 +637,37 @@ where
                     }
                 };
 
+                let opt_closest = self.table_actions.lock().unwrap().get(&trans_id.action_id()).cloned();
+                if let Some(TableAction::Closest(closest)) = opt_closest {
+                    let mut nodes: Vec<Node> = Vec::new();
+                    if self.want.v4() {
+                        nodes.extend(
+                            f.nodes()
+                                .iter()
+                                .map(|(id, v4_addr)| Node::as_questionable(id, SocketAddr::V4(v4_addr))),
+                        );
+                    }
+                    if let (Some(nodes6), true) = (f.nodes6(), self.want.v6()) {
+                        nodes.extend(
+                            nodes6
+                                .iter()
+                                .map(|(id, v6_addr)| Node::as_questionable(id, SocketAddr::V6(v6_addr))),
+                        );
+                    }
+
+                    let status = closest
+                        .recv_response(
+                            trans_id,
+                            nodes,
+                            self.routing_table.clone(),
+                            self.out_channel.clone(),
+                            &self.scheduled_task_sender,
+                        )
+                        .await;
+
+                    self.handle_closest_status(trans_id.action_id(), &closest, &status);
+                }
+
                 let bootstrap_complete = {
                     if let Some((bootstrap, attempts)) = opt_bootstrap {
                         let response = bootstrap
@@ -548,7 +741,7 @@ where
                 {
                     let mut routing_table = self.routing_table.write().unwrap();
 
-                    routing_table.add_node(&node);
+                    add_node(&mut routing_table, &self.blacklist.lock().unwrap(), &node);
                 }
 
                 let opt_lookup = {
@@ -570,6 +763,18 @@ where
                             );
                             None
                         }
+                        Some(TableAction::Sweep(_)) => {
+                            tracing::error!("bip_dht: Resolved a GetPeersResponse ActionID to a TableSweep...");
+                            None
+                        }
+                        Some(TableAction::Closest(_)) => {
+                            tracing::error!("bip_dht: Resolved a GetPeersResponse ActionID to a TableClosest...");
+                            None
+                        }
+                        Some(TableAction::Import(_)) => {
+                            tracing::error!("bip_dht: Resolved a GetPeersResponse ActionID to a TableImport...");
+                            None
+                        }
                         None => {
                             tracing::error!(
                                 "bip_dht: Resolved a TransactionID to a GetPeersResponse but no \
@@ -593,20 +798,9 @@ where
                         .await
                     {
                         LookupStatus::Searching => (),
-                        LookupStatus::Completed => {
-                            self.broadcast_dht_event(DhtEvent::LookupCompleted(lookup.info_hash()));
-                        }
+                        LookupStatus::Completed => self.handle_lookup_completed(lookup.info_hash(), lookup.stats()),
                         LookupStatus::Failed => self.handle_shutdown(ShutdownCause::Unspecified),
-                        LookupStatus::Values(values) => {
-                            for v4_addr in values {
-                                let sock_addr = SocketAddr::V4(v4_addr);
-                                self.handshaker
-                                    .lock()
-                                    .await
-                                    .connect(None, lookup.info_hash(), sock_addr)
-                                    .await;
-                            }
-                        }
+                        LookupStatus::Values(values) => self.handle_lookup_values(lookup.info_hash(), values).await,
                     }
                 }
             }
@@ -622,9 +816,16 @@ where
                 tracing::info!("bip_dht: Received an ErrorMessage...");
 
                 tracing::warn!("bip_dht: KRPC error message from {:?}: {:?}", addr, e);
+                self.metrics.error_received(e.error_code());
             }
             Err(e) => {
                 tracing::warn!("bip_dht: Error parsing KRPC message: {:?}", e);
+
+                let misbehavior = match e {
+                    DhtError::UnsolicitedResponse => Misbehavior::UnknownTransaction,
+                    _ => Misbehavior::MalformedMessage,
+                };
+                self.strike_node(addr, misbehavior);
             }
         }
     }
@@ -633,6 +834,41 @@ where
         self.event_notifiers.lock().unwrap().push(sender);
     }
 
+    fn handle_register_query_sender(&self, sender: mpsc::Sender<IncomingQuery>) {
+        self.query_notifiers.lock().unwrap().push(sender);
+    }
+
+    fn handle_routing_table(&self, sender: oneshot::Sender<Vec<NodeInfo>>) {
+        let nodes = self.routing_table.read().unwrap().node_infos();
+
+        if sender.send(nodes).is_err() {
+            tracing::warn!("bip_dht: Failed to send a routing table snapshot, receiver was dropped...");
+        }
+    }
+
+    fn handle_storage_stats(&self, sender: oneshot::Sender<StorageStats>) {
+        let stats = self.active_stores.stats();
+
+        if sender.send(stats).is_err() {
+            tracing::warn!("bip_dht: Failed to send storage stats, receiver was dropped...");
+        }
+    }
+
+    fn handle_blacklist(&self, sender: oneshot::Sender<Vec<BlacklistEntry>>) {
+        let entries = self.blacklist.lock().unwrap().entries();
+
+        if sender.send(entries).is_err() {
+            tracing::warn!("bip_dht: Failed to send a blacklist snapshot, receiver was dropped...");
+        }
+    }
+
+    /// Record a strike against the node at the given address for misbehaving.
+    fn strike_node(&self, addr: SocketAddr, misbehavior: Misbehavior) {
+        if self.blacklist.lock().unwrap().strike(addr, misbehavior) {
+            tracing::warn!("bip_dht: Blacklisted node {} after a {:?}...", addr, misbehavior);
+        }
+    }
+
     fn handle_start_bootstrap(&self, routers: Vec<Router>, nodes: Vec<SocketAddr>) -> BoxFuture<'_, ()> {
         async move {
             let router_iter = routers.into_iter().filter_map(|r| r.ipv4_addr().ok().map(SocketAddr::V4));
@@ -675,8 +911,28 @@ where
         .boxed()
     }
 
-    fn handle_start_lookup(&self, info_hash: InfoHash, should_announce: bool) -> BoxFuture<'_, ()> {
+    fn handle_start_lookup(&self, info_hash: InfoHash, should_announce: bool, use_cache: bool) -> BoxFuture<'_, ()> {
         async move {
+            // Announcing needs the tokens of the closest nodes, which only a new lookup will get
+            let cached = if use_cache && !should_announce {
+                self.lookup_cache.lock().unwrap().get(&info_hash)
+            } else {
+                None
+            };
+
+            if let Some((peers, stats)) = cached {
+                tracing::debug!(
+                    "bip_dht: Reusing {} peers of a recent lookup for {:?}...",
+                    peers.len(),
+                    info_hash
+                );
+
+                self.connect_peers(info_hash, peers).await;
+                self.broadcast_dht_event(DhtEvent::LookupCompleted(info_hash, stats));
+
+                return;
+            }
+
             let mid_generator = self.aid_generator.lock().unwrap().generate();
             let action_id = mid_generator.action_id();
 
@@ -685,7 +941,7 @@ where
                 self.future_actions
                     .lock()
                     .unwrap()
-                    .push(PostBootstrapAction::Lookup(info_hash, should_announce));
+                    .push(PostBootstrapAction::Lookup(info_hash, should_announce, use_cache));
             } else {
                 let node_id = self.routing_table.read().unwrap().node_id();
                 // Start the lookup right now if not bootstrapping
@@ -694,6 +950,8 @@ where
                     info_hash,
                     mid_generator,
                     should_announce,
+                    self.lookup_config,
+                    self.rtt_estimator.clone(),
                     self.routing_table.clone(),
                     self.out_channel.clone(),
                     self.scheduled_task_sender.clone(),
@@ -713,6 +971,153 @@ where
         .boxed()
     }
 
+    /// Hand the peers found by a lookup to the handshaker, remembering them for the lookup cache.
+    async fn handle_lookup_values(&self, info_hash: InfoHash, values: Vec<SocketAddrV4>) {
+        self.lookup_cache.lock().unwrap().add_peers(info_hash, &values);
+
+        self.connect_peers(info_hash, values).await;
+    }
+
+    fn handle_lookup_completed(&self, info_hash: InfoHash, stats: LookupStats) {
+        self.lookup_cache.lock().unwrap().complete(info_hash, stats);
+
+        self.broadcast_dht_event(DhtEvent::LookupCompleted(info_hash, stats));
+    }
+
+    async fn connect_peers(&self, info_hash: InfoHash, peers: Vec<SocketAddrV4>) {
+        for v4_addr in peers {
+            let sock_addr = SocketAddr::V4(v4_addr);
+
+            self.handshaker.lock().await.connect(None, info_hash, sock_addr).await;
+        }
+    }
+
+    fn handle_start_sweep(&self, targets: Vec<NodeId>, config: SweepConfig) {
+        if self.bootstrapping.load(Ordering::Acquire) {
+            // Queue it up if we are currently bootstrapping
+            self.future_actions
+                .lock()
+                .unwrap()
+                .push(PostBootstrapAction::Sweep(targets, config));
+        } else {
+            let mid_generator = self.aid_generator.lock().unwrap().generate();
+            let action_id = mid_generator.action_id();
+            let node_id = self.routing_table.read().unwrap().node_id();
+
+            let sweep = TableSweep::start(
+                node_id,
+                targets,
+                mid_generator,
+                config,
+                self.routing_table.clone(),
+                self.out_channel.clone(),
+                self.scheduled_task_sender.clone(),
+            );
+
+            self.table_actions
+                .lock()
+                .unwrap()
+                .insert(action_id, TableAction::Sweep(Arc::new(sweep)));
+        }
+    }
+
+    fn handle_start_closest(&self, target: NodeId, results: mpsc::Sender<NodeInfo>) -> BoxFuture<'_, ()> {
+        async move {
+            if self.bootstrapping.load(Ordering::Acquire) {
+                // Queue it up if we are currently bootstrapping
+                self.future_actions
+                    .lock()
+                    .unwrap()
+                    .push(PostBootstrapAction::Closest(target, results));
+                return;
+            }
+
+            let mid_generator = self.aid_generator.lock().unwrap().generate();
+            let action_id = mid_generator.action_id();
+
+            let closest = {
+                let routing_table = self.routing_table.read().unwrap();
+
+                Arc::new(TableClosest::new(
+                    routing_table.node_id(),
+                    target,
+                    mid_generator,
+                    self.lookup_config.alpha(),
+                    self.rtt_estimator.clone(),
+                    &routing_table,
+                    self.want,
+                    results,
+                ))
+            };
+
+            self.table_actions
+                .lock()
+                .unwrap()
+                .insert(action_id, TableAction::Closest(closest.clone()));
+
+            let status = closest
+                .start_request_round(
+                    self.routing_table.clone(),
+                    self.out_channel.clone(),
+                    &self.scheduled_task_sender,
+                )
+                .await;
+
+            self.handle_closest_status(action_id, &closest, &status);
+        }
+        .boxed()
+    }
+
+    async fn handle_start_import(&self, nodes: Vec<(NodeId, SocketAddr)>) {
+        // Imported nodes are not trusted with a place in our routing table until they have responded to us
+        let addrs: Vec<SocketAddr> = {
+            let blacklist = self.blacklist.lock().unwrap();
+
+            nodes
+                .into_iter()
+                .map(|(_, addr)| addr)
+                .filter(|addr| match addr {
+                    SocketAddr::V4(_) => self.want.v4(),
+                    SocketAddr::V6(_) => self.want.v6(),
+                })
+                .filter(|addr| !blacklist.contains(addr))
+                .collect()
+        };
+
+        let mid_generator = self.aid_generator.lock().unwrap().generate();
+        let action_id = mid_generator.action_id();
+        let node_id = self.routing_table.read().unwrap().node_id();
+
+        let import = Box::pin(TableImport::start(
+            node_id,
+            addrs,
+            mid_generator,
+            self.out_channel.clone(),
+            self.scheduled_task_sender.clone(),
+        ))
+        .await;
+
+        if import.stats().queried_nodes() == 0 {
+            self.broadcast_dht_event(DhtEvent::ImportCompleted(import.stats()));
+        } else {
+            self.table_actions
+                .lock()
+                .unwrap()
+                .insert(action_id, TableAction::Import(Arc::new(import)));
+        }
+    }
+
+    fn handle_closest_status(&self, action_id: ActionID, closest: &TableClosest, status: &ClosestStatus) {
+        match status {
+            ClosestStatus::Searching => (),
+            ClosestStatus::Completed => {
+                self.table_actions.lock().unwrap().remove(&action_id);
+                closest.finish();
+            }
+            ClosestStatus::Failed => self.handle_shutdown(ShutdownCause::Unspecified),
+        }
+    }
+
     fn handle_shutdown(&self, cause: ShutdownCause) {
         self.broadcast_dht_event(DhtEvent::ShuttingDown(cause));
     }
@@ -731,6 +1136,15 @@ where
             ScheduledTaskCheck::LookupEndGame(trans_id) => {
                 self.handle_check_lookup_endgame(trans_id).await;
             }
+            ScheduledTaskCheck::SweepTimeout(trans_id) => {
+                self.handle_check_sweep_timeout(trans_id);
+            }
+            ScheduledTaskCheck::ClosestTimeout(trans_id) => {
+                self.handle_check_closest_timeout(trans_id).await;
+            }
+            ScheduledTaskCheck::ImportTimeout(trans_id) => {
+                self.handle_check_import_timeout(trans_id);
+            }
         }
     }
 
@@ -761,6 +1175,18 @@ where
                 );
                 None
             }
+            Some(TableAction::Sweep(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table refresh but TableSweep found...");
+                None
+            }
+            Some(TableAction::Closest(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table refresh but TableClosest found...");
+                None
+            }
+            Some(TableAction::Import(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table refresh but TableImport found...");
+                None
+            }
             None => {
                 tracing::error!(
                     "bip_dht: Resolved a TransactionID to a check table refresh but no action \
@@ -774,6 +1200,17 @@ where
             Some(RefreshStatus::Refreshing) | None => (),
             Some(RefreshStatus::Failed) => self.handle_shutdown(ShutdownCause::Unspecified),
         }
+
+        self.report_sizes();
+    }
+
+    /// Report the size of our routing table and announce storage to the metrics.
+    fn report_sizes(&self) {
+        let nodes = self.routing_table.read().unwrap().node_infos().len();
+        self.metrics.routing_table_size(nodes);
+
+        let stats = self.active_stores.stats();
+        self.metrics.stored_announces(stats);
     }
 
     async fn handle_check_bootstrap_timeout(&self, trans_id: TransactionID) {
@@ -807,6 +1244,18 @@ where
                     );
                     None
                 }
+                Some(TableAction::Sweep(_)) => {
+                    tracing::error!("bip_dht: Resolved a TransactionID to a check table bootstrap but TableSweep found...");
+                    None
+                }
+                Some(TableAction::Closest(_)) => {
+                    tracing::error!("bip_dht: Resolved a TransactionID to a check table bootstrap but TableClosest found...");
+                    None
+                }
+                Some(TableAction::Import(_)) => {
+                    tracing::error!("bip_dht: Resolved a TransactionID to a check table bootstrap but TableImport found...");
+                    None
+                }
                 None => {
                     tracing::error!(
                         "bip_dht: Resolved a TransactionID to a check table bootstrap but no \
@@ -862,6 +1311,7 @@ where
                     )
                     .await,
                 lookup.info_hash(),
+                lookup.stats(),
             )),
             Some(TableAction::Bootstrap(_, _)) => {
                 tracing::error!(
@@ -877,6 +1327,18 @@ where
                 );
                 None
             }
+            Some(TableAction::Sweep(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableSweep found...");
+                None
+            }
+            Some(TableAction::Closest(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableClosest found...");
+                None
+            }
+            Some(TableAction::Import(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableImport found...");
+                None
+            }
             None => {
                 tracing::error!(
                     "bip_dht: Resolved a TransactionID to a check table lookup but no action \
@@ -887,19 +1349,10 @@ where
         };
 
         match opt_lookup_info {
-            Some((LookupStatus::Searching, _)) | None => (),
-            Some((LookupStatus::Completed, info_hash)) => {
-                self.broadcast_dht_event(DhtEvent::LookupCompleted(info_hash));
-            }
-            Some((LookupStatus::Failed, _)) => self.handle_shutdown(ShutdownCause::Unspecified),
-            Some((LookupStatus::Values(v), info_hash)) => {
-                // Add values to handshaker
-                for v4_addr in v {
-                    let sock_addr = SocketAddr::V4(v4_addr);
-
-                    self.handshaker.lock().await.connect(None, info_hash, sock_addr).await;
-                }
-            }
+            Some((LookupStatus::Searching, _, _)) | None => (),
+            Some((LookupStatus::Completed, info_hash, stats)) => self.handle_lookup_completed(info_hash, stats),
+            Some((LookupStatus::Failed, _, _)) => self.handle_shutdown(ShutdownCause::Unspecified),
+            Some((LookupStatus::Values(values), info_hash, _)) => self.handle_lookup_values(info_hash, values).await,
         }
     }
 
@@ -909,12 +1362,14 @@ where
         let opt_lookup_info = match table_actions {
             Some(TableAction::Lookup(lookup)) => {
                 let handshaker_port = self.handshaker.lock().await.port();
+                let connect_port = self.announce_port.connect_port(handshaker_port);
 
                 Some((
                     lookup
-                        .recv_finished(handshaker_port, self.routing_table.clone(), self.out_channel.clone())
+                        .recv_finished(connect_port, self.routing_table.clone(), self.out_channel.clone())
                         .await,
                     lookup.info_hash(),
+                    lookup.stats(),
                 ))
             }
             Some(TableAction::Bootstrap(_, _)) => {
@@ -931,6 +1386,18 @@ where
                 );
                 None
             }
+            Some(TableAction::Sweep(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableSweep found...");
+                None
+            }
+            Some(TableAction::Closest(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableClosest found...");
+                None
+            }
+            Some(TableAction::Import(_)) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check table lookup but TableImport found...");
+                None
+            }
             None => {
                 tracing::error!(
                     "bip_dht: Resolved a TransactionID to a check table lookup but no action \
@@ -941,22 +1408,84 @@ where
         };
 
         match opt_lookup_info {
-            Some((LookupStatus::Searching, _)) | None => (),
-            Some((LookupStatus::Completed, info_hash)) => {
-                self.broadcast_dht_event(DhtEvent::LookupCompleted(info_hash));
+            Some((LookupStatus::Searching, _, _)) | None => (),
+            Some((LookupStatus::Completed, info_hash, stats)) => self.handle_lookup_completed(info_hash, stats),
+            Some((LookupStatus::Failed, _, _)) => self.handle_shutdown(ShutdownCause::Unspecified),
+            Some((LookupStatus::Values(values), info_hash, _)) => self.handle_lookup_values(info_hash, values).await,
+        }
+    }
+
+    fn handle_check_sweep_timeout(&self, trans_id: TransactionID) {
+        let table_action = self.table_actions.lock().unwrap().remove(&trans_id.action_id());
+
+        match table_action {
+            Some(TableAction::Sweep(sweep)) => self.broadcast_dht_event(DhtEvent::SweepCompleted(sweep.stats())),
+            Some(other) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check sweep timeout but a different action found...");
+                self.table_actions.lock().unwrap().insert(trans_id.action_id(), other);
             }
-            Some((LookupStatus::Failed, _)) => self.handle_shutdown(ShutdownCause::Unspecified),
-            Some((LookupStatus::Values(v), info_hash)) => {
-                // Add values to handshaker
-                for v4_addr in v {
-                    let sock_addr = SocketAddr::V4(v4_addr);
+            None => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check sweep timeout but no action found...");
+            }
+        }
+    }
 
-                    self.handshaker.lock().await.connect(None, info_hash, sock_addr).await;
-                }
+    fn handle_check_import_timeout(&self, trans_id: TransactionID) {
+        let table_action = self.table_actions.lock().unwrap().remove(&trans_id.action_id());
+
+        match table_action {
+            Some(TableAction::Import(import)) => self.broadcast_dht_event(DhtEvent::ImportCompleted(import.stats())),
+            Some(other) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check import timeout but a different action found...");
+                self.table_actions.lock().unwrap().insert(trans_id.action_id(), other);
             }
+            // Every imported node responded before the timeout
+            None => (),
         }
     }
 
+    async fn handle_check_closest_timeout(&self, trans_id: TransactionID) {
+        let table_action = self.table_actions.lock().unwrap().get(&trans_id.action_id()).cloned();
+
+        match table_action {
+            Some(TableAction::Closest(closest)) => {
+                let status = closest
+                    .recv_timeout(
+                        trans_id,
+                        self.routing_table.clone(),
+                        self.out_channel.clone(),
+                        &self.scheduled_task_sender,
+                    )
+                    .await;
+
+                self.handle_closest_status(trans_id.action_id(), &closest, &status);
+            }
+            Some(_) => {
+                tracing::error!("bip_dht: Resolved a TransactionID to a check closest timeout but a different action found...");
+            }
+            // The lookup may have completed before the query timed out
+            None => (),
+        }
+    }
+
+    fn broadcast_incoming_query(&self, request: &RequestType<'_>, addr: SocketAddr) {
+        let query = match request {
+            RequestType::Ping(p) => IncomingQuery::new(QueryKind::Ping, p.node_id(), addr, None),
+            RequestType::FindNode(f) => IncomingQuery::new(QueryKind::FindNode, f.node_id(), addr, Some(f.target_id())),
+            RequestType::GetPeers(g) => IncomingQuery::new(QueryKind::GetPeers, g.node_id(), addr, Some(g.info_hash())),
+            RequestType::AnnouncePeer(a) => IncomingQuery::new(QueryKind::AnnouncePeer, a.node_id(), addr, Some(a.info_hash())),
+        };
+        self.metrics.query_received(query.kind());
+
+        let mut query_notifiers = self.query_notifiers.lock().unwrap();
+
+        // Slow monitors miss queries rather than stalling the DHT, only closed ones are removed
+        query_notifiers.retain(|send| match send.clone().try_send(query) {
+            Ok(()) => true,
+            Err(e) => !e.is_disconnected(),
+        });
+    }
+
     fn broadcast_dht_event(&self, event: DhtEvent) {
         self.event_notifiers
             .lock()
@@ -980,9 +1509,15 @@ where
         let mut future_actions = self.future_actions.lock().unwrap().split_off(0);
         for table_action in future_actions.drain(..) {
             match table_action {
-                PostBootstrapAction::Lookup(info_hash, should_announce) => {
+                PostBootstrapAction::Lookup(info_hash, should_announce, use_cache) => {
                     drop(table_action);
-                    self.handle_start_lookup(info_hash, should_announce).await;
+                    self.handle_start_lookup(info_hash, should_announce, use_cache).await;
+                }
+                PostBootstrapAction::Sweep(targets, config) => {
+                    self.handle_start_sweep(targets, config);
+                }
+                PostBootstrapAction::Closest(target, results) => {
+                    self.handle_start_closest(target, results).await;
                 }
                 PostBootstrapAction::Refresh(refresh, trans_id) => {
                     {
@@ -999,6 +1534,55 @@ where
 
 // ----------------------------------------------------------------------------//
 
+/// Add the given node to the routing table, unless it is blacklisted.
+fn add_node(routing_table: &mut RoutingTable, blacklist: &Blacklist, node: &Node) {
+    if !blacklist.contains(&node.addr()) {
+        routing_table.add_node(node);
+    }
+}
+
+/// Add the given compact nodes, of either address family, to the routing table as questionable.
+/// Returns the number of nodes in the compact node info, including any that are blacklisted.
+/// Compact IPv4 and IPv6 node info for the nodes closest to the target, up to 8 of each wanted family.
+fn closest_compact_nodes(routing_table: &RoutingTable, target: NodeId, want: Want) -> (Vec<u8>, Vec<u8>) {
+    let mut closest_nodes_bytes = Vec::with_capacity(26 * 8);
+    let mut closest_nodes6_bytes = Vec::with_capacity(38 * 8);
+    let (mut num_nodes, mut num_nodes6) = (0, 0);
+
+    for node in routing_table.closest_nodes(target) {
+        match node.addr() {
+            SocketAddr::V4(_) if want.v4() && num_nodes < 8 => {
+                closest_nodes_bytes.extend_from_slice(&node.encode());
+                num_nodes += 1;
+            }
+            SocketAddr::V6(_) if want.v6() && num_nodes6 < 8 => {
+                closest_nodes6_bytes.extend_from_slice(&node.encode6());
+                num_nodes6 += 1;
+            }
+            _ => (),
+        }
+
+        if (!want.v4() || num_nodes == 8) && (!want.v6() || num_nodes6 == 8) {
+            break;
+        }
+    }
+
+    (closest_nodes_bytes, closest_nodes6_bytes)
+}
+
+fn add_questionable_nodes<'a, N>(routing_table: &mut RoutingTable, blacklist: &Blacklist, nodes: N) -> usize
+where
+    N: CompactNodes<'a>,
+{
+    let mut num_nodes = 0;
+    for (id, addr) in nodes.iter() {
+        add_node(routing_table, blacklist, &Node::as_questionable(id, addr.into()));
+        num_nodes += 1;
+    }
+
+    num_nodes
+}
+
 /// Attempt to rebootstrap or shutdown the dht if we have no nodes after rebootstrapping multiple time.
 /// Returns None if the DHT is shutting down, Some(true) if the rebootstrap process started, Some(false) if a rebootstrap is not necessary.
 fn attempt_rebootstrap(
@@ -0,0 +1,136 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use futures::channel::mpsc;
+use futures::SinkExt as _;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Duration};
+use util::bt::NodeId;
+
+use crate::message::find_node::FindNodeRequest;
+use crate::transaction::MIDGenerator;
+use crate::worker::ScheduledTaskCheck;
+
+const IMPORT_RESPONSE_TIMEOUT_MS: u64 = 3000;
+
+/// Statistics gathered while importing a `RoutingSnapshot`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct ImportStats {
+    queried_nodes: usize,
+    responded_nodes: usize,
+}
+
+impl ImportStats {
+    /// Number of imported nodes that were checked, leaving out those blacklisted or of an unreachable address family.
+    #[must_use]
+    pub fn queried_nodes(&self) -> usize {
+        self.queried_nodes
+    }
+
+    /// Number of imported nodes that responded, and so were merged into our routing table.
+    #[must_use]
+    pub fn responded_nodes(&self) -> usize {
+        self.responded_nodes
+    }
+}
+
+/// Checks the liveness of imported nodes with a `find_node` query for our own id.
+///
+/// Only the nodes that respond are added to our routing table, along with the nodes they return.
+#[allow(clippy::module_name_repetitions)]
+pub struct TableImport {
+    queried_nodes: usize,
+    responded_nodes: AtomicUsize,
+    // Dropping the import aborts any pending timeout check
+    _tasks: Mutex<JoinSet<()>>,
+}
+
+impl TableImport {
+    /// Start an import, queries are sent to every node right away.
+    pub async fn start(
+        node_id: NodeId,
+        nodes: Vec<SocketAddr>,
+        mut id_generator: MIDGenerator,
+        mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        mut scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
+    ) -> TableImport {
+        let mut queried_nodes = 0;
+
+        for addr in nodes {
+            let trans_id = id_generator.generate();
+            let find_node_msg = FindNodeRequest::new(trans_id.as_ref(), node_id, node_id).encode();
+
+            if out.send((find_node_msg, addr)).await.is_err() {
+                tracing::error!("bip_dht: TableImport failed to send a find node message to the out channel...");
+                break;
+            }
+
+            queried_nodes += 1;
+        }
+
+        let mut tasks = JoinSet::new();
+        tasks.spawn(async move {
+            sleep(Duration::from_millis(IMPORT_RESPONSE_TIMEOUT_MS)).await;
+
+            // Only the action id will be used
+            let trans_id = id_generator.generate();
+            if scheduled_task_sender
+                .send(ScheduledTaskCheck::ImportTimeout(trans_id))
+                .await
+                .is_err()
+            {
+                tracing::error!("bip_dht: TableImport failed to send an import timeout to the scheduled channel...");
+            }
+        });
+
+        TableImport {
+            queried_nodes,
+            responded_nodes: AtomicUsize::default(),
+            _tasks: Mutex::new(tasks),
+        }
+    }
+
+    /// Record a response from one of the imported nodes, returning true once every node has responded.
+    pub fn recv_response(&self) -> bool {
+        self.responded_nodes.fetch_add(1, Ordering::Relaxed) + 1 >= self.queried_nodes
+    }
+
+    pub fn stats(&self) -> ImportStats {
+        ImportStats {
+            queried_nodes: self.queried_nodes,
+            responded_nodes: self.responded_nodes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+    use futures::StreamExt as _;
+    use util::bt::{self, NodeId};
+    use util::test as bip_test;
+
+    use crate::transaction::AIDGenerator;
+    use crate::worker::import::TableImport;
+
+    #[tokio::test]
+    async fn positive_import_queries_every_node() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let addrs = bip_test::dummy_block_socket_addrs(3);
+
+        let (out, out_recv) = mpsc::channel(16);
+        let (scheduled, _scheduled_recv) = mpsc::channel(1);
+
+        let import = TableImport::start(node_id, addrs.clone(), AIDGenerator::new().generate(), out, scheduled).await;
+
+        let queried: Vec<_> = out_recv.take(3).map(|(_, addr)| addr).collect().await;
+        assert_eq!(addrs, queried);
+
+        assert!(!import.recv_response());
+        assert!(!import.recv_response());
+        assert!(import.recv_response());
+        assert_eq!(3, import.stats().responded_nodes());
+    }
+}
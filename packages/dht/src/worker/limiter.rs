@@ -0,0 +1,450 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use bencode::{BDecodeOpt, BRefAccess, BencodeRef};
+
+use crate::metrics::SharedMetrics;
+use crate::worker::QueryKind;
+
+const DEFAULT_GLOBAL_QUERIES_PER_SECOND: u32 = 250;
+const DEFAULT_GLOBAL_BURST: u32 = 100;
+const DEFAULT_NODE_QUERIES_PER_SECOND: u32 = 2;
+const DEFAULT_NODE_BURST: u32 = 10;
+const DEFAULT_RESPONSE_TIMEOUT_SECS: u64 = 20;
+
+// Once this many nodes are tracked, nodes whose buckets have refilled are forgotten
+const NODE_BUCKET_PRUNE_THRESHOLD: usize = 1024;
+
+const TRANSACTION_ID_KEY: &[u8] = b"t";
+const MESSAGE_TYPE_KEY: &[u8] = b"y";
+const REQUEST_TYPE_KEY: &[u8] = b"q";
+const RESPONSE_TYPE_KEY: &[u8] = b"r";
+
+/// Configures the rate at which the DHT sends queries to remote nodes.
+///
+/// Queries exceeding the global rate are delayed, while queries exceeding the rate for a
+/// single node are dropped, and will time out like any other unanswered query.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct RateLimitConfig {
+    global_queries_per_second: u32,
+    global_burst: u32,
+    node_queries_per_second: u32,
+    node_burst: u32,
+    response_timeout: Duration,
+}
+
+impl RateLimitConfig {
+    /// Sets the sustained rate at which queries will be sent across all nodes.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_global_queries_per_second(mut self, queries_per_second: u32) -> RateLimitConfig {
+        self.global_queries_per_second = queries_per_second.max(1);
+        self
+    }
+
+    /// Sets the number of queries that may be sent at once across all nodes, before being limited to the sustained rate.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_global_burst(mut self, burst: u32) -> RateLimitConfig {
+        self.global_burst = burst.max(1);
+        self
+    }
+
+    /// Sets the sustained rate at which queries will be sent to a single node.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_node_queries_per_second(mut self, queries_per_second: u32) -> RateLimitConfig {
+        self.node_queries_per_second = queries_per_second.max(1);
+        self
+    }
+
+    /// Sets the number of queries that may be sent at once to a single node, before being limited to the sustained rate.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_node_burst(mut self, burst: u32) -> RateLimitConfig {
+        self.node_burst = burst.max(1);
+        self
+    }
+
+    /// Sets how long a response to a query is accepted for after the query was sent.
+    #[must_use]
+    pub fn with_response_timeout(mut self, timeout: Duration) -> RateLimitConfig {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Gets the sustained rate at which queries will be sent across all nodes.
+    #[must_use]
+    pub fn global_queries_per_second(&self) -> u32 {
+        self.global_queries_per_second
+    }
+
+    /// Gets the number of queries that may be sent at once across all nodes.
+    #[must_use]
+    pub fn global_burst(&self) -> u32 {
+        self.global_burst
+    }
+
+    /// Gets the sustained rate at which queries will be sent to a single node.
+    #[must_use]
+    pub fn node_queries_per_second(&self) -> u32 {
+        self.node_queries_per_second
+    }
+
+    /// Gets the number of queries that may be sent at once to a single node.
+    #[must_use]
+    pub fn node_burst(&self) -> u32 {
+        self.node_burst
+    }
+
+    /// Gets how long a response to a query is accepted for.
+    #[must_use]
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            global_queries_per_second: DEFAULT_GLOBAL_QUERIES_PER_SECOND,
+            global_burst: DEFAULT_GLOBAL_BURST,
+            node_queries_per_second: DEFAULT_NODE_QUERIES_PER_SECOND,
+            node_burst: DEFAULT_NODE_BURST,
+            response_timeout: Duration::from_secs(DEFAULT_RESPONSE_TIMEOUT_SECS),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Token bucket refilled at a constant rate, up to its burst size.
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn full(burst: u32, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: f64::from(burst),
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, rate: u32, burst: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * f64::from(rate)).min(f64::from(burst));
+        self.last_refill = now;
+    }
+
+    /// Time until a token is available, if one is not available now.
+    fn wait_time(&self, rate: u32) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / f64::from(rate)))
+        }
+    }
+
+    fn is_full(&self, burst: u32) -> bool {
+        self.tokens >= f64::from(burst)
+    }
+}
+
+/// Whether a query may be sent right now.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum QueryPermit {
+    /// Query may be sent, and a response to it is now expected.
+    Granted,
+    /// Global rate was exceeded, the query may be sent after the given amount of time.
+    Wait(Duration),
+    /// Rate for the node was exceeded, the query should not be sent.
+    Denied,
+}
+
+/// Limits the rate of outgoing queries, and tracks the queries that are awaiting a response.
+///
+/// Responses are only accepted from the address a query with the same transaction id was sent to,
+/// so that spoofed responses cannot be used to poison our routing table.
+#[allow(clippy::module_name_repetitions)]
+pub struct QueryLimiter {
+    config: RateLimitConfig,
+    global: TokenBucket,
+    nodes: HashMap<SocketAddr, TokenBucket>,
+    // Expiration and send time of each query
+    outstanding: HashMap<(Vec<u8>, SocketAddr), (Instant, Instant)>,
+    expirations: VecDeque<(Instant, Vec<u8>, SocketAddr)>,
+    metrics: SharedMetrics,
+}
+
+impl QueryLimiter {
+    /// Create a `QueryLimiter` that reports response latencies and timeouts to the given metrics.
+    pub fn new(config: RateLimitConfig, metrics: SharedMetrics) -> QueryLimiter {
+        QueryLimiter {
+            config,
+            global: TokenBucket::full(config.global_burst(), Instant::now()),
+            nodes: HashMap::new(),
+            outstanding: HashMap::new(),
+            expirations: VecDeque::new(),
+            metrics,
+        }
+    }
+
+    /// Attempt to send a query with the given transaction id to the given address.
+    pub fn send_query(&mut self, trans_id: &[u8], addr: SocketAddr, now: Instant) -> QueryPermit {
+        self.expire_queries(now);
+
+        let (node_rate, node_burst) = (self.config.node_queries_per_second(), self.config.node_burst());
+        let (global_rate, global_burst) = (self.config.global_queries_per_second(), self.config.global_burst());
+
+        if self.nodes.len() >= NODE_BUCKET_PRUNE_THRESHOLD {
+            self.nodes.retain(|_, bucket| {
+                bucket.refill(node_rate, node_burst, now);
+                !bucket.is_full(node_burst)
+            });
+        }
+
+        let node = self.nodes.entry(addr).or_insert_with(|| TokenBucket::full(node_burst, now));
+        node.refill(node_rate, node_burst, now);
+        if node.wait_time(node_rate).is_some() {
+            return QueryPermit::Denied;
+        }
+
+        self.global.refill(global_rate, global_burst, now);
+        if let Some(wait) = self.global.wait_time(global_rate) {
+            return QueryPermit::Wait(wait);
+        }
+
+        node.tokens -= 1.0;
+        self.global.tokens -= 1.0;
+
+        let expires = now + self.config.response_timeout();
+        self.outstanding.insert((trans_id.to_vec(), addr), (expires, now));
+        self.expirations.push_back((expires, trans_id.to_vec(), addr));
+
+        QueryPermit::Granted
+    }
+
+    /// Whether a response with the given transaction id from the given address answers one of our queries.
+    ///
+    /// A query is only answered once, so any further responses to it are rejected.
+    pub fn recv_response(&mut self, trans_id: &[u8], addr: SocketAddr, now: Instant) -> bool {
+        self.expire_queries(now);
+
+        match self.outstanding.remove(&(trans_id.to_vec(), addr)) {
+            Some((_, sent)) => {
+                self.metrics.response_received(now.saturating_duration_since(sent));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a query with the given transaction id is awaiting a response from an address other than the given one.
+    pub fn sent_elsewhere(&self, trans_id: &[u8], addr: SocketAddr) -> bool {
+        self.outstanding
+            .keys()
+            .any(|(outstanding, outstanding_addr)| outstanding == trans_id && *outstanding_addr != addr)
+    }
+
+    fn expire_queries(&mut self, now: Instant) {
+        while self.expirations.front().is_some_and(|(expires, _, _)| *expires <= now) {
+            let (expires, trans_id, addr) = self.expirations.pop_front().unwrap();
+            let key = (trans_id, addr);
+
+            // The same query may have been sent again since, with a later expiration
+            if self
+                .outstanding
+                .get(&key)
+                .is_some_and(|(outstanding, _)| *outstanding == expires)
+            {
+                self.outstanding.remove(&key);
+                self.metrics.query_timed_out();
+            }
+        }
+    }
+}
+
+/// Transaction id of the given message if it is a query, along with the kind of query if it is known.
+pub fn query_transaction_id(message: &[u8]) -> Option<(Vec<u8>, Option<QueryKind>)> {
+    let bencode = BencodeRef::decode(message, BDecodeOpt::default()).ok()?;
+    let dict = bencode.dict()?;
+
+    if dict.lookup(MESSAGE_TYPE_KEY)?.bytes()? != REQUEST_TYPE_KEY {
+        return None;
+    }
+
+    let trans_id = dict.lookup(TRANSACTION_ID_KEY)?.bytes()?.to_vec();
+    let opt_kind = dict
+        .lookup(REQUEST_TYPE_KEY)
+        .and_then(BRefAccess::bytes)
+        .and_then(QueryKind::from_method);
+
+    Some((trans_id, opt_kind))
+}
+
+/// Transaction id of the given message, if it is a response.
+pub fn response_transaction_id<'a>(bencode: &'a BencodeRef<'a>) -> Option<&'a [u8]> {
+    let dict = bencode.dict()?;
+
+    if dict.lookup(MESSAGE_TYPE_KEY)?.bytes()? != RESPONSE_TYPE_KEY {
+        return None;
+    }
+
+    dict.lookup(TRANSACTION_ID_KEY)?.bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use bencode::{ben_bytes, ben_map};
+
+    use super::{QueryLimiter, QueryPermit, RateLimitConfig};
+    use crate::metrics::{DhtMetrics, SharedMetrics};
+    use crate::worker::QueryKind;
+
+    #[derive(Default)]
+    struct RecordedMetrics {
+        latencies: Mutex<Vec<Duration>>,
+        timeouts: Mutex<usize>,
+    }
+
+    impl DhtMetrics for RecordedMetrics {
+        fn response_received(&self, latency: Duration) {
+            self.latencies.lock().unwrap().push(latency);
+        }
+
+        fn query_timed_out(&self) {
+            *self.timeouts.lock().unwrap() += 1;
+        }
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn positive_accept_response_from_queried_addr() {
+        let mut limiter = QueryLimiter::new(RateLimitConfig::default(), SharedMetrics::default());
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+
+        assert!(limiter.recv_response(b"aa", addr(1), now));
+    }
+
+    #[test]
+    fn negative_reject_response_from_other_addr() {
+        let mut limiter = QueryLimiter::new(RateLimitConfig::default(), SharedMetrics::default());
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+
+        assert!(!limiter.recv_response(b"aa", addr(2), now));
+        assert!(limiter.sent_elsewhere(b"aa", addr(2)));
+        assert!(!limiter.recv_response(b"ab", addr(1), now));
+        assert!(!limiter.sent_elsewhere(b"ab", addr(1)));
+        assert!(limiter.recv_response(b"aa", addr(1), now));
+        assert!(!limiter.recv_response(b"aa", addr(1), now));
+    }
+
+    #[test]
+    fn negative_reject_response_after_timeout() {
+        let config = RateLimitConfig::default().with_response_timeout(Duration::from_secs(1));
+        let mut limiter = QueryLimiter::new(config, SharedMetrics::default());
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+
+        assert!(!limiter.recv_response(b"aa", addr(1), now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn negative_deny_queries_over_node_burst() {
+        let config = RateLimitConfig::default().with_node_burst(2).with_node_queries_per_second(1);
+        let mut limiter = QueryLimiter::new(config, SharedMetrics::default());
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+        assert_eq!(limiter.send_query(b"ab", addr(1), now), QueryPermit::Granted);
+        assert_eq!(limiter.send_query(b"ac", addr(1), now), QueryPermit::Denied);
+        assert_eq!(limiter.send_query(b"ac", addr(2), now), QueryPermit::Granted);
+
+        assert_eq!(
+            limiter.send_query(b"ac", addr(1), now + Duration::from_secs(1)),
+            QueryPermit::Granted
+        );
+    }
+
+    #[test]
+    fn negative_delay_queries_over_global_burst() {
+        let config = RateLimitConfig::default()
+            .with_global_burst(1)
+            .with_global_queries_per_second(2);
+        let mut limiter = QueryLimiter::new(config, SharedMetrics::default());
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+        assert!(matches!(limiter.send_query(b"ab", addr(2), now), QueryPermit::Wait(_)));
+        assert_eq!(
+            limiter.send_query(b"ab", addr(2), now + Duration::from_millis(500)),
+            QueryPermit::Granted
+        );
+    }
+
+    #[test]
+    fn positive_report_response_latency() {
+        let metrics = Arc::new(RecordedMetrics::default());
+        let mut limiter = QueryLimiter::new(RateLimitConfig::default(), SharedMetrics::new(metrics.clone()));
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+        assert!(limiter.recv_response(b"aa", addr(1), now + Duration::from_millis(300)));
+        assert!(!limiter.recv_response(b"aa", addr(1), now + Duration::from_millis(400)));
+
+        assert_eq!(vec![Duration::from_millis(300)], *metrics.latencies.lock().unwrap());
+        assert_eq!(0, *metrics.timeouts.lock().unwrap());
+    }
+
+    #[test]
+    fn positive_report_query_timeout() {
+        let metrics = Arc::new(RecordedMetrics::default());
+        let config = RateLimitConfig::default().with_response_timeout(Duration::from_secs(1));
+        let mut limiter = QueryLimiter::new(config, SharedMetrics::new(metrics.clone()));
+        let now = Instant::now();
+
+        assert_eq!(limiter.send_query(b"aa", addr(1), now), QueryPermit::Granted);
+        assert_eq!(limiter.send_query(b"ab", addr(1), now), QueryPermit::Granted);
+        assert!(limiter.recv_response(b"ab", addr(1), now));
+
+        assert!(!limiter.recv_response(b"aa", addr(1), now + Duration::from_secs(2)));
+
+        assert_eq!(1, *metrics.timeouts.lock().unwrap());
+    }
+
+    #[test]
+    fn positive_query_transaction_id_with_kind() {
+        let message = (ben_map! {
+            "t" => ben_bytes!("aa"),
+            "y" => ben_bytes!("q"),
+            "q" => ben_bytes!("get_peers")
+        })
+        .encode();
+
+        assert_eq!(
+            Some((b"aa".to_vec(), Some(QueryKind::GetPeers))),
+            super::query_transaction_id(&message)
+        );
+    }
+}
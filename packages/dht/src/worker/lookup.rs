@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::net::{SocketAddr, SocketAddrV4};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
@@ -10,8 +10,7 @@ use futures::future::BoxFuture;
 use futures::{FutureExt, SinkExt as _};
 use tokio::task::JoinSet;
 use tokio::time::{sleep, Duration, Instant};
-use util::bt::{self, InfoHash, NodeId};
-use util::net;
+use util::bt::{InfoHash, NodeId};
 use util::sha::ShaHash;
 
 use crate::message::announce_peer::{AnnouncePeerRequest, ConnectPort};
@@ -22,15 +21,193 @@ use crate::routing::table::RoutingTable;
 use crate::transaction::{MIDGenerator, TransactionID};
 use crate::worker::ScheduledTaskCheck;
 
-const LOOKUP_TIMEOUT_MS: u64 = 1500;
-const ENDGAME_TIMEOUT_MS: u64 = 1500;
+const DEFAULT_ALPHA: usize = 4;
+const DEFAULT_INITIAL_TIMEOUT_MS: u64 = 1500;
+const DEFAULT_MIN_TIMEOUT_MS: u64 = 250;
+const DEFAULT_MAX_TIMEOUT_MS: u64 = 3000;
 
-const INITIAL_PICK_NUM: usize = 4; // Alpha
-const ITERATIVE_PICK_NUM: usize = 3; // Beta
 const ANNOUNCE_PICK_NUM: usize = 8; // # Announces
 
-type Distance = ShaHash;
-type DistanceToBeat = ShaHash;
+pub(crate) type Distance = ShaHash;
+
+/// Port announced to the nodes closest to an `InfoHash` after a lookup.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum AnnouncePort {
+    /// Announce the port that the handshaker is listening on.
+    #[default]
+    Handshaker,
+    /// Ask the nodes to use the source port of our announce, for when peers
+    /// should connect to us on the same port as our DHT socket (for example with uTP).
+    Implied,
+    /// Announce the given port.
+    Explicit(u16),
+}
+
+impl AnnouncePort {
+    /// Resolve the port that will be sent in the announce, given the port of the handshaker.
+    pub(crate) fn connect_port(self, handshaker_port: u16) -> ConnectPort {
+        match self {
+            AnnouncePort::Handshaker => ConnectPort::Explicit(handshaker_port),
+            AnnouncePort::Implied => ConnectPort::Implied,
+            AnnouncePort::Explicit(port) => ConnectPort::Explicit(port),
+        }
+    }
+}
+
+/// Configures how iterative lookups are performed by the DHT.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct LookupConfig {
+    alpha: usize,
+    initial_timeout: Duration,
+    min_timeout: Duration,
+    max_timeout: Duration,
+}
+
+impl LookupConfig {
+    /// Sets the number of queries that a lookup will keep in flight at once.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_alpha(mut self, alpha: usize) -> LookupConfig {
+        self.alpha = alpha.max(1);
+        self
+    }
+
+    /// Sets the timeout used for queries before any round trip times have been observed.
+    #[must_use]
+    pub fn with_initial_timeout(mut self, timeout: Duration) -> LookupConfig {
+        self.initial_timeout = timeout;
+        self
+    }
+
+    /// Sets the lower bound that adaptive query timeouts will be clamped to.
+    #[must_use]
+    pub fn with_min_timeout(mut self, timeout: Duration) -> LookupConfig {
+        self.min_timeout = timeout;
+        self
+    }
+
+    /// Sets the upper bound that adaptive query timeouts will be clamped to.
+    #[must_use]
+    pub fn with_max_timeout(mut self, timeout: Duration) -> LookupConfig {
+        self.max_timeout = timeout;
+        self
+    }
+
+    /// Gets the number of concurrent queries per lookup.
+    #[must_use]
+    pub fn alpha(&self) -> usize {
+        self.alpha
+    }
+
+    /// Gets the timeout used before any round trip times have been observed.
+    #[must_use]
+    pub fn initial_timeout(&self) -> Duration {
+        self.initial_timeout
+    }
+
+    /// Gets the lower bound for adaptive query timeouts.
+    #[must_use]
+    pub fn min_timeout(&self) -> Duration {
+        self.min_timeout
+    }
+
+    /// Gets the upper bound for adaptive query timeouts.
+    #[must_use]
+    pub fn max_timeout(&self) -> Duration {
+        self.max_timeout
+    }
+}
+
+impl Default for LookupConfig {
+    fn default() -> LookupConfig {
+        LookupConfig {
+            alpha: DEFAULT_ALPHA,
+            initial_timeout: Duration::from_millis(DEFAULT_INITIAL_TIMEOUT_MS),
+            min_timeout: Duration::from_millis(DEFAULT_MIN_TIMEOUT_MS),
+            max_timeout: Duration::from_millis(DEFAULT_MAX_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Statistics gathered over the lifetime of a single lookup.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct LookupStats {
+    hops: usize,
+    queried_nodes: usize,
+    responded_nodes: usize,
+    timed_out_nodes: usize,
+    duration: Duration,
+}
+
+impl LookupStats {
+    /// Number of hops away from our routing table that the lookup travelled.
+    #[must_use]
+    pub fn hops(&self) -> usize {
+        self.hops
+    }
+
+    /// Number of nodes that were sent a query.
+    #[must_use]
+    pub fn queried_nodes(&self) -> usize {
+        self.queried_nodes
+    }
+
+    /// Number of nodes that responded to a query.
+    #[must_use]
+    pub fn responded_nodes(&self) -> usize {
+        self.responded_nodes
+    }
+
+    /// Number of nodes that did not respond before their query timed out.
+    #[must_use]
+    pub fn timed_out_nodes(&self) -> usize {
+        self.timed_out_nodes
+    }
+
+    /// Time elapsed between starting and finishing the lookup.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Smoothed round trip time estimator used to compute adaptive query timeouts.
+///
+/// Follows the retransmission timeout computation from RFC 6298.
+#[derive(Debug, Copy, Clone)]
+pub struct RttEstimator {
+    config: LookupConfig,
+    smoothed: Option<(Duration, Duration)>,
+}
+
+impl RttEstimator {
+    pub fn new(config: LookupConfig) -> RttEstimator {
+        RttEstimator { config, smoothed: None }
+    }
+
+    /// Feed an observed round trip time into the estimator.
+    pub fn sample(&mut self, rtt: Duration) {
+        self.smoothed = Some(match self.smoothed {
+            None => (rtt, rtt / 2),
+            Some((srtt, rttvar)) => {
+                let delta = srtt.checked_sub(rtt).or_else(|| rtt.checked_sub(srtt)).unwrap_or_default();
+
+                (srtt * 7 / 8 + rtt / 8, rttvar * 3 / 4 + delta / 4)
+            }
+        });
+    }
+
+    /// Timeout that should be used for the next query.
+    pub fn timeout(&self) -> Duration {
+        match self.smoothed {
+            None => self.config.initial_timeout,
+            Some((srtt, rttvar)) => (srtt + rttvar * 4).max(self.config.min_timeout).min(self.config.max_timeout),
+        }
+    }
+}
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, PartialEq, Eq)]
@@ -41,27 +218,48 @@ pub enum LookupStatus {
     Failed,
 }
 
+/// Progress of a single node within the lookup shortlist.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum NodeProgress {
+    Unqueried,
+    Queried,
+    Responded,
+    TimedOut,
+}
+
+/// Query that is waiting on a response.
+struct ActiveRequest {
+    node: Node,
+    hop: usize,
+    sent: Instant,
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct TableLookup {
     table_id: NodeId,
     target_id: InfoHash,
+    config: LookupConfig,
     in_endgame: AtomicBool,
-    recv_values: AtomicBool,
     id_generator: Mutex<MIDGenerator>,
     will_announce: bool,
-    active_lookups: Mutex<HashMap<TransactionID, (DistanceToBeat, Instant)>>,
+    started: Instant,
+    stats: Mutex<LookupStats>,
+    rtt_estimator: Arc<Mutex<RttEstimator>>,
+    active_lookups: Mutex<HashMap<TransactionID, ActiveRequest>>,
     announce_tokens: Mutex<HashMap<Node, Vec<u8>>>,
-    requested_nodes: Mutex<HashSet<Node>>,
-    all_sorted_nodes: Mutex<Vec<(Distance, Node, Arc<AtomicBool>)>>,
+    all_sorted_nodes: Mutex<Vec<(Distance, Node, NodeProgress)>>,
     tasks: Arc<Mutex<JoinSet<Result<(), SendError>>>>,
 }
 
 impl TableLookup {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<'a>(
         table_id: NodeId,
         target_id: InfoHash,
         id_generator: MIDGenerator,
         will_announce: bool,
+        config: LookupConfig,
+        rtt_estimator: Arc<Mutex<RttEstimator>>,
         table: Arc<RwLock<RoutingTable>>,
         out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
@@ -76,40 +274,26 @@ impl TableLookup {
                 .filter(|n| n.status() == NodeStatus::Good)
                 .take(bucket::MAX_BUCKET_SIZE)
             {
-                insert_sorted_node(&all_sorted_nodes, target_id, node.clone(), false);
+                insert_sorted_node(&all_sorted_nodes, target_id, node.clone());
             }
 
-            let initial_pick_nodes = pick_initial_nodes(all_sorted_nodes.lock().unwrap().iter_mut());
-            let initial_pick_nodes_filtered = initial_pick_nodes.iter().filter(|&&(_, good)| good).map(|(node, _)| {
-                let distance_to_beat = node.id() ^ target_id;
-
-                (node, distance_to_beat)
-            });
-
             let table_lookup = TableLookup {
                 table_id,
                 target_id,
+                config,
                 in_endgame: AtomicBool::default(),
-                recv_values: AtomicBool::default(),
                 id_generator: Mutex::new(id_generator),
                 will_announce,
+                started: Instant::now(),
+                stats: Mutex::default(),
+                rtt_estimator,
                 all_sorted_nodes,
                 announce_tokens: Mutex::new(HashMap::new()),
-                requested_nodes: Mutex::new(HashSet::new()),
-                active_lookups: Mutex::new(HashMap::with_capacity(INITIAL_PICK_NUM)),
+                active_lookups: Mutex::new(HashMap::with_capacity(config.alpha)),
                 tasks: Arc::default(),
             };
 
-            if table_lookup
-                .start_request_round(
-                    initial_pick_nodes_filtered,
-                    table.clone(),
-                    out.clone(),
-                    &scheduled_task_sender,
-                )
-                .await
-                == LookupStatus::Failed
-            {
+            if table_lookup.start_request_round(1, table, out, &scheduled_task_sender).await == LookupStatus::Failed {
                 None
             } else {
                 Some(table_lookup)
@@ -122,6 +306,10 @@ impl TableLookup {
         self.target_id
     }
 
+    pub fn stats(&self) -> LookupStats {
+        *self.stats.lock().unwrap()
+    }
+
     pub async fn recv_response<B>(
         &self,
         node: Node,
@@ -135,95 +323,49 @@ impl TableLookup {
         B: BRefAccess<BType = B> + Clone,
         B::BType: PartialEq + Eq + core::hash::Hash + std::fmt::Debug,
     {
-        let Some((dist_to_beat, _)) = self.active_lookups.lock().unwrap().remove(&trans_id) else {
+        let Some(request) = self.active_lookups.lock().unwrap().remove(&trans_id) else {
             tracing::warn!(
                 "bip_dht: Received expired/unsolicited node response for an active table \
                    lookup..."
             );
-            return self.current_lookup_status();
+            // Nothing about the lookup changed, which also keeps a finished lookup from completing twice
+            return LookupStatus::Searching;
         };
 
+        self.rtt_estimator.lock().unwrap().sample(request.sent.elapsed());
+        {
+            let mut stats = self.stats.lock().unwrap();
+
+            stats.responded_nodes += 1;
+            stats.hops = stats.hops.max(request.hop);
+        }
+        set_node_progress(&self.all_sorted_nodes, self.target_id, &request.node, NodeProgress::Responded);
+
         if let Some(token) = msg.token() {
             self.announce_tokens.lock().unwrap().insert(node, token.to_vec());
         }
 
         let (opt_values, opt_nodes) = match msg.info_type() {
             CompactInfoType::Nodes(n) => (None, Some(n)),
-            CompactInfoType::Values(v) => {
-                self.recv_values.store(true, Ordering::Relaxed);
-                (Some(v.into_iter().collect()), None)
-            }
+            CompactInfoType::Values(v) => (Some(v.into_iter().collect()), None),
             CompactInfoType::Both(n, v) => (Some(v.into_iter().collect()), Some(n)),
         };
 
-        let (iterate_nodes, next_dist_to_beat) = if let Some(nodes) = opt_nodes {
-            #[allow(clippy::mutable_key_type)]
-            let requested_nodes = &self.requested_nodes;
-
-            let already_requested = |node_info: &(NodeId, SocketAddrV4)| {
-                let node = Node::as_questionable(node_info.0, SocketAddr::V4(node_info.1));
-
-                !requested_nodes.lock().unwrap().contains(&node)
-            };
-
-            let next_dist_to_beat = nodes
-                .into_iter()
-                .filter(&already_requested)
-                .fold(dist_to_beat, |closest, (id, _)| {
-                    let distance = self.target_id ^ id;
-
-                    if distance < closest {
-                        distance
-                    } else {
-                        closest
-                    }
-                });
-
-            let iterate_nodes = if next_dist_to_beat < dist_to_beat {
-                let iterate_nodes = pick_iterate_nodes(nodes.into_iter().filter(&already_requested), self.target_id);
-
-                for (id, v4_addr) in nodes {
-                    let addr = SocketAddr::V4(v4_addr);
-                    let node = Node::as_questionable(id, addr);
-                    let will_ping = iterate_nodes.iter().any(|(n, _)| n == &node);
-
-                    insert_sorted_node(&self.all_sorted_nodes, self.target_id, node, will_ping);
-                }
+        if let Some(nodes) = opt_nodes {
+            for (id, v4_addr) in nodes {
+                let node = Node::as_questionable(id, SocketAddr::V4(v4_addr));
 
-                Some(iterate_nodes)
-            } else {
-                for (id, v4_addr) in nodes {
-                    let addr = SocketAddr::V4(v4_addr);
-                    let node = Node::as_questionable(id, addr);
-
-                    insert_sorted_node(&self.all_sorted_nodes, self.target_id, node, false);
-                }
-
-                None
-            };
-
-            (iterate_nodes, next_dist_to_beat)
-        } else {
-            (None, dist_to_beat)
-        };
-
-        if !self.in_endgame.load(Ordering::Relaxed) {
-            if let Some(ref nodes) = iterate_nodes {
-                let filtered_nodes = nodes.iter().filter(|&&(_, good)| good).map(|(n, _)| (n, next_dist_to_beat));
-                if self
-                    .start_request_round(filtered_nodes, table.clone(), out.clone(), &scheduled_task_sender)
-                    .await
-                    == LookupStatus::Failed
-                {
-                    return LookupStatus::Failed;
-                }
+                insert_sorted_node(&self.all_sorted_nodes, self.target_id, node);
             }
+        }
 
-            if self.active_lookups.lock().unwrap().is_empty()
-                && self.start_endgame_round(table, out.clone(), scheduled_task_sender).await == LookupStatus::Failed
-            {
-                return LookupStatus::Failed;
-            }
+        if !self.in_endgame.load(Ordering::Relaxed)
+            && self
+                .start_request_round(request.hop + 1, table, out, &scheduled_task_sender)
+                .await
+                == LookupStatus::Failed
+        {
+            return LookupStatus::Failed;
         }
 
         match opt_values {
@@ -239,17 +381,23 @@ impl TableLookup {
         out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
     ) -> LookupStatus {
-        if self.active_lookups.lock().unwrap().remove(&trans_id).is_none() {
+        let Some(request) = self.active_lookups.lock().unwrap().remove(&trans_id) else {
             tracing::warn!(
                 "bip_dht: Received expired/unsolicited node timeout for an active table \
                    lookup..."
             );
-            return self.current_lookup_status();
-        }
+            return LookupStatus::Searching;
+        };
+
+        self.stats.lock().unwrap().timed_out_nodes += 1;
+        set_node_progress(&self.all_sorted_nodes, self.target_id, &request.node, NodeProgress::TimedOut);
 
+        // Replace the unresponsive node with the next closest one at the same depth
         if !self.in_endgame.load(Ordering::Relaxed)
-            && self.active_lookups.lock().unwrap().is_empty()
-            && self.start_endgame_round(table, out.clone(), scheduled_task_sender).await == LookupStatus::Failed
+            && self
+                .start_request_round(request.hop, table, out, &scheduled_task_sender)
+                .await
+                == LookupStatus::Failed
         {
             return LookupStatus::Failed;
         }
@@ -259,7 +407,7 @@ impl TableLookup {
 
     pub async fn recv_finished(
         &self,
-        handshake_port: u16,
+        connect_port: ConnectPort,
         table: Arc<RwLock<RoutingTable>>,
         mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
     ) -> LookupStatus {
@@ -284,13 +432,8 @@ impl TableLookup {
                 let announce_tokens = announce_tokens.lock().unwrap();
                 let token = announce_tokens.get(&node).unwrap();
 
-                let announce_peer_req = AnnouncePeerRequest::new(
-                    trans_id.as_ref(),
-                    self.table_id,
-                    self.target_id,
-                    token.as_ref(),
-                    ConnectPort::Explicit(handshake_port),
-                );
+                let announce_peer_req =
+                    AnnouncePeerRequest::new(trans_id.as_ref(), self.table_id, self.target_id, token.as_ref(), connect_port);
                 let announce_peer_msg = announce_peer_req.encode();
 
                 node_announces.push((node, announce_peer_msg));
@@ -316,6 +459,7 @@ impl TableLookup {
 
         self.active_lookups.lock().unwrap().clear();
         self.in_endgame.store(false, Ordering::Relaxed);
+        self.stats.lock().unwrap().duration = self.started.elapsed();
 
         if fatal_error {
             LookupStatus::Failed
@@ -332,23 +476,31 @@ impl TableLookup {
         }
     }
 
-    async fn start_request_round<'a, I>(
+    /// Fill any free query slots with the closest nodes we have not queried yet.
+    ///
+    /// Once the closest nodes have all responded, or there is nobody left to query, the
+    /// lookup is short circuited into the endgame without waiting on outstanding queries.
+    async fn start_request_round(
         &self,
-        nodes: I,
+        hop: usize,
         table: Arc<RwLock<RoutingTable>>,
         mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>,
-    ) -> LookupStatus
-    where
-        I: Iterator<Item = (&'a Node, DistanceToBeat)>,
-    {
-        let mut messages_sent = 0;
-        for (node, dist_to_beat) in nodes {
-            let trans_id = self.id_generator.lock().unwrap().generate();
+    ) -> LookupStatus {
+        let pick_nodes = {
+            let free_slots = self.config.alpha.saturating_sub(self.active_lookups.lock().unwrap().len());
+
+            if closest_nodes_stable(&self.all_sorted_nodes) {
+                Vec::new()
+            } else {
+                pick_closest_unqueried(&self.all_sorted_nodes, free_slots)
+            }
+        };
 
-            let timeout = Instant::now() + Duration::from_millis(LOOKUP_TIMEOUT_MS);
+        let timeout = self.rtt_estimator.lock().unwrap().timeout();
 
-            self.active_lookups.lock().unwrap().insert(trans_id, (dist_to_beat, timeout));
+        for node in pick_nodes {
+            let trans_id = self.id_generator.lock().unwrap().generate();
 
             let get_peers_msg = GetPeersRequest::new(trans_id.as_ref(), self.table_id, self.target_id).encode();
             if out.send((get_peers_msg, node.addr())).await.is_err() {
@@ -356,151 +508,124 @@ impl TableLookup {
                 return LookupStatus::Failed;
             }
 
-            self.requested_nodes.lock().unwrap().insert(node.clone());
+            self.active_lookups.lock().unwrap().insert(
+                trans_id,
+                ActiveRequest {
+                    node: node.clone(),
+                    hop,
+                    sent: Instant::now(),
+                },
+            );
+            self.stats.lock().unwrap().queried_nodes += 1;
 
             let routing_table = table.read().unwrap();
 
-            if let Some(n) = routing_table.find_node(node) {
+            if let Some(n) = routing_table.find_node(&node) {
                 n.local_request();
             }
 
-            messages_sent += 1;
-
             // Schedule a timeout check
-            let mut this_scheduled_task_sender = scheduled_task_sender.clone();
-            self.tasks.lock().unwrap().spawn(async move {
-                sleep(Duration::from_millis(LOOKUP_TIMEOUT_MS)).await;
-
-                match this_scheduled_task_sender
-                    .send(ScheduledTaskCheck::LookupTimeout(trans_id))
-                    .await
-                {
-                    Ok(()) => {
-                        tracing::debug!("sent scheduled lookup timeout");
-                        Ok(())
-                    }
-                    Err(e) => {
-                        tracing::debug!("error sending scheduled lookup timeout: {e}");
-                        Err(e)
-                    }
-                }
-            });
+            self.schedule_check(ScheduledTaskCheck::LookupTimeout(trans_id), timeout, scheduled_task_sender);
         }
 
-        if messages_sent == 0 {
-            self.active_lookups.lock().unwrap().clear();
+        let stable = closest_nodes_stable(&self.all_sorted_nodes);
+        if stable || self.active_lookups.lock().unwrap().is_empty() {
+            if stable {
+                tracing::debug!("bip_dht: Closest nodes for lookup stabilized, short circuiting...");
+            }
+
+            self.start_endgame(scheduled_task_sender);
+        }
+
+        if self.stats.lock().unwrap().queried_nodes == 0 {
             LookupStatus::Completed
         } else {
             LookupStatus::Searching
         }
     }
 
-    async fn start_endgame_round(
-        &self,
-        table: Arc<RwLock<RoutingTable>>,
-        mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
-        _scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
-    ) -> LookupStatus {
-        self.in_endgame.store(true, Ordering::SeqCst);
-
-        let timeout = Instant::now() + Duration::from_millis(ENDGAME_TIMEOUT_MS);
-
-        let mut endgame_messages = Vec::new();
-
-        if !self.recv_values.load(Ordering::SeqCst) {
-            {
-                let all_nodes = self.all_sorted_nodes.lock().unwrap();
-                for node_info in all_nodes.iter().filter(|(_, _, req)| !req.load(Ordering::Acquire)) {
-                    let (node_dist, node, req) = node_info;
-
-                    let trans_id = self.id_generator.lock().unwrap().generate();
-
-                    self.active_lookups.lock().unwrap().insert(trans_id, (*node_dist, timeout));
+    /// Stop issuing queries and schedule the lookup to be finished.
+    fn start_endgame(&self, scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>) {
+        if self.in_endgame.swap(true, Ordering::SeqCst) {
+            return;
+        }
 
-                    let get_peers_msg = GetPeersRequest::new(trans_id.as_ref(), self.table_id, self.target_id).encode();
+        let trans_id = self.id_generator.lock().unwrap().generate();
 
-                    endgame_messages.push((node.clone(), get_peers_msg, req.clone()));
-                }
-            }
+        self.schedule_check(
+            ScheduledTaskCheck::LookupEndGame(trans_id),
+            Duration::ZERO,
+            scheduled_task_sender,
+        );
+    }
 
-            for (node, get_peers_msg, req) in endgame_messages {
-                if out.send((get_peers_msg, node.addr())).await.is_err() {
-                    tracing::error!("bip_dht: Could not send an endgame message through the channel...");
-                    return LookupStatus::Failed;
+    fn schedule_check(
+        &self,
+        check: ScheduledTaskCheck,
+        delay: Duration,
+        scheduled_task_sender: &mpsc::Sender<ScheduledTaskCheck>,
+    ) {
+        let mut this_scheduled_task_sender = scheduled_task_sender.clone();
+        self.tasks.lock().unwrap().spawn(async move {
+            sleep(delay).await;
+
+            match this_scheduled_task_sender.send(check).await {
+                Ok(()) => {
+                    tracing::debug!("sent scheduled lookup check {check:?}");
+                    Ok(())
                 }
-
-                let routing_table = table.read().unwrap();
-
-                if let Some(n) = routing_table.find_node(&node) {
-                    n.local_request();
+                Err(e) => {
+                    tracing::debug!("error sending scheduled lookup check {check:?}: {e}");
+                    Err(e)
                 }
-
-                req.store(true, Ordering::Release);
             }
-        }
-
-        LookupStatus::Searching
+        });
     }
 }
 
-/// Picks a number of nodes from the sorted distance iterator to ping on the first round.
-fn pick_initial_nodes<'a, I>(sorted_nodes: I) -> [(Node, bool); INITIAL_PICK_NUM]
-where
-    I: Iterator<Item = &'a mut (Distance, Node, Arc<AtomicBool>)>,
-{
-    let dummy_id = [0u8; bt::NODE_ID_LEN].into();
-    let default = (Node::as_bad(dummy_id, net::default_route_v4()), false);
-
-    let mut pick_nodes = [default.clone(), default.clone(), default.clone(), default.clone()];
-    for (src, dst) in sorted_nodes.zip(pick_nodes.iter_mut()) {
-        dst.0 = src.1.clone();
-        dst.1 = true;
-
-        // Mark that the node has been requested from
-        src.2.store(true, Ordering::Relaxed);
-    }
-
-    pick_nodes
+/// Returns true if the closest nodes that have not timed out have all responded to us.
+pub(crate) fn closest_nodes_stable(nodes: &Mutex<Vec<(Distance, Node, NodeProgress)>>) -> bool {
+    nodes
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|&&(_, _, progress)| progress != NodeProgress::TimedOut)
+        .take(bucket::MAX_BUCKET_SIZE)
+        .all(|&(_, _, progress)| progress == NodeProgress::Responded)
 }
 
-fn pick_iterate_nodes<I>(unsorted_nodes: I, target_id: InfoHash) -> [(Node, bool); ITERATIVE_PICK_NUM]
-where
-    I: Iterator<Item = (NodeId, SocketAddrV4)>,
-{
-    let dummy_id = [0u8; bt::NODE_ID_LEN].into();
-    let default = (Node::as_bad(dummy_id, net::default_route_v4()), false);
-
-    let mut pick_nodes = [default.clone(), default.clone(), default.clone()];
-    for (id, v4_addr) in unsorted_nodes {
-        let addr = SocketAddr::V4(v4_addr);
-        let node = Node::as_questionable(id, addr);
-
-        insert_closest_nodes(&mut pick_nodes, target_id, node);
-    }
+/// Picks up to `count` of the closest nodes that have not been queried, marking them as queried.
+pub(crate) fn pick_closest_unqueried(nodes: &Mutex<Vec<(Distance, Node, NodeProgress)>>, count: usize) -> Vec<Node> {
+    let mut nodes = nodes.lock().unwrap();
 
-    pick_nodes
+    nodes
+        .iter_mut()
+        .filter(|(_, _, progress)| *progress != NodeProgress::TimedOut)
+        .take(bucket::MAX_BUCKET_SIZE)
+        .filter(|(_, _, progress)| *progress == NodeProgress::Unqueried)
+        .take(count)
+        .map(|(_, node, progress)| {
+            *progress = NodeProgress::Queried;
+            node.clone()
+        })
+        .collect()
 }
 
-fn insert_closest_nodes(nodes: &mut [(Node, bool)], target_id: InfoHash, new_node: Node) {
-    let new_distance = target_id ^ new_node.id();
-
-    for &mut (ref mut old_node, ref mut used) in &mut *nodes {
-        if *used {
-            let old_distance = target_id ^ old_node.id();
+pub(crate) fn set_node_progress(
+    nodes: &Mutex<Vec<(Distance, Node, NodeProgress)>>,
+    target: InfoHash,
+    node: &Node,
+    progress: NodeProgress,
+) {
+    let mut nodes = nodes.lock().unwrap();
+    let node_dist = target ^ node.id();
 
-            if new_distance < old_distance {
-                *old_node = new_node;
-                return;
-            }
-        } else {
-            *old_node = new_node;
-            *used = true;
-            return;
-        }
+    if let Some(entry) = nodes.iter_mut().find(|(dist, n, _)| *dist == node_dist && n == node) {
+        entry.2 = progress;
     }
 }
 
-fn insert_sorted_node(nodes: &Mutex<Vec<(Distance, Node, Arc<AtomicBool>)>>, target: InfoHash, node: Node, pinged: bool) {
+pub(crate) fn insert_sorted_node(nodes: &Mutex<Vec<(Distance, Node, NodeProgress)>>, target: InfoHash, node: Node) {
     let mut nodes = nodes.lock().unwrap();
     let node_id = node.id();
     let node_dist = target ^ node_id;
@@ -509,9 +634,72 @@ fn insert_sorted_node(nodes: &Mutex<Vec<(Distance, Node, Arc<AtomicBool>)>>, tar
     match search_result {
         Ok(dup_index) => {
             if nodes[dup_index].1 != node {
-                nodes.insert(dup_index, (node_dist, node, Arc::new(AtomicBool::new(pinged))));
+                nodes.insert(dup_index, (node_dist, node, NodeProgress::Unqueried));
             }
         }
-        Err(ins_index) => nodes.insert(ins_index, (node_dist, node, Arc::new(AtomicBool::new(pinged)))),
+        Err(ins_index) => nodes.insert(ins_index, (node_dist, node, NodeProgress::Unqueried)),
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AnnouncePort, LookupConfig, RttEstimator};
+    use crate::message::announce_peer::ConnectPort;
+
+    #[test]
+    fn positive_announce_port_resolves_connect_port() {
+        assert_eq!(ConnectPort::Explicit(6881), AnnouncePort::default().connect_port(6881));
+        assert_eq!(ConnectPort::Implied, AnnouncePort::Implied.connect_port(6881));
+        assert_eq!(ConnectPort::Explicit(51413), AnnouncePort::Explicit(51413).connect_port(6881));
+    }
+
+    #[test]
+    fn positive_initial_timeout_without_samples() {
+        let config = LookupConfig::default();
+        let estimator = RttEstimator::new(config);
+
+        assert_eq!(config.initial_timeout(), estimator.timeout());
+    }
+
+    #[test]
+    fn positive_timeout_adapts_to_samples() {
+        let config = LookupConfig::default()
+            .with_min_timeout(Duration::from_millis(1))
+            .with_max_timeout(Duration::from_secs(10));
+        let mut estimator = RttEstimator::new(config);
+
+        estimator.sample(Duration::from_millis(100));
+
+        // First sample uses a variance of half the round trip time
+        assert_eq!(Duration::from_millis(300), estimator.timeout());
+
+        for _ in 0..50 {
+            estimator.sample(Duration::from_millis(100));
+        }
+
+        assert!(estimator.timeout() < Duration::from_millis(110));
+    }
+
+    #[test]
+    fn positive_timeout_clamped_to_bounds() {
+        let config = LookupConfig::default()
+            .with_min_timeout(Duration::from_millis(500))
+            .with_max_timeout(Duration::from_secs(1));
+        let mut estimator = RttEstimator::new(config);
+
+        estimator.sample(Duration::from_millis(10));
+        assert_eq!(Duration::from_millis(500), estimator.timeout());
+
+        estimator.sample(Duration::from_secs(30));
+        assert_eq!(Duration::from_secs(1), estimator.timeout());
+    }
+
+    #[test]
+    fn positive_zero_alpha_treated_as_one() {
+        let config = LookupConfig::default().with_alpha(0);
+
+        assert_eq!(1, config.alpha());
+    }
+}
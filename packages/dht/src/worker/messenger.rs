@@ -1,5 +1,6 @@
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use futures::channel::mpsc;
 use futures::stream::StreamExt;
@@ -7,20 +8,54 @@ use futures::SinkExt as _;
 use tokio::net::UdpSocket;
 use tokio::task;
 
-use crate::worker::OneshotTask;
+use crate::metrics::SharedMetrics;
+use crate::worker::blacklist::Blacklist;
+use crate::worker::limiter::{self, QueryLimiter, QueryPermit};
+use crate::worker::{OneshotTask, QueryKind};
 
 const OUTGOING_MESSAGE_CAPACITY: usize = 4096;
 
 #[allow(clippy::module_name_repetitions)]
-pub fn create_outgoing_messenger(socket: &Arc<UdpSocket>) -> mpsc::Sender<(Vec<u8>, SocketAddr)> {
+pub fn create_outgoing_messenger(
+    socket: &Arc<UdpSocket>,
+    query_limiter: Arc<Mutex<QueryLimiter>>,
+    blacklist: Arc<Mutex<Blacklist>>,
+    metrics: SharedMetrics,
+) -> mpsc::Sender<(Vec<u8>, SocketAddr)> {
     #[allow(clippy::type_complexity)]
     let (send, mut recv): (mpsc::Sender<(Vec<u8>, SocketAddr)>, mpsc::Receiver<(Vec<u8>, SocketAddr)>) =
         mpsc::channel(OUTGOING_MESSAGE_CAPACITY);
 
     let socket = socket.clone();
+    let ipv6_socket = socket.local_addr().is_ok_and(|addr| addr.is_ipv6());
     task::spawn(async move {
         while let Some((message, addr)) = recv.next().await {
-            send_bytes(&socket, &message[..], addr).await;
+            if let Some((trans_id, opt_kind)) = limiter::query_transaction_id(&message) {
+                if blacklist.lock().unwrap().contains(&addr) {
+                    tracing::warn!(
+                        "bip_dht: Outgoing messenger dropped a query to {}, node is blacklisted...",
+                        addr
+                    );
+                    continue;
+                }
+
+                let admitted = admit_query(&query_limiter, &trans_id, addr).await;
+
+                report_query(&metrics, opt_kind, admitted);
+                if !admitted {
+                    continue;
+                }
+            }
+
+            // An IPv6 socket can only reach IPv4 nodes through their IPv4-mapped address
+            let send_addr = match addr {
+                SocketAddr::V4(v4_addr) if ipv6_socket => {
+                    SocketAddr::new(IpAddr::V6(v4_addr.ip().to_ipv6_mapped()), v4_addr.port())
+                }
+                _ => addr,
+            };
+
+            send_bytes(&socket, &message[..], send_addr).await;
         }
 
         tracing::info!("bip_dht: Outgoing messenger received a channel hangup, exiting thread...");
@@ -29,6 +64,33 @@ pub fn create_outgoing_messenger(socket: &Arc<UdpSocket>) -> mpsc::Sender<(Vec<u
     send
 }
 
+/// Wait until the query may be sent, returning false if it should be dropped instead.
+async fn admit_query(query_limiter: &Mutex<QueryLimiter>, trans_id: &[u8], addr: SocketAddr) -> bool {
+    loop {
+        let permit = query_limiter.lock().unwrap().send_query(trans_id, addr, Instant::now());
+
+        match permit {
+            QueryPermit::Granted => return true,
+            QueryPermit::Wait(wait) => tokio::time::sleep(wait).await,
+            QueryPermit::Denied => {
+                tracing::warn!(
+                    "bip_dht: Outgoing messenger dropped a query to {}, rate limit for the node exceeded...",
+                    addr
+                );
+                return false;
+            }
+        }
+    }
+}
+
+fn report_query(metrics: &SharedMetrics, opt_kind: Option<QueryKind>, admitted: bool) {
+    match opt_kind {
+        Some(kind) if admitted => metrics.query_sent(kind),
+        Some(kind) => metrics.query_dropped(kind),
+        None => (),
+    }
+}
+
 async fn send_bytes(socket: &UdpSocket, bytes: &[u8], addr: SocketAddr) {
     let mut bytes_sent = 0;
 
@@ -58,6 +120,7 @@ pub fn create_incoming_messenger(socket: Arc<UdpSocket>, send: mpsc::Sender<Ones
             match socket.recv_from(&mut buffer).await {
                 Ok((size, addr)) => {
                     let message = buffer[..size].to_vec();
+                    let addr = canonical_addr(addr);
                     if !send_message(&send, message, addr).await {
                         break;
                     }
@@ -72,6 +135,17 @@ pub fn create_incoming_messenger(socket: Arc<UdpSocket>, send: mpsc::Sender<Ones
     });
 }
 
+/// IPv4 nodes reaching a dual-stack socket show up with an IPv4-mapped address, give them their IPv4 address.
+fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6_addr) => match v6_addr.ip().to_ipv4_mapped() {
+            Some(v4_ip) => SocketAddr::new(IpAddr::V4(v4_ip), v6_addr.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
 async fn send_message(send: &mpsc::Sender<OneshotTask>, bytes: Vec<u8>, addr: SocketAddr) -> bool {
     send.clone().send(OneshotTask::Incoming(bytes, addr)).await.is_ok()
 }
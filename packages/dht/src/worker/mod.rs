@@ -1,33 +1,67 @@
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 use tokio::net::UdpSocket;
 use tokio::task::JoinSet;
-use util::bt::InfoHash;
+use util::bt::{InfoHash, NodeId};
 
 use crate::handshaker_trait::HandshakerTrait;
+use crate::message::request;
+use crate::metrics::SharedMetrics;
 use crate::router::Router;
-use crate::routing::table::{self, RoutingTable};
+use crate::routing::node::NodeInfo;
+use crate::routing::table::{RoutingConfig, RoutingTable};
+use crate::storage::{SharedStorage, StorageConfig, StorageStats};
 use crate::transaction::TransactionID;
+use crate::worker::blacklist::{Blacklist, BlacklistConfig, BlacklistEntry};
+use crate::worker::cache::LookupCacheConfig;
+use crate::worker::import::ImportStats;
+use crate::worker::limiter::{QueryLimiter, RateLimitConfig};
+use crate::worker::lookup::{AnnouncePort, LookupConfig, LookupStats};
+use crate::worker::sweep::{SweepConfig, SweepStats};
 
+pub mod blacklist;
 pub mod bootstrap;
+pub mod cache;
+pub mod closest;
 pub mod handler;
+pub mod import;
+pub mod limiter;
 pub mod lookup;
 pub mod messenger;
+pub mod readiness;
 pub mod refresh;
+pub mod sweep;
 
 /// Task that our DHT will execute immediately.
-#[derive(Clone)]
 pub enum OneshotTask {
     /// Process an incoming message from a remote node.
     Incoming(Vec<u8>, SocketAddr),
     /// Register a sender to send `DhtEvents` to.
     RegisterSender(mpsc::Sender<DhtEvent>),
+    /// Register a sender to send `IncomingQuery` to.
+    RegisterQuerySender(mpsc::Sender<IncomingQuery>),
+    /// Send a snapshot of the nodes in the routing table.
+    RoutingTable(oneshot::Sender<Vec<NodeInfo>>),
+    /// Send statistics about the peers stored on behalf of remote nodes.
+    StorageStats(oneshot::Sender<StorageStats>),
+    /// Send a snapshot of the nodes that strikes are recorded against.
+    Blacklist(oneshot::Sender<Vec<BlacklistEntry>>),
+    /// Forget the strikes against every node.
+    ClearBlacklist,
+    /// Send the number of good nodes once the routing table holds at least the given number of them.
+    WaitBootstrapped(usize, oneshot::Sender<usize>),
     /// Load a new bootstrap operation into worker storage.
     StartBootstrap(Vec<Router>, Vec<SocketAddr>),
-    /// Start a lookup for the given `InfoHash`.
-    StartLookup(InfoHash, bool),
+    /// Start a lookup for the given `InfoHash`, announcing if set, and reusing the results of a recent lookup if allowed.
+    StartLookup(InfoHash, bool, bool),
+    /// Start a `find_node` sweep over the given targets.
+    StartSweep(Vec<NodeId>, SweepConfig),
+    /// Start a lookup for the nodes closest to the given `NodeId`, sending them to the given sender.
+    StartClosest(NodeId, mpsc::Sender<NodeInfo>),
+    /// Start checking the liveness of the given nodes, merging those that respond into the routing table.
+    StartImport(Vec<(NodeId, SocketAddr)>),
     /// Gracefully shutdown the DHT and associated workers.
     Shutdown(ShutdownCause),
 }
@@ -42,8 +76,13 @@ pub enum ScheduledTaskCheck {
     /// Check the progress of a current lookup.
     LookupTimeout(TransactionID),
     /// Check the progress of the lookup endgame.
-    #[allow(dead_code)]
     LookupEndGame(TransactionID),
+    /// Check that the sweep has finished waiting for responses.
+    SweepTimeout(TransactionID),
+    /// Check the progress of a current closest nodes lookup.
+    ClosestTimeout(TransactionID),
+    /// Check that the import has finished waiting for responses.
+    ImportTimeout(TransactionID),
 }
 
 /// Event that occurred within the DHT which clients may be interested in.
@@ -51,12 +90,86 @@ pub enum ScheduledTaskCheck {
 pub enum DhtEvent {
     /// DHT completed the bootstrap.
     BootstrapCompleted,
-    /// Lookup operation for the given `InfoHash` completed.
-    LookupCompleted(InfoHash),
+    /// Lookup operation for the given `InfoHash` completed, along with statistics gathered while searching.
+    LookupCompleted(InfoHash, LookupStats),
+    /// Sweep operation completed, along with statistics gathered while sweeping.
+    SweepCompleted(SweepStats),
+    /// Import of a `RoutingSnapshot` completed, along with statistics gathered while checking the nodes.
+    ImportCompleted(ImportStats),
     /// DHT is shutting down for some reason.
     ShuttingDown(ShutdownCause),
 }
 
+/// Type of query exchanged with a remote node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    /// `ping` query.
+    Ping,
+    /// `find_node` query.
+    FindNode,
+    /// `get_peers` query.
+    GetPeers,
+    /// `announce_peer` query.
+    AnnouncePeer,
+}
+
+impl QueryKind {
+    /// Kind of query with the given method name, if it is one we support.
+    pub(crate) fn from_method(method: &[u8]) -> Option<QueryKind> {
+        match std::str::from_utf8(method).ok()? {
+            request::PING_TYPE_KEY => Some(QueryKind::Ping),
+            request::FIND_NODE_TYPE_KEY => Some(QueryKind::FindNode),
+            request::GET_PEERS_TYPE_KEY => Some(QueryKind::GetPeers),
+            request::ANNOUNCE_PEER_TYPE_KEY => Some(QueryKind::AnnouncePeer),
+            _ => None,
+        }
+    }
+}
+
+/// Query received from a remote node, for monitoring the DHT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IncomingQuery {
+    kind: QueryKind,
+    node_id: NodeId,
+    addr: SocketAddr,
+    target: Option<NodeId>,
+}
+
+impl IncomingQuery {
+    pub(crate) fn new(kind: QueryKind, node_id: NodeId, addr: SocketAddr, target: Option<NodeId>) -> IncomingQuery {
+        IncomingQuery {
+            kind,
+            node_id,
+            addr,
+            target,
+        }
+    }
+
+    /// Type of query that was received.
+    #[must_use]
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    /// Id of the node that sent the query.
+    #[must_use]
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// Address the query was received from.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Target id of a `find_node` query, or the `InfoHash` of a `get_peers` or `announce_peer` query.
+    #[must_use]
+    pub fn target(&self) -> Option<NodeId> {
+        self.target
+    }
+}
+
 /// Event that occurred within the DHT which caused it to shutdown.
 #[derive(Copy, Clone, Debug)]
 pub enum ShutdownCause {
@@ -70,11 +183,22 @@ pub enum ShutdownCause {
 
 /// Spawns the necessary workers that make up our local DHT node and connects them via channels
 /// so that they can send and receive DHT messages.
+#[allow(clippy::too_many_arguments)]
 pub fn start_mainline_dht<H>(
     send_socket: &Arc<UdpSocket>,
     recv_socket: Arc<UdpSocket>,
+    node_id: NodeId,
     read_only: bool,
     _: Option<SocketAddr>,
+    lookup_config: LookupConfig,
+    lookup_cache_config: LookupCacheConfig,
+    announce_port: AnnouncePort,
+    storage_config: StorageConfig,
+    storage: SharedStorage,
+    rate_limit_config: RateLimitConfig,
+    routing_config: RoutingConfig,
+    blacklist_config: BlacklistConfig,
+    metrics: SharedMetrics,
     handshaker: H,
     kill_sock: Arc<UdpSocket>,
     kill_addr: SocketAddr,
@@ -82,11 +206,29 @@ pub fn start_mainline_dht<H>(
 where
     H: HandshakerTrait + 'static,
 {
-    let outgoing = messenger::create_outgoing_messenger(send_socket);
+    // Shared so that responses are only accepted for the queries that the messenger actually sent
+    let query_limiter = Arc::new(Mutex::new(QueryLimiter::new(rate_limit_config, metrics.clone())));
+    // Shared so that queries are never sent to blacklisted nodes
+    let blacklist = Arc::new(Mutex::new(Blacklist::new(blacklist_config)));
+    let outgoing = messenger::create_outgoing_messenger(send_socket, query_limiter.clone(), blacklist.clone(), metrics.clone());
 
-    // TODO: Utilize the security extension.
-    let routing_table = RoutingTable::new(table::random_node_id());
-    let message_sender = handler::create_dht_handler(routing_table, outgoing, read_only, handshaker, kill_sock, kill_addr);
+    let routing_table = RoutingTable::new(node_id).with_config(routing_config);
+    let message_sender = handler::create_dht_handler(
+        routing_table,
+        outgoing,
+        read_only,
+        lookup_config,
+        lookup_cache_config,
+        announce_port,
+        storage_config,
+        storage,
+        query_limiter,
+        blacklist,
+        metrics,
+        handshaker,
+        kill_sock,
+        kill_addr,
+    );
 
     messenger::create_incoming_messenger(recv_socket, message_sender.0.clone());
 
@@ -0,0 +1,91 @@
+use futures::channel::oneshot;
+
+use crate::routing::node::NodeStatus;
+use crate::routing::table::RoutingTable;
+
+/// Clients waiting for the routing table to hold a minimum number of good nodes.
+#[derive(Default)]
+pub struct BootstrapWaiters {
+    waiters: Vec<(usize, oneshot::Sender<usize>)>,
+}
+
+impl BootstrapWaiters {
+    /// Add a client waiting for the given number of good nodes, which is sent the number of good nodes once reached.
+    pub fn push(&mut self, min_good_nodes: usize, sender: oneshot::Sender<usize>) {
+        self.waiters.push((min_good_nodes, sender));
+    }
+
+    /// Notify the clients waiting for at most the number of good nodes in the routing table.
+    ///
+    /// Clients that stopped waiting are dropped.
+    pub fn notify(&mut self, table: &RoutingTable) {
+        self.waiters.retain(|(_, sender)| !sender.is_canceled());
+        if self.waiters.is_empty() {
+            return;
+        }
+
+        let good_nodes = num_good_nodes(table);
+        let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.waiters)
+            .into_iter()
+            .partition(|&(min_good_nodes, _)| good_nodes >= min_good_nodes);
+        self.waiters = waiting;
+
+        for (_, sender) in ready {
+            // Client may have stopped waiting in the meantime
+            let _ = sender.send(good_nodes);
+        }
+    }
+}
+
+/// Number of good nodes in the routing table.
+pub fn num_good_nodes(table: &RoutingTable) -> usize {
+    table
+        .node_infos()
+        .iter()
+        .filter(|info| info.status() == NodeStatus::Good)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::oneshot;
+    use util::bt::{self, NodeId};
+    use util::test as bip_test;
+
+    use super::BootstrapWaiters;
+    use crate::routing::node::Node;
+    use crate::routing::table::RoutingTable;
+
+    #[test]
+    fn positive_notify_once_enough_good_nodes() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let addrs = bip_test::dummy_block_socket_addrs(2);
+        let mut table = RoutingTable::new(node_id);
+        let mut waiters = BootstrapWaiters::default();
+
+        let (send_one, mut recv_one) = oneshot::channel();
+        let (send_two, mut recv_two) = oneshot::channel();
+        waiters.push(1, send_one);
+        waiters.push(2, send_two);
+
+        table.add_node(&Node::as_good([1u8; bt::NODE_ID_LEN].into(), addrs[0]));
+        table.add_node(&Node::as_questionable([2u8; bt::NODE_ID_LEN].into(), addrs[1]));
+        waiters.notify(&table);
+
+        assert_eq!(Ok(Some(1)), recv_one.try_recv());
+        assert_eq!(Ok(None), recv_two.try_recv());
+    }
+
+    #[test]
+    fn negative_drop_canceled_waiters() {
+        let table = RoutingTable::new([0u8; bt::NODE_ID_LEN].into());
+        let mut waiters = BootstrapWaiters::default();
+
+        let (send, recv) = oneshot::channel();
+        waiters.push(1, send);
+        drop(recv);
+        waiters.notify(&table);
+
+        assert!(waiters.waiters.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use futures::channel::mpsc;
+use futures::SinkExt as _;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Duration, Instant};
+use util::bt::NodeId;
+
+use crate::message::find_node::FindNodeRequest;
+use crate::routing::table::RoutingTable;
+use crate::transaction::MIDGenerator;
+use crate::worker::ScheduledTaskCheck;
+
+const DEFAULT_QUERIES_PER_SECOND: u32 = 50;
+const DEFAULT_NODES_PER_TARGET: usize = 8;
+const DEFAULT_RESPONSE_TIMEOUT_MS: u64 = 3000;
+
+/// Configures how bulk `find_node` sweeps are performed by the DHT.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct SweepConfig {
+    queries_per_second: u32,
+    nodes_per_target: usize,
+    response_timeout: Duration,
+}
+
+impl SweepConfig {
+    /// Sets the maximum rate at which `find_node` queries will be sent.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_queries_per_second(mut self, queries_per_second: u32) -> SweepConfig {
+        self.queries_per_second = queries_per_second.max(1);
+        self
+    }
+
+    /// Sets the number of closest known nodes that will be queried for each target.
+    ///
+    /// A value of zero is treated as one.
+    #[must_use]
+    pub fn with_nodes_per_target(mut self, nodes_per_target: usize) -> SweepConfig {
+        self.nodes_per_target = nodes_per_target.max(1);
+        self
+    }
+
+    /// Sets how long to wait for responses after the last query has been sent.
+    #[must_use]
+    pub fn with_response_timeout(mut self, timeout: Duration) -> SweepConfig {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Gets the maximum rate at which queries will be sent.
+    #[must_use]
+    pub fn queries_per_second(&self) -> u32 {
+        self.queries_per_second
+    }
+
+    /// Gets the number of nodes queried per target.
+    #[must_use]
+    pub fn nodes_per_target(&self) -> usize {
+        self.nodes_per_target
+    }
+
+    /// Gets how long to wait for responses after the last query has been sent.
+    #[must_use]
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    fn query_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.queries_per_second
+    }
+}
+
+impl Default for SweepConfig {
+    fn default() -> SweepConfig {
+        SweepConfig {
+            queries_per_second: DEFAULT_QUERIES_PER_SECOND,
+            nodes_per_target: DEFAULT_NODES_PER_TARGET,
+            response_timeout: Duration::from_millis(DEFAULT_RESPONSE_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Statistics gathered over the lifetime of a single sweep.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct SweepStats {
+    targets: usize,
+    queried_nodes: usize,
+    responded_nodes: usize,
+    discovered_nodes: usize,
+    duration: Duration,
+}
+
+impl SweepStats {
+    /// Number of targets that were swept.
+    #[must_use]
+    pub fn targets(&self) -> usize {
+        self.targets
+    }
+
+    /// Number of nodes that were sent a query.
+    #[must_use]
+    pub fn queried_nodes(&self) -> usize {
+        self.queried_nodes
+    }
+
+    /// Number of nodes that responded to a query.
+    #[must_use]
+    pub fn responded_nodes(&self) -> usize {
+        self.responded_nodes
+    }
+
+    /// Number of nodes contained in all of the responses, including duplicates.
+    #[must_use]
+    pub fn discovered_nodes(&self) -> usize {
+        self.discovered_nodes
+    }
+
+    /// Total time the sweep took, including waiting for late responses.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Sends `find_node` queries for a list of targets to the closest nodes in our routing table.
+#[allow(clippy::module_name_repetitions)]
+pub struct TableSweep {
+    targets: usize,
+    queried_nodes: Arc<AtomicUsize>,
+    responded_nodes: AtomicUsize,
+    discovered_nodes: AtomicUsize,
+    start_time: Instant,
+    // Dropping the sweep aborts any queries that have yet to be sent
+    _tasks: Mutex<JoinSet<()>>,
+}
+
+impl TableSweep {
+    /// Start a sweep, queries will be sent in the background at the configured rate.
+    pub fn start(
+        node_id: NodeId,
+        targets: Vec<NodeId>,
+        mut id_generator: MIDGenerator,
+        config: SweepConfig,
+        table: Arc<RwLock<RoutingTable>>,
+        mut out: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        mut scheduled_task_sender: mpsc::Sender<ScheduledTaskCheck>,
+    ) -> TableSweep {
+        let queried_nodes = Arc::new(AtomicUsize::default());
+        let num_targets = targets.len();
+
+        let mut tasks = JoinSet::new();
+        let task_queried_nodes = queried_nodes.clone();
+        tasks.spawn(async move {
+            let mut interval = tokio::time::interval(config.query_interval());
+            let mut queried_addrs = HashSet::new();
+
+            for target_id in targets {
+                let nodes: Vec<SocketAddr> = table
+                    .read()
+                    .unwrap()
+                    .closest_nodes(target_id)
+                    .filter(|node| queried_addrs.insert(node.addr()))
+                    .take(config.nodes_per_target())
+                    .map(|node| {
+                        // Mark that we requested from the node
+                        node.local_request();
+                        node.addr()
+                    })
+                    .collect();
+
+                for addr in nodes {
+                    interval.tick().await;
+
+                    let trans_id = id_generator.generate();
+                    let find_node_msg = FindNodeRequest::new(trans_id.as_ref(), node_id, target_id).encode();
+
+                    if out.send((find_node_msg, addr)).await.is_err() {
+                        tracing::error!("bip_dht: TableSweep failed to send a find node message to the out channel...");
+                        return;
+                    }
+
+                    task_queried_nodes.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            sleep(config.response_timeout()).await;
+
+            // Only the action id will be used
+            let trans_id = id_generator.generate();
+            if scheduled_task_sender
+                .send(ScheduledTaskCheck::SweepTimeout(trans_id))
+                .await
+                .is_err()
+            {
+                tracing::error!("bip_dht: TableSweep failed to send a sweep timeout to the scheduled channel...");
+            }
+        });
+
+        TableSweep {
+            targets: num_targets,
+            queried_nodes,
+            responded_nodes: AtomicUsize::default(),
+            discovered_nodes: AtomicUsize::default(),
+            start_time: Instant::now(),
+            _tasks: Mutex::new(tasks),
+        }
+    }
+
+    /// Record a response to one of our queries containing the given number of nodes.
+    pub fn recv_response(&self, num_nodes: usize) {
+        self.responded_nodes.fetch_add(1, Ordering::Relaxed);
+        self.discovered_nodes.fetch_add(num_nodes, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> SweepStats {
+        SweepStats {
+            targets: self.targets,
+            queried_nodes: self.queried_nodes.load(Ordering::Relaxed),
+            responded_nodes: self.responded_nodes.load(Ordering::Relaxed),
+            discovered_nodes: self.discovered_nodes.load(Ordering::Relaxed),
+            duration: self.start_time.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use futures::channel::mpsc;
+    use futures::StreamExt as _;
+    use tokio::time::{Duration, Instant};
+    use util::bt::{self, NodeId};
+    use util::test as bip_test;
+
+    use crate::routing::node::Node;
+    use crate::routing::table::RoutingTable;
+    use crate::transaction::AIDGenerator;
+    use crate::worker::sweep::{SweepConfig, TableSweep};
+    use crate::worker::ScheduledTaskCheck;
+
+    #[test]
+    fn positive_config_clamps_zero_values() {
+        let config = SweepConfig::default().with_queries_per_second(0).with_nodes_per_target(0);
+
+        assert_eq!(config.queries_per_second(), 1);
+        assert_eq!(config.nodes_per_target(), 1);
+    }
+
+    #[tokio::test]
+    async fn positive_sweep_rate_limited() {
+        let node_id: NodeId = [0u8; bt::NODE_ID_LEN].into();
+        let mut table = RoutingTable::new(node_id);
+        for (index, addr) in bip_test::dummy_block_socket_addrs(4).into_iter().enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let id: NodeId = [index as u8 + 1; bt::NODE_ID_LEN].into();
+            table.add_node(&Node::as_good(id, addr));
+        }
+
+        let (out, mut out_recv) = mpsc::channel(16);
+        let (scheduled, mut scheduled_recv) = mpsc::channel(1);
+        let config = SweepConfig::default()
+            .with_queries_per_second(20)
+            .with_nodes_per_target(4)
+            .with_response_timeout(Duration::from_millis(10));
+
+        let start = Instant::now();
+        // Two targets over the same nodes, each node should only be queried once
+        let sweep = TableSweep::start(
+            node_id,
+            vec![[1u8; bt::NODE_ID_LEN].into(), [2u8; bt::NODE_ID_LEN].into()],
+            AIDGenerator::new().generate(),
+            config,
+            Arc::new(RwLock::new(table)),
+            out,
+            scheduled,
+        );
+
+        assert!(matches!(
+            scheduled_recv.next().await,
+            Some(ScheduledTaskCheck::SweepTimeout(_))
+        ));
+        // First query is sent immediately, the rest are spaced out
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        let mut queried = Vec::new();
+        while let Ok((_, addr)) = out_recv.try_recv() {
+            queried.push(addr);
+        }
+
+        assert_eq!(queried.len(), 4);
+        assert_eq!(sweep.stats().queried_nodes(), 4);
+        assert_eq!(sweep.stats().targets(), 2);
+    }
+}
@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 use lru_cache::LruCache;
 
@@ -82,6 +83,19 @@ where
         self.inner.sync_file(path)
     }
 
+    fn move_file<P, Q>(&self, from: P, to: Q) -> std::io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.run_with_lock(|cache, _| {
+            cache.remove(from.as_ref());
+            cache.remove(to.as_ref());
+        });
+
+        self.inner.move_file(from, to)
+    }
+
     fn file_size(&self, file: &Self::File) -> std::io::Result<u64> {
         let lock_file = file
             .lock()
@@ -90,6 +104,14 @@ where
         self.inner.file_size(&*lock_file)
     }
 
+    fn file_modified(&self, file: &Self::File) -> std::io::Result<Option<SystemTime>> {
+        let lock_file = file
+            .lock()
+            .expect("bip_disk: Failed To Lock File In FileHandleCache::file_modified");
+
+        self.inner.file_modified(&*lock_file)
+    }
+
     fn read_file(&self, file: &mut Self::File, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
         let mut lock_file = file
             .lock()
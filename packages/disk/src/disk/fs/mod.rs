@@ -1,8 +1,10 @@
 use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 pub mod cache;
 pub mod native;
+pub mod sanitize;
 
 /// Trait for performing operations on some file system.
 ///
@@ -31,6 +33,24 @@ pub trait FileSystem {
     where
         P: AsRef<Path> + Send + 'static;
 
+    /// Move the file to a new path, replacing any file already there.
+    ///
+    /// Intermediate directories of the new path will be created if necessary.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO error if there is an problem, or if the `FileSystem` does not support moving files.
+    fn move_file<P, Q>(&self, _from: P, _to: Q) -> std::io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "File System Does Not Support Moving Files",
+        ))
+    }
+
     /// Get the size of the file in bytes.
     ///
     /// # Errors
@@ -38,6 +58,15 @@ pub trait FileSystem {
     /// It would return an IO error if there is an problem.
     fn file_size(&self, file: &Self::File) -> std::io::Result<u64>;
 
+    /// Get the last modification time of the file, if the `FileSystem` keeps track of it.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO error if there is an problem.
+    fn file_modified(&self, _file: &Self::File) -> std::io::Result<Option<SystemTime>> {
+        Ok(None)
+    }
+
     /// Read the contents of the file at the given offset.
     ///
     /// On success, return the number of bytes read.
@@ -79,10 +108,22 @@ where
         FileSystem::sync_file(*self, path)
     }
 
+    fn move_file<P, Q>(&self, from: P, to: Q) -> std::io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        FileSystem::move_file(*self, from, to)
+    }
+
     fn file_size(&self, file: &Self::File) -> std::io::Result<u64> {
         FileSystem::file_size(*self, file)
     }
 
+    fn file_modified(&self, file: &Self::File) -> std::io::Result<Option<SystemTime>> {
+        FileSystem::file_modified(*self, file)
+    }
+
     fn read_file(&self, file: &mut Self::File, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
         FileSystem::read_file(*self, file, offset, buffer)
     }
@@ -1,21 +1,30 @@
 use std::borrow::Cow;
 use std::io::{Read as _, Seek as _, Write as _};
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
 
+use crate::disk::fs::sanitize::SanitizePolicy;
 use crate::disk::fs::FileSystem;
 
 // TODO: This should be sanitizing paths passed into it so they don't escape the base directory!!!
 
+/// Alignment of offsets, lengths, and buffers for direct IO.
+///
+/// This covers the logical block size of any common storage device.
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+
 /// File that exists on disk.
 #[allow(clippy::module_name_repetitions)]
 pub struct NativeFile {
     file: std::fs::File,
+    direct: bool,
 }
 
 impl NativeFile {
     /// Create a new `NativeFile`.
-    fn new(file: std::fs::File) -> NativeFile {
-        NativeFile { file }
+    fn new(file: std::fs::File, direct: bool) -> NativeFile {
+        NativeFile { file, direct }
     }
 }
 
@@ -23,6 +32,10 @@ impl NativeFile {
 #[allow(clippy::module_name_repetitions)]
 pub struct NativeFileSystem {
     current_dir: PathBuf,
+    direct_io: bool,
+    sanitize: SanitizePolicy,
+    // Held exclusively by unaligned direct writes, so their read-modify-write cannot overwrite other writes
+    direct_write_lock: RwLock<()>,
 }
 
 impl NativeFileSystem {
@@ -33,8 +46,37 @@ impl NativeFileSystem {
     {
         NativeFileSystem {
             current_dir: default.as_ref().to_path_buf(),
+            direct_io: false,
+            sanitize: SanitizePolicy::default(),
+            direct_write_lock: RwLock::new(()),
         }
     }
+
+    /// Whether or not files should be opened for direct IO, bypassing the OS page cache.
+    ///
+    /// This avoids caching blocks twice when the application keeps its own block cache. Reads and
+    /// writes are aligned internally, so unaligned writes turn into a read-modify-write of the
+    /// surrounding blocks. Direct IO is only supported on Linux; elsewhere, or if the underlying
+    /// file system does not support it, files are opened for buffered IO.
+    ///
+    /// Defaults to false.
+    #[must_use]
+    pub fn with_direct_io(mut self, direct_io: bool) -> NativeFileSystem {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// Policy for rewriting the names of files and directories that can not be created on this platform.
+    ///
+    /// Only the components of the paths given to this `NativeFileSystem` are rewritten, never the
+    /// components of its directory.
+    ///
+    /// Defaults to `SanitizePolicy::Windows` on Windows, and `SanitizePolicy::Preserve` everywhere else.
+    #[must_use]
+    pub fn with_sanitize_policy(mut self, policy: SanitizePolicy) -> NativeFileSystem {
+        self.sanitize = policy;
+        self
+    }
 }
 
 impl FileSystem for NativeFileSystem {
@@ -44,10 +86,10 @@ impl FileSystem for NativeFileSystem {
     where
         P: AsRef<Path> + Send + 'static,
     {
-        let combine_path = combine_user_path(&path, &self.current_dir);
-        let file = create_new_file(combine_path)?;
+        let combine_path = combine_user_path(&path, &self.current_dir, self.sanitize);
+        let (file, direct) = create_new_file(long_path(combine_path)?, self.direct_io)?;
 
-        Ok(NativeFile::new(file))
+        Ok(NativeFile::new(file, direct))
     }
 
     fn sync_file<P>(&self, _path: P) -> std::io::Result<()>
@@ -57,27 +99,74 @@ impl FileSystem for NativeFileSystem {
         Ok(())
     }
 
+    fn move_file<P, Q>(&self, from: P, to: Q) -> std::io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        let from = long_path(combine_user_path(&from, &self.current_dir, self.sanitize))?;
+        let to = long_path(combine_user_path(&to, &self.current_dir, self.sanitize))?;
+
+        if let Some(parent_dir) = to.parent() {
+            std::fs::create_dir_all(parent_dir)?;
+        }
+
+        match std::fs::rename(&from, &to) {
+            Ok(()) => Ok(()),
+            // Files can not be renamed across file systems, so fall back to copying them
+            Err(err) => std::fs::copy(&from, &to)
+                .and_then(|_| std::fs::remove_file(&from))
+                .map_err(|_| err),
+        }
+    }
+
     fn file_size(&self, file: &NativeFile) -> std::io::Result<u64> {
         file.file.metadata().map(|metadata| metadata.len())
     }
 
+    fn file_modified(&self, file: &NativeFile) -> std::io::Result<Option<SystemTime>> {
+        file.file.metadata()?.modified().map(Some)
+    }
+
     fn read_file(&self, file: &mut NativeFile, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+        if file.direct {
+            return read_aligned(&mut file.file, offset, buffer);
+        }
+
         file.file.seek(std::io::SeekFrom::Start(offset))?;
 
         file.file.read(buffer)
     }
 
     fn write_file(&self, file: &mut NativeFile, offset: u64, buffer: &[u8]) -> std::io::Result<usize> {
+        if file.direct {
+            if is_aligned(offset, buffer.len()) {
+                let _guard = self
+                    .direct_write_lock
+                    .read()
+                    .expect("bip_disk: Failed To Lock Direct Write In NativeFileSystem::write_file");
+
+                return write_aligned(&mut file.file, offset, buffer);
+            }
+
+            let _guard = self
+                .direct_write_lock
+                .write()
+                .expect("bip_disk: Failed To Lock Direct Write In NativeFileSystem::write_file");
+
+            return write_aligned(&mut file.file, offset, buffer);
+        }
+
         file.file.seek(std::io::SeekFrom::Start(offset))?;
 
         file.file.write(buffer)
     }
 }
 
-/// Create a new file with read and write options.
+/// Create a new file with read and write options, returning whether or not it was opened for direct IO.
 ///
 /// Intermediate directories will be created if they do not exist.
-fn create_new_file<P>(path: P) -> std::io::Result<std::fs::File>
+fn create_new_file<P>(path: P, direct_io: bool) -> std::io::Result<(std::fs::File, bool)>
 where
     P: AsRef<Path>,
 {
@@ -85,12 +174,20 @@ where
         Some(parent_dir) => {
             std::fs::create_dir_all(parent_dir)?;
 
-            std::fs::OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .truncate(false)
-                .open(&path)
+            let mut options = std::fs::OpenOptions::new();
+            options.read(true).write(true).create(true).truncate(false);
+
+            if direct_io {
+                match open_direct(&options, &path) {
+                    // File system does not support direct IO, fall back to buffered IO
+                    Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => (),
+                    Err(err) => return Err(err),
+                    Ok(Some(file)) => return Ok((file, true)),
+                    Ok(None) => (),
+                }
+            }
+
+            options.open(&path).map(|file| (file, false))
         }
         None => Err(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -99,8 +196,134 @@ where
     }
 }
 
-/// Create a path from the user path and current directory.
-fn combine_user_path<'a, P>(user_path: &'a P, current_dir: &Path) -> Cow<'a, Path>
+/// Open the file for direct IO, if the platform supports it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn open_direct<P>(options: &std::fs::OpenOptions, path: P) -> std::io::Result<Option<std::fs::File>>
+where
+    P: AsRef<Path>,
+{
+    use std::os::unix::fs::OpenOptionsExt as _;
+
+    options.clone().custom_flags(libc::O_DIRECT).open(path).map(Some)
+}
+
+/// Open the file for direct IO, if the platform supports it.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[allow(clippy::unnecessary_wraps)]
+fn open_direct<P>(_options: &std::fs::OpenOptions, _path: P) -> std::io::Result<Option<std::fs::File>>
+where
+    P: AsRef<Path>,
+{
+    Ok(None)
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Buffer whose contents start at an address aligned for direct IO.
+struct AlignedBuffer {
+    bytes: Vec<u8>,
+    start: usize,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize) -> AlignedBuffer {
+        let bytes = vec![0u8; len + DIRECT_IO_ALIGNMENT];
+        let start = bytes.as_ptr().align_offset(DIRECT_IO_ALIGNMENT);
+        assert!(start < DIRECT_IO_ALIGNMENT, "bip_disk: Failed To Align Direct IO Buffer");
+
+        AlignedBuffer { bytes, start, len }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[self.start..self.start + self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[self.start..self.start + self.len]
+    }
+}
+
+#[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` is newer than our minimum supported rust version
+fn is_aligned(offset: u64, len: usize) -> bool {
+    offset % DIRECT_IO_ALIGNMENT as u64 == 0 && len % DIRECT_IO_ALIGNMENT == 0
+}
+
+/// Expand the given region so that it starts and ends on an alignment boundary.
+#[allow(clippy::manual_div_ceil)] // `u64::div_ceil` is newer than our minimum supported rust version
+fn aligned_region(offset: u64, len: usize) -> (u64, usize) {
+    let alignment = DIRECT_IO_ALIGNMENT as u64;
+
+    let start = offset - offset % alignment;
+    let end = (offset + len as u64 + alignment - 1) / alignment * alignment;
+
+    (
+        start,
+        usize::try_from(end - start).expect("bip_disk: Direct IO Region Too Large"),
+    )
+}
+
+/// Read into the aligned buffer from the given aligned offset, stopping early at the end of the file.
+fn read_region(file: &mut std::fs::File, offset: u64, buffer: &mut AlignedBuffer) -> std::io::Result<usize> {
+    file.seek(std::io::SeekFrom::Start(offset))?;
+
+    let region = buffer.as_mut_slice();
+    let mut total_read = 0;
+    while total_read < region.len() {
+        match file.read(&mut region[total_read..]) {
+            Ok(0) => break,
+            Ok(bytes_read) => total_read += bytes_read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(total_read)
+}
+
+/// Read the contents of the file at the given offset using only aligned reads.
+fn read_aligned(file: &mut std::fs::File, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let (region_offset, region_len) = aligned_region(offset, buffer.len());
+    let mut region = AlignedBuffer::new(region_len);
+
+    let region_read = read_region(file, region_offset, &mut region)?;
+
+    let skip = usize::try_from(offset - region_offset).expect("bip_disk: Direct IO Region Too Large");
+    let bytes_read = region_read.saturating_sub(skip).min(buffer.len());
+    buffer[..bytes_read].copy_from_slice(&region.as_slice()[skip..skip + bytes_read]);
+
+    Ok(bytes_read)
+}
+
+/// Write the contents of the file at the given offset using only aligned writes.
+///
+/// Unaligned writes read the surrounding blocks first, and restore the length of the file afterwards.
+fn write_aligned(file: &mut std::fs::File, offset: u64, buffer: &[u8]) -> std::io::Result<usize> {
+    let (region_offset, region_len) = aligned_region(offset, buffer.len());
+    let mut region = AlignedBuffer::new(region_len);
+
+    let file_len = file.metadata()?.len();
+    let aligned = is_aligned(offset, buffer.len());
+    if !aligned {
+        read_region(file, region_offset, &mut region)?;
+    }
+
+    let skip = usize::try_from(offset - region_offset).expect("bip_disk: Direct IO Region Too Large");
+    region.as_mut_slice()[skip..skip + buffer.len()].copy_from_slice(buffer);
+
+    file.seek(std::io::SeekFrom::Start(region_offset))?;
+    file.write_all(region.as_slice())?;
+
+    let written_len = file_len.max(offset + buffer.len() as u64);
+    if !aligned && written_len < region_offset + region_len as u64 {
+        file.set_len(written_len)?;
+    }
+
+    Ok(buffer.len())
+}
+
+/// Create a path from the user path and current directory, sanitizing the components of the user path.
+fn combine_user_path<'a, P>(user_path: &'a P, current_dir: &Path, sanitize: SanitizePolicy) -> Cow<'a, Path>
 where
     P: AsRef<Path>,
 {
@@ -112,9 +335,176 @@ where
         let mut combine_user_path = current_dir.to_path_buf();
 
         for user_path_piece in ref_user_path {
-            combine_user_path.push(user_path_piece);
+            combine_user_path.push(sanitize.sanitize_component(user_path_piece));
         }
 
         Cow::Owned(combine_user_path)
     }
 }
+
+/// Length at which Windows paths stop working without the `\\?\` prefix.
+///
+/// Directories are limited to 248 characters rather than `MAX_PATH`, to leave room for an 8.3 file name.
+#[cfg(windows)]
+const MAX_WINDOWS_DIR_PATH: usize = 248;
+
+/// Prefix long paths with `\\?\`, so that they are not limited to `MAX_PATH` characters on Windows.
+///
+/// Prefixed paths are not normalized by Windows, so the path is made absolute and normalized here.
+#[cfg(windows)]
+fn long_path(path: Cow<'_, Path>) -> std::io::Result<Cow<'_, Path>> {
+    use std::path::{Component, Prefix};
+
+    if path.as_os_str().len() < MAX_WINDOWS_DIR_PATH {
+        return Ok(path);
+    }
+
+    let absolute_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(&path)
+    };
+
+    let mut long_path = std::ffi::OsString::new();
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+    for component in absolute_path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                // Already prefixed
+                Prefix::Verbatim(_) | Prefix::VerbatimUNC(..) | Prefix::VerbatimDisk(_) | Prefix::DeviceNS(_) => {
+                    return Ok(path);
+                }
+                Prefix::UNC(server, share) => {
+                    long_path.push(r"\\?\UNC\");
+                    long_path.push(server);
+                    long_path.push(r"\");
+                    long_path.push(share);
+                }
+                Prefix::Disk(_) => {
+                    long_path.push(r"\\?\");
+                    long_path.push(prefix.as_os_str());
+                }
+            },
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+
+    for part in parts {
+        long_path.push(r"\");
+        long_path.push(part);
+    }
+
+    Ok(Cow::Owned(PathBuf::from(long_path)))
+}
+
+/// Prefix long paths so that they are not limited in length, which is only necessary on Windows.
+#[cfg(not(windows))]
+#[allow(clippy::unnecessary_wraps)]
+fn long_path(path: Cow<'_, Path>) -> std::io::Result<Cow<'_, Path>> {
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{read_aligned, write_aligned, NativeFileSystem, DIRECT_IO_ALIGNMENT};
+    use crate::disk::fs::sanitize::SanitizePolicy;
+    use crate::disk::fs::FileSystem as _;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("disk_native_{name}_{}", rand::random::<u64>()))
+    }
+
+    fn temp_file(name: &str) -> (PathBuf, std::fs::File) {
+        let path = temp_dir(name);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .unwrap();
+
+        (path, file)
+    }
+
+    #[test]
+    fn positive_aligned_write_extends_file_to_exact_length() {
+        let (path, mut file) = temp_file("extend");
+
+        assert_eq!(3, write_aligned(&mut file, 10, b"abc").unwrap());
+        assert_eq!(13, file.metadata().unwrap().len());
+
+        let mut buffer = [1u8; 13];
+        assert_eq!(13, read_aligned(&mut file, 0, &mut buffer).unwrap());
+        assert_eq!(b"\0\0\0\0\0\0\0\0\0\0abc", &buffer);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn positive_aligned_write_preserves_surrounding_bytes() {
+        let (path, mut file) = temp_file("preserve");
+        let contents = vec![7u8; DIRECT_IO_ALIGNMENT * 2 + 100];
+
+        write_aligned(&mut file, 0, &contents).unwrap();
+        write_aligned(&mut file, DIRECT_IO_ALIGNMENT as u64 - 2, &[1, 2, 3, 4]).unwrap();
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(
+            8,
+            read_aligned(&mut file, DIRECT_IO_ALIGNMENT as u64 - 4, &mut buffer).unwrap()
+        );
+        assert_eq!([7, 7, 1, 2, 3, 4, 7, 7], buffer);
+        assert_eq!(contents.len() as u64, file.metadata().unwrap().len());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn positive_aligned_read_stops_at_end_of_file() {
+        let (path, mut file) = temp_file("eof");
+
+        write_aligned(&mut file, 0, b"hello").unwrap();
+
+        let mut buffer = [0u8; 10];
+        assert_eq!(3, read_aligned(&mut file, 2, &mut buffer).unwrap());
+        assert_eq!(b"llo", &buffer[..3]);
+        assert_eq!(0, read_aligned(&mut file, 20, &mut buffer).unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn positive_direct_io_file_system_round_trip() {
+        let dir = temp_dir("direct");
+        let fs = NativeFileSystem::with_directory(&dir).with_direct_io(true);
+
+        let mut file = fs.open_file("file").unwrap();
+        assert_eq!(5, fs.write_file(&mut file, 1, b"world").unwrap());
+        assert_eq!(6, fs.file_size(&file).unwrap());
+
+        let mut buffer = [0u8; 5];
+        assert_eq!(5, fs.read_file(&mut file, 1, &mut buffer).unwrap());
+        assert_eq!(b"world", &buffer);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn positive_windows_policy_sanitizes_user_path() {
+        let dir = temp_dir("sanitize");
+        let fs = NativeFileSystem::with_directory(&dir).with_sanitize_policy(SanitizePolicy::Windows);
+
+        let mut file = fs.open_file("CON/a:b.").unwrap();
+        assert_eq!(3, fs.write_file(&mut file, 0, b"abc").unwrap());
+
+        assert!(dir.join("CON_").join("a_b_").is_file());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
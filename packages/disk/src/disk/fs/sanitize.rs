@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+
+/// Character that invalid characters in a file name are replaced with.
+const REPLACEMENT_CHAR: char = '_';
+
+/// Device names that Windows reserves in every directory, regardless of their extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3",
+    "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Policy for rewriting the names of files and directories from a torrent before they are created on disk.
+///
+/// Names are rewritten deterministically, so the same torrent always maps to the same files. Distinct
+/// names in a torrent may map to the same name on disk, for example `a:b` and `a_b`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SanitizePolicy {
+    /// Use names as they are given.
+    Preserve,
+    /// Rewrite names that can not be created on Windows.
+    ///
+    /// Characters that are invalid in file names are replaced with an underscore, as are trailing dots and
+    /// spaces which Windows would otherwise silently strip. Reserved device names, such as `CON` or `nul.txt`,
+    /// have an underscore appended to their stem.
+    Windows,
+}
+
+impl SanitizePolicy {
+    /// Rewrite a single component of a path according to this policy.
+    #[must_use]
+    pub fn sanitize_component<'a>(&self, component: &'a OsStr) -> Cow<'a, OsStr> {
+        match self {
+            SanitizePolicy::Preserve => Cow::Borrowed(component),
+            SanitizePolicy::Windows => {
+                let name = component.to_string_lossy();
+                let sanitized = sanitize_windows(&name);
+
+                if sanitized.as_str() == component {
+                    Cow::Borrowed(component)
+                } else {
+                    Cow::Owned(OsString::from(sanitized))
+                }
+            }
+        }
+    }
+}
+
+impl Default for SanitizePolicy {
+    /// Rewrites names on Windows, and preserves them everywhere else.
+    fn default() -> SanitizePolicy {
+        if cfg!(windows) {
+            SanitizePolicy::Windows
+        } else {
+            SanitizePolicy::Preserve
+        }
+    }
+}
+
+fn sanitize_windows(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if is_invalid_windows_char(c) { REPLACEMENT_CHAR } else { c })
+        .collect();
+
+    let kept_len = sanitized.trim_end_matches(['.', ' ']).len();
+    let trailing = sanitized.len() - kept_len;
+    sanitized.truncate(kept_len);
+    for _ in 0..trailing {
+        sanitized.push(REPLACEMENT_CHAR);
+    }
+
+    if sanitized.is_empty() {
+        sanitized.push(REPLACEMENT_CHAR);
+    }
+
+    let stem_len = sanitized.find('.').unwrap_or(sanitized.len());
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(&sanitized[..stem_len]))
+    {
+        sanitized.insert(stem_len, REPLACEMENT_CHAR);
+    }
+
+    sanitized
+}
+
+fn is_invalid_windows_char(c: char) -> bool {
+    c.is_ascii_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::ffi::OsStr;
+
+    use super::SanitizePolicy;
+
+    fn sanitize(name: &str) -> String {
+        SanitizePolicy::Windows
+            .sanitize_component(OsStr::new(name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn positive_valid_name_borrowed() {
+        assert!(matches!(
+            SanitizePolicy::Windows.sanitize_component(OsStr::new("file.txt")),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn positive_preserve_keeps_invalid_name() {
+        assert_eq!(
+            OsStr::new("a:b?"),
+            SanitizePolicy::Preserve.sanitize_component(OsStr::new("a:b?"))
+        );
+    }
+
+    #[test]
+    fn positive_replace_invalid_chars() {
+        assert_eq!("a_b_c_d_", sanitize("a<b:c|d*"));
+        assert_eq!("tab_name", sanitize("tab\tname"));
+    }
+
+    #[test]
+    fn positive_replace_trailing_dots_and_spaces() {
+        assert_eq!("name__", sanitize("name. "));
+        assert_eq!("__", sanitize(".."));
+        assert_eq!("_", sanitize(""));
+        assert_eq!(".hidden", sanitize(".hidden"));
+    }
+
+    #[test]
+    fn positive_mangle_reserved_names() {
+        assert_eq!("CON_", sanitize("CON"));
+        assert_eq!("nul_.txt", sanitize("nul.txt"));
+        assert_eq!("Com1_.tar.gz", sanitize("Com1.tar.gz"));
+        assert_eq!("CONSOLE", sanitize("CONSOLE"));
+        assert_eq!("COM10", sanitize("COM10"));
+    }
+}
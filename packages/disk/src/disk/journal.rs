@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use util::bt::InfoHash;
+
+/// Reason a piece was verified against its hash.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerificationTrigger {
+    /// All of the blocks of the piece were written.
+    Written,
+    /// A block of the piece was loaded with checksum on read enabled.
+    Read,
+}
+
+/// Outcome of verifying a single piece, recorded in the verification journal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationRecord {
+    hash: InfoHash,
+    piece_index: u64,
+    trigger: VerificationTrigger,
+    verified_at: SystemTime,
+    duration: Duration,
+    expected_hash: InfoHash,
+    actual_hash: InfoHash,
+    contributors: Vec<SocketAddr>,
+}
+
+impl VerificationRecord {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        hash: InfoHash,
+        piece_index: u64,
+        trigger: VerificationTrigger,
+        verified_at: SystemTime,
+        duration: Duration,
+        expected_hash: InfoHash,
+        actual_hash: InfoHash,
+        contributors: Vec<SocketAddr>,
+    ) -> VerificationRecord {
+        VerificationRecord {
+            hash,
+            piece_index,
+            trigger,
+            verified_at,
+            duration,
+            expected_hash,
+            actual_hash,
+            contributors,
+        }
+    }
+
+    /// `InfoHash` of the torrent the piece belongs to.
+    #[must_use]
+    pub fn hash(&self) -> InfoHash {
+        self.hash
+    }
+
+    /// Index of the piece that was verified.
+    #[must_use]
+    pub fn piece_index(&self) -> u64 {
+        self.piece_index
+    }
+
+    /// Reason the piece was verified.
+    #[must_use]
+    pub fn trigger(&self) -> VerificationTrigger {
+        self.trigger
+    }
+
+    /// Time at which the piece was verified.
+    #[must_use]
+    pub fn verified_at(&self) -> SystemTime {
+        self.verified_at
+    }
+
+    /// Time taken to read and hash the piece.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Whether the piece matched its expected hash.
+    #[must_use]
+    pub fn is_good(&self) -> bool {
+        self.expected_hash == self.actual_hash
+    }
+
+    /// Hash of the piece given in the torrent.
+    #[must_use]
+    pub fn expected_hash(&self) -> InfoHash {
+        self.expected_hash
+    }
+
+    /// Hash calculated from the bytes of the piece in the `FileSystem`.
+    #[must_use]
+    pub fn actual_hash(&self) -> InfoHash {
+        self.actual_hash
+    }
+
+    /// Addresses of the peers that contributed blocks to the piece, see `Block::with_contributor`.
+    ///
+    /// Empty for pieces verified on read, or whose blocks were processed without a contributor.
+    #[must_use]
+    pub fn contributors(&self) -> &[SocketAddr] {
+        &self.contributors
+    }
+}
+
+/// Bounded ring buffer of the most recent `VerificationRecord`s, dropping the oldest once full.
+#[derive(Debug, Default)]
+pub struct VerificationJournal {
+    capacity: usize,
+    records: VecDeque<VerificationRecord>,
+}
+
+impl VerificationJournal {
+    /// Create a new `VerificationJournal`, a capacity of zero disables the journal.
+    pub fn new(capacity: usize) -> VerificationJournal {
+        VerificationJournal {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    pub fn record(&mut self, record: VerificationRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Records in the journal, oldest first.
+    pub fn records(&self) -> Vec<VerificationRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use util::bt;
+
+    use super::{VerificationJournal, VerificationRecord, VerificationTrigger};
+
+    fn record(piece_index: u64) -> VerificationRecord {
+        let hash = [0u8; bt::INFO_HASH_LEN].into();
+
+        VerificationRecord::new(
+            hash,
+            piece_index,
+            VerificationTrigger::Written,
+            SystemTime::now(),
+            Duration::ZERO,
+            hash,
+            hash,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn positive_drop_oldest_record() {
+        let mut journal = VerificationJournal::new(2);
+
+        for piece_index in 0..3 {
+            journal.record(record(piece_index));
+        }
+
+        let indices: Vec<u64> = journal.records().iter().map(VerificationRecord::piece_index).collect();
+        assert_eq!(vec![1, 2], indices);
+    }
+
+    #[test]
+    fn negative_disabled_journal() {
+        let mut journal = VerificationJournal::new(0);
+
+        journal.record(record(0));
+
+        assert!(journal.records().is_empty());
+    }
+}
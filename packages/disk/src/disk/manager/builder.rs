@@ -1,3 +1,4 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::disk::fs::FileSystem;
@@ -6,6 +7,9 @@ use crate::disk::manager::DiskManager;
 const DEFAULT_PENDING_SIZE: usize = 10;
 const DEFAULT_COMPLETED_SIZE: usize = 10;
 const DEFAULT_THREAD_POOL_SIZE: usize = 4;
+const DEFAULT_CHECKSUM_CACHE_SIZE: usize = 64;
+const DEFAULT_READ_AHEAD_WINDOW: usize = 1024 * 1024;
+const DEFAULT_HASH_BUFFER_SIZE: usize = 16 * 1024 * 1024;
 
 /// `DiskManagerBuilder` for building `DiskManager`s with different settings.
 #[allow(clippy::module_name_repetitions)]
@@ -13,6 +17,16 @@ pub struct DiskManagerBuilder {
     thread_pool_size: usize,
     pending_size: usize,
     completed_size: usize,
+    checksum_on_read: bool,
+    checksum_cache_size: usize,
+    read_ahead_window: usize,
+    resume_edge_hash: bool,
+    resume_partial_pieces: bool,
+    directory_quota: Option<u64>,
+    verification_journal_size: usize,
+    hash_buffer_size: usize,
+    incomplete_directory: Option<PathBuf>,
+    completed_directory: Option<PathBuf>,
 }
 
 impl Default for DiskManagerBuilder {
@@ -21,6 +35,16 @@ impl Default for DiskManagerBuilder {
             thread_pool_size: DEFAULT_THREAD_POOL_SIZE,
             pending_size: DEFAULT_PENDING_SIZE,
             completed_size: DEFAULT_COMPLETED_SIZE,
+            checksum_on_read: false,
+            checksum_cache_size: DEFAULT_CHECKSUM_CACHE_SIZE,
+            read_ahead_window: DEFAULT_READ_AHEAD_WINDOW,
+            resume_edge_hash: true,
+            resume_partial_pieces: false,
+            directory_quota: None,
+            verification_journal_size: 0,
+            hash_buffer_size: DEFAULT_HASH_BUFFER_SIZE,
+            incomplete_directory: None,
+            completed_directory: None,
         }
     }
 }
@@ -53,6 +77,110 @@ impl DiskManagerBuilder {
         self
     }
 
+    /// Specify whether pieces should be verified against their hash before blocks are loaded from them.
+    ///
+    /// Useful when seeding from storage that may be failing, as blocks from a corrupt piece will
+    /// not be loaded, and an `ODiskMessage::FoundCorruptPiece` will be sent instead.
+    #[must_use]
+    pub fn with_checksum_on_read(mut self, enabled: bool) -> DiskManagerBuilder {
+        self.checksum_on_read = enabled;
+        self
+    }
+
+    /// Specify the number of recently verified pieces, per torrent, that will not be verified again when read.
+    #[must_use]
+    pub fn with_checksum_cache_size(mut self, size: usize) -> DiskManagerBuilder {
+        self.checksum_cache_size = size;
+        self
+    }
+
+    /// Specify the number of bytes read ahead of the blocks loaded for torrents accessed sequentially.
+    ///
+    /// Sequential access is enabled per torrent with `IDiskMessage::SetSequentialAccess`, so that
+    /// subsequent blocks of the same and next (good) pieces are already cached when they are loaded,
+    /// for example when streaming a torrent for local playback. A window of zero disables read-ahead.
+    #[must_use]
+    pub fn with_read_ahead_window(mut self, size: usize) -> DiskManagerBuilder {
+        self.read_ahead_window = size;
+        self
+    }
+
+    /// Specify whether the first and last blocks of each file are hashed when saving `ResumeData`.
+    ///
+    /// Without this, files are fingerprinted only by their size and modification time.
+    #[must_use]
+    pub fn with_resume_edge_hash(mut self, enabled: bool) -> DiskManagerBuilder {
+        self.resume_edge_hash = enabled;
+        self
+    }
+
+    /// Specify whether blocks written for incomplete pieces are recorded when saving `ResumeData`.
+    ///
+    /// This lets in-progress pieces survive a restart, so only their missing blocks need to be requested again.
+    #[must_use]
+    pub fn with_resume_partial_pieces(mut self, enabled: bool) -> DiskManagerBuilder {
+        self.resume_partial_pieces = enabled;
+        self
+    }
+
+    /// Specify the limit, in bytes, shared by every torrent in the download directory of the `FileSystem`.
+    ///
+    /// The limit can be changed later with `IDiskMessage::SetQuota`, defaults to no limit.
+    #[must_use]
+    pub fn with_directory_quota(mut self, limit: u64) -> DiskManagerBuilder {
+        self.directory_quota = Some(limit);
+        self
+    }
+
+    /// Specify the number of piece verification outcomes kept in memory, for debugging recurring hash failures.
+    ///
+    /// Each outcome records its timing, the expected and calculated hashes, and the peers that contributed
+    /// blocks to the piece (see `Block::with_contributor`). Once full, the oldest outcome is dropped. The
+    /// outcomes are queried with `IDiskMessage::QueryVerificationJournal`, a size of zero (the default)
+    /// disables the journal.
+    #[must_use]
+    pub fn with_verification_journal_size(mut self, size: usize) -> DiskManagerBuilder {
+        self.verification_journal_size = size;
+        self
+    }
+
+    /// Specify the number of bytes, shared by every torrent, buffered for blocks written out of order.
+    ///
+    /// Pieces are hashed as their blocks are written, blocks written ahead of the rest of their piece are
+    /// buffered until the blocks before them are written. Once the buffer is full, pieces with blocks that
+    /// do not fit are instead read back from the `FileSystem` to be checked, defaults to 16 MiB.
+    #[must_use]
+    pub fn with_hash_buffer_size(mut self, size: usize) -> DiskManagerBuilder {
+        self.hash_buffer_size = size;
+        self
+    }
+
+    /// Specify the directory, within the `FileSystem`, that the files of torrents are downloaded in to.
+    ///
+    /// Defaults to storing the files directly in the `FileSystem`.
+    #[must_use]
+    pub fn with_incomplete_directory<P>(mut self, dir: P) -> DiskManagerBuilder
+    where
+        P: Into<PathBuf>,
+    {
+        self.incomplete_directory = Some(dir.into());
+        self
+    }
+
+    /// Specify the directory, within the `FileSystem`, that the files of torrents are moved to once complete.
+    ///
+    /// Once every piece of a torrent is good, its files are moved and it continues to be seeded from
+    /// the new location, which is sent as an `ODiskMessage::TorrentFinished`. The directory can be
+    /// changed per torrent with `IDiskMessage::SetCompletedDirectory`, defaults to not moving files.
+    #[must_use]
+    pub fn with_completed_directory<P>(mut self, dir: P) -> DiskManagerBuilder
+    where
+        P: Into<PathBuf>,
+    {
+        self.completed_directory = Some(dir.into());
+        self
+    }
+
     /// Retrieve the `ThreadPool` size.
     #[must_use]
     pub fn thread_pool_size(&self) -> usize {
@@ -71,6 +199,66 @@ impl DiskManagerBuilder {
         self.completed_size
     }
 
+    /// Retrieve whether pieces are verified when read.
+    #[must_use]
+    pub fn checksum_on_read(&self) -> bool {
+        self.checksum_on_read
+    }
+
+    /// Retrieve the number of verified pieces cached per torrent.
+    #[must_use]
+    pub fn checksum_cache_size(&self) -> usize {
+        self.checksum_cache_size
+    }
+
+    /// Retrieve the number of bytes read ahead for torrents accessed sequentially.
+    #[must_use]
+    pub fn read_ahead_window(&self) -> usize {
+        self.read_ahead_window
+    }
+
+    /// Retrieve whether the first and last blocks of each file are hashed when saving `ResumeData`.
+    #[must_use]
+    pub fn resume_edge_hash(&self) -> bool {
+        self.resume_edge_hash
+    }
+
+    /// Retrieve whether blocks written for incomplete pieces are recorded when saving `ResumeData`.
+    #[must_use]
+    pub fn resume_partial_pieces(&self) -> bool {
+        self.resume_partial_pieces
+    }
+
+    /// Retrieve the limit, in bytes, shared by every torrent in the download directory.
+    #[must_use]
+    pub fn directory_quota(&self) -> Option<u64> {
+        self.directory_quota
+    }
+
+    /// Retrieve the number of piece verification outcomes kept in memory.
+    #[must_use]
+    pub fn verification_journal_size(&self) -> usize {
+        self.verification_journal_size
+    }
+
+    /// Retrieve the number of bytes buffered for blocks written out of order.
+    #[must_use]
+    pub fn hash_buffer_size(&self) -> usize {
+        self.hash_buffer_size
+    }
+
+    /// Retrieve the directory that the files of torrents are downloaded in to.
+    #[must_use]
+    pub fn incomplete_directory(&self) -> Option<&Path> {
+        self.incomplete_directory.as_deref()
+    }
+
+    /// Retrieve the directory that the files of torrents are moved to once complete.
+    #[must_use]
+    pub fn completed_directory(&self) -> Option<&Path> {
+        self.completed_directory.as_deref()
+    }
+
     /// Build a `DiskManager` with the given `FileSystem`.
     pub fn build<F>(self, fs: Arc<F>) -> DiskManager<F>
     where
@@ -45,7 +45,7 @@ where
         let stream_capacity = builder.stream_buffer_capacity();
 
         let (out_send, out_recv) = mpsc::channel(stream_capacity);
-        let context = DiskManagerContext::new(out_send, fs);
+        let context = DiskManagerContext::new(out_send, fs, builder);
         let wake_queue = Arc::new(SegQueue::new());
 
         let sink = DiskManagerSink::new(context, sink_capacity, cur_sink_capacity.clone(), wake_queue.clone());
@@ -56,8 +56,10 @@ impl Stream for DiskManagerStream {
             Poll::Ready(Some(msg)) => {
                 match msg {
                     ODiskMessage::TorrentAdded(_)
+                    | ODiskMessage::TorrentResumed(_, _)
                     | ODiskMessage::TorrentRemoved(_)
                     | ODiskMessage::TorrentSynced(_)
+                    | ODiskMessage::ResumeDataSaved(_)
                     | ODiskMessage::BlockLoaded(_)
                     | ODiskMessage::BlockProcessed(_) => {
                         self.complete_work();
@@ -1,11 +1,17 @@
+use std::path::PathBuf;
+
 use metainfo::Metainfo;
 use util::bt::InfoHash;
 
+use crate::disk::journal::VerificationRecord;
+use crate::disk::resume::{PartialPiece, ResumeData, ResumeVerification};
 use crate::error::{BlockError, TorrentError};
 use crate::memory::block::{Block, BlockMut};
 
 pub mod fs;
+pub mod journal;
 pub mod manager;
+pub mod resume;
 mod tasks;
 
 //----------------------------------------------------------------------------//
@@ -15,6 +21,15 @@ mod tasks;
 pub enum IDiskMessage {
     /// Message to add a torrent to the disk manager.
     AddTorrent(Metainfo),
+    /// Message to add a torrent to the disk manager, using the given `ResumeData`.
+    ///
+    /// Files that still match their fingerprint are trusted, so their good pieces
+    /// are not checked again. Pieces overlapping files that changed are checked,
+    /// and if the `ResumeData` does not match the torrent, every piece is checked.
+    ///
+    /// Partial pieces recorded in the `ResumeData` are restored unless they overlap
+    /// a file that changed, in which case the whole piece has to be downloaded again.
+    ResumeTorrent(Metainfo, ResumeData),
     /// Message to remove a torrent from the disk manager.
     ///
     /// Note, this will NOT remove any data from the `FileSystem`,
@@ -32,10 +47,67 @@ pub enum IDiskMessage {
     /// message should be sent, otherwise, `IDiskMessage::RemoveTorrent` is
     /// sufficient.
     SyncTorrent(InfoHash),
+    /// Message to save the `ResumeData` for the torrent, so that it can be resumed after a restart.
+    ///
+    /// The `FileSystem` should be synced beforehand, so that the fingerprint of each file is final.
+    /// Incomplete pieces are only recorded if enabled with `DiskManagerBuilder::with_resume_partial_pieces`.
+    SaveResumeData(InfoHash),
     /// Message to load the given block in to memory.
     LoadBlock(BlockMut),
     /// Message to process the given block and persist it.
     ProcessBlock(Block),
+    /// Message to set the `VerifyPriority` of the given piece for the torrent (hash).
+    ///
+    /// Once all of their blocks are written, urgent pieces are hashed ahead of any
+    /// other pieces waiting to be verified, and are reported as soon as they are checked.
+    SetPiecePriority(InfoHash, u64, VerifyPriority),
+    /// Message to set whether the torrent (hash) is accessed sequentially, for example when streaming.
+    ///
+    /// While enabled, loading a block also reads ahead the subsequent blocks of the same and
+    /// next good pieces in to memory, up to `DiskManagerBuilder::with_read_ahead_window` bytes.
+    SetSequentialAccess(InfoHash, bool),
+    /// Message to set (or with `None`, remove) the limit, in bytes, of the given `QuotaScope`.
+    ///
+    /// Usage is projected from the pieces that have been allocated, so pieces are counted
+    /// in full once any of their blocks are written. Torrents paused by the previous limit
+    /// will try to allocate new pieces again.
+    SetQuota(QuotaScope, Option<u64>),
+    /// Message to query the outcomes of the most recent piece verifications, across all torrents.
+    ///
+    /// Empty unless enabled with `DiskManagerBuilder::with_verification_journal_size`.
+    QueryVerificationJournal,
+    /// Message to set (or with `None`, remove) the directory that the files of the torrent (hash) are moved to once complete.
+    ///
+    /// Overrides `DiskManagerBuilder::with_completed_directory` for the torrent. If the torrent is already
+    /// complete, its files are moved right away.
+    SetCompletedDirectory(InfoHash, Option<PathBuf>),
+    /// Message to remap the file at the given index of the torrent (hash) to the given path, for example to rename
+    /// the file or flatten its directories.
+    ///
+    /// The path is relative to the directory the files of the torrent are stored under, and replaces the path given
+    /// by the torrent, including its name. The file is moved right away, even while the torrent is downloading, and
+    /// the remapping is kept when the files are moved to the completed directory, and recorded in the `ResumeData`.
+    RemapFile(InfoHash, u64, PathBuf),
+}
+
+/// Scope over which the disk usage of allocated pieces is limited.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuotaScope {
+    /// Quota shared by every torrent in the download directory of the `FileSystem`.
+    Directory,
+    /// Quota for the given torrent (hash).
+    Torrent(InfoHash),
+}
+
+/// Priority with which a piece is hashed, once all of its blocks have been written.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VerifyPriority {
+    /// Piece is verified in order with other pieces.
+    #[default]
+    Normal,
+    /// Piece is verified before any normal pieces, for example when it is needed
+    /// for the endgame or a streaming deadline.
+    Urgent,
 }
 
 /// Messages that can be received from the `DiskManager`.
@@ -46,21 +118,66 @@ pub enum ODiskMessage {
     /// Any good pieces already existing for the torrent will be sent
     /// as `FoundGoodPiece` messages BEFORE this message is sent.
     TorrentAdded(InfoHash),
+    /// Message indicating that the torrent has been added from `ResumeData`,
+    /// as well as how its pieces were verified.
+    ///
+    /// Any good pieces for the torrent will be sent as `FoundGoodPiece`, and any
+    /// restored partial pieces as `FoundPartialPiece`, messages BEFORE this message is sent.
+    TorrentResumed(InfoHash, ResumeVerification),
     /// Message indicating that the torrent has been removed.
     TorrentRemoved(InfoHash),
     /// Message indicating that the torrent has been synced.
     TorrentSynced(InfoHash),
+    /// Message indicating that the `ResumeData` for a torrent has been saved.
+    ResumeDataSaved(ResumeData),
     /// Message indicating that a good piece has been identified for
     /// the given torrent (hash), as well as the piece index.
     FoundGoodPiece(InfoHash, u64),
+    /// Message indicating that the blocks written for an incomplete piece were restored
+    /// from `ResumeData` for the given torrent (hash), so only the missing blocks need to be requested.
+    FoundPartialPiece(InfoHash, PartialPiece),
     /// Message indicating that a bad piece has been identified for
     /// the given torrent (hash), as well as the piece index.
     FoundBadPiece(InfoHash, u64),
+    /// Message indicating that a piece previously found to be good no longer
+    /// matches its hash when read, for the given torrent (hash), as well as the
+    /// piece index, and the file and offset within that file where the piece begins.
+    ///
+    /// Only sent when checksum on read is enabled, the piece should be considered
+    /// missing, and will be checked again once it has been rewritten.
+    FoundCorruptPiece(InfoHash, u64, PathBuf, u64),
     /// Message indicating that the given block has been loaded.
     BlockLoaded(BlockMut),
     /// Message indicating that the given block has been processed.
     BlockProcessed(Block),
-    /// Error occurring from a `AddTorrent` or `RemoveTorrent` message.
+    /// Message indicating that the `VerifyPriority` of the given piece for the torrent (hash) has been set.
+    PiecePrioritySet(InfoHash, u64, VerifyPriority),
+    /// Message indicating that sequential access has been enabled (or disabled) for the torrent (hash).
+    SequentialAccessSet(InfoHash, bool),
+    /// Message indicating that the limit of the given `QuotaScope` has been set.
+    QuotaSet(QuotaScope, Option<u64>),
+    /// Message indicating that the directory the files of the torrent (hash) are moved to once complete has been set.
+    CompletedDirectorySet(InfoHash, Option<PathBuf>),
+    /// Message indicating that the file at the given index of the torrent (hash) has been remapped, as well
+    /// as the new path of the file within the `FileSystem`.
+    FileRemapped(InfoHash, u64, PathBuf),
+    /// Message indicating that every piece of the torrent (hash) is good, and its files were moved to
+    /// the completed directory, as well as the new path of each file within the `FileSystem`.
+    ///
+    /// Only sent if a completed directory is set, the torrent continues to be seeded from the new paths.
+    TorrentFinished(InfoHash, Vec<PathBuf>),
+    /// Message indicating that allocating a new piece for the given torrent (hash) would
+    /// exceed the limit of the given `QuotaScope`, as well as that limit.
+    ///
+    /// Writes that would allocate new pieces for the torrent are paused, failing with
+    /// `BlockError::QuotaExceeded`, while blocks of already allocated pieces are still
+    /// written. Only sent once per pause, so the application can prompt the user to
+    /// raise the quota with `IDiskMessage::SetQuota` or free up space.
+    QuotaExceeded(InfoHash, QuotaScope, u64),
+    /// Message containing the outcomes of the most recent piece verifications, oldest first.
+    VerificationJournal(Vec<VerificationRecord>),
+    /// Error occurring from a `AddTorrent`, `ResumeTorrent`, `RemoveTorrent`, `SaveResumeData`, `SetPiecePriority`, `SetSequentialAccess`,
+    /// `SetQuota`, `SetCompletedDirectory` or `RemapFile` message, or from moving the files of a torrent once complete.
     TorrentError(InfoHash, TorrentError),
     /// Error occurring from a `LoadBlock` message.
     LoadBlockError(BlockMut, BlockError),
@@ -0,0 +1,435 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bencode::{ben_bytes, ben_int, ben_list, ben_map, BDecodeOpt, BMutAccess, BRefAccess, BencodeMut, BencodeRef};
+use util::bt::InfoHash;
+use util::sha::ShaHash;
+
+/// Number of bytes hashed at both the start and the end of a file for its `FileFingerprint`.
+pub const FINGERPRINT_BLOCK_LEN: usize = 16 * 1024;
+
+const INFO_HASH_KEY: &[u8] = b"info hash";
+const PIECES_KEY: &[u8] = b"pieces";
+const FILES_KEY: &[u8] = b"files";
+const SIZE_KEY: &[u8] = b"size";
+const MODIFIED_SECS_KEY: &[u8] = b"mtime";
+const MODIFIED_NANOS_KEY: &[u8] = b"mtime nanos";
+const EDGE_HASH_KEY: &[u8] = b"edge hash";
+const PARTIAL_KEY: &[u8] = b"partial";
+const PIECE_KEY: &[u8] = b"piece";
+const BLOCKS_KEY: &[u8] = b"blocks";
+const REMAPPED_KEY: &[u8] = b"remapped";
+const FILE_KEY: &[u8] = b"file";
+const PATH_KEY: &[u8] = b"path";
+
+/// Fingerprint of the contents of a file, used to detect whether it changed since resume data was saved.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileFingerprint {
+    size: u64,
+    modified: Option<SystemTime>,
+    edge_hash: Option<ShaHash>,
+}
+
+impl FileFingerprint {
+    /// Create a new `FileFingerprint`.
+    ///
+    /// The edge hash, if present, covers the first and last `FINGERPRINT_BLOCK_LEN` bytes of the file.
+    #[must_use]
+    pub fn new(size: u64, modified: Option<SystemTime>, edge_hash: Option<ShaHash>) -> FileFingerprint {
+        FileFingerprint {
+            size,
+            modified,
+            edge_hash,
+        }
+    }
+
+    /// Size of the file in bytes.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Last modification time of the file, if the `FileSystem` keeps track of it.
+    #[must_use]
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Hash of the first and last blocks of the file, if it was calculated.
+    #[must_use]
+    pub fn edge_hash(&self) -> Option<ShaHash> {
+        self.edge_hash
+    }
+}
+
+/// Blocks written for a piece that was not yet complete when `ResumeData` was saved.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartialPiece {
+    piece_index: u64,
+    blocks: Vec<(u64, usize)>,
+}
+
+impl PartialPiece {
+    /// Create a new `PartialPiece`, with each block given as its offset within the piece and its length.
+    #[must_use]
+    pub fn new(piece_index: u64, blocks: Vec<(u64, usize)>) -> PartialPiece {
+        PartialPiece { piece_index, blocks }
+    }
+
+    /// Index of the piece.
+    #[must_use]
+    pub fn piece_index(&self) -> u64 {
+        self.piece_index
+    }
+
+    /// Offset within the piece and length of each block that was written, so only the rest need to be requested.
+    #[must_use]
+    pub fn blocks(&self) -> &[(u64, usize)] {
+        &self.blocks
+    }
+}
+
+/// Describes how the pieces of a resumed torrent were verified.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ResumeVerification {
+    /// Every file matched its fingerprint, so the good pieces were trusted without being hashed.
+    Trusted,
+    /// Some files changed, so only the pieces overlapping those files were hashed.
+    Partial,
+    /// The resume data did not match the torrent, so every piece was hashed.
+    Full,
+}
+
+/// Fast resume data for a torrent, recording the good pieces along with a fingerprint for each file.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeData {
+    info_hash: InfoHash,
+    good_pieces: Vec<u64>,
+    files: Vec<FileFingerprint>,
+    partial_pieces: Vec<PartialPiece>,
+    remapped_files: Vec<(u64, PathBuf)>,
+}
+
+impl ResumeData {
+    /// Create a new `ResumeData`, with the file fingerprints given in the same order as the files in the torrent.
+    #[must_use]
+    pub fn new(info_hash: InfoHash, good_pieces: Vec<u64>, files: Vec<FileFingerprint>) -> ResumeData {
+        ResumeData {
+            info_hash,
+            good_pieces,
+            files,
+            partial_pieces: Vec::new(),
+            remapped_files: Vec::new(),
+        }
+    }
+
+    /// Set the pieces that were only partially written when this data was saved.
+    #[must_use]
+    pub fn with_partial_pieces(mut self, partial_pieces: Vec<PartialPiece>) -> ResumeData {
+        self.partial_pieces = partial_pieces;
+        self
+    }
+
+    /// Set the files that were remapped away from the path given by the torrent, as their index and new path.
+    #[must_use]
+    pub fn with_remapped_files(mut self, remapped_files: Vec<(u64, PathBuf)>) -> ResumeData {
+        self.remapped_files = remapped_files;
+        self
+    }
+
+    /// Info hash of the torrent this data was saved for.
+    #[must_use]
+    pub fn info_hash(&self) -> InfoHash {
+        self.info_hash
+    }
+
+    /// Pieces that were good when this data was saved.
+    #[must_use]
+    pub fn good_pieces(&self) -> &[u64] {
+        &self.good_pieces
+    }
+
+    /// Fingerprints of the files, in the same order as the files in the torrent.
+    #[must_use]
+    pub fn files(&self) -> &[FileFingerprint] {
+        &self.files
+    }
+
+    /// Pieces that were only partially written when this data was saved.
+    #[must_use]
+    pub fn partial_pieces(&self) -> &[PartialPiece] {
+        &self.partial_pieces
+    }
+
+    /// Index and path, relative to the directory of the torrent, of each file that was remapped when this data was saved.
+    #[must_use]
+    pub fn remapped_files(&self) -> &[(u64, PathBuf)] {
+        &self.remapped_files
+    }
+
+    /// Encode the `ResumeData` as bencode, so that it can be persisted.
+    ///
+    /// Remapped paths are encoded as a list of UTF-8 components, so components which are not
+    /// valid UTF-8 are converted lossily.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if a piece index or file size does not fit in a bencode integer.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut pieces = BencodeMut::new_list();
+        {
+            let pieces_access = pieces.list_mut().unwrap();
+
+            for &piece_index in &self.good_pieces {
+                pieces_access.push(ben_int!(piece_index.try_into().unwrap()));
+            }
+        }
+
+        let mut files = BencodeMut::new_list();
+        {
+            let files_access = files.list_mut().unwrap();
+
+            for file in &self.files {
+                let mut bencode_file = ben_map! {
+                    SIZE_KEY => ben_int!(file.size.try_into().unwrap())
+                };
+                {
+                    let bencode_file_access = bencode_file.dict_mut().unwrap();
+
+                    if let Some(since_epoch) = file.modified.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()) {
+                        bencode_file_access.insert(MODIFIED_SECS_KEY.into(), ben_int!(since_epoch.as_secs().try_into().unwrap()));
+                        bencode_file_access.insert(MODIFIED_NANOS_KEY.into(), ben_int!(since_epoch.subsec_nanos().into()));
+                    }
+
+                    if let Some(edge_hash) = file.edge_hash.as_ref() {
+                        bencode_file_access.insert(EDGE_HASH_KEY.into(), ben_bytes!(edge_hash.as_ref()));
+                    }
+                }
+
+                files_access.push(bencode_file);
+            }
+        }
+
+        let mut resume_data = ben_map! {
+            INFO_HASH_KEY => ben_bytes!(self.info_hash.as_ref()),
+            PIECES_KEY => pieces,
+            FILES_KEY => files
+        };
+
+        if !self.partial_pieces.is_empty() {
+            let mut partial = BencodeMut::new_list();
+            {
+                let partial_access = partial.list_mut().unwrap();
+
+                for partial_piece in &self.partial_pieces {
+                    let mut blocks = BencodeMut::new_list();
+                    {
+                        let blocks_access = blocks.list_mut().unwrap();
+
+                        for &(offset, length) in &partial_piece.blocks {
+                            blocks_access.push(ben_list!(
+                                ben_int!(offset.try_into().unwrap()),
+                                ben_int!(length.try_into().unwrap())
+                            ));
+                        }
+                    }
+
+                    partial_access.push(ben_map! {
+                        PIECE_KEY => ben_int!(partial_piece.piece_index.try_into().unwrap()),
+                        BLOCKS_KEY => blocks
+                    });
+                }
+            }
+
+            resume_data.dict_mut().unwrap().insert(PARTIAL_KEY.into(), partial);
+        }
+
+        if !self.remapped_files.is_empty() {
+            let mut remapped = BencodeMut::new_list();
+            {
+                let remapped_access = remapped.list_mut().unwrap();
+
+                for (index, path) in &self.remapped_files {
+                    let mut components = BencodeMut::new_list();
+                    {
+                        let components_access = components.list_mut().unwrap();
+
+                        for component in path {
+                            components_access.push(ben_bytes!(component.to_string_lossy().into_owned()));
+                        }
+                    }
+
+                    remapped_access.push(ben_map! {
+                        FILE_KEY => ben_int!((*index).try_into().unwrap()),
+                        PATH_KEY => components
+                    });
+                }
+            }
+
+            resume_data.dict_mut().unwrap().insert(REMAPPED_KEY.into(), remapped);
+        }
+
+        resume_data.encode()
+    }
+
+    /// Decode `ResumeData` previously encoded with `to_bytes`, returning `None` if the bytes are not valid.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8]) -> Option<ResumeData> {
+        let bencode = BencodeRef::decode(bytes, BDecodeOpt::default()).ok()?;
+        let dict = bencode.dict()?;
+
+        let info_hash = InfoHash::from_hash(dict.lookup(INFO_HASH_KEY)?.bytes()?).ok()?;
+
+        let good_pieces = dict
+            .lookup(PIECES_KEY)?
+            .list()?
+            .into_iter()
+            .map(|piece_index| piece_index.int()?.try_into().ok())
+            .collect::<Option<Vec<u64>>>()?;
+
+        let files = dict
+            .lookup(FILES_KEY)?
+            .list()?
+            .into_iter()
+            .map(|bencode_file| {
+                let file_dict = bencode_file.dict()?;
+
+                let size = file_dict.lookup(SIZE_KEY)?.int()?.try_into().ok()?;
+                let modified = match (file_dict.lookup(MODIFIED_SECS_KEY), file_dict.lookup(MODIFIED_NANOS_KEY)) {
+                    (Some(secs), Some(nanos)) => {
+                        Some(UNIX_EPOCH + Duration::new(secs.int()?.try_into().ok()?, nanos.int()?.try_into().ok()?))
+                    }
+                    _ => None,
+                };
+                let edge_hash = match file_dict.lookup(EDGE_HASH_KEY) {
+                    Some(edge_hash) => Some(ShaHash::from_hash(edge_hash.bytes()?).ok()?),
+                    None => None,
+                };
+
+                Some(FileFingerprint::new(size, modified, edge_hash))
+            })
+            .collect::<Option<Vec<FileFingerprint>>>()?;
+
+        // Partial pieces are optional, resume data saved without them is still valid
+        let partial_pieces = match dict.lookup(PARTIAL_KEY) {
+            Some(partial) => partial
+                .list()?
+                .into_iter()
+                .map(|bencode_partial| {
+                    let partial_dict = bencode_partial.dict()?;
+
+                    let piece_index = partial_dict.lookup(PIECE_KEY)?.int()?.try_into().ok()?;
+                    let blocks = partial_dict
+                        .lookup(BLOCKS_KEY)?
+                        .list()?
+                        .into_iter()
+                        .map(|bencode_block| {
+                            let block = bencode_block.list()?;
+
+                            Some((block.get(0)?.int()?.try_into().ok()?, block.get(1)?.int()?.try_into().ok()?))
+                        })
+                        .collect::<Option<Vec<(u64, usize)>>>()?;
+
+                    Some(PartialPiece::new(piece_index, blocks))
+                })
+                .collect::<Option<Vec<PartialPiece>>>()?,
+            None => Vec::new(),
+        };
+
+        let remapped_files = match dict.lookup(REMAPPED_KEY) {
+            Some(remapped) => remapped
+                .list()?
+                .into_iter()
+                .map(|bencode_remapped| {
+                    let remapped_dict = bencode_remapped.dict()?;
+
+                    let index = remapped_dict.lookup(FILE_KEY)?.int()?.try_into().ok()?;
+                    let path = remapped_dict
+                        .lookup(PATH_KEY)?
+                        .list()?
+                        .into_iter()
+                        .map(|component| component.str())
+                        .collect::<Option<PathBuf>>()?;
+
+                    Some((index, path))
+                })
+                .collect::<Option<Vec<(u64, PathBuf)>>>()?,
+            None => Vec::new(),
+        };
+
+        Some(
+            ResumeData::new(info_hash, good_pieces, files)
+                .with_partial_pieces(partial_pieces)
+                .with_remapped_files(remapped_files),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use util::bt;
+    use util::sha::ShaHash;
+
+    use super::{FileFingerprint, PartialPiece, ResumeData};
+
+    #[test]
+    fn positive_resume_data_round_trip() {
+        let resume_data = ResumeData::new(
+            [1u8; bt::INFO_HASH_LEN].into(),
+            vec![0, 2, 5],
+            vec![
+                FileFingerprint::new(
+                    1024,
+                    Some(UNIX_EPOCH + Duration::new(1_700_000_000, 123)),
+                    Some(ShaHash::from_bytes(b"edges")),
+                ),
+                FileFingerprint::new(0, None, None),
+            ],
+        );
+
+        assert_eq!(Some(resume_data.clone()), ResumeData::from_bytes(&resume_data.to_bytes()));
+    }
+
+    #[test]
+    fn positive_resume_data_partial_pieces_round_trip() {
+        let resume_data = ResumeData::new(
+            [1u8; bt::INFO_HASH_LEN].into(),
+            vec![0],
+            vec![FileFingerprint::new(0, None, None)],
+        )
+        .with_partial_pieces(vec![
+            PartialPiece::new(1, vec![(0, 16384)]),
+            PartialPiece::new(3, vec![(0, 16384), (32768, 8192)]),
+        ]);
+
+        assert_eq!(Some(resume_data.clone()), ResumeData::from_bytes(&resume_data.to_bytes()));
+    }
+
+    #[test]
+    fn positive_resume_data_remapped_files_round_trip() {
+        let resume_data = ResumeData::new(
+            [1u8; bt::INFO_HASH_LEN].into(),
+            vec![0],
+            vec![FileFingerprint::new(0, None, None), FileFingerprint::new(0, None, None)],
+        )
+        .with_remapped_files(vec![(0, "renamed.mkv".into()), (1, "extras/sample.mkv".into())]);
+
+        assert_eq!(Some(resume_data.clone()), ResumeData::from_bytes(&resume_data.to_bytes()));
+    }
+
+    #[test]
+    fn negative_resume_data_missing_pieces() {
+        let mut bytes = b"d5:files".to_vec();
+        bytes.extend_from_slice(b"le9:info hash20:");
+        bytes.extend_from_slice(&[1u8; bt::INFO_HASH_LEN]);
+        bytes.push(b'e');
+
+        assert_eq!(None, ResumeData::from_bytes(&bytes));
+    }
+}
@@ -1,17 +1,24 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use futures::channel::mpsc;
 use futures::future::BoxFuture;
 use futures::lock::Mutex;
 use futures::sink::SinkExt;
+use lru_cache::LruCache;
 use metainfo::Metainfo;
 use util::bt::InfoHash;
 
+use crate::disk::journal::{VerificationJournal, VerificationRecord};
+use crate::disk::tasks::helpers::location::TorrentLocation;
 use crate::disk::tasks::helpers::piece_checker::PieceCheckerState;
+use crate::disk::tasks::helpers::piece_hasher::HashBuffer;
+use crate::disk::tasks::helpers::quota::{self, DirectoryQuota, QuotaExceeded, TorrentQuota};
+use crate::disk::tasks::helpers::read_ahead::ReadAhead;
 use crate::disk::ODiskMessage;
-use crate::FileSystem;
+use crate::{DiskManagerBuilder, FileSystem};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
@@ -23,6 +30,16 @@ where
     torrents: Arc<RwLock<HashMap<InfoHash, MetainfoState>>>,
     pub out: mpsc::Sender<ODiskMessage>,
     fs: Arc<F>,
+    checksum_on_read: bool,
+    checksum_cache_size: usize,
+    read_ahead_window: usize,
+    resume_edge_hash: bool,
+    resume_partial_pieces: bool,
+    directory_quota: Arc<std::sync::Mutex<DirectoryQuota>>,
+    journal: Arc<std::sync::Mutex<VerificationJournal>>,
+    hash_buffer: Arc<HashBuffer>,
+    incomplete_directory: Option<PathBuf>,
+    completed_directory: Option<PathBuf>,
 }
 
 impl<F> Clone for DiskManagerContext<F>
@@ -35,6 +52,16 @@ where
             torrents: self.torrents.clone(),
             out: self.out.clone(),
             fs: self.fs.clone(),
+            checksum_on_read: self.checksum_on_read,
+            checksum_cache_size: self.checksum_cache_size,
+            read_ahead_window: self.read_ahead_window,
+            resume_edge_hash: self.resume_edge_hash,
+            resume_partial_pieces: self.resume_partial_pieces,
+            directory_quota: self.directory_quota.clone(),
+            journal: self.journal.clone(),
+            hash_buffer: self.hash_buffer.clone(),
+            incomplete_directory: self.incomplete_directory.clone(),
+            completed_directory: self.completed_directory.clone(),
         }
     }
 }
@@ -43,11 +70,32 @@ where
 pub struct MetainfoState {
     pub file: Metainfo,
     pub checker: Arc<Mutex<PieceCheckerState>>,
+    /// Pieces recently verified when read, so that we do not hash the whole piece for every block.
+    pub verified: Arc<Mutex<LruCache<u64, ()>>>,
+    /// Pieces allocated in the `FileSystem`, counted against the disk quota.
+    pub quota: Arc<std::sync::Mutex<TorrentQuota>>,
+    /// Bytes read ahead of the loaded blocks, when the torrent is accessed sequentially.
+    pub read_ahead: Arc<Mutex<ReadAhead>>,
+    /// Outcomes of piece verifications, shared between all torrents.
+    pub journal: Arc<std::sync::Mutex<VerificationJournal>>,
+    /// Out of order blocks buffered while hashing pieces as they are written, shared between all torrents.
+    pub hash_buffer: Arc<HashBuffer>,
+    /// Directory the files are stored under, held for reading while the files are accessed.
+    pub location: Arc<RwLock<TorrentLocation>>,
 }
 
 impl MetainfoState {
-    pub fn new(file: Metainfo, state: Arc<Mutex<PieceCheckerState>>) -> MetainfoState {
-        MetainfoState { file, checker: state }
+    pub fn new(file: Metainfo, state: Arc<Mutex<PieceCheckerState>>, verified_capacity: usize) -> MetainfoState {
+        MetainfoState {
+            file,
+            checker: state,
+            verified: Arc::new(Mutex::new(LruCache::new(verified_capacity))),
+            quota: Arc::default(),
+            read_ahead: Arc::default(),
+            journal: Arc::default(),
+            hash_buffer: Arc::default(),
+            location: Arc::default(),
+        }
     }
 }
 
@@ -56,11 +104,23 @@ where
     F: FileSystem + Sync + 'static,
     Arc<F>: Send + Sync,
 {
-    pub fn new(out: mpsc::Sender<ODiskMessage>, fs: Arc<F>) -> DiskManagerContext<F> {
+    pub fn new(out: mpsc::Sender<ODiskMessage>, fs: Arc<F>, builder: &DiskManagerBuilder) -> DiskManagerContext<F> {
         DiskManagerContext {
             torrents: Arc::new(RwLock::new(HashMap::new())),
             out,
             fs,
+            checksum_on_read: builder.checksum_on_read(),
+            checksum_cache_size: builder.checksum_cache_size(),
+            read_ahead_window: builder.read_ahead_window(),
+            resume_edge_hash: builder.resume_edge_hash(),
+            resume_partial_pieces: builder.resume_partial_pieces(),
+            directory_quota: Arc::new(std::sync::Mutex::new(DirectoryQuota::new(builder.directory_quota()))),
+            journal: Arc::new(std::sync::Mutex::new(VerificationJournal::new(
+                builder.verification_journal_size(),
+            ))),
+            hash_buffer: Arc::new(HashBuffer::new(builder.hash_buffer_size())),
+            incomplete_directory: builder.incomplete_directory().map(PathBuf::from),
+            completed_directory: builder.completed_directory().map(PathBuf::from),
         }
     }
 
@@ -73,10 +133,34 @@ where
         &self.fs
     }
 
+    pub fn checksum_on_read(&self) -> bool {
+        self.checksum_on_read
+    }
+
+    pub fn read_ahead_window(&self) -> usize {
+        self.read_ahead_window
+    }
+
+    pub fn resume_edge_hash(&self) -> bool {
+        self.resume_edge_hash
+    }
+
+    pub fn resume_partial_pieces(&self) -> bool {
+        self.resume_partial_pieces
+    }
+
+    /// Directory that the files of newly added torrents are stored under.
+    pub fn incomplete_directory(&self) -> Option<PathBuf> {
+        self.incomplete_directory.clone()
+    }
+
+    /// Insert the torrent, counting its `allocated` pieces against the disk quota, with the given files remapped.
     pub fn insert_torrent(
         &self,
         file: Metainfo,
         state: &Arc<Mutex<PieceCheckerState>>,
+        allocated: &[u64],
+        remapped_files: &[(u64, PathBuf)],
     ) -> Result<InfoHash, (InfoHash, Box<MetainfoState>)> {
         let mut write_torrents = self
             .torrents
@@ -90,7 +174,19 @@ where
         match entry {
             Entry::Occupied(key) => Err((hash, key.get().clone().into())),
             Entry::Vacant(vac) => {
-                vac.insert(MetainfoState::new(file, state.clone()));
+                let torrent_quota = TorrentQuota::new(file.info(), allocated);
+                self.directory_quota.lock().unwrap().add_torrent(&torrent_quota);
+
+                let mut metainfo_state = MetainfoState::new(file, state.clone(), self.checksum_cache_size);
+                metainfo_state.quota = Arc::new(std::sync::Mutex::new(torrent_quota));
+                metainfo_state.journal = self.journal.clone();
+                metainfo_state.hash_buffer = self.hash_buffer.clone();
+                metainfo_state.location = Arc::new(RwLock::new(
+                    TorrentLocation::new(self.incomplete_directory(), self.completed_directory.clone())
+                        .with_remapped_files(metainfo_state.file.info(), remapped_files),
+                ));
+
+                vac.insert(metainfo_state);
                 Ok(hash)
             }
         }
@@ -118,6 +214,62 @@ where
             .write()
             .expect("bip_disk: DiskManagerContext::remove_torrent Failed To Write Torrent");
 
-        write_torrents.remove(&hash).is_some()
+        match write_torrents.remove(&hash) {
+            Some(state) => {
+                self.directory_quota
+                    .lock()
+                    .unwrap()
+                    .remove_torrent(&state.quota.lock().unwrap());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Allocate the given piece of the torrent, unless that would exceed the quota of the torrent or the directory.
+    pub fn allocate_piece(&self, hash: InfoHash, state: &MetainfoState, piece_index: u64) -> Result<(), QuotaExceeded> {
+        let piece_size = quota::piece_size(state.file.info(), piece_index);
+
+        let mut directory_quota = self.directory_quota.lock().unwrap();
+        state
+            .quota
+            .lock()
+            .unwrap()
+            .allocate(&mut directory_quota, hash, piece_index, piece_size)
+    }
+
+    /// Outcomes of the most recent piece verifications, oldest first.
+    pub fn verification_records(&self) -> Vec<VerificationRecord> {
+        self.journal.lock().unwrap().records()
+    }
+
+    /// Set the limit shared by every torrent in the download directory.
+    pub fn set_directory_quota(&self, limit: Option<u64>) {
+        let read_torrents = self
+            .torrents
+            .read()
+            .expect("bip_disk: DiskManagerContext::set_directory_quota Failed To Read Torrent");
+
+        self.directory_quota.lock().unwrap().set_limit(limit);
+
+        for state in read_torrents.values() {
+            state.quota.lock().unwrap().clear_paused();
+        }
+    }
+
+    /// Set the limit of the given torrent, returns false if the torrent is not added.
+    pub fn set_torrent_quota(&self, hash: InfoHash, limit: Option<u64>) -> bool {
+        let read_torrents = self
+            .torrents
+            .read()
+            .expect("bip_disk: DiskManagerContext::set_torrent_quota Failed To Read Torrent");
+
+        match read_torrents.get(&hash) {
+            Some(state) => {
+                state.quota.lock().unwrap().set_limit(limit);
+                true
+            }
+            None => false,
+        }
     }
 }
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use util::sha::ShaHashBuilder;
+
+use crate::disk::fs::FileSystem;
+use crate::disk::resume::{FileFingerprint, FINGERPRINT_BLOCK_LEN};
+
+/// Calculate the `FileFingerprint` for the file at the given path, hashing its first and last blocks if `with_hash` is set.
+pub fn fingerprint_file<F>(fs: &F, path: PathBuf, with_hash: bool) -> std::io::Result<FileFingerprint>
+where
+    F: FileSystem,
+{
+    let mut file = fs.open_file(path)?;
+
+    let size = fs.file_size(&file)?;
+    let modified = fs.file_modified(&file)?;
+
+    let edge_hash = if with_hash {
+        let edge_len = std::cmp::min(size, FINGERPRINT_BLOCK_LEN as u64);
+        let mut edge_buffer = vec![0u8; edge_len.try_into().unwrap()];
+
+        let mut builder = ShaHashBuilder::new();
+        for offset in [0, size - edge_len] {
+            let bytes_read = fs.read_file(&mut file, offset, &mut edge_buffer)?;
+            assert_eq!(bytes_read, edge_buffer.len());
+
+            builder = builder.add_bytes(&edge_buffer);
+        }
+
+        Some(builder.build())
+    } else {
+        None
+    };
+
+    Ok(FileFingerprint::new(size, modified, edge_hash))
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use metainfo::{File, Info};
+
+use crate::disk::fs::FileSystem;
+use crate::disk::tasks::helpers;
+
+/// Directory that the files of a torrent are stored under, and the directory they are moved to once it is complete.
+///
+/// Both directories are relative to the `FileSystem`, no directory stores the files directly in it.
+///
+/// Files may be remapped away from the path given by the torrent, to a path relative to the directory.
+#[derive(Debug, Default, Clone)]
+pub struct TorrentLocation {
+    current: Option<PathBuf>,
+    completed: Option<PathBuf>,
+    remapped: HashMap<PathBuf, PathBuf>,
+}
+
+impl TorrentLocation {
+    pub fn new(current: Option<PathBuf>, completed: Option<PathBuf>) -> TorrentLocation {
+        TorrentLocation {
+            current,
+            completed,
+            remapped: HashMap::new(),
+        }
+    }
+
+    /// Remap the files at the given indices to the given paths, ignoring indices out of range for the torrent.
+    pub fn with_remapped_files(mut self, info: &Info, remapped_files: &[(u64, PathBuf)]) -> TorrentLocation {
+        for (index, path) in remapped_files {
+            if let Some(file) = nth_file(info, *index) {
+                self.remapped
+                    .insert(helpers::build_path(info.directory(), file), path.clone());
+            }
+        }
+
+        self
+    }
+
+    /// Index and path of each remapped file of the torrent, ordered by index.
+    pub fn remapped_files(&self, info: &Info) -> Vec<(u64, PathBuf)> {
+        (0u64..)
+            .zip(info.files())
+            .filter_map(|(index, file)| {
+                let path = self.remapped.get(&helpers::build_path(info.directory(), file))?;
+
+                Some((index, path.clone()))
+            })
+            .collect()
+    }
+
+    /// Path of the given file of the torrent within the `FileSystem`.
+    pub fn file_path(&self, info: &Info, file: &File) -> PathBuf {
+        let path = self.relative_path(info, file);
+
+        match &self.current {
+            Some(dir) => dir.join(path),
+            None => path,
+        }
+    }
+
+    /// Path of the given file of the torrent relative to the directory, taking remapping into account.
+    fn relative_path(&self, info: &Info, file: &File) -> PathBuf {
+        let path = helpers::build_path(info.directory(), file);
+
+        match self.remapped.get(&path) {
+            Some(remapped) => remapped.clone(),
+            None => path,
+        }
+    }
+
+    /// Remap the file at the given index of the torrent to the given path, relative to the directory, moving it there.
+    ///
+    /// Returns the new path of the file within the `FileSystem`, or `None` if the index is out of range, or the
+    /// path is not relative, or is already used by another file of the torrent.
+    pub fn remap_file<F>(&mut self, fs: &F, info: &Info, index: u64, path: PathBuf) -> std::io::Result<Option<PathBuf>>
+    where
+        F: FileSystem,
+    {
+        let Some(file) = nth_file(info, index) else {
+            return Ok(None);
+        };
+
+        let is_taken = info
+            .files()
+            .any(|other| !std::ptr::eq(other, file) && self.relative_path(info, other) == path);
+        if !is_relative(&path) || is_taken {
+            return Ok(None);
+        }
+
+        let from = self.file_path(info, file);
+        let original = helpers::build_path(info.directory(), file);
+        let previous = if path == original {
+            self.remapped.remove(&original)
+        } else {
+            self.remapped.insert(original.clone(), path)
+        };
+        let to = self.file_path(info, file);
+
+        if from != to {
+            if let Err(err) = fs.move_file(from.clone(), to.clone()) {
+                match previous {
+                    Some(previous) => self.remapped.insert(original, previous),
+                    None => self.remapped.remove(&original),
+                };
+
+                return Err(err);
+            }
+        }
+
+        Ok(Some(to))
+    }
+
+    /// Set the directory the files are moved to once the torrent is complete, `None` leaves them where they are.
+    pub fn set_completed(&mut self, completed: Option<PathBuf>) {
+        self.completed = completed;
+    }
+
+    /// Move the files of the torrent to the completed directory, unless it is not set or they are already there.
+    ///
+    /// Returns the new path of each file, or `None` if no file was moved. If a file can not be moved, the
+    /// files moved before it are moved back, so that the torrent is not split between both directories.
+    pub fn move_to_completed<F>(&mut self, fs: &F, info: &Info) -> std::io::Result<Option<Vec<PathBuf>>>
+    where
+        F: FileSystem,
+    {
+        if self.completed.is_none() || self.completed == self.current {
+            return Ok(None);
+        }
+
+        let mut target = TorrentLocation::new(self.completed.clone(), None);
+        target.remapped.clone_from(&self.remapped);
+
+        let moves: Vec<(PathBuf, PathBuf)> = info
+            .files()
+            .map(|file| (self.file_path(info, file), target.file_path(info, file)))
+            .collect();
+
+        for (index, (from, to)) in moves.iter().enumerate() {
+            if let Err(err) = fs.move_file(from.clone(), to.clone()) {
+                for (from, to) in moves[..index].iter().rev() {
+                    if let Err(err) = fs.move_file(to.clone(), from.clone()) {
+                        tracing::warn!("Failed To Move {to:?} Back To {from:?}: {err}");
+                    }
+                }
+
+                return Err(err);
+            }
+        }
+
+        self.current = target.current;
+
+        Ok(Some(moves.into_iter().map(|(_, to)| to).collect()))
+    }
+}
+
+/// File at the given index of the torrent.
+fn nth_file(info: &Info, index: u64) -> Option<&File> {
+    info.files().nth(usize::try_from(index).ok()?)
+}
+
+/// Whether the path is relative and stays within the directory it is joined to.
+fn is_relative(path: &Path) -> bool {
+    path.components().next().is_some() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
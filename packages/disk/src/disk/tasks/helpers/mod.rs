@@ -2,8 +2,13 @@ use std::path::{Path, PathBuf};
 
 use metainfo::File;
 
+pub mod fingerprint;
+pub mod location;
 pub mod piece_accessor;
 pub mod piece_checker;
+pub mod piece_hasher;
+pub mod quota;
+pub mod read_ahead;
 
 pub fn build_path(parent_directory: Option<&Path>, file: &File) -> PathBuf {
     match parent_directory {
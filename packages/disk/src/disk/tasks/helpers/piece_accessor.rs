@@ -1,8 +1,8 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::disk::fs::FileSystem;
 use crate::disk::tasks::context::MetainfoState;
-use crate::disk::tasks::helpers;
 use crate::memory::block::BlockMetadata;
 
 pub struct PieceAccessor<F> {
@@ -37,6 +37,22 @@ where
         })
     }
 
+    /// Locate the file, and the offset within that file, where the given block begins.
+    pub fn locate(&self, message: &BlockMetadata) -> Option<(PathBuf, u64)> {
+        let mut total_bytes_to_skip = (message.piece_index() * self.state.file.info().piece_length()) + message.block_offset();
+        let location = self.state.location.read().unwrap();
+
+        for file in self.state.file.info().files() {
+            if total_bytes_to_skip < file.length() {
+                return Some((location.file_path(self.state.file.info(), file), total_bytes_to_skip));
+            }
+
+            total_bytes_to_skip -= file.length();
+        }
+
+        None
+    }
+
     /// Run the given closure with the file, the file offset, and the read/write buffer start (inclusive) and end (exclusive) indices.
     /// TODO: We do not detect when/if the file size changes after the initial file size check, so the returned number of
     fn run_with_file_regions<C>(&self, message: &BlockMetadata, mut callback: C) -> std::io::Result<()>
@@ -47,6 +63,9 @@ where
         let mut total_bytes_accessed = 0;
         let total_block_length = message.block_length() as u64;
 
+        // Files are not moved while they are accessed
+        let location = self.state.location.read().unwrap();
+
         for file in self.state.file.info().files() {
             let total_file_size = file.length();
 
@@ -57,7 +76,7 @@ where
             bytes_to_access -= min_bytes_to_skip;
 
             if bytes_to_access > 0 && total_bytes_accessed < total_block_length {
-                let file_path = helpers::build_path(self.state.file.info().directory(), file);
+                let file_path = location.file_path(self.state.file.info(), file);
                 let fs_file = self.fs.open_file(file_path)?;
 
                 let total_max_bytes_to_access = total_block_length - total_bytes_accessed;
@@ -1,5 +1,7 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex, PoisonError, RwLock};
+use std::time::{Instant, SystemTime};
 
 use futures::future::BoxFuture;
 use futures::lock::Mutex;
@@ -7,9 +9,13 @@ use metainfo::{Info, Metainfo};
 use util::bt::InfoHash;
 
 use crate::disk::fs::FileSystem;
+use crate::disk::journal::{VerificationRecord, VerificationTrigger};
+use crate::disk::resume::PartialPiece;
 use crate::disk::tasks::context::MetainfoState;
-use crate::disk::tasks::helpers;
+use crate::disk::tasks::helpers::location::TorrentLocation;
 use crate::disk::tasks::helpers::piece_accessor::PieceAccessor;
+use crate::disk::tasks::helpers::piece_hasher::{HashBuffer, PieceHasher};
+use crate::disk::VerifyPriority;
 use crate::error::{TorrentError, TorrentResult};
 use crate::memory::block::BlockMetadata;
 
@@ -24,8 +30,31 @@ where
     F: FileSystem + Sync + 'static,
     Arc<F>: Send + Sync,
 {
-    /// Create the initial `PieceCheckerState` for the `PieceChecker`.
-    pub async fn init_state(fs: Arc<F>, info_dict: Info) -> TorrentResult<Arc<Mutex<PieceCheckerState>>> {
+    /// Create the initial `PieceCheckerState` for the `PieceChecker`, for files stored at the given location.
+    pub async fn init_state(
+        fs: Arc<F>,
+        info_dict: Info,
+        location: TorrentLocation,
+    ) -> TorrentResult<Arc<Mutex<PieceCheckerState>>> {
+        PieceChecker::init_resumed_state(fs, info_dict, location, |_| true, &[]).await
+    }
+
+    /// Create the initial `PieceCheckerState` for the `PieceChecker`, only checking the pieces accepted
+    /// by `should_check`, and marking the `trusted` pieces as good without checking them.
+    ///
+    /// Files shorter than expected, as left behind by a crash while they were being allocated, are
+    /// allocated to their full size. Pieces overlapping the bytes they were missing are absent, so
+    /// they are neither checked, nor marked as good even if `trusted`.
+    pub async fn init_resumed_state<C>(
+        fs: Arc<F>,
+        info_dict: Info,
+        location: TorrentLocation,
+        should_check: C,
+        trusted: &[u64],
+    ) -> TorrentResult<Arc<Mutex<PieceCheckerState>>>
+    where
+        C: Fn(u64) -> bool + Send,
+    {
         let total_blocks = info_dict.pieces().count();
         let last_piece_size = last_piece_size(&info_dict);
 
@@ -33,13 +62,26 @@ where
 
         let file = Metainfo::new(info_dict.clone());
 
-        let state = MetainfoState::new(file, checker_state.clone());
+        // Only used for the initial check, so there is no need to cache pieces verified on read
+        let mut state = MetainfoState::new(file, checker_state.clone(), 0);
+        state.location = Arc::new(RwLock::new(location));
         {
             let mut piece_checker = PieceChecker::with_state(fs, state);
 
-            piece_checker.validate_files_sizes()?;
-            piece_checker.fill_checker_state().await;
+            let absent_pieces = piece_checker.validate_files_sizes()?;
+            piece_checker
+                .fill_checker_state(|piece_index| !absent_pieces.contains(&piece_index) && should_check(piece_index))
+                .await;
             piece_checker.calculate_diff().await?;
+
+            let mut check_state = checker_state.lock().await;
+
+            for &piece_index in trusted
+                .iter()
+                .filter(|&&piece_index| piece_index < total_blocks as u64 && !absent_pieces.contains(&piece_index))
+            {
+                check_state.mark_good(piece_index);
+            }
         }
 
         Ok(checker_state)
@@ -52,42 +94,145 @@ where
 
     /// Calculate the diff of old to new good/bad pieces and store them in the piece checker state
     /// to be retrieved by the caller.
-    pub async fn calculate_diff(self) -> std::io::Result<()> {
-        let piece_length = self.state.file.info().piece_length();
+    ///
+    /// Urgent pieces are checked first.
+    pub async fn calculate_diff(&self) -> std::io::Result<()> {
+        self.calculate_diff_with(false).await
+    }
+
+    /// Same as `calculate_diff`, but only checks pieces flagged as urgent.
+    pub async fn calculate_urgent_diff(&self) -> std::io::Result<()> {
+        self.calculate_diff_with(true).await
+    }
+
+    async fn calculate_diff_with(&self, urgent_only: bool) -> std::io::Result<()> {
+        let piece_length: usize = self.state.file.info().piece_length().try_into().unwrap();
         // TODO: Use Block Allocator
-        let mut piece_buffer = vec![0u8; piece_length.try_into().unwrap()];
+        let mut piece_buffer = vec![0u8; piece_length];
 
         let piece_accessor = PieceAccessor::new(self.fs.clone(), self.state.clone());
 
-        self.state
-            .checker
-            .lock()
-            .await
-            .run_with_whole_pieces(piece_length.try_into().unwrap(), |message| {
-                piece_accessor.read_piece(&mut piece_buffer[..message.block_length()], message)?;
-
-                let calculated_hash = InfoHash::from_bytes(&piece_buffer[..message.block_length()]);
-                let expected_hash = InfoHash::from_hash(
-                    self.state
-                        .file
-                        .info()
-                        .pieces()
-                        .nth(message.piece_index().try_into().unwrap())
-                        .expect("bip_peer: Piece Checker Failed To Retrieve Expected Hash"),
-                )
-                .expect("bip_peer: Wrong Length Of Expected Hash Received");
-
-                Ok(calculated_hash == expected_hash)
-            })?;
+        let whole_pieces = self.state.checker.lock().await.whole_pieces(piece_length, urgent_only);
+
+        // Pieces are hashed without holding the lock, so that urgent pieces can be checked in between
+        for piece_index in whole_pieces {
+            let (message, opt_hasher) = {
+                let mut check_state = self.state.checker.lock().await;
+
+                let Some(message) = check_state.take_whole_piece(piece_index, piece_length) else {
+                    continue;
+                };
+
+                (message, check_state.take_piece_hasher(piece_index))
+            };
+
+            let started = Instant::now();
+            // Pieces hashed as their blocks were written only need to be read back if that was not possible
+            let opt_hash = opt_hasher.and_then(|hasher| {
+                hasher
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .finish(message.block_length())
+            });
+
+            let calculated_hash = match opt_hash {
+                Some(hash) => hash,
+                None => match piece_accessor.read_piece(&mut piece_buffer[..message.block_length()], &message) {
+                    Ok(()) => InfoHash::from_bytes(&piece_buffer[..message.block_length()]),
+                    Err(err) => {
+                        self.state.checker.lock().await.add_pending_block(message);
+
+                        return Err(err);
+                    }
+                },
+            };
+            let expected_hash = expected_piece_hash(self.state.file.info(), piece_index);
+
+            let contributors = self
+                .state
+                .checker
+                .lock()
+                .await
+                .record_whole_piece(piece_index, calculated_hash == expected_hash);
+
+            self.record_verification(
+                piece_index,
+                VerificationTrigger::Written,
+                started,
+                (expected_hash, calculated_hash),
+                contributors,
+            );
+        }
 
         Ok(())
     }
 
-    /// Fill the `PieceCheckerState` with all piece messages for each file in our info dictionary.
+    /// Read the given piece from the file system and check it against its expected hash.
+    pub fn verify_piece(&self, piece_index: u64) -> std::io::Result<bool> {
+        let info = self.state.file.info();
+        let last_piece_size = last_piece_size(info);
+
+        let is_last_piece = piece_index + 1 == info.pieces().count() as u64;
+        let piece_size = if is_last_piece && last_piece_size != 0 {
+            last_piece_size
+        } else {
+            info.piece_length().try_into().unwrap()
+        };
+
+        let started = Instant::now();
+        let mut piece_buffer = vec![0u8; piece_size];
+        PieceAccessor::new(self.fs.clone(), self.state.clone()).read_piece(
+            &mut piece_buffer,
+            &BlockMetadata::with_default_hash(piece_index, 0, piece_size),
+        )?;
+
+        let calculated_hash = InfoHash::from_bytes(&piece_buffer);
+        let expected_hash = expected_piece_hash(info, piece_index);
+        self.record_verification(
+            piece_index,
+            VerificationTrigger::Read,
+            started,
+            (expected_hash, calculated_hash),
+            Vec::new(),
+        );
+
+        Ok(calculated_hash == expected_hash)
+    }
+
+    /// Record the outcome of verifying the given piece, started at the given time, in the verification journal.
+    fn record_verification(
+        &self,
+        piece_index: u64,
+        trigger: VerificationTrigger,
+        started: Instant,
+        (expected_hash, calculated_hash): (InfoHash, InfoHash),
+        contributors: Vec<SocketAddr>,
+    ) {
+        let mut journal = self.state.journal.lock().unwrap();
+        if !journal.is_enabled() {
+            return;
+        }
+
+        journal.record(VerificationRecord::new(
+            self.state.file.info().info_hash(),
+            piece_index,
+            trigger,
+            SystemTime::now(),
+            started.elapsed(),
+            expected_hash,
+            calculated_hash,
+            contributors,
+        ));
+    }
+
+    /// Fill the `PieceCheckerState` with piece messages, accepted by `should_check`, for each file in our info dictionary.
     ///
     /// This is done once when a torrent file is added to see if we have any good pieces that
     /// the caller can use to skip (if the torrent was partially downloaded before).
-    async fn fill_checker_state(&mut self) {
+    async fn fill_checker_state<C>(&mut self, should_check: C)
+    where
+        C: Fn(u64) -> bool,
+    {
         let piece_length = self.state.file.info().piece_length();
         let total_bytes: u64 = self.state.file.info().files().map(metainfo::File::length).sum();
 
@@ -95,7 +240,7 @@ where
         let last_piece_size = last_piece_size(self.state.file.info());
 
         let mut check_state = self.state.checker.lock().await;
-        for piece_index in 0..full_pieces {
+        for piece_index in (0..full_pieces).filter(|&piece_index| should_check(piece_index)) {
             check_state.add_pending_block(BlockMetadata::with_default_hash(
                 piece_index,
                 0,
@@ -103,53 +248,78 @@ where
             ));
         }
 
-        if last_piece_size != 0 {
+        if last_piece_size != 0 && should_check(full_pieces) {
             check_state.add_pending_block(BlockMetadata::with_default_hash(full_pieces, 0, last_piece_size));
         }
     }
 
     /// Validates the file sizes for the given torrent file and block allocates them if they do not exist.
     ///
-    /// This function will, if the file does not exist, or exists and is zero size, fill the file with zeroes.
-    /// Otherwise, if the file exists and it is of the correct size, it will be left alone. If it is of the wrong
-    /// size, an error will be thrown as we do not want to overwrite and existing file that maybe just had the same
-    /// name as a file in our dictionary.
-    fn validate_files_sizes(&mut self) -> TorrentResult<()> {
+    /// This function will, if the file does not exist, or exists and is shorter than expected, fill the rest of
+    /// the file with zeroes, returning the pieces overlapping the bytes that were missing. Otherwise, if the file
+    /// exists and it is of the correct size, it will be left alone. If it is larger than expected, an error will
+    /// be thrown as we do not want to overwrite and existing file that maybe just had the same name as a file in
+    /// our dictionary.
+    fn validate_files_sizes(&mut self) -> TorrentResult<HashSet<u64>> {
+        let location = self.state.location.read().unwrap();
+        let piece_length = self.state.file.info().piece_length();
+
+        let mut absent_pieces = HashSet::new();
+        let mut file_start = 0;
         for file in self.state.file.info().files() {
-            let file_path = helpers::build_path(self.state.file.info().directory(), file);
+            let file_path = location.file_path(self.state.file.info(), file);
             let expected_size = file.length();
 
-            self.fs
+            let actual_size = self
+                .fs
                 .open_file(file_path.clone())
                 .map_err(std::convert::Into::into)
                 .and_then(|mut file| {
-                    // File May Or May Not Have Existed Before, If The File Is Zero
-                    // Length, Assume It Wasn't There (User Doesn't Lose Any Data)
+                    // File May Or May Not Have Existed Before, If The File Is Zero Length, Assume It
+                    // Wasn't There, If It Is Shorter, Assume We Crashed Allocating It (User Doesn't Lose Any Data)
                     let actual_size = self.fs.file_size(&file)?;
 
-                    let size_matches = actual_size == expected_size;
-                    let size_is_zero = actual_size == 0;
-
-                    if !size_matches && size_is_zero {
+                    if actual_size < expected_size {
                         self.fs
                             .write_file(&mut file, expected_size - 1, &[0])
                             .expect("bip_peer: Failed To Create File When Validating Sizes");
-                    } else if !size_matches {
+                    } else if actual_size > expected_size {
                         return Err(TorrentError::ExistingFileSizeCheck {
-                            file_path,
+                            file_path: file_path.clone(),
                             expected_size,
                             actual_size,
                         });
                     }
 
-                    Ok(())
+                    Ok(actual_size)
                 })?;
+
+            if 0 < actual_size && actual_size < expected_size {
+                tracing::warn!(?file_path, actual_size, expected_size, "file was only partially allocated");
+            }
+            if actual_size < expected_size {
+                let file_end = file_start + expected_size;
+
+                absent_pieces.extend(((file_start + actual_size) / piece_length)..=((file_end - 1) / piece_length));
+            }
+
+            file_start += expected_size;
         }
 
-        Ok(())
+        Ok(absent_pieces)
     }
 }
 
+fn expected_piece_hash(info_dict: &Info, piece_index: u64) -> InfoHash {
+    InfoHash::from_hash(
+        info_dict
+            .pieces()
+            .nth(piece_index.try_into().unwrap())
+            .expect("bip_peer: Piece Checker Failed To Retrieve Expected Hash"),
+    )
+    .expect("bip_peer: Wrong Length Of Expected Hash Received")
+}
+
 fn last_piece_size(info_dict: &Info) -> usize {
     let piece_length = info_dict.piece_length();
     let total_bytes: u64 = info_dict.files().map(metainfo::File::length).sum();
@@ -166,6 +336,9 @@ pub struct PieceCheckerState {
     new_states: Vec<PieceState>,
     old_states: HashSet<PieceState>,
     pending_blocks: HashMap<u64, Vec<BlockMetadata>>,
+    contributors: HashMap<u64, HashSet<SocketAddr>>,
+    hashers: HashMap<u64, Arc<StdMutex<PieceHasher>>>,
+    urgent: HashSet<u64>,
     total_blocks: usize,
     last_block_size: usize,
 }
@@ -185,6 +358,9 @@ impl PieceCheckerState {
             new_states: Vec::new(),
             old_states: HashSet::new(),
             pending_blocks: HashMap::new(),
+            contributors: HashMap::new(),
+            hashers: HashMap::new(),
+            urgent: HashSet::new(),
             total_blocks,
             last_block_size,
         }
@@ -195,6 +371,107 @@ impl PieceCheckerState {
         self.pending_blocks.entry(msg.piece_index()).or_default().push(msg);
     }
 
+    /// Record that the given peer contributed a block to the given piece, until the piece is checked.
+    pub fn add_contributor(&mut self, piece_index: u64, addr: SocketAddr) {
+        self.contributors.entry(piece_index).or_default().insert(addr);
+    }
+
+    /// `PieceHasher` that the blocks written to the given piece should be added to, before they are added as pending.
+    ///
+    /// There is none if the piece is already good, or if blocks were written to it without being hashed, as
+    /// when they were restored from resume data, in which case the piece is read back once it is whole.
+    pub fn piece_hasher(&mut self, piece_index: u64, buffer: &Arc<HashBuffer>) -> Option<Arc<StdMutex<PieceHasher>>> {
+        let has_unhashed_blocks = self
+            .pending_blocks
+            .get(&piece_index)
+            .is_some_and(|messages| !messages.is_empty());
+        if self.is_good(piece_index) || (has_unhashed_blocks && !self.hashers.contains_key(&piece_index)) {
+            return None;
+        }
+
+        let hasher = self
+            .hashers
+            .entry(piece_index)
+            .or_insert_with(|| Arc::new(StdMutex::new(PieceHasher::new(buffer.clone()))));
+
+        Some(hasher.clone())
+    }
+
+    /// Mark the given piece as good without checking it, it will be reported with the next diff.
+    pub fn mark_good(&mut self, piece_index: u64) {
+        self.pending_blocks.remove(&piece_index);
+        self.new_states.push(PieceState::Good(piece_index));
+    }
+
+    /// Pieces that have been identified as good, and were already reported.
+    pub fn good_pieces(&self) -> Vec<u64> {
+        let mut good_pieces: Vec<u64> = self
+            .old_states
+            .iter()
+            .filter_map(|piece_state| match piece_state {
+                PieceState::Good(piece_index) => Some(*piece_index),
+                PieceState::Bad(_) => None,
+            })
+            .collect();
+        good_pieces.sort_unstable();
+
+        good_pieces
+    }
+
+    /// Whether the given piece has been identified as good, and was already reported.
+    pub fn is_good(&self, piece_index: u64) -> bool {
+        self.old_states.contains(&PieceState::Good(piece_index))
+    }
+
+    /// Whether every piece has been identified as good, and was already reported.
+    pub fn is_complete(&self) -> bool {
+        (0..self.total_blocks as u64).all(|piece_index| self.is_good(piece_index))
+    }
+
+    /// Pieces that have had some, but not all, of their blocks written, and are not known to be good.
+    pub fn partial_pieces(&self) -> Vec<PartialPiece> {
+        let mut partial_pieces: Vec<PartialPiece> = self
+            .pending_blocks
+            .iter()
+            .filter(|(_, messages)| !messages.is_empty())
+            .filter(|(piece_index, _)| !self.old_states.contains(&PieceState::Good(**piece_index)))
+            .filter(|(piece_index, _)| !self.new_states.contains(&PieceState::Good(**piece_index)))
+            .map(|(&piece_index, messages)| {
+                let mut blocks: Vec<(u64, usize)> = messages
+                    .iter()
+                    .map(|message| (message.block_offset(), message.block_length()))
+                    .collect();
+                blocks.sort_unstable();
+
+                PartialPiece::new(piece_index, blocks)
+            })
+            .collect();
+        partial_pieces.sort_unstable_by_key(PartialPiece::piece_index);
+
+        partial_pieces
+    }
+
+    /// Pieces that are known to be good, or have had some of their blocks written, and so take up space in the `FileSystem`.
+    pub fn allocated_pieces(&self) -> Vec<u64> {
+        let mut allocated_pieces = self.good_pieces();
+        allocated_pieces.extend(self.partial_pieces().iter().map(PartialPiece::piece_index));
+
+        allocated_pieces
+    }
+
+    /// Set the `VerifyPriority` of the given piece, urgent pieces stay urgent until they are found to be good.
+    pub fn set_priority(&mut self, piece_index: u64, priority: VerifyPriority) {
+        match priority {
+            VerifyPriority::Normal => self.urgent.remove(&piece_index),
+            VerifyPriority::Urgent => self.urgent.insert(piece_index),
+        };
+    }
+
+    /// Forget that the given piece was good, so that it will be checked again once it is rewritten.
+    pub fn invalidate_piece(&mut self, piece_index: u64) {
+        self.old_states.remove(&PieceState::Good(piece_index));
+    }
+
     /// Run the given closures against `NewGood` and `NewBad` messages. Each of the messages will
     /// then either be dropped (`NewBad`) or converted to `OldGood` (`NewGood`).
     pub async fn run_with_diff<F>(&mut self, mut callback: F)
@@ -208,39 +485,62 @@ impl PieceCheckerState {
         }
     }
 
-    /// Pass any pieces that have not been identified as `OldGood` into the callback which determines
-    /// if the piece is good or bad so it can be marked as `NewGood` or `NewBad`.
-    fn run_with_whole_pieces<F>(&mut self, piece_length: usize, mut callback: F) -> std::io::Result<()>
-    where
-        F: FnMut(&BlockMetadata) -> std::io::Result<bool>,
-    {
+    /// Pieces that have all of their blocks written, and have not been identified as `OldGood`, urgent pieces first.
+    fn whole_pieces(&mut self, piece_length: usize, urgent_only: bool) -> Vec<u64> {
         self.merge_pieces();
 
-        let new_states = &mut self.new_states;
-        let old_states = &self.old_states;
+        let mut whole_pieces: Vec<u64> = self
+            .pending_blocks
+            .iter()
+            .filter(|(_, messages)| piece_is_complete(self.total_blocks, self.last_block_size, piece_length, messages))
+            .map(|(&piece_index, _)| piece_index)
+            .filter(|piece_index| !self.old_states.contains(&PieceState::Good(*piece_index)))
+            .filter(|piece_index| !urgent_only || self.urgent.contains(piece_index))
+            .collect();
+        whole_pieces.sort_unstable_by_key(|piece_index| (!self.urgent.contains(piece_index), *piece_index));
+
+        whole_pieces
+    }
 
-        let total_blocks = self.total_blocks;
-        let last_block_size = self.last_block_size;
+    /// Take the merged block for the given piece, if it is still waiting to be checked.
+    fn take_whole_piece(&mut self, piece_index: u64, piece_length: usize) -> Option<BlockMetadata> {
+        if self.old_states.contains(&PieceState::Good(piece_index)) {
+            return None;
+        }
 
-        for messages in self
-            .pending_blocks
-            .values_mut()
-            .filter(|messages| piece_is_complete(total_blocks, last_block_size, piece_length, messages))
-            .filter(|messages| !old_states.contains(&PieceState::Good(messages[0].piece_index())))
-        {
-            let is_good = callback(&messages[0])?;
+        let messages = self.pending_blocks.get_mut(&piece_index)?;
+        if !piece_is_complete(self.total_blocks, self.last_block_size, piece_length, messages) {
+            return None;
+        }
 
-            if is_good {
-                new_states.push(PieceState::Good(messages[0].piece_index()));
-            } else {
-                new_states.push(PieceState::Bad(messages[0].piece_index()));
-            }
+        messages.pop()
+    }
+
+    /// Take the `PieceHasher` of the given piece, blocks written to the piece afterwards go to a new one.
+    fn take_piece_hasher(&mut self, piece_index: u64) -> Option<Arc<StdMutex<PieceHasher>>> {
+        self.hashers.remove(&piece_index)
+    }
 
-            // TODO: Should do a partial clear if user callback errors.
-            messages.clear();
+    /// Record the result of checking a piece taken with `take_whole_piece` as `NewGood` or `NewBad`.
+    ///
+    /// Returns the peers that contributed blocks to the piece, which are forgotten.
+    fn record_whole_piece(&mut self, piece_index: u64, is_good: bool) -> Vec<SocketAddr> {
+        let mut contributors: Vec<SocketAddr> = self
+            .contributors
+            .remove(&piece_index)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        contributors.sort_unstable();
+
+        if is_good {
+            self.urgent.remove(&piece_index);
+            self.new_states.push(PieceState::Good(piece_index));
+        } else {
+            self.new_states.push(PieceState::Bad(piece_index));
         }
 
-        Ok(())
+        contributors
     }
 
     /// Merges all pending piece messages into a single messages if possible.
@@ -334,8 +634,37 @@ fn merge_piece_messages(message_a: &BlockMetadata, message_b: &BlockMetadata) ->
 mod tests {
     use util::bt;
 
+    use super::PieceCheckerState;
+    use crate::disk::VerifyPriority;
     use crate::memory::block::BlockMetadata;
 
+    #[test]
+    fn positive_urgent_whole_pieces_first() {
+        let mut state = PieceCheckerState::new(4, 0);
+
+        for piece_index in 0..4 {
+            state.add_pending_block(BlockMetadata::new([0u8; bt::INFO_HASH_LEN].into(), piece_index, 0, 512));
+            state.add_pending_block(BlockMetadata::new([0u8; bt::INFO_HASH_LEN].into(), piece_index, 512, 512));
+        }
+        state.set_priority(2, VerifyPriority::Urgent);
+        state.set_priority(3, VerifyPriority::Urgent);
+        state.set_priority(3, VerifyPriority::Normal);
+
+        assert_eq!(vec![2], state.whole_pieces(1024, true));
+        assert_eq!(vec![2, 0, 1, 3], state.whole_pieces(1024, false));
+
+        let message = state.take_whole_piece(2, 1024).unwrap();
+        assert_eq!(
+            (2, 0, 1024),
+            (message.piece_index(), message.block_offset(), message.block_length())
+        );
+        assert!(state.take_whole_piece(2, 1024).is_none());
+
+        // Urgent pieces that turn out bad stay urgent for when they are rewritten
+        state.record_whole_piece(2, false);
+        assert!(state.urgent.contains(&2));
+    }
+
     #[test]
     fn positive_merge_duplicate_messages() {
         let metadata_a = BlockMetadata::new([0u8; bt::INFO_HASH_LEN].into(), 0, 5, 5);
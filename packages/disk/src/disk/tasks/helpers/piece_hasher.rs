@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use util::bt::InfoHash;
+use util::sha::ShaHashBuilder;
+
+/// Limit on the bytes of out of order blocks buffered by every `PieceHasher` sharing it.
+#[derive(Debug, Default)]
+pub struct HashBuffer {
+    limit: usize,
+    buffered: AtomicUsize,
+}
+
+impl HashBuffer {
+    /// Create a new `HashBuffer` holding at most `limit` bytes.
+    pub fn new(limit: usize) -> HashBuffer {
+        HashBuffer {
+            limit,
+            buffered: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserve room for the given number of bytes, returns false if that would exceed the limit.
+    fn reserve(&self, len: usize) -> bool {
+        self.buffered
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |buffered| {
+                buffered.checked_add(len).filter(|&buffered| buffered <= self.limit)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, len: usize) {
+        self.buffered.fetch_sub(len, Ordering::AcqRel);
+    }
+}
+
+/// Hashes the blocks of a piece as they are written, so that the piece can be checked without reading it back.
+///
+/// Blocks are hashed from the start of the piece, blocks written ahead of the bytes hashed so far are
+/// buffered until the blocks before them are written, as long as the `HashBuffer` has room for them.
+/// Blocks overlapping bytes that were already hashed or buffered (for example when a block is written
+/// twice), or that do not fit in the `HashBuffer`, poison the hasher, and the piece is then read back
+/// from the `FileSystem` to be checked.
+pub struct PieceHasher {
+    hasher: ShaHashBuilder,
+    hashed: u64,
+    buffered: BTreeMap<u64, Vec<u8>>,
+    buffer: Arc<HashBuffer>,
+    poisoned: bool,
+}
+
+impl PieceHasher {
+    /// Create a new `PieceHasher` buffering out of order blocks in the given `HashBuffer`.
+    pub fn new(buffer: Arc<HashBuffer>) -> PieceHasher {
+        PieceHasher {
+            hasher: ShaHashBuilder::default(),
+            hashed: 0,
+            buffered: BTreeMap::new(),
+            buffer,
+            poisoned: false,
+        }
+    }
+
+    /// Add the bytes of the block written at the given offset of the piece.
+    pub fn add_block(&mut self, block_offset: u64, bytes: &[u8]) {
+        if self.poisoned {
+            return;
+        }
+
+        let block_end = block_offset + bytes.len() as u64;
+        let overlaps_buffered = self
+            .buffered
+            .range(..block_end)
+            .next_back()
+            .is_some_and(|(&offset, buffered)| offset + buffered.len() as u64 > block_offset);
+        if block_offset < self.hashed || overlaps_buffered {
+            self.poison();
+
+            return;
+        }
+
+        if block_offset != self.hashed {
+            if self.buffer.reserve(bytes.len()) {
+                self.buffered.insert(block_offset, bytes.to_vec());
+            } else {
+                self.poison();
+            }
+
+            return;
+        }
+
+        self.hash_bytes(bytes);
+        while let Some(buffered) = self.buffered.remove(&self.hashed) {
+            self.buffer.release(buffered.len());
+            self.hash_bytes(&buffered);
+        }
+    }
+
+    /// Hash of the piece, if every byte of a piece of the given size was added exactly once.
+    pub fn finish(&self, piece_size: usize) -> Option<InfoHash> {
+        let is_whole = !self.poisoned && self.buffered.is_empty() && self.hashed == piece_size as u64;
+
+        is_whole.then(|| self.hasher.build())
+    }
+
+    fn hash_bytes(&mut self, bytes: &[u8]) {
+        self.hasher = std::mem::take(&mut self.hasher).add_bytes(bytes);
+        self.hashed += bytes.len() as u64;
+    }
+
+    fn poison(&mut self) {
+        self.poisoned = true;
+        self.release_buffered();
+    }
+
+    fn release_buffered(&mut self) {
+        let len = self.buffered.values().map(Vec::len).sum();
+
+        self.buffered.clear();
+        self.buffer.release(len);
+    }
+}
+
+impl Drop for PieceHasher {
+    fn drop(&mut self) {
+        self.release_buffered();
+    }
+}
+
+impl std::fmt::Debug for PieceHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PieceHasher")
+            .field("hashed", &self.hashed)
+            .field("buffered", &self.buffered.keys().collect::<Vec<_>>())
+            .field("poisoned", &self.poisoned)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use util::bt::InfoHash;
+
+    use super::{HashBuffer, PieceHasher};
+
+    fn piece_hasher() -> PieceHasher {
+        PieceHasher::new(Arc::new(HashBuffer::new(1024)))
+    }
+
+    #[test]
+    fn positive_hash_out_of_order_blocks() {
+        let piece: Vec<u8> = (0..=255).collect();
+        let mut hasher = piece_hasher();
+
+        hasher.add_block(128, &piece[128..192]);
+        hasher.add_block(192, &piece[192..]);
+        assert_eq!(None, hasher.finish(piece.len()));
+
+        hasher.add_block(0, &piece[..128]);
+        assert_eq!(Some(InfoHash::from_bytes(&piece)), hasher.finish(piece.len()));
+    }
+
+    #[test]
+    fn negative_hash_rewritten_block() {
+        let piece: Vec<u8> = (0..=255).collect();
+        let mut hasher = piece_hasher();
+
+        hasher.add_block(0, &piece[..128]);
+        hasher.add_block(0, &piece[..128]);
+        hasher.add_block(128, &piece[128..]);
+
+        assert_eq!(None, hasher.finish(piece.len()));
+    }
+
+    #[test]
+    fn negative_hash_overlapping_buffered_block() {
+        let piece: Vec<u8> = (0..=255).collect();
+        let mut hasher = piece_hasher();
+
+        hasher.add_block(128, &piece[128..]);
+        hasher.add_block(192, &piece[192..]);
+        hasher.add_block(0, &piece[..128]);
+
+        assert_eq!(None, hasher.finish(piece.len()));
+    }
+
+    #[test]
+    fn negative_hash_buffer_full() {
+        let piece: Vec<u8> = (0..=255).collect();
+        let buffer = Arc::new(HashBuffer::new(64));
+        let mut hasher = PieceHasher::new(buffer.clone());
+
+        hasher.add_block(128, &piece[128..192]);
+        hasher.add_block(192, &piece[192..]);
+        hasher.add_block(0, &piece[..128]);
+        assert_eq!(None, hasher.finish(piece.len()));
+
+        // Room taken by the poisoned hasher is given back, for the hashers of other pieces
+        let mut hasher = PieceHasher::new(buffer);
+        hasher.add_block(192, &piece[192..]);
+        hasher.add_block(0, &piece[..192]);
+        assert_eq!(Some(InfoHash::from_bytes(&piece)), hasher.finish(piece.len()));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use metainfo::Info;
+use util::bt::InfoHash;
+
+use crate::disk::QuotaScope;
+
+/// Bytes allocated for the pieces of every torrent in the download directory, and the quota they are limited to.
+#[derive(Debug, Default)]
+pub struct DirectoryQuota {
+    limit: Option<u64>,
+    used: u64,
+}
+
+impl DirectoryQuota {
+    pub fn new(limit: Option<u64>) -> DirectoryQuota {
+        DirectoryQuota { limit, used: 0 }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+    }
+
+    /// Add the bytes allocated for a newly added torrent.
+    pub fn add_torrent(&mut self, torrent: &TorrentQuota) {
+        self.used += torrent.used;
+    }
+
+    /// Release the bytes allocated for a removed torrent.
+    pub fn remove_torrent(&mut self, torrent: &TorrentQuota) {
+        self.used -= torrent.used;
+    }
+}
+
+/// Pieces allocated for a torrent, and the quota they are limited to.
+#[derive(Debug, Default)]
+pub struct TorrentQuota {
+    limit: Option<u64>,
+    pieces: HashSet<u64>,
+    used: u64,
+    paused: bool,
+}
+
+/// Quota that allocating a piece would have exceeded.
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub scope: QuotaScope,
+    pub limit: u64,
+    /// Whether writes were just paused, rather than having already been paused by an earlier allocation.
+    pub newly_paused: bool,
+}
+
+impl TorrentQuota {
+    /// Create a `TorrentQuota` for a torrent that already has data for the given pieces.
+    pub fn new(info: &Info, allocated: &[u64]) -> TorrentQuota {
+        TorrentQuota {
+            limit: None,
+            pieces: allocated.iter().copied().collect(),
+            used: allocated.iter().map(|&piece_index| piece_size(info, piece_index)).sum(),
+            paused: false,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+        self.paused = false;
+    }
+
+    /// Allow the next allocation to report that writes were paused again.
+    pub fn clear_paused(&mut self) {
+        self.paused = false;
+    }
+
+    /// Allocate the given piece, unless that would exceed the quota of the torrent or the directory.
+    ///
+    /// Pieces that were already allocated are always accepted, so partially written pieces can be finished.
+    pub fn allocate(
+        &mut self,
+        directory: &mut DirectoryQuota,
+        hash: InfoHash,
+        piece_index: u64,
+        piece_size: u64,
+    ) -> Result<(), QuotaExceeded> {
+        if self.pieces.contains(&piece_index) {
+            return Ok(());
+        }
+
+        let opt_exceeded = match (self.limit, directory.limit) {
+            (Some(limit), _) if self.used + piece_size > limit => Some((QuotaScope::Torrent(hash), limit)),
+            (_, Some(limit)) if directory.used + piece_size > limit => Some((QuotaScope::Directory, limit)),
+            _ => None,
+        };
+
+        if let Some((scope, limit)) = opt_exceeded {
+            let newly_paused = !self.paused;
+            self.paused = true;
+
+            return Err(QuotaExceeded {
+                scope,
+                limit,
+                newly_paused,
+            });
+        }
+
+        self.pieces.insert(piece_index);
+        self.used += piece_size;
+        self.paused = false;
+        directory.used += piece_size;
+
+        Ok(())
+    }
+}
+
+/// Size of the given piece, taking into account that the last piece may be shorter.
+pub fn piece_size(info: &Info, piece_index: u64) -> u64 {
+    let piece_length = info.piece_length();
+    let total_bytes: u64 = info.files().map(metainfo::File::length).sum();
+
+    std::cmp::min(piece_length, total_bytes.saturating_sub(piece_index * piece_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use util::bt::{self, InfoHash};
+
+    use super::{DirectoryQuota, QuotaExceeded, TorrentQuota};
+    use crate::disk::QuotaScope;
+
+    fn any_info_hash() -> InfoHash {
+        [55u8; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_allocate_within_quota() {
+        let mut directory = DirectoryQuota::new(Some(2048));
+        let mut torrent = TorrentQuota::default();
+
+        torrent.allocate(&mut directory, any_info_hash(), 0, 1024).unwrap();
+        torrent.allocate(&mut directory, any_info_hash(), 1, 1024).unwrap();
+
+        assert_eq!(2048, directory.used);
+        assert_eq!(2048, torrent.used);
+    }
+
+    #[test]
+    fn positive_allocated_piece_not_counted_twice() {
+        let mut directory = DirectoryQuota::new(Some(1024));
+        let mut torrent = TorrentQuota::default();
+
+        torrent.allocate(&mut directory, any_info_hash(), 0, 1024).unwrap();
+        torrent.allocate(&mut directory, any_info_hash(), 0, 1024).unwrap();
+
+        assert_eq!(1024, directory.used);
+    }
+
+    #[test]
+    fn negative_allocate_exceeds_directory_quota() {
+        let mut directory = DirectoryQuota::new(Some(1024));
+        let mut torrent = TorrentQuota::default();
+
+        torrent.allocate(&mut directory, any_info_hash(), 0, 1024).unwrap();
+
+        assert_eq!(
+            Err(QuotaExceeded {
+                scope: QuotaScope::Directory,
+                limit: 1024,
+                newly_paused: true
+            }),
+            torrent.allocate(&mut directory, any_info_hash(), 1, 1024)
+        );
+        assert_eq!(
+            Err(QuotaExceeded {
+                scope: QuotaScope::Directory,
+                limit: 1024,
+                newly_paused: false
+            }),
+            torrent.allocate(&mut directory, any_info_hash(), 2, 1024)
+        );
+    }
+
+    #[test]
+    fn negative_allocate_exceeds_torrent_quota() {
+        let mut directory = DirectoryQuota::new(None);
+        let mut torrent = TorrentQuota::default();
+        torrent.set_limit(Some(1000));
+
+        assert_eq!(
+            Err(QuotaExceeded {
+                scope: QuotaScope::Torrent(any_info_hash()),
+                limit: 1000,
+                newly_paused: true
+            }),
+            torrent.allocate(&mut directory, any_info_hash(), 0, 1024)
+        );
+
+        torrent.set_limit(Some(1024));
+        torrent.allocate(&mut directory, any_info_hash(), 0, 1024).unwrap();
+    }
+}
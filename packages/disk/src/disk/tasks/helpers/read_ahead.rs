@@ -0,0 +1,103 @@
+/// Bytes read ahead of the blocks loaded for a torrent that is accessed sequentially.
+///
+/// Offsets are relative to the start of the torrent, so the cached bytes may span pieces.
+#[derive(Debug, Default)]
+pub struct ReadAhead {
+    sequential: bool,
+    start: u64,
+    buffer: Vec<u8>,
+}
+
+impl ReadAhead {
+    pub fn sequential(&self) -> bool {
+        self.sequential
+    }
+
+    /// Set whether the torrent is accessed sequentially, any cached bytes are dropped when disabled.
+    pub fn set_sequential(&mut self, enabled: bool) {
+        self.sequential = enabled;
+
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// Copy the cached bytes at the given offset in to the buffer, returns false if not all of them are cached.
+    pub fn read(&self, offset: u64, buffer: &mut [u8]) -> bool {
+        let Some(begin) = offset.checked_sub(self.start) else {
+            return false;
+        };
+        let Ok(begin) = usize::try_from(begin) else {
+            return false;
+        };
+
+        match self.buffer.get(begin..begin + buffer.len()) {
+            Some(cached) => {
+                buffer.copy_from_slice(cached);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of bytes cached past the given offset.
+    pub fn remaining(&self, offset: u64) -> u64 {
+        (self.start + self.buffer.len() as u64).saturating_sub(offset.max(self.start))
+    }
+
+    /// Replace the cached bytes with the given bytes, starting at the given offset.
+    pub fn fill(&mut self, start: u64, buffer: Vec<u8>) {
+        self.start = start;
+        self.buffer = buffer;
+    }
+
+    /// Drop the cached bytes if any of them overlap the given range, as they were overwritten.
+    pub fn invalidate(&mut self, offset: u64, length: u64) {
+        if offset < self.start + self.buffer.len() as u64 && self.start < offset + length {
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadAhead;
+
+    #[test]
+    fn positive_read_cached_bytes() {
+        let mut read_ahead = ReadAhead::default();
+        read_ahead.fill(100, (0..10).collect());
+
+        let mut buffer = [0u8; 4];
+        assert!(read_ahead.read(103, &mut buffer));
+        assert_eq!([3, 4, 5, 6], buffer);
+        assert_eq!(3, read_ahead.remaining(107));
+    }
+
+    #[test]
+    fn negative_read_outside_cached_bytes() {
+        let mut read_ahead = ReadAhead::default();
+        read_ahead.fill(100, (0..10).collect());
+
+        let mut buffer = [0u8; 4];
+        assert!(!read_ahead.read(99, &mut buffer));
+        assert!(!read_ahead.read(107, &mut buffer));
+    }
+
+    #[test]
+    fn negative_read_invalidated_bytes() {
+        let mut read_ahead = ReadAhead::default();
+        read_ahead.fill(100, (0..10).collect());
+
+        read_ahead.invalidate(0, 100);
+        read_ahead.invalidate(110, 10);
+        assert_eq!(10, read_ahead.remaining(0));
+
+        read_ahead.invalidate(109, 1);
+        assert!(!read_ahead.read(100, &mut [0u8; 1]));
+    }
+}
@@ -1,18 +1,24 @@
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError};
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::{FutureExt, SinkExt as _};
-use metainfo::Metainfo;
+use metainfo::{Info, Metainfo};
 use util::bt::InfoHash;
 
 use crate::disk::fs::FileSystem;
-use crate::disk::tasks::context::DiskManagerContext;
+use crate::disk::resume::{PartialPiece, ResumeData, ResumeVerification};
+use crate::disk::tasks::context::{DiskManagerContext, MetainfoState};
+use crate::disk::tasks::helpers::fingerprint;
+use crate::disk::tasks::helpers::location::TorrentLocation;
 use crate::disk::tasks::helpers::piece_accessor::PieceAccessor;
 use crate::disk::tasks::helpers::piece_checker::{PieceChecker, PieceCheckerState, PieceState};
-use crate::disk::{IDiskMessage, ODiskMessage};
+use crate::disk::tasks::helpers::read_ahead::ReadAhead;
+use crate::disk::{IDiskMessage, ODiskMessage, QuotaScope, VerifyPriority};
 use crate::error::{BlockError, BlockResult, TorrentError, TorrentResult};
-use crate::memory::block::{Block, BlockMut};
+use crate::memory::block::{Block, BlockMetadata, BlockMut};
 
 pub mod context;
 mod helpers;
@@ -33,6 +39,14 @@ where
                 Err(err) => ODiskMessage::TorrentError(info_hash, err),
             }
         }
+        IDiskMessage::ResumeTorrent(metainfo, resume_data) => {
+            let info_hash = metainfo.info().info_hash();
+
+            match execute_resume_torrent(metainfo, &resume_data, context, sender.clone()).await {
+                Ok(verification) => ODiskMessage::TorrentResumed(info_hash, verification),
+                Err(err) => ODiskMessage::TorrentError(info_hash, err),
+            }
+        }
         IDiskMessage::RemoveTorrent(hash) => match execute_remove_torrent(hash, &context) {
             Ok(()) => ODiskMessage::TorrentRemoved(hash),
             Err(err) => ODiskMessage::TorrentError(hash, err),
@@ -41,7 +55,11 @@ where
             Ok(()) => ODiskMessage::TorrentSynced(hash),
             Err(err) => ODiskMessage::TorrentError(hash, err),
         },
-        IDiskMessage::LoadBlock(mut block) => match execute_load_block(&mut block, context).await {
+        IDiskMessage::SaveResumeData(hash) => match execute_save_resume_data(hash, context).await {
+            Ok(resume_data) => ODiskMessage::ResumeDataSaved(resume_data),
+            Err(err) => ODiskMessage::TorrentError(hash, err),
+        },
+        IDiskMessage::LoadBlock(mut block) => match execute_load_block(&mut block, context, sender.clone()).await {
             Ok(()) => ODiskMessage::BlockLoaded(block),
             Err(err) => ODiskMessage::LoadBlockError(block, err),
         },
@@ -49,6 +67,31 @@ where
             Ok(()) => ODiskMessage::BlockProcessed(block),
             Err(err) => ODiskMessage::ProcessBlockError(block, err),
         },
+        IDiskMessage::SetPiecePriority(hash, index, priority) => {
+            match execute_set_piece_priority(hash, index, priority, context).await {
+                Ok(()) => ODiskMessage::PiecePrioritySet(hash, index, priority),
+                Err(err) => ODiskMessage::TorrentError(hash, err),
+            }
+        }
+        IDiskMessage::SetSequentialAccess(hash, enabled) => match execute_set_sequential_access(hash, enabled, context).await {
+            Ok(()) => ODiskMessage::SequentialAccessSet(hash, enabled),
+            Err(err) => ODiskMessage::TorrentError(hash, err),
+        },
+        IDiskMessage::SetQuota(scope, limit) => match execute_set_quota(scope, limit, &context) {
+            Ok(()) => ODiskMessage::QuotaSet(scope, limit),
+            Err((hash, err)) => ODiskMessage::TorrentError(hash, err),
+        },
+        IDiskMessage::QueryVerificationJournal => ODiskMessage::VerificationJournal(context.verification_records()),
+        IDiskMessage::RemapFile(hash, index, path) => match execute_remap_file(hash, index, path, context).await {
+            Ok(new_path) => ODiskMessage::FileRemapped(hash, index, new_path),
+            Err(err) => ODiskMessage::TorrentError(hash, err),
+        },
+        IDiskMessage::SetCompletedDirectory(hash, dir) => {
+            match execute_set_completed_directory(hash, dir.clone(), context, sender.clone()).await {
+                Ok(()) => ODiskMessage::CompletedDirectorySet(hash, dir),
+                Err(err) => ODiskMessage::TorrentError(hash, err),
+            }
+        }
     };
 
     tracing::trace!("sending output disk message:  {out_msg:?}");
@@ -71,17 +114,207 @@ where
     Arc<F>: Send + Sync,
 {
     let info_hash = file.info().info_hash();
-    let init_state = PieceChecker::init_state(context.filesystem().clone(), file.info().clone()).await?;
+    let init_state = PieceChecker::init_state(
+        context.filesystem().clone(),
+        file.info().clone(),
+        TorrentLocation::new(context.incomplete_directory(), None),
+    )
+    .await?;
 
     // In case we are resuming a download, we need to send the diff for the newly added torrent
     send_piece_diff(&init_state, info_hash, sender, true).await;
 
-    match context.insert_torrent(file, &init_state) {
+    let allocated = init_state.lock().await.allocated_pieces();
+    match context.insert_torrent(file, &init_state, &allocated, &[]) {
         Ok(_) => Ok(()),
         Err((hash, _)) => Err(TorrentError::ExistingInfoHash { hash }),
     }
 }
 
+async fn execute_resume_torrent<F>(
+    file: Metainfo,
+    resume_data: &ResumeData,
+    context: DiskManagerContext<F>,
+    sender: mpsc::Sender<ODiskMessage>,
+) -> TorrentResult<ResumeVerification>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let info_hash = file.info().info_hash();
+    let filesystem = context.filesystem().clone();
+    let matches_torrent = resume_data.info_hash() == info_hash && resume_data.files().len() == file.info().files().count();
+
+    // Files are only looked for where they were remapped to if the resume data is for this torrent
+    let remapped_files = if matches_torrent { resume_data.remapped_files() } else { &[] };
+    let location = TorrentLocation::new(context.incomplete_directory(), None).with_remapped_files(file.info(), remapped_files);
+
+    // Fingerprint the files before their sizes are validated, as that may write to them
+    let opt_changed_files = if matches_torrent {
+        let info = file.info();
+        let mut changed_files = Vec::with_capacity(resume_data.files().len());
+
+        for (file, recorded) in info.files().zip(resume_data.files()) {
+            let path = location.file_path(info, file);
+            let current = fingerprint::fingerprint_file(&*filesystem, path, recorded.edge_hash().is_some())?;
+
+            changed_files.push(current != *recorded);
+        }
+
+        Some(changed_files)
+    } else {
+        None
+    };
+
+    let (verification, check_pieces) = match opt_changed_files {
+        Some(changed_files) if !changed_files.contains(&true) => (ResumeVerification::Trusted, Some(HashSet::new())),
+        Some(changed_files) if changed_files.contains(&false) => (
+            ResumeVerification::Partial,
+            Some(pieces_overlapping_files(file.info(), &changed_files)),
+        ),
+        _ => (ResumeVerification::Full, None),
+    };
+
+    // Blocks of a partial piece can only be trusted if none of the files it overlaps changed
+    let partial_pieces: Vec<PartialPiece> = match &check_pieces {
+        Some(check_pieces) => resume_data
+            .partial_pieces()
+            .iter()
+            .filter(|partial| partial.piece_index() < file.info().pieces().count() as u64)
+            .filter(|partial| !check_pieces.contains(&partial.piece_index()))
+            .filter(|partial| !resume_data.good_pieces().contains(&partial.piece_index()))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let init_state = match check_pieces {
+        Some(check_pieces) => {
+            let trusted: Vec<u64> = resume_data
+                .good_pieces()
+                .iter()
+                .copied()
+                .filter(|piece_index| !check_pieces.contains(piece_index))
+                .collect();
+
+            PieceChecker::init_resumed_state(
+                filesystem,
+                file.info().clone(),
+                location,
+                |piece_index| check_pieces.contains(&piece_index),
+                &trusted,
+            )
+            .await?
+        }
+        None => PieceChecker::init_state(filesystem, file.info().clone(), location).await?,
+    };
+
+    send_piece_diff(&init_state, info_hash, sender.clone(), true).await;
+
+    if !partial_pieces.is_empty() {
+        restore_partial_pieces(&init_state, info_hash, &partial_pieces).await;
+
+        send_partial_pieces(info_hash, partial_pieces, sender).await;
+    }
+
+    let allocated = init_state.lock().await.allocated_pieces();
+    match context.insert_torrent(file, &init_state, &allocated, remapped_files) {
+        Ok(_) => Ok(verification),
+        Err((hash, _)) => Err(TorrentError::ExistingInfoHash { hash }),
+    }
+}
+
+/// Add the blocks of each partial piece back as pending, so the piece is checked once its missing blocks are written.
+async fn restore_partial_pieces(checker_state: &Arc<Mutex<PieceCheckerState>>, hash: InfoHash, partial_pieces: &[PartialPiece]) {
+    let mut check_state = checker_state.lock().await;
+
+    for partial in partial_pieces {
+        for &(block_offset, block_length) in partial.blocks() {
+            check_state.add_pending_block(BlockMetadata::new(hash, partial.piece_index(), block_offset, block_length));
+        }
+    }
+}
+
+async fn send_partial_pieces(hash: InfoHash, partial_pieces: Vec<PartialPiece>, mut sender: mpsc::Sender<ODiskMessage>) {
+    for partial in partial_pieces {
+        sender
+            .send(ODiskMessage::FoundPartialPiece(hash, partial))
+            .await
+            .expect("bip_disk: Failed To Send Partial Piece Message");
+    }
+}
+
+/// Indices of all pieces that contain bytes from any of the files marked as changed.
+fn pieces_overlapping_files(info: &Info, changed_files: &[bool]) -> HashSet<u64> {
+    let piece_length = info.piece_length();
+    let mut overlapping = HashSet::new();
+
+    let mut file_start = 0;
+    for (file, &changed) in info.files().zip(changed_files) {
+        let file_end = file_start + file.length();
+
+        if changed && file_end > file_start {
+            overlapping.extend((file_start / piece_length)..=((file_end - 1) / piece_length));
+        }
+
+        file_start = file_end;
+    }
+
+    overlapping
+}
+
+async fn execute_save_resume_data<F>(hash: InfoHash, context: DiskManagerContext<F>) -> TorrentResult<ResumeData>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let resume_edge_hash = context.resume_edge_hash();
+    let resume_partial_pieces = context.resume_partial_pieces();
+
+    let save_result = context
+        .update_torrent(hash, |fs, state| {
+            async move {
+                let (good_pieces, partial_pieces) = {
+                    let check_state = state.checker.lock().await;
+
+                    let partial_pieces = if resume_partial_pieces {
+                        check_state.partial_pieces()
+                    } else {
+                        Vec::new()
+                    };
+
+                    (check_state.good_pieces(), partial_pieces)
+                };
+
+                let (files, remapped_files) = {
+                    let location = state.location.read().unwrap();
+
+                    let files = state
+                        .file
+                        .info()
+                        .files()
+                        .map(|file| {
+                            fingerprint::fingerprint_file(&*fs, location.file_path(state.file.info(), file), resume_edge_hash)
+                        })
+                        .collect::<std::io::Result<Vec<_>>>()?;
+
+                    (files, location.remapped_files(state.file.info()))
+                };
+
+                Ok(ResumeData::new(hash, good_pieces, files)
+                    .with_partial_pieces(partial_pieces)
+                    .with_remapped_files(remapped_files))
+            }
+            .boxed()
+        })
+        .await;
+
+    match save_result {
+        Some(result) => result,
+        None => Err(TorrentError::InfoHashNotFound { hash }),
+    }
+}
+
 fn execute_remove_torrent<F>(hash: InfoHash, context: &DiskManagerContext<F>) -> TorrentResult<()>
 where
     F: FileSystem + Sync + 'static,
@@ -103,10 +336,10 @@ where
 
     let sync_result = context
         .update_torrent(hash, |_, state| {
-            let opt_parent_dir = state.file.info().directory();
+            let location = state.location.read().unwrap();
 
             for file in state.file.info().files() {
-                let path = helpers::build_path(opt_parent_dir, file);
+                let path = location.file_path(state.file.info(), file);
 
                 match filesystem.sync_file(path) {
                     Ok(()) => continue,
@@ -124,33 +357,125 @@ where
     }
 }
 
-async fn execute_load_block<F>(block: &mut BlockMut, context: DiskManagerContext<F>) -> BlockResult<()>
+async fn execute_load_block<F>(
+    block: &mut BlockMut,
+    context: DiskManagerContext<F>,
+    mut sender: mpsc::Sender<ODiskMessage>,
+) -> BlockResult<()>
 where
     F: FileSystem + Sync + 'static,
     Arc<F>: Send + Sync,
 {
     let metadata = block.metadata();
     let info_hash = metadata.info_hash();
-    let context = context.clone();
+    let checksum_on_read = context.checksum_on_read();
+    let read_ahead_window = context.read_ahead_window();
 
     let access_result = context
         .update_torrent(info_hash, |fs, state| {
             async move {
-                let piece_accessor = PieceAccessor::new(fs, state);
+                let piece_index = metadata.piece_index();
+
+                if checksum_on_read && !state.verified.lock().await.contains_key(&piece_index) {
+                    if !PieceChecker::with_state(fs.clone(), state.clone()).verify_piece(piece_index)? {
+                        state.checker.lock().await.invalidate_piece(piece_index);
+
+                        return Ok(Some(
+                            PieceAccessor::new(fs, state.clone()).locate(&BlockMetadata::with_default_hash(piece_index, 0, 0)),
+                        ));
+                    }
+
+                    state.verified.lock().await.insert(piece_index, ());
+                }
+
+                let piece_accessor = PieceAccessor::new(fs, state.clone());
+
+                let mut read_ahead = state.read_ahead.lock().await;
+                if read_ahead_window == 0 || !read_ahead.sequential() {
+                    // Read The Piece In From The Filesystem;
+                    return piece_accessor.read_piece(&mut *block, &metadata).map(|()| None);
+                }
 
-                // Read The Piece In From The Filesystem;
-                piece_accessor.read_piece(&mut *block, &metadata)
+                let offset = piece_index * state.file.info().piece_length() + metadata.block_offset();
+                if !read_ahead.read(offset, &mut block[..]) {
+                    piece_accessor.read_piece(&mut *block, &metadata)?;
+                }
+
+                // Refill once half of the window has been consumed, so that the next blocks never stall
+                let end = offset + metadata.block_length() as u64;
+                if read_ahead.remaining(end) < read_ahead_window as u64 / 2 {
+                    if let Err(err) = fill_read_ahead(&piece_accessor, &state, &mut read_ahead, end, read_ahead_window).await {
+                        tracing::warn!("Failed To Read Ahead Of Piece {piece_index} For Torrent {info_hash}: {err}");
+
+                        read_ahead.clear();
+                    }
+                }
+
+                Ok(None)
             }
             .boxed()
         })
         .await;
 
     match access_result {
-        Some(result) => Ok(result?),
+        Some(Ok(None)) => Ok(()),
+        Some(Ok(Some(opt_location))) => {
+            let index = metadata.piece_index();
+            tracing::warn!("Piece {index} For Torrent {info_hash} Failed Verification On Read At {opt_location:?}");
+
+            if let Some((file_path, file_offset)) = opt_location {
+                sender
+                    .send(ODiskMessage::FoundCorruptPiece(info_hash, index, file_path, file_offset))
+                    .await
+                    .expect("bip_disk: Failed To Send Corrupt Piece Message");
+            }
+
+            Err(BlockError::CorruptPiece { hash: info_hash, index })
+        }
+        Some(Err(err)) => Err(err.into()),
         None => Err(BlockError::InfoHashNotFound { hash: info_hash }),
     }
 }
 
+/// Read up to `window` bytes, starting at the given offset of the torrent, stopping before the first piece that is not good.
+async fn fill_read_ahead<F>(
+    piece_accessor: &PieceAccessor<F>,
+    state: &MetainfoState,
+    read_ahead: &mut ReadAhead,
+    start: u64,
+    window: usize,
+) -> std::io::Result<()>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let piece_length = state.file.info().piece_length();
+    let total_length: u64 = state.file.info().files().map(metainfo::File::length).sum();
+    let mut end = total_length.min(start + window as u64);
+
+    let checker = state.checker.lock().await;
+    let mut piece_index = start / piece_length;
+    while piece_index * piece_length < end {
+        if !checker.is_good(piece_index) {
+            end = end.min(piece_index * piece_length).max(start);
+            break;
+        }
+        piece_index += 1;
+    }
+    drop(checker);
+
+    let length = usize::try_from(end - start).expect("bip_disk: Read Ahead Window Exceeds usize");
+    let mut buffer = vec![0u8; length];
+    if length != 0 {
+        let metadata = BlockMetadata::with_default_hash(start / piece_length, start % piece_length, length);
+
+        piece_accessor.read_piece(&mut buffer, &metadata)?;
+    }
+    read_ahead.fill(start, buffer);
+
+    Ok(())
+}
+
 async fn execute_process_block<F>(
     block: &Block,
     context: DiskManagerContext<F>,
@@ -162,27 +487,78 @@ where
 {
     let metadata = block.metadata();
     let info_hash = metadata.info_hash();
+    let quota_context = context.clone();
 
     let block_result = context
         .update_torrent(info_hash, |fs, state| {
             tracing::trace!("Updating Blocks for Torrent: {info_hash}");
 
             async move {
+                let piece_index = metadata.piece_index();
+
+                if let Err(exceeded) = quota_context.allocate_piece(info_hash, &state, piece_index) {
+                    tracing::debug!("Piece {piece_index} For Torrent {info_hash} Would Exceed Quota: {exceeded:?}");
+
+                    if exceeded.newly_paused {
+                        sender
+                            .clone()
+                            .send(ODiskMessage::QuotaExceeded(info_hash, exceeded.scope, exceeded.limit))
+                            .await
+                            .expect("bip_disk: Failed To Send Quota Exceeded Message");
+                    }
+
+                    return Err(BlockError::QuotaExceeded {
+                        hash: info_hash,
+                        index: piece_index,
+                    });
+                }
+
                 let piece_accessor = PieceAccessor::new(fs.clone(), state.clone());
 
                 // Write Out Piece Out To The Filesystem And Recalculate The Diff
-                let block_result = match piece_accessor.write_piece(block, &metadata) {
-                    Ok(()) => {
-                        state.checker.lock().await.add_pending_block(metadata);
+                if let Err(e) = piece_accessor.write_piece(block, &metadata) {
+                    send_piece_diff(&state.checker, info_hash, sender.clone(), false).await;
+
+                    return Err(e.into());
+                }
 
-                        PieceChecker::with_state(fs, state.clone()).calculate_diff().await
+                // Hash the block while it is at hand, so that the piece does not have to be read back once it is whole
+                let opt_hasher = state.checker.lock().await.piece_hasher(piece_index, &state.hash_buffer);
+                if let Some(hasher) = opt_hasher {
+                    hasher
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .add_block(metadata.block_offset(), block);
+                }
+
+                state.verified.lock().await.remove(&metadata.piece_index());
+                state.read_ahead.lock().await.invalidate(
+                    metadata.piece_index() * state.file.info().piece_length() + metadata.block_offset(),
+                    metadata.block_length() as u64,
+                );
+                {
+                    let mut check_state = state.checker.lock().await;
+
+                    check_state.add_pending_block(metadata);
+                    if let Some(addr) = block.contributor() {
+                        check_state.add_contributor(metadata.piece_index(), addr);
                     }
-                    Err(e) => Err(e),
-                };
+                }
 
-                send_piece_diff(&state.checker, state.file.info().info_hash(), sender.clone(), false).await;
+                let piece_checker = PieceChecker::with_state(fs.clone(), state.clone());
 
-                block_result
+                // Report urgent pieces before waiting on the rest to be hashed
+                let urgent_result = piece_checker.calculate_urgent_diff().await;
+                send_piece_diff(&state.checker, info_hash, sender.clone(), false).await;
+                urgent_result?;
+
+                let block_result = piece_checker.calculate_diff().await;
+                send_piece_diff(&state.checker, info_hash, sender.clone(), false).await;
+                block_result?;
+
+                finish_torrent(&*fs, &state, info_hash, sender.clone()).await;
+
+                Ok(())
             }
             .boxed()
         })
@@ -196,6 +572,160 @@ where
     }
 }
 
+async fn execute_set_sequential_access<F>(hash: InfoHash, enabled: bool, context: DiskManagerContext<F>) -> TorrentResult<()>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let opt_result = context
+        .update_torrent(hash, |_, state| {
+            async move {
+                state.read_ahead.lock().await.set_sequential(enabled);
+            }
+            .boxed()
+        })
+        .await;
+
+    opt_result.ok_or(TorrentError::InfoHashNotFound { hash })
+}
+
+async fn execute_set_piece_priority<F>(
+    hash: InfoHash,
+    index: u64,
+    priority: VerifyPriority,
+    context: DiskManagerContext<F>,
+) -> TorrentResult<()>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let opt_result = context
+        .update_torrent(hash, |_, state| {
+            async move {
+                let num_pieces = state.file.info().pieces().count() as u64;
+                if index >= num_pieces {
+                    return Err(TorrentError::PieceOutOfRange { hash, index, num_pieces });
+                }
+
+                state.checker.lock().await.set_priority(index, priority);
+
+                Ok(())
+            }
+            .boxed()
+        })
+        .await;
+
+    match opt_result {
+        Some(result) => result,
+        None => Err(TorrentError::InfoHashNotFound { hash }),
+    }
+}
+
+async fn execute_set_completed_directory<F>(
+    hash: InfoHash,
+    dir: Option<PathBuf>,
+    context: DiskManagerContext<F>,
+    sender: mpsc::Sender<ODiskMessage>,
+) -> TorrentResult<()>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let opt_result = context
+        .update_torrent(hash, |fs, state| {
+            async move {
+                state.location.write().unwrap().set_completed(dir);
+
+                finish_torrent(&*fs, &state, hash, sender).await;
+            }
+            .boxed()
+        })
+        .await;
+
+    opt_result.ok_or(TorrentError::InfoHashNotFound { hash })
+}
+
+async fn execute_remap_file<F>(
+    hash: InfoHash,
+    index: u64,
+    path: PathBuf,
+    context: DiskManagerContext<F>,
+) -> TorrentResult<PathBuf>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    let opt_result = context
+        .update_torrent(hash, |fs, state| {
+            let info = state.file.info();
+            let num_files = info.files().count() as u64;
+            if index >= num_files {
+                return std::future::ready(Err(TorrentError::FileOutOfRange { hash, index, num_files })).boxed();
+            }
+
+            let remap_result = state.location.write().unwrap().remap_file(&*fs, info, index, path.clone());
+
+            let result = match remap_result {
+                Ok(Some(new_path)) => Ok(new_path),
+                Ok(None) => Err(TorrentError::InvalidFilePath { hash, path }),
+                Err(err) => Err(err.into()),
+            };
+
+            std::future::ready(result).boxed()
+        })
+        .await;
+
+    opt_result.unwrap_or(Err(TorrentError::InfoHashNotFound { hash }))
+}
+
+/// Move the files of the torrent to its completed directory, if every piece is good, and report their new paths.
+async fn finish_torrent<F>(fs: &F, state: &MetainfoState, hash: InfoHash, mut sender: mpsc::Sender<ODiskMessage>)
+where
+    F: FileSystem,
+{
+    if !state.checker.lock().await.is_complete() {
+        return;
+    }
+
+    let move_result = state.location.write().unwrap().move_to_completed(fs, state.file.info());
+
+    let opt_out_msg = match move_result {
+        Ok(Some(paths)) => Some(ODiskMessage::TorrentFinished(hash, paths)),
+        Ok(None) => None,
+        Err(err) => {
+            tracing::warn!("Failed To Move Files Of Completed Torrent {hash}: {err}");
+
+            Some(ODiskMessage::TorrentError(hash, err.into()))
+        }
+    };
+
+    if let Some(out_msg) = opt_out_msg {
+        sender
+            .send(out_msg)
+            .await
+            .expect("bip_disk: Failed To Send Torrent Finished Message");
+    }
+}
+
+fn execute_set_quota<F>(
+    scope: QuotaScope,
+    limit: Option<u64>,
+    context: &DiskManagerContext<F>,
+) -> Result<(), (InfoHash, TorrentError)>
+where
+    F: FileSystem + Sync + 'static,
+    Arc<F>: Send + Sync,
+{
+    match scope {
+        QuotaScope::Directory => {
+            context.set_directory_quota(limit);
+            Ok(())
+        }
+        QuotaScope::Torrent(hash) if context.set_torrent_quota(hash, limit) => Ok(()),
+        QuotaScope::Torrent(hash) => Err((hash, TorrentError::InfoHashNotFound { hash })),
+    }
+}
+
 async fn send_piece_diff(
     checker_state: &Arc<Mutex<PieceCheckerState>>,
     hash: InfoHash,
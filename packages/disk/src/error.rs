@@ -11,6 +11,12 @@ pub enum BlockError {
 
     #[error("Failed To Load/Process Block Because The InfoHash {hash:?} Is Not Currently Added")]
     InfoHashNotFound { hash: InfoHash },
+
+    #[error("Failed To Load Block Because Piece {index} For InfoHash {hash:?} Failed Verification")]
+    CorruptPiece { hash: InfoHash, index: u64 },
+
+    #[error("Failed To Process Block Because Allocating Piece {index} For InfoHash {hash:?} Would Exceed The Disk Quota")]
+    QuotaExceeded { hash: InfoHash, index: u64 },
 }
 
 pub type BlockResult<T> = Result<T, BlockError>;
@@ -36,6 +42,15 @@ pub enum TorrentError {
 
     #[error("Failed To Remove Torrent Because The InfoHash {hash:?} Is Not Currently Added")]
     InfoHashNotFound { hash: InfoHash },
+
+    #[error("Failed To Set Piece Priority Because Piece {index} Is Out Of Range For InfoHash {hash:?} With {num_pieces} Pieces")]
+    PieceOutOfRange { hash: InfoHash, index: u64, num_pieces: u64 },
+
+    #[error("Failed To Remap File Because File {index} Is Out Of Range For InfoHash {hash:?} With {num_files} Files")]
+    FileOutOfRange { hash: InfoHash, index: u64, num_files: u64 },
+
+    #[error("Failed To Remap File Because {path:?} Is Not A Relative Path Distinct From The Other Files Of InfoHash {hash:?}")]
+    InvalidFilePath { hash: InfoHash, path: PathBuf },
 }
 
 pub type TorrentResult<T> = Result<T, TorrentError>;
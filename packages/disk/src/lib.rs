@@ -5,14 +5,17 @@ mod memory;
 pub mod error;
 
 pub use crate::disk::fs::FileSystem;
+pub use crate::disk::journal::{VerificationRecord, VerificationTrigger};
 pub use crate::disk::manager::builder::DiskManagerBuilder;
 pub use crate::disk::manager::{DiskManager, DiskManagerSink, DiskManagerStream};
-pub use crate::disk::{IDiskMessage, ODiskMessage};
+pub use crate::disk::resume::{FileFingerprint, PartialPiece, ResumeData, ResumeVerification, FINGERPRINT_BLOCK_LEN};
+pub use crate::disk::{IDiskMessage, ODiskMessage, QuotaScope, VerifyPriority};
 pub use crate::memory::block::{Block, BlockMetadata, BlockMut};
 
 /// Built in objects implementing `FileSystem`.
 pub mod fs {
     pub use crate::disk::fs::native::{NativeFile, NativeFileSystem};
+    pub use crate::disk::fs::sanitize::SanitizePolicy;
 }
 
 /// Built in objects implementing `FileSystem` for caching.
@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 
 use bytes::{Bytes, BytesMut};
@@ -65,12 +66,26 @@ impl Default for BlockMetadata {
 pub struct Block {
     metadata: BlockMetadata,
     block_data: Bytes,
+    contributor: Option<SocketAddr>,
 }
 
 impl Block {
     /// Create a new `Block`.
     pub fn new(metadata: BlockMetadata, block_data: Bytes) -> Block {
-        Block { metadata, block_data }
+        Block {
+            metadata,
+            block_data,
+            contributor: None,
+        }
+    }
+
+    /// Set the address of the peer the block was received from.
+    ///
+    /// Recorded in the verification journal for the piece, see `DiskManagerBuilder::with_verification_journal_size`.
+    #[must_use]
+    pub fn with_contributor(mut self, addr: SocketAddr) -> Block {
+        self.contributor = Some(addr);
+        self
     }
 
     /// Access the metadata for the block.
@@ -78,6 +93,11 @@ impl Block {
         self.metadata
     }
 
+    /// Address of the peer the block was received from, if it was set.
+    pub fn contributor(&self) -> Option<SocketAddr> {
+        self.contributor
+    }
+
     pub fn into_parts(self) -> (BlockMetadata, Bytes) {
         (self.metadata, self.block_data)
     }
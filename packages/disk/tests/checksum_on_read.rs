@@ -0,0 +1,109 @@
+use bytes::BytesMut;
+use common::{random_buffer, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, INIT};
+use disk::error::BlockError;
+use disk::{BlockMetadata, BlockMut, DiskManagerBuilder, IDiskMessage, ODiskMessage};
+use futures::{SinkExt as _, StreamExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tokio::time::{timeout, Duration};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+#[tokio::test]
+async fn positive_checksum_on_read_detects_corruption() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Create some "files" as random bytes
+    let data_a = (random_buffer(1023), "/path/to/file/a".into());
+    let data_b = (random_buffer(2000), "/path/to/file/b".into());
+
+    // Create our accessor for our in-memory files and create a torrent file for them
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    // Spin up a disk manager with checksum on read and add our created torrent to it
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new()
+        .with_checksum_on_read(true)
+        .build(filesystem.clone());
+
+    let files_bytes = [data_a.0, data_b.0].concat();
+    let load_block = |piece_index| {
+        let mut bytes = BytesMut::new();
+        bytes.extend_from_slice(&[0u8; 50]);
+
+        BlockMut::new(BlockMetadata::new(info_hash, piece_index, 0, 50), bytes)
+    };
+    let corrupt_byte = |index: usize| {
+        filesystem.run_with_lock(|files| {
+            let file_b = files.iter_mut().find(|(path, _)| path.ends_with("b")).unwrap().1;
+            file_b[index] = !file_b[index];
+        });
+    };
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let result = timeout(timeout_duration, async {
+        let mut good_pieces = 0;
+        let mut corrupted = false;
+        let mut found_corrupt = false;
+
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    send_block(&mut send, &files_bytes[0..1024], info_hash, 0, 0, 1024, |_| ()).await;
+                    send_block(&mut send, &files_bytes[1024..2048], info_hash, 1, 0, 1024, |_| ()).await;
+                }
+                Some(Ok(ODiskMessage::FoundGoodPiece(_, _))) => {
+                    good_pieces += 1;
+
+                    if good_pieces == 2 {
+                        // Piece 0 is verified and cached, so corrupting it afterwards goes unnoticed
+                        send.send(IDiskMessage::LoadBlock(load_block(0))).await.unwrap();
+                    }
+                }
+                Some(Ok(ODiskMessage::BlockProcessed(_))) => (),
+                Some(Ok(ODiskMessage::BlockLoaded(block))) if block.metadata().piece_index() == 0 => {
+                    if corrupted {
+                        continue;
+                    }
+                    corrupted = true;
+
+                    // Last byte of piece 0 lives in file b, followed by piece 1
+                    corrupt_byte(0);
+                    corrupt_byte(1);
+
+                    send.send(IDiskMessage::LoadBlock(load_block(0))).await.unwrap();
+                    send.send(IDiskMessage::LoadBlock(load_block(1))).await.unwrap();
+                }
+                Some(Ok(ODiskMessage::FoundCorruptPiece(hash, index, file_path, file_offset))) => {
+                    assert_eq!(hash, info_hash);
+                    assert_eq!(index, 1);
+                    assert!(file_path.ends_with("b"));
+                    assert_eq!(file_offset, 1);
+
+                    found_corrupt = true;
+                }
+                Some(Ok(ODiskMessage::LoadBlockError(block, err))) => return (block, err, found_corrupt),
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await;
+
+    let (block, err, found_corrupt) = result.unwrap();
+
+    assert!(found_corrupt);
+    assert_eq!(block.metadata().piece_index(), 1);
+    assert!(matches!(err, BlockError::CorruptPiece { index: 1, .. }));
+}
@@ -221,6 +221,22 @@ impl FileSystem for InMemoryFileSystem {
         Ok(())
     }
 
+    fn move_file<P, Q>(&self, from: P, to: Q) -> std::io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        Q: AsRef<Path> + Send + 'static,
+    {
+        self.run_with_lock(|files| {
+            let buffer = files
+                .remove(from.as_ref())
+                .ok_or(std::io::Error::new(std::io::ErrorKind::NotFound, "File Not Found"))?;
+
+            files.insert(to.as_ref().to_path_buf(), buffer);
+
+            Ok(())
+        })
+    }
+
     fn file_size(&self, file: &Self::File) -> std::io::Result<u64> {
         self.run_with_lock(|files| {
             files
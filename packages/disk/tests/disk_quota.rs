@@ -0,0 +1,141 @@
+use common::{random_buffer, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, INIT};
+use disk::error::{BlockError, TorrentError};
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage, QuotaScope};
+use futures::{SinkExt as _, StreamExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tokio::time::{timeout, Duration};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+#[tokio::test]
+async fn positive_directory_quota_pauses_new_pieces() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let data = (random_buffer(3000), "/path/to/file/a".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    // Room for only two of the three pieces
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new().with_directory_quota(2048).build(filesystem);
+
+    let piece = |piece_index: usize| {
+        let start = piece_index * 1024;
+        &data.0[start..std::cmp::min(start + 1024, data.0.len())]
+    };
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let result = timeout(timeout_duration, async {
+        let mut good_pieces = Vec::new();
+        let mut exceeded = None;
+        let mut quota_error = None;
+
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    send_block(&mut send, piece(0), info_hash, 0, 0, 1024, |_| ()).await;
+                }
+                Some(Ok(ODiskMessage::FoundGoodPiece(_, index))) => good_pieces.push(index),
+                Some(Ok(ODiskMessage::BlockProcessed(block))) => match block.metadata().piece_index() {
+                    0 => send_block(&mut send, piece(1), info_hash, 1, 0, 1024, |_| ()).await,
+                    1 => send_block(&mut send, piece(2), info_hash, 2, 0, 952, |_| ()).await,
+                    _ => return (good_pieces, exceeded, quota_error),
+                },
+                Some(Ok(ODiskMessage::QuotaExceeded(hash, scope, limit))) => exceeded = Some((hash, scope, limit)),
+                Some(Ok(ODiskMessage::ProcessBlockError(_, err))) => {
+                    quota_error = Some(err);
+
+                    // Once the quota is lifted, the paused piece can be written
+                    send.send(IDiskMessage::SetQuota(QuotaScope::Directory, None)).await.unwrap();
+                }
+                Some(Ok(ODiskMessage::QuotaSet(QuotaScope::Directory, None))) => {
+                    send_block(&mut send, piece(2), info_hash, 2, 0, 952, |_| ()).await;
+                }
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await;
+
+    let (good_pieces, exceeded, quota_error) = result.unwrap();
+
+    assert_eq!(vec![0, 1, 2], good_pieces);
+    assert_eq!(Some((info_hash, QuotaScope::Directory, 2048)), exceeded);
+    assert!(matches!(quota_error, Some(BlockError::QuotaExceeded { index: 2, .. })));
+}
+
+#[tokio::test]
+async fn positive_torrent_quota_exceeded() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let data = (random_buffer(2048), "/path/to/file/a".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+    let unknown_hash = [55u8; bt::INFO_HASH_LEN].into();
+
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new().build(filesystem);
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let result = timeout(timeout_duration, async {
+        let mut exceeded = None;
+        let mut not_found = false;
+
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    send.send(IDiskMessage::SetQuota(QuotaScope::Torrent(unknown_hash), Some(1000)))
+                        .await
+                        .unwrap();
+                }
+                Some(Ok(ODiskMessage::TorrentError(hash, TorrentError::InfoHashNotFound { .. }))) => {
+                    assert_eq!(unknown_hash, hash);
+                    not_found = true;
+
+                    send.send(IDiskMessage::SetQuota(QuotaScope::Torrent(info_hash), Some(1000)))
+                        .await
+                        .unwrap();
+                }
+                Some(Ok(ODiskMessage::QuotaSet(QuotaScope::Torrent(hash), Some(1000)))) if hash == info_hash => {
+                    send_block(&mut send, &data.0[..1024], info_hash, 0, 0, 1024, |_| ()).await;
+                }
+                Some(Ok(ODiskMessage::QuotaExceeded(hash, scope, limit))) => exceeded = Some((hash, scope, limit)),
+                Some(Ok(ODiskMessage::ProcessBlockError(_, err))) => return (not_found, exceeded, err),
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await;
+
+    let (not_found, exceeded, err) = result.unwrap();
+
+    assert!(not_found);
+    assert_eq!(Some((info_hash, QuotaScope::Torrent(info_hash), 1000)), exceeded);
+    assert!(matches!(err, BlockError::QuotaExceeded { index: 0, .. }));
+}
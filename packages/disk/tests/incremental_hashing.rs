@@ -0,0 +1,85 @@
+use common::{random_buffer, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, INIT};
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage};
+use futures::{SinkExt as _, StreamExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tokio::time::{timeout, Duration};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+#[tokio::test]
+async fn positive_out_of_order_and_rewritten_blocks() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Create some "files" as random bytes
+    let data_a = (random_buffer(1023), "/path/to/file/a".into());
+    let data_b = (random_buffer(2000), "/path/to/file/b".into());
+
+    // Create our accessor for our in-memory files and create a torrent file for them
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    // Spin up a disk manager and add our created torrent to it
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new().build(filesystem.clone());
+
+    let files_bytes = [data_a.0, data_b.0].concat();
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let result = timeout(timeout_duration, async {
+        let mut good_pieces = Vec::new();
+        let mut bad_pieces = Vec::new();
+
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    // Blocks of piece 0 arrive out of order, and one of them twice
+                    send_block(&mut send, &files_bytes[512..768], info_hash, 0, 512, 256, |_| ()).await;
+                    send_block(&mut send, &files_bytes[768..1024], info_hash, 0, 768, 256, |_| ()).await;
+                    send_block(&mut send, &files_bytes[512..768], info_hash, 0, 512, 256, |_| ()).await;
+                    send_block(&mut send, &files_bytes[0..512], info_hash, 0, 0, 512, |_| ()).await;
+
+                    // Piece 1 is first written with a corrupt block, then written again
+                    send_block(&mut send, &files_bytes[1024..1536], info_hash, 1, 0, 512, |_| ()).await;
+                    send_block(&mut send, &files_bytes[1536..2048], info_hash, 1, 512, 512, |bytes| {
+                        bytes[0] = !bytes[0];
+                    })
+                    .await;
+                }
+                Some(Ok(ODiskMessage::FoundGoodPiece(_, index))) => {
+                    good_pieces.push(index);
+
+                    if good_pieces.len() == 2 {
+                        return (good_pieces, bad_pieces);
+                    }
+                }
+                Some(Ok(ODiskMessage::FoundBadPiece(_, index))) => {
+                    bad_pieces.push(index);
+
+                    send_block(&mut send, &files_bytes[1536..2048], info_hash, 1, 512, 512, |_| ()).await;
+                    send_block(&mut send, &files_bytes[1024..1536], info_hash, 1, 0, 512, |_| ()).await;
+                }
+                Some(Ok(ODiskMessage::BlockProcessed(_))) => (),
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await;
+
+    let (mut good_pieces, bad_pieces) = result.unwrap();
+    good_pieces.sort_unstable();
+
+    assert_eq!(vec![0, 1], good_pieces);
+    assert_eq!(vec![1], bad_pieces);
+}
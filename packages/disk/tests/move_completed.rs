@@ -0,0 +1,88 @@
+use bytes::BytesMut;
+use common::{
+    random_buffer, runtime_loop_with_timeout, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor,
+    DEFAULT_TIMEOUT, INIT,
+};
+use disk::{BlockMetadata, BlockMut, DiskManagerBuilder, IDiskMessage, ODiskMessage};
+use futures::future::{self, Either};
+use futures::{FutureExt, SinkExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+#[tokio::test]
+async fn positive_move_completed_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Create some "files" as random bytes, filling one piece each
+    let data_a = (random_buffer(1024), "path/to/file/a".into());
+    let data_b = (random_buffer(1000), "path/to/file/b".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new()
+        .with_incomplete_directory("incomplete")
+        .with_completed_directory("complete")
+        .build(filesystem.clone());
+
+    let (mut send, recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file.clone())).await.unwrap();
+
+    let ((), recv) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), recv, msg| match msg {
+        Ok(ODiskMessage::TorrentAdded(_)) => Either::Left(future::ready(((), recv)).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    send_block(&mut send, &data_a.0, info_hash, 0, 0, 1024, |_| ()).await;
+    send_block(&mut send, &data_b.0, info_hash, 1, 0, 1000, |_| ()).await;
+
+    // Two blocks processed, two good pieces, and the torrent finished
+    let (paths, recv) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((None, 0), recv), |(paths, messages_recvd), recv, msg| {
+        let paths = match msg {
+            Ok(ODiskMessage::TorrentFinished(hash, paths)) if hash == info_hash => Some(paths),
+            Ok(ODiskMessage::FoundGoodPiece(_, _) | ODiskMessage::BlockProcessed(_)) => paths,
+            unexpected => panic!("Unexpected Message: {unexpected:?}"),
+        };
+
+        if messages_recvd + 1 == 5 {
+            Either::Left(future::ready((paths.unwrap(), recv)).boxed())
+        } else {
+            Either::Right(future::ready(((paths, messages_recvd + 1), recv)).boxed())
+        }
+    })
+    .await;
+
+    assert_eq!(2, paths.len());
+    assert!(paths.iter().all(|path| path.starts_with("complete")));
+    filesystem.run_with_lock(|files| {
+        let mut stored: Vec<_> = files.keys().cloned().collect();
+        stored.sort();
+
+        assert_eq!(paths, stored);
+    });
+
+    // Torrent is still seeded from the completed directory
+    let load_block = BlockMut::new(BlockMetadata::new(info_hash, 1, 0, 1000), BytesMut::zeroed(1000));
+    send.send(IDiskMessage::LoadBlock(load_block)).await.unwrap();
+
+    runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), _, msg| match msg {
+        Ok(ODiskMessage::BlockLoaded(block)) => {
+            assert_eq!(&data_b.0[..], &block[..]);
+
+            Either::Left(future::ready(()).boxed())
+        }
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+}
@@ -0,0 +1,123 @@
+use bytes::BytesMut;
+use common::{random_buffer, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, INIT};
+use disk::{BlockMetadata, BlockMut, DiskManagerBuilder, IDiskMessage, ODiskMessage};
+use futures::{SinkExt as _, StreamExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tokio::time::{timeout, Duration};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+/// Load the first block of piece 1, after loading the first block of piece 0 and then
+/// changing the bytes of piece 1 behind the back of the `DiskManager`.
+async fn load_after_change(sequential: bool) -> (Vec<u8>, Vec<u8>) {
+    // Create some "files" as random bytes
+    let data_a = (random_buffer(1023), "/path/to/file/a".into());
+    let data_b = (random_buffer(3073), "/path/to/file/b".into());
+
+    // Create our accessor for our in-memory files and create a torrent file for them
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    // Spin up a disk manager with a window spanning the rest of piece 0 and all of piece 1
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new()
+        .with_read_ahead_window(2048)
+        .build(filesystem.clone());
+
+    let files_bytes = [data_a.0, data_b.0].concat();
+    let load_block = |piece_index| {
+        let mut bytes = BytesMut::new();
+        bytes.extend_from_slice(&[0u8; 512]);
+
+        BlockMut::new(BlockMetadata::new(info_hash, piece_index, 0, 512), bytes)
+    };
+    let change_piece_1 = || {
+        filesystem.run_with_lock(|files| {
+            // Piece 1 starts at the second byte of file b
+            let file_b = files.iter_mut().find(|(path, _)| path.ends_with("b")).unwrap().1;
+            for byte in &mut file_b[1..=1024] {
+                *byte = !*byte;
+            }
+        });
+    };
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let result = timeout(timeout_duration, async {
+        let mut good_pieces = 0;
+
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    for piece_index in 0..4 {
+                        let begin = piece_index * 1024;
+                        let data = &files_bytes[begin..begin + 1024];
+
+                        send_block(&mut send, data, info_hash, piece_index as u64, 0, 1024, |_| ()).await;
+                    }
+                }
+                Some(Ok(ODiskMessage::BlockProcessed(_))) => (),
+                Some(Ok(ODiskMessage::FoundGoodPiece(_, _))) => {
+                    good_pieces += 1;
+
+                    if good_pieces == 4 {
+                        send.send(IDiskMessage::SetSequentialAccess(info_hash, sequential))
+                            .await
+                            .unwrap();
+                    }
+                }
+                Some(Ok(ODiskMessage::SequentialAccessSet(hash, enabled))) => {
+                    assert_eq!(info_hash, hash);
+                    assert_eq!(sequential, enabled);
+
+                    send.send(IDiskMessage::LoadBlock(load_block(0))).await.unwrap();
+                }
+                Some(Ok(ODiskMessage::BlockLoaded(block))) if block.metadata().piece_index() == 0 => {
+                    change_piece_1();
+
+                    send.send(IDiskMessage::LoadBlock(load_block(1))).await.unwrap();
+                }
+                Some(Ok(ODiskMessage::BlockLoaded(block))) => return block.to_vec(),
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await;
+
+    (files_bytes[1024..1536].to_vec(), result.unwrap())
+}
+
+#[tokio::test]
+async fn positive_read_ahead_sequential_access() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Piece 1 was read ahead while loading piece 0, so the change is not seen
+    let (original, loaded) = load_after_change(true).await;
+
+    assert_eq!(original, loaded);
+}
+
+#[tokio::test]
+async fn negative_read_ahead_random_access() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (original, loaded) = load_after_change(false).await;
+
+    assert!(original
+        .iter()
+        .zip(loaded.iter())
+        .all(|(original, loaded)| *original == !*loaded));
+}
@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use common::{
+    random_buffer, runtime_loop_with_timeout, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, DEFAULT_TIMEOUT,
+    INIT,
+};
+use disk::error::TorrentError;
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage, ResumeData, ResumeVerification};
+use futures::future::{self, Either};
+use futures::{FutureExt, SinkExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+/// Torrent of two complete files, each filling one piece, stored in the file system.
+fn complete_torrent() -> (std::sync::Arc<InMemoryFileSystem>, Metainfo) {
+    let data_a: (Vec<u8>, PathBuf) = (random_buffer(1024), "/path/to/file/a".into());
+    let data_b: (Vec<u8>, PathBuf) = (random_buffer(1000), "/path/to/file/b".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+
+    let filesystem = InMemoryFileSystem::new();
+    filesystem.run_with_lock(|files| {
+        files.insert(data_a.1, data_a.0);
+        files.insert(data_b.1, data_b.0);
+    });
+
+    (filesystem, metainfo_file)
+}
+
+#[tokio::test]
+async fn positive_remap_file_kept_in_resume_data() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file) = complete_torrent();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let (mut send, recv) = DiskManagerBuilder::new().build(filesystem.me()).into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file.clone())).await.unwrap();
+
+    let ((), recv) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), recv, msg| match msg {
+        Ok(ODiskMessage::TorrentAdded(_)) => Either::Left(future::ready(((), recv)).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, _)) => Either::Right(future::ready(((), recv)).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    send.send(IDiskMessage::RemapFile(info_hash, 1, "flat/b.renamed".into()))
+        .await
+        .unwrap();
+    send.send(IDiskMessage::SaveResumeData(info_hash)).await.unwrap();
+
+    let (resume_data, _) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, (None, recv), |opt_path, recv, msg| match msg {
+        Ok(ODiskMessage::FileRemapped(hash, 1, path)) if hash == info_hash => {
+            Either::Right(future::ready((Some(path), recv)).boxed())
+        }
+        Ok(ODiskMessage::ResumeDataSaved(resume_data)) => {
+            assert_eq!(Some(PathBuf::from("flat/b.renamed")), opt_path);
+
+            Either::Left(future::ready((resume_data, recv)).boxed())
+        }
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    filesystem.run_with_lock(|files| {
+        assert!(files.contains_key(&PathBuf::from("flat/b.renamed")));
+        assert!(!files.contains_key(&PathBuf::from("/path/to/file/b")));
+    });
+    assert_eq!(&[(1, PathBuf::from("flat/b.renamed"))], resume_data.remapped_files());
+
+    // Resuming looks for the file where it was remapped to, so every piece is still trusted
+    let resume_data = ResumeData::from_bytes(&resume_data.to_bytes()).unwrap();
+    let (mut send, recv) = DiskManagerBuilder::new().build(filesystem.me()).into_parts();
+    send.send(IDiskMessage::ResumeTorrent(metainfo_file, resume_data))
+        .await
+        .unwrap();
+
+    let (good_pieces, verification) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, (0, recv), |good_pieces, recv, msg| match msg {
+        Ok(ODiskMessage::TorrentResumed(_, verification)) => Either::Left(future::ready((good_pieces, verification)).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, _)) => Either::Right(future::ready((good_pieces + 1, recv)).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    assert_eq!(ResumeVerification::Trusted, verification);
+    assert_eq!(2, good_pieces);
+}
+
+#[tokio::test]
+async fn negative_remap_file_invalid_paths() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file) = complete_torrent();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let (mut send, recv) = DiskManagerBuilder::new().build(filesystem.me()).into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let ((), recv) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), recv, msg| match msg {
+        Ok(ODiskMessage::TorrentAdded(_)) => Either::Left(future::ready(((), recv)).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, _)) => Either::Right(future::ready(((), recv)).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    send.send(IDiskMessage::RemapFile(info_hash, 0, "a".into())).await.unwrap();
+    // Paths escaping the directory, already used by another file, or for files out of range are rejected
+    send.send(IDiskMessage::RemapFile(info_hash, 1, "../b".into())).await.unwrap();
+    send.send(IDiskMessage::RemapFile(info_hash, 1, "a".into())).await.unwrap();
+    send.send(IDiskMessage::RemapFile(info_hash, 2, "c".into())).await.unwrap();
+
+    runtime_loop_with_timeout(DEFAULT_TIMEOUT, (0, recv), |messages_recvd, recv, msg| {
+        match msg {
+            Ok(
+                ODiskMessage::FileRemapped(_, 0, _)
+                | ODiskMessage::TorrentError(
+                    _,
+                    TorrentError::FileOutOfRange {
+                        index: 2, num_files: 2, ..
+                    },
+                ),
+            ) => (),
+            Ok(ODiskMessage::TorrentError(_, TorrentError::InvalidFilePath { .. })) if messages_recvd < 3 => (),
+            unexpected => panic!("Unexpected Message: {unexpected:?}"),
+        }
+
+        if messages_recvd + 1 == 4 {
+            Either::Left(future::ready(()).boxed())
+        } else {
+            Either::Right(future::ready((messages_recvd + 1, recv)).boxed())
+        }
+    })
+    .await;
+
+    filesystem.run_with_lock(|files| {
+        assert!(files.contains_key(&PathBuf::from("a")));
+        assert!(files.contains_key(&PathBuf::from("/path/to/file/b")));
+    });
+}
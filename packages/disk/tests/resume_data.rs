@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use common::{
+    random_buffer, runtime_loop_with_timeout, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, DEFAULT_TIMEOUT,
+    INIT,
+};
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage, ResumeData, ResumeVerification};
+use futures::future::{self, Either};
+use futures::{FutureExt, SinkExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+const PIECE_LENGTH: usize = 1024;
+const FILE_A_PIECES: usize = 40;
+// Sending waits for the message to be processed, so every found piece must fit in the stream buffer
+const STREAM_BUFFER_CAPACITY: usize = 64;
+
+/// Fill a file system with a complete torrent, and save its resume data.
+async fn complete_torrent_with_resume_data() -> (Arc<InMemoryFileSystem>, Metainfo, ResumeData) {
+    let data_a: (Vec<u8>, PathBuf) = (random_buffer(FILE_A_PIECES * PIECE_LENGTH), "/path/to/file/a".into());
+    let data_b: (Vec<u8>, PathBuf) = (random_buffer(1000), "/path/to/file/b".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(PIECE_LENGTH))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let filesystem = InMemoryFileSystem::new();
+    filesystem.run_with_lock(|files| {
+        files.insert(data_a.1, data_a.0);
+        files.insert(data_b.1, data_b.0);
+    });
+
+    let (mut send, recv) = DiskManagerBuilder::new()
+        .with_stream_buffer_capacity(STREAM_BUFFER_CAPACITY)
+        .build(filesystem.me())
+        .into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file.clone())).await.unwrap();
+
+    let (good_pieces, recv) = runtime_loop_with_timeout(DEFAULT_TIMEOUT, (0, recv), |good_pieces, recv, msg| match msg {
+        Ok(ODiskMessage::TorrentAdded(_)) => Either::Left(future::ready((good_pieces, recv)).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, _)) => Either::Right(future::ready((good_pieces + 1, recv)).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+    assert_eq!(FILE_A_PIECES + 1, good_pieces);
+
+    send.send(IDiskMessage::SaveResumeData(info_hash)).await.unwrap();
+
+    let resume_data = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), _, msg| match msg {
+        Ok(ODiskMessage::ResumeDataSaved(resume_data)) => Either::Left(future::ready(resume_data).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    assert_eq!(info_hash, resume_data.info_hash());
+    assert_eq!((0..=FILE_A_PIECES as u64).collect::<Vec<_>>(), resume_data.good_pieces());
+    assert!(resume_data.files().iter().all(|file| file.edge_hash().is_some()));
+
+    // Resume data is persisted across restarts
+    let resume_data = ResumeData::from_bytes(&resume_data.to_bytes()).unwrap();
+
+    (filesystem, metainfo_file, resume_data)
+}
+
+/// Resume the torrent in a new disk manager, returning the good pieces along with how they were verified.
+async fn resume_torrent(
+    filesystem: &Arc<InMemoryFileSystem>,
+    metainfo_file: Metainfo,
+    resume_data: ResumeData,
+) -> (Vec<u64>, ResumeVerification) {
+    let (mut send, recv) = DiskManagerBuilder::new()
+        .with_stream_buffer_capacity(STREAM_BUFFER_CAPACITY)
+        .build(filesystem.me())
+        .into_parts();
+    send.send(IDiskMessage::ResumeTorrent(metainfo_file, resume_data))
+        .await
+        .unwrap();
+
+    runtime_loop_with_timeout(DEFAULT_TIMEOUT, (Vec::new(), recv), |mut good_pieces, recv, msg| match msg {
+        Ok(ODiskMessage::TorrentResumed(_, verification)) => Either::Left(future::ready((good_pieces, verification)).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, index)) => {
+            good_pieces.push(index);
+            Either::Right(future::ready((good_pieces, recv)).boxed())
+        }
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await
+}
+
+#[tokio::test]
+async fn positive_resume_trusts_unchanged_files() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file, resume_data) = complete_torrent_with_resume_data().await;
+
+    // Corrupt a byte that is not covered by the fingerprint, so only hashing the piece would catch it
+    filesystem.run_with_lock(|files| {
+        files.get_mut(&PathBuf::from("/path/to/file/a")).unwrap()[(FILE_A_PIECES / 2) * PIECE_LENGTH] ^= 0xFF;
+    });
+
+    let (good_pieces, verification) = resume_torrent(&filesystem, metainfo_file, resume_data).await;
+
+    assert_eq!(ResumeVerification::Trusted, verification);
+    assert_eq!(FILE_A_PIECES + 1, good_pieces.len());
+}
+
+#[tokio::test]
+async fn positive_resume_verifies_changed_files() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file, resume_data) = complete_torrent_with_resume_data().await;
+
+    filesystem.run_with_lock(|files| {
+        files.get_mut(&PathBuf::from("/path/to/file/b")).unwrap()[0] ^= 0xFF;
+    });
+
+    let (good_pieces, verification) = resume_torrent(&filesystem, metainfo_file, resume_data).await;
+
+    // Only the piece holding file b was checked, and found bad
+    assert_eq!(ResumeVerification::Partial, verification);
+    assert_eq!(FILE_A_PIECES, good_pieces.len());
+    assert!(!good_pieces.contains(&(FILE_A_PIECES as u64)));
+}
+
+#[tokio::test]
+async fn negative_resume_mismatched_data_checks_everything() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file, resume_data) = complete_torrent_with_resume_data().await;
+    let resume_data = ResumeData::new(resume_data.info_hash(), resume_data.good_pieces().to_vec(), Vec::new());
+
+    filesystem.run_with_lock(|files| {
+        files.get_mut(&PathBuf::from("/path/to/file/a")).unwrap()[0] ^= 0xFF;
+    });
+
+    let (good_pieces, verification) = resume_torrent(&filesystem, metainfo_file, resume_data).await;
+
+    assert_eq!(ResumeVerification::Full, verification);
+    assert_eq!(FILE_A_PIECES, good_pieces.len());
+    assert!(!good_pieces.contains(&0));
+}
+
+#[tokio::test]
+async fn positive_resume_partially_allocated_file() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file, resume_data) = complete_torrent_with_resume_data().await;
+
+    // Crash while file a was being allocated, leaving only its first half
+    filesystem.run_with_lock(|files| {
+        files
+            .get_mut(&PathBuf::from("/path/to/file/a"))
+            .unwrap()
+            .truncate((FILE_A_PIECES / 2) * PIECE_LENGTH);
+    });
+
+    let (good_pieces, verification) = resume_torrent(&filesystem, metainfo_file, resume_data).await;
+
+    // Pieces in the missing half are absent, even though the resume data lists them as good
+    assert_eq!(ResumeVerification::Partial, verification);
+    assert_eq!(FILE_A_PIECES / 2 + 1, good_pieces.len());
+    assert!(good_pieces
+        .iter()
+        .all(|&index| index < (FILE_A_PIECES / 2) as u64 || index == FILE_A_PIECES as u64));
+
+    let file_a_len = filesystem.run_with_lock(|files| files[&PathBuf::from("/path/to/file/a")].len());
+    assert_eq!(FILE_A_PIECES * PIECE_LENGTH, file_a_len);
+}
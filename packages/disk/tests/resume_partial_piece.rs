@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use common::{
+    random_buffer, runtime_loop_with_timeout, send_block, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor,
+    DEFAULT_TIMEOUT, INIT,
+};
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage, PartialPiece, ResumeData, ResumeVerification};
+use futures::future::{self, Either};
+use futures::{FutureExt, SinkExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+const PIECE_LENGTH: usize = 1024;
+const NUM_PIECES: usize = 4;
+const STREAM_BUFFER_CAPACITY: usize = 64;
+
+/// Start downloading a torrent, writing only the first half of piece 1, and save its resume data.
+async fn partial_torrent_with_resume_data(
+    resume_partial_pieces: bool,
+) -> (Arc<InMemoryFileSystem>, Metainfo, Vec<u8>, ResumeData) {
+    let data: (Vec<u8>, PathBuf) = (random_buffer(NUM_PIECES * PIECE_LENGTH), "/path/to/file/a".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(PIECE_LENGTH))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let filesystem = InMemoryFileSystem::new();
+    let (mut send, recv) = DiskManagerBuilder::new()
+        .with_stream_buffer_capacity(STREAM_BUFFER_CAPACITY)
+        .with_resume_partial_pieces(resume_partial_pieces)
+        .build(filesystem.me())
+        .into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file.clone())).await.unwrap();
+
+    let half_piece = PIECE_LENGTH / 2;
+    let first_half = &data.0[PIECE_LENGTH..PIECE_LENGTH + half_piece];
+
+    let recv = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), recv, msg| match msg {
+        Ok(ODiskMessage::TorrentAdded(_)) => Either::Left(future::ready(recv).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    send_block(&mut send, first_half, info_hash, 1, 0, half_piece, |_| ()).await;
+
+    let recv = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), recv, msg| match msg {
+        Ok(ODiskMessage::BlockProcessed(_)) => Either::Left(future::ready(recv).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    send.send(IDiskMessage::SaveResumeData(info_hash)).await.unwrap();
+
+    let resume_data = runtime_loop_with_timeout(DEFAULT_TIMEOUT, ((), recv), |(), _, msg| match msg {
+        Ok(ODiskMessage::ResumeDataSaved(resume_data)) => Either::Left(future::ready(resume_data).boxed()),
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+
+    // Resume data is persisted across restarts
+    let resume_data = ResumeData::from_bytes(&resume_data.to_bytes()).unwrap();
+
+    (filesystem, metainfo_file, data.0, resume_data)
+}
+
+#[tokio::test]
+async fn positive_resume_restores_partial_piece() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (filesystem, metainfo_file, data, resume_data) = partial_torrent_with_resume_data(true).await;
+    let info_hash = metainfo_file.info().info_hash();
+    let half_piece = PIECE_LENGTH / 2;
+
+    assert_eq!(&[PartialPiece::new(1, vec![(0, half_piece)])], resume_data.partial_pieces());
+
+    let (mut send, recv) = DiskManagerBuilder::new()
+        .with_stream_buffer_capacity(STREAM_BUFFER_CAPACITY)
+        .build(filesystem.me())
+        .into_parts();
+    send.send(IDiskMessage::ResumeTorrent(metainfo_file, resume_data))
+        .await
+        .unwrap();
+
+    let (partial_pieces, recv) = runtime_loop_with_timeout(
+        DEFAULT_TIMEOUT,
+        (Vec::new(), recv),
+        |mut partial_pieces, recv, msg| match msg {
+            Ok(ODiskMessage::TorrentResumed(_, ResumeVerification::Trusted)) => {
+                Either::Left(future::ready((partial_pieces, recv)).boxed())
+            }
+            Ok(ODiskMessage::FoundPartialPiece(_, partial)) => {
+                partial_pieces.push(partial);
+                Either::Right(future::ready((partial_pieces, recv)).boxed())
+            }
+            unexpected => panic!("Unexpected Message: {unexpected:?}"),
+        },
+    )
+    .await;
+    assert_eq!(vec![PartialPiece::new(1, vec![(0, half_piece)])], partial_pieces);
+
+    // Only the missing half of the piece is needed to complete it
+    let second_half = &data[PIECE_LENGTH + half_piece..2 * PIECE_LENGTH];
+    send_block(&mut send, second_half, info_hash, 1, half_piece as u64, half_piece, |_| ()).await;
+
+    let good_pieces = runtime_loop_with_timeout(DEFAULT_TIMEOUT, (Vec::new(), recv), |mut good_pieces, recv, msg| match msg {
+        Ok(ODiskMessage::BlockProcessed(_)) => Either::Left(future::ready(good_pieces).boxed()),
+        Ok(ODiskMessage::FoundGoodPiece(_, index)) => {
+            good_pieces.push(index);
+            Either::Right(future::ready((good_pieces, recv)).boxed())
+        }
+        unexpected => panic!("Unexpected Message: {unexpected:?}"),
+    })
+    .await;
+    assert_eq!(vec![1], good_pieces);
+}
+
+#[tokio::test]
+async fn negative_partial_pieces_not_saved_by_default() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (_, _, _, resume_data) = partial_torrent_with_resume_data(false).await;
+
+    assert!(resume_data.partial_pieces().is_empty());
+}
@@ -0,0 +1,90 @@
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use common::{random_buffer, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, INIT};
+use disk::{Block, BlockMetadata, DiskManagerBuilder, IDiskMessage, ODiskMessage, VerificationTrigger};
+use futures::{SinkExt as _, StreamExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tokio::time::{timeout, Duration};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+#[tokio::test]
+async fn positive_verification_journal() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Create some "files" as random bytes
+    let data_a = (random_buffer(1024), "/path/to/file/a".into());
+    let data_b = (random_buffer(1024), "/path/to/file/b".into());
+
+    // Create our accessor for our in-memory files and create a torrent file for them
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data_a.clone(), data_b.clone()]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    // Spin up a disk manager that keeps the outcome of every verification
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new().with_verification_journal_size(16).build(filesystem);
+
+    let bad_peer: SocketAddr = "127.0.0.1:6881".parse().unwrap();
+    let good_peer: SocketAddr = "127.0.0.1:6882".parse().unwrap();
+    let block = |piece_index: u64, data: &[u8], addr| {
+        Block::new(
+            BlockMetadata::new(info_hash, piece_index, 0, 1024),
+            Bytes::copy_from_slice(data),
+        )
+        .with_contributor(addr)
+    };
+
+    let (mut send, mut recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let timeout_duration = Duration::from_millis(500);
+    let records = timeout(timeout_duration, async {
+        loop {
+            match recv.next().await {
+                Some(Ok(ODiskMessage::TorrentAdded(_))) => {
+                    let corrupted: Vec<u8> = data_a.0.iter().map(|byte| !*byte).collect();
+
+                    send.send(IDiskMessage::ProcessBlock(block(0, &corrupted, bad_peer)))
+                        .await
+                        .unwrap();
+                }
+                Some(Ok(ODiskMessage::FoundBadPiece(_, 0))) => {
+                    send.send(IDiskMessage::ProcessBlock(block(1, &data_b.0, good_peer)))
+                        .await
+                        .unwrap();
+                }
+                Some(Ok(ODiskMessage::FoundGoodPiece(_, 1))) => {
+                    send.send(IDiskMessage::QueryVerificationJournal).await.unwrap();
+                }
+                Some(Ok(ODiskMessage::VerificationJournal(records))) => return records,
+                Some(Ok(ODiskMessage::BlockProcessed(_))) => (),
+                Some(unexpected) => panic!("Unexpected Message: {unexpected:?}"),
+                None => panic!("End Of Stream Reached"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(2, records.len());
+
+    assert_eq!(info_hash, records[0].hash());
+    assert_eq!(0, records[0].piece_index());
+    assert_eq!(VerificationTrigger::Written, records[0].trigger());
+    assert!(!records[0].is_good());
+    assert_ne!(records[0].expected_hash(), records[0].actual_hash());
+    assert_eq!(&[bad_peer], records[0].contributors());
+
+    assert_eq!(1, records[1].piece_index());
+    assert!(records[1].is_good());
+    assert_eq!(&[good_peer], records[1].contributors());
+}
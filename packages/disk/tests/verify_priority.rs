@@ -0,0 +1,83 @@
+use common::{
+    random_buffer, runtime_loop_with_timeout, tracing_stderr_init, InMemoryFileSystem, MultiFileDirectAccessor, DEFAULT_TIMEOUT,
+    INIT,
+};
+use disk::error::TorrentError;
+use disk::{DiskManagerBuilder, IDiskMessage, ODiskMessage, VerifyPriority};
+use futures::future::{self, Either};
+use futures::{FutureExt as _, SinkExt as _};
+use metainfo::{Metainfo, MetainfoBuilder, PieceLength};
+use tracing::level_filters::LevelFilter;
+
+mod common;
+
+#[tokio::test]
+async fn positive_set_piece_priority() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let data = (random_buffer(3000), "/path/to/file/a".into());
+
+    let files_accessor = MultiFileDirectAccessor::new("/my/downloads/".into(), vec![data]);
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, files_accessor, |_| ())
+        .unwrap();
+    let metainfo_file = Metainfo::from_bytes(metainfo_bytes).unwrap();
+    let info_hash = metainfo_file.info().info_hash();
+
+    let filesystem = InMemoryFileSystem::new();
+    let disk_manager = DiskManagerBuilder::new().build(filesystem);
+
+    let (mut send, recv) = disk_manager.into_parts();
+    send.send(IDiskMessage::AddTorrent(metainfo_file)).await.unwrap();
+
+    let (priority_set, out_of_range) = runtime_loop_with_timeout(
+        DEFAULT_TIMEOUT,
+        ((send, None, false), recv),
+        |(mut send, priority_set, out_of_range), recv, msg| {
+            let state = match msg {
+                Ok(ODiskMessage::TorrentAdded(_)) => {
+                    let fut = async move {
+                        send.send(IDiskMessage::SetPiecePriority(info_hash, 2, VerifyPriority::Urgent))
+                            .await
+                            .unwrap();
+                        send.send(IDiskMessage::SetPiecePriority(info_hash, 3, VerifyPriority::Urgent))
+                            .await
+                            .unwrap();
+
+                        ((send, priority_set, out_of_range), recv)
+                    }
+                    .boxed();
+
+                    return Either::Right(fut);
+                }
+                Ok(ODiskMessage::PiecePrioritySet(hash, index, priority)) => {
+                    assert_eq!(info_hash, hash);
+                    (send, Some((index, priority)), out_of_range)
+                }
+                Ok(ODiskMessage::TorrentError(
+                    hash,
+                    TorrentError::PieceOutOfRange {
+                        index: 3, num_pieces: 3, ..
+                    },
+                )) => {
+                    assert_eq!(info_hash, hash);
+                    (send, priority_set, true)
+                }
+                unexpected => panic!("Unexpected Message: {unexpected:?}"),
+            };
+
+            if state.1.is_some() && state.2 {
+                Either::Left(future::ready((state.1, state.2)).boxed())
+            } else {
+                Either::Right(future::ready((state, recv)).boxed())
+            }
+        },
+    )
+    .await;
+
+    assert_eq!(Some((2, VerifyPriority::Urgent)), priority_set);
+    assert!(out_of_range);
+}
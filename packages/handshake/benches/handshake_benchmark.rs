@@ -0,0 +1,51 @@
+use std::hint::black_box;
+
+use bytes::{BufMut as _, Bytes, BytesMut};
+use criterion::{criterion_group, criterion_main, Criterion};
+use handshake::{Extensions, HandshakeMessage, InfoHash, PeerId, Protocol};
+use util::test::{allocations_during, CountingAllocator};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn any_message() -> HandshakeMessage {
+    let hash: InfoHash = [55u8; 20].into();
+    let pid: PeerId = [22u8; 20].into();
+
+    HandshakeMessage::from_parts(Protocol::BitTorrent, Extensions::new(), hash, pid)
+}
+
+fn bench_parse(bytes: &Bytes) -> HandshakeMessage {
+    HandshakeMessage::parse_bytes(bytes).unwrap()
+}
+
+fn bench_write(message: &HandshakeMessage, buffer: &mut BytesMut) {
+    buffer.clear();
+    message.write_bytes_sync(&mut buffer.writer()).unwrap();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let message = any_message();
+
+    let mut buffer = BytesMut::with_capacity(message.write_len());
+    message.write_bytes_sync(&mut (&mut buffer).writer()).unwrap();
+    let bytes = buffer.split().freeze();
+
+    // Splitting the bytes out above took the capacity with it
+    let mut buffer = BytesMut::with_capacity(message.write_len());
+
+    // Guard the hot path, a regression here should fail loudly rather than show up as noise
+    assert_eq!(0, allocations_during(|| drop(black_box(bench_parse(black_box(&bytes))))));
+    assert_eq!(0, allocations_during(|| bench_write(black_box(&message), &mut buffer)));
+
+    c.bench_function("handshake parse", |b| {
+        b.iter(|| bench_parse(black_box(&bytes)));
+    });
+
+    c.bench_function("handshake write", |b| {
+        b.iter(|| bench_write(black_box(&message), &mut buffer));
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,203 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc;
+use util::bt::{InfoHash, PeerId};
+
+use crate::transport::BindError;
+
+/// Number of events buffered for a receiver before further events are dropped.
+const ATTEMPT_EVENT_CAPACITY: usize = 256;
+
+/// Stage reached by an outgoing connection attempt.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AttemptStage {
+    /// Started connecting to the peer.
+    Dialing,
+    /// Connection to the peer was established.
+    Connected,
+    /// Our handshake was sent to the peer.
+    HandshakeSent,
+    /// Handshake with the peer completed successfully.
+    Completed,
+    /// Attempt failed for the given reason.
+    Failed(AttemptFailure),
+}
+
+/// Reason that an outgoing connection attempt failed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AttemptFailure {
+    /// Attempt was blocked by a `HandshakeFilter`.
+    Filtered,
+    /// Peer took too long to connect or respond.
+    TimedOut,
+    /// Peer refused the connection.
+    Refused,
+    /// Connecting to the peer failed with some other error.
+    Connect(std::io::ErrorKind),
+    /// Binding to the local address configured for outgoing connections failed with the given error,
+    /// or the address is of a different family than that of the peer.
+    Bind(std::io::ErrorKind),
+    /// Attempt was abandoned because a connection to another address of the peer was established first.
+    Cancelled,
+    /// Connection was closed before the handshakes were exchanged.
+    Disconnected,
+    /// Peer sent a handshake that could not be parsed.
+    InvalidHandshake,
+    /// Peer responded with a different `InfoHash` than the one we asked for.
+    WrongInfoHash,
+    /// Peer responded with a different `Protocol` than the one we asked for.
+    WrongProtocol,
+    /// Peer could not prove that it knows our `PreSharedKey`.
+    Unauthenticated,
+}
+
+impl AttemptFailure {
+    pub(crate) fn from_connect_error(error: &std::io::Error) -> AttemptFailure {
+        if let Some(bind_error) = BindError::find(error) {
+            return AttemptFailure::Bind(bind_error.kind());
+        }
+
+        match error.kind() {
+            std::io::ErrorKind::TimedOut => AttemptFailure::TimedOut,
+            std::io::ErrorKind::ConnectionRefused => AttemptFailure::Refused,
+            kind => AttemptFailure::Connect(kind),
+        }
+    }
+}
+
+/// Event reported for an outgoing connection attempt.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AttemptEvent {
+    addr: SocketAddr,
+    hash: InfoHash,
+    pid: Option<PeerId>,
+    stage: AttemptStage,
+}
+
+impl AttemptEvent {
+    pub(crate) fn new(addr: SocketAddr, hash: InfoHash, stage: AttemptStage) -> AttemptEvent {
+        AttemptEvent {
+            addr,
+            hash,
+            pid: None,
+            stage,
+        }
+    }
+
+    pub(crate) fn with_peer_id(mut self, pid: PeerId) -> AttemptEvent {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Address of the peer being connected to.
+    #[must_use]
+    pub fn address(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    /// `InfoHash` that the attempt was made for.
+    #[must_use]
+    pub fn hash(&self) -> &InfoHash {
+        &self.hash
+    }
+
+    /// `PeerId` of the peer, once its handshake has been received.
+    #[must_use]
+    pub fn peer_id(&self) -> Option<&PeerId> {
+        self.pid.as_ref()
+    }
+
+    /// Stage reached by the attempt.
+    #[must_use]
+    pub fn stage(&self) -> AttemptStage {
+        self.stage
+    }
+}
+
+/// Receivers of `AttemptEvent`s, shared between the handlers of a `Handshaker`.
+#[derive(Clone, Default)]
+pub struct AttemptEvents {
+    senders: Arc<Mutex<Vec<mpsc::Sender<AttemptEvent>>>>,
+}
+
+impl AttemptEvents {
+    pub fn new() -> AttemptEvents {
+        AttemptEvents::default()
+    }
+
+    /// Register a new receiver for all future events.
+    pub fn subscribe(&self) -> mpsc::Receiver<AttemptEvent> {
+        let (send, recv) = mpsc::channel(ATTEMPT_EVENT_CAPACITY);
+
+        self.senders.lock().unwrap().push(send);
+
+        recv
+    }
+
+    /// Send the event to every receiver, dropping it for receivers that have fallen behind.
+    pub fn report(&self, event: AttemptEvent) {
+        tracing::trace!("connection attempt {event:?}");
+
+        self.senders.lock().unwrap().retain_mut(|send| match send.try_send(event) {
+            Ok(()) => true,
+            Err(err) => !err.is_disconnected(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use util::bt::{self, InfoHash};
+
+    use super::{AttemptEvent, AttemptEvents, AttemptFailure, AttemptStage};
+
+    fn any_info_hash() -> InfoHash {
+        [55u8; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_report_to_all_receivers() {
+        let events = AttemptEvents::new();
+        let mut recv_one = events.subscribe();
+        let mut recv_two = events.subscribe();
+
+        let event = AttemptEvent::new("1.2.3.4:5".parse().unwrap(), any_info_hash(), AttemptStage::Dialing);
+        events.report(event);
+
+        assert_eq!(event, recv_one.try_recv().unwrap());
+        assert_eq!(event, recv_two.try_recv().unwrap());
+    }
+
+    #[test]
+    fn positive_dropped_receiver_removed() {
+        let events = AttemptEvents::new();
+        drop(events.subscribe());
+
+        events.report(AttemptEvent::new(
+            "1.2.3.4:5".parse().unwrap(),
+            any_info_hash(),
+            AttemptStage::Dialing,
+        ));
+
+        assert!(events.senders.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn positive_connect_error_failures() {
+        assert_eq!(
+            AttemptFailure::TimedOut,
+            AttemptFailure::from_connect_error(&Error::from(ErrorKind::TimedOut))
+        );
+        assert_eq!(
+            AttemptFailure::Refused,
+            AttemptFailure::from_connect_error(&Error::from(ErrorKind::ConnectionRefused))
+        );
+        assert_eq!(
+            AttemptFailure::Connect(ErrorKind::AddrNotAvailable),
+            AttemptFailure::from_connect_error(&Error::from(ErrorKind::AddrNotAvailable))
+        );
+    }
+}
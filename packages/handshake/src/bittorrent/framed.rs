@@ -37,7 +37,7 @@ where
     sock: S,
 
     write_buffer: BytesMut,
-    read_buffer: Vec<u8>,
+    read_buffer: BytesMut,
     read_pos: usize,
     state: HandshakeState,
 }
@@ -55,7 +55,7 @@ where
         FramedHandshake {
             sock,
             write_buffer: BytesMut::with_capacity(1),
-            read_buffer: Vec::default(),
+            read_buffer: BytesMut::new(),
             read_pos: 0,
             state: HandshakeState::Waiting,
         }
@@ -83,13 +83,9 @@ where
     #[instrument(skip(self))]
     fn start_send(mut self: Pin<&mut Self>, item: HandshakeMessage) -> Result<(), Self::Error> {
         tracing::trace!("start_send called with item: {item:?}");
-        let mut cursor = std::io::Cursor::new(Vec::with_capacity(item.write_len()));
-        item.write_bytes_sync(&mut cursor)?;
-
+        // Encode straight into the write buffer, without an intermediate buffer
         self.write_buffer.reserve(item.write_len());
-        self.write_buffer.put_slice(cursor.get_ref());
-
-        Ok(())
+        item.write_bytes_sync(&mut (&mut self.write_buffer).writer())
     }
 
     #[instrument(skip(self, cx))]
@@ -139,153 +135,134 @@ where
 
     #[instrument(skip(self, cx))]
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.state {
-            HandshakeState::Waiting => {
-                tracing::trace!("handshake waiting...");
-                let mut this = self.project();
-
-                assert!(this.read_buffer.is_empty());
-                assert_eq!(0, *this.read_pos);
-
-                this.read_buffer.push(0);
-                assert_eq!(1, this.read_buffer.len());
-
-                let mut buf = ReadBuf::new(this.read_buffer);
-                buf.set_filled(0);
-
-                tracing::trace!("Read Buffer: {buf:?}");
-                tracing::trace!("Sock Buffer: {:?}", this.sock);
-
-                {
-                    let ready = match this.sock.as_mut().poll_read(cx, &mut buf) {
-                        Poll::Ready(ready) => ready,
+        // Loop through the states, only returning once we are done or the socket is pending
+        loop {
+            match self.state {
+                HandshakeState::Waiting => {
+                    tracing::trace!("handshake waiting...");
+                    let mut this = self.as_mut().project();
+
+                    assert!(this.read_buffer.is_empty());
+                    assert_eq!(0, *this.read_pos);
+
+                    let mut byte = [0u8; 1];
+                    let mut buf = ReadBuf::new(&mut byte);
+
+                    tracing::trace!("Sock Buffer: {:?}", this.sock);
+
+                    match this.sock.as_mut().poll_read(cx, &mut buf) {
+                        Poll::Ready(Ok(())) => (),
+                        Poll::Ready(Err(e)) => {
+                            tracing::error!("Error reading bytes: {:?}", e);
+                            *this.state = HandshakeState::Errored;
+                            return Poll::Ready(Some(Err(e)));
+                        }
                         Poll::Pending => {
-                            this.read_buffer.clear();
                             tracing::trace!("socket pending...");
                             return Poll::Pending;
                         }
-                    };
-
-                    if let Err(e) = ready {
-                        tracing::error!("Error reading bytes: {:?}", e);
-                        *this.state = HandshakeState::Errored;
-                        return Poll::Ready(Some(Err(e)));
-                    };
-                }
-
-                let filled = buf.filled();
-
-                let byte = match filled.len() {
-                    0 => {
-                        tracing::trace!("zero bytes read... pending");
-                        this.read_buffer.clear();
-                        cx.waker().wake_by_ref();
-                        return Poll::Pending;
                     }
-                    1 => filled[0],
-                    2.. => unreachable!("bip_handshake: limited by buffer size {filled:?}"),
-                };
 
-                let length = message::write_len_with_protocol_len(byte);
+                    let byte = match buf.filled() {
+                        [] => {
+                            tracing::trace!("zero bytes read... pending");
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        [byte] => *byte,
+                        filled => unreachable!("bip_handshake: limited by buffer size {filled:?}"),
+                    };
 
-                tracing::debug!("length byte: {byte}, expands to: {length} bytes");
+                    let length = message::write_len_with_protocol_len(byte);
 
-                this.read_buffer.resize(length, 0);
-                *this.read_pos = 1;
-                *this.state = HandshakeState::Reading;
+                    tracing::debug!("length byte: {byte}, expands to: {length} bytes");
 
-                cx.waker().wake_by_ref();
-                return Poll::Pending;
-            }
-            HandshakeState::Reading => {
-                tracing::trace!("handshake reading...");
-                let mut this = self.project();
+                    this.read_buffer.resize(length, 0);
+                    this.read_buffer[0] = byte;
+                    *this.read_pos = 1;
+                    *this.state = HandshakeState::Reading;
+                }
+                HandshakeState::Reading => {
+                    tracing::trace!("handshake reading...");
+                    let mut this = self.as_mut().project();
 
-                assert!(!this.read_buffer.is_empty());
+                    assert!(!this.read_buffer.is_empty());
 
-                let length = this.read_buffer.len();
-                let pos = this.read_pos;
+                    let length = this.read_buffer.len();
+                    let pos = this.read_pos;
 
-                assert_ne!(0, *pos);
-                assert!(*pos < length);
+                    assert_ne!(0, *pos);
+                    assert!(*pos < length);
 
-                let mut buf = ReadBuf::new(this.read_buffer);
-                buf.set_filled(*pos);
-                tracing::trace!("have {pos} bytes out of {length}...");
+                    let mut buf = ReadBuf::new(&mut this.read_buffer[..]);
+                    buf.set_filled(*pos);
+                    tracing::trace!("have {pos} bytes out of {length}...");
 
-                {
-                    let ready = match this.sock.as_mut().poll_read(cx, &mut buf) {
-                        Poll::Ready(ready) => ready,
+                    match this.sock.as_mut().poll_read(cx, &mut buf) {
+                        Poll::Ready(Ok(())) => (),
+                        Poll::Ready(Err(e)) => {
+                            tracing::error!("Error reading bytes: {:?}", e);
+                            *this.state = HandshakeState::Errored;
+                            return Poll::Ready(Some(Err(e)));
+                        }
                         Poll::Pending => {
                             tracing::trace!("socket pending...");
                             return Poll::Pending;
                         }
-                    };
-
-                    if let Err(e) = ready {
-                        tracing::error!("Error reading bytes: {:?}", e);
-                        *this.state = HandshakeState::Errored;
-                        return Poll::Ready(Some(Err(e)));
-                    };
-                }
+                    }
 
-                let filled = buf.filled().len();
-                assert!(filled <= length);
-                assert!(*pos <= filled);
-                let added = filled - *pos;
-                *pos = filled;
+                    let filled = buf.filled().len();
+                    assert!(filled <= length);
+                    assert!(*pos <= filled);
 
-                tracing::trace!("read {added} bytes, for a total of: {pos} / {length}...");
+                    if filled == *pos {
+                        tracing::trace!("zero bytes read... pending");
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
 
-                if filled == length {
-                    tracing::trace!("have full amount");
-                    *this.state = HandshakeState::Ready;
-                };
+                    let added = filled - *pos;
+                    *pos = filled;
 
-                cx.waker().wake_by_ref();
-                return Poll::Pending;
-            }
+                    tracing::trace!("read {added} bytes, for a total of: {pos} / {length}...");
 
-            HandshakeState::Ready => {
-                tracing::trace!("handshake ready...");
+                    if filled == length {
+                        tracing::trace!("have full amount");
+                        *this.state = HandshakeState::Ready;
+                    }
+                }
+                HandshakeState::Ready => {
+                    tracing::trace!("handshake ready...");
 
-                assert!(!self.read_buffer.is_empty());
-                assert_eq!(self.read_pos, self.read_buffer.len());
+                    assert!(!self.read_buffer.is_empty());
+                    assert_eq!(self.read_pos, self.read_buffer.len());
 
-                let buf = std::mem::take(&mut self.read_buffer);
+                    let buf = self.read_buffer.split().freeze();
 
-                match HandshakeMessage::from_bytes(&buf) {
-                    Ok(((), message)) => {
-                        tracing::trace!("Parsed HandshakeMessage: {:?}", message);
-                        self.state = HandshakeState::Finished;
+                    return match HandshakeMessage::parse_bytes(&buf) {
+                        Ok(message) => {
+                            tracing::trace!("Parsed HandshakeMessage: {:?}", message);
+                            self.state = HandshakeState::Finished;
 
-                        return Poll::Ready(Some(Ok(message)));
-                    }
-                    Err(nom::Err::Incomplete(needed)) => {
-                        tracing::error!("Failed to parse incomplete HandshakeMessage: {needed:?}");
-                        self.state = HandshakeState::Errored;
-
-                        return Poll::Ready(Some(Err(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!("Failed to parse incomplete HandshakeMessage: {needed:?}"),
-                        ))));
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to parse HandshakeMessage");
-                        self.state = HandshakeState::Errored;
+                            Poll::Ready(Some(Ok(message)))
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to parse HandshakeMessage: {e}");
+                            self.state = HandshakeState::Errored;
 
-                        return Poll::Ready(Some(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))));
-                    }
+                            Poll::Ready(Some(Err(e)))
+                        }
+                    };
+                }
+                HandshakeState::Finished => {
+                    tracing::trace!("handshake finished...");
+                    return Poll::Ready(None);
                 }
-            }
-            HandshakeState::Finished => {
-                tracing::trace!("handshake finished...");
-                return Poll::Ready(None);
-            }
 
-            HandshakeState::Errored => {
-                tracing::warn!("handshake polled while errored...");
-                return Poll::Ready(None);
+                HandshakeState::Errored => {
+                    tracing::warn!("handshake polled while errored...");
+                    return Poll::Ready(None);
+                }
             }
         }
     }
@@ -1,13 +1,10 @@
-use nom::bytes::complete::take;
-use nom::combinator::map_res;
-use nom::sequence::tuple;
-use nom::IResult;
 use tokio::io::{AsyncWrite, AsyncWriteExt as _};
 use util::bt::{self, InfoHash, PeerId};
 
 use crate::message::extensions::{self, Extensions};
 use crate::message::protocol::Protocol;
 
+/// Handshake message sent and received at the start of a `BitTorrent` connection.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct HandshakeMessage {
@@ -19,6 +16,11 @@ pub struct HandshakeMessage {
 
 impl HandshakeMessage {
     /// Create a new `HandshakeMessage` from the given components.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if a custom protocol is longer than 255 bytes.
+    #[must_use]
     pub fn from_parts(prot: Protocol, ext: Extensions, hash: InfoHash, pid: PeerId) -> HandshakeMessage {
         if let Protocol::Custom(ref custom) = prot {
             assert!(
@@ -31,10 +33,42 @@ impl HandshakeMessage {
         HandshakeMessage { prot, ext, hash, pid }
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> IResult<(), HandshakeMessage> {
-        parse_remote_handshake(bytes)
+    /// Parse a `HandshakeMessage` from the bytes of a complete handshake.
+    ///
+    /// Fields are read directly out of the given bytes; unless the protocol is a custom
+    /// protocol, which has to be copied out, parsing does not allocate.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the bytes are not exactly one handshake.
+    pub fn parse_bytes(bytes: &[u8]) -> std::io::Result<HandshakeMessage> {
+        let Some((&protocol_len, remaining)) = bytes.split_first() else {
+            return Err(invalid_handshake("Missing Protocol Length"));
+        };
+
+        if bytes.len() != write_len_with_protocol_len(protocol_len) {
+            return Err(invalid_handshake("Length Does Not Match Protocol Length"));
+        }
+
+        let (raw_prot, remaining) = remaining.split_at(protocol_len as usize);
+        let (raw_ext, remaining) = remaining.split_at(extensions::NUM_EXTENSION_BYTES);
+        let (raw_hash, raw_pid) = remaining.split_at(bt::INFO_HASH_LEN);
+
+        let prot = Protocol::from_raw_bytes(raw_prot);
+        let ext = Extensions::from_bytes(raw_ext)
+            .map_err(|_| invalid_handshake("Invalid Extensions"))?
+            .1;
+        let hash = InfoHash::from_hash(raw_hash).map_err(|_| invalid_handshake("Invalid InfoHash"))?;
+        let pid = PeerId::from_hash(raw_pid).map_err(|_| invalid_handshake("Invalid PeerId"))?;
+
+        Ok(HandshakeMessage::from_parts(prot, ext, hash, pid))
     }
 
+    /// Write the `HandshakeMessage` out to the given async writer.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO Error if unable to write bytes.
     #[allow(dead_code)]
     pub async fn write_bytes<W>(&self, writer: &mut W) -> std::io::Result<()>
     where
@@ -49,6 +83,11 @@ impl HandshakeMessage {
         Ok(())
     }
 
+    /// Write the `HandshakeMessage` out to the given writer.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO Error if unable to write bytes.
     pub fn write_bytes_sync<W>(&self, writer: &mut W) -> std::io::Result<()>
     where
         W: std::io::Write,
@@ -62,11 +101,15 @@ impl HandshakeMessage {
         Ok(())
     }
 
+    /// Number of bytes the `HandshakeMessage` is written out as.
+    #[must_use]
     pub fn write_len(&self) -> usize {
         #[allow(clippy::cast_possible_truncation)]
         write_len_with_protocol_len(self.prot.write_len() as u8)
     }
 
+    /// Break the `HandshakeMessage` into its components.
+    #[must_use]
     pub fn into_parts(self) -> (Protocol, Extensions, InfoHash, PeerId) {
         (self.prot, self.ext, self.hash, self.pid)
     }
@@ -76,30 +119,11 @@ pub fn write_len_with_protocol_len(protocol_len: u8) -> usize {
     1 + (protocol_len as usize) + extensions::NUM_EXTENSION_BYTES + bt::INFO_HASH_LEN + bt::PEER_ID_LEN
 }
 
-#[allow(clippy::ptr_arg)]
-fn parse_remote_handshake(bytes: &Vec<u8>) -> IResult<(), HandshakeMessage> {
-    let res = tuple((
-        Protocol::from_bytes,
-        Extensions::from_bytes,
-        parse_remote_hash,
-        parse_remote_pid,
-    ))(bytes);
-
-    let (_, (prot, ext, hash, pid)) = res.map_err(|e: nom::Err<nom::error::Error<&[u8]>>| e.map_input(|_| ()))?;
-
-    Ok(((), HandshakeMessage::from_parts(prot, ext, hash, pid)))
-}
-
-fn parse_remote_hash(bytes: &[u8]) -> IResult<&[u8], InfoHash> {
-    map_res(take(bt::INFO_HASH_LEN), |hash: &[u8]| {
-        InfoHash::from_hash(hash).map_err(|_| nom::Err::Error((bytes, nom::error::ErrorKind::LengthValue)))
-    })(bytes)
-}
-
-fn parse_remote_pid(bytes: &[u8]) -> IResult<&[u8], PeerId> {
-    map_res(take(bt::PEER_ID_LEN), |pid: &[u8]| {
-        PeerId::from_hash(pid).map_err(|_| nom::Err::Error((bytes, nom::error::ErrorKind::LengthValue)))
-    })(bytes)
+fn invalid_handshake(details: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("bip_handshake: Invalid Handshake Message, {details}"),
+    )
 }
 
 #[cfg(test)]
@@ -140,7 +164,7 @@ mod tests {
         buffer.write_all(exp_hash.as_ref()).unwrap();
         buffer.write_all(exp_pid.as_ref()).unwrap();
 
-        let recv_message = HandshakeMessage::from_bytes(&buffer).unwrap().1;
+        let recv_message = HandshakeMessage::parse_bytes(&buffer).unwrap();
 
         assert_eq!(exp_message, recv_message);
     }
@@ -161,7 +185,7 @@ mod tests {
         buffer.write_all(exp_hash.as_ref()).unwrap();
         buffer.write_all(exp_pid.as_ref()).unwrap();
 
-        let recv_message = HandshakeMessage::from_bytes(&buffer).unwrap().1;
+        let recv_message = HandshakeMessage::parse_bytes(&buffer).unwrap();
 
         assert_eq!(exp_message, recv_message);
     }
@@ -182,16 +206,28 @@ mod tests {
         buffer.write_all(exp_hash.as_ref()).unwrap();
         buffer.write_all(exp_pid.as_ref()).unwrap();
 
-        let recv_message = HandshakeMessage::from_bytes(&buffer).unwrap().1;
+        let recv_message = HandshakeMessage::parse_bytes(&buffer).unwrap();
 
         assert_eq!(exp_message, recv_message);
     }
 
+    #[test]
+    fn negative_truncated_handshake() {
+        let mut buffer = Vec::new();
+
+        let message = HandshakeMessage::from_parts(Protocol::BitTorrent, any_extensions(), any_info_hash(), any_peer_id());
+        message.write_bytes_sync(&mut buffer).unwrap();
+        buffer.pop();
+
+        assert!(HandshakeMessage::parse_bytes(&buffer).is_err());
+        assert!(HandshakeMessage::parse_bytes(&[]).is_err());
+    }
+
     #[test]
     #[should_panic(expected = "bip_handshake: Handshake Message With Protocol Length Greater Than 255 Found")]
     fn negative_create_overflow_protocol() {
         let overflow_protocol = Protocol::Custom(vec![0u8; 256]);
 
-        HandshakeMessage::from_parts(overflow_protocol, any_extensions(), any_info_hash(), any_peer_id());
+        let _message = HandshakeMessage::from_parts(overflow_protocol, any_extensions(), any_info_hash(), any_peer_id());
     }
 }
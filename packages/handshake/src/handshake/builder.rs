@@ -1,4 +1,5 @@
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
 
 use rand::Rng as _;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -7,17 +8,20 @@ use util::bt::PeerId;
 use util::convert;
 
 use super::Handshaker;
-use crate::{Extensions, HandshakerConfig, Transport};
+use crate::policy::AcceptAll;
+use crate::{Extensions, HandshakePolicy, HandshakerConfig, PreSharedKey, Transport};
 
 /// Build configuration for `Handshaker` object creation.
 #[allow(clippy::module_name_repetitions)]
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct HandshakerBuilder {
     pub(super) bind: SocketAddr,
     pub(super) port: u16,
     pub(super) pid: PeerId,
     pub(super) ext: Extensions,
     pub(super) config: HandshakerConfig,
+    pub(super) policy: Arc<dyn HandshakePolicy + Send + Sync>,
+    pub(super) psk: Option<PreSharedKey>,
 }
 
 impl Default for HandshakerBuilder {
@@ -36,6 +40,8 @@ impl Default for HandshakerBuilder {
             pid,
             ext: Extensions::default(),
             config: HandshakerConfig::default(),
+            policy: Arc::new(AcceptAll),
+            psk: None,
         }
     }
 }
@@ -84,6 +90,28 @@ impl HandshakerBuilder {
         self
     }
 
+    /// Policy deciding whether to accept a handshake from a peer that connected to us.
+    ///
+    /// Defaults to `AcceptAll`; see `RejectSelf` for rejecting self-connections.
+    pub fn with_policy<P>(&mut self, policy: P) -> &mut HandshakerBuilder
+    where
+        P: HandshakePolicy + Send + Sync + 'static,
+    {
+        self.policy = Arc::new(policy);
+
+        self
+    }
+
+    /// Key that peers must prove knowledge of before their connection is yielded.
+    ///
+    /// Intended for private deployments; peers without the key (including all regular
+    /// `BitTorrent` clients) are rejected after the handshake. Defaults to no key.
+    pub fn with_pre_shared_key(&mut self, key: PreSharedKey) -> &mut HandshakerBuilder {
+        self.psk = Some(key);
+
+        self
+    }
+
     /// Configuration that will be used to alter the internal behavior of handshaking.
     ///
     /// This will typically not need to be set unless you know what you are doing.
@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::time::Duration;
 
 const DEFAULT_HANDSHAKE_BUFFER_SIZE: usize = 1000;
@@ -8,6 +9,8 @@ const DEFAULT_DONE_BUFFER_SIZE: usize = 10;
 /// mpmc future channel support, we can bump this up).
 const DEFAULT_HANDSHAKE_TIMEOUT_MILLIS: u64 = 1000;
 const DEFAULT_HANDSHAKE_CONNECT_TIMEOUT_MILLIS: u64 = 1000;
+const DEFAULT_DEDUP_WINDOW_MILLIS: u64 = 0;
+const DEFAULT_FALLBACK_DELAY_MILLIS: u64 = 250;
 
 /// Configures the internals of a `Handshaker`.
 #[allow(clippy::module_name_repetitions)]
@@ -18,6 +21,9 @@ pub struct HandshakerConfig {
     done_buffer_size: usize,
     handshake_timeout: Duration,
     connect_timeout: Duration,
+    dedup_window: Duration,
+    fallback_delay: Duration,
+    source_addr: Option<IpAddr>,
 }
 
 impl HandshakerConfig {
@@ -62,6 +68,51 @@ impl HandshakerConfig {
         self
     }
 
+    /// Sets the window that `Handshaker` uses to detect duplicate
+    /// connections to the same peer for the same torrent, such as
+    /// when both sides connect to each other simultaneously.
+    ///
+    /// Only one connection per peer id and info hash is yielded within
+    /// the window; the connection initiated by the peer with the lower
+    /// peer id is kept, the other is closed. Connections which may lose
+    /// the tie-break are delayed by up to this window.
+    ///
+    /// Defaults to zero, which disables deduplication; every connection
+    /// is yielded as soon as its handshake completes.
+    #[must_use]
+    pub fn with_dedup_window(mut self, window: Duration) -> HandshakerConfig {
+        self.dedup_window = window;
+        self
+    }
+
+    /// Sets the delay that `Handshaker` waits for a connection to the
+    /// IPv6 address of a dual stack peer before also dialing its IPv4
+    /// address, for an `InitiateMessage` with an alternate address.
+    ///
+    /// Whichever connection is established first is kept, the other
+    /// attempt is cancelled.
+    ///
+    /// Defaults to 250 milliseconds.
+    #[must_use]
+    pub fn with_fallback_delay(mut self, delay: Duration) -> HandshakerConfig {
+        self.fallback_delay = delay;
+        self
+    }
+
+    /// Sets the local address that `Handshaker` binds outgoing connections
+    /// to, such as the address of a VPN interface, unless a different
+    /// address was set for the torrent being connected to.
+    ///
+    /// Connections that cannot be bound to the address are reported as
+    /// failed rather than made over the default route.
+    ///
+    /// Defaults to `None`, which uses the default route.
+    #[must_use]
+    pub fn with_source_addr(mut self, addr: Option<IpAddr>) -> HandshakerConfig {
+        self.source_addr = addr;
+        self
+    }
+
     /// Gets the sink buffer size.
     #[must_use]
     pub fn sink_buffer_size(&self) -> usize {
@@ -91,6 +142,24 @@ impl HandshakerConfig {
     pub fn connect_timeout(&self) -> Duration {
         self.connect_timeout
     }
+
+    /// Gets the duplicate connection window.
+    #[must_use]
+    pub fn dedup_window(&self) -> Duration {
+        self.dedup_window
+    }
+
+    /// Gets the delay before dialing the fallback address of a dual stack peer.
+    #[must_use]
+    pub fn fallback_delay(&self) -> Duration {
+        self.fallback_delay
+    }
+
+    /// Gets the local address for outgoing connections.
+    #[must_use]
+    pub fn source_addr(&self) -> Option<IpAddr> {
+        self.source_addr
+    }
 }
 
 impl Default for HandshakerConfig {
@@ -101,6 +170,9 @@ impl Default for HandshakerConfig {
             done_buffer_size: DEFAULT_DONE_BUFFER_SIZE,
             handshake_timeout: Duration::from_millis(DEFAULT_HANDSHAKE_TIMEOUT_MILLIS),
             connect_timeout: Duration::from_millis(DEFAULT_HANDSHAKE_CONNECT_TIMEOUT_MILLIS),
+            dedup_window: Duration::from_millis(DEFAULT_DEDUP_WINDOW_MILLIS),
+            fallback_delay: Duration::from_millis(DEFAULT_FALLBACK_DELAY_MILLIS),
+            source_addr: None,
         }
     }
 }
@@ -0,0 +1,341 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures::{Sink, SinkExt as _, Stream, StreamExt as _};
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+use util::bt::{InfoHash, PeerId};
+
+use crate::message::complete::CompleteMessage;
+
+/// Which side of the connection initiated it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HandshakeDirection {
+    /// We connected to the peer.
+    Initiated,
+    /// The peer connected to us.
+    Accepted,
+}
+
+/// Outcome of accepting a completed handshake into the `Deduplicator`.
+pub struct Resolution<S> {
+    /// Connection that should be forwarded upstream.
+    pub surface: Option<CompleteMessage<S>>,
+    /// Connection that lost the tie-break and should be closed.
+    pub close: Option<CompleteMessage<S>>,
+}
+
+/// Resolves duplicate connections to the same (peer id, info hash) caused by simultaneous opens.
+///
+/// Both peers must agree on which connection survives, so the tie-break is canonical: the connection
+/// initiated by the peer with the lower peer id is preferred. Preferred connections are surfaced
+/// immediately, while the other direction is held for the dedup window in case the preferred
+/// connection is still on its way.
+pub struct Deduplicator<S> {
+    pid: PeerId,
+    window: Duration,
+    held: HashMap<(PeerId, InfoHash), (Instant, CompleteMessage<S>)>,
+    surfaced: HashMap<(PeerId, InfoHash), Instant>,
+}
+
+impl<S> Deduplicator<S> {
+    pub fn new(pid: PeerId, window: Duration) -> Deduplicator<S> {
+        Deduplicator {
+            pid,
+            window,
+            held: HashMap::new(),
+            surfaced: HashMap::new(),
+        }
+    }
+
+    /// Accept a completed handshake, returning what should be surfaced and what should be closed.
+    pub fn accept(&mut self, direction: HandshakeDirection, message: CompleteMessage<S>, now: Instant) -> Resolution<S> {
+        let window = self.window;
+        self.surfaced
+            .retain(|_, surfaced_at| now.saturating_duration_since(*surfaced_at) < window);
+
+        let key = (*message.peer_id(), *message.hash());
+
+        if self.surfaced.contains_key(&key) {
+            return Resolution {
+                surface: None,
+                close: Some(message),
+            };
+        }
+
+        if is_preferred(direction, &self.pid, message.peer_id()) {
+            let close = self.held.remove(&key).map(|(_, held)| held);
+            self.surfaced.insert(key, now);
+
+            Resolution {
+                surface: Some(message),
+                close,
+            }
+        } else {
+            match self.held.entry(key) {
+                Entry::Occupied(_) => Resolution {
+                    surface: None,
+                    close: Some(message),
+                },
+                Entry::Vacant(vacant) => {
+                    vacant.insert((now + window, message));
+
+                    Resolution {
+                        surface: None,
+                        close: None,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Release all held connections whose window has passed without a preferred duplicate showing up.
+    pub fn expire(&mut self, now: Instant) -> Vec<CompleteMessage<S>> {
+        let expired: Vec<(PeerId, InfoHash)> = self
+            .held
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| {
+                self.surfaced.insert(key, now);
+
+                self.held.remove(&key).map(|(_, message)| message)
+            })
+            .collect()
+    }
+
+    /// Release all held connections, regardless of their deadline.
+    pub fn drain(&mut self) -> Vec<CompleteMessage<S>> {
+        self.held.drain().map(|(_, (_, message))| message).collect()
+    }
+
+    /// Earliest deadline of any held connection.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.held.values().map(|(deadline, _)| *deadline).min()
+    }
+}
+
+/// Returns true if the connection in the given direction wins the simultaneous open tie-break.
+fn is_preferred(direction: HandshakeDirection, local_pid: &PeerId, remote_pid: &PeerId) -> bool {
+    match direction {
+        HandshakeDirection::Initiated => local_pid <= remote_pid,
+        HandshakeDirection::Accepted => remote_pid < local_pid,
+    }
+}
+
+/// Forward completed handshakes from the stream to the sink, dropping duplicate connections.
+///
+/// A zero window disables deduplication, every completed handshake is forwarded as is.
+#[allow(clippy::module_name_repetitions)]
+pub async fn dedup_handler<M, K, S>(mut stream: M, mut sink: K, pid: PeerId, window: Duration)
+where
+    M: Stream<Item = std::io::Result<(HandshakeDirection, CompleteMessage<S>)>> + Unpin,
+    K: Sink<std::io::Result<CompleteMessage<S>>> + Unpin,
+    S: AsyncWrite + Send + Unpin + 'static,
+{
+    if window.is_zero() {
+        while let Some(item) = stream.next().await {
+            if sink.send(item.map(|(_, message)| message)).await.is_err() {
+                return;
+            }
+        }
+
+        return;
+    }
+
+    let mut dedup = Deduplicator::new(pid, window);
+
+    loop {
+        let next_deadline = dedup.next_deadline();
+        let wait_deadline = async move {
+            match next_deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => futures::future::pending().await,
+            }
+        };
+
+        let (surface, close) = tokio::select! {
+            item = stream.next() => match item {
+                Some(Ok((direction, message))) => {
+                    let resolution = dedup.accept(direction, message, Instant::now());
+
+                    (resolution.surface.into_iter().collect(), resolution.close)
+                }
+                Some(Err(err)) => {
+                    if sink.send(Err(err)).await.is_err() {
+                        break;
+                    }
+
+                    continue;
+                }
+                None => break,
+            },
+            () = wait_deadline => (dedup.expire(Instant::now()), None),
+        };
+
+        // Shutting down a duplicate can be slow, it should not hold up the connections behind it
+        if let Some(message) = close {
+            tokio::spawn(close_gracefully(message));
+        }
+
+        for message in surface {
+            if sink.send(Ok(message)).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    // Nothing left that could be preferred over the held connections
+    for message in dedup.drain() {
+        if sink.send(Ok(message)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn close_gracefully<S>(message: CompleteMessage<S>)
+where
+    S: AsyncWrite + Unpin,
+{
+    let (.., addr, mut sock) = message.into_parts();
+
+    if let Err(err) = sock.shutdown().await {
+        tracing::debug!("failed to shutdown duplicate connection to {addr}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use futures::channel::mpsc;
+    use futures::{SinkExt as _, StreamExt as _};
+    use util::bt::{self, InfoHash, PeerId};
+
+    use crate::handshake::handler::dedup::{dedup_handler, Deduplicator, HandshakeDirection};
+    use crate::message::complete::CompleteMessage;
+    use crate::message::extensions::Extensions;
+    use crate::message::protocol::Protocol;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    fn lower_peer_id() -> PeerId {
+        [4u8; bt::PEER_ID_LEN].into()
+    }
+
+    fn higher_peer_id() -> PeerId {
+        [5u8; bt::PEER_ID_LEN].into()
+    }
+
+    fn any_info_hash() -> InfoHash {
+        [55u8; bt::INFO_HASH_LEN].into()
+    }
+
+    fn complete_message(pid: PeerId, port: u16) -> CompleteMessage<u16> {
+        CompleteMessage::new(
+            Protocol::BitTorrent,
+            Extensions::new(),
+            any_info_hash(),
+            pid,
+            format!("1.2.3.4:{port}").parse().unwrap(),
+            port,
+        )
+    }
+
+    #[test]
+    fn positive_preferred_connection_surfaced_immediately() {
+        let mut dedup = Deduplicator::new(lower_peer_id(), WINDOW);
+
+        let resolution = dedup.accept(
+            HandshakeDirection::Initiated,
+            complete_message(higher_peer_id(), 1),
+            Instant::now(),
+        );
+
+        assert_eq!(resolution.surface.map(|message| *message.socket()), Some(1));
+        assert!(resolution.close.is_none());
+    }
+
+    #[test]
+    fn positive_preferred_connection_replaces_held() {
+        let mut dedup = Deduplicator::new(lower_peer_id(), WINDOW);
+        let now = Instant::now();
+
+        let held = dedup.accept(HandshakeDirection::Accepted, complete_message(higher_peer_id(), 1), now);
+        assert!(held.surface.is_none() && held.close.is_none());
+
+        let resolution = dedup.accept(HandshakeDirection::Initiated, complete_message(higher_peer_id(), 2), now);
+
+        assert_eq!(resolution.surface.map(|message| *message.socket()), Some(2));
+        assert_eq!(resolution.close.map(|message| *message.socket()), Some(1));
+        assert!(dedup.expire(now + WINDOW).is_empty());
+    }
+
+    #[test]
+    fn positive_held_connection_released_after_window() {
+        let mut dedup = Deduplicator::new(higher_peer_id(), WINDOW);
+        let now = Instant::now();
+
+        dedup.accept(HandshakeDirection::Initiated, complete_message(lower_peer_id(), 1), now);
+
+        assert!(dedup.expire(now).is_empty());
+        assert_eq!(dedup.next_deadline(), Some(now + WINDOW));
+
+        let released = dedup.expire(now + WINDOW);
+        assert_eq!(released.iter().map(|message| *message.socket()).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn negative_duplicate_after_surfaced_closed() {
+        let mut dedup = Deduplicator::new(higher_peer_id(), WINDOW);
+        let now = Instant::now();
+
+        dedup.accept(HandshakeDirection::Accepted, complete_message(lower_peer_id(), 1), now);
+        let resolution = dedup.accept(HandshakeDirection::Initiated, complete_message(lower_peer_id(), 2), now);
+
+        assert!(resolution.surface.is_none());
+        assert_eq!(resolution.close.map(|message| *message.socket()), Some(2));
+    }
+
+    #[test]
+    fn positive_reconnect_after_window_surfaced() {
+        let mut dedup = Deduplicator::new(higher_peer_id(), WINDOW);
+        let now = Instant::now();
+
+        dedup.accept(HandshakeDirection::Accepted, complete_message(lower_peer_id(), 1), now);
+        let resolution = dedup.accept(
+            HandshakeDirection::Accepted,
+            complete_message(lower_peer_id(), 2),
+            now + WINDOW,
+        );
+
+        assert_eq!(resolution.surface.map(|message| *message.socket()), Some(2));
+    }
+
+    #[tokio::test]
+    async fn positive_zero_window_forwards_duplicates_immediately() {
+        let (mut dedup_send, dedup_recv) = mpsc::channel(2);
+        let (sock_send, mut sock_recv) = mpsc::channel(2);
+
+        tokio::spawn(dedup_handler(dedup_recv, sock_send, higher_peer_id(), Duration::ZERO));
+
+        for (port, direction) in [(1, HandshakeDirection::Initiated), (2, HandshakeDirection::Accepted)] {
+            let message = CompleteMessage::new(
+                Protocol::BitTorrent,
+                Extensions::new(),
+                any_info_hash(),
+                lower_peer_id(),
+                format!("1.2.3.4:{port}").parse().unwrap(),
+                tokio::io::sink(),
+            );
+            dedup_send.send(Ok((direction, message))).await.unwrap();
+
+            let forwarded = sock_recv.next().await.unwrap().unwrap();
+            assert_eq!(forwarded.address().port(), port);
+        }
+    }
+}
@@ -1,45 +1,132 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use futures::future::BoxFuture;
-use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _, TryFutureExt as _};
 use tokio::io::{AsyncRead, AsyncWrite};
-use util::bt::PeerId;
+use tracing::Instrument as _;
+use util::bt::{InfoHash, PeerId};
 
+use crate::attempt::{AttemptEvent, AttemptEvents, AttemptFailure, AttemptStage};
 use crate::bittorrent::framed::FramedHandshake;
 use crate::bittorrent::message::HandshakeMessage;
 use crate::filter::filters::Filters;
 use crate::handshake::handler;
+use crate::handshake::handler::dedup::HandshakeDirection;
 use crate::handshake::handler::HandshakeType;
 use crate::message::complete::CompleteMessage;
 use crate::message::extensions::Extensions;
 use crate::message::initiate::InitiateMessage;
+use crate::policy::{HandshakePolicy, PolicyDecision, RemoteHandshake};
+use crate::psk::{self, PreSharedKey};
+
+/// Completed handshake along with the side that initiated the connection.
+type DirectedMessage<S> = (HandshakeDirection, CompleteMessage<S>);
+
+/// Policy shared between the `HandshakerBuilder` and the handshake handler.
+type SharedPolicy = Arc<dyn HandshakePolicy + Send + Sync>;
 
 #[allow(clippy::module_name_repetitions)]
 pub fn execute_handshake<'a, S>(
     item: std::io::Result<HandshakeType<S>>,
-    context: &(Extensions, PeerId, Filters, Duration),
-) -> BoxFuture<'a, std::io::Result<Option<CompleteMessage<S>>>>
+    context: &(
+        Extensions,
+        PeerId,
+        Filters,
+        SharedPolicy,
+        Option<PreSharedKey>,
+        AttemptEvents,
+        Duration,
+    ),
+) -> BoxFuture<'a, std::io::Result<Option<DirectedMessage<S>>>>
 where
     S: AsyncWrite + AsyncRead + std::fmt::Debug + Send + Unpin + 'a,
 {
-    let (ext, pid, filters, timeout) = context;
+    let (ext, pid, filters, policy, psk, events, timeout) = context;
 
     match item {
         Ok(HandshakeType::Initiate(sock, init_msg)) => {
-            initiate_handshake(sock, init_msg, *ext, *pid, filters.clone(), *timeout).boxed()
+            let span = peer_span(init_msg.address());
+            span.record("info_hash", tracing::field::display(init_msg.hash().short()));
+
+            initiate_handshake(
+                sock,
+                init_msg,
+                *ext,
+                *pid,
+                filters.clone(),
+                psk.clone(),
+                events.clone(),
+                *timeout,
+            )
+            .map_ok(|opt_msg| opt_msg.map(|msg| (HandshakeDirection::Initiated, msg)))
+            .instrument(span)
+            .boxed()
+        }
+        Ok(HandshakeType::Complete(sock, addr)) => {
+            complete_handshake(sock, addr, *ext, *pid, filters.clone(), policy.clone(), psk.clone(), *timeout)
+                .map_ok(|opt_msg| opt_msg.map(|msg| (HandshakeDirection::Accepted, msg)))
+                .instrument(peer_span(&addr))
+                .boxed()
         }
-        Ok(HandshakeType::Complete(sock, addr)) => complete_handshake(sock, addr, *ext, *pid, filters.clone(), *timeout).boxed(),
         Err(err) => async move { Err(err) }.boxed(),
     }
 }
 
+/// Span for a single connection, the remote peer id and info hash are recorded once known.
+///
+/// Field names match those used by `PeerInfo` in the peer crate, so a connection can be followed across crates.
+fn peer_span(addr: &SocketAddr) -> tracing::Span {
+    tracing::info_span!(
+        "peer",
+        peer_addr = %addr,
+        peer_id = tracing::field::Empty,
+        info_hash = tracing::field::Empty
+    )
+}
+
+/// Records the identity of the remote peer on the current span.
+fn record_remote(remote_pid: &PeerId, remote_hash: &InfoHash) {
+    let span = tracing::Span::current();
+
+    span.record("peer_id", tracing::field::display(remote_pid.short()));
+    span.record("info_hash", tracing::field::display(remote_hash.short()));
+}
+
+/// Prove knowledge of the key to the peer (if we have one), returning whether the peer knows it as well.
+async fn authenticate<S>(
+    sock: &mut S,
+    opt_psk: Option<&PreSharedKey>,
+    hash: &InfoHash,
+    initiator: bool,
+    timeout: Duration,
+) -> bool
+where
+    S: AsyncWrite + AsyncRead + Unpin,
+{
+    let Some(key) = opt_psk else {
+        return true;
+    };
+
+    matches!(
+        tokio::time::timeout(timeout, psk::authenticate(sock, key, hash, initiator)).await,
+        Ok(Ok(true))
+    )
+}
+
+/// Initiates a handshake over an outgoing connection, reporting its progress to the `AttemptEvents`.
+///
+/// Rejected handshakes are reported and skipped.
+#[allow(clippy::too_many_arguments)]
 async fn initiate_handshake<S>(
     sock: S,
     init_msg: InitiateMessage,
     ext: Extensions,
     pid: PeerId,
     filters: Filters,
+    psk: Option<PreSharedKey>,
+    events: AttemptEvents,
     timeout: Duration,
 ) -> std::io::Result<Option<CompleteMessage<S>>>
 where
@@ -49,22 +136,51 @@ where
 
     let (prot, hash, addr) = init_msg.into_parts();
     let handshake_msg = HandshakeMessage::from_parts(prot.clone(), ext, hash, pid);
+    let fail = |failure| events.report(AttemptEvent::new(addr, hash, AttemptStage::Failed(failure)));
 
-    let send_result = tokio::time::timeout(timeout, framed.send(handshake_msg)).await;
-    if send_result.is_err() {
-        return Ok(None);
+    match tokio::time::timeout(timeout, framed.send(handshake_msg)).await {
+        Ok(Ok(())) => events.report(AttemptEvent::new(addr, hash, AttemptStage::HandshakeSent)),
+        Ok(Err(_)) => {
+            tracing::debug!("handshake failed sending");
+            fail(AttemptFailure::Disconnected);
+            return Ok(None);
+        }
+        Err(_) => {
+            tracing::debug!("handshake timed out sending");
+            fail(AttemptFailure::TimedOut);
+            return Ok(None);
+        }
     }
 
-    let recv_result = tokio::time::timeout(timeout, framed.next()).await;
-    let Ok(Some(Ok(msg))) = recv_result else { return Ok(None) };
+    let msg = match tokio::time::timeout(timeout, framed.next()).await {
+        Ok(Some(Ok(msg))) => msg,
+        recv_result => {
+            tracing::debug!("handshake failed receiving");
+            fail(match recv_result {
+                Err(_) => AttemptFailure::TimedOut,
+                Ok(None) => AttemptFailure::Disconnected,
+                Ok(Some(_)) => AttemptFailure::InvalidHandshake,
+            });
+            return Ok(None);
+        }
+    };
 
     let (remote_prot, remote_ext, remote_hash, remote_pid) = msg.into_parts();
-    let socket = framed.into_inner();
+    let mut socket = framed.into_inner();
+    record_remote(&remote_pid, &remote_hash);
+
+    let fail = |failure| {
+        events.report(AttemptEvent::new(addr, hash, AttemptStage::Failed(failure)).with_peer_id(remote_pid));
+    };
 
     if remote_hash != hash {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "not matching hash"))
+        tracing::debug!("handshake rejected: not matching hash");
+        fail(AttemptFailure::WrongInfoHash);
+        Ok(None)
     } else if remote_prot != prot {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "not matching port"))
+        tracing::debug!("handshake rejected: not matching protocol");
+        fail(AttemptFailure::WrongProtocol);
+        Ok(None)
     } else if handler::should_filter(
         Some(&addr),
         Some(&remote_prot),
@@ -73,25 +189,32 @@ where
         Some(&remote_pid),
         &filters,
     ) {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "should not filter"))
+        tracing::debug!("handshake rejected: filtered");
+        fail(AttemptFailure::Filtered);
+        Ok(None)
+    } else if !authenticate(&mut socket, psk.as_ref(), &hash, true, timeout).await {
+        tracing::debug!("handshake rejected: unauthenticated");
+        fail(AttemptFailure::Unauthenticated);
+        Ok(None)
     } else {
-        Ok(Some(CompleteMessage::new(
-            prot,
-            ext.union(&remote_ext),
-            hash,
-            remote_pid,
-            addr,
-            socket,
-        )))
+        tracing::debug!("handshake completed");
+        events.report(AttemptEvent::new(addr, hash, AttemptStage::Completed).with_peer_id(remote_pid));
+
+        Ok(Some(
+            CompleteMessage::new(prot, ext.union(&remote_ext), hash, remote_pid, addr, socket).with_remote_extensions(remote_ext),
+        ))
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn complete_handshake<S>(
     sock: S,
     addr: SocketAddr,
     ext: Extensions,
     pid: PeerId,
     filters: Filters,
+    policy: SharedPolicy,
+    psk: Option<PreSharedKey>,
     timeout: Duration,
 ) -> std::io::Result<Option<CompleteMessage<S>>>
 where
@@ -100,9 +223,13 @@ where
     let mut framed = FramedHandshake::new(sock);
 
     let recv_result = tokio::time::timeout(timeout, framed.next()).await;
-    let Ok(Some(Ok(msg))) = recv_result else { return Ok(None) };
+    let Ok(Some(Ok(msg))) = recv_result else {
+        tracing::debug!("handshake timed out receiving");
+        return Ok(None);
+    };
 
     let (remote_prot, remote_ext, remote_hash, remote_pid) = msg.into_parts();
+    record_remote(&remote_pid, &remote_hash);
 
     if handler::should_filter(
         Some(&addr),
@@ -112,41 +239,59 @@ where
         Some(&remote_pid),
         &filters,
     ) {
+        tracing::debug!("handshake rejected: filtered");
         Err(std::io::Error::new(std::io::ErrorKind::Other, "should not filter"))
+    } else if policy.on_incoming(&RemoteHandshake::new(
+        &addr,
+        &remote_prot,
+        &remote_ext,
+        &remote_hash,
+        &remote_pid,
+        &pid,
+    )) == PolicyDecision::Reject
+    {
+        tracing::debug!("handshake rejected: policy");
+        Ok(None)
     } else {
         let handshake_msg = HandshakeMessage::from_parts(remote_prot.clone(), ext, remote_hash, pid);
 
         let send_result = tokio::time::timeout(timeout, framed.send(handshake_msg)).await;
         if send_result.is_err() {
+            tracing::debug!("handshake timed out sending");
+            return Ok(None);
+        }
+
+        let mut socket = framed.into_inner();
+        if !authenticate(&mut socket, psk.as_ref(), &remote_hash, false, timeout).await {
+            tracing::debug!("handshake rejected: unauthenticated");
             return Ok(None);
         }
 
-        let socket = framed.into_inner();
+        tracing::debug!("handshake completed");
 
-        Ok(Some(CompleteMessage::new(
-            remote_prot,
-            ext.union(&remote_ext),
-            remote_hash,
-            remote_pid,
-            addr,
-            socket,
-        )))
+        Ok(Some(
+            CompleteMessage::new(remote_prot, ext.union(&remote_ext), remote_hash, remote_pid, addr, socket)
+                .with_remote_extensions(remote_ext),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::sync::Arc;
     use std::time::Duration;
 
     use util::bt::{self, InfoHash, PeerId};
 
     use super::HandshakeMessage;
+    use crate::attempt::AttemptEvents;
     use crate::filter::filters::Filters;
     use crate::handshake::handler::handshaker;
     use crate::message::extensions::{self, Extensions};
     use crate::message::initiate::InitiateMessage;
     use crate::message::protocol::Protocol;
+    use crate::policy::{AcceptAll, PolicyDecision, RemoteHandshake};
 
     fn any_peer_id() -> PeerId {
         [22u8; bt::PEER_ID_LEN].into()
@@ -192,6 +337,8 @@ mod tests {
             init_ext,
             init_pid,
             init_filters,
+            None,
+            AttemptEvents::new(),
             Duration::from_millis(100),
         )
         .await
@@ -205,15 +352,11 @@ mod tests {
         assert_eq!(remote_addr, *complete_message.address());
 
         let sent_message =
-            HandshakeMessage::from_bytes(&complete_message.socket().get_ref()[..remote_message.write_len()].to_vec())
-                .unwrap()
-                .1;
+            HandshakeMessage::parse_bytes(&complete_message.socket().get_ref()[..remote_message.write_len()]).unwrap();
         let local_message = HandshakeMessage::from_parts(init_prot, init_ext, init_hash, init_pid);
 
         let recv_message =
-            HandshakeMessage::from_bytes(&complete_message.socket().get_ref()[remote_message.write_len()..].to_vec())
-                .unwrap()
-                .1;
+            HandshakeMessage::parse_bytes(&complete_message.socket().get_ref()[remote_message.write_len()..]).unwrap();
 
         assert_eq!(local_message, sent_message);
         assert_eq!(remote_message, recv_message);
@@ -242,6 +385,8 @@ mod tests {
             comp_ext,
             comp_pid,
             comp_filters,
+            Arc::new(AcceptAll),
+            None,
             Duration::from_millis(100),
         )
         .await
@@ -255,17 +400,48 @@ mod tests {
         assert_eq!(remote_addr, *complete_message.address());
 
         let sent_message =
-            HandshakeMessage::from_bytes(&complete_message.socket().get_ref()[remote_message.write_len()..].to_vec())
-                .unwrap()
-                .1;
+            HandshakeMessage::parse_bytes(&complete_message.socket().get_ref()[remote_message.write_len()..]).unwrap();
         let local_message = HandshakeMessage::from_parts(remote_protocol, comp_ext, remote_hash, comp_pid);
 
         let recv_message =
-            HandshakeMessage::from_bytes(&complete_message.socket().get_ref()[..remote_message.write_len()].to_vec())
-                .unwrap()
-                .1;
+            HandshakeMessage::parse_bytes(&complete_message.socket().get_ref()[..remote_message.write_len()]).unwrap();
 
         assert_eq!(local_message, sent_message);
         assert_eq!(remote_message, recv_message);
     }
+
+    #[tokio::test]
+    async fn negative_complete_handshake_rejected_by_policy() {
+        let remote_pid = any_peer_id();
+        let remote_addr = "1.2.3.4:5".parse().unwrap();
+        let remote_message = HandshakeMessage::from_parts(Protocol::BitTorrent, any_extensions(), any_info_hash(), remote_pid);
+
+        let mut writer = std::io::Cursor::new(vec![0u8; remote_message.write_len() * 2]);
+
+        remote_message.write_bytes(&mut writer).await.unwrap();
+        writer.set_position(0);
+
+        let reject_remote = move |handshake: &RemoteHandshake<'_>| {
+            if *handshake.peer_id() == remote_pid {
+                PolicyDecision::Reject
+            } else {
+                PolicyDecision::Accept
+            }
+        };
+
+        let opt_complete_message = handshaker::complete_handshake(
+            writer,
+            remote_addr,
+            any_extensions(),
+            any_other_peer_id(),
+            Filters::new(),
+            Arc::new(reject_remote),
+            None,
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
+
+        assert!(opt_complete_message.is_none());
+    }
 }
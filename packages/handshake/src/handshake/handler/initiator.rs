@@ -1,58 +1,254 @@
 /// Handle the initiation of connections, which are returned as a `HandshakeType`.
 #[allow(clippy::module_name_repetitions)]
+use std::net::SocketAddr;
 use std::time::Duration;
 
-use futures::future::{self, BoxFuture};
-use futures::{FutureExt, TryFutureExt as _};
+use futures::future::{self, BoxFuture, Either};
+use futures::FutureExt;
 
+use crate::attempt::{AttemptEvent, AttemptEvents, AttemptFailure, AttemptStage};
 use crate::filter::filters::Filters;
 use crate::handshake::handler;
 use crate::handshake::handler::HandshakeType;
 use crate::message::initiate::InitiateMessage;
+use crate::source_addr::SourceAddrs;
 use crate::transport::Transport;
 
 /// Handle the initiation of connections, which are returned as a `HandshakeType`.
+///
+/// Connections are bound to the local address set for the torrent in the `SourceAddrs`, if any.
+/// Connections that could not be made are reported to the `AttemptEvents` and skipped.
+///
+/// Peers with an alternate address of another family are dialed in happy eyeballs style: the
+/// IPv6 address is dialed first, and the IPv4 address once the fallback delay has passed or the
+/// first attempt failed. The connection established first wins, and the other attempt is cancelled.
 #[allow(clippy::module_name_repetitions)]
 pub fn initiator_handler<'a, 'b, T>(
     item: InitiateMessage,
-    context: &'b (T, Filters, Duration),
+    context: &'b (T, Filters, SourceAddrs, AttemptEvents, Duration, Duration),
 ) -> BoxFuture<'a, std::io::Result<Option<HandshakeType<T::Socket>>>>
 where
     T: Transport + Send + Sync + 'a,
     <T as Transport>::Socket: Send + Sync,
 {
-    let (transport, filters, timeout) = context;
-    let timeout = *timeout;
-
-    if handler::should_filter(
-        Some(item.address()),
-        Some(item.protocol()),
-        None,
-        Some(item.hash()),
-        None,
-        filters,
-    ) {
-        future::ok(None).boxed()
-    } else {
-        transport
-            .connect(*item.address(), timeout)
-            .map_ok(|s| Some(HandshakeType::Initiate(s, item)))
-            .boxed()
+    let (transport, filters, source_addrs, events, timeout, fallback_delay) = context;
+    let (timeout, fallback_delay) = (*timeout, *fallback_delay);
+    let hash = *item.hash();
+
+    let mut addrs = vec![*item.address()];
+    if let Some(alt_addr) = item
+        .alternate_address()
+        .filter(|alt| alt.is_ipv4() != item.address().is_ipv4())
+    {
+        addrs.push(*alt_addr);
+    }
+    // IPv6 is preferred, as long as it connects within the fallback delay
+    addrs.sort_by_key(SocketAddr::is_ipv4);
+
+    let source = source_addrs.lookup(&hash);
+    let mut dials: Vec<(SocketAddr, T::FutureSocket)> = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        if handler::should_filter(Some(&addr), Some(item.protocol()), None, Some(&hash), None, filters) {
+            events.report(AttemptEvent::new(addr, hash, AttemptStage::Failed(AttemptFailure::Filtered)));
+
+            continue;
+        }
+
+        if source.is_some_and(|source| source.is_ipv4() != addr.is_ipv4()) {
+            tracing::debug!("connection to {addr} skipped: source address {source:?} is of a different family");
+            events.report(AttemptEvent::new(
+                addr,
+                hash,
+                AttemptStage::Failed(AttemptFailure::Bind(std::io::ErrorKind::AddrNotAvailable)),
+            ));
+
+            continue;
+        }
+
+        // Transports start connecting once the future is first polled
+        let socket = match source {
+            Some(source) => transport.connect_from(addr, source, timeout),
+            None => transport.connect(addr, timeout),
+        };
+        dials.push((addr, socket));
+    }
+
+    let events = events.clone();
+    let mut dials = dials.into_iter();
+    let Some((first_addr, first)) = dials.next() else {
+        return future::ok(None).boxed();
+    };
+    let fallback = dials.next();
+
+    async move {
+        events.report(AttemptEvent::new(first_addr, hash, AttemptStage::Dialing));
+        let first = report_connect(first_addr, hash, first, events.clone());
+
+        let Some((second_addr, second)) = fallback else {
+            return Ok(first.await.map(|sock| winning_handshake(sock, item, first_addr)));
+        };
+
+        let first = match future::select(first, Box::pin(tokio::time::sleep(fallback_delay))).await {
+            Either::Left((Some(sock), _)) => return Ok(Some(winning_handshake(sock, item, first_addr))),
+            Either::Left((None, _)) => None,
+            Either::Right(((), first)) => Some(first),
+        };
+
+        events.report(AttemptEvent::new(second_addr, hash, AttemptStage::Dialing));
+        let second = report_connect(second_addr, hash, second, events.clone());
+
+        let Some(first) = first else {
+            return Ok(second.await.map(|sock| winning_handshake(sock, item, second_addr)));
+        };
+
+        let (winner, loser_addr) = match future::select(first, second).await {
+            Either::Left((Some(sock), _)) => ((sock, first_addr), second_addr),
+            Either::Right((Some(sock), _)) => ((sock, second_addr), first_addr),
+            Either::Left((None, second)) => {
+                return Ok(second.await.map(|sock| winning_handshake(sock, item, second_addr)));
+            }
+            Either::Right((None, first)) => {
+                return Ok(first.await.map(|sock| winning_handshake(sock, item, first_addr)));
+            }
+        };
+
+        // Dropping the attempt that lost the race cancels it
+        events.report(AttemptEvent::new(
+            loser_addr,
+            hash,
+            AttemptStage::Failed(AttemptFailure::Cancelled),
+        ));
+
+        Ok(Some(winning_handshake(winner.0, item, winner.1)))
     }
+    .boxed()
+}
+
+/// Connect with the socket future, reporting whether the connection was established.
+fn report_connect<S, F>(
+    addr: SocketAddr,
+    hash: util::bt::InfoHash,
+    socket: F,
+    events: AttemptEvents,
+) -> BoxFuture<'static, Option<S>>
+where
+    S: Send + 'static,
+    F: futures::Future<Output = std::io::Result<S>> + Send + 'static,
+{
+    socket
+        .map(move |result| match result {
+            Ok(sock) => {
+                events.report(AttemptEvent::new(addr, hash, AttemptStage::Connected));
+
+                Some(sock)
+            }
+            Err(err) => {
+                tracing::debug!("connection to {addr} failed: {err}");
+                events.report(AttemptEvent::new(
+                    addr,
+                    hash,
+                    AttemptStage::Failed(AttemptFailure::from_connect_error(&err)),
+                ));
+
+                None
+            }
+        })
+        .boxed()
+}
+
+/// Handshake to initiate over the winning connection, which was made to the given address.
+fn winning_handshake<S>(sock: S, item: InitiateMessage, addr: SocketAddr) -> HandshakeType<S> {
+    let (prot, hash, _) = item.into_parts();
+
+    HandshakeType::Initiate(sock, InitiateMessage::new(prot, hash, addr))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::net::{IpAddr, SocketAddr};
     use std::time::Duration;
 
+    use futures::future::{self, FutureExt as _};
     use util::bt::{self, InfoHash, PeerId};
 
+    use crate::attempt::{AttemptEvents, AttemptFailure, AttemptStage};
     use crate::filter::filters::test_filters::{BlockAddrFilter, BlockPeerIdFilter, BlockProtocolFilter};
     use crate::filter::filters::Filters;
     use crate::handshake::handler::HandshakeType;
     use crate::message::initiate::InitiateMessage;
     use crate::message::protocol::Protocol;
+    use crate::source_addr::SourceAddrs;
     use crate::transport::test_transports::MockTransport;
+    use crate::transport::Transport;
+
+    /// Transport connecting to IPv6 addresses after the given delay, or refusing them, and to IPv4 addresses at once.
+    struct DualStackTransport {
+        v6_delay: Option<Duration>,
+    }
+
+    impl Transport for DualStackTransport {
+        type Socket = <MockTransport as Transport>::Socket;
+        type FutureSocket = <MockTransport as Transport>::FutureSocket;
+        type Listener = <MockTransport as Transport>::Listener;
+        type FutureListener = <MockTransport as Transport>::FutureListener;
+
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureSocket {
+            let socket = MockTransport.connect(addr, timeout);
+
+            match (addr.is_ipv6(), self.v6_delay) {
+                (false, _) => socket,
+                (true, Some(delay)) => tokio::time::sleep(delay).then(|()| socket).boxed(),
+                (true, None) => future::err(std::io::ErrorKind::ConnectionRefused.into()).boxed(),
+            }
+        }
+
+        fn connect_from(&self, addr: SocketAddr, _source: IpAddr, timeout: Duration) -> Self::FutureSocket {
+            self.connect(addr, timeout)
+        }
+
+        fn listen(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureListener {
+            MockTransport.listen(addr, timeout)
+        }
+    }
+
+    /// Dial a dual stack peer, returning the address connected to along with the reported events.
+    async fn dial_dual_stack(v6_delay: Option<Duration>) -> (Option<SocketAddr>, Vec<(SocketAddr, AttemptStage)>) {
+        let message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap())
+            .with_alternate_address("[::1]:5".parse().unwrap());
+
+        let events = AttemptEvents::new();
+        let mut recv_events = events.subscribe();
+
+        let recv_enum_item = super::initiator_handler(
+            message,
+            &(
+                DualStackTransport { v6_delay },
+                Filters::new(),
+                SourceAddrs::default(),
+                events,
+                Duration::from_millis(1000),
+                Duration::from_millis(50),
+            ),
+        )
+        .await
+        .unwrap();
+        let addr = match recv_enum_item {
+            Some(HandshakeType::Initiate(_, msg)) => {
+                assert!(msg.alternate_address().is_none());
+
+                Some(*msg.address())
+            }
+            Some(HandshakeType::Complete(_, _)) => panic!("Expected HandshakeType::Initiate"),
+            None => None,
+        };
+
+        let mut stages = Vec::new();
+        while let Ok(event) = recv_events.try_recv() {
+            stages.push((*event.address(), event.stage()));
+        }
+
+        (addr, stages)
+    }
 
     fn any_peer_id() -> PeerId {
         [22u8; bt::PEER_ID_LEN].into()
@@ -66,9 +262,19 @@ mod tests {
     async fn positive_empty_filter() {
         let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap());
 
+        let events = AttemptEvents::new();
+        let mut recv_events = events.subscribe();
+
         let recv_enum_item = super::initiator_handler(
             exp_message.clone(),
-            &(MockTransport, Filters::new(), Duration::from_millis(1000)),
+            &(
+                MockTransport,
+                Filters::new(),
+                SourceAddrs::default(),
+                events,
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
         )
         .await
         .unwrap();
@@ -78,6 +284,8 @@ mod tests {
         };
 
         assert_eq!(exp_message, recv_item);
+        assert_eq!(AttemptStage::Dialing, recv_events.try_recv().unwrap().stage());
+        assert_eq!(AttemptStage::Connected, recv_events.try_recv().unwrap().stage());
     }
 
     #[tokio::test]
@@ -87,10 +295,19 @@ mod tests {
 
         let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap());
 
-        let recv_enum_item =
-            super::initiator_handler(exp_message.clone(), &(MockTransport, filters, Duration::from_millis(1000)))
-                .await
-                .unwrap();
+        let recv_enum_item = super::initiator_handler(
+            exp_message.clone(),
+            &(
+                MockTransport,
+                filters,
+                SourceAddrs::default(),
+                AttemptEvents::new(),
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
         let recv_item = match recv_enum_item {
             Some(HandshakeType::Initiate(_, msg)) => msg,
             Some(HandshakeType::Complete(_, _)) | None => panic!("Expected HandshakeType::Initiate"),
@@ -106,10 +323,19 @@ mod tests {
 
         let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap());
 
-        let recv_enum_item =
-            super::initiator_handler(exp_message.clone(), &(MockTransport, filters, Duration::from_millis(1000)))
-                .await
-                .unwrap();
+        let recv_enum_item = super::initiator_handler(
+            exp_message.clone(),
+            &(
+                MockTransport,
+                filters,
+                SourceAddrs::default(),
+                AttemptEvents::new(),
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
         let recv_item = match recv_enum_item {
             Some(HandshakeType::Initiate(_, msg)) => msg,
             Some(HandshakeType::Complete(_, _)) | None => panic!("Expected HandshakeType::Initiate"),
@@ -129,13 +355,164 @@ mod tests {
             "1.2.3.4:5".parse().unwrap(),
         );
 
-        let recv_enum_item =
-            super::initiator_handler(exp_message.clone(), &(MockTransport, filters, Duration::from_millis(1000)))
-                .await
-                .unwrap();
+        let events = AttemptEvents::new();
+        let mut recv_events = events.subscribe();
+
+        let recv_enum_item = super::initiator_handler(
+            exp_message.clone(),
+            &(
+                MockTransport,
+                filters,
+                SourceAddrs::default(),
+                events,
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
         match recv_enum_item {
             None => (),
             Some(HandshakeType::Initiate(_, _) | HandshakeType::Complete(_, _)) => panic!("Expected No Handshake"),
         }
+
+        let event = recv_events.try_recv().unwrap();
+        assert_eq!(AttemptStage::Failed(AttemptFailure::Filtered), event.stage());
+        assert_eq!(exp_message.address(), event.address());
+    }
+
+    #[tokio::test]
+    async fn positive_binds_torrent_source_addr() {
+        let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap());
+
+        let source_addrs = SourceAddrs::new(Some("10.0.0.1".parse().unwrap()));
+        source_addrs.set_torrent(any_info_hash(), Some("127.0.0.1".parse().unwrap()));
+
+        let recv_enum_item = super::initiator_handler(
+            exp_message.clone(),
+            &(
+                MockTransport,
+                Filters::new(),
+                source_addrs,
+                AttemptEvents::new(),
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
+        let recv_item = match recv_enum_item {
+            Some(HandshakeType::Initiate(_, msg)) => msg,
+            Some(HandshakeType::Complete(_, _)) | None => panic!("Expected HandshakeType::Initiate"),
+        };
+
+        assert_eq!(exp_message, recv_item);
+    }
+
+    #[tokio::test]
+    async fn negative_fails_to_bind_source_addr() {
+        let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "1.2.3.4:5".parse().unwrap());
+
+        let events = AttemptEvents::new();
+        let mut recv_events = events.subscribe();
+
+        let recv_enum_item = super::initiator_handler(
+            exp_message,
+            &(
+                MockTransport,
+                Filters::new(),
+                SourceAddrs::new(Some("10.0.0.1".parse().unwrap())),
+                events,
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
+        assert!(recv_enum_item.is_none());
+
+        assert_eq!(AttemptStage::Dialing, recv_events.try_recv().unwrap().stage());
+        assert_eq!(
+            AttemptStage::Failed(AttemptFailure::Bind(std::io::ErrorKind::AddrNotAvailable)),
+            recv_events.try_recv().unwrap().stage()
+        );
+    }
+
+    #[tokio::test]
+    async fn negative_source_addr_wrong_family() {
+        let exp_message = InitiateMessage::new(Protocol::BitTorrent, any_info_hash(), "[::1]:5".parse().unwrap());
+
+        let events = AttemptEvents::new();
+        let mut recv_events = events.subscribe();
+
+        let recv_enum_item = super::initiator_handler(
+            exp_message,
+            &(
+                MockTransport,
+                Filters::new(),
+                SourceAddrs::new(Some("127.0.0.1".parse().unwrap())),
+                events,
+                Duration::from_millis(1000),
+                Duration::from_millis(250),
+            ),
+        )
+        .await
+        .unwrap();
+        assert!(recv_enum_item.is_none());
+
+        assert_eq!(
+            AttemptStage::Failed(AttemptFailure::Bind(std::io::ErrorKind::AddrNotAvailable)),
+            recv_events.try_recv().unwrap().stage()
+        );
+    }
+
+    #[tokio::test]
+    async fn positive_dual_stack_prefers_ipv6() {
+        let v6_addr: SocketAddr = "[::1]:5".parse().unwrap();
+
+        let (addr, stages) = dial_dual_stack(Some(Duration::ZERO)).await;
+
+        assert_eq!(Some(v6_addr), addr);
+        assert_eq!(
+            vec![(v6_addr, AttemptStage::Dialing), (v6_addr, AttemptStage::Connected)],
+            stages
+        );
+    }
+
+    #[tokio::test]
+    async fn positive_dual_stack_falls_back_to_ipv4() {
+        let (v4_addr, v6_addr): (SocketAddr, SocketAddr) = ("1.2.3.4:5".parse().unwrap(), "[::1]:5".parse().unwrap());
+
+        let (addr, stages) = dial_dual_stack(Some(Duration::from_secs(5))).await;
+
+        // The slow IPv6 attempt loses the race, and is cancelled
+        assert_eq!(Some(v4_addr), addr);
+        assert_eq!(
+            vec![
+                (v6_addr, AttemptStage::Dialing),
+                (v4_addr, AttemptStage::Dialing),
+                (v4_addr, AttemptStage::Connected),
+                (v6_addr, AttemptStage::Failed(AttemptFailure::Cancelled)),
+            ],
+            stages
+        );
+    }
+
+    #[tokio::test]
+    async fn positive_dual_stack_ipv6_refused() {
+        let (v4_addr, v6_addr): (SocketAddr, SocketAddr) = ("1.2.3.4:5".parse().unwrap(), "[::1]:5".parse().unwrap());
+
+        let (addr, stages) = dial_dual_stack(None).await;
+
+        assert_eq!(Some(v4_addr), addr);
+        assert_eq!(
+            vec![
+                (v6_addr, AttemptStage::Dialing),
+                (v6_addr, AttemptStage::Failed(AttemptFailure::Refused)),
+                (v4_addr, AttemptStage::Dialing),
+                (v4_addr, AttemptStage::Connected),
+            ],
+            stages
+        );
     }
 }
@@ -11,6 +11,7 @@ use crate::message::extensions::Extensions;
 use crate::message::initiate::InitiateMessage;
 use crate::message::protocol::Protocol;
 
+pub mod dedup;
 pub mod handshaker;
 pub mod initiator;
 pub mod listener;
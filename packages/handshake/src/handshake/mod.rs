@@ -1,19 +1,23 @@
+use std::net::IpAddr;
 use std::task::{Context, Poll};
 
 use builder::HandshakerBuilder;
 use futures::channel::mpsc;
+use futures::stream::{select_with_strategy, PollNext};
 use futures::{Sink, SinkExt as _, Stream, StreamExt as _};
 use handler::listener::ListenerHandler;
-use handler::{handshaker, initiator};
+use handler::{dedup, handshaker, initiator};
 use sink::HandshakerSink;
 use stream::HandshakerStream;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::task::JoinSet;
-use util::bt::PeerId;
+use util::bt::{InfoHash, PeerId};
 
+use crate::attempt::AttemptEvents;
 use crate::filter::filters::Filters;
 use crate::local_addr::LocalAddr as _;
-use crate::{CompleteMessage, DiscoveryInfo, HandshakeFilter, HandshakeFilters, InitiateMessage, Transport};
+use crate::source_addr::SourceAddrs;
+use crate::{AttemptEvent, CompleteMessage, DiscoveryInfo, HandshakeFilter, HandshakeFilters, InitiateMessage, Transport};
 
 pub mod builder;
 pub mod config;
@@ -38,6 +42,27 @@ impl<S> Handshaker<S> {
     }
 }
 
+impl<S> Handshaker<S> {
+    /// A Receiver which will receive events for every outgoing connection attempt.
+    ///
+    /// Events are dropped for this receiver if it falls behind, so that reporting never stalls the `Handshaker`.
+    #[must_use]
+    pub fn attempt_events(&self) -> mpsc::Receiver<AttemptEvent> {
+        self.sink.attempt_events()
+    }
+
+    /// Set the local address that outgoing connections are bound to, or `None` to use the default route.
+    pub fn set_source_addr(&self, addr: Option<IpAddr>) {
+        self.sink.set_source_addr(addr);
+    }
+
+    /// Set the local address that outgoing connections for the given torrent are bound to,
+    /// or `None` to fall back to the address set for all torrents.
+    pub fn set_torrent_source_addr(&self, hash: InfoHash, addr: Option<IpAddr>) {
+        self.sink.set_torrent_source_addr(hash, addr);
+    }
+}
+
 impl<S> DiscoveryInfo for Handshaker<S> {
     fn port(&self) -> u16 {
         self.sink.port()
@@ -90,20 +115,34 @@ where
         };
 
         let (addr_send, addr_recv) = mpsc::channel(config.sink_buffer_size());
+        let (priority_send, priority_recv) = mpsc::channel(config.sink_buffer_size());
         let (hand_send, hand_recv) = mpsc::channel(config.wait_buffer_size());
+        let (dedup_send, dedup_recv) = mpsc::channel(config.done_buffer_size());
         let (sock_send, sock_recv) = mpsc::channel(config.done_buffer_size());
 
         let filters = Filters::new();
+        let source_addrs = SourceAddrs::new(config.source_addr());
+        let events = AttemptEvents::new();
 
         // Hook up our pipeline of handlers which will take some connection info, process it, and forward it
 
         let mut tasks = JoinSet::new();
 
+        // Prioritized initiations are always connected to first
+        let initiate_recv = select_with_strategy(priority_recv, addr_recv, |(): &mut ()| PollNext::Left);
+
         tasks.spawn(handler::loop_handler(
-            addr_recv,
+            initiate_recv,
             initiator::initiator_handler,
             hand_send.clone(),
-            Box::pin((transport, filters.clone(), timeout)),
+            Box::pin((
+                transport,
+                filters.clone(),
+                source_addrs.clone(),
+                events.clone(),
+                timeout,
+                config.fallback_delay(),
+            )),
         ));
 
         tasks.spawn(handler::loop_handler(
@@ -116,11 +155,34 @@ where
         tasks.spawn(handler::loop_handler(
             hand_recv,
             handshaker::execute_handshake,
+            dedup_send,
+            Box::pin((
+                builder.ext,
+                builder.pid,
+                filters.clone(),
+                builder.policy.clone(),
+                builder.psk.clone(),
+                events.clone(),
+                timeout,
+            )),
+        ));
+
+        tasks.spawn(dedup::dedup_handler(
+            dedup_recv,
             sock_send,
-            Box::pin((builder.ext, builder.pid, filters.clone(), timeout)),
+            builder.pid,
+            config.dedup_window(),
         ));
 
-        let sink = HandshakerSink::new(addr_send, open_port, builder.pid, filters);
+        let sink = HandshakerSink::new(
+            addr_send,
+            priority_send,
+            open_port,
+            builder.pid,
+            filters,
+            source_addrs,
+            events,
+        );
         let stream = HandshakerStream::new(sock_recv);
 
         Ok((Handshaker { sink, stream }, tasks))
@@ -1,34 +1,84 @@
 //! `Sink` portion of the `Handshaker` for initiating handshakes.
 
+use std::net::IpAddr;
+
 use futures::channel::mpsc;
 use futures::sink::Sink;
 use futures::task::{Context, Poll};
 use futures::SinkExt as _;
-use util::bt::PeerId;
+use util::bt::{InfoHash, PeerId};
 
+use crate::attempt::{AttemptEvent, AttemptEvents};
 use crate::discovery::DiscoveryInfo;
 use crate::filter::filters::Filters;
 use crate::filter::{HandshakeFilter, HandshakeFilters};
 use crate::message::initiate::InitiateMessage;
+use crate::source_addr::SourceAddrs;
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone)]
 pub struct HandshakerSink {
     send: mpsc::Sender<InitiateMessage>,
+    priority_send: mpsc::Sender<InitiateMessage>,
     port: u16,
     pid: PeerId,
     filters: Filters,
+    source_addrs: SourceAddrs,
+    events: AttemptEvents,
 }
 
 impl HandshakerSink {
-    pub(super) fn new(send: mpsc::Sender<InitiateMessage>, port: u16, pid: PeerId, filters: Filters) -> HandshakerSink {
+    pub(super) fn new(
+        send: mpsc::Sender<InitiateMessage>,
+        priority_send: mpsc::Sender<InitiateMessage>,
+        port: u16,
+        pid: PeerId,
+        filters: Filters,
+        source_addrs: SourceAddrs,
+        events: AttemptEvents,
+    ) -> HandshakerSink {
         HandshakerSink {
             send,
+            priority_send,
             port,
             pid,
             filters,
+            source_addrs,
+            events,
+        }
+    }
+
+    /// Create a `HandshakerSink` whose `InitiateMessage`s are connected to before those of any non prioritized sink.
+    ///
+    /// Useful for peers that are cheap to connect to, such as those discovered on the local network.
+    #[must_use]
+    pub fn prioritized(&self) -> HandshakerSink {
+        HandshakerSink {
+            send: self.priority_send.clone(),
+            ..self.clone()
         }
     }
+
+    /// A Receiver which will receive events for every outgoing connection attempt.
+    ///
+    /// Events are dropped for this receiver if it falls behind, so that reporting never stalls the `Handshaker`.
+    #[must_use]
+    pub fn attempt_events(&self) -> mpsc::Receiver<AttemptEvent> {
+        self.events.subscribe()
+    }
+
+    /// Set the local address that outgoing connections are bound to, or `None` to use the default route.
+    ///
+    /// Applies to torrents without an address of their own; see `HandshakerConfig::with_source_addr`.
+    pub fn set_source_addr(&self, addr: Option<IpAddr>) {
+        self.source_addrs.set_global(addr);
+    }
+
+    /// Set the local address that outgoing connections for the given torrent are bound to,
+    /// or `None` to fall back to the address set for all torrents.
+    pub fn set_torrent_source_addr(&self, hash: InfoHash, addr: Option<IpAddr>) {
+        self.source_addrs.set_torrent(hash, addr);
+    }
 }
 
 impl DiscoveryInfo for HandshakerSink {
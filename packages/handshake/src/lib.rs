@@ -1,11 +1,23 @@
+mod attempt;
 mod bittorrent;
 mod discovery;
 mod filter;
 mod handshake;
 mod local_addr;
+mod local_discovery;
 mod message;
+mod peer_addr;
+mod policy;
+mod port_mapping;
+mod psk;
+mod sniffing_transport;
+mod source_addr;
 mod transport;
+#[cfg(unix)]
+mod unix_transport;
 
+pub use crate::attempt::{AttemptEvent, AttemptFailure, AttemptStage};
+pub use crate::bittorrent::message::HandshakeMessage;
 pub use crate::discovery::DiscoveryInfo;
 pub use crate::filter::{FilterDecision, HandshakeFilter, HandshakeFilters};
 pub use crate::handshake::builder::HandshakerBuilder;
@@ -14,15 +26,23 @@ pub use crate::handshake::sink::HandshakerSink;
 pub use crate::handshake::stream::HandshakerStream;
 pub use crate::handshake::Handshaker;
 pub use crate::local_addr::LocalAddr;
+pub use crate::local_discovery::{LocalDiscoveryConfig, LocalServiceDiscovery, LSD_MULTICAST_V4};
 pub use crate::message::complete::CompleteMessage;
 pub use crate::message::extensions::{Extension, Extensions};
 pub use crate::message::initiate::InitiateMessage;
 pub use crate::message::protocol::Protocol;
+pub use crate::peer_addr::PeerAddr;
+pub use crate::policy::{AcceptAll, HandshakePolicy, PolicyDecision, RejectSelf, RemoteHandshake};
+pub use crate::port_mapping::{MappingMethod, MappingProtocol, PortMapping, PortMappingConfig, PortMappingEvent};
+pub use crate::psk::PreSharedKey;
 pub use crate::transport::Transport;
 
 /// Built in objects implementing `Transport`.
 pub mod transports {
-    pub use crate::transport::{TcpListenerStream, TcpTransport};
+    pub use crate::sniffing_transport::{SniffedSocket, SniffingListener, SniffingTransport};
+    pub use crate::transport::{BindError, TcpListenerStream, TcpTransport};
+    #[cfg(unix)]
+    pub use crate::unix_transport::{UnixListenerStream, UnixTransport};
 }
 
 pub use util::bt::{InfoHash, PeerId};
@@ -0,0 +1,390 @@
+//! Local Service Discovery of peers on the same network.
+//!
+//! See [BEP 0014](http://www.bittorrent.org/beps/bep_0014.html).
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket as StdUdpSocket};
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::{SinkExt as _, StreamExt as _};
+use socket2::{Domain, Protocol as SocketProtocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+use util::bt::{self, InfoHash};
+
+use crate::discovery::DiscoveryInfo as _;
+use crate::handshake::sink::HandshakerSink;
+use crate::message::initiate::InitiateMessage;
+use crate::message::protocol::Protocol;
+
+/// Multicast group and port that local service discovery announcements are sent to.
+pub const LSD_MULTICAST_V4: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 192, 152, 143), 6771);
+
+const DEFAULT_ANNOUNCE_INTERVAL_SECS: u64 = 5 * 60;
+const MAX_INFO_HASHES_PER_ANNOUNCE: usize = 20;
+const MAX_ANNOUNCE_LEN: usize = 1400;
+const COMMAND_CHANNEL_CAPACITY: usize = 16;
+
+const ANNOUNCE_REQUEST_LINE: &str = "BT-SEARCH * HTTP/1.1";
+const HOST_HEADER: &str = "Host";
+const PORT_HEADER: &str = "Port";
+const INFO_HASH_HEADER: &str = "Infohash";
+const COOKIE_HEADER: &str = "cookie";
+
+/// Configures a `LocalServiceDiscovery`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct LocalDiscoveryConfig {
+    bind_addr: SocketAddr,
+    announce_addr: SocketAddr,
+    announce_interval: Duration,
+}
+
+impl LocalDiscoveryConfig {
+    /// Sets the address that announcements from other clients are received on.
+    ///
+    /// Defaults to any interface on the local service discovery port. If the announce
+    /// address is a multicast group, it is joined on all interfaces.
+    #[must_use]
+    pub fn with_bind_addr(mut self, addr: SocketAddr) -> LocalDiscoveryConfig {
+        self.bind_addr = addr;
+        self
+    }
+
+    /// Sets the address that our announcements are sent to.
+    ///
+    /// Defaults to the local service discovery multicast group.
+    #[must_use]
+    pub fn with_announce_addr(mut self, addr: SocketAddr) -> LocalDiscoveryConfig {
+        self.announce_addr = addr;
+        self
+    }
+
+    /// Sets how often all enabled `InfoHash`(s) are announced.
+    ///
+    /// An `InfoHash` is also announced as soon as it is enabled.
+    #[must_use]
+    pub fn with_announce_interval(mut self, interval: Duration) -> LocalDiscoveryConfig {
+        self.announce_interval = interval;
+        self
+    }
+
+    /// Gets the address that announcements are received on.
+    #[must_use]
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.bind_addr
+    }
+
+    /// Gets the address that our announcements are sent to.
+    #[must_use]
+    pub fn announce_addr(&self) -> SocketAddr {
+        self.announce_addr
+    }
+
+    /// Gets how often all enabled `InfoHash`(s) are announced.
+    #[must_use]
+    pub fn announce_interval(&self) -> Duration {
+        self.announce_interval
+    }
+}
+
+impl Default for LocalDiscoveryConfig {
+    fn default() -> LocalDiscoveryConfig {
+        LocalDiscoveryConfig {
+            bind_addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, LSD_MULTICAST_V4.port())),
+            announce_addr: SocketAddr::V4(LSD_MULTICAST_V4),
+            announce_interval: Duration::from_secs(DEFAULT_ANNOUNCE_INTERVAL_SECS),
+        }
+    }
+}
+
+enum Command {
+    Enable(InfoHash),
+    Disable(InfoHash),
+}
+
+/// Announces enabled `InfoHash`(s) on the local network, and listens for the announcements of other clients.
+///
+/// Peers announcing an enabled `InfoHash` are sent to the prioritized `HandshakerSink`, so
+/// that they are connected to before peers discovered through other means.
+///
+/// Discovery stops when this is dropped.
+#[allow(clippy::module_name_repetitions)]
+pub struct LocalServiceDiscovery {
+    send: mpsc::Sender<Command>,
+    local_addr: SocketAddr,
+    _tasks: JoinSet<()>,
+}
+
+impl LocalServiceDiscovery {
+    /// Start local service discovery, advertising the port of the given `HandshakerSink`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO Error if unable to bind to the address, or join the multicast group.
+    pub fn run(handshaker: &HandshakerSink, config: LocalDiscoveryConfig) -> std::io::Result<LocalServiceDiscovery> {
+        let socket = bind_socket(config)?;
+        let local_addr = socket.local_addr()?;
+
+        let (send, recv) = mpsc::channel(COMMAND_CHANNEL_CAPACITY);
+
+        let mut tasks = JoinSet::new();
+        tasks.spawn(run_discovery(socket, handshaker.prioritized(), config, recv));
+
+        Ok(LocalServiceDiscovery {
+            send,
+            local_addr,
+            _tasks: tasks,
+        })
+    }
+
+    /// Address that announcements are received on.
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Start announcing the given `InfoHash`, and connecting to local peers announcing it.
+    pub async fn enable(&self, hash: InfoHash) {
+        if self.send.clone().send(Command::Enable(hash)).await.is_err() {
+            tracing::warn!("local service discovery has shut down, unable to enable {hash:?}");
+        }
+    }
+
+    /// Stop announcing the given `InfoHash`, and ignore local peers announcing it.
+    pub async fn disable(&self, hash: InfoHash) {
+        if self.send.clone().send(Command::Disable(hash)).await.is_err() {
+            tracing::warn!("local service discovery has shut down, unable to disable {hash:?}");
+        }
+    }
+}
+
+fn bind_socket(config: LocalDiscoveryConfig) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(config.bind_addr), Type::DGRAM, Some(SocketProtocol::UDP))?;
+
+    // Other clients on this host will be listening on the same port
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&config.bind_addr.into())?;
+
+    if let SocketAddr::V4(announce_addr) = config.announce_addr {
+        if announce_addr.ip().is_multicast() {
+            socket.join_multicast_v4(announce_addr.ip(), &Ipv4Addr::UNSPECIFIED)?;
+        }
+    }
+
+    UdpSocket::from_std(StdUdpSocket::from(socket))
+}
+
+async fn run_discovery(
+    socket: UdpSocket,
+    mut handshaker: HandshakerSink,
+    config: LocalDiscoveryConfig,
+    mut recv: mpsc::Receiver<Command>,
+) {
+    let cookie = format!("{:08x}", rand::random::<u32>());
+    let mut enabled = HashSet::new();
+
+    let mut announce_timer = tokio::time::interval(config.announce_interval());
+    let mut buffer = vec![0u8; MAX_ANNOUNCE_LEN];
+
+    loop {
+        tokio::select! {
+            command = recv.next() => match command {
+                Some(Command::Enable(hash)) => {
+                    if enabled.insert(hash) {
+                        announce(&socket, &handshaker, config, &cookie, &[hash]).await;
+                    }
+                }
+                Some(Command::Disable(hash)) => {
+                    enabled.remove(&hash);
+                }
+                None => break,
+            },
+            _ = announce_timer.tick() => {
+                let hashes: Vec<InfoHash> = enabled.iter().copied().collect();
+
+                for chunk in hashes.chunks(MAX_INFO_HASHES_PER_ANNOUNCE) {
+                    announce(&socket, &handshaker, config, &cookie, chunk).await;
+                }
+            }
+            result = socket.recv_from(&mut buffer) => match result {
+                Ok((len, addr)) => {
+                    let Some(announcement) = Announcement::from_bytes(&buffer[..len]) else {
+                        tracing::debug!(%addr, "received an invalid local service discovery announcement");
+                        continue;
+                    };
+
+                    if announcement.cookie.as_deref() == Some(cookie.as_str()) {
+                        continue;
+                    }
+
+                    let peer_addr = SocketAddr::new(addr.ip(), announcement.port);
+
+                    for hash in announcement.hashes.into_iter().filter(|hash| enabled.contains(hash)) {
+                        tracing::debug!(%peer_addr, ?hash, "discovered local peer");
+
+                        if handshaker.send(InitiateMessage::new(Protocol::BitTorrent, hash, peer_addr)).await.is_err() {
+                            tracing::info!("handshaker has shut down, stopping local service discovery");
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(%e, "failed to receive a local service discovery announcement");
+                }
+            },
+        }
+    }
+}
+
+async fn announce(
+    socket: &UdpSocket,
+    handshaker: &HandshakerSink,
+    config: LocalDiscoveryConfig,
+    cookie: &str,
+    hashes: &[InfoHash],
+) {
+    if hashes.is_empty() {
+        return;
+    }
+
+    let announcement = Announcement {
+        port: handshaker.port(),
+        hashes: hashes.to_vec(),
+        cookie: Some(cookie.to_owned()),
+    };
+
+    if let Err(e) = socket
+        .send_to(&announcement.to_bytes(config.announce_addr()), config.announce_addr())
+        .await
+    {
+        tracing::warn!(%e, "failed to send a local service discovery announcement");
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Local service discovery announcement of the torrents a client is participating in.
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Announcement {
+    port: u16,
+    hashes: Vec<InfoHash>,
+    cookie: Option<String>,
+}
+
+impl Announcement {
+    fn to_bytes(&self, host: SocketAddr) -> Vec<u8> {
+        let mut message = format!(
+            "{ANNOUNCE_REQUEST_LINE}\r\n{HOST_HEADER}: {host}\r\n{PORT_HEADER}: {}\r\n",
+            self.port
+        );
+
+        for hash in &self.hashes {
+            let _ = write!(message, "{INFO_HASH_HEADER}: ");
+            for byte in hash.as_ref() {
+                let _ = write!(message, "{byte:02x}");
+            }
+            message.push_str("\r\n");
+        }
+
+        if let Some(cookie) = &self.cookie {
+            let _ = write!(message, "{COOKIE_HEADER}: {cookie}\r\n");
+        }
+
+        message.push_str("\r\n\r\n");
+
+        message.into_bytes()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Announcement> {
+        let message = std::str::from_utf8(bytes).ok()?;
+        let mut lines = message.split("\r\n");
+
+        if lines.next()? != ANNOUNCE_REQUEST_LINE {
+            return None;
+        }
+
+        let mut port = None;
+        let mut hashes = Vec::new();
+        let mut cookie = None;
+
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':')?;
+            let value = value.trim();
+
+            if name.eq_ignore_ascii_case(PORT_HEADER) {
+                port = Some(value.parse().ok()?);
+            } else if name.eq_ignore_ascii_case(INFO_HASH_HEADER) {
+                hashes.push(InfoHash::from_hash(&unhex(value)?).ok()?);
+            } else if name.eq_ignore_ascii_case(COOKIE_HEADER) {
+                cookie = Some(value.to_owned());
+            }
+        }
+
+        Some(Announcement {
+            port: port?,
+            hashes,
+            cookie,
+        })
+    }
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != bt::INFO_HASH_LEN * 2 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use util::bt::{self, InfoHash};
+
+    use super::{Announcement, LSD_MULTICAST_V4};
+
+    #[test]
+    fn positive_announcement_round_trip() {
+        let announcement = Announcement {
+            port: 6881,
+            hashes: vec![[1u8; bt::INFO_HASH_LEN].into(), [0xABu8; bt::INFO_HASH_LEN].into()],
+            cookie: Some("cafebabe".to_owned()),
+        };
+
+        let bytes = announcement.to_bytes(LSD_MULTICAST_V4.into());
+
+        assert_eq!(Some(announcement), Announcement::from_bytes(&bytes));
+    }
+
+    #[test]
+    fn positive_parse_announcement_any_header_case() {
+        let bytes = b"BT-SEARCH * HTTP/1.1\r\nHost: 239.192.152.143:6771\r\nport: 51413\r\n\
+                      INFOHASH: 0101010101010101010101010101010101010101\r\n\r\n\r\n";
+
+        let announcement = Announcement::from_bytes(bytes).unwrap();
+
+        assert_eq!(announcement.port, 51413);
+        assert_eq!(announcement.hashes, vec![InfoHash::from([1u8; bt::INFO_HASH_LEN])]);
+        assert_eq!(announcement.cookie, None);
+    }
+
+    #[test]
+    fn negative_parse_announcement_missing_port() {
+        let bytes = b"BT-SEARCH * HTTP/1.1\r\nInfohash: 0101010101010101010101010101010101010101\r\n\r\n\r\n";
+
+        assert_eq!(None, Announcement::from_bytes(bytes));
+    }
+
+    #[test]
+    fn negative_parse_announcement_invalid_info_hash() {
+        let bytes = b"BT-SEARCH * HTTP/1.1\r\nPort: 6881\r\nInfohash: 0101\r\n\r\n\r\n";
+
+        assert_eq!(None, Announcement::from_bytes(bytes));
+    }
+}
@@ -2,7 +2,7 @@ use std::net::SocketAddr;
 
 use util::bt::{InfoHash, PeerId};
 
-use crate::message::extensions::Extensions;
+use crate::message::extensions::{Extension, Extensions};
 use crate::message::protocol::Protocol;
 
 /// Message containing completed handshaking information.
@@ -11,6 +11,7 @@ use crate::message::protocol::Protocol;
 pub struct CompleteMessage<S> {
     prot: Protocol,
     ext: Extensions,
+    remote_ext: Extensions,
     hash: InfoHash,
     pid: PeerId,
     addr: SocketAddr,
@@ -19,10 +20,13 @@ pub struct CompleteMessage<S> {
 
 impl<S> CompleteMessage<S> {
     /// Create a new `CompleteMessage` over the given socket S.
+    ///
+    /// The remote `Extensions` are assumed to be equal to the given `Extensions`, see `with_remote_extensions`.
     pub fn new(prot: Protocol, ext: Extensions, hash: InfoHash, pid: PeerId, addr: SocketAddr, sock: S) -> CompleteMessage<S> {
         CompleteMessage {
             prot,
             ext,
+            remote_ext: ext,
             hash,
             pid,
             addr,
@@ -30,6 +34,13 @@ impl<S> CompleteMessage<S> {
         }
     }
 
+    /// Set the `Extensions` that the peer advertised in its handshake.
+    #[must_use]
+    pub fn with_remote_extensions(mut self, remote_ext: Extensions) -> CompleteMessage<S> {
+        self.remote_ext = remote_ext;
+        self
+    }
+
     /// Protocol that this peer is operating over.
     pub fn protocol(&self) -> &Protocol {
         &self.prot
@@ -40,6 +51,31 @@ impl<S> CompleteMessage<S> {
         &self.ext
     }
 
+    /// Extensions that the peer advertised, regardless of whether we support them.
+    pub fn remote_extensions(&self) -> &Extensions {
+        &self.remote_ext
+    }
+
+    /// Reserved bytes that the peer sent in its handshake.
+    pub fn remote_reserved(&self) -> &[u8] {
+        self.remote_ext.as_bytes()
+    }
+
+    /// Whether both you and the peer support the extension protocol.
+    pub fn supports_extension_protocol(&self) -> bool {
+        self.ext.contains(Extension::ExtensionProtocol)
+    }
+
+    /// Whether both you and the peer support the dht protocol.
+    pub fn supports_dht(&self) -> bool {
+        self.ext.contains(Extension::Dht)
+    }
+
+    /// Whether both you and the peer support the fast extension.
+    pub fn supports_fast(&self) -> bool {
+        self.ext.contains(Extension::Fast)
+    }
+
     /// Hash that the peer is interested in.
     pub fn hash(&self) -> &InfoHash {
         &self.hash
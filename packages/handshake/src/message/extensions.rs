@@ -6,9 +6,14 @@ use tokio::io::{AsyncWrite, AsyncWriteExt as _};
 pub const NUM_EXTENSION_BYTES: usize = 8;
 
 /// Enumeration of all extensions that can be activated.
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
 pub enum Extension {
     /// Support for the extension protocol `http://www.bittorrent.org/beps/bep_0010.html`.
     ExtensionProtocol = 43,
+    /// Support for the fast extension `http://www.bittorrent.org/beps/bep_0006.html`.
+    Fast = 61,
+    /// Support for the dht protocol `http://www.bittorrent.org/beps/bep_0005.html`.
+    Dht = 63,
 }
 
 /// `Extensions` supported by either end of a handshake.
@@ -69,6 +74,12 @@ impl Extensions {
         self.bytes[byte_index] & (0x80 >> bit_index) != 0
     }
 
+    /// Raw reserved bytes that make up the `Extensions`.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; NUM_EXTENSION_BYTES] {
+        &self.bytes
+    }
+
     /// Write the `Extensions` to the given async writer.
     ///
     /// # Errors
@@ -153,4 +164,16 @@ mod tests {
         assert_eq!(expected_extensions, extensions);
         assert!(!extensions.contains(Extension::ExtensionProtocol));
     }
+
+    #[test]
+    fn positive_add_dht_and_fast() {
+        let mut extensions = Extensions::new();
+        extensions.add(Extension::Dht);
+        extensions.add(Extension::Fast);
+
+        assert_eq!(&[0, 0, 0, 0, 0, 0, 0, 0x05], extensions.as_bytes());
+        assert!(extensions.contains(Extension::Dht));
+        assert!(extensions.contains(Extension::Fast));
+        assert!(!extensions.contains(Extension::ExtensionProtocol));
+    }
 }
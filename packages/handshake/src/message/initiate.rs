@@ -11,13 +11,29 @@ pub struct InitiateMessage {
     prot: Protocol,
     hash: InfoHash,
     addr: SocketAddr,
+    alt_addr: Option<SocketAddr>,
 }
 
 impl InitiateMessage {
     /// Create a new `InitiateMessage`.
     #[must_use]
     pub fn new(prot: Protocol, hash: InfoHash, addr: SocketAddr) -> InitiateMessage {
-        InitiateMessage { prot, hash, addr }
+        InitiateMessage {
+            prot,
+            hash,
+            addr,
+            alt_addr: None,
+        }
+    }
+
+    /// Set another address that the peer advertised, such as its IPv6 address alongside an IPv4 one.
+    ///
+    /// If the two addresses are of different families, both are dialed in happy eyeballs
+    /// style, and only the connection that is established first is kept.
+    #[must_use]
+    pub fn with_alternate_address(mut self, addr: SocketAddr) -> InitiateMessage {
+        self.alt_addr = Some(addr);
+        self
     }
 
     /// Protocol that we want to connect to the peer with.
@@ -38,6 +54,12 @@ impl InitiateMessage {
         &self.addr
     }
 
+    /// Other address that we may connect to for the peer.
+    #[must_use]
+    pub fn alternate_address(&self) -> Option<&SocketAddr> {
+        self.alt_addr.as_ref()
+    }
+
     /// Break the `InitiateMessage` up into its parts.
     #[must_use]
     pub fn into_parts(self) -> (Protocol, InfoHash, SocketAddr) {
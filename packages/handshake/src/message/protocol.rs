@@ -24,6 +24,15 @@ impl Protocol {
         parse_protocol(bytes)
     }
 
+    /// Create a `Protocol` from the raw protocol bytes, not including the length byte.
+    pub(crate) fn from_raw_bytes(raw_protocol: &[u8]) -> Protocol {
+        if raw_protocol == BT_PROTOCOL {
+            Protocol::BitTorrent
+        } else {
+            Protocol::Custom(raw_protocol.to_vec())
+        }
+    }
+
     /// Write the `Protocol` out to the given writer.
     ///
     /// # Errors
@@ -82,11 +91,8 @@ fn parse_protocol(bytes: &[u8]) -> IResult<&[u8], Protocol> {
 
 fn parse_real_protocol(bytes: &[u8]) -> IResult<&[u8], Protocol> {
     let (remaining, (_length, raw_protocol)) = tuple((u8, take(bytes[0] as usize)))(bytes)?;
-    if raw_protocol == BT_PROTOCOL {
-        Ok((remaining, Protocol::BitTorrent))
-    } else {
-        Ok((remaining, Protocol::Custom(raw_protocol.to_vec())))
-    }
+
+    Ok((remaining, Protocol::from_raw_bytes(raw_protocol)))
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,48 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Address of a peer endpoint, which may not be reachable over IP.
+///
+/// Connections are identified by a `SocketAddr` throughout the handshaker, so a `Transport` for
+/// non-IP endpoints hands out addresses standing in for them, which it can resolve to a `PeerAddr`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PeerAddr {
+    /// Peer reachable over IP.
+    Ip(SocketAddr),
+    /// Peer connected over a Unix domain socket, at the given path if its socket was bound to one.
+    Unix(Option<PathBuf>),
+}
+
+impl PeerAddr {
+    /// Whether the peer is reachable over IP.
+    #[must_use]
+    pub fn is_ip(&self) -> bool {
+        matches!(self, PeerAddr::Ip(_))
+    }
+
+    /// Address of the peer, if reachable over IP.
+    #[must_use]
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            PeerAddr::Ip(addr) => Some(*addr),
+            PeerAddr::Unix(_) => None,
+        }
+    }
+}
+
+impl From<SocketAddr> for PeerAddr {
+    fn from(addr: SocketAddr) -> PeerAddr {
+        PeerAddr::Ip(addr)
+    }
+}
+
+impl std::fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeerAddr::Ip(addr) => write!(f, "{addr}"),
+            PeerAddr::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+            PeerAddr::Unix(None) => write!(f, "unix:(unnamed)"),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::net::SocketAddr;
+
+use util::bt::{InfoHash, PeerId};
+
+use crate::message::extensions::Extensions;
+use crate::message::protocol::Protocol;
+
+/// Parsed handshake received from a peer that connected to us.
+pub struct RemoteHandshake<'a> {
+    addr: &'a SocketAddr,
+    prot: &'a Protocol,
+    ext: &'a Extensions,
+    hash: &'a InfoHash,
+    pid: &'a PeerId,
+    local_pid: &'a PeerId,
+}
+
+impl<'a> RemoteHandshake<'a> {
+    pub(crate) fn new(
+        addr: &'a SocketAddr,
+        prot: &'a Protocol,
+        ext: &'a Extensions,
+        hash: &'a InfoHash,
+        pid: &'a PeerId,
+        local_pid: &'a PeerId,
+    ) -> RemoteHandshake<'a> {
+        RemoteHandshake {
+            addr,
+            prot,
+            ext,
+            hash,
+            pid,
+            local_pid,
+        }
+    }
+
+    /// Address of the remote peer.
+    #[must_use]
+    pub fn address(&self) -> &SocketAddr {
+        self.addr
+    }
+
+    /// Protocol sent by the remote peer.
+    #[must_use]
+    pub fn protocol(&self) -> &Protocol {
+        self.prot
+    }
+
+    /// Extensions sent by the remote peer.
+    #[must_use]
+    pub fn extensions(&self) -> &Extensions {
+        self.ext
+    }
+
+    /// `InfoHash` the remote peer wants to communicate over.
+    #[must_use]
+    pub fn hash(&self) -> &InfoHash {
+        self.hash
+    }
+
+    /// `PeerId` claimed by the remote peer.
+    #[must_use]
+    pub fn peer_id(&self) -> &PeerId {
+        self.pid
+    }
+
+    /// Our own `PeerId`, as advertised by the `Handshaker`.
+    #[must_use]
+    pub fn local_peer_id(&self) -> &PeerId {
+        self.local_pid
+    }
+}
+
+//----------------------------------------------------------------------------------//
+
+/// Decision made by a `HandshakePolicy` for an incoming handshake.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PolicyDecision {
+    /// Respond to the handshake and surface the connection.
+    Accept,
+    /// Drop the connection without responding.
+    Reject,
+}
+
+/// Trait for custom acceptance logic run on every incoming handshake.
+///
+/// Unlike a `HandshakeFilter`, which decides on each field in isolation, a policy sees the
+/// whole parsed handshake at once, after the filters have passed it. This allows checks such
+/// as verifying the peer id against an allowlist for a given `InfoHash`, or rejecting
+/// connections from ourselves.
+///
+/// Closures taking a `&RemoteHandshake` and returning a `PolicyDecision` implement this trait.
+#[allow(clippy::module_name_repetitions)]
+pub trait HandshakePolicy {
+    /// Make a decision based on the parsed remote handshake.
+    fn on_incoming(&self, handshake: &RemoteHandshake<'_>) -> PolicyDecision;
+}
+
+impl<F> HandshakePolicy for F
+where
+    F: Fn(&RemoteHandshake<'_>) -> PolicyDecision,
+{
+    fn on_incoming(&self, handshake: &RemoteHandshake<'_>) -> PolicyDecision {
+        self(handshake)
+    }
+}
+
+/// Policy accepting every incoming handshake, this is the default policy.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct AcceptAll;
+
+impl HandshakePolicy for AcceptAll {
+    fn on_incoming(&self, _handshake: &RemoteHandshake<'_>) -> PolicyDecision {
+        PolicyDecision::Accept
+    }
+}
+
+/// Policy rejecting incoming handshakes that claim our own `PeerId`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct RejectSelf;
+
+impl HandshakePolicy for RejectSelf {
+    fn on_incoming(&self, handshake: &RemoteHandshake<'_>) -> PolicyDecision {
+        if handshake.peer_id() == handshake.local_peer_id() {
+            PolicyDecision::Reject
+        } else {
+            PolicyDecision::Accept
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use util::bt::{self, InfoHash, PeerId};
+
+    use super::{AcceptAll, HandshakePolicy, PolicyDecision, RejectSelf, RemoteHandshake};
+    use crate::message::extensions::Extensions;
+    use crate::message::protocol::Protocol;
+
+    fn any_addr() -> SocketAddr {
+        "1.2.3.4:5".parse().unwrap()
+    }
+
+    fn any_info_hash() -> InfoHash {
+        [55u8; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_accept_all() {
+        let (addr, prot, ext, hash) = (any_addr(), Protocol::BitTorrent, Extensions::new(), any_info_hash());
+        let pid: PeerId = [22u8; bt::PEER_ID_LEN].into();
+
+        let handshake = RemoteHandshake::new(&addr, &prot, &ext, &hash, &pid, &pid);
+
+        assert_eq!(PolicyDecision::Accept, AcceptAll.on_incoming(&handshake));
+    }
+
+    #[test]
+    fn positive_reject_self() {
+        let (addr, prot, ext, hash) = (any_addr(), Protocol::BitTorrent, Extensions::new(), any_info_hash());
+        let pid: PeerId = [22u8; bt::PEER_ID_LEN].into();
+        let other_pid: PeerId = [33u8; bt::PEER_ID_LEN].into();
+
+        let self_handshake = RemoteHandshake::new(&addr, &prot, &ext, &hash, &pid, &pid);
+        let other_handshake = RemoteHandshake::new(&addr, &prot, &ext, &hash, &other_pid, &pid);
+
+        assert_eq!(PolicyDecision::Reject, RejectSelf.on_incoming(&self_handshake));
+        assert_eq!(PolicyDecision::Accept, RejectSelf.on_incoming(&other_handshake));
+    }
+
+    #[test]
+    fn positive_closure_policy() {
+        let (addr, prot, ext, hash) = (any_addr(), Protocol::BitTorrent, Extensions::new(), any_info_hash());
+        let pid: PeerId = [22u8; bt::PEER_ID_LEN].into();
+        let allowed: PeerId = [33u8; bt::PEER_ID_LEN].into();
+
+        let allowlist = move |handshake: &RemoteHandshake<'_>| {
+            if *handshake.peer_id() == allowed {
+                PolicyDecision::Accept
+            } else {
+                PolicyDecision::Reject
+            }
+        };
+
+        let allowed_handshake = RemoteHandshake::new(&addr, &prot, &ext, &hash, &allowed, &pid);
+        let other_handshake = RemoteHandshake::new(&addr, &prot, &ext, &hash, &pid, &pid);
+
+        assert_eq!(PolicyDecision::Accept, allowlist.on_incoming(&allowed_handshake));
+        assert_eq!(PolicyDecision::Reject, allowlist.on_incoming(&other_handshake));
+    }
+}
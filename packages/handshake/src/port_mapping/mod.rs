@@ -0,0 +1,353 @@
+//! Port mappings on the gateway of the local network, so that remote peers are able to connect to us.
+//!
+//! Mappings are requested with NAT-PMP, see [RFC 6886](https://www.rfc-editor.org/rfc/rfc6886), falling
+//! back to `UPnP` IGD if the gateway does not respond to it. PCP gateways are only supported if they also
+//! respond to NAT-PMP requests, as recommended by [RFC 6887](https://www.rfc-editor.org/rfc/rfc6887).
+
+use std::net::Ipv4Addr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt as _};
+use tokio::task::JoinSet;
+
+use crate::discovery::DiscoveryInfo as _;
+use crate::handshake::sink::HandshakerSink;
+use crate::port_mapping::upnp::UpnpGateway;
+
+mod natpmp;
+mod upnp;
+
+const DEFAULT_LEASE_DURATION_SECS: u64 = 2 * 60 * 60;
+/// Time waited before trying again, after no port could be mapped.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Number of events buffered before further events are dropped.
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Transport protocol of a port mapping.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MappingProtocol {
+    Tcp,
+    Udp,
+}
+
+/// Protocol that a port mapping was requested from the gateway with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MappingMethod {
+    NatPmp,
+    Upnp,
+}
+
+/// Event reported by a `PortMapping` as it maps (and renews) ports on the gateway.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PortMappingEvent {
+    /// External address of the gateway was discovered, or has changed.
+    ExternalAddress(Ipv4Addr),
+    /// Gateway forwards the external port to the internal port.
+    Mapped {
+        protocol: MappingProtocol,
+        internal_port: u16,
+        external_port: u16,
+        method: MappingMethod,
+    },
+    /// Gateway could not be asked to forward the internal port.
+    Failed { protocol: MappingProtocol, internal_port: u16 },
+}
+
+/// Configures a `PortMapping`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PortMappingConfig {
+    udp_ports: Vec<u16>,
+    gateway: Option<Ipv4Addr>,
+    lease_duration: Duration,
+}
+
+impl PortMappingConfig {
+    /// Adds a UDP port to map, such as the port of the DHT or of a UDP tracker client.
+    #[must_use]
+    pub fn with_udp_port(mut self, port: u16) -> PortMappingConfig {
+        if !self.udp_ports.contains(&port) {
+            self.udp_ports.push(port);
+        }
+        self
+    }
+
+    /// Sets the address of the gateway that NAT-PMP requests are sent to.
+    ///
+    /// Defaults to the default route of the host, if it can be determined. `UPnP` gateways
+    /// are always discovered on the local network instead.
+    #[must_use]
+    pub fn with_gateway(mut self, gateway: Ipv4Addr) -> PortMappingConfig {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Sets how long the gateway should keep our mappings for.
+    ///
+    /// Mappings are renewed when half of the lease has passed, so that they expire
+    /// shortly after the `PortMapping` is dropped.
+    #[must_use]
+    pub fn with_lease_duration(mut self, lease_duration: Duration) -> PortMappingConfig {
+        self.lease_duration = lease_duration;
+        self
+    }
+
+    /// Gets the UDP ports to map.
+    #[must_use]
+    pub fn udp_ports(&self) -> &[u16] {
+        &self.udp_ports
+    }
+
+    /// Gets the address of the gateway that NAT-PMP requests are sent to, if one was set.
+    #[must_use]
+    pub fn gateway(&self) -> Option<Ipv4Addr> {
+        self.gateway
+    }
+
+    /// Gets how long the gateway should keep our mappings for.
+    #[must_use]
+    pub fn lease_duration(&self) -> Duration {
+        self.lease_duration
+    }
+}
+
+impl Default for PortMappingConfig {
+    fn default() -> PortMappingConfig {
+        PortMappingConfig {
+            udp_ports: Vec::new(),
+            gateway: None,
+            lease_duration: Duration::from_secs(DEFAULT_LEASE_DURATION_SECS),
+        }
+    }
+}
+
+/// Maps the listen port of a `Handshaker`, along with any configured UDP ports, on the gateway of the local network.
+///
+/// Mappings are renewed periodically for as long as this is alive. The external address of the gateway
+/// should be given to the DHT (`DhtBuilder::set_external_addr`) so that it can pick a BEP 42 node id, and
+/// used as the source address in tracker announces. Events are yielded as a `Stream`, and are dropped if
+/// they are not consumed.
+///
+/// Mapping stops when this is dropped.
+#[allow(clippy::module_name_repetitions)]
+pub struct PortMapping {
+    external_addr: Arc<Mutex<Option<Ipv4Addr>>>,
+    recv: mpsc::Receiver<PortMappingEvent>,
+    _tasks: JoinSet<()>,
+}
+
+impl PortMapping {
+    /// Start mapping the port of the given `HandshakerSink` over TCP, and the configured UDP ports.
+    #[must_use]
+    pub fn run(handshaker: &HandshakerSink, config: &PortMappingConfig) -> PortMapping {
+        let mut mappings = vec![(MappingProtocol::Tcp, handshaker.port())];
+        mappings.extend(config.udp_ports().iter().map(|&port| (MappingProtocol::Udp, port)));
+
+        let external_addr = Arc::new(Mutex::new(None));
+        let (send, recv) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+
+        let mapper = Mapper {
+            mappings,
+            lease_duration: config.lease_duration(),
+            natpmp_gateway: config.gateway().or_else(default_gateway),
+            upnp_gateway: None,
+            external_addr: external_addr.clone(),
+            events: send,
+        };
+
+        let mut tasks = JoinSet::new();
+        tasks.spawn(mapper.run());
+
+        PortMapping {
+            external_addr,
+            recv,
+            _tasks: tasks,
+        }
+    }
+
+    /// External address of the gateway, if it has been discovered.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn external_addr(&self) -> Option<Ipv4Addr> {
+        *self.external_addr.lock().unwrap()
+    }
+}
+
+impl Stream for PortMapping {
+    type Item = PortMappingEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.recv.poll_next_unpin(cx)
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+struct Mapper {
+    mappings: Vec<(MappingProtocol, u16)>,
+    lease_duration: Duration,
+    natpmp_gateway: Option<Ipv4Addr>,
+    upnp_gateway: Option<UpnpGateway>,
+    external_addr: Arc<Mutex<Option<Ipv4Addr>>>,
+    events: mpsc::Sender<PortMappingEvent>,
+}
+
+impl Mapper {
+    async fn run(mut self) {
+        loop {
+            let renew_after = if self.renew().await {
+                self.lease_duration / 2
+            } else {
+                RETRY_INTERVAL
+            };
+
+            tokio::time::sleep(renew_after).await;
+        }
+    }
+
+    /// Request every mapping from the gateway, returning false if the gateway could not be reached.
+    async fn renew(&mut self) -> bool {
+        if let Some(gateway) = self.natpmp_gateway {
+            match self.renew_natpmp(gateway).await {
+                Ok(()) => return true,
+                Err(e) => tracing::debug!(%e, %gateway, "NAT-PMP is unavailable, falling back to UPnP"),
+            }
+        }
+
+        match self.renew_upnp().await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(%e, "unable to map ports on the gateway");
+
+                for &(protocol, internal_port) in &self.mappings {
+                    self.report(PortMappingEvent::Failed { protocol, internal_port });
+                }
+                false
+            }
+        }
+    }
+
+    async fn renew_natpmp(&self, gateway: Ipv4Addr) -> std::io::Result<()> {
+        // Gateways without NAT-PMP support never respond, so find out before requesting every mapping
+        self.set_external_addr(natpmp::external_address(gateway).await?);
+
+        for &(protocol, internal_port) in &self.mappings {
+            match natpmp::map_port(gateway, protocol, internal_port, self.lease_duration).await {
+                Ok(mapping) => self.report(PortMappingEvent::Mapped {
+                    protocol,
+                    internal_port,
+                    external_port: mapping.external_port,
+                    method: MappingMethod::NatPmp,
+                }),
+                Err(e) => {
+                    tracing::warn!(%e, ?protocol, internal_port, "gateway did not map port with NAT-PMP");
+                    self.report(PortMappingEvent::Failed { protocol, internal_port });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn renew_upnp(&mut self) -> std::io::Result<()> {
+        let gateway = match self.upnp_gateway.take() {
+            Some(gateway) => gateway,
+            None => UpnpGateway::discover().await?,
+        };
+
+        self.set_external_addr(gateway.external_address().await?);
+
+        for &(protocol, internal_port) in &self.mappings {
+            match gateway.map_port(protocol, internal_port, self.lease_duration).await {
+                Ok(()) => self.report(PortMappingEvent::Mapped {
+                    protocol,
+                    internal_port,
+                    external_port: internal_port,
+                    method: MappingMethod::Upnp,
+                }),
+                Err(e) => {
+                    tracing::warn!(%e, ?protocol, internal_port, "gateway did not map port with UPnP");
+                    self.report(PortMappingEvent::Failed { protocol, internal_port });
+                }
+            }
+        }
+
+        self.upnp_gateway = Some(gateway);
+
+        Ok(())
+    }
+
+    fn set_external_addr(&self, addr: Ipv4Addr) {
+        let previous = self.external_addr.lock().unwrap().replace(addr);
+
+        if previous != Some(addr) {
+            tracing::info!(%addr, "discovered external address of the gateway");
+            self.report(PortMappingEvent::ExternalAddress(addr));
+        }
+    }
+
+    fn report(&self, event: PortMappingEvent) {
+        if self.events.clone().try_send(event).is_err() {
+            tracing::trace!(?event, "dropped port mapping event");
+        }
+    }
+}
+
+/// Gateway of the default route of the host.
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Option<Ipv4Addr> {
+    parse_route_table(&std::fs::read_to_string("/proc/net/route").ok()?)
+}
+
+/// Gateway of the default route of the host.
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Option<Ipv4Addr> {
+    None
+}
+
+/// Find the gateway of the default route in the contents of `/proc/net/route`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        let (destination, gateway) = (fields.next()?, fields.next()?);
+
+        if destination != "00000000" {
+            return None;
+        }
+
+        // Addresses are written as the hex value of the address in memory, so in host byte order
+        let gateway = u32::from_str_radix(gateway, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_ne_bytes())).filter(|gateway| !gateway.is_unspecified())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn positive_parse_route_table_default_gateway() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                     eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+
+        assert_eq!(Some(Ipv4Addr::new(192, 168, 1, 1)), super::parse_route_table(table));
+    }
+
+    #[test]
+    fn negative_parse_route_table_no_default_route() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n";
+
+        assert_eq!(None, super::parse_route_table(table));
+    }
+}
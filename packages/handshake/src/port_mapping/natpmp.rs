@@ -0,0 +1,210 @@
+//! NAT Port Mapping Protocol client.
+//!
+//! See [RFC 6886](https://www.rfc-editor.org/rfc/rfc6886).
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::port_mapping::MappingProtocol;
+
+/// Port that the gateway listens for NAT-PMP requests on.
+const NATPMP_PORT: u16 = 5351;
+
+const NATPMP_VERSION: u8 = 0;
+const RESPONSE_OPCODE_FLAG: u8 = 128;
+const RESULT_SUCCESS: u16 = 0;
+
+const EXTERNAL_ADDRESS_OPCODE: u8 = 0;
+const MAP_UDP_OPCODE: u8 = 1;
+const MAP_TCP_OPCODE: u8 = 2;
+
+const EXTERNAL_ADDRESS_RESPONSE_LEN: usize = 12;
+const MAP_RESPONSE_LEN: usize = 16;
+const MAX_RESPONSE_LEN: usize = 16;
+
+/// Time waited for the first response, which is doubled on every retransmission.
+const INITIAL_RESPONSE_TIMEOUT: Duration = Duration::from_millis(250);
+/// Number of times a request is sent before the gateway is considered to not support NAT-PMP.
+///
+/// RFC 6886 allows up to nine attempts, but that would stall the fall back to `UPnP` for over a minute.
+const MAX_REQUEST_ATTEMPTS: u32 = 4;
+
+/// Mapping created by the gateway.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NatPmpMapping {
+    pub external_port: u16,
+    pub lifetime: Duration,
+}
+
+/// Ask the gateway for its external address.
+pub async fn external_address(gateway: Ipv4Addr) -> io::Result<Ipv4Addr> {
+    let response = request(gateway, &[NATPMP_VERSION, EXTERNAL_ADDRESS_OPCODE], EXTERNAL_ADDRESS_OPCODE).await?;
+
+    parse_external_address_response(&response)
+}
+
+/// Ask the gateway to map the given internal port, for the given lifetime.
+///
+/// A lifetime of zero removes the mapping.
+pub async fn map_port(
+    gateway: Ipv4Addr,
+    protocol: MappingProtocol,
+    internal_port: u16,
+    lifetime: Duration,
+) -> io::Result<NatPmpMapping> {
+    let opcode = map_opcode(protocol);
+    let response = request(gateway, &map_request(protocol, internal_port, lifetime), opcode).await?;
+
+    parse_map_response(&response, internal_port)
+}
+
+/// Send the request to the gateway, retransmitting it until a response with the given opcode is received.
+async fn request(gateway: Ipv4Addr, request: &[u8], opcode: u8) -> io::Result<Vec<u8>> {
+    let gateway_addr = SocketAddr::V4(SocketAddrV4::new(gateway, NATPMP_PORT));
+
+    let socket = UdpSocket::bind(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))).await?;
+    socket.connect(gateway_addr).await?;
+
+    let mut response_timeout = INITIAL_RESPONSE_TIMEOUT;
+    let mut buffer = [0u8; MAX_RESPONSE_LEN];
+
+    for _ in 0..MAX_REQUEST_ATTEMPTS {
+        socket.send(request).await?;
+
+        let deadline = tokio::time::Instant::now() + response_timeout;
+        while let Ok(result) = tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+            let len = result?;
+
+            if len >= 2 && buffer[0] == NATPMP_VERSION && buffer[1] == opcode | RESPONSE_OPCODE_FLAG {
+                return Ok(buffer[..len].to_vec());
+            }
+        }
+
+        response_timeout *= 2;
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "gateway did not respond to NAT-PMP request",
+    ))
+}
+
+fn map_opcode(protocol: MappingProtocol) -> u8 {
+    match protocol {
+        MappingProtocol::Udp => MAP_UDP_OPCODE,
+        MappingProtocol::Tcp => MAP_TCP_OPCODE,
+    }
+}
+
+fn map_request(protocol: MappingProtocol, internal_port: u16, lifetime: Duration) -> [u8; 12] {
+    let lifetime_secs = u32::try_from(lifetime.as_secs()).unwrap_or(u32::MAX);
+
+    let mut request = [0u8; 12];
+    request[0] = NATPMP_VERSION;
+    request[1] = map_opcode(protocol);
+    request[4..6].copy_from_slice(&internal_port.to_be_bytes());
+    // Suggest the same external port, the gateway is free to pick another one
+    request[6..8].copy_from_slice(&internal_port.to_be_bytes());
+    request[8..12].copy_from_slice(&lifetime_secs.to_be_bytes());
+
+    request
+}
+
+fn check_result(response: &[u8], expected_len: usize) -> io::Result<()> {
+    if response.len() < expected_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated NAT-PMP response"));
+    }
+
+    let result_code = u16::from_be_bytes([response[2], response[3]]);
+    if result_code != RESULT_SUCCESS {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("gateway rejected NAT-PMP request with result code {result_code}"),
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_external_address_response(response: &[u8]) -> io::Result<Ipv4Addr> {
+    check_result(response, EXTERNAL_ADDRESS_RESPONSE_LEN)?;
+
+    Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+fn parse_map_response(response: &[u8], internal_port: u16) -> io::Result<NatPmpMapping> {
+    check_result(response, MAP_RESPONSE_LEN)?;
+
+    if u16::from_be_bytes([response[8], response[9]]) != internal_port {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "NAT-PMP response is for a different internal port",
+        ));
+    }
+
+    Ok(NatPmpMapping {
+        external_port: u16::from_be_bytes([response[10], response[11]]),
+        lifetime: Duration::from_secs(u64::from(u32::from_be_bytes([
+            response[12],
+            response[13],
+            response[14],
+            response[15],
+        ]))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use super::NatPmpMapping;
+    use crate::port_mapping::MappingProtocol;
+
+    #[test]
+    fn positive_map_request() {
+        let request = super::map_request(MappingProtocol::Tcp, 6881, Duration::from_secs(7200));
+
+        assert_eq!([0, 2, 0, 0, 0x1A, 0xE1, 0x1A, 0xE1, 0, 0, 0x1C, 0x20], request);
+    }
+
+    #[test]
+    fn positive_parse_external_address_response() {
+        let response = [0, 128, 0, 0, 0, 0, 0, 10, 203, 0, 113, 7];
+
+        assert_eq!(
+            Ipv4Addr::new(203, 0, 113, 7),
+            super::parse_external_address_response(&response).unwrap()
+        );
+    }
+
+    #[test]
+    fn positive_parse_map_response() {
+        let response = [0, 129, 0, 0, 0, 0, 0, 10, 0x1A, 0xE1, 0x1A, 0xE2, 0, 0, 0x0E, 0x10];
+
+        assert_eq!(
+            NatPmpMapping {
+                external_port: 6882,
+                lifetime: Duration::from_secs(3600)
+            },
+            super::parse_map_response(&response, 6881).unwrap()
+        );
+    }
+
+    #[test]
+    fn negative_parse_map_response_error_result() {
+        let response = [0, 129, 0, 2, 0, 0, 0, 10, 0x1A, 0xE1, 0, 0, 0, 0, 0, 0];
+
+        assert!(super::parse_map_response(&response, 6881).is_err());
+    }
+
+    #[test]
+    fn negative_parse_map_response_truncated() {
+        let response = [0, 129, 0, 0, 0, 0, 0, 10];
+
+        assert!(super::parse_map_response(&response, 6881).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! `UPnP` Internet Gateway Device client.
+//!
+//! Only the small subset of `UPnP` needed to map ports is implemented: the gateway is found with
+//! an SSDP search, and the `WANIPConnection` (or `WANPPPConnection`) service listed in its device
+//! description is controlled with plain SOAP requests.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::port_mapping::MappingProtocol;
+
+/// Multicast group and port that SSDP searches are sent to.
+const SSDP_MULTICAST_V4: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 255, 250), 1900);
+
+const GATEWAY_SEARCH_TARGET: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+const CONNECTION_SERVICE_TYPES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:",
+    "urn:schemas-upnp-org:service:WANPPPConnection:",
+];
+
+const LOCATION_HEADER: &str = "Location";
+
+/// Time waited for gateways to respond to a search.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+/// Time waited for a gateway to respond to a HTTP request.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAX_SSDP_RESPONSE_LEN: usize = 1500;
+const MAX_HTTP_RESPONSE_LEN: usize = 64 * 1024;
+
+const PORT_MAPPING_DESCRIPTION: &str = "bittorrent";
+
+/// Connection service of a gateway that port mappings can be requested from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UpnpGateway {
+    addr: SocketAddr,
+    control_path: String,
+    service_type: String,
+}
+
+impl UpnpGateway {
+    /// Search the local network for a gateway, and find the connection service in its description.
+    pub async fn discover() -> io::Result<UpnpGateway> {
+        let location = search().await?;
+        let (addr, path) = parse_http_url(&location)?;
+
+        let description = http_request(
+            addr,
+            &format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"),
+        )
+        .await?;
+
+        let (service_type, control_url) = parse_description(&description)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "gateway does not have a connection service"))?;
+
+        let (addr, control_path) = if control_url.starts_with("http://") {
+            parse_http_url(&control_url)?
+        } else if control_url.starts_with('/') {
+            (addr, control_url)
+        } else {
+            (addr, format!("/{control_url}"))
+        };
+
+        Ok(UpnpGateway {
+            addr,
+            control_path,
+            service_type,
+        })
+    }
+
+    /// Ask the gateway for its external address.
+    pub async fn external_address(&self) -> io::Result<Ipv4Addr> {
+        let response = self.soap_request("GetExternalIPAddress", "").await?;
+
+        tag_text(&response, "NewExternalIPAddress")
+            .and_then(|addr| addr.trim().parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "gateway responded without an external address"))
+    }
+
+    /// Ask the gateway to map the same external port to the given internal port on this host, for the given lifetime.
+    pub async fn map_port(&self, protocol: MappingProtocol, internal_port: u16, lifetime: Duration) -> io::Result<()> {
+        let local_ip = local_ip_towards(self.addr).await?;
+
+        let arguments = format!(
+            "<NewRemoteHost></NewRemoteHost>\
+             <NewExternalPort>{internal_port}</NewExternalPort>\
+             <NewProtocol>{}</NewProtocol>\
+             <NewInternalPort>{internal_port}</NewInternalPort>\
+             <NewInternalClient>{local_ip}</NewInternalClient>\
+             <NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>{PORT_MAPPING_DESCRIPTION}</NewPortMappingDescription>\
+             <NewLeaseDuration>{}</NewLeaseDuration>",
+            protocol_name(protocol),
+            lifetime.as_secs()
+        );
+
+        self.soap_request("AddPortMapping", &arguments).await.map(|_| ())
+    }
+
+    async fn soap_request(&self, action: &str, arguments: &str) -> io::Result<String> {
+        let body = soap_envelope(&self.service_type, action, arguments);
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/xml; charset=\"utf-8\"\r\n\
+             SOAPAction: \"{}#{action}\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.control_path,
+            self.addr,
+            self.service_type,
+            body.len()
+        );
+
+        http_request(self.addr, &request).await
+    }
+}
+
+/// Send an SSDP search for gateways, returning the description location of the first one to respond.
+async fn search() -> io::Result<String> {
+    let socket = UdpSocket::bind(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))).await?;
+    socket.send_to(search_request().as_bytes(), SSDP_MULTICAST_V4).await?;
+
+    let mut buffer = vec![0u8; MAX_SSDP_RESPONSE_LEN];
+    let deadline = tokio::time::Instant::now() + SEARCH_TIMEOUT;
+
+    while let Ok(result) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+        let (len, addr) = result?;
+
+        if let Some(location) = parse_search_response(&buffer[..len]) {
+            return Ok(location);
+        }
+
+        tracing::debug!(%addr, "received an invalid SSDP search response");
+    }
+
+    Err(io::Error::new(io::ErrorKind::TimedOut, "no UPnP gateway responded to search"))
+}
+
+fn search_request() -> String {
+    format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_MULTICAST_V4}\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {GATEWAY_SEARCH_TARGET}\r\n\r\n",
+        SEARCH_TIMEOUT.as_secs() - 1
+    )
+}
+
+fn parse_search_response(bytes: &[u8]) -> Option<String> {
+    let response = std::str::from_utf8(bytes).ok()?;
+    let mut lines = response.split("\r\n");
+
+    let status_line = lines.next()?;
+    if !status_line.starts_with("HTTP/") || status_line.split(' ').nth(1)? != "200" {
+        return None;
+    }
+
+    lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case(LOCATION_HEADER))
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+/// Split a `http://host:port/path` URL into the address of the host and the path.
+///
+/// Gateways advertise themselves by address, so host names are not resolved.
+fn parse_http_url(url: &str) -> io::Result<(SocketAddr, String)> {
+    let invalid_url = || io::Error::new(io::ErrorKind::InvalidData, format!("unsupported gateway url {url}"));
+
+    let rest = url.strip_prefix("http://").ok_or_else(invalid_url)?;
+    let (host, path) = rest.find('/').map_or((rest, "/"), |index| rest.split_at(index));
+
+    let addr = match host.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => SocketAddr::new(host.parse::<Ipv4Addr>().map_err(|_| invalid_url())?.into(), 80),
+    };
+
+    Ok((addr, path.to_owned()))
+}
+
+/// Find the type and control URL of the first connection service in a device description.
+fn parse_description(description: &str) -> Option<(String, String)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service = service.split("</service>").next()?;
+        let service_type = tag_text(service, "serviceType")?.trim();
+        let control_url = tag_text(service, "controlURL")?.trim();
+
+        CONNECTION_SERVICE_TYPES
+            .iter()
+            .any(|connection_type| service_type.starts_with(connection_type))
+            .then(|| (service_type.to_owned(), control_url.to_owned()))
+    })
+}
+
+/// Text between the first opening and closing tag with the given name, ignoring any namespace prefix.
+fn tag_text<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("{name}>"))? + name.len() + 1;
+    let end = start + xml[start..].find("</")?;
+
+    Some(&xml[start..end])
+}
+
+fn soap_envelope(service_type: &str, action: &str, arguments: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{action} xmlns:u=\"{service_type}\">{arguments}</u:{action}></s:Body>\
+         </s:Envelope>"
+    )
+}
+
+fn protocol_name(protocol: MappingProtocol) -> &'static str {
+    match protocol {
+        MappingProtocol::Tcp => "TCP",
+        MappingProtocol::Udp => "UDP",
+    }
+}
+
+/// Local address of the interface that the gateway is reached through.
+async fn local_ip_towards(addr: SocketAddr) -> io::Result<std::net::IpAddr> {
+    let socket = UdpSocket::bind(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))).await?;
+    socket.connect(addr).await?;
+
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Send a HTTP request to the gateway, returning the body of a successful response.
+async fn http_request(addr: SocketAddr, request: &str) -> io::Result<String> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = Vec::new();
+        (&mut stream)
+            .take(MAX_HTTP_RESPONSE_LEN as u64)
+            .read_to_end(&mut response)
+            .await?;
+
+        Ok::<_, io::Error>(response)
+    };
+
+    let response = tokio::time::timeout(HTTP_TIMEOUT, exchange)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "gateway did not respond to HTTP request"))??;
+
+    parse_http_response(&response)
+}
+
+fn parse_http_response(response: &[u8]) -> io::Result<String> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated HTTP response from gateway"))?;
+
+    let status = head.split(' ').nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("gateway responded with HTTP status {status}"),
+        ));
+    }
+
+    Ok(body.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><serviceList>\
+        <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+        <controlURL>/ctl/L3F</controlURL></service>\
+        <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+        <controlURL>/ctl/IPConn</controlURL></service>\
+        </serviceList></device></root>";
+
+    #[test]
+    fn positive_parse_search_response() {
+        let response = b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+                         LOCATION: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+
+        assert_eq!(
+            Some("http://192.168.1.1:5000/rootDesc.xml".to_owned()),
+            super::parse_search_response(response)
+        );
+    }
+
+    #[test]
+    fn negative_parse_search_response_not_ok() {
+        let response = b"HTTP/1.1 404 Not Found\r\nLocation: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+
+        assert_eq!(None, super::parse_search_response(response));
+    }
+
+    #[test]
+    fn positive_parse_http_url() {
+        assert_eq!(
+            (SocketAddr::from(([192, 168, 1, 1], 5000)), "/rootDesc.xml".to_owned()),
+            super::parse_http_url("http://192.168.1.1:5000/rootDesc.xml").unwrap()
+        );
+        assert_eq!(
+            (SocketAddr::from(([192, 168, 1, 1], 80)), "/".to_owned()),
+            super::parse_http_url("http://192.168.1.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn negative_parse_http_url_host_name() {
+        assert!(super::parse_http_url("http://router.local/rootDesc.xml").is_err());
+        assert!(super::parse_http_url("https://192.168.1.1/rootDesc.xml").is_err());
+    }
+
+    #[test]
+    fn positive_parse_description_connection_service() {
+        assert_eq!(
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1".to_owned(),
+                "/ctl/IPConn".to_owned()
+            )),
+            super::parse_description(DESCRIPTION)
+        );
+    }
+
+    #[test]
+    fn positive_parse_external_address() {
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+                        <NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>\
+                        </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+
+        assert_eq!(
+            Some(Ipv4Addr::new(203, 0, 113, 7)),
+            super::tag_text(response, "NewExternalIPAddress").and_then(|addr| addr.parse().ok())
+        );
+    }
+
+    #[test]
+    fn negative_parse_http_response_error_status() {
+        let response = b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n";
+
+        assert!(super::parse_http_response(response).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use bencode::{ben_bytes, ben_map, BConvert, BDecodeOpt, BencodeConvertError, BencodeRef};
+use hmac::{Hmac, KeyInit as _, Mac};
+use rand::Rng as _;
+use sha1::Sha1;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use util::bt::InfoHash;
+
+/// Length of the nonce sent by each side.
+const NONCE_LEN: usize = 20;
+
+/// Role byte mixed into the proof sent by the side that initiated the connection.
+const INITIATOR_ROLE: u8 = b'I';
+/// Role byte mixed into the proof sent by the side that accepted the connection.
+const ACCEPTOR_ROLE: u8 = b'A';
+
+/// Message id of the extension protocol, see BEP 10.
+const EXTENDED_MESSAGE_ID: u8 = 20;
+/// Extended message id of the extension handshake, see BEP 10.
+const EXTENDED_HANDSHAKE_ID: u8 = 0;
+/// Largest extension handshake we are willing to read while authenticating.
+const MAX_EXTENDED_HANDSHAKE_LEN: usize = 1024;
+
+const NONCE_KEY: &[u8] = b"psk_nonce";
+const PROOF_KEY: &[u8] = b"psk_proof";
+
+/// Key shared between all peers of a private deployment.
+///
+/// When set on a `HandshakerBuilder`, both sides send a random nonce in an extension handshake
+/// directly after the handshake, followed by a second extension handshake carrying an HMAC-SHA1
+/// over the info hash and the nonce of the other side. Peers that do not prove knowledge of the
+/// key are dropped before the connection is yielded, so no pieces are ever exchanged with them.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
+pub struct PreSharedKey {
+    mac: Hmac<Sha1>,
+}
+
+impl PreSharedKey {
+    /// Create a new `PreSharedKey` from the given secret bytes.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if HMAC rejected the key, which it does not for keys of any length.
+    #[must_use]
+    pub fn new<K>(key: K) -> PreSharedKey
+    where
+        K: AsRef<[u8]>,
+    {
+        PreSharedKey {
+            mac: Hmac::new_from_slice(key.as_ref()).expect("hmac accepts keys of any length"),
+        }
+    }
+
+    /// HMAC-SHA1 over the concatenation of the given parts.
+    fn hmac(&self, parts: &[&[u8]]) -> Hmac<Sha1> {
+        parts.iter().fold(self.mac.clone(), Mac::chain_update)
+    }
+
+    /// Proof of the key sent by the side with the given role, for the nonce of the other side.
+    fn proof(&self, role: u8, hash: &InfoHash, nonce: &[u8]) -> Hmac<Sha1> {
+        self.hmac(&[&[role], hash.as_ref(), nonce])
+    }
+}
+
+impl std::fmt::Debug for PreSharedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreSharedKey").finish_non_exhaustive()
+    }
+}
+
+/// Prove knowledge of the key to the peer and verify that the peer knows it as well.
+///
+/// Returns false if the peer sent an invalid proof.
+pub async fn authenticate<S>(sock: &mut S, key: &PreSharedKey, hash: &InfoHash, initiator: bool) -> std::io::Result<bool>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (local_role, remote_role) = if initiator {
+        (INITIATOR_ROLE, ACCEPTOR_ROLE)
+    } else {
+        (ACCEPTOR_ROLE, INITIATOR_ROLE)
+    };
+
+    let local_nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+    write_extended_handshake(sock, NONCE_KEY, &local_nonce).await?;
+
+    let remote_nonce = read_extended_handshake(sock, NONCE_KEY).await?;
+
+    let local_proof = key.proof(local_role, hash, &remote_nonce).finalize().into_bytes();
+    write_extended_handshake(sock, PROOF_KEY, &local_proof).await?;
+
+    let remote_proof = read_extended_handshake(sock, PROOF_KEY).await?;
+
+    // Compares in constant time, so the time taken does not depend on the proof
+    Ok(key.proof(remote_role, hash, &local_nonce).verify_slice(&remote_proof).is_ok())
+}
+
+/// Write an extension handshake holding a single byte string under the given key.
+async fn write_extended_handshake<S>(sock: &mut S, key: &[u8], value: &[u8]) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let payload = (ben_map! {
+        key => ben_bytes!(value)
+    })
+    .encode();
+
+    let message_len = u32::try_from(payload.len() + 2).expect("extension handshake fits in a message");
+
+    let mut message = Vec::with_capacity(payload.len() + 6);
+    message.extend_from_slice(&message_len.to_be_bytes());
+    message.extend_from_slice(&[EXTENDED_MESSAGE_ID, EXTENDED_HANDSHAKE_ID]);
+    message.extend_from_slice(&payload);
+
+    sock.write_all(&message).await?;
+    sock.flush().await
+}
+
+/// Read an extension handshake and return the byte string held under the given key.
+async fn read_extended_handshake<S>(sock: &mut S, key: &[u8]) -> std::io::Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let message_len = sock.read_u32().await? as usize;
+    if !(2..=MAX_EXTENDED_HANDSHAKE_LEN).contains(&message_len) {
+        return Err(invalid_data(format!("Extension Handshake Has Invalid Length {message_len}")));
+    }
+
+    let mut message = vec![0u8; message_len];
+    sock.read_exact(&mut message).await?;
+
+    let [EXTENDED_MESSAGE_ID, EXTENDED_HANDSHAKE_ID, payload @ ..] = &message[..] else {
+        return Err(invalid_data("Expected An Extension Handshake"));
+    };
+
+    let bencode = BencodeRef::decode(payload, BDecodeOpt::default()).map_err(|err| invalid_data(err.to_string()))?;
+    let dict = CONVERT.convert_dict(&bencode, "root")?;
+
+    CONVERT.lookup_and_convert_bytes(dict, key).map(<[u8]>::to_vec)
+}
+
+fn invalid_data<E>(error: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+const CONVERT: IoErrorBencodeConvert = IoErrorBencodeConvert;
+
+struct IoErrorBencodeConvert;
+
+impl BConvert for IoErrorBencodeConvert {
+    type Error = std::io::Error;
+
+    fn handle_error(&self, error: BencodeConvertError) -> Self::Error {
+        invalid_data(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::Mac as _;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use util::bt::{self, InfoHash};
+
+    use super::PreSharedKey;
+
+    fn any_info_hash() -> InfoHash {
+        [55u8; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_hmac_matches_rfc_2202() {
+        let key = PreSharedKey::new("Jefe");
+
+        let hmac = key.hmac(&[b"what do ya ", b"want for nothing?"]);
+
+        assert_eq!(
+            [
+                0xEF, 0xFC, 0xDF, 0x6A, 0xE5, 0xEB, 0x2F, 0xA2, 0xD2, 0x74, 0x16, 0xD5, 0xF1, 0x84, 0xDF, 0x9C, 0x25, 0x9A, 0x7C,
+                0x79
+            ],
+            hmac.finalize().into_bytes().as_slice()
+        );
+    }
+
+    #[test]
+    fn positive_hmac_long_key() {
+        let key = PreSharedKey::new([0xAAu8; 80]);
+
+        let hmac = key.hmac(&[b"Test Using Larger Than Block-Size Key - Hash Key First"]);
+
+        assert_eq!(
+            [
+                0xAA, 0x4A, 0xE5, 0xE1, 0x52, 0x72, 0xD0, 0x0E, 0x95, 0x70, 0x56, 0x37, 0xCE, 0x8A, 0x3B, 0x55, 0xED, 0x40, 0x21,
+                0x12
+            ],
+            hmac.finalize().into_bytes().as_slice()
+        );
+    }
+
+    #[tokio::test]
+    async fn positive_authenticate_same_key() {
+        let (mut initiator, mut acceptor) = tokio::io::duplex(128);
+        let key = PreSharedKey::new("secret");
+        let hash = any_info_hash();
+
+        let (initiated, accepted) = tokio::join!(
+            super::authenticate(&mut initiator, &key, &hash, true),
+            super::authenticate(&mut acceptor, &key, &hash, false)
+        );
+
+        assert!(initiated.unwrap());
+        assert!(accepted.unwrap());
+    }
+
+    #[tokio::test]
+    async fn negative_authenticate_different_key() {
+        let (mut initiator, mut acceptor) = tokio::io::duplex(128);
+        let (key, other_key) = (PreSharedKey::new("secret"), PreSharedKey::new("guess"));
+        let hash = any_info_hash();
+
+        let (initiated, accepted) = tokio::join!(
+            super::authenticate(&mut initiator, &key, &hash, true),
+            super::authenticate(&mut acceptor, &other_key, &hash, false)
+        );
+
+        assert!(!initiated.unwrap());
+        assert!(!accepted.unwrap());
+    }
+
+    #[tokio::test]
+    async fn negative_authenticate_same_role() {
+        let (mut one, mut two) = tokio::io::duplex(128);
+        let key = PreSharedKey::new("secret");
+        let hash = any_info_hash();
+
+        // Reflecting our own proof back at us must not be accepted
+        let (one_result, two_result) = tokio::join!(
+            super::authenticate(&mut one, &key, &hash, true),
+            super::authenticate(&mut two, &key, &hash, true)
+        );
+
+        assert!(!one_result.unwrap());
+        assert!(!two_result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn positive_nonce_sent_as_extension_handshake() {
+        let (mut local, mut remote) = tokio::io::duplex(128);
+        let key = PreSharedKey::new("secret");
+        let hash = any_info_hash();
+
+        tokio::spawn(async move { super::authenticate(&mut local, &key, &hash, true).await });
+
+        let message_len = remote.read_u32().await.unwrap() as usize;
+        let mut message = vec![0u8; message_len];
+        remote.read_exact(&mut message).await.unwrap();
+
+        assert_eq!(&[20, 0], &message[..2]);
+        assert!(message[2..].starts_with(b"d9:psk_nonce20:"));
+    }
+
+    #[tokio::test]
+    async fn negative_authenticate_not_extension_handshake() {
+        let (mut local, mut remote) = tokio::io::duplex(128);
+        let key = PreSharedKey::new("secret");
+        let hash = any_info_hash();
+
+        // A raw nonce, as sent by peers that do not frame it in an extension handshake
+        remote.write_all(&[0u8; 20]).await.unwrap();
+
+        assert!(super::authenticate(&mut local, &key, &hash, true).await.is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt as _, Stream, StreamExt as _, TryFutureExt as _};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, ReadBuf};
+
+use crate::local_addr::LocalAddr;
+use crate::message::protocol::Protocol;
+use crate::transport::Transport;
+
+/// Default time a connection is given to send the start of its handshake before it is routed elsewhere.
+const DEFAULT_SNIFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Callback receiving the accepted connections that did not start with a handshake.
+type OtherHandler<S> = Arc<dyn Fn(SniffedSocket<S>, SocketAddr) + Send + Sync>;
+
+/// Sniffing of an accepted connection, resolving to the connection if it started with a handshake.
+type SniffFuture<S> = BoxFuture<'static, Option<(SniffedSocket<S>, SocketAddr)>>;
+
+//----------------------------------------------------------------------------------//
+
+/// A `Transport` sharing its listening port between handshakes and other protocols.
+///
+/// Each accepted connection is read from until its first bytes either match the protocol of the
+/// handshake, in which case it is handshaked as usual, or do not, in which case it is handed to a
+/// callback, for example to be served by a TLS or HTTP server. Connections that do not send the start
+/// of a handshake within the sniff timeout, such as protocols where the server speaks first, are handed
+/// to the callback as well.
+///
+/// The bytes read while sniffing are replayed by the `SniffedSocket`, so neither side loses them.
+/// Outgoing connections are not sniffed.
+#[allow(clippy::module_name_repetitions)]
+pub struct SniffingTransport<T>
+where
+    T: Transport,
+{
+    transport: T,
+    other: OtherHandler<T::Socket>,
+    expected: Vec<u8>,
+    sniff_timeout: Duration,
+}
+
+impl<T> SniffingTransport<T>
+where
+    T: Transport,
+{
+    /// Create a new `SniffingTransport` over the given `Transport`, handing connections that are not
+    /// handshakes to the given callback.
+    pub fn new<F>(transport: T, other: F) -> SniffingTransport<T>
+    where
+        F: Fn(SniffedSocket<T::Socket>, SocketAddr) + Send + Sync + 'static,
+    {
+        SniffingTransport {
+            transport,
+            other: Arc::new(other),
+            expected: expected_bytes(&Protocol::BitTorrent),
+            sniff_timeout: DEFAULT_SNIFF_TIMEOUT,
+        }
+    }
+
+    /// Protocol that handshakes are expected to use, which should match the protocol of the `InitiateMessage`(s).
+    ///
+    /// Defaults to `Protocol::BitTorrent`.
+    #[must_use]
+    pub fn with_protocol(mut self, protocol: &Protocol) -> SniffingTransport<T> {
+        self.expected = expected_bytes(protocol);
+        self
+    }
+
+    /// Time a connection is given to send the start of its handshake.
+    ///
+    /// Defaults to 10 seconds.
+    #[must_use]
+    pub fn with_sniff_timeout(mut self, timeout: Duration) -> SniffingTransport<T> {
+        self.sniff_timeout = timeout;
+        self
+    }
+}
+
+impl<T> Transport for SniffingTransport<T>
+where
+    T: Transport,
+    T::Socket: Send,
+    T::Listener: Send,
+{
+    type Socket = SniffedSocket<T::Socket>;
+    type FutureSocket = BoxFuture<'static, std::io::Result<Self::Socket>>;
+    type Listener = SniffingListener<T::Listener, T::Socket>;
+    type FutureListener = BoxFuture<'static, std::io::Result<Self::Listener>>;
+
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureSocket {
+        self.transport.connect(addr, timeout).map_ok(SniffedSocket::new).boxed()
+    }
+
+    fn connect_from(&self, addr: SocketAddr, source: IpAddr, timeout: Duration) -> Self::FutureSocket {
+        self.transport
+            .connect_from(addr, source, timeout)
+            .map_ok(SniffedSocket::new)
+            .boxed()
+    }
+
+    fn listen(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureListener {
+        let other = self.other.clone();
+        let expected = self.expected.clone();
+        let sniff_timeout = self.sniff_timeout;
+
+        self.transport
+            .listen(addr, timeout)
+            .map_ok(move |listener| SniffingListener {
+                listener: Some(listener),
+                sniffing: FuturesUnordered::new(),
+                other,
+                expected: expected.into(),
+                sniff_timeout,
+            })
+            .boxed()
+    }
+}
+
+/// Length byte followed by the protocol bytes, as sent at the start of a handshake.
+fn expected_bytes(protocol: &Protocol) -> Vec<u8> {
+    let mut expected = Vec::with_capacity(1 + protocol.write_len());
+    protocol
+        .write_bytes_sync(&mut expected)
+        .expect("bip_handshake: Failed To Write Protocol To Vec");
+
+    expected
+}
+
+//----------------------------------------------------------------------------------//
+
+/// Socket of a `SniffingTransport`, replaying the bytes read while sniffing before reading from the underlying socket.
+#[derive(Debug)]
+pub struct SniffedSocket<S> {
+    peeked: Vec<u8>,
+    position: usize,
+    socket: S,
+}
+
+impl<S> SniffedSocket<S> {
+    fn new(socket: S) -> SniffedSocket<S> {
+        SniffedSocket::with_peeked(socket, Vec::new())
+    }
+
+    fn with_peeked(socket: S, peeked: Vec<u8>) -> SniffedSocket<S> {
+        SniffedSocket {
+            peeked,
+            position: 0,
+            socket,
+        }
+    }
+
+    /// Bytes read while sniffing that have not been read from this socket yet.
+    #[must_use]
+    pub fn peeked(&self) -> &[u8] {
+        &self.peeked[self.position..]
+    }
+
+    /// Reference to the underlying socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Mutable reference to the underlying socket; reading from it directly skips the peeked bytes.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.socket
+    }
+}
+
+impl<S> AsyncRead for SniffedSocket<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.position < this.peeked.len() {
+            let remaining = &this.peeked[this.position..];
+            let len = std::cmp::min(remaining.len(), buf.remaining());
+
+            buf.put_slice(&remaining[..len]);
+            this.position += len;
+
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut this.socket).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for SniffedSocket<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().socket).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().socket).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().socket).poll_shutdown(cx)
+    }
+}
+
+//----------------------------------------------------------------------------------//
+
+/// Listener of a `SniffingTransport`, yielding only the connections that start with a handshake.
+///
+/// Connections are sniffed concurrently, so a connection that is slow to send its first bytes does
+/// not hold up the connections accepted after it.
+#[allow(clippy::module_name_repetitions)]
+pub struct SniffingListener<L, S> {
+    listener: Option<L>,
+    sniffing: FuturesUnordered<SniffFuture<S>>,
+    other: OtherHandler<S>,
+    expected: Arc<[u8]>,
+    sniff_timeout: Duration,
+}
+
+impl<L, S> SniffingListener<L, S>
+where
+    S: AsyncRead + Unpin + Send + 'static,
+{
+    fn sniff(&self, mut socket: S, addr: SocketAddr) -> SniffFuture<S> {
+        let other = self.other.clone();
+        let expected = self.expected.clone();
+        let sniff_timeout = self.sniff_timeout;
+
+        async move {
+            let mut peeked = Vec::with_capacity(expected.len());
+            let result = tokio::time::timeout(sniff_timeout, read_matching(&mut socket, &expected, &mut peeked)).await;
+
+            match result {
+                Ok(Ok(true)) => return Some((SniffedSocket::with_peeked(socket, peeked), addr)),
+                Ok(Err(e)) => {
+                    tracing::debug!(%e, %addr, "failed to sniff connection");
+                    return None;
+                }
+                Ok(Ok(false)) | Err(_) => (),
+            }
+
+            other(SniffedSocket::with_peeked(socket, peeked), addr);
+
+            None
+        }
+        .boxed()
+    }
+}
+
+/// Read from the socket for as long as the bytes read match the expected bytes, returning whether all of them did.
+async fn read_matching<S>(socket: &mut S, expected: &[u8], peeked: &mut Vec<u8>) -> std::io::Result<bool>
+where
+    S: AsyncRead + Unpin,
+{
+    // Length byte followed by at most 255 protocol bytes
+    let mut buffer = [0u8; 256];
+
+    while peeked.len() < expected.len() {
+        let missing = expected.len() - peeked.len();
+        let read = socket.read(&mut buffer[..missing]).await?;
+        if read == 0 {
+            return Ok(false);
+        }
+
+        peeked.extend_from_slice(&buffer[..read]);
+
+        if !expected.starts_with(peeked) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+impl<L, S> LocalAddr for SniffingListener<L, S>
+where
+    L: LocalAddr,
+{
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match &self.listener {
+            Some(listener) => listener.local_addr(),
+            None => Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "Listener Closed")),
+        }
+    }
+}
+
+impl<L, S> Stream for SniffingListener<L, S>
+where
+    L: Stream<Item = std::io::Result<(S, SocketAddr)>> + Unpin,
+    S: AsyncRead + Unpin + Send + 'static,
+{
+    type Item = std::io::Result<(SniffedSocket<S>, SocketAddr)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        while let Some(listener) = &mut this.listener {
+            match listener.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok((socket, addr)))) => {
+                    let sniff = this.sniff(socket, addr);
+                    this.sniffing.push(sniff);
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => this.listener = None,
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            match this.sniffing.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(item))) => return Poll::Ready(Some(Ok(item))),
+                Poll::Ready(Some(None)) => (),
+                Poll::Ready(None) if this.listener.is_none() => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt as _;
+
+    use super::{expected_bytes, read_matching, SniffedSocket};
+    use crate::message::protocol::Protocol;
+
+    #[tokio::test]
+    async fn positive_sniff_handshake_replays_peeked() {
+        let expected = expected_bytes(&Protocol::BitTorrent);
+        let mut sent = expected.clone();
+        sent.extend_from_slice(&[0u8; 8]);
+
+        let mut socket = std::io::Cursor::new(sent.clone());
+        let mut peeked = Vec::new();
+        assert!(read_matching(&mut socket, &expected, &mut peeked).await.unwrap());
+
+        let mut sniffed = SniffedSocket::with_peeked(socket, peeked);
+        let mut received = Vec::new();
+        sniffed.read_to_end(&mut received).await.unwrap();
+
+        assert_eq!(sent, received);
+    }
+
+    #[tokio::test]
+    async fn negative_sniff_other_protocol() {
+        let expected = expected_bytes(&Protocol::BitTorrent);
+
+        let mut socket = std::io::Cursor::new(b"GET / HTTP/1.1\r\n\r\n".to_vec());
+        let mut peeked = Vec::new();
+        assert!(!read_matching(&mut socket, &expected, &mut peeked).await.unwrap());
+
+        assert_eq!(b"GET / HTTP/1.1\r\n\r\n", &peeked[..]);
+    }
+}
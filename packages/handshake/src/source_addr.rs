@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+use util::bt::InfoHash;
+
+/// Local addresses that outgoing connections are bound to, shared between the `HandshakerSink` and the initiator.
+#[derive(Clone, Default)]
+pub struct SourceAddrs {
+    inner: Arc<RwLock<SourceAddrsInner>>,
+}
+
+#[derive(Default)]
+struct SourceAddrsInner {
+    global: Option<IpAddr>,
+    torrents: HashMap<InfoHash, IpAddr>,
+}
+
+impl SourceAddrs {
+    pub fn new(global: Option<IpAddr>) -> SourceAddrs {
+        let source_addrs = SourceAddrs::default();
+        source_addrs.set_global(global);
+
+        source_addrs
+    }
+
+    /// Set the address used for torrents without their own address, or `None` to use the default route.
+    pub fn set_global(&self, addr: Option<IpAddr>) {
+        self.inner
+            .write()
+            .expect("bip_handshake: Poisoned Write Lock In SourceAddrs")
+            .global = addr;
+    }
+
+    /// Set the address used for the given torrent, or `None` to fall back to the global address.
+    pub fn set_torrent(&self, hash: InfoHash, addr: Option<IpAddr>) {
+        let mut inner = self.inner.write().expect("bip_handshake: Poisoned Write Lock In SourceAddrs");
+
+        match addr {
+            Some(addr) => inner.torrents.insert(hash, addr),
+            None => inner.torrents.remove(&hash),
+        };
+    }
+
+    /// Address that connections for the given torrent should be bound to, if any.
+    pub fn lookup(&self, hash: &InfoHash) -> Option<IpAddr> {
+        let inner = self.inner.read().expect("bip_handshake: Poisoned Read Lock In SourceAddrs");
+
+        inner.torrents.get(hash).copied().or(inner.global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use util::bt::{self, InfoHash};
+
+    use super::SourceAddrs;
+
+    #[test]
+    fn positive_torrent_overrides_global() {
+        let global: IpAddr = "10.0.0.1".parse().unwrap();
+        let torrent: IpAddr = "10.0.0.2".parse().unwrap();
+        let (hash_one, hash_two): (InfoHash, InfoHash) = ([1u8; bt::INFO_HASH_LEN].into(), [2u8; bt::INFO_HASH_LEN].into());
+
+        let source_addrs = SourceAddrs::new(Some(global));
+        source_addrs.set_torrent(hash_one, Some(torrent));
+
+        assert_eq!(Some(torrent), source_addrs.lookup(&hash_one));
+        assert_eq!(Some(global), source_addrs.lookup(&hash_two));
+
+        source_addrs.set_torrent(hash_one, None);
+        source_addrs.set_global(None);
+
+        assert_eq!(None, source_addrs.lookup(&hash_one));
+    }
+}
@@ -1,12 +1,12 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures::future::BoxFuture;
+use futures::future::{self, BoxFuture};
 use futures::{Future, FutureExt as _, Stream, TryFutureExt as _};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
 
 use crate::local_addr::LocalAddr;
 
@@ -31,6 +31,14 @@ pub trait Transport {
     /// Returns an IO error if unable to connect to the socket.
     fn connect(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureSocket;
 
+    /// Connect to the given address using this transport, from the given local address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an IO error wrapping a `BindError` if unable to bind to the local address,
+    /// or an IO error if unable to connect to the socket.
+    fn connect_from(&self, addr: SocketAddr, source: IpAddr, timeout: Duration) -> Self::FutureSocket;
+
     /// Listen on the given address using this transport.
     ///
     /// # Errors
@@ -60,6 +68,19 @@ impl Transport for TcpTransport {
         socket.map(|s| s.and_then(|s| s)).boxed()
     }
 
+    fn connect_from(&self, addr: SocketAddr, source: IpAddr, timeout: Duration) -> Self::FutureSocket {
+        let socket = match bind_tcp_socket(source) {
+            Ok(socket) => socket,
+            Err(e) => return future::err(e).boxed(),
+        };
+
+        let socket = tokio::time::timeout(timeout, socket.connect(addr))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))
+            .boxed();
+
+        socket.map(|s| s.and_then(|s| s)).boxed()
+    }
+
     fn listen(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureListener {
         let listener = TcpListener::bind(addr);
 
@@ -73,6 +94,73 @@ impl Transport for TcpTransport {
     }
 }
 
+/// Create a `TcpSocket` bound to the given local address, with any port.
+fn bind_tcp_socket(source: IpAddr) -> std::io::Result<TcpSocket> {
+    let socket = match source {
+        IpAddr::V4(_) => TcpSocket::new_v4(),
+        IpAddr::V6(_) => TcpSocket::new_v6(),
+    };
+
+    socket
+        .and_then(|socket| socket.bind(SocketAddr::new(source, 0)).map(|()| socket))
+        .map_err(|e| BindError::new(source, e).into())
+}
+
+//----------------------------------------------------------------------------------//
+
+/// Error for a `Transport` that could not bind to the local address of an outgoing connection.
+///
+/// Wrapped in the `std::io::Error` returned from `Transport::connect_from`, so that
+/// it can be told apart from a failure to connect to the peer itself.
+#[derive(Debug)]
+pub struct BindError {
+    addr: IpAddr,
+    error: std::io::Error,
+}
+
+impl BindError {
+    /// Create a new `BindError` for the given local address.
+    #[must_use]
+    pub fn new(source: IpAddr, error: std::io::Error) -> BindError {
+        BindError { addr: source, error }
+    }
+
+    /// Local address that could not be bound to.
+    #[must_use]
+    pub fn source_addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Kind of the underlying error.
+    #[must_use]
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.error.kind()
+    }
+
+    /// `BindError` wrapped by the given error, if any.
+    pub(crate) fn find(error: &std::io::Error) -> Option<&BindError> {
+        error.get_ref().and_then(|inner| inner.downcast_ref::<BindError>())
+    }
+}
+
+impl std::fmt::Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to bind outgoing connection to {}: {}", self.addr, self.error)
+    }
+}
+
+impl std::error::Error for BindError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<BindError> for std::io::Error {
+    fn from(error: BindError) -> std::io::Error {
+        std::io::Error::new(error.kind(), error)
+    }
+}
+
 //----------------------------------------------------------------------------------//
 
 /// A custom stream for `TcpListener`.
@@ -111,7 +199,7 @@ impl Stream for TcpListenerStream {
 #[cfg(test)]
 pub mod test_transports {
 
-    use std::net::SocketAddr;
+    use std::net::{IpAddr, SocketAddr};
     use std::pin::Pin;
     use std::task::{Context, Poll};
     use std::time::Duration;
@@ -121,10 +209,12 @@ pub mod test_transports {
     use futures::stream::{self, Empty, Stream};
     use futures::{FutureExt as _, StreamExt as _};
 
-    use super::Transport;
+    use super::{BindError, Transport};
     use crate::LocalAddr;
 
     /// A mock transport for testing purposes.
+    ///
+    /// Binding to a local address only succeeds for loopback addresses.
     pub struct MockTransport;
 
     impl Transport for MockTransport {
@@ -137,6 +227,16 @@ pub mod test_transports {
             future::ok(std::io::Cursor::new(Vec::new())).boxed()
         }
 
+        fn connect_from(&self, addr: SocketAddr, source: IpAddr, timeout: Duration) -> Self::FutureSocket {
+            if source.is_loopback() {
+                self.connect(addr, timeout)
+            } else {
+                let error = std::io::Error::from(std::io::ErrorKind::AddrNotAvailable);
+
+                future::err(BindError::new(source, error).into()).boxed()
+            }
+        }
+
         fn listen(&self, addr: SocketAddr, _timeout: Duration) -> Self::FutureListener {
             future::ok(MockListener::new(addr)).boxed()
         }
@@ -0,0 +1,261 @@
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use futures::{FutureExt as _, Stream, TryFutureExt as _};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::local_addr::LocalAddr;
+use crate::peer_addr::PeerAddr;
+use crate::transport::Transport;
+
+/// First segment of the addresses standing in for Unix domain socket endpoints.
+///
+/// Taken from the discard-only block (RFC 6666), so they never collide with the address of an IP peer.
+const ENDPOINT_PREFIX: u16 = 0x0100;
+const NAMED_ENDPOINT: u16 = 1;
+const UNNAMED_ENDPOINT: u16 = 2;
+
+fn endpoint_addr(kind: u16, id: u32) -> SocketAddr {
+    let [a, b, c, d] = id.to_be_bytes();
+    let ip = Ipv6Addr::new(
+        ENDPOINT_PREFIX,
+        0,
+        0,
+        0,
+        kind,
+        0,
+        u16::from_be_bytes([a, b]),
+        u16::from_be_bytes([c, d]),
+    );
+
+    SocketAddr::new(IpAddr::V6(ip), 0)
+}
+
+fn endpoint_id(addr: SocketAddr) -> Option<(u16, u32)> {
+    let IpAddr::V6(ip) = addr.ip() else {
+        return None;
+    };
+
+    match ip.segments() {
+        [ENDPOINT_PREFIX, 0, 0, 0, kind, 0, high, low] => {
+            let [a, b] = high.to_be_bytes();
+            let [c, d] = low.to_be_bytes();
+
+            Some((kind, u32::from_be_bytes([a, b, c, d])))
+        }
+        _ => None,
+    }
+}
+
+/// Unix domain socket paths known to a `UnixTransport`.
+#[derive(Debug, Default)]
+struct Endpoints {
+    paths: Vec<PathBuf>,
+    next_unnamed: u32,
+}
+
+impl Endpoints {
+    fn register(&mut self, path: &Path) -> SocketAddr {
+        let index = self.paths.iter().position(|known| known == path).unwrap_or_else(|| {
+            self.paths.push(path.to_path_buf());
+            self.paths.len() - 1
+        });
+
+        endpoint_addr(
+            NAMED_ENDPOINT,
+            u32::try_from(index).expect("bip_handshake: Too Many Unix Endpoints"),
+        )
+    }
+
+    fn register_unnamed(&mut self) -> SocketAddr {
+        let id = self.next_unnamed;
+        self.next_unnamed = self.next_unnamed.wrapping_add(1);
+
+        endpoint_addr(UNNAMED_ENDPOINT, id)
+    }
+
+    fn resolve(&self, addr: SocketAddr) -> PeerAddr {
+        match endpoint_id(addr) {
+            Some((NAMED_ENDPOINT, id)) => match usize::try_from(id).ok().and_then(|index| self.paths.get(index)) {
+                Some(path) => PeerAddr::Unix(Some(path.clone())),
+                None => PeerAddr::Ip(addr),
+            },
+            Some((UNNAMED_ENDPOINT, _)) => PeerAddr::Unix(None),
+            _ => PeerAddr::Ip(addr),
+        }
+    }
+}
+
+//----------------------------------------------------------------------------------//
+
+/// A `Transport` implementation for Unix domain sockets, so that co-located processes can be peers without TCP.
+///
+/// Each socket path is given an address with `UnixTransport::endpoint`, which is used wherever the
+/// handshaker expects a `SocketAddr`, for example to listen with `HandshakerBuilder::with_bind_addr`
+/// or to connect with an `InitiateMessage`. Accepted connections from sockets not bound to a path are
+/// each given a new address. `UnixTransport::peer_addr` resolves any of these addresses to a `PeerAddr`.
+///
+/// Cloning the `UnixTransport` shares the addresses between the clones. Source addresses set on the
+/// handshaker do not apply to Unix domain sockets, and should be left unset.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default)]
+pub struct UnixTransport {
+    endpoints: Arc<Mutex<Endpoints>>,
+}
+
+impl UnixTransport {
+    /// Create a new `UnixTransport`.
+    #[must_use]
+    pub fn new() -> UnixTransport {
+        UnixTransport::default()
+    }
+
+    /// Address standing in for the Unix domain socket at the given path.
+    ///
+    /// Returns the same address each time it is called with the same path.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    pub fn endpoint<P>(&self, path: P) -> SocketAddr
+    where
+        P: AsRef<Path>,
+    {
+        self.endpoints.lock().unwrap().register(path.as_ref())
+    }
+
+    /// Resolve an address given out by this transport to the endpoint it stands in for.
+    ///
+    /// Any other address is returned as a `PeerAddr::Ip`.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn peer_addr(&self, addr: SocketAddr) -> PeerAddr {
+        self.endpoints.lock().unwrap().resolve(addr)
+    }
+
+    fn path(&self, addr: SocketAddr) -> std::io::Result<PathBuf> {
+        match self.peer_addr(addr) {
+            PeerAddr::Unix(Some(path)) => Ok(path),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{addr} does not stand in for a unix domain socket path"),
+            )),
+        }
+    }
+}
+
+impl Transport for UnixTransport {
+    type Socket = UnixStream;
+    type FutureSocket = BoxFuture<'static, std::io::Result<Self::Socket>>;
+    type Listener = UnixListenerStream;
+    type FutureListener = BoxFuture<'static, std::io::Result<Self::Listener>>;
+
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> Self::FutureSocket {
+        let path = match self.path(addr) {
+            Ok(path) => path,
+            Err(e) => return future::err(e).boxed(),
+        };
+
+        let socket = tokio::time::timeout(timeout, async move { UnixStream::connect(path).await })
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))
+            .boxed();
+
+        socket.map(|s| s.and_then(|s| s)).boxed()
+    }
+
+    fn connect_from(&self, addr: SocketAddr, _source: IpAddr, timeout: Duration) -> Self::FutureSocket {
+        self.connect(addr, timeout)
+    }
+
+    fn listen(&self, addr: SocketAddr, _timeout: Duration) -> Self::FutureListener {
+        let path = match self.path(addr) {
+            Ok(path) => path,
+            Err(e) => return future::err(e).boxed(),
+        };
+        let endpoints = self.endpoints.clone();
+
+        async move {
+            let listener = UnixListener::bind(path)?;
+
+            Ok(UnixListenerStream {
+                listener,
+                addr,
+                endpoints,
+            })
+        }
+        .boxed()
+    }
+}
+
+//----------------------------------------------------------------------------------//
+
+/// A custom stream for `UnixListener`, yielding the address given to each accepted connection.
+pub struct UnixListenerStream {
+    listener: UnixListener,
+    addr: SocketAddr,
+    endpoints: Arc<Mutex<Endpoints>>,
+}
+
+impl LocalAddr for UnixListenerStream {
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+}
+
+impl Stream for UnixListenerStream {
+    type Item = std::io::Result<(UnixStream, SocketAddr)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.listener.poll_accept(cx) {
+            Poll::Ready(Ok((socket, remote))) => {
+                let mut endpoints = self.endpoints.lock().unwrap();
+                let addr = match remote.as_pathname() {
+                    Some(path) => endpoints.register(path),
+                    None => endpoints.register_unnamed(),
+                };
+
+                Poll::Ready(Some(Ok((socket, addr))))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::UnixTransport;
+    use crate::PeerAddr;
+
+    #[test]
+    fn positive_resolve_endpoint() {
+        let transport = UnixTransport::new();
+
+        let addr = transport.endpoint("/tmp/peer.sock");
+
+        assert_eq!(addr, transport.endpoint("/tmp/peer.sock"));
+        assert_ne!(addr, transport.endpoint("/tmp/other.sock"));
+        assert_eq!(
+            PeerAddr::Unix(Some(PathBuf::from("/tmp/peer.sock"))),
+            transport.peer_addr(addr)
+        );
+    }
+
+    #[test]
+    fn negative_resolve_ip_addr() {
+        let transport = UnixTransport::new();
+        let addr = "[::1]:6881".parse().unwrap();
+
+        assert_eq!(PeerAddr::Ip(addr), transport.peer_addr(addr));
+    }
+}
@@ -0,0 +1,87 @@
+use common::{tracing_stderr_init, INIT};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{AttemptFailure, AttemptStage, DiscoveryInfo, HandshakerBuilder, InitiateMessage, Protocol};
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_attempt_events() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let handshaker_two_pid = [5u8; bt::PEER_ID_LEN].into();
+    let (handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id(handshaker_two_pid)
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    // Grab a port that nobody is listening on
+    let closed_addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+
+    let mut events = handshaker_one.attempt_events();
+
+    let test = tokio::spawn(async move {
+        // Failed attempts should not stop further attempts from being made
+        for addr in [closed_addr, handshaker_two_addr] {
+            handshaker_one
+                .send(InitiateMessage::new(
+                    Protocol::BitTorrent,
+                    [55u8; bt::INFO_HASH_LEN].into(),
+                    addr,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let message = handshaker_one.next().await.unwrap().unwrap();
+        assert_eq!(handshaker_two_addr, *message.address());
+
+        let closed_stages: Vec<AttemptStage> = (&mut events).take(2).map(|event| event.stage()).collect().await;
+        assert_eq!(
+            vec![AttemptStage::Dialing, AttemptStage::Failed(AttemptFailure::Refused)],
+            closed_stages
+        );
+
+        let open_events: Vec<_> = (&mut events).take(4).collect().await;
+        assert!(open_events.iter().all(|event| *event.address() == handshaker_two_addr));
+        assert_eq!(
+            vec![
+                AttemptStage::Dialing,
+                AttemptStage::Connected,
+                AttemptStage::HandshakeSent,
+                AttemptStage::Completed
+            ],
+            open_events.iter().map(handshake::AttemptEvent::stage).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&handshaker_two_pid), open_events[3].peer_id());
+
+        drop(handshaker_two);
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use common::{tracing_stderr_init, INIT};
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{DiscoveryInfo, HandshakerBuilder, LocalDiscoveryConfig, LocalServiceDiscovery};
+use tokio::net::TcpStream;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn positive_local_discovery_connects_peers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let hash = [55u8; bt::INFO_HASH_LEN].into();
+
+    let (handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let (handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let (sink_one, mut stream_one) = handshaker_one.into_parts();
+    let (sink_two, mut stream_two) = handshaker_two.into_parts();
+
+    // Unicast announcements directly to each other, rather than relying on multicast in the test environment
+    let discovery_one = LocalServiceDiscovery::run(
+        &sink_one,
+        LocalDiscoveryConfig::default()
+            .with_bind_addr("127.0.0.1:0".parse().unwrap())
+            .with_announce_addr("127.0.0.1:9".parse().unwrap()),
+    )
+    .unwrap();
+
+    let discovery_two = LocalServiceDiscovery::run(
+        &sink_two,
+        LocalDiscoveryConfig::default()
+            .with_bind_addr("127.0.0.1:0".parse().unwrap())
+            .with_announce_addr(discovery_one.local_addr())
+            .with_announce_interval(ANNOUNCE_INTERVAL),
+    )
+    .unwrap();
+
+    discovery_one.enable(hash).await;
+    discovery_two.enable(hash).await;
+
+    let test = tokio::spawn(async move {
+        let complete = async {
+            let item_one: handshake::CompleteMessage<TcpStream> = stream_one.next().await.unwrap().unwrap();
+            let item_two: handshake::CompleteMessage<TcpStream> = stream_two.next().await.unwrap().unwrap();
+
+            (item_one, item_two)
+        };
+
+        let (item_one, item_two) = tokio::time::timeout(DISCOVERY_TIMEOUT, complete).await.unwrap();
+
+        assert_eq!(hash, *item_one.hash());
+        assert_eq!(hash, *item_two.hash());
+        assert_eq!(sink_two.port(), item_one.address().port());
+    });
+
+    let res = test.await;
+
+    drop(discovery_one);
+    drop(discovery_two);
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
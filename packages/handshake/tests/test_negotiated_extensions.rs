@@ -0,0 +1,92 @@
+use common::{tracing_stderr_init, INIT};
+use futures::future::try_join;
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{DiscoveryInfo, Extension, Extensions, HandshakerBuilder, InitiateMessage, Protocol};
+use tokio::net::TcpStream;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_negotiated_extensions() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut handshaker_one_ext = Extensions::new();
+    handshaker_one_ext.add(Extension::ExtensionProtocol);
+    handshaker_one_ext.add(Extension::Dht);
+
+    let mut handshaker_two_ext = Extensions::new();
+    handshaker_two_ext.add(Extension::Dht);
+    handshaker_two_ext.add(Extension::Fast);
+
+    let handshaker_one_addr = "127.0.0.1:0".parse().unwrap();
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_one_addr)
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .with_extensions(handshaker_one_ext)
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let handshaker_two_addr = "127.0.0.1:0".parse().unwrap();
+
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_two_addr)
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .with_extensions(handshaker_two_ext)
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let test = tokio::spawn(async move {
+        let mut handshaker_two_addr = handshaker_two_addr;
+        handshaker_two_addr.set_port(handshaker_two.port());
+
+        handshaker_one
+            .send(InitiateMessage::new(
+                Protocol::BitTorrent,
+                [55u8; bt::INFO_HASH_LEN].into(),
+                handshaker_two_addr,
+            ))
+            .await
+            .unwrap();
+
+        let handshaker_one_future = async {
+            let message: handshake::CompleteMessage<TcpStream> = handshaker_one.next().await.unwrap().unwrap();
+            Ok::<_, ()>(message)
+        };
+
+        let handshaker_two_future = async {
+            let message: handshake::CompleteMessage<TcpStream> = handshaker_two.next().await.unwrap().unwrap();
+            Ok::<_, ()>(message)
+        };
+
+        let (item_one, item_two) = try_join(handshaker_one_future, handshaker_two_future).await.unwrap();
+
+        // Each side sees exactly what the other advertised
+        assert_eq!(handshaker_two_ext, *item_one.remote_extensions());
+        assert_eq!(handshaker_one_ext, *item_two.remote_extensions());
+        assert_eq!(handshaker_two_ext.as_bytes(), item_one.remote_reserved());
+
+        // But only the dht protocol is supported by both
+        for item in [&item_one, &item_two] {
+            assert_eq!(Protocol::BitTorrent, *item.protocol());
+            assert!(item.supports_dht());
+            assert!(!item.supports_fast());
+            assert!(!item.supports_extension_protocol());
+        }
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
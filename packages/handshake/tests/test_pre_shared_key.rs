@@ -0,0 +1,130 @@
+use common::{tracing_stderr_init, INIT};
+use futures::future::try_join;
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{AttemptFailure, AttemptStage, DiscoveryInfo, HandshakerBuilder, InitiateMessage, PreSharedKey, Protocol};
+use tokio::net::TcpStream;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_pre_shared_key_same_key() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .with_pre_shared_key(PreSharedKey::new("private swarm"))
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let handshaker_two_pid = [5u8; bt::PEER_ID_LEN].into();
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id(handshaker_two_pid)
+        .with_pre_shared_key(PreSharedKey::new("private swarm"))
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    let test = tokio::spawn(async move {
+        handshaker_one
+            .send(InitiateMessage::new(
+                Protocol::BitTorrent,
+                [55u8; bt::INFO_HASH_LEN].into(),
+                handshaker_two_addr,
+            ))
+            .await
+            .unwrap();
+
+        let handshaker_one_future = async {
+            let message: handshake::CompleteMessage<TcpStream> = handshaker_one.next().await.unwrap().unwrap();
+            Ok::<_, ()>(message)
+        };
+
+        let handshaker_two_future = async {
+            let message: handshake::CompleteMessage<TcpStream> = handshaker_two.next().await.unwrap().unwrap();
+            Ok::<_, ()>(message)
+        };
+
+        let (item_one, _item_two) = try_join(handshaker_one_future, handshaker_two_future).await.unwrap();
+
+        assert_eq!(handshaker_two_addr, *item_one.address());
+        assert_eq!(handshaker_two_pid, *item_one.peer_id());
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
+
+#[tokio::test]
+async fn negative_pre_shared_key_different_key() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .with_pre_shared_key(PreSharedKey::new("private swarm"))
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let (handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .with_pre_shared_key(PreSharedKey::new("public swarm"))
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    let mut events = handshaker_one.attempt_events();
+
+    let test = tokio::spawn(async move {
+        handshaker_one
+            .send(InitiateMessage::new(
+                Protocol::BitTorrent,
+                [55u8; bt::INFO_HASH_LEN].into(),
+                handshaker_two_addr,
+            ))
+            .await
+            .unwrap();
+
+        let stages: Vec<AttemptStage> = (&mut events).take(4).map(|event| event.stage()).collect().await;
+        assert_eq!(
+            vec![
+                AttemptStage::Dialing,
+                AttemptStage::Connected,
+                AttemptStage::HandshakeSent,
+                AttemptStage::Failed(AttemptFailure::Unauthenticated)
+            ],
+            stages
+        );
+
+        drop(handshaker_two);
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
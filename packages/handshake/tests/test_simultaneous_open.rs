@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use common::{tracing_stderr_init, INIT};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{DiscoveryInfo, HandshakerBuilder, HandshakerConfig, InitiateMessage, Protocol};
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_simultaneous_open_yields_single_connection() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let config = HandshakerConfig::default().with_dedup_window(Duration::from_millis(200));
+
+    let handshaker_one_addr = "127.0.0.1:0".parse().unwrap();
+    let handshaker_one_pid = [4u8; bt::PEER_ID_LEN].into();
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_one_addr)
+        .with_peer_id(handshaker_one_pid)
+        .with_config(config)
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_one_addr = handshaker_one_addr;
+    handshaker_one_addr.set_port(handshaker_one.port());
+
+    let handshaker_two_addr = "127.0.0.1:0".parse().unwrap();
+    let handshaker_two_pid = [5u8; bt::PEER_ID_LEN].into();
+
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_two_addr)
+        .with_peer_id(handshaker_two_pid)
+        .with_config(config)
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr = handshaker_two_addr;
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    let test = tokio::spawn(async move {
+        let hash = [55u8; bt::INFO_HASH_LEN].into();
+
+        // Both sides connect to each other at the same time
+        handshaker_one
+            .send(InitiateMessage::new(Protocol::BitTorrent, hash, handshaker_two_addr))
+            .await
+            .unwrap();
+        handshaker_two
+            .send(InitiateMessage::new(Protocol::BitTorrent, hash, handshaker_one_addr))
+            .await
+            .unwrap();
+
+        let item_one = handshaker_one.next().await.unwrap().unwrap();
+        let item_two = handshaker_two.next().await.unwrap().unwrap();
+
+        assert_eq!(handshaker_two_pid, *item_one.peer_id());
+        assert_eq!(handshaker_one_pid, *item_two.peer_id());
+
+        // Connection initiated by the lower peer id is the one that is kept
+        assert_eq!(handshaker_two_addr, *item_one.address());
+
+        // The duplicate connection should never be yielded
+        let duplicate_one = tokio::time::timeout(Duration::from_millis(400), handshaker_one.next()).await;
+        let duplicate_two = tokio::time::timeout(Duration::from_millis(10), handshaker_two.next()).await;
+
+        assert!(duplicate_one.is_err());
+        assert!(duplicate_two.is_err());
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
@@ -0,0 +1,75 @@
+use common::{tracing_stderr_init, INIT};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::{SniffedSocket, SniffingTransport, TcpTransport};
+use handshake::{DiscoveryInfo, HandshakerBuilder, InitiateMessage, Protocol};
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpStream;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+const HTTP_REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+#[tokio::test]
+async fn positive_share_port_with_other_protocol() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (other_send, mut other_recv) = tokio::sync::mpsc::unbounded_channel();
+    let transport = SniffingTransport::new(TcpTransport, move |socket: SniffedSocket<TcpStream>, addr| {
+        other_send.send((socket, addr)).unwrap();
+    });
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .build(transport)
+        .await
+        .unwrap();
+    let handshaker_one_addr = format!("127.0.0.1:{}", handshaker_one.port()).parse().unwrap();
+
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let test = tokio::spawn(async move {
+        // Connection speaking another protocol is routed to the callback, without losing any bytes
+        let mut http_client = TcpStream::connect(handshaker_one_addr).await.unwrap();
+        http_client.write_all(HTTP_REQUEST).await.unwrap();
+
+        let (mut socket, addr) = other_recv.recv().await.unwrap();
+        assert_eq!(http_client.local_addr().unwrap(), addr);
+
+        let mut request = vec![0u8; HTTP_REQUEST.len()];
+        socket.read_exact(&mut request).await.unwrap();
+        assert_eq!(HTTP_REQUEST, &request[..]);
+
+        // Handshakes on the same port still complete
+        handshaker_two
+            .send(InitiateMessage::new(
+                Protocol::BitTorrent,
+                [55u8; bt::INFO_HASH_LEN].into(),
+                handshaker_one_addr,
+            ))
+            .await
+            .unwrap();
+
+        let (item_one, item_two) = tokio::join!(handshaker_one.next(), handshaker_two.next());
+
+        assert_eq!([5u8; bt::PEER_ID_LEN], *item_one.unwrap().unwrap().peer_id().as_ref());
+        assert_eq!([4u8; bt::PEER_ID_LEN], *item_two.unwrap().unwrap().peer_id().as_ref());
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
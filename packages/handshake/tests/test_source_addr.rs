@@ -0,0 +1,117 @@
+use std::net::{IpAddr, SocketAddr};
+
+use common::{tracing_stderr_init, INIT};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::TcpTransport;
+use handshake::{AttemptFailure, AttemptStage, DiscoveryInfo, HandshakerBuilder, HandshakerConfig, InitiateMessage, Protocol};
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_connect_from_source_addr() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    // Any address in 127.0.0.0/8 is local, so connections can be bound to one other than 127.0.0.1
+    let source_addr: IpAddr = "127.0.0.2".parse().unwrap();
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .with_config(HandshakerConfig::default().with_source_addr(Some(source_addr)))
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    let test = tokio::spawn(async move {
+        handshaker_one
+            .send(InitiateMessage::new(
+                Protocol::BitTorrent,
+                [55u8; bt::INFO_HASH_LEN].into(),
+                handshaker_two_addr,
+            ))
+            .await
+            .unwrap();
+
+        let message = handshaker_two.next().await.unwrap().unwrap();
+        assert_eq!(source_addr, message.address().ip());
+
+        drop(handshaker_one);
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
+
+#[tokio::test]
+async fn negative_connect_from_unavailable_source_addr() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([4u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let (handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr("127.0.0.1:0".parse().unwrap())
+        .with_peer_id([5u8; bt::PEER_ID_LEN].into())
+        .build(TcpTransport)
+        .await
+        .unwrap();
+
+    let mut handshaker_two_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    handshaker_two_addr.set_port(handshaker_two.port());
+
+    // Address from TEST-NET-1, which is not assigned to any of our interfaces
+    let hash = [55u8; bt::INFO_HASH_LEN].into();
+    handshaker_one.set_torrent_source_addr(hash, Some("192.0.2.1".parse().unwrap()));
+
+    let mut events = handshaker_one.attempt_events();
+
+    let test = tokio::spawn(async move {
+        handshaker_one
+            .send(InitiateMessage::new(Protocol::BitTorrent, hash, handshaker_two_addr))
+            .await
+            .unwrap();
+
+        // The connection must fail rather than fall back to the default route
+        let stages: Vec<AttemptStage> = (&mut events).take(2).map(|event| event.stage()).collect().await;
+        assert_eq!(
+            vec![
+                AttemptStage::Dialing,
+                AttemptStage::Failed(AttemptFailure::Bind(std::io::ErrorKind::AddrNotAvailable))
+            ],
+            stages
+        );
+
+        drop(handshaker_two);
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+
+    res.unwrap();
+}
@@ -0,0 +1,97 @@
+#![cfg(unix)]
+
+use common::{tracing_stderr_init, INIT};
+use futures::future::try_join;
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use handshake::transports::UnixTransport;
+use handshake::{HandshakerBuilder, InitiateMessage, PeerAddr, Protocol};
+use tokio::net::UnixStream;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+
+mod common;
+
+#[tokio::test]
+async fn positive_connect_over_unix_socket() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let transport = UnixTransport::new();
+    let socket_paths: Vec<_> = ["one", "two"]
+        .iter()
+        .map(|name| std::env::temp_dir().join(format!("bip_handshake_{}_{name}.sock", std::process::id())))
+        .collect();
+    for path in &socket_paths {
+        if path.exists() {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    let handshaker_one_addr = transport.endpoint(&socket_paths[0]);
+    let handshaker_one_pid = [4u8; bt::PEER_ID_LEN].into();
+
+    let (mut handshaker_one, mut tasks_one) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_one_addr)
+        .with_peer_id(handshaker_one_pid)
+        .build(transport.clone())
+        .await
+        .unwrap();
+
+    let handshaker_two_addr = transport.endpoint(&socket_paths[1]);
+    let handshaker_two_pid = [5u8; bt::PEER_ID_LEN].into();
+
+    let (mut handshaker_two, mut tasks_two) = HandshakerBuilder::new()
+        .with_bind_addr(handshaker_two_addr)
+        .with_peer_id(handshaker_two_pid)
+        .build(transport.clone())
+        .await
+        .unwrap();
+
+    let test = tokio::spawn({
+        let transport = transport.clone();
+
+        async move {
+            handshaker_one
+                .send(InitiateMessage::new(
+                    Protocol::BitTorrent,
+                    [55u8; bt::INFO_HASH_LEN].into(),
+                    handshaker_two_addr,
+                ))
+                .await
+                .unwrap();
+
+            let handshaker_one_future = async {
+                let message: handshake::CompleteMessage<UnixStream> = handshaker_one.next().await.unwrap().unwrap();
+                Ok::<_, ()>(message)
+            };
+
+            let handshaker_two_future = async {
+                let message: handshake::CompleteMessage<UnixStream> = handshaker_two.next().await.unwrap().unwrap();
+                Ok::<_, ()>(message)
+            };
+
+            let (item_one, item_two) = try_join(handshaker_one_future, handshaker_two_future).await.unwrap();
+
+            // Result from handshaker one should match handshaker two's endpoint, the connecting socket is unnamed
+            assert_eq!(handshaker_two_addr, *item_one.address());
+            assert_eq!(PeerAddr::Unix(None), transport.peer_addr(*item_two.address()));
+
+            assert_eq!(handshaker_one_pid, *item_two.peer_id());
+            assert_eq!(handshaker_two_pid, *item_one.peer_id());
+        }
+    });
+
+    let res = test.await;
+
+    tasks_one.shutdown().await;
+    tasks_two.shutdown().await;
+    for path in &socket_paths {
+        if path.exists() {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    res.unwrap();
+}
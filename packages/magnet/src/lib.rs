@@ -1,3 +1,4 @@
+use metainfo::Info;
 use url::Url;
 use util::bt::InfoHash;
 use util::sha::ShaHash;
@@ -113,12 +114,85 @@ impl MagnetLink {
             _ => None,
         }
     }
+
+    /// Display name of the torrent, if the link carries one.
+    #[must_use]
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    /// Announce urls of the trackers listed in the link.
+    #[must_use]
+    pub fn trackers(&self) -> &[String] {
+        &self.address_tracker
+    }
+
+    /// Assemble the bytes of a metainfo file for the link, once its `Info` has been fetched.
+    ///
+    /// The info dictionary is copied verbatim, so the metainfo file keeps the info hash
+    /// of the link, and the trackers of the link are each placed in their own tier.
+    ///
+    /// Returns `None` if the `Info` does not match the info hash of the link.
+    #[must_use]
+    pub fn to_torrent(&self, info: &Info) -> Option<Vec<u8>> {
+        if self.get_info_hash() != Some(info.info_hash()) {
+            return None;
+        }
+
+        // Keys of the root dictionary are written in sorted order
+        let mut bytes = b"d".to_vec();
+        if let Some(main_tracker) = self.address_tracker.first() {
+            write_bytes(&mut bytes, b"announce");
+            write_bytes(&mut bytes, main_tracker.as_bytes());
+
+            write_bytes(&mut bytes, b"announce-list");
+            bytes.push(b'l');
+            for tracker in &self.address_tracker {
+                bytes.push(b'l');
+                write_bytes(&mut bytes, tracker.as_bytes());
+                bytes.push(b'e');
+            }
+            bytes.push(b'e');
+        }
+        write_bytes(&mut bytes, b"info");
+        bytes.extend_from_slice(info.as_bytes());
+        bytes.push(b'e');
+
+        Some(bytes)
+    }
+}
+
+/// Write the bencoded form of the byte string.
+fn write_bytes(dst: &mut Vec<u8>, src: &[u8]) {
+    dst.extend_from_slice(src.len().to_string().as_bytes());
+    dst.push(b':');
+    dst.extend_from_slice(src);
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fmt::Write as _;
+
+    use metainfo::{DirectAccessor, Metainfo, MetainfoBuilder};
     use util::sha::ShaHash;
 
+    fn downloaded_info() -> metainfo::Info {
+        let accessor = DirectAccessor::new("file.txt", b"Some file data for the magnet link");
+        let bytes = MetainfoBuilder::new()
+            .set_comment(Some("Not part of the info dictionary"))
+            .build(1, accessor, |_| ())
+            .unwrap();
+
+        Metainfo::from_bytes(bytes).unwrap().info().clone()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut hex, byte| {
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        })
+    }
+
     #[test]
     fn test_wikipedia() {
         /* cSpell:disable */
@@ -191,4 +265,36 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn positive_to_torrent_keeps_info_hash() {
+        let info = downloaded_info();
+        let url = format!(
+            "magnet:?xt=urn:btih:{}&dn=file.txt&tr=udp%3A%2F%2Ftracker.one%3A80&tr=http%3A%2F%2Ftracker.two%2Fannounce",
+            hex(info.info_hash().as_ref())
+        );
+        let link = crate::MagnetLink::parse(&url).unwrap();
+
+        let metainfo = Metainfo::from_bytes(link.to_torrent(&info).unwrap()).unwrap();
+
+        assert_eq!(info.info_hash(), metainfo.info().info_hash());
+        assert_eq!(info.as_bytes(), metainfo.info().as_bytes());
+        assert_eq!(Some("udp://tracker.one:80"), metainfo.main_tracker());
+        assert_eq!(
+            Some(&vec![
+                vec!["udp://tracker.one:80".to_string()],
+                vec!["http://tracker.two/announce".to_string()]
+            ]),
+            metainfo.trackers()
+        );
+    }
+
+    #[test]
+    fn negative_to_torrent_mismatched_info_hash() {
+        let info = downloaded_info();
+        let url = format!("magnet:?xt=urn:btih:{}", hex(&[0u8; 20]));
+        let link = crate::MagnetLink::parse(&url).unwrap();
+
+        assert_eq!(None, link.to_torrent(&info));
+    }
 }
@@ -3,8 +3,8 @@ use std::path::Path;
 
 use chrono::offset::{TimeZone, Utc};
 use metainfo::error::ParseError;
-use metainfo::{Metainfo, MetainfoBuilder};
-use pbr::ProgressBar;
+use metainfo::{BuildOutput, BuildStage, Metainfo, MetainfoBuilder};
+use pbr::{ProgressBar, Units};
 
 fn main() {
     println!("\nIMPORTANT: Remember to run in release mode for real world performance...\n");
@@ -37,27 +37,27 @@ fn create_torrent<S>(src_path: S) -> Result<Vec<u8>, ParseError>
 where
     S: AsRef<Path>,
 {
-    let count = 10000;
-    let mut pb = ProgressBar::new(count);
+    let mut pb = ProgressBar::new(0);
     pb.format("╢▌▌░╟");
+    pb.set_units(Units::Bytes);
 
     let builder = MetainfoBuilder::new()
         .set_created_by(Some("bip_metainfo"))
         .set_comment(Some("Just Some Comment"));
 
-    let mut prev_progress = 0;
-    builder.build(2, src_path, move |progress| {
-        #[allow(clippy::cast_possible_truncation)]
-        #[allow(clippy::cast_sign_loss)]
-        #[allow(clippy::cast_precision_loss)]
-        let whole_progress = (progress * (count as f64)) as u64;
-        let delta_progress = whole_progress - prev_progress;
+    builder
+        .build_with_progress(2, src_path, move |progress| {
+            pb.total = progress.total_bytes();
 
-        if delta_progress > 0 {
-            pb.add(delta_progress);
-        }
-        prev_progress = whole_progress;
-    })
+            match (progress.stage(), progress.current_file()) {
+                (BuildStage::Hashing, Some(file)) => pb.message(&format!("{} ", file.display())),
+                (BuildStage::Finished, _) => pb.message("Done "),
+                (BuildStage::Hashing, None) => (),
+            }
+
+            pb.set(progress.bytes_hashed());
+        })
+        .map(BuildOutput::into_bytes)
 }
 
 /// Print general information about the torrent.
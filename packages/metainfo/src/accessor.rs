@@ -3,6 +3,8 @@ use std::path::{Path, PathBuf};
 use util::sha::ShaHash;
 use walkdir::{self, DirEntry, WalkDir};
 
+use crate::builder::reproducible;
+
 /// Trait for types convertible as a Result into some Accessor.
 pub trait IntoAccessor {
     /// Concrete Accessor type that will be converted into.
@@ -82,11 +84,26 @@ pub enum PieceAccess<'a> {
 
 // ----------------------------------------------------------------------------//
 
+/// Predicate deciding whether a file, given its relative path, is included.
+type FileFilter = Box<dyn Fn(&Path) -> bool + Send + Sync>;
+
 /// Accessor that pulls data in from the file system.
+///
+/// When pointed at a directory, all files within the directory are walked. By default,
+/// symbolic links are skipped and hidden (dot) files are included; this, along with
+/// excluding files by glob pattern or predicate, can be configured before building.
 #[allow(clippy::module_name_repetitions)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FileAccessor {
     absolute_path: PathBuf,
     directory_name: Option<PathBuf>,
+    follow_symlinks: bool,
+    include_hidden: bool,
+    sort_files: bool,
+    exclude_patterns: Vec<String>,
+    filters: Vec<FileFilter>,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    use_io_uring: bool,
 }
 
 impl FileAccessor {
@@ -115,8 +132,147 @@ impl FileAccessor {
         Ok(FileAccessor {
             absolute_path,
             directory_name,
+            follow_symlinks: false,
+            include_hidden: true,
+            sort_files: false,
+            exclude_patterns: Vec::new(),
+            filters: Vec::new(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            use_io_uring: false,
         })
     }
+
+    /// Sets whether symbolic links should be followed (and the files they point to
+    /// included) or skipped entirely.
+    #[must_use]
+    pub fn with_follow_symlinks(mut self, follow: bool) -> FileAccessor {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets whether hidden files and directories (those starting with a `.`) should be included.
+    #[must_use]
+    pub fn with_hidden_files(mut self, include: bool) -> FileAccessor {
+        self.include_hidden = include;
+        self
+    }
+
+    /// Sets whether files should be sorted by their relative path (byte-wise, using `/` as the separator).
+    ///
+    /// Paths are normalized to NFC before being compared, giving the order expected by
+    /// `MetainfoBuilder::set_reproducible`.
+    ///
+    /// Directory entries are otherwise yielded in whatever order the file system returns them,
+    /// which can produce different info hashes for identical data on different machines.
+    #[must_use]
+    pub fn with_sorted_files(mut self, sort: bool) -> FileAccessor {
+        self.sort_files = sort;
+        self
+    }
+
+    /// Exclude all files matching the given glob pattern.
+    ///
+    /// Patterns support `*` and `?` wildcards which do not match across directories, as
+    /// well as `**` which does. Patterns without a `/` are matched against the file name
+    /// (for example, `*.tmp` or `.DS_Store`), otherwise they are matched against the path
+    /// of the file relative to the torrent directory (for example, `cache/**`).
+    #[must_use]
+    pub fn with_exclude_pattern<P>(mut self, pattern: P) -> FileAccessor
+    where
+        P: Into<String>,
+    {
+        self.exclude_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only include files for which the given predicate returns true.
+    ///
+    /// The predicate is given the path of the file relative to the torrent directory.
+    #[must_use]
+    pub fn with_filter<F>(mut self, filter: F) -> FileAccessor
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Sets whether files should be read through `io_uring`, keeping several large sequential
+    /// reads in flight instead of reading each piece on demand.
+    ///
+    /// If `io_uring` is unavailable on the running kernel, files are read as usual.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[must_use]
+    pub fn with_io_uring(mut self, enable: bool) -> FileAccessor {
+        self.use_io_uring = enable;
+        self
+    }
+
+    /// Number of leading components to strip from a walked path to make it relative.
+    fn num_skip_paths(&self) -> usize {
+        if self.access_directory().is_some() {
+            self.absolute_path.iter().count()
+        } else {
+            self.absolute_path.iter().count() - 1
+        }
+    }
+
+    /// Walk the files that should be included in the torrent, returning their entry and relative path.
+    fn walk_files(&self) -> std::io::Result<Vec<(DirEntry, PathBuf)>> {
+        let num_skip_paths = self.num_skip_paths();
+
+        let mut files = Vec::new();
+        for res_entry in WalkDir::new(&self.absolute_path)
+            .follow_links(self.follow_symlinks)
+            .into_iter()
+            .filter_entry(|entry| self.include_hidden || entry.depth() == 0 || !is_hidden(entry))
+            .filter(entry_file_filter)
+        {
+            let entry = res_entry?;
+
+            // TODO: Switch to using strip_relative when it is stabilized
+            let relative_path = entry.path().iter().skip(num_skip_paths).fold(PathBuf::new(), |mut acc, nex| {
+                acc.push(nex);
+                acc
+            });
+
+            if self.is_included(&relative_path) {
+                files.push((entry, relative_path));
+            }
+        }
+
+        if self.sort_files {
+            files.sort_by_cached_key(|(_, relative_path)| {
+                let components: Vec<String> = relative_path
+                    .iter()
+                    .map(|component| component.to_string_lossy().into_owned())
+                    .collect();
+
+                reproducible::sort_key(&components)
+            });
+        }
+
+        Ok(files)
+    }
+
+    /// Returns true if the file at the given relative path passes all exclusions and filters.
+    fn is_included(&self, relative_path: &Path) -> bool {
+        let file_name = relative_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let joined_path = joined_path(relative_path);
+
+        let excluded = self.exclude_patterns.iter().any(|pattern| {
+            if pattern.contains('/') {
+                glob_matches(pattern, &joined_path)
+            } else {
+                glob_matches(pattern, &file_name)
+            }
+        });
+
+        !excluded && self.filters.iter().all(|filter| filter(relative_path))
+    }
 }
 
 impl IntoAccessor for FileAccessor {
@@ -147,22 +303,10 @@ impl Accessor for FileAccessor {
     where
         C: FnMut(u64, &Path),
     {
-        let num_skip_paths = if self.access_directory().is_some() {
-            self.absolute_path.iter().count()
-        } else {
-            self.absolute_path.iter().count() - 1
-        };
-
-        for res_entry in WalkDir::new(&self.absolute_path).into_iter().filter(entry_file_filter) {
-            let entry = res_entry?;
+        for (entry, relative_path) in self.walk_files()? {
             let entry_metadata = entry.metadata()?;
 
             let file_length = entry_metadata.len();
-            // TODO: Switch to using strip_relative when it is stabilized
-            let relative_path = entry.path().iter().skip(num_skip_paths).fold(PathBuf::new(), |mut acc, nex| {
-                acc.push(nex);
-                acc
-            });
 
             callback(file_length, relative_path.as_path());
         }
@@ -174,10 +318,24 @@ impl Accessor for FileAccessor {
     where
         C: for<'a> FnMut(PieceAccess<'a>) -> std::io::Result<()>,
     {
-        for res_entry in WalkDir::new(&self.absolute_path).into_iter().filter(entry_file_filter) {
-            let entry = res_entry?;
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let mut opt_uring = if self.use_io_uring {
+            crate::uring::UringReader::new().ok()
+        } else {
+            None
+        };
+
+        for (entry, _) in self.walk_files()? {
             let mut file = std::fs::File::open(entry.path())?;
 
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            if let Some(uring) = opt_uring.as_mut() {
+                let mut reader = uring.read_file(&file)?;
+
+                callback(PieceAccess::Compute(&mut reader))?;
+                continue;
+            }
+
             callback(PieceAccess::Compute(&mut file))?;
         }
 
@@ -190,6 +348,45 @@ fn entry_file_filter(res_entry: &walkdir::Result<DirEntry>) -> bool {
     res_entry.as_ref().map(|f| f.file_type().is_file()).unwrap_or(true)
 }
 
+/// Joins the components of a relative path with `/`, regardless of platform.
+fn joined_path(relative_path: &Path) -> String {
+    relative_path
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns true if the entry is a hidden (dot) file or directory.
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Returns true if the text matches the glob pattern.
+///
+/// Supports `?` and `*` which match any character or run of characters except `/`, and `**`
+/// which matches any run of characters including `/`.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    glob_matches_chars(&pattern, &text)
+}
+
+fn glob_matches_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|skip| glob_matches_chars(rest, &text[skip..])),
+        ['*', rest @ ..] => {
+            let max_skip = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+
+            (0..=max_skip).any(|skip| glob_matches_chars(rest, &text[skip..]))
+        }
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && glob_matches_chars(rest, &text[1..]),
+        [p, rest @ ..] => matches!(text, [c, ..] if c == p) && glob_matches_chars(rest, &text[1..]),
+    }
+}
+
 // ----------------------------------------------------------------------------//
 
 /// Accessor that pulls data in directly from memory.
@@ -244,3 +441,134 @@ impl<'a> Accessor for DirectAccessor<'a> {
         callback(PieceAccess::Compute(&mut cursor))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::accessor::{glob_matches, Accessor, FileAccessor};
+
+    /// Creates a fresh directory tree under the system temp directory for walking.
+    fn create_test_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("metainfo_accessor_{name}_{}", rand::random::<u64>()));
+
+        for relative in ["a.txt", "b.tmp", ".DS_Store", "cache/c.txt", ".hidden/d.txt"] {
+            let path = root.join(relative);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, relative.as_bytes()).unwrap();
+        }
+
+        root
+    }
+
+    fn accessed_paths(accessor: &FileAccessor) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        accessor.access_metadata(|_, path| paths.push(path.to_path_buf())).unwrap();
+
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn positive_glob_matches_file_name() {
+        assert!(glob_matches("*.tmp", "b.tmp"));
+        assert!(glob_matches(".DS_Store", ".DS_Store"));
+        assert!(glob_matches("?.txt", "a.txt"));
+    }
+
+    #[test]
+    fn negative_glob_single_star_does_not_cross_directories() {
+        assert!(!glob_matches("*.txt", "cache/c.txt"));
+        assert!(!glob_matches("?", "/"));
+        assert!(!glob_matches("*.tmp", "b.tmp.bak"));
+    }
+
+    #[test]
+    fn positive_glob_double_star_crosses_directories() {
+        assert!(glob_matches("cache/**", "cache/c.txt"));
+        assert!(glob_matches("**/*.txt", "cache/nested/c.txt"));
+    }
+
+    #[test]
+    fn positive_file_accessor_default_includes_hidden() {
+        let root = create_test_tree("default");
+        let accessor = FileAccessor::new(&root).unwrap();
+
+        let paths = accessed_paths(&accessor);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(paths.len(), 5);
+    }
+
+    #[test]
+    fn positive_file_accessor_excludes_hidden_and_patterns() {
+        let root = create_test_tree("filtered");
+        let accessor = FileAccessor::new(&root)
+            .unwrap()
+            .with_hidden_files(false)
+            .with_exclude_pattern("*.tmp")
+            .with_filter(|path: &Path| !path.starts_with("cache"));
+
+        let paths = accessed_paths(&accessor);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(paths, vec![PathBuf::from("a.txt")]);
+    }
+
+    #[test]
+    fn positive_file_accessor_sorted_files() {
+        let root = create_test_tree("sorted");
+        let accessor = FileAccessor::new(&root).unwrap().with_sorted_files(true);
+
+        let mut paths = Vec::new();
+        accessor.access_metadata(|_, path| paths.push(path.to_path_buf())).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let expected: Vec<PathBuf> = [".DS_Store", ".hidden/d.txt", "a.txt", "b.tmp", "cache/c.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn positive_file_accessor_follow_symlinks() {
+        let root = create_test_tree("symlinks");
+        std::os::unix::fs::symlink(root.join("a.txt"), root.join("link.txt")).unwrap();
+
+        let skipped = accessed_paths(&FileAccessor::new(&root).unwrap());
+        let followed = accessed_paths(&FileAccessor::new(&root).unwrap().with_follow_symlinks(true));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!skipped.contains(&PathBuf::from("link.txt")));
+        assert!(followed.contains(&PathBuf::from("link.txt")));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn positive_file_accessor_io_uring_same_pieces() {
+        use crate::accessor::PieceAccess;
+
+        fn accessed_bytes(accessor: &FileAccessor) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            accessor
+                .access_pieces(|access| match access {
+                    PieceAccess::Compute(region) => region.read_to_end(&mut bytes).map(|_| ()),
+                    PieceAccess::PreComputed(_) => unreachable!(),
+                })
+                .unwrap();
+
+            bytes
+        }
+
+        let root = create_test_tree("io_uring");
+
+        let regular = accessed_bytes(&FileAccessor::new(&root).unwrap().with_sorted_files(true));
+        let uring = accessed_bytes(&FileAccessor::new(&root).unwrap().with_sorted_files(true).with_io_uring(true));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(regular, uring);
+    }
+}
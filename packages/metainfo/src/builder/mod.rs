@@ -1,15 +1,26 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::iter::ExactSizeIterator;
+use std::path::PathBuf;
 
-use bencode::{ben_bytes, ben_int, ben_map, BMutAccess, BRefAccess, BencodeMut};
+use bencode::{ben_bytes, ben_int, ben_list, ben_map, BDictAccess, BMutAccess, BRefAccess, BencodeMut};
 use util::sha::{self, ShaHash};
 
 use crate::accessor::{Accessor, IntoAccessor};
 use crate::error::ParseError;
+use crate::metainfo::{FileAttributes, Node};
 use crate::parse;
 
 mod buffer;
+mod padding;
+mod progress;
+pub(crate) mod reproducible;
 mod worker;
 
+use crate::builder::padding::PaddedAccessor;
+use crate::builder::progress::FileOffsets;
+pub use crate::builder::progress::{BuildProgress, BuildStage};
+
 // Piece length is inversely related to the file size.
 // Transfer reliability is inversely related to the piece length.
 // Transfer reliability is directly related to the file size.
@@ -50,6 +61,34 @@ pub enum PieceLength {
     Custom(usize),
 }
 
+/// Output of a build, containing the encoded bytes along with the files in the order they were hashed.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildOutput {
+    bytes: Vec<u8>,
+    files: Vec<PathBuf>,
+}
+
+impl BuildOutput {
+    /// Encoded bytes for the metainfo file (or info dictionary).
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Relative paths of all files, in the order they appear in the torrent and were hashed.
+    #[must_use]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Consume the output, returning the encoded bytes.
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
 /// Builder for generating a torrent file from some accessor.
 #[allow(clippy::module_name_repetitions)]
 pub struct MetainfoBuilder<'a> {
@@ -133,6 +172,36 @@ impl<'a> MetainfoBuilder<'a> {
         self
     }
 
+    /// Set or unset the DHT nodes used to bootstrap the torrent when it is trackerless.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if unable to get the dictionary.
+    #[must_use]
+    pub fn set_nodes(mut self, opt_nodes: Option<&'a [Node]>) -> MetainfoBuilder<'a> {
+        {
+            let dict_access = self.root.dict_mut().unwrap();
+
+            if let Some(nodes) = opt_nodes {
+                let mut list = BencodeMut::new_list();
+
+                {
+                    let list_access = list.list_mut().unwrap();
+
+                    for node in nodes {
+                        list_access.push(ben_list!(ben_bytes!(node.host()), ben_int!(i64::from(node.port()))));
+                    }
+                }
+
+                dict_access.insert(parse::NODES_KEY.into(), list);
+            } else {
+                dict_access.remove(parse::NODES_KEY);
+            }
+        }
+
+        self
+    }
+
     /// Set or unset the creation date for the torrent.
     ///
     /// # Panics
@@ -209,6 +278,37 @@ impl<'a> MetainfoBuilder<'a> {
         self
     }
 
+    /// Sets whether padding files (BEP 47) should be inserted so each file starts at a piece boundary.
+    #[must_use]
+    pub fn set_padding(mut self, padding: bool) -> MetainfoBuilder<'a> {
+        self.info = self.info.set_padding(padding);
+
+        self
+    }
+
+    /// Sets whether the torrent file should be built reproducibly, see `InfoBuilder::set_reproducible`.
+    ///
+    /// The created by is also omitted, as it usually names the platform or version of the client. The
+    /// creation date is only included if given with `set_creation_date`, so it can be fixed, for example
+    /// to the time of the release being packaged.
+    #[must_use]
+    pub fn set_reproducible(mut self, reproducible: bool) -> MetainfoBuilder<'a> {
+        self.info = self.info.set_reproducible(reproducible);
+
+        self
+    }
+
+    /// Set or unset the attributes (BEP 47) for the file with the given relative path.
+    #[must_use]
+    pub fn set_file_attributes<P>(mut self, path: P, opt_attributes: Option<FileAttributes>) -> MetainfoBuilder<'a>
+    where
+        P: Into<PathBuf>,
+    {
+        self.info = self.info.set_file_attributes(path, opt_attributes);
+
+        self
+    }
+
     /// Get decoded value of announce-list key
     ///
     /// # Panics
@@ -231,6 +331,17 @@ impl<'a> MetainfoBuilder<'a> {
         parse::parse_announce_url(dict_access).map(String::from)
     }
 
+    /// Get decoded value of nodes key
+    ///
+    /// # Panics
+    ///
+    /// It would panic if unable to get the dictionary.
+    pub fn get_nodes(&self) -> Option<Vec<Node>> {
+        let dict_access = self.root.dict().unwrap();
+
+        parse::parse_nodes(dict_access).map(parse::convert_nodes)
+    }
+
     /// Get decoded value of creation-date key
     ///
     /// # Panics
@@ -274,17 +385,38 @@ impl<'a> MetainfoBuilder<'a> {
     where
         A: IntoAccessor,
         C: FnMut(f64) + Send + 'static,
+    {
+        self.build_output(threads, accessor, progress).map(BuildOutput::into_bytes)
+    }
+
+    /// Build the metainfo file, returning the bytes along with the order the files were hashed in.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if unable to get the accessor.
+    pub fn build_output<A, C>(self, threads: usize, accessor: A, progress: C) -> Result<BuildOutput, ParseError>
+    where
+        A: IntoAccessor,
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.build_with_progress(threads, accessor, fraction_progress(progress))
+    }
+
+    /// Build the metainfo file, reporting detailed `BuildProgress` updates to the given callback.
+    ///
+    /// Every update has been reported by the time this returns.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if unable to get the accessor.
+    pub fn build_with_progress<A, C>(self, threads: usize, accessor: A, progress: C) -> Result<BuildOutput, ParseError>
+    where
+        A: IntoAccessor,
+        C: FnMut(BuildProgress) + Send + 'static,
     {
         let accessor = accessor.into_accessor()?;
 
-        build_with_accessor(
-            threads,
-            accessor,
-            progress,
-            Some(self.root),
-            self.info.info,
-            self.info.piece_length,
-        )
+        build_with_accessor(threads, accessor, progress, Some(self.root), self.info)
     }
 }
 
@@ -297,6 +429,9 @@ pub struct InfoBuilder<'a> {
     // Stored outside of root as some of the variants need the total
     // file sizes in order for the final piece length to be calculated.
     piece_length: PieceLength,
+    padding: bool,
+    reproducible: bool,
+    file_attributes: HashMap<PathBuf, FileAttributes>,
 }
 
 impl<'a> Default for InfoBuilder<'a> {
@@ -304,6 +439,9 @@ impl<'a> Default for InfoBuilder<'a> {
         Self {
             info: BencodeMut::new_dict(),
             piece_length: PieceLength::OptBalanced,
+            padding: false,
+            reproducible: false,
+            file_attributes: HashMap::new(),
         }
     }
 }
@@ -341,6 +479,51 @@ impl<'a> InfoBuilder<'a> {
         self
     }
 
+    /// Sets whether padding files (BEP 47) should be inserted so each file starts at a piece boundary.
+    ///
+    /// Padding files are placed in the `.pad` directory and have their data, which is all
+    /// zeros, hashed as part of the pieces; they never need to be written to disk. Files
+    /// given by the accessor that are in the `.pad` directory are always marked as padding.
+    #[must_use]
+    pub fn set_padding(mut self, padding: bool) -> InfoBuilder<'a> {
+        self.padding = padding;
+
+        self
+    }
+
+    /// Sets whether the info dictionary should be built reproducibly, so the same files give the same
+    /// bytes (and info hash) on every platform.
+    ///
+    /// Path components are normalized to NFC, with both `/` and `\` treated as separators, and the
+    /// attributes that depend on the file system (executable, hidden and symlink) are omitted. The files
+    /// must be given by the accessor in sorted order, see `FileAccessor::with_sorted_files`, otherwise
+    /// building fails with `ParseError::UnsortedFiles`.
+    #[must_use]
+    pub fn set_reproducible(mut self, reproducible: bool) -> InfoBuilder<'a> {
+        self.reproducible = reproducible;
+
+        self
+    }
+
+    /// Set or unset the attributes (BEP 47) for the file with the given relative path.
+    ///
+    /// Attributes set for files that the accessor does not provide are ignored.
+    #[must_use]
+    pub fn set_file_attributes<P>(mut self, path: P, opt_attributes: Option<FileAttributes>) -> InfoBuilder<'a>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        if let Some(attributes) = opt_attributes {
+            self.file_attributes.insert(path, attributes);
+        } else {
+            self.file_attributes.remove(&path);
+        }
+
+        self
+    }
+
     /// Build the metainfo file from the given accessor and the number of worker threads.
     ///
     /// # Errors
@@ -350,40 +533,114 @@ impl<'a> InfoBuilder<'a> {
     where
         A: IntoAccessor,
         C: FnMut(f64) + Send + 'static,
+    {
+        self.build_output(threads, accessor, progress).map(BuildOutput::into_bytes)
+    }
+
+    /// Build the info dictionary, returning the bytes along with the order the files were hashed in.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if unable to get the accessor.
+    pub fn build_output<A, C>(self, threads: usize, accessor: A, progress: C) -> Result<BuildOutput, ParseError>
+    where
+        A: IntoAccessor,
+        C: FnMut(f64) + Send + 'static,
+    {
+        self.build_with_progress(threads, accessor, fraction_progress(progress))
+    }
+
+    /// Build the info dictionary, reporting detailed `BuildProgress` updates to the given callback.
+    ///
+    /// Every update has been reported by the time this returns.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if unable to get the accessor.
+    pub fn build_with_progress<A, C>(self, threads: usize, accessor: A, progress: C) -> Result<BuildOutput, ParseError>
+    where
+        A: IntoAccessor,
+        C: FnMut(BuildProgress) + Send + 'static,
     {
         let accessor = accessor.into_accessor()?;
 
-        build_with_accessor(threads, accessor, progress, None, self.info, self.piece_length)
+        build_with_accessor(threads, accessor, progress, None, self)
     }
 }
 
 // ----------------------------------------------------------------------------//
 
+/// Adapt a callback taking the fraction of pieces hashed into one taking `BuildProgress` updates.
+fn fraction_progress<C>(mut progress: C) -> impl FnMut(BuildProgress) + Send + 'static
+where
+    C: FnMut(f64) + Send + 'static,
+{
+    move |update: BuildProgress| {
+        if update.stage() == BuildStage::Hashing {
+            progress(update.fraction());
+        }
+    }
+}
+
 fn build_with_accessor<'a, A, C>(
     threads: usize,
     accessor: A,
     progress: C,
     opt_root: Option<BencodeMut<'a>>,
-    info: BencodeMut<'a>,
-    piece_length: PieceLength,
-) -> Result<Vec<u8>, ParseError>
+    builder: InfoBuilder<'a>,
+) -> Result<BuildOutput, ParseError>
 where
     A: Accessor,
-    C: FnMut(f64) + Send + 'static,
+    C: FnMut(BuildProgress) + Send + 'static,
 {
     assert!(threads != 0, "bip_metainfo: Cannot Build Metainfo File With threads == 0");
 
     // Collect all of the file information into a list
     let mut files_info = Vec::new();
+    let mut files = Vec::new();
     accessor.access_metadata(|len, path| {
         let path_list: Vec<String> = path.iter().map(|os_str| os_str.to_string_lossy().into_owned()).collect();
+        let is_padding = padding::is_padding_path(&path_list);
 
-        files_info.push((len, path_list));
+        files_info.push((len, path_list, is_padding));
+        files.push(path.to_path_buf());
     })?;
 
+    let InfoBuilder {
+        info,
+        piece_length,
+        padding,
+        reproducible,
+        file_attributes,
+    } = builder;
+
+    let (files_info, file_attributes) = if reproducible {
+        let file_attributes = file_attributes
+            .into_iter()
+            .map(|(path, attributes)| {
+                let components: Vec<String> = path.iter().map(|os_str| os_str.to_string_lossy().into_owned()).collect();
+
+                (reproducible::normalize_path(&components).iter().collect(), attributes)
+            })
+            .collect();
+
+        (reproducible::normalize_files(files_info)?, file_attributes)
+    } else {
+        (files_info, file_attributes)
+    };
+
+    // Piece length is determined before padding, as the padding depends on it
+    let piece_length = determine_piece_length(files_info.iter().fold(0, |acc, nex| acc + nex.0), &piece_length);
+
+    let (files_info, pads) = if padding {
+        padding::insert_padding(files_info, piece_length as u64)
+    } else {
+        (files_info, Vec::new())
+    };
+    let accessor = PaddedAccessor::new(accessor, &files_info, pads);
+
     // Build the pieces for the data our accessor is pointing at
     let total_files_len = files_info.iter().fold(0, |acc, nex| acc + nex.0);
-    let piece_length = determine_piece_length(total_files_len, &piece_length);
 
     #[allow(clippy::cast_precision_loss)]
     let total_num_pieces = (total_files_len as f64) / (piece_length as f64);
@@ -396,9 +653,16 @@ where
     #[allow(clippy::cast_possible_truncation)]
     let total_num_pieces: i64 = total_num_pieces.ceil() as i64;
 
+    let file_offsets = FileOffsets::new(
+        files_info
+            .iter()
+            .map(|(len, path, _)| (*len, path.iter().collect::<PathBuf>())),
+    );
+
     let pieces_list = worker::start_hasher_workers(
         &accessor,
         piece_length,
+        file_offsets,
         total_num_pieces.try_into().unwrap(),
         threads,
         progress,
@@ -406,7 +670,15 @@ where
     let pieces = map_pieces_list(pieces_list.into_iter().map(|(_, piece)| piece));
 
     let mut single_file_name = String::new();
-    let access_directory = accessor.access_directory().map(std::path::Path::to_string_lossy);
+    let access_directory = accessor.access_directory().map(|directory| {
+        let directory = directory.to_string_lossy();
+
+        if reproducible {
+            Cow::Owned(reproducible::normalize_name(&directory))
+        } else {
+            directory
+        }
+    });
 
     // Move these below access directory for borrow checker
     let mut info = info;
@@ -428,7 +700,7 @@ where
                     let bencode_files_access = bencode_files.list_mut().unwrap();
 
                     // Multi File
-                    for &(len, ref path) in &files_info {
+                    for &(len, ref path, is_padding) in &files_info {
                         let mut bencode_path = BencodeMut::new_list();
 
                         {
@@ -439,10 +711,18 @@ where
                             }
                         }
 
-                        bencode_files_access.push(ben_map! {
+                        let mut bencode_file = ben_map! {
                             parse::LENGTH_KEY => ben_int!(len.try_into().unwrap()),
                             parse::PATH_KEY   => bencode_path
-                        });
+                        };
+                        insert_file_attributes(
+                            bencode_file.dict_mut().unwrap(),
+                            file_attributes.get(&path.iter().collect::<PathBuf>()),
+                            is_padding,
+                            reproducible,
+                        );
+
+                        bencode_files_access.push(bencode_file);
                     }
                 }
 
@@ -456,7 +736,7 @@ where
                     let bencode_files_access = bencode_files.list_mut().unwrap();
 
                     // Multi File
-                    for &(len, ref path) in &files_info {
+                    for &(len, ref path, is_padding) in &files_info {
                         let mut bencode_path = BencodeMut::new_list();
 
                         {
@@ -467,10 +747,18 @@ where
                             }
                         }
 
-                        bencode_files_access.push(ben_map! {
+                        let mut bencode_file = ben_map! {
                             parse::LENGTH_KEY => ben_int!(len.try_into().unwrap()),
                             parse::PATH_KEY   => bencode_path
-                        });
+                        };
+                        insert_file_attributes(
+                            bencode_file.dict_mut().unwrap(),
+                            file_attributes.get(&path.iter().collect::<PathBuf>()),
+                            is_padding,
+                            reproducible,
+                        );
+
+                        bencode_files_access.push(bencode_file);
                     }
                 }
 
@@ -485,16 +773,68 @@ where
 
                 info_access.insert(parse::LENGTH_KEY.into(), ben_int!(files_info[0].0.try_into().unwrap()));
                 info_access.insert(parse::NAME_KEY.into(), ben_bytes!(&single_file_name[..]));
+                insert_file_attributes(info_access, file_attributes.get(&files[0]), files_info[0].2, reproducible);
             }
         }
     }
 
-    if let Some(mut root) = opt_root {
-        root.dict_mut().unwrap().insert(parse::INFO_KEY.into(), info);
+    let bytes = if let Some(mut root) = opt_root {
+        let root_access = root.dict_mut().unwrap();
+
+        if reproducible {
+            root_access.remove(parse::CREATED_BY_KEY);
+        }
+        root_access.insert(parse::INFO_KEY.into(), info);
 
-        Ok(root.encode())
+        root.encode()
     } else {
-        Ok(info.encode())
+        info.encode()
+    };
+
+    Ok(BuildOutput { bytes, files })
+}
+
+/// Insert the attributes (BEP 47) of a file into its info or file dictionary.
+fn insert_file_attributes<'b>(
+    dict_access: &mut dyn BDictAccess<Cow<'b, [u8]>, BencodeMut<'b>>,
+    opt_attributes: Option<&FileAttributes>,
+    is_padding: bool,
+    reproducible: bool,
+) {
+    let mut attr = opt_attributes
+        .map(|attributes| attributes.attr().to_owned())
+        .unwrap_or_default();
+    if reproducible {
+        reproducible::strip_environment_attrs(&mut attr);
+    }
+    if is_padding && !attr.contains(parse::PADDING_ATTR) {
+        attr.push_str(parse::PADDING_ATTR);
+    }
+
+    if !attr.is_empty() {
+        dict_access.insert(parse::ATTR_KEY.into(), ben_bytes!(attr));
+    }
+
+    let Some(attributes) = opt_attributes else {
+        return;
+    };
+
+    if let Some(symlink_path) = attributes.symlink_path().filter(|_| !reproducible) {
+        let mut bencode_path = BencodeMut::new_list();
+
+        {
+            let bencode_path_access = bencode_path.list_mut().unwrap();
+
+            for path_element in symlink_path {
+                bencode_path_access.push(ben_bytes!(path_element.to_string_lossy().into_owned()));
+            }
+        }
+
+        dict_access.insert(parse::SYMLINK_PATH_KEY.into(), bencode_path);
+    }
+
+    if let Some(sha1) = attributes.sha1() {
+        dict_access.insert(parse::SHA1_KEY.into(), ben_bytes!(sha1.as_ref().to_vec()));
     }
 }
 
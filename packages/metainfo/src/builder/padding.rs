@@ -0,0 +1,253 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::accessor::{Accessor, PieceAccess};
+
+/// Directory that padding files are placed in, as recommended by BEP 47.
+pub const PADDING_DIRECTORY: &str = ".pad";
+
+/// File in the torrent, as its length, path components, and whether it is a padding file.
+pub type FileEntry = (u64, Vec<String>, bool);
+
+/// Returns true if the given path components place the file in the padding directory.
+pub fn is_padding_path(path: &[String]) -> bool {
+    path.first().is_some_and(|component| component == PADDING_DIRECTORY)
+}
+
+/// Insert a padding file after every file that does not end on a piece boundary, so that
+/// each file starts at a piece boundary.
+///
+/// Padding is not inserted after the last file containing data, nor before an existing
+/// padding file. Returns the padded files along with the (offset, length) of each run of
+/// zeros to insert into the data of the accessor.
+pub fn insert_padding(files: Vec<FileEntry>, piece_length: u64) -> (Vec<FileEntry>, Vec<(u64, u64)>) {
+    let mut remaining_bytes: u64 = files.iter().map(|file| file.0).sum();
+    let mut data_offset = 0;
+    let mut padded_offset = 0;
+
+    let mut padded_files = Vec::with_capacity(files.len());
+    let mut pads = Vec::new();
+
+    let mut files = files.into_iter().peekable();
+    while let Some(file) = files.next() {
+        let (len, is_padding) = (file.0, file.2);
+
+        remaining_bytes -= len;
+        data_offset += len;
+        padded_offset += len;
+        padded_files.push(file);
+
+        let next_is_padding = files.peek().is_some_and(|next| next.2);
+        let unaligned = padded_offset % piece_length;
+
+        if !is_padding && !next_is_padding && remaining_bytes != 0 && unaligned != 0 {
+            let pad_len = piece_length - unaligned;
+
+            padded_files.push((pad_len, vec![PADDING_DIRECTORY.to_owned(), pad_len.to_string()], true));
+            pads.push((data_offset, pad_len));
+            padded_offset += pad_len;
+        }
+    }
+
+    (padded_files, pads)
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Accessor that inserts the zeros for padding files into the data of another accessor.
+pub struct PaddedAccessor<A> {
+    accessor: A,
+    files: Vec<(u64, PathBuf)>,
+    pads: Vec<(u64, u64)>,
+}
+
+impl<A> PaddedAccessor<A> {
+    pub fn new(accessor: A, files: &[FileEntry], pads: Vec<(u64, u64)>) -> PaddedAccessor<A> {
+        let files = files.iter().map(|(len, path, _)| (*len, path.iter().collect())).collect();
+
+        PaddedAccessor { accessor, files, pads }
+    }
+}
+
+impl<A> Accessor for PaddedAccessor<A>
+where
+    A: Accessor,
+{
+    fn access_directory(&self) -> Option<&Path> {
+        self.accessor.access_directory()
+    }
+
+    fn access_metadata<C>(&self, mut callback: C) -> std::io::Result<()>
+    where
+        C: FnMut(u64, &Path),
+    {
+        for (len, path) in &self.files {
+            callback(*len, path);
+        }
+
+        Ok(())
+    }
+
+    fn access_pieces<C>(&self, mut callback: C) -> std::io::Result<()>
+    where
+        C: for<'a> FnMut(PieceAccess<'a>) -> std::io::Result<()>,
+    {
+        let mut position = PaddingPosition::default();
+
+        self.accessor.access_pieces(|piece_access| match piece_access {
+            PieceAccess::Compute(region) => callback(PieceAccess::Compute(&mut PaddingReader {
+                inner: region,
+                pads: &self.pads,
+                position: &mut position,
+            })),
+            PieceAccess::PreComputed(hash) => callback(PieceAccess::PreComputed(hash)),
+        })?;
+
+        // Padding after the last of the data (before some empty files) has not been read yet
+        if position.next_pad < self.pads.len() || position.pad_left != 0 {
+            callback(PieceAccess::Compute(&mut PaddingReader {
+                inner: &mut std::io::empty(),
+                pads: &self.pads,
+                position: &mut position,
+            }))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Position within the data of an accessor, shared between each of its regions.
+#[derive(Default)]
+struct PaddingPosition {
+    data_offset: u64,
+    next_pad: usize,
+    pad_left: u64,
+}
+
+/// Reader over a region of accessor data that inserts the zeros for padding files.
+struct PaddingReader<'a, 'b> {
+    inner: &'a mut dyn Read,
+    pads: &'b [(u64, u64)],
+    position: &'b mut PaddingPosition,
+}
+
+impl<'a, 'b> Read for PaddingReader<'a, 'b> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = &mut *self.position;
+
+        loop {
+            if position.pad_left != 0 {
+                let zeros = usize::try_from(position.pad_left).unwrap_or(usize::MAX).min(buf.len());
+                buf[..zeros].fill(0);
+                position.pad_left -= zeros as u64;
+
+                return Ok(zeros);
+            }
+
+            match self.pads.get(position.next_pad) {
+                Some(&(offset, len)) if offset == position.data_offset => {
+                    position.pad_left = len;
+                    position.next_pad += 1;
+                }
+                Some(&(offset, _)) => {
+                    let max_read = usize::try_from(offset - position.data_offset)
+                        .unwrap_or(usize::MAX)
+                        .min(buf.len());
+                    let bytes_read = self.inner.read(&mut buf[..max_read])?;
+                    position.data_offset += bytes_read as u64;
+
+                    return Ok(bytes_read);
+                }
+                None => {
+                    let bytes_read = self.inner.read(buf)?;
+                    position.data_offset += bytes_read as u64;
+
+                    return Ok(bytes_read);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::accessor::{Accessor, PieceAccess};
+    use crate::builder::padding::{insert_padding, FileEntry, PaddedAccessor};
+
+    fn file(len: u64, name: &str) -> FileEntry {
+        (len, vec![name.to_owned()], false)
+    }
+
+    struct SplitAccessor(Vec<Vec<u8>>);
+
+    impl Accessor for SplitAccessor {
+        fn access_directory(&self) -> Option<&Path> {
+            None
+        }
+
+        fn access_metadata<C>(&self, _callback: C) -> std::io::Result<()>
+        where
+            C: FnMut(u64, &Path),
+        {
+            Ok(())
+        }
+
+        fn access_pieces<C>(&self, mut callback: C) -> std::io::Result<()>
+        where
+            C: for<'a> FnMut(PieceAccess<'a>) -> std::io::Result<()>,
+        {
+            for region in &self.0 {
+                callback(PieceAccess::Compute(&mut &region[..]))?;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn positive_insert_padding_aligns_files() {
+        let files = vec![file(3, "one"), file(8, "two"), file(5, "three"), file(0, "empty")];
+
+        let (padded, pads) = insert_padding(files, 4);
+
+        let lens: Vec<_> = padded.iter().map(|(len, _, is_padding)| (*len, *is_padding)).collect();
+        assert_eq!(vec![(3, false), (1, true), (8, false), (5, false), (0, false)], lens);
+        assert_eq!(vec![".pad".to_owned(), "1".to_owned()], padded[1].1);
+        assert_eq!(vec![(3, 1)], pads);
+    }
+
+    #[test]
+    fn positive_insert_padding_keeps_existing_padding() {
+        let files = vec![
+            file(3, "one"),
+            (1, vec![".pad".to_owned(), "1".to_owned()], true),
+            file(2, "two"),
+        ];
+
+        let (padded, pads) = insert_padding(files.clone(), 4);
+
+        assert_eq!(files, padded);
+        assert!(pads.is_empty());
+    }
+
+    #[test]
+    fn positive_padded_accessor_inserts_zeros() {
+        let accessor = SplitAccessor(vec![vec![1, 1, 1], vec![2, 2], vec![2, 3]]);
+        let padded = PaddedAccessor::new(accessor, &[], vec![(3, 1), (5, 3)]);
+
+        let mut data = Vec::new();
+        padded
+            .access_pieces(|piece_access| {
+                let PieceAccess::Compute(region) = piece_access else {
+                    panic!("bip_metainfo: Expected data to be computed...")
+                };
+
+                region.read_to_end(&mut data).map(|_| ())
+            })
+            .unwrap();
+
+        assert_eq!(vec![1, 1, 1, 0, 2, 2, 0, 0, 0, 2, 3], data);
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+/// Stage of a build that a `BuildProgress` update was reported in.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuildStage {
+    /// Pieces are being read and hashed.
+    Hashing,
+    /// All pieces have been hashed.
+    Finished,
+}
+
+/// Progress update for a build, reported to the progress callback.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildProgress {
+    stage: BuildStage,
+    bytes_hashed: u64,
+    total_bytes: u64,
+    current_file: Option<PathBuf>,
+    pieces_done: u64,
+    pieces_total: u64,
+}
+
+impl BuildProgress {
+    pub(crate) fn new(
+        stage: BuildStage,
+        bytes_hashed: u64,
+        total_bytes: u64,
+        current_file: Option<PathBuf>,
+        pieces_done: u64,
+        pieces_total: u64,
+    ) -> BuildProgress {
+        BuildProgress {
+            stage,
+            bytes_hashed,
+            total_bytes,
+            current_file,
+            pieces_done,
+            pieces_total,
+        }
+    }
+
+    /// Stage of the build.
+    #[must_use]
+    pub fn stage(&self) -> BuildStage {
+        self.stage
+    }
+
+    /// Number of bytes that have been read for hashing.
+    #[must_use]
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed
+    }
+
+    /// Total number of bytes across all files.
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Relative path of the file that the last hashed byte belongs to, if known.
+    #[must_use]
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current_file.as_deref()
+    }
+
+    /// Number of pieces that have been read for hashing.
+    #[must_use]
+    pub fn pieces_done(&self) -> u64 {
+        self.pieces_done
+    }
+
+    /// Total number of pieces.
+    #[must_use]
+    pub fn pieces_total(&self) -> u64 {
+        self.pieces_total
+    }
+
+    /// Fraction of pieces done, between 0 and 1.
+    #[must_use]
+    pub fn fraction(&self) -> f64 {
+        if self.pieces_total == 0 {
+            return 1.0;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = (self.pieces_done as f64) / (self.pieces_total as f64);
+
+        fraction
+    }
+}
+
+/// Tracks which file a byte offset falls within.
+pub(crate) struct FileOffsets {
+    // End offset of each file, along with its path
+    files: Vec<(u64, PathBuf)>,
+}
+
+impl FileOffsets {
+    /// Create a new `FileOffsets` from the lengths and paths of the files, in hashing order.
+    pub(crate) fn new<I>(files: I) -> FileOffsets
+    where
+        I: IntoIterator<Item = (u64, PathBuf)>,
+    {
+        let mut end = 0;
+        let files = files
+            .into_iter()
+            .map(|(len, path)| {
+                end += len;
+
+                (end, path)
+            })
+            .collect();
+
+        FileOffsets { files }
+    }
+
+    /// Total number of bytes across all files.
+    pub(crate) fn total_bytes(&self) -> u64 {
+        self.files.last().map_or(0, |(end, _)| *end)
+    }
+
+    /// Path of the file containing the byte just before the given offset.
+    pub(crate) fn file_ending_at(&self, offset: u64) -> Option<&Path> {
+        let index = self.files.partition_point(|(end, _)| *end < offset);
+
+        self.files.get(index).map(|(_, path)| path.as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{BuildProgress, BuildStage, FileOffsets};
+
+    #[test]
+    fn positive_file_ending_at() {
+        let offsets = FileOffsets::new(vec![
+            (10, PathBuf::from("a")),
+            (0, PathBuf::from("b")),
+            (5, PathBuf::from("c")),
+        ]);
+
+        assert_eq!(15, offsets.total_bytes());
+        assert_eq!(Some(Path::new("a")), offsets.file_ending_at(1));
+        assert_eq!(Some(Path::new("a")), offsets.file_ending_at(10));
+        assert_eq!(Some(Path::new("c")), offsets.file_ending_at(11));
+        assert_eq!(Some(Path::new("c")), offsets.file_ending_at(15));
+        assert_eq!(None, offsets.file_ending_at(16));
+    }
+
+    #[test]
+    fn positive_fraction() {
+        let progress = BuildProgress::new(BuildStage::Hashing, 5, 20, None, 1, 4);
+        let empty = BuildProgress::new(BuildStage::Finished, 0, 0, None, 0, 0);
+
+        assert!((progress.fraction() - 0.25).abs() < f64::EPSILON);
+        assert!((empty.fraction() - 1.0).abs() < f64::EPSILON);
+    }
+}
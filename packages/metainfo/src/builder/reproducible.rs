@@ -0,0 +1,100 @@
+use unicode_normalization::UnicodeNormalization as _;
+
+use crate::builder::padding::FileEntry;
+use crate::error::ParseError;
+use crate::parse;
+
+/// Attributes (BEP 47) that depend on the file system the torrent was created on.
+const ENVIRONMENT_ATTRS: [char; 3] = [parse::EXECUTABLE_ATTR, parse::HIDDEN_ATTR, parse::SYMLINK_ATTR];
+
+/// Normalize a path component to NFC.
+pub fn normalize_name(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// Normalize the path components of a file, splitting any component on either separator.
+///
+/// Windows file systems treat `\` as a separator where others allow it within a file name,
+/// so it is always treated as a separator to give the same path on every platform.
+pub fn normalize_path(path: &[String]) -> Vec<String> {
+    path.iter()
+        .flat_map(|component| component.split(['/', '\\']))
+        .filter(|component| !component.is_empty())
+        .map(normalize_name)
+        .collect()
+}
+
+/// Normalize the paths of all files, which must be given in sorted order.
+///
+/// The pieces are hashed in the order the accessor gives the files, so the files can not be
+/// sorted here without changing the data; an accessor should instead be asked to sort them,
+/// for example with `FileAccessor::with_sorted_files`.
+pub fn normalize_files(files: Vec<FileEntry>) -> Result<Vec<FileEntry>, ParseError> {
+    let files: Vec<FileEntry> = files
+        .into_iter()
+        .map(|(len, path, is_padding)| (len, normalize_path(&path), is_padding))
+        .collect();
+
+    let mut data_paths = files.iter().filter(|file| !file.2).map(|file| file.1.join("/"));
+    if let Some(mut previous) = data_paths.next() {
+        for path in data_paths {
+            if previous >= path {
+                return Err(ParseError::UnsortedFiles {
+                    details: format!("{path} was given after {previous}"),
+                });
+            }
+
+            previous = path;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Key that files are sorted by, their normalized path joined with `/` and compared byte-wise.
+pub fn sort_key(path: &[String]) -> String {
+    normalize_path(path).join("/")
+}
+
+/// Remove the attributes that depend on the file system the torrent was created on.
+pub fn strip_environment_attrs(attr: &mut String) {
+    attr.retain(|c| !ENVIRONMENT_ATTRS.contains(&c));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::reproducible::{normalize_files, normalize_path, strip_environment_attrs};
+    use crate::error::ParseError;
+
+    fn path(components: &[&str]) -> Vec<String> {
+        components.iter().map(|component| (*component).to_owned()).collect()
+    }
+
+    #[test]
+    fn positive_normalize_path() {
+        // Decomposed "é" followed by a Windows style separator
+        let normalized = normalize_path(&path(&["cafe\u{301}\\menu", "a.txt"]));
+
+        assert_eq!(path(&["caf\u{e9}", "menu", "a.txt"]), normalized);
+    }
+
+    #[test]
+    fn positive_strip_environment_attrs() {
+        let mut attr = "xhlp".to_owned();
+
+        strip_environment_attrs(&mut attr);
+
+        assert_eq!("p", attr);
+    }
+
+    #[test]
+    fn negative_unsorted_files() {
+        let files = vec![
+            (1, path(&["b"]), false),
+            (1, path(&[".pad", "1"]), true),
+            (1, path(&["a"]), false),
+        ];
+
+        assert!(matches!(normalize_files(files), Err(ParseError::UnsortedFiles { .. })));
+    }
+}
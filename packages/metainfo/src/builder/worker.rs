@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::sync::{mpsc, Arc};
 
 use crossbeam::queue::SegQueue;
@@ -5,6 +6,7 @@ use util::sha::ShaHash;
 
 use crate::accessor::{Accessor, PieceAccess};
 use crate::builder::buffer::{PieceBuffer, PieceBuffers};
+use crate::builder::progress::{BuildProgress, BuildStage, FileOffsets};
 use crate::error::ParseError;
 
 /// Messages sent to the master hasher.
@@ -24,16 +26,19 @@ enum WorkerMessage {
 }
 
 /// Starts a number of hasher workers which will generate the hash pieces for the files we send to it.
+///
+/// All progress updates will have been delivered by the time this returns.
 pub fn start_hasher_workers<A, C>(
     accessor: A,
     piece_length: usize,
+    files: FileOffsets,
     num_pieces: u64,
     num_workers: usize,
     progress: C,
 ) -> Result<Vec<(usize, ShaHash)>, ParseError>
 where
     A: Accessor,
-    C: FnMut(f64) + Send + 'static,
+    C: FnMut(BuildProgress) + Send + 'static,
 {
     // Create channels to communicate with the master
     let (master_send, master_recv) = mpsc::channel();
@@ -57,12 +62,20 @@ where
     }
 
     // Create a worker thread to execute the user callback for the progress update
-    std::thread::spawn(move || {
-        start_progress_updater(prog_recv, num_pieces, progress);
+    let progress_updater = std::thread::spawn(move || {
+        start_progress_updater(prog_recv, piece_length as u64, &files, num_pieces, progress);
     });
 
     // Create the master worker to coordinate between the workers
-    start_hash_master(accessor, num_workers, &master_recv, &work_queue, &piece_buffers, &prog_send)
+    let result = start_hash_master(accessor, num_workers, &master_recv, &work_queue, &piece_buffers, &prog_send);
+
+    // Closing the channel lets the updater report the final progress and exit
+    drop(prog_send);
+    if progress_updater.join().is_err() {
+        // TODO: Add logging here
+    }
+
+    result
 }
 
 // ----------------------------------------------------------------------------//
@@ -160,16 +173,39 @@ where
 
 // ----------------------------------------------------------------------------//
 
-fn start_progress_updater<C>(recv: mpsc::Receiver<usize>, num_pieces: u64, mut progress: C)
-where
-    C: FnMut(f64),
+fn start_progress_updater<C>(
+    recv: mpsc::Receiver<usize>,
+    piece_length: u64,
+    files: &FileOffsets,
+    num_pieces: u64,
+    mut progress: C,
+) where
+    C: FnMut(BuildProgress),
 {
-    for finished_piece in recv {
-        #[allow(clippy::cast_precision_loss)]
-        let percent_complete = (finished_piece as f64) / (num_pieces as f64);
+    let total_bytes = files.total_bytes();
 
-        progress(percent_complete);
+    for finished_piece in recv {
+        let bytes_hashed = (finished_piece as u64).saturating_mul(piece_length).min(total_bytes);
+        let current_file = files.file_ending_at(bytes_hashed).map(Path::to_path_buf);
+
+        progress(BuildProgress::new(
+            BuildStage::Hashing,
+            bytes_hashed,
+            total_bytes,
+            current_file,
+            finished_piece as u64,
+            num_pieces,
+        ));
     }
+
+    progress(BuildProgress::new(
+        BuildStage::Finished,
+        total_bytes,
+        total_bytes,
+        None,
+        num_pieces,
+        num_pieces,
+    ));
 }
 
 // ----------------------------------------------------------------------------//
@@ -206,12 +242,13 @@ fn start_hash_worker(send: &mpsc::Sender<MasterMessage>, work: &Arc<SegQueue<Wor
 mod tests {
 
     use std::ops::{Index, Range};
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
     use std::sync::mpsc;
 
     use util::sha::ShaHash;
 
     use crate::accessor::{Accessor, PieceAccess};
+    use crate::builder::progress::{BuildProgress, BuildStage, FileOffsets};
     use crate::builder::worker;
 
     // Keep these numbers fairly small to avoid lengthy tests
@@ -292,9 +329,18 @@ mod tests {
         #[allow(clippy::cast_possible_truncation)]
         let total_num_pieces: i64 = total_num_pieces.ceil() as i64;
 
+        let files = FileOffsets::new(
+            accessor
+                .buffer_ranges
+                .iter()
+                .enumerate()
+                .map(|(index, range)| (range.len() as u64, PathBuf::from(index.to_string()))),
+        );
+
         let received_pieces = worker::start_hasher_workers(
             accessor,
             piece_length,
+            files,
             total_num_pieces.try_into().unwrap(),
             num_threads,
             move |update| {
@@ -310,9 +356,20 @@ mod tests {
             .map(|(index, chunk)| (index, ShaHash::from_bytes(chunk)))
             .collect::<Vec<(usize, ShaHash)>>();
 
-        let updates_received = prog_recv.iter().count() as u64;
+        // Every update has been delivered by the time the workers return
+        let updates: Vec<BuildProgress> = prog_recv.try_iter().collect();
+        let (finished, hashing) = updates.split_last().unwrap();
+
+        let total_bytes = accessor.as_slice().len() as u64;
+        let last_file = (accessor.buffer_ranges.len() - 1).to_string();
 
-        assert_eq!(total_num_pieces, updates_received.try_into().unwrap());
+        assert_eq!(total_num_pieces, i64::try_from(hashing.len()).unwrap());
+        assert!(hashing.iter().all(|update| update.stage() == BuildStage::Hashing));
+        assert_eq!(Some(Path::new(&last_file)), hashing.last().unwrap().current_file());
+        assert_eq!(BuildStage::Finished, finished.stage());
+        assert_eq!(total_bytes, finished.bytes_hashed());
+        assert_eq!(total_bytes, finished.total_bytes());
+        assert_eq!(finished.pieces_total(), finished.pieces_done());
         assert_eq!(received_pieces, computed_pieces);
     }
 
@@ -21,4 +21,32 @@ pub enum ParseError {
 
     #[error("Missing Data Detected In File: {details}")]
     MissingData { details: String },
+
+    #[error("Files Not Given In Sorted Order: {details}")]
+    UnsortedFiles { details: String },
+}
+
+impl ParseError {
+    /// Nest a bencode error within the value of the given dictionary key, see `BencodePath`.
+    #[must_use]
+    pub fn in_key<K>(self, key: K) -> ParseError
+    where
+        K: AsRef<[u8]>,
+    {
+        match self {
+            ParseError::BencodeConvert(err) => ParseError::BencodeConvert(err.in_key(key)),
+            ParseError::BencodeParse(err) => ParseError::BencodeParse(err.in_key(key)),
+            other => other,
+        }
+    }
+
+    /// Nest a bencode error within the given list index, see `BencodePath`.
+    #[must_use]
+    pub fn in_index(self, index: usize) -> ParseError {
+        match self {
+            ParseError::BencodeConvert(err) => ParseError::BencodeConvert(err.in_index(index)),
+            ParseError::BencodeParse(err) => ParseError::BencodeParse(err.in_index(index)),
+            other => other,
+        }
+    }
 }
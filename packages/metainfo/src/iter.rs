@@ -1,8 +1,10 @@
 //! Iterators over torrent file information.
 
+use std::ops::Range;
+
 use util::sha;
 
-use crate::metainfo::File;
+use crate::metainfo::{File, FileSpan, PieceFileSlice};
 
 /// Iterator over each File within the `MetainfoFile`.
 pub struct Files<'a> {
@@ -32,6 +34,91 @@ impl<'a> Iterator for Files<'a> {
 
 // ----------------------------------------------------------------------------//
 
+/// Iterator over the location of each File within the pieces of the `MetainfoFile`.
+pub struct FileSpans<'a> {
+    index: usize,
+    files: &'a [File],
+    offsets: &'a [u64],
+    piece_length: u64,
+}
+
+impl<'a> FileSpans<'a> {
+    #[must_use]
+    pub fn new(files: &'a [File], offsets: &'a [u64], piece_length: u64) -> FileSpans<'a> {
+        FileSpans {
+            index: 0,
+            files,
+            offsets,
+            piece_length,
+        }
+    }
+}
+
+impl<'a> Iterator for FileSpans<'a> {
+    type Item = FileSpan;
+
+    fn next(&mut self) -> Option<FileSpan> {
+        let file = self.files.get(self.index)?;
+        let span = FileSpan::new(self.offsets[self.index], file.length(), self.piece_length);
+
+        self.index += 1;
+        Some(span)
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Iterator over the parts of each File that a single piece within the `MetainfoFile` covers.
+pub struct PieceFiles<'a> {
+    index: usize,
+    files: &'a [File],
+    offsets: &'a [u64],
+    piece: Range<u64>,
+}
+
+impl<'a> PieceFiles<'a> {
+    /// Create a new `PieceFiles`, starting at the given file index, for the given byte range of the piece.
+    #[must_use]
+    pub fn new(files: &'a [File], offsets: &'a [u64], index: usize, piece: Range<u64>) -> PieceFiles<'a> {
+        PieceFiles {
+            index,
+            files,
+            offsets,
+            piece,
+        }
+    }
+}
+
+impl<'a> Iterator for PieceFiles<'a> {
+    type Item = PieceFileSlice;
+
+    fn next(&mut self) -> Option<PieceFileSlice> {
+        while let Some(file) = self.files.get(self.index) {
+            let file_index = self.index;
+            let file_start = self.offsets[file_index];
+            if file_start >= self.piece.end {
+                return None;
+            }
+            self.index += 1;
+
+            let start = file_start.max(self.piece.start);
+            let end = (file_start + file.length()).min(self.piece.end);
+            if start < end {
+                return Some(PieceFileSlice::new(
+                    file_index,
+                    start - file_start,
+                    start - self.piece.start,
+                    end - start,
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
 /// Iterator over each piece hash within the `MetainfoFile`.
 pub struct Pieces<'a> {
     index: usize,
@@ -63,11 +63,13 @@ mod builder;
 pub mod error;
 mod metainfo;
 mod parse;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
 pub mod iter;
 
 pub use util::bt::InfoHash;
 
-pub use self::metainfo::{File, Info, Metainfo};
+pub use self::metainfo::{File, FileAttributes, FileSpan, Info, Metainfo, Node, PieceFileSlice};
 pub use crate::accessor::{Accessor, DirectAccessor, FileAccessor, IntoAccessor, PieceAccess};
-pub use crate::builder::{InfoBuilder, MetainfoBuilder, PieceLength};
+pub use crate::builder::{BuildOutput, BuildProgress, BuildStage, InfoBuilder, MetainfoBuilder, PieceLength};
@@ -1,6 +1,9 @@
 //! Accessing the fields of a Metainfo file.
 
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::{io, vec};
 
 use bencode::{BDecodeOpt, BDictAccess, BRefAccess, BencodeRef};
 use util::bt::InfoHash;
@@ -9,7 +12,7 @@ use util::sha::{self, ShaHash};
 use crate::accessor::{Accessor, IntoAccessor, PieceAccess};
 use crate::builder::{InfoBuilder, MetainfoBuilder, PieceLength};
 use crate::error::ParseError;
-use crate::iter::{Files, Pieces};
+use crate::iter::{FileSpans, Files, PieceFiles, Pieces};
 use crate::parse;
 
 /// Contains optional metadata for a torrent file.
@@ -18,6 +21,7 @@ pub struct Metainfo {
     comment: Option<String>,
     announce: Option<String>,
     announce_list: Option<Vec<Vec<String>>>,
+    nodes: Option<Vec<Node>>,
     encoding: Option<String>,
     created_by: Option<String>,
     creation_date: Option<i64>,
@@ -31,6 +35,7 @@ impl Metainfo {
             comment: None,
             announce: None,
             announce_list: None,
+            nodes: None,
             encoding: None,
             created_by: None,
             creation_date: None,
@@ -64,6 +69,12 @@ impl Metainfo {
         self.announce_list.as_ref()
     }
 
+    /// List of DHT nodes used to bootstrap trackerless torrents.
+    #[must_use]
+    pub fn nodes(&self) -> Option<&[Node]> {
+        self.nodes.as_deref()
+    }
+
     /// Comment included within the metainfo file.
     #[must_use]
     pub fn comment(&self) -> Option<&str> {
@@ -104,6 +115,7 @@ impl Metainfo {
         // Since there are no file system accesses here, should be fine to unwrap
         MetainfoBuilder::new()
             .set_main_tracker(self.main_tracker())
+            .set_nodes(self.nodes())
             .set_creation_date(self.creation_date())
             .set_comment(self.comment())
             .set_created_by(self.created_by())
@@ -121,6 +133,7 @@ impl From<Info> for Metainfo {
             comment: None,
             announce: None,
             announce_list: None,
+            nodes: None,
             encoding: None,
             created_by: None,
             creation_date: None,
@@ -141,6 +154,7 @@ fn parse_meta_bytes(bytes: &[u8]) -> Result<Metainfo, ParseError> {
             .map(parse::convert_announce_list)
             .or(None)
     };
+    let opt_nodes = parse::parse_nodes(root_dict).map(parse::convert_nodes);
 
     let opt_comment = parse::parse_comment(root_dict).map(std::borrow::ToOwned::to_owned);
     let opt_encoding = parse::parse_encoding(root_dict).map(std::borrow::ToOwned::to_owned);
@@ -148,12 +162,13 @@ fn parse_meta_bytes(bytes: &[u8]) -> Result<Metainfo, ParseError> {
     let opt_creation_date = parse::parse_creation_date(root_dict);
 
     let info_bencode = parse::parse_info_bencode(root_dict)?;
-    let info = parse_info_dictionary(info_bencode)?;
+    let info = parse_info_dictionary(info_bencode).map_err(|err| err.in_key(parse::INFO_KEY))?;
 
     Ok(Metainfo {
         comment: opt_comment,
         announce,
         announce_list: opt_announce_list,
+        nodes: opt_nodes,
         encoding: opt_encoding,
         created_by: opt_created_by,
         creation_date: opt_creation_date,
@@ -163,16 +178,73 @@ fn parse_meta_bytes(bytes: &[u8]) -> Result<Metainfo, ParseError> {
 
 // ----------------------------------------------------------------------------//
 
+/// DHT node, given as a host and port, that can be used to bootstrap a trackerless torrent.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Node {
+    host: String,
+    port: u16,
+}
+
+impl Node {
+    /// Create a new `Node` from a host, which may be an ip address or a domain name, and a port.
+    pub fn new<H>(host: H, port: u16) -> Node
+    where
+        H: Into<String>,
+    {
+        Node { host: host.into(), port }
+    }
+
+    /// Host of the node, either an ip address or a domain name.
+    #[must_use]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port of the node.
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Address of the node, if the host is an ip address.
+    ///
+    /// Nodes given by domain name can be resolved through `ToSocketAddrs`.
+    #[must_use]
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.host.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, self.port))
+    }
+}
+
+impl From<SocketAddr> for Node {
+    fn from(addr: SocketAddr) -> Node {
+        Node::new(addr.ip().to_string(), addr.port())
+    }
+}
+
+impl ToSocketAddrs for Node {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        (self.host.as_str(), self.port).to_socket_addrs()
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
 /// Contains directory and checksum data for a torrent file.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Info {
     info_hash: InfoHash,
     files: Vec<File>,
+    // Byte offset of each file within the torrent
+    file_offsets: Vec<u64>,
     pieces: Vec<[u8; sha::SHA_HASH_LEN]>,
     piece_len: u64,
     is_private: Option<bool>,
     // Present only for multi file torrents.
     file_directory: Option<PathBuf>,
+    // Info dictionary exactly as it was parsed, which is what the info hash covers
+    bytes: Vec<u8>,
 }
 
 impl Info {
@@ -238,6 +310,54 @@ impl Info {
         Files::new(&self.files)
     }
 
+    /// Location of the file at the given index within the pieces of the torrent, if it exists.
+    #[must_use]
+    pub fn file_span(&self, index: usize) -> Option<FileSpan> {
+        let file = self.files.get(index)?;
+
+        Some(FileSpan::new(self.file_offsets[index], file.length(), self.piece_len))
+    }
+
+    /// Iterator over the location of each file within the pieces of the torrent, in the order of `files`.
+    #[must_use]
+    pub fn file_spans(&self) -> FileSpans<'_> {
+        FileSpans::new(&self.files, &self.file_offsets, self.piece_len)
+    }
+
+    /// Iterator over the parts of files that the piece at the given index covers, in the order of `files`.
+    ///
+    /// Files of zero length are never covered, and pieces out of range cover no files.
+    #[must_use]
+    pub fn piece_files(&self, index: u64) -> PieceFiles<'_> {
+        let total_length = self
+            .file_offsets
+            .last()
+            .zip(self.files.last())
+            .map_or(0, |(offset, file)| offset + file.length());
+
+        let start = index.saturating_mul(self.piece_len).min(total_length);
+        let end = start.saturating_add(self.piece_len).min(total_length);
+
+        // First file that ends after the start of the piece
+        let first_file = self
+            .file_offsets
+            .iter()
+            .zip(&self.files)
+            .position(|(offset, file)| offset + file.length() > start)
+            .unwrap_or(self.files.len());
+
+        PieceFiles::new(&self.files, &self.file_offsets, first_file, start..end)
+    }
+
+    /// Bencoded bytes of the `Info` dictionary, exactly as they were parsed.
+    ///
+    /// Unlike [`Info::to_bytes`], these always hash to the info hash, even when the
+    /// dictionary holds keys that are not otherwise exposed.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
     /// Retrieve the bencoded bytes for the `Info` dictionary.
     ///
     /// # Panics
@@ -246,10 +366,16 @@ impl Info {
     #[must_use]
     pub fn to_bytes(&self) -> Vec<u8> {
         // Since there are no file system accesses here, should be fine to unwrap
-        InfoBuilder::new()
+        let builder = InfoBuilder::new()
             .set_private_flag(self.is_private())
             // TODO: Revisit this cast...
-            .set_piece_length(PieceLength::Custom(self.piece_length().try_into().unwrap()))
+            .set_piece_length(PieceLength::Custom(self.piece_length().try_into().unwrap()));
+
+        self.files()
+            .filter(|file| !file.attributes().is_empty())
+            .fold(builder, |builder, file| {
+                builder.set_file_attributes(file.path(), Some(file.attributes().clone()))
+            })
             .build(1, self, |_| ())
             .unwrap()
     }
@@ -325,35 +451,53 @@ fn parse_info_dictionary(info_bencode: &BencodeRef<'_>) -> Result<Info, ParseErr
         let files_bencode = parse::parse_files_list(info_dict)?;
 
         let mut files_list = Vec::with_capacity(files_bencode.len());
-        for file_bencode in files_bencode {
-            let file_dict = parse::parse_file_dict(file_bencode)?;
-            let file = File::as_multi_file(file_dict)?;
+        for (index, file_bencode) in files_bencode.into_iter().enumerate() {
+            let file = parse::parse_file_dict(file_bencode)
+                .and_then(File::as_multi_file)
+                .map_err(|err| err.in_index(index).in_key(parse::FILES_KEY))?;
 
             files_list.push(file);
         }
 
         Ok(Info {
             info_hash,
+            file_offsets: file_offsets(&files_list),
             files: files_list,
             pieces: piece_buffers,
             piece_len,
             is_private,
             file_directory: Some(file_directory_path),
+            bytes: info_bencode.buffer().to_vec(),
         })
     } else {
         let file = File::as_single_file(info_dict)?;
 
         Ok(Info {
             info_hash,
+            file_offsets: vec![0],
             files: vec![file],
             pieces: piece_buffers,
             piece_len,
             is_private,
             file_directory: None,
+            bytes: info_bencode.buffer().to_vec(),
         })
     }
 }
 
+/// Byte offset of each of the files within the torrent.
+fn file_offsets(files: &[File]) -> Vec<u64> {
+    files
+        .iter()
+        .scan(0, |offset, file| {
+            let start = *offset;
+            *offset += file.length();
+
+            Some(start)
+        })
+        .collect()
+}
+
 /// Returns whether or not this is a multi file torrent.
 fn is_multi_file_torrent<B>(info_dict: &dyn BDictAccess<B::BKey, B>) -> bool
 where
@@ -391,22 +535,25 @@ pub struct File {
     len: u64,
     path: PathBuf,
     md5sum: Option<Vec<u8>>,
+    attributes: FileAttributes,
 }
 
 impl File {
     /// Parse the info dictionary and generate a single file File.
     fn as_single_file<B>(info_dict: &dyn BDictAccess<B::BKey, B>) -> Result<File, ParseError>
     where
-        B: BRefAccess,
+        B: BRefAccess<BType = B>,
     {
         let length = parse::parse_length(info_dict)?;
         let md5sum = parse::parse_md5sum(info_dict).map(std::borrow::ToOwned::to_owned);
+        let attributes = FileAttributes::parse(info_dict)?;
         let name = parse::parse_name(info_dict)?;
 
         Ok(File {
             len: length,
             path: name.to_owned().into(),
             md5sum,
+            attributes,
         })
     }
 
@@ -417,12 +564,13 @@ impl File {
     {
         let length = parse::parse_length(file_dict)?;
         let md5sum = parse::parse_md5sum(file_dict).map(std::borrow::ToOwned::to_owned);
+        let attributes = FileAttributes::parse(file_dict)?;
 
         let path_list_bencode = parse::parse_path_list(file_dict)?;
 
         let mut path_buf = PathBuf::new();
-        for path_bencode in path_list_bencode {
-            let path = parse::parse_path_str(path_bencode)?;
+        for (index, path_bencode) in path_list_bencode.into_iter().enumerate() {
+            let path = parse::parse_path_str(path_bencode).map_err(|err| err.in_index(index).in_key(parse::PATH_KEY))?;
 
             path_buf.push(path);
         }
@@ -431,6 +579,7 @@ impl File {
             len: length,
             path: path_buf,
             md5sum,
+            attributes,
         })
     }
 
@@ -453,6 +602,287 @@ impl File {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    /// Optional attributes of the file (BEP 47), as a string of single character flags.
+    #[must_use]
+    pub fn attr(&self) -> Option<&str> {
+        Some(self.attributes.attr()).filter(|attr| !attr.is_empty())
+    }
+
+    /// Whether or not this is a padding file.
+    ///
+    /// Padding files are made up of all zeros and only exist to align the following
+    /// file to a piece boundary, so they should not be written to disk.
+    #[must_use]
+    pub fn is_padding(&self) -> bool {
+        self.attributes.is_padding()
+    }
+
+    /// Attributes of the file (BEP 47).
+    #[must_use]
+    pub fn attributes(&self) -> &FileAttributes {
+        &self.attributes
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Location of a file within the pieces of a torrent.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FileSpan {
+    offset: u64,
+    length: u64,
+    piece_length: u64,
+}
+
+impl FileSpan {
+    pub(crate) fn new(offset: u64, length: u64, piece_length: u64) -> FileSpan {
+        FileSpan {
+            offset,
+            length,
+            // Every byte is its own piece, rather than dividing by zero, for torrents without a piece length
+            piece_length: piece_length.max(1),
+        }
+    }
+
+    /// Byte offset of the start of the file within the torrent.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the file in bytes.
+    #[must_use]
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Indices of the pieces that cover the file, which is empty for files of zero length.
+    #[must_use]
+    pub fn pieces(&self) -> Range<u64> {
+        let first_piece = self.offset / self.piece_length;
+
+        if self.length == 0 {
+            first_piece..first_piece
+        } else {
+            first_piece..(self.offset + self.length - 1) / self.piece_length + 1
+        }
+    }
+
+    /// Index of the first piece that covers the file, if any.
+    #[must_use]
+    pub fn first_piece(&self) -> Option<u64> {
+        let pieces = self.pieces();
+
+        (!pieces.is_empty()).then_some(pieces.start)
+    }
+
+    /// Index of the last piece that covers the file, if any.
+    #[must_use]
+    pub fn last_piece(&self) -> Option<u64> {
+        let pieces = self.pieces();
+
+        (!pieces.is_empty()).then(|| pieces.end - 1)
+    }
+
+    /// Byte offset of the start of the file within its first piece.
+    #[must_use]
+    pub fn start_in_first_piece(&self) -> u64 {
+        self.offset % self.piece_length
+    }
+
+    /// Byte offset just past the end of the file within its last piece.
+    #[must_use]
+    pub fn end_in_last_piece(&self) -> u64 {
+        if self.length == 0 {
+            self.start_in_first_piece()
+        } else {
+            (self.offset + self.length - 1) % self.piece_length + 1
+        }
+    }
+}
+
+/// Part of a file that is covered by a piece.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PieceFileSlice {
+    file_index: usize,
+    file_offset: u64,
+    piece_offset: u64,
+    length: u64,
+}
+
+impl PieceFileSlice {
+    pub(crate) fn new(file_index: usize, file_offset: u64, piece_offset: u64, length: u64) -> PieceFileSlice {
+        PieceFileSlice {
+            file_index,
+            file_offset,
+            piece_offset,
+            length,
+        }
+    }
+
+    /// Index of the file, in the order of `Info::files`.
+    #[must_use]
+    pub fn file_index(&self) -> usize {
+        self.file_index
+    }
+
+    /// Byte offset of the slice within the file.
+    #[must_use]
+    pub fn file_offset(&self) -> u64 {
+        self.file_offset
+    }
+
+    /// Byte offset of the slice within the piece.
+    #[must_use]
+    pub fn piece_offset(&self) -> u64 {
+        self.piece_offset
+    }
+
+    /// Length of the slice in bytes.
+    #[must_use]
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Optional attributes of a file (BEP 47).
+///
+/// Attribute flags are kept in the order they were set in, so that parsed attributes
+/// are encoded exactly as they were found.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FileAttributes {
+    attr: String,
+    symlink_path: Option<PathBuf>,
+    sha1: Option<ShaHash>,
+}
+
+impl FileAttributes {
+    /// Create a new `FileAttributes` without any attributes set.
+    #[must_use]
+    pub fn new() -> FileAttributes {
+        FileAttributes::default()
+    }
+
+    /// Parse the attributes from the info or file dictionary.
+    fn parse<B>(info_or_file_dict: &dyn BDictAccess<B::BKey, B>) -> Result<FileAttributes, ParseError>
+    where
+        B: BRefAccess<BType = B>,
+    {
+        let attr = parse::parse_attr(info_or_file_dict).unwrap_or_default().to_owned();
+        let sha1 = parse::parse_sha1(info_or_file_dict).and_then(|sha1| ShaHash::from_hash(sha1).ok());
+
+        let symlink_path = if let Some(path_list_bencode) = parse::parse_symlink_path_list(info_or_file_dict) {
+            let mut path_buf = PathBuf::new();
+            for (index, path_bencode) in path_list_bencode.into_iter().enumerate() {
+                let path =
+                    parse::parse_path_str(path_bencode).map_err(|err| err.in_index(index).in_key(parse::SYMLINK_PATH_KEY))?;
+
+                path_buf.push(path);
+            }
+
+            Some(path_buf)
+        } else {
+            None
+        };
+
+        Ok(FileAttributes {
+            attr,
+            symlink_path,
+            sha1,
+        })
+    }
+
+    /// Sets whether the file is executable.
+    #[must_use]
+    pub fn with_executable(self, executable: bool) -> FileAttributes {
+        self.with_flag(parse::EXECUTABLE_ATTR, executable)
+    }
+
+    /// Sets whether the file is hidden.
+    #[must_use]
+    pub fn with_hidden(self, hidden: bool) -> FileAttributes {
+        self.with_flag(parse::HIDDEN_ATTR, hidden)
+    }
+
+    /// Sets the path that the file is a symbolic link to, relative to the torrent directory.
+    ///
+    /// Symbolic links have a length of zero, as their data is that of the file they point to.
+    #[must_use]
+    pub fn with_symlink_path<P>(mut self, path: P) -> FileAttributes
+    where
+        P: Into<PathBuf>,
+    {
+        self.symlink_path = Some(path.into());
+        self.with_flag(parse::SYMLINK_ATTR, true)
+    }
+
+    /// Sets the SHA-1 hash of the whole file.
+    #[must_use]
+    pub fn with_sha1(mut self, sha1: ShaHash) -> FileAttributes {
+        self.sha1 = Some(sha1);
+        self
+    }
+
+    fn with_flag(mut self, flag: char, enable: bool) -> FileAttributes {
+        if enable && !self.attr.contains(flag) {
+            self.attr.push(flag);
+        } else if !enable {
+            self.attr.retain(|existing| existing != flag);
+        }
+
+        self
+    }
+
+    /// Attribute flags of the file, which may be empty.
+    #[must_use]
+    pub fn attr(&self) -> &str {
+        &self.attr
+    }
+
+    /// Whether or not the file is a padding file.
+    #[must_use]
+    pub fn is_padding(&self) -> bool {
+        self.attr.contains(parse::PADDING_ATTR)
+    }
+
+    /// Whether or not the file is executable.
+    #[must_use]
+    pub fn is_executable(&self) -> bool {
+        self.attr.contains(parse::EXECUTABLE_ATTR)
+    }
+
+    /// Whether or not the file is hidden.
+    #[must_use]
+    pub fn is_hidden(&self) -> bool {
+        self.attr.contains(parse::HIDDEN_ATTR)
+    }
+
+    /// Whether or not the file is a symbolic link.
+    #[must_use]
+    pub fn is_symlink(&self) -> bool {
+        self.attr.contains(parse::SYMLINK_ATTR)
+    }
+
+    /// Path that the file is a symbolic link to, relative to the torrent directory.
+    #[must_use]
+    pub fn symlink_path(&self) -> Option<&Path> {
+        self.symlink_path.as_deref()
+    }
+
+    /// Optional SHA-1 hash of the whole file.
+    #[must_use]
+    pub fn sha1(&self) -> Option<ShaHash> {
+        self.sha1
+    }
+
+    /// Whether or not no attributes are set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.attr.is_empty() && self.symlink_path.is_none() && self.sha1.is_none()
+    }
 }
 
 #[cfg(test)]
@@ -463,7 +893,7 @@ mod tests {
     use util::bt::InfoHash;
     use util::sha;
 
-    use crate::metainfo::Metainfo;
+    use crate::metainfo::{Info, Metainfo, PieceFileSlice};
     use crate::parse;
 
     type FilesOpt<'a> = Option<Vec<(Option<i64>, Option<&'a [u8]>, Option<Vec<String>>)>>;
@@ -917,14 +1347,14 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "called `Result::unwrap()` on an `Err` value: BencodeParse(BytesEmpty { pos: 0 })")]
+    #[should_panic(expected = "called `Result::unwrap()` on an `Err` value: BencodeParse(BytesEmpty { pos: 0, path: <root> })")]
     fn negative_parse_from_empty_bytes() {
         Metainfo::from_bytes(b"").unwrap();
     }
 
     #[test]
     #[should_panic(
-        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [112, 105, 101, 99, 101, 32, 108, 101, 110, 103, 116, 104] })"
+        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [112, 105, 101, 99, 101, 32, 108, 101, 110, 103, 116, 104], path: info.piece length })"
     )]
     fn negative_parse_with_no_piece_length() {
         let tracker = "udp://dummy_domain.com:8989";
@@ -951,7 +1381,7 @@ mod tests {
 
     #[test]
     #[should_panic(
-        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [112, 105, 101, 99, 101, 115] })"
+        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [112, 105, 101, 99, 101, 115], path: info.pieces })"
     )]
     fn negative_parse_with_no_pieces() {
         let tracker = "udp://dummy_domain.com:8989";
@@ -976,7 +1406,7 @@ mod tests {
 
     #[test]
     #[should_panic(
-        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [102, 105, 108, 101, 115] })"
+        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [102, 105, 108, 101, 115], path: info.files })"
     )]
     fn negative_parse_from_single_file_with_no_file_length() {
         let tracker = "udp://dummy_domain.com:8989";
@@ -1001,7 +1431,7 @@ mod tests {
 
     #[test]
     #[should_panic(
-        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [110, 97, 109, 101] })"
+        expected = "called `Result::unwrap()` on an `Err` value: BencodeConvert(MissingKey { key: [110, 97, 109, 101], path: info.name })"
     )]
     fn negative_parse_from_single_file_with_no_file_name() {
         let tracker = "udp://dummy_domain.com:8989";
@@ -1023,4 +1453,81 @@ mod tests {
             &Some(vec![(Some(file_len), None, None)]),
         );
     }
+
+    /// Info for four files of lengths 5, 0, 7 and 4, with a piece length of 4.
+    fn four_file_info() -> Info {
+        let mut bytes = b"d5:filesl".to_vec();
+        for (length, name) in [(5, "a"), (0, "b"), (7, "c"), (4, "d")] {
+            bytes.extend(format!("d6:lengthi{length}e4:pathl1:{name}ee").as_bytes());
+        }
+        bytes.extend(b"e4:name3:dir12:piece lengthi4e6:pieces80:");
+        bytes.extend([0u8; 4 * sha::SHA_HASH_LEN]);
+        bytes.push(b'e');
+
+        Info::from_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn positive_file_spans() {
+        let info = four_file_info();
+        let spans: Vec<_> = info
+            .file_spans()
+            .map(|span| {
+                (
+                    span.offset(),
+                    span.pieces(),
+                    span.start_in_first_piece(),
+                    span.end_in_last_piece(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            vec![(0, 0..2, 0, 1), (5, 1..1, 1, 1), (5, 1..3, 1, 4), (12, 3..4, 0, 4)],
+            spans
+        );
+        assert_eq!(Some(info.file_span(2).unwrap()), info.file_spans().nth(2));
+        assert_eq!(None, info.file_span(4));
+    }
+
+    #[test]
+    fn positive_file_span_empty_file_has_no_pieces() {
+        let span = four_file_info().file_span(1).unwrap();
+
+        assert_eq!(None, span.first_piece());
+        assert_eq!(None, span.last_piece());
+    }
+
+    #[test]
+    fn positive_piece_files() {
+        let info = four_file_info();
+
+        assert_eq!(
+            vec![PieceFileSlice::new(0, 4, 0, 1), PieceFileSlice::new(2, 0, 1, 3)],
+            info.piece_files(1).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![PieceFileSlice::new(3, 0, 0, 4)], info.piece_files(3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn negative_piece_files_out_of_range() {
+        assert_eq!(0, four_file_info().piece_files(4).count());
+    }
+
+    #[test]
+    fn negative_parse_error_path() {
+        let mut bytes = b"d4:infod5:filesl".to_vec();
+        bytes.extend(b"d6:lengthi5e4:pathl1:aee");
+        bytes.extend(b"d6:length1:54:pathl1:bee");
+        bytes.extend(b"e4:name3:dir12:piece lengthi4e6:pieces20:");
+        bytes.extend([0u8; sha::SHA_HASH_LEN]);
+        bytes.extend(b"ee");
+
+        let err = Metainfo::from_bytes(bytes).unwrap_err();
+
+        assert_eq!(
+            "Bencode conversion error: Wrong Type In Bencode For info.files[1].length At 49 Expected Type Integer",
+            err.to_string()
+        );
+    }
 }
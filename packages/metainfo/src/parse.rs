@@ -1,6 +1,7 @@
 use bencode::{BConvert, BDictAccess, BListAccess, BRefAccess, BencodeConvertError};
 
 use crate::error::ParseError;
+use crate::metainfo::Node;
 
 /// Struct implemented the `BencodeConvert` trait for decoding the metainfo file.
 struct MetainfoConverter;
@@ -27,6 +28,7 @@ pub const COMMENT_KEY: &[u8] = b"comment";
 pub const CREATED_BY_KEY: &[u8] = b"created by";
 pub const ENCODING_KEY: &[u8] = b"encoding";
 pub const INFO_KEY: &[u8] = b"info";
+pub const NODES_KEY: &[u8] = b"nodes";
 
 /// Keys found within the info dictionary of a metainfo file.
 pub const PIECE_LENGTH_KEY: &[u8] = b"piece length";
@@ -39,6 +41,15 @@ pub const FILES_KEY: &[u8] = b"files";
 pub const LENGTH_KEY: &[u8] = b"length";
 pub const MD5SUM_KEY: &[u8] = b"md5sum";
 pub const PATH_KEY: &[u8] = b"path";
+pub const ATTR_KEY: &[u8] = b"attr";
+pub const SYMLINK_PATH_KEY: &[u8] = b"symlink path";
+pub const SHA1_KEY: &[u8] = b"sha1";
+
+/// Flags found within the attributes of a file dictionary.
+pub const PADDING_ATTR: &str = "p";
+pub const EXECUTABLE_ATTR: char = 'x';
+pub const HIDDEN_ATTR: char = 'h';
+pub const SYMLINK_ATTR: char = 'l';
 
 /// Parses the root bencode as a dictionary.
 #[allow(clippy::module_name_repetitions)]
@@ -75,6 +86,31 @@ where
         .collect()
 }
 
+/// Parses the nodes list from the root dictionary.
+#[allow(clippy::module_name_repetitions)]
+pub fn parse_nodes<B>(root_dict: &dyn BDictAccess<B::BKey, B>) -> Option<&dyn BListAccess<B>>
+where
+    B: BRefAccess<BType = B>,
+{
+    CONVERT.lookup_and_convert_list(root_dict, NODES_KEY).ok()
+}
+
+/// Converts list of `[host, port]` pairs to vec of nodes, skipping malformed entries.
+pub fn convert_nodes<B>(list: &dyn BListAccess<B>) -> Vec<Node>
+where
+    B: BRefAccess<BType = B>,
+{
+    list.into_iter()
+        .filter_map(bencode::BRefAccess::list)
+        .filter_map(|entry| {
+            let host = entry.get(0)?.str()?;
+            let port = entry.get(1)?.int()?.try_into().ok()?;
+
+            Some(Node::new(host, port))
+        })
+        .collect()
+}
+
 /// Parses the announce url from the root dictionary.
 #[allow(clippy::module_name_repetitions)]
 pub fn parse_announce_url<'a, B>(root_dict: &'a dyn BDictAccess<B::BKey, B>) -> Option<&'a str>
@@ -209,6 +245,33 @@ where
     CONVERT.lookup_and_convert_bytes(info_or_file_dict, MD5SUM_KEY).ok()
 }
 
+/// Parses the attributes from the info or file dictionary.
+#[allow(clippy::module_name_repetitions)]
+pub fn parse_attr<'a, B>(info_or_file_dict: &'a dyn BDictAccess<B::BKey, B>) -> Option<&'a str>
+where
+    B: BRefAccess + 'a,
+{
+    CONVERT.lookup_and_convert_str(info_or_file_dict, ATTR_KEY).ok()
+}
+
+/// Parses the symlink path list from the info or file dictionary.
+#[allow(clippy::module_name_repetitions)]
+pub fn parse_symlink_path_list<B>(info_or_file_dict: &dyn BDictAccess<B::BKey, B>) -> Option<&dyn BListAccess<B>>
+where
+    B: BRefAccess<BType = B>,
+{
+    CONVERT.lookup_and_convert_list(info_or_file_dict, SYMLINK_PATH_KEY).ok()
+}
+
+/// Parses the sha1 from the info or file dictionary.
+#[allow(clippy::module_name_repetitions)]
+pub fn parse_sha1<'a, B>(info_or_file_dict: &'a dyn BDictAccess<B::BKey, B>) -> Option<&'a [u8]>
+where
+    B: BRefAccess + 'a,
+{
+    CONVERT.lookup_and_convert_bytes(info_or_file_dict, SHA1_KEY).ok()
+}
+
 /// Parses the path list from the file dictionary.
 #[allow(clippy::module_name_repetitions)]
 pub fn parse_path_list<B>(file_dict: &dyn BDictAccess<B::BKey, B>) -> Result<&dyn BListAccess<B>, ParseError>
@@ -0,0 +1,318 @@
+//! Sequential file reads submitted ahead of time through `io_uring`.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Error, Read};
+use std::os::unix::io::AsRawFd as _;
+
+use io_uring::{opcode, types, IoUring};
+
+/// Number of reads kept in flight for a file.
+const READ_AHEAD_BUFFERS: usize = 4;
+/// Length of each (registered) read buffer.
+const READ_BUFFER_LEN: usize = 1024 * 1024;
+/// Number of entries in the submission queue, rounded up to a power of two by the kernel.
+const RING_ENTRIES: u32 = 8;
+
+const _: () = assert!(READ_AHEAD_BUFFERS <= RING_ENTRIES as usize);
+
+/// Reader which keeps a number of large sequential reads in flight, using buffers registered with the kernel.
+///
+/// The ring and buffers are created once, and re-used for every file that is read.
+pub struct UringReader {
+    // Dropped before the buffers, so the kernel never writes to freed memory
+    ring: IoUring,
+    buffers: Vec<Box<[u8]>>,
+}
+
+impl UringReader {
+    /// Create a new `UringReader`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO error if `io_uring` is unavailable, or the buffers could not be registered.
+    pub fn new() -> std::io::Result<UringReader> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+
+        let mut buffers: Vec<Box<[u8]>> = (0..READ_AHEAD_BUFFERS)
+            .map(|_| vec![0u8; READ_BUFFER_LEN].into_boxed_slice())
+            .collect();
+
+        let iovecs: Vec<libc::iovec> = buffers
+            .iter_mut()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr().cast(),
+                iov_len: buffer.len(),
+            })
+            .collect();
+
+        // SAFETY: The buffers are never reallocated, and outlive the ring they are registered with
+        unsafe { ring.submitter().register_buffers(&iovecs)? };
+
+        Ok(UringReader { ring, buffers })
+    }
+
+    /// Start reading the given file from the beginning.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO error if unable to get the length of the file, or to submit the first reads.
+    pub fn read_file<'a>(&'a mut self, file: &'a File) -> std::io::Result<UringFileReader<'a>> {
+        let file_len = file.metadata()?.len();
+
+        let mut reader = UringFileReader {
+            uring: self,
+            file,
+            file_len,
+            next_offset: 0,
+            slots: [Slot::default(); READ_AHEAD_BUFFERS],
+            order: VecDeque::with_capacity(READ_AHEAD_BUFFERS),
+            in_flight: 0,
+        };
+
+        for index in 0..READ_AHEAD_BUFFERS {
+            reader.submit(index)?;
+        }
+
+        Ok(reader)
+    }
+}
+
+/// Region of a file that is read into a single buffer.
+#[derive(Debug, Default, Copy, Clone)]
+struct Slot {
+    offset: u64,
+    len: usize,
+    filled: usize,
+    consumed: usize,
+    pending: bool,
+}
+
+/// Reader for a single file, yielding the bytes of the buffers in the order they were submitted.
+pub struct UringFileReader<'a> {
+    uring: &'a mut UringReader,
+    file: &'a File,
+    file_len: u64,
+    next_offset: u64,
+    slots: [Slot; READ_AHEAD_BUFFERS],
+    order: VecDeque<usize>,
+    in_flight: usize,
+}
+
+impl UringFileReader<'_> {
+    /// Submit a read for the next region of the file into the given buffer, if any is left.
+    fn submit(&mut self, index: usize) -> std::io::Result<()> {
+        if self.next_offset >= self.file_len {
+            return Ok(());
+        }
+
+        let remaining = self.file_len - self.next_offset;
+        let len = usize::try_from(remaining).map_or(READ_BUFFER_LEN, |remaining| remaining.min(READ_BUFFER_LEN));
+
+        self.slots[index] = Slot {
+            offset: self.next_offset,
+            len,
+            ..Slot::default()
+        };
+        self.next_offset += len as u64;
+        self.order.push_back(index);
+
+        self.push_read(index)
+    }
+
+    /// Push a read for the unfilled part of the given buffer.
+    fn push_read(&mut self, index: usize) -> std::io::Result<()> {
+        let slot = &mut self.slots[index];
+        let buffer = &mut self.uring.buffers[index][slot.filled..slot.len];
+
+        let entry = opcode::ReadFixed::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            u32::try_from(buffer.len()).expect("read buffers should fit in a u32"),
+            u16::try_from(index).expect("buffer index should fit in a u16"),
+        )
+        .offset(slot.offset + slot.filled as u64)
+        .build()
+        .user_data(index as u64);
+
+        // SAFETY: The buffer is registered, and is not accessed again until the read completes
+        unsafe { self.uring.ring.submission().push(&entry) }.expect("submission queue should have room for every buffer");
+        self.uring.ring.submit()?;
+
+        slot.pending = true;
+        self.in_flight += 1;
+
+        Ok(())
+    }
+
+    /// Wait for at least one read to complete, re-submitting any that were cut short.
+    fn wait_completion(&mut self) -> std::io::Result<()> {
+        self.uring.ring.submit_and_wait(1)?;
+
+        let completions: Vec<(u64, i32)> = self
+            .uring
+            .ring
+            .completion()
+            .map(|entry| (entry.user_data(), entry.result()))
+            .collect();
+
+        // Account for every completion up front, so none are left pending if handling one fails
+        for &(user_data, _) in &completions {
+            let index = usize::try_from(user_data).expect("buffer index should fit in a usize");
+
+            self.in_flight -= 1;
+            self.slots[index].pending = false;
+        }
+
+        for (user_data, result) in completions {
+            let index = usize::try_from(user_data).expect("buffer index should fit in a usize");
+
+            match usize::try_from(result) {
+                // File was truncated while we were reading it, nothing more to read
+                Ok(0) => {
+                    let slot = &mut self.slots[index];
+
+                    slot.len = slot.filled;
+                    self.file_len = self.file_len.min(slot.offset + slot.filled as u64);
+                }
+                Ok(read) => {
+                    let slot = &mut self.slots[index];
+                    slot.filled += read;
+
+                    if slot.filled < slot.len {
+                        self.push_read(index)?;
+                    }
+                }
+                Err(_) if -result == libc::EINTR || -result == libc::EAGAIN => self.push_read(index)?,
+                Err(_) => return Err(Error::from_raw_os_error(-result)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for UringFileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let Some(&index) = self.order.front() else {
+                return Ok(0);
+            };
+
+            if self.slots[index].pending {
+                self.wait_completion()?;
+                continue;
+            }
+
+            let slot = &mut self.slots[index];
+            if slot.consumed < slot.filled {
+                let len = buf.len().min(slot.filled - slot.consumed);
+
+                buf[..len].copy_from_slice(&self.uring.buffers[index][slot.consumed..slot.consumed + len]);
+                slot.consumed += len;
+
+                return Ok(len);
+            }
+
+            // Buffer has been fully consumed, re-use it for the next region of the file
+            self.order.pop_front();
+            self.submit(index)?;
+        }
+    }
+}
+
+impl Drop for UringFileReader<'_> {
+    fn drop(&mut self) {
+        // Buffers are re-used for the next file, so wait for any outstanding reads into them
+        while self.in_flight > 0 {
+            if self.uring.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+
+            self.in_flight -= self.uring.ring.completion().count();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use super::{UringReader, READ_BUFFER_LEN};
+
+    #[test]
+    fn positive_read_file_larger_than_read_ahead() {
+        // Kernels (or sandboxes) without io_uring fall back to regular reads
+        let Ok(mut uring) = UringReader::new() else {
+            return;
+        };
+
+        let path = std::env::temp_dir().join(format!("metainfo_uring_{}", rand::random::<u64>()));
+        let mut contents = vec![0u8; READ_BUFFER_LEN * 5 + 17];
+        rand::Rng::fill(&mut rand::thread_rng(), contents.as_mut_slice());
+        std::fs::write(&path, &contents).unwrap();
+
+        for _ in 0..2 {
+            let file = std::fs::File::open(&path).unwrap();
+            let mut read = Vec::new();
+
+            uring.read_file(&file).unwrap().read_to_end(&mut read).unwrap();
+
+            assert_eq!(read, contents);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn negative_read_failure_leaves_no_read_in_flight() {
+        let Ok(mut uring) = UringReader::new() else {
+            return;
+        };
+
+        let path = std::env::temp_dir().join(format!("metainfo_uring_failed_{}", rand::random::<u64>()));
+        let contents = vec![0u8; READ_BUFFER_LEN * 5];
+        std::fs::write(&path, &contents).unwrap();
+
+        // Reads of a file that is only opened for writing all fail
+        {
+            let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+            let mut reader = uring.read_file(&file).unwrap();
+
+            let mut buf = [0u8; 16];
+            assert!(reader.read(&mut buf).is_err());
+
+            let pending = reader.slots.iter().filter(|slot| slot.pending).count();
+            assert_eq!(reader.in_flight, pending);
+        }
+
+        // Ring is still usable once the failed reader was dropped
+        let file = std::fs::File::open(&path).unwrap();
+        let mut read = Vec::new();
+        uring.read_file(&file).unwrap().read_to_end(&mut read).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, contents);
+    }
+
+    #[test]
+    fn positive_read_empty_file() {
+        let Ok(mut uring) = UringReader::new() else {
+            return;
+        };
+
+        let path = std::env::temp_dir().join(format!("metainfo_uring_empty_{}", rand::random::<u64>()));
+        std::fs::write(&path, []).unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut read = Vec::new();
+        uring.read_file(&file).unwrap().read_to_end(&mut read).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(read.is_empty());
+    }
+}
@@ -1,10 +1,57 @@
-use metainfo::MetainfoBuilder;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use metainfo::error::ParseError;
+use metainfo::{
+    Accessor, BuildStage, DirectAccessor, FileAttributes, Info, InfoBuilder, IntoAccessor, Metainfo, MetainfoBuilder, Node,
+    PieceAccess, PieceLength,
+};
+use util::sha::ShaHash;
 
 const TRACKER: &str = "udp://foo.bar.baz:6969";
 const DATE: i64 = 1_517_651_523_851;
 const COMMENT: &str = "Foo bar baz";
 const CREATED_BY: &str = "Fridge";
 
+/// Accessor for a directory of files held in memory.
+struct MultiAccessor(Vec<(&'static str, Vec<u8>)>);
+
+impl IntoAccessor for MultiAccessor {
+    type Accessor = MultiAccessor;
+
+    fn into_accessor(self) -> std::io::Result<MultiAccessor> {
+        Ok(self)
+    }
+}
+
+impl Accessor for MultiAccessor {
+    fn access_directory(&self) -> Option<&Path> {
+        Some(Path::new("Directory"))
+    }
+
+    fn access_metadata<C>(&self, mut callback: C) -> std::io::Result<()>
+    where
+        C: FnMut(u64, &Path),
+    {
+        for (name, data) in &self.0 {
+            callback(data.len() as u64, Path::new(name));
+        }
+
+        Ok(())
+    }
+
+    fn access_pieces<C>(&self, mut callback: C) -> std::io::Result<()>
+    where
+        C: for<'a> FnMut(PieceAccess<'a>) -> std::io::Result<()>,
+    {
+        for (_, data) in &self.0 {
+            callback(PieceAccess::Compute(&mut &data[..]))?;
+        }
+
+        Ok(())
+    }
+}
+
 #[test]
 fn positive_set_trackers() {
     let trackers = vec![vec![TRACKER.to_string()]];
@@ -21,6 +68,35 @@ fn positive_set_main_tracker() {
     assert_eq!(builder.get_main_tracker(), Some(TRACKER.to_string()));
 }
 
+#[test]
+fn positive_set_nodes() {
+    let nodes = vec![Node::new("router.bittorrent.com", 6881), Node::new("127.0.0.1", 6882)];
+
+    let builder = MetainfoBuilder::new().set_nodes(Some(&nodes));
+
+    assert_eq!(builder.get_nodes(), Some(nodes.clone()));
+}
+
+#[test]
+fn positive_parse_nodes() {
+    let nodes = vec![Node::new("router.bittorrent.com", 6881), Node::new("127.0.0.1", 6882)];
+    let accessor = DirectAccessor::new("FileName.txt", b"Some file data");
+
+    let bytes = MetainfoBuilder::new()
+        .set_nodes(Some(&nodes))
+        .build(1, accessor, |_| ())
+        .unwrap();
+    let metainfo = Metainfo::from_bytes(bytes).unwrap();
+
+    assert_eq!(metainfo.nodes(), Some(&nodes[..]));
+    assert_eq!(metainfo.nodes().unwrap()[0].socket_addr(), None);
+    assert_eq!(
+        metainfo.nodes().unwrap()[1].socket_addr(),
+        Some("127.0.0.1:6882".parse().unwrap())
+    );
+    assert_eq!(Metainfo::from_bytes(metainfo.to_bytes()).unwrap().nodes(), Some(&nodes[..]));
+}
+
 #[test]
 fn positive_set_creation_date() {
     let builder = MetainfoBuilder::new().set_creation_date(Some(DATE));
@@ -41,3 +117,185 @@ fn positive_set_created_by() {
 
     assert_eq!(builder.get_created_by(), Some(CREATED_BY.to_string()));
 }
+
+#[test]
+fn positive_build_output_exposes_file_order() {
+    let accessor = DirectAccessor::new("FileName.txt", b"Some file data");
+
+    let output = MetainfoBuilder::new().build_output(1, accessor, |_| ()).unwrap();
+    let metainfo = Metainfo::from_bytes(output.bytes()).unwrap();
+
+    assert_eq!(output.files(), [PathBuf::from("FileName.txt")]);
+    assert_eq!(metainfo.info().files().count(), output.files().len());
+}
+
+#[test]
+fn positive_build_with_progress_reports_bytes_and_pieces() {
+    let file_data = [0u8; 2500];
+    let accessor = DirectAccessor::new("FileName.txt", &file_data);
+    let (send, recv) = mpsc::channel();
+
+    InfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build_with_progress(1, accessor, move |progress| send.send(progress).unwrap())
+        .unwrap();
+
+    let updates: Vec<_> = recv.try_iter().collect();
+    let (finished, hashing) = updates.split_last().unwrap();
+
+    assert_eq!(
+        vec![(1024, 1), (2048, 2), (2500, 3)],
+        hashing
+            .iter()
+            .map(|progress| (progress.bytes_hashed(), progress.pieces_done()))
+            .collect::<Vec<_>>()
+    );
+    assert!(hashing.iter().all(|progress| progress.stage() == BuildStage::Hashing
+        && progress.total_bytes() == 2500
+        && progress.pieces_total() == 3
+        && progress.current_file() == Some(Path::new("FileName.txt"))));
+
+    assert_eq!(BuildStage::Finished, finished.stage());
+    assert_eq!(2500, finished.bytes_hashed());
+    assert_eq!(3, finished.pieces_done());
+}
+
+#[test]
+fn positive_build_with_padding_aligns_files() {
+    let accessor = MultiAccessor(vec![("one", vec![1u8; 1500]), ("two", vec![2u8; 100])]);
+
+    let bytes = InfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .set_padding(true)
+        .build(1, accessor, |_| ())
+        .unwrap();
+    let info = Info::from_bytes(bytes).unwrap();
+
+    let files: Vec<_> = info
+        .files()
+        .map(|file| (file.path().to_path_buf(), file.length(), file.is_padding()))
+        .collect();
+    assert_eq!(
+        vec![
+            (PathBuf::from("one"), 1500, false),
+            (PathBuf::from(".pad/548"), 548, true),
+            (PathBuf::from("two"), 100, false),
+        ],
+        files
+    );
+    assert_eq!(Some("p"), info.files().nth(1).unwrap().attr());
+    assert_eq!(None, info.files().next().unwrap().attr());
+
+    // Padding is hashed as zeros
+    let mut data = vec![1u8; 1500];
+    data.extend_from_slice(&[0u8; 548]);
+    data.extend_from_slice(&[2u8; 100]);
+
+    let expected: Vec<_> = data.chunks(1024).map(ShaHash::from_bytes).collect();
+    let pieces: Vec<_> = info.pieces().map(|piece| ShaHash::from_hash(piece).unwrap()).collect();
+    assert_eq!(expected, pieces);
+
+    // Existing padding files are kept when rebuilding
+    assert_eq!(info.info_hash(), Info::from_bytes(info.to_bytes()).unwrap().info_hash());
+}
+
+#[test]
+fn positive_build_without_padding() {
+    let accessor = MultiAccessor(vec![("one", vec![1u8; 1500]), ("two", vec![2u8; 100])]);
+
+    let bytes = InfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, accessor, |_| ())
+        .unwrap();
+    let info = Info::from_bytes(bytes).unwrap();
+
+    assert_eq!(2, info.files().count());
+    assert!(info.files().all(|file| !file.is_padding()));
+}
+
+#[test]
+fn positive_build_with_file_attributes_round_trips() {
+    let accessor = MultiAccessor(vec![
+        ("run.sh", b"#!/bin/sh".to_vec()),
+        ("link", Vec::new()),
+        (".hidden", vec![1u8; 10]),
+    ]);
+    let sha1 = ShaHash::from_bytes(b"#!/bin/sh");
+
+    let bytes = InfoBuilder::new()
+        .set_file_attributes("run.sh", Some(FileAttributes::new().with_executable(true).with_sha1(sha1)))
+        .set_file_attributes("link", Some(FileAttributes::new().with_symlink_path("sub/run.sh")))
+        .set_file_attributes(".hidden", Some(FileAttributes::new().with_hidden(true)))
+        .set_file_attributes("missing", Some(FileAttributes::new().with_hidden(true)))
+        .build(1, accessor, |_| ())
+        .unwrap();
+    let info = Info::from_bytes(bytes).unwrap();
+
+    let attributes: Vec<_> = info.files().map(|file| file.attributes().clone()).collect();
+    assert!(attributes[0].is_executable() && !attributes[0].is_symlink());
+    assert_eq!(Some(sha1), attributes[0].sha1());
+    assert!(attributes[1].is_symlink());
+    assert_eq!(Some(Path::new("sub/run.sh")), attributes[1].symlink_path());
+    assert_eq!(Some("h"), info.files().nth(2).unwrap().attr());
+
+    assert_eq!(info.info_hash(), Info::from_bytes(info.to_bytes()).unwrap().info_hash());
+}
+
+#[test]
+fn positive_build_single_file_with_attributes() {
+    let accessor = DirectAccessor::new("FileName.txt", b"Some file data");
+
+    let bytes = InfoBuilder::new()
+        .set_file_attributes(
+            "FileName.txt",
+            Some(FileAttributes::new().with_hidden(true).with_executable(true)),
+        )
+        .build(1, accessor, |_| ())
+        .unwrap();
+    let info = Info::from_bytes(bytes).unwrap();
+
+    let file = info.files().next().unwrap();
+    assert_eq!(Some("hx"), file.attr());
+    assert!(file.attributes().is_hidden() && file.attributes().is_executable());
+    assert!(!file.is_padding());
+
+    let unset = FileAttributes::new().with_executable(true).with_executable(false);
+    assert!(unset.is_empty());
+}
+
+#[test]
+fn positive_build_reproducible_across_platforms() {
+    // Same files as created on macOS (decomposed unicode) and Windows (precomposed, `\` separators)
+    let decomposed = MultiAccessor(vec![("cafe\u{301}/menu.txt", vec![1u8; 1500]), ("run.sh", vec![2u8; 100])]);
+    let precomposed = MultiAccessor(vec![("caf\u{e9}\\menu.txt", vec![1u8; 1500]), ("run.sh", vec![2u8; 100])]);
+
+    let build = |accessor, created_by, executable| {
+        MetainfoBuilder::new()
+            .set_reproducible(true)
+            .set_creation_date(Some(DATE))
+            .set_created_by(Some(created_by))
+            .set_file_attributes("run.sh", Some(FileAttributes::new().with_executable(executable)))
+            .build(2, accessor, |_| ())
+            .unwrap()
+    };
+
+    let bytes = build(decomposed, "Client/1.0 (macOS)", true);
+    assert_eq!(bytes, build(precomposed, "Client/1.1 (Windows)", false));
+
+    let metainfo = Metainfo::from_bytes(bytes).unwrap();
+    assert_eq!(Some(DATE), metainfo.creation_date());
+    assert_eq!(None, metainfo.created_by());
+
+    let paths: Vec<_> = metainfo.info().files().map(|file| file.path().to_path_buf()).collect();
+    assert_eq!(vec![PathBuf::from("caf\u{e9}/menu.txt"), PathBuf::from("run.sh")], paths);
+    assert!(metainfo.info().files().all(|file| file.attr().is_none()));
+}
+
+#[test]
+fn negative_build_reproducible_unsorted_files() {
+    let accessor = MultiAccessor(vec![("two", vec![2u8; 100]), ("one", vec![1u8; 100])]);
+
+    let result = InfoBuilder::new().set_reproducible(true).build(1, accessor, |_| ());
+
+    assert!(matches!(result, Err(ParseError::UnsortedFiles { .. })));
+}
@@ -0,0 +1,108 @@
+use std::hint::black_box;
+
+use bytes::{BufMut as _, BytesMut};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use peer::{PeerProtocol, PeerProtocolCodec};
+use tokio_util::codec::Decoder as _;
+
+/// Number of messages buffered at once, as if they arrived in a single read from the socket.
+const NUM_MESSAGES: usize = 1024;
+
+/// Length prefixed protocol that does no work to parse a message, so only the cost of framing is measured.
+struct FramingProtocol {
+    length_prefixed: bool,
+}
+
+impl PeerProtocol for FramingProtocol {
+    type ProtocolMessage = usize;
+    type ProtocolMessageError = std::io::Error;
+
+    fn bytes_needed(&mut self, bytes: &[u8]) -> std::io::Result<Option<usize>> {
+        Ok(bytes
+            .get(..4)
+            .map(|prefix| 4 + u32::from_be_bytes(prefix.try_into().unwrap()) as usize))
+    }
+
+    fn parse_bytes(&mut self, bytes: &[u8]) -> std::io::Result<Result<Self::ProtocolMessage, Self::ProtocolMessageError>> {
+        Ok(Ok(bytes.len()))
+    }
+
+    fn write_bytes<W>(
+        &mut self,
+        item: &Result<Self::ProtocolMessage, Self::ProtocolMessageError>,
+        mut writer: W,
+    ) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let message_len = self.message_size(item)?;
+        let payload_len =
+            u32::try_from(message_len - 4).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+
+        writer.write_all(&payload_len.to_be_bytes())?;
+        writer.write_all(&vec![0u8; message_len - 4])?;
+
+        Ok(message_len)
+    }
+
+    fn message_size(&mut self, item: &Result<Self::ProtocolMessage, Self::ProtocolMessageError>) -> std::io::Result<usize> {
+        match item {
+            Ok(message_len) => Ok(*message_len),
+            Err(err) => Err(std::io::Error::new(err.kind(), err.to_string())),
+        }
+    }
+
+    fn is_length_prefixed(&self) -> bool {
+        self.length_prefixed
+    }
+}
+
+/// Buffer holding `NUM_MESSAGES` messages with the given payload length.
+fn buffered_messages(payload_len: usize) -> BytesMut {
+    let mut protocol = FramingProtocol { length_prefixed: true };
+    let mut writer = BytesMut::new().writer();
+
+    for _ in 0..NUM_MESSAGES {
+        protocol.write_bytes(&Ok(4 + payload_len), &mut writer).unwrap();
+    }
+
+    writer.into_inner()
+}
+
+fn decode_all(codec: &mut PeerProtocolCodec<FramingProtocol>, mut bytes: BytesMut) -> usize {
+    let mut num_decoded = 0;
+    while let Some(message) = codec.decode(&mut bytes).unwrap() {
+        black_box(message);
+        num_decoded += 1;
+    }
+
+    num_decoded
+}
+
+fn bench_decode(c: &mut Criterion, name: &str, bytes: &BytesMut) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(NUM_MESSAGES as u64));
+
+    for length_prefixed in [true, false] {
+        let mut codec = PeerProtocolCodec::new(FramingProtocol { length_prefixed });
+        assert_eq!(NUM_MESSAGES, decode_all(&mut codec, bytes.clone()));
+
+        let id = if length_prefixed { "batched" } else { "unbatched" };
+        group.bench_function(id, |b| {
+            b.iter_batched(|| bytes.clone(), |bytes| decode_all(&mut codec, bytes), BatchSize::SmallInput);
+        });
+    }
+
+    group.finish();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    // Sizes of have and request messages, the most frequent small messages
+    bench_decode(c, "decode 5 byte messages", &buffered_messages(5));
+    bench_decode(c, "decode 13 byte messages", &buffered_messages(13));
+    // Size of a piece message carrying a 16 KiB block
+    bench_decode(c, "decode 16 KiB messages", &buffered_messages(9 + 16 * 1024));
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,419 @@
+//! Set of pieces, as exchanged in `BitFieldMessage`(s) and used for piece selection.
+
+use bytes::Bytes;
+
+use crate::message::BitFieldMessage;
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+const BYTES_PER_WORD: usize = BITS_PER_WORD / 8;
+
+/// Fixed length set of piece indices.
+///
+/// Bits are stored in words laid out in wire order, so the first piece is the most significant bit of the
+/// first word, which makes set operations and conversion to and from a `BitFieldMessage` cheap.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Bitfield {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitfield {
+    /// Create a new `Bitfield` for the given number of pieces, with no piece set.
+    #[must_use]
+    pub fn new(len: usize) -> Bitfield {
+        Bitfield {
+            words: vec![0; words_for(len)],
+            len,
+        }
+    }
+
+    /// Create a new `Bitfield` for the given number of pieces, with every piece set.
+    #[must_use]
+    pub fn full(len: usize) -> Bitfield {
+        let mut bitfield = Bitfield {
+            words: vec![u64::MAX; words_for(len)],
+            len,
+        };
+        bitfield.clear_spare_bits();
+
+        bitfield
+    }
+
+    /// Create a new `Bitfield` for the given number of pieces from the bytes of a bitfield, in wire order.
+    ///
+    /// Spare bits past the number of pieces are ignored, and missing bytes are treated as unset.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8], len: usize) -> Bitfield {
+        let mut bitfield = Bitfield::new(len);
+
+        for (word, chunk) in bitfield.words.iter_mut().zip(bytes.chunks(BYTES_PER_WORD)) {
+            let mut word_bytes = [0u8; BYTES_PER_WORD];
+            word_bytes[..chunk.len()].copy_from_slice(chunk);
+
+            *word = u64::from_be_bytes(word_bytes);
+        }
+        bitfield.clear_spare_bits();
+
+        bitfield
+    }
+
+    /// Create a new `Bitfield` for the given number of pieces from a `BitFieldMessage`.
+    ///
+    /// Spare bits past the number of pieces are ignored, and missing bytes are treated as unset.
+    #[must_use]
+    pub fn from_message(message: &BitFieldMessage, len: usize) -> Bitfield {
+        Bitfield::from_bytes(message.bitfield(), len)
+    }
+
+    /// Bytes of the bitfield, in wire order, with spare bits unset.
+    #[must_use]
+    pub fn to_bytes(&self) -> Bytes {
+        let mut bytes: Vec<u8> = self.words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        bytes.truncate(bytes_for(self.len));
+
+        bytes.into()
+    }
+
+    /// Create a `BitFieldMessage` revealing the pieces of the bitfield.
+    #[must_use]
+    pub fn to_message(&self) -> BitFieldMessage {
+        BitFieldMessage::new(self.to_bytes())
+    }
+
+    /// Number of pieces in the bitfield.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether or not the bitfield has no pieces.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether or not the piece at the given index is set.
+    ///
+    /// Indices out of range are never set.
+    #[must_use]
+    pub fn get(&self, index: usize) -> bool {
+        index < self.len && self.words[index / BITS_PER_WORD] & bit_mask(index) != 0
+    }
+
+    /// Set the piece at the given index, returning whether or not it was previously set.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the index is out of range.
+    pub fn set(&mut self, index: usize) -> bool {
+        assert!(index < self.len, "bip_peer: Bitfield Index Out Of Range");
+
+        let word = &mut self.words[index / BITS_PER_WORD];
+        let was_set = *word & bit_mask(index) != 0;
+        *word |= bit_mask(index);
+
+        was_set
+    }
+
+    /// Unset the piece at the given index, returning whether or not it was previously set.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the index is out of range.
+    pub fn unset(&mut self, index: usize) -> bool {
+        assert!(index < self.len, "bip_peer: Bitfield Index Out Of Range");
+
+        let word = &mut self.words[index / BITS_PER_WORD];
+        let was_set = *word & bit_mask(index) != 0;
+        *word &= !bit_mask(index);
+
+        was_set
+    }
+
+    /// Number of pieces set.
+    #[must_use]
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Number of pieces not set.
+    #[must_use]
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    /// Whether or not every piece is set.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.count_ones() == self.len
+    }
+
+    /// Whether or not no piece is set.
+    #[must_use]
+    pub fn is_clear(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Whether or not every piece set in this bitfield is also set in the other.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    #[must_use]
+    pub fn is_subset(&self, other: &Bitfield) -> bool {
+        self.zip_words(other).all(|(lhs, rhs)| lhs & !rhs == 0)
+    }
+
+    /// Keep only the pieces that are also set in the other bitfield.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    pub fn intersect_with(&mut self, other: &Bitfield) {
+        self.apply(other, |lhs, rhs| lhs & rhs);
+    }
+
+    /// Unset the pieces that are set in the other bitfield.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    pub fn difference_with(&mut self, other: &Bitfield) {
+        self.apply(other, |lhs, rhs| lhs & !rhs);
+    }
+
+    /// Set the pieces that are set in the other bitfield.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    pub fn union_with(&mut self, other: &Bitfield) {
+        self.apply(other, |lhs, rhs| lhs | rhs);
+    }
+
+    /// Pieces set in both this bitfield and the other.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    #[must_use]
+    pub fn intersection(&self, other: &Bitfield) -> Bitfield {
+        let mut bitfield = self.clone();
+        bitfield.intersect_with(other);
+
+        bitfield
+    }
+
+    /// Pieces set in this bitfield but not in the other.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    #[must_use]
+    pub fn difference(&self, other: &Bitfield) -> Bitfield {
+        let mut bitfield = self.clone();
+        bitfield.difference_with(other);
+
+        bitfield
+    }
+
+    /// Pieces set in either this bitfield or the other.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lengths of the bitfields differ.
+    #[must_use]
+    pub fn union(&self, other: &Bitfield) -> Bitfield {
+        let mut bitfield = self.clone();
+        bitfield.union_with(other);
+
+        bitfield
+    }
+
+    /// Iterate over the indices of the pieces that are set, in ascending order.
+    pub fn iter_set(&self) -> impl Iterator<Item = usize> + '_ {
+        SetBits::new(self.words.iter().copied())
+    }
+
+    /// Iterate over the indices of the pieces that are not set, in ascending order.
+    pub fn iter_unset(&self) -> impl Iterator<Item = usize> + '_ {
+        let len = self.len;
+
+        SetBits::new(self.words.iter().map(|word| !word)).take_while(move |index| *index < len)
+    }
+
+    fn zip_words<'a>(&'a self, other: &'a Bitfield) -> impl Iterator<Item = (u64, u64)> + 'a {
+        assert_eq!(self.len, other.len, "bip_peer: Bitfield Lengths Differ");
+
+        self.words.iter().copied().zip(other.words.iter().copied())
+    }
+
+    fn apply<F>(&mut self, other: &Bitfield, op: F)
+    where
+        F: Fn(u64, u64) -> u64,
+    {
+        assert_eq!(self.len, other.len, "bip_peer: Bitfield Lengths Differ");
+
+        for (lhs, rhs) in self.words.iter_mut().zip(other.words.iter()) {
+            *lhs = op(*lhs, *rhs);
+        }
+    }
+
+    fn clear_spare_bits(&mut self) {
+        let used_bits = self.len % BITS_PER_WORD;
+
+        if let (Some(last), true) = (self.words.last_mut(), used_bits != 0) {
+            *last &= u64::MAX << (BITS_PER_WORD - used_bits);
+        }
+    }
+}
+
+impl From<&Bitfield> for BitFieldMessage {
+    fn from(bitfield: &Bitfield) -> BitFieldMessage {
+        bitfield.to_message()
+    }
+}
+
+/// Iterator over the indices of the set bits of a sequence of words.
+struct SetBits<I> {
+    words: I,
+    current: u64,
+    base: usize,
+}
+
+impl<I> SetBits<I>
+where
+    I: Iterator<Item = u64>,
+{
+    fn new(words: I) -> SetBits<I> {
+        SetBits {
+            words,
+            current: 0,
+            // Wraps to zero when the first word is loaded
+            base: BITS_PER_WORD.wrapping_neg(),
+        }
+    }
+}
+
+impl<I> Iterator for SetBits<I>
+where
+    I: Iterator<Item = u64>,
+{
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.current = self.words.next()?;
+            self.base = self.base.wrapping_add(BITS_PER_WORD);
+        }
+
+        let offset = self.current.leading_zeros() as usize;
+        self.current &= !(1 << (BITS_PER_WORD - 1 - offset));
+
+        Some(self.base + offset)
+    }
+}
+
+fn bit_mask(index: usize) -> u64 {
+    1 << (BITS_PER_WORD - 1 - index % BITS_PER_WORD)
+}
+
+#[allow(clippy::manual_div_ceil)] // `usize::div_ceil` is newer than our minimum supported rust version
+fn words_for(len: usize) -> usize {
+    (len + BITS_PER_WORD - 1) / BITS_PER_WORD
+}
+
+#[allow(clippy::manual_div_ceil)] // `usize::div_ceil` is newer than our minimum supported rust version
+fn bytes_for(len: usize) -> usize {
+    (len + 7) / 8
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::Bitfield;
+    use crate::message::BitFieldMessage;
+
+    fn bitfield(len: usize, set: &[usize]) -> Bitfield {
+        let mut bitfield = Bitfield::new(len);
+        for index in set {
+            bitfield.set(*index);
+        }
+
+        bitfield
+    }
+
+    #[test]
+    fn positive_set_get_unset() {
+        let mut bitfield = Bitfield::new(70);
+
+        assert!(!bitfield.set(65));
+        assert!(bitfield.set(65));
+        assert!(bitfield.get(65));
+        assert!(!bitfield.get(64));
+        assert!(!bitfield.get(70));
+
+        assert!(bitfield.unset(65));
+        assert!(bitfield.is_clear());
+    }
+
+    #[test]
+    fn positive_full_counts() {
+        let bitfield = Bitfield::full(70);
+
+        assert_eq!(70, bitfield.count_ones());
+        assert_eq!(0, bitfield.count_zeros());
+        assert!(bitfield.is_full());
+        assert_eq!(
+            Bytes::from(vec![0xFF; 8].into_iter().chain([0xFC]).collect::<Vec<u8>>()),
+            bitfield.to_bytes()
+        );
+    }
+
+    #[test]
+    fn positive_set_operations() {
+        let lhs = bitfield(100, &[0, 3, 64, 99]);
+        let rhs = bitfield(100, &[3, 50, 99]);
+
+        assert_eq!(vec![3, 99], lhs.intersection(&rhs).iter_set().collect::<Vec<_>>());
+        assert_eq!(vec![0, 64], lhs.difference(&rhs).iter_set().collect::<Vec<_>>());
+        assert_eq!(vec![0, 3, 50, 64, 99], lhs.union(&rhs).iter_set().collect::<Vec<_>>());
+
+        assert!(lhs.intersection(&rhs).is_subset(&rhs));
+        assert!(!lhs.is_subset(&rhs));
+    }
+
+    #[test]
+    fn positive_iter_unset() {
+        let bitfield = Bitfield::full(66).difference(&bitfield(66, &[1, 65]));
+
+        assert_eq!(vec![1, 65], bitfield.iter_unset().collect::<Vec<_>>());
+        assert_eq!(Vec::<usize>::new(), Bitfield::new(0).iter_unset().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn positive_message_round_trip() {
+        let message = BitFieldMessage::new(Bytes::from_static(&[0xA0, 0x01, 0xFF]));
+
+        // Spare bits past the last piece are dropped
+        let bitfield = Bitfield::from_message(&message, 20);
+
+        assert_eq!(vec![0, 2, 15, 16, 17, 18, 19], bitfield.iter_set().collect::<Vec<_>>());
+        assert_eq!(&[0xA0, 0x01, 0xF0], bitfield.to_message().bitfield());
+    }
+
+    #[test]
+    fn positive_from_short_bytes() {
+        let bitfield = Bitfield::from_bytes(&[0x80], 20);
+
+        assert_eq!(vec![0], bitfield.iter_set().collect::<Vec<_>>());
+        assert_eq!(3, bitfield.to_bytes().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "bip_peer: Bitfield Lengths Differ")]
+    fn negative_set_operation_length_mismatch() {
+        let _bitfield = Bitfield::new(8).union(&Bitfield::new(9));
+    }
+}
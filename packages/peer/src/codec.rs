@@ -1,16 +1,34 @@
 //! Codecs operating over `PeerProtocol`s.
 
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
 use crate::protocol::PeerProtocol;
+use crate::stats::WireStatsHandle;
+
+/// Length of the prefix framing each message of a length prefixed `PeerProtocol`.
+const LENGTH_PREFIX_LEN: usize = 4;
+
+/// Largest message that is framed as part of a batch.
+///
+/// Framing is cheap compared to handling the data of larger messages (such as piece messages),
+/// so batching them would only cost an extra pass over their data.
+const MAX_BATCHED_MESSAGE_LEN: usize = 1024;
 
 /// Codec operating over some `PeerProtocol`.
+///
+/// For length prefixed protocols, consecutive small messages that are buffered are framed in a
+/// single pass, and the framed messages are then parsed one per call to `decode`, rather than
+/// calling `PeerProtocol::bytes_needed` and splitting the buffer for every message.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct PeerProtocolCodec<P> {
     protocol: P,
     max_payload: Option<usize>,
+    stats: WireStatsHandle,
+    // Complete messages that were framed together, and the offset of the next one to parse
+    batch: Bytes,
+    batch_offset: usize,
 }
 
 impl<P> PeerProtocolCodec<P> {
@@ -23,6 +41,9 @@ impl<P> PeerProtocolCodec<P> {
         PeerProtocolCodec {
             protocol,
             max_payload: None,
+            stats: WireStatsHandle::default(),
+            batch: Bytes::new(),
+            batch_offset: 0,
         }
     }
 
@@ -32,19 +53,84 @@ impl<P> PeerProtocolCodec<P> {
         PeerProtocolCodec {
             protocol,
             max_payload: Some(max_payload),
+            stats: WireStatsHandle::default(),
+            batch: Bytes::new(),
+            batch_offset: 0,
         }
     }
+
+    /// Handle to the statistics of the messages encoded and decoded by this codec.
+    ///
+    /// Messages are classified assuming the peer wire protocol framing, so the handle can
+    /// be kept around to take snapshots after the codec has been moved into a connection.
+    pub fn stats(&self) -> WireStatsHandle {
+        self.stats.clone()
+    }
+
+    /// Split the complete, small, length prefixed messages at the front of `src` into `batch`.
+    ///
+    /// Messages are then parsed directly out of the batch, without splitting each one out of `src`.
+    fn frame_batch(&mut self, src: &mut BytesMut) {
+        let (num_frames, batch_len) = scan_length_prefixed(src, self.max_payload);
+
+        if num_frames < 2 {
+            // Not worth batching, leave it to the single message path
+            return;
+        }
+
+        self.batch = src.split_to(batch_len).freeze();
+        self.batch_offset = 0;
+
+        self.stats.record_received_all(length_prefixed_frames(&self.batch));
+    }
 }
 
-impl<P> Decoder for PeerProtocolCodec<P>
+/// Total length of the message at the front of the given bytes, if its big endian `u32` length prefix is complete.
+fn length_prefixed_len(bytes: &[u8]) -> Option<usize> {
+    let prefix: [u8; LENGTH_PREFIX_LEN] = bytes.get(..LENGTH_PREFIX_LEN)?.try_into().ok()?;
+
+    usize::try_from(u32::from_be_bytes(prefix))
+        .ok()
+        .and_then(|payload_len| payload_len.checked_add(LENGTH_PREFIX_LEN))
+}
+
+/// Complete messages at the front of the given bytes, framed by a big endian `u32` length prefix.
+fn length_prefixed_frames(mut bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let frame_len = length_prefixed_len(bytes).filter(|&frame_len| frame_len <= bytes.len())?;
+        let (frame, rest) = bytes.split_at(frame_len);
+        bytes = rest;
+
+        Some(frame)
+    })
+}
+
+/// Scan the given bytes for complete messages framed by a big endian `u32` length prefix.
+///
+/// Stops at the first message that is incomplete, larger than `max_payload`, or too large to
+/// be batched. Returns the number of complete messages, and the number of bytes they take up.
+///
+/// The scan is scalar: each length prefix gives the offset of the next one, so the prefixes can
+/// not be located with SIMD without speculating on message boundaries. Vectorizing the scan is
+/// deferred until benchmarks show it dominating the cost of framing, which they do not yet.
+fn scan_length_prefixed(bytes: &[u8], max_payload: Option<usize>) -> (usize, usize) {
+    let max_frame_len = max_payload.map_or(MAX_BATCHED_MESSAGE_LEN, |max_payload| {
+        max_payload.min(MAX_BATCHED_MESSAGE_LEN)
+    });
+
+    length_prefixed_frames(bytes)
+        .take_while(|frame| frame.len() <= max_frame_len)
+        .fold((0, 0), |(num_frames, batch_len), frame| {
+            (num_frames + 1, batch_len + frame.len())
+        })
+}
+
+impl<P> PeerProtocolCodec<P>
 where
     P: PeerProtocol,
-    <P as PeerProtocol>::ProtocolMessageError: std::error::Error + Send + Sync + 'static,
 {
-    type Item = P::ProtocolMessage;
-    type Error = std::io::Error;
-
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+    /// Split the message at the front of `src` out, if it is complete.
+    fn frame_single(&mut self, src: &mut BytesMut) -> std::io::Result<Option<Bytes>> {
         let bytes_needed = self.protocol.bytes_needed(src)?;
 
         let Some(bytes_needed) = bytes_needed else {
@@ -60,13 +146,53 @@ where
             }
         };
 
-        let bytes = if bytes_needed <= src.len() {
-            src.split_to(bytes_needed).freeze()
+        if bytes_needed <= src.len() {
+            Ok(Some(src.split_to(bytes_needed).freeze()))
         } else {
-            return Ok(None);
+            Ok(None)
+        }
+    }
+}
+
+impl<P> Decoder for PeerProtocolCodec<P>
+where
+    P: PeerProtocol,
+    <P as PeerProtocol>::ProtocolMessageError: std::error::Error + Send + Sync + 'static,
+{
+    type Item = P::ProtocolMessage;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.batch_offset == self.batch.len() && self.protocol.is_length_prefixed() {
+            self.frame_batch(src);
+        }
+
+        let result = if self.batch_offset < self.batch.len() {
+            let remaining = &self.batch[self.batch_offset..];
+            let frame_len = length_prefixed_len(remaining).expect("bip_peer: Batch Only Contains Complete Messages");
+            let bytes = &remaining[..frame_len];
+            self.batch_offset += frame_len;
+
+            // Stats for the whole batch were recorded when it was framed
+            let result = self.protocol.parse_bytes(bytes);
+
+            if self.batch_offset == self.batch.len() {
+                // Release the batch, so its memory can be reclaimed by the buffer
+                self.batch = Bytes::new();
+                self.batch_offset = 0;
+            }
+
+            result
+        } else {
+            let Some(bytes) = self.frame_single(src)? else {
+                return Ok(None);
+            };
+
+            self.stats.record_received(&bytes);
+            self.protocol.parse_bytes(&bytes)
         };
 
-        match self.protocol.parse_bytes(&bytes) {
+        match result {
             Ok(item) => item.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)),
             Err(err) => Err(err),
         }
@@ -87,19 +213,26 @@ where
 
         dst.reserve(size);
 
+        let start = dst.len();
         let _ = self.protocol.write_bytes(&message, dst.writer())?;
 
+        self.stats.record_sent(&dst[start..]);
+
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use bytes::BytesMut;
-    use tokio_util::codec::Decoder as _;
+    use bytes::{Bytes, BytesMut};
+    use tokio_util::codec::{Decoder as _, Encoder as _};
 
     use super::PeerProtocolCodec;
+    use crate::message::{HaveMessage, PeerWireProtocolMessage, PieceMessage};
+    use crate::protocol::null::NullProtocol;
+    use crate::protocol::wire::PeerWireProtocol;
     use crate::protocol::PeerProtocol;
+    use crate::stats::WireMessageType;
 
     struct ConsumeProtocol;
 
@@ -153,4 +286,88 @@ mod tests {
         assert!(codec.decode(&mut bytes).is_err());
         assert_eq!(bytes.len(), 200);
     }
+
+    #[test]
+    fn positive_stats_count_overhead_and_payload() {
+        let mut codec = PeerProtocolCodec::new(PeerWireProtocol::new(NullProtocol::new()));
+        let stats = codec.stats();
+        let mut bytes = BytesMut::new();
+
+        codec.encode(Ok(PeerWireProtocolMessage::KeepAlive), &mut bytes).unwrap();
+        codec
+            .encode(
+                Ok(PeerWireProtocolMessage::Piece(PieceMessage::new(
+                    0,
+                    0,
+                    Bytes::from_static(&[0u8; 16]),
+                ))),
+                &mut bytes,
+            )
+            .unwrap();
+
+        while codec.decode(&mut bytes).unwrap().is_some() {}
+
+        let snapshot = stats.snapshot();
+
+        for direction in [snapshot.sent(), snapshot.received()] {
+            assert_eq!(2, direction.total_messages());
+            assert_eq!(1, direction.keep_alives());
+            assert_eq!(1, direction.messages(WireMessageType::Piece));
+            assert_eq!(16, direction.payload_bytes());
+            assert_eq!(4 + 13, direction.overhead_bytes());
+        }
+    }
+
+    #[test]
+    fn positive_decode_batch_of_messages() {
+        let mut codec = PeerProtocolCodec::new(PeerWireProtocol::new(NullProtocol::new()));
+        let stats = codec.stats();
+        let mut bytes = BytesMut::new();
+
+        for piece_index in 0..3 {
+            codec
+                .encode(Ok(PeerWireProtocolMessage::Have(HaveMessage::new(piece_index))), &mut bytes)
+                .unwrap();
+        }
+        codec.encode(Ok(PeerWireProtocolMessage::KeepAlive), &mut bytes).unwrap();
+        // Start of a message that is not complete yet
+        bytes.extend_from_slice(&[0, 0, 0, 5, 4]);
+
+        let mut num_decoded = 0;
+        while codec.decode(&mut bytes).unwrap().is_some() {
+            num_decoded += 1;
+        }
+
+        let snapshot = stats.snapshot();
+        let received = snapshot.received();
+        assert_eq!(4, num_decoded);
+        assert_eq!(3, received.messages(WireMessageType::Have));
+        assert_eq!(1, received.keep_alives());
+        assert_eq!(&[0, 0, 0, 5, 4][..], &bytes[..]);
+    }
+
+    #[test]
+    fn negative_decode_batch_above_max_payload() {
+        let mut codec = PeerProtocolCodec::with_max_payload(PeerWireProtocol::new(NullProtocol::new()), 9);
+        let mut bytes = BytesMut::new();
+
+        codec.encode(Ok(PeerWireProtocolMessage::KeepAlive), &mut bytes).unwrap();
+        codec
+            .encode(Ok(PeerWireProtocolMessage::Have(HaveMessage::new(1))), &mut bytes)
+            .unwrap();
+        bytes.extend_from_slice(&[0, 0, 0, 6, 4, 0, 0, 0, 1, 0]);
+
+        // Messages before the one above the max payload are still yielded
+        assert!(codec.decode(&mut bytes).unwrap().is_some());
+        assert!(codec.decode(&mut bytes).unwrap().is_some());
+        assert!(codec.decode(&mut bytes).is_err());
+        assert_eq!(10, bytes.len());
+    }
+
+    #[test]
+    fn positive_scan_stops_at_incomplete_message() {
+        let scanned = super::scan_length_prefixed(&[0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 2, 1], None);
+
+        assert_eq!((2, 9), scanned);
+    }
 }
@@ -1,17 +1,30 @@
+mod bitfield;
 mod codec;
 mod manager;
 mod message;
 mod protocol;
+mod requests;
+mod scheduler;
+mod slots;
+mod stats;
 
 pub use codec::PeerProtocolCodec;
 
+pub use crate::bitfield::Bitfield;
 pub use crate::manager::builder::PeerManagerBuilder;
-pub use crate::manager::messages::{ManagedMessage, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage};
+pub use crate::manager::messages::{
+    DisconnectReason, ManagedMessage, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage,
+};
 pub use crate::manager::peer_info::PeerInfo;
 pub use crate::manager::sink::PeerManagerSink;
 pub use crate::manager::stream::PeerManagerStream;
+pub use crate::manager::validation::{MessageKind, ProtocolViolation, ViolationPolicy};
 pub use crate::manager::PeerManager;
 pub use crate::protocol::{NestedPeerProtocol, PeerProtocol};
+pub use crate::requests::{RequestTracker, TimedOutRequest};
+pub use crate::scheduler::UploadScheduler;
+pub use crate::slots::{ConnectionDirection, ConnectionSlot, ConnectionSlots, SlotConfig};
+pub use crate::stats::{DirectionStats, WireMessageType, WireStats, WireStatsHandle};
 
 /// Serializable and deserializable protocol messages.
 pub mod messages {
@@ -22,9 +35,10 @@ pub mod messages {
 
     pub use crate::message::{
         BitFieldIter, BitFieldMessage, BitsExtensionMessage, CancelMessage, ExtendedMessage, ExtendedType, HaveMessage,
-        NullProtocolMessage, PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError, PeerWireProtocolMessage,
-        PeerWireProtocolMessageError, PieceMessage, PortMessage, RequestMessage, UtMetadataDataMessage, UtMetadataMessage,
-        UtMetadataRejectMessage, UtMetadataRequestMessage,
+        MetadataCompression, NullProtocolMessage, PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError,
+        PeerWireProtocolMessage, PeerWireProtocolMessageError, PieceMessage, PortMessage, RequestMessage, UtHolepunchErrorCode,
+        UtHolepunchMessage, UtHolepunchMessageError, UtMetadataDataMessage, UtMetadataMessage, UtMetadataRejectMessage,
+        UtMetadataRequestMessage,
     };
 }
 
@@ -36,7 +50,7 @@ pub mod error {
 
 /// Implementations of `PeerProtocol`.
 pub mod protocols {
-    pub use crate::protocol::extension::PeerExtensionProtocol;
+    pub use crate::protocol::extension::{ExtensionIds, PeerExtensionProtocol};
     pub use crate::protocol::null::NullProtocol;
     pub use crate::protocol::unit::UnitProtocol;
     pub use crate::protocol::wire::PeerWireProtocol;
@@ -3,6 +3,7 @@ use std::time::Duration;
 use futures::sink::Sink;
 use futures::{Stream, TryStream};
 
+use super::validation::ViolationPolicy;
 use super::{ManagedMessage, PeerManager};
 
 const DEFAULT_PEER_CAPACITY: usize = 1000;
@@ -10,6 +11,8 @@ const DEFAULT_SINK_BUFFER_CAPACITY: usize = 100;
 const DEFAULT_STREAM_BUFFER_CAPACITY: usize = 100;
 const DEFAULT_HEARTBEAT_INTERVAL_MILLIS: u64 = 60 * 1000;
 const DEFAULT_HEARTBEAT_TIMEOUT_MILLIS: u64 = 2 * 60 * 1000;
+const DEFAULT_CHOKE_REQUEST_WINDOW_MILLIS: u64 = 10 * 1000;
+const DEFAULT_SEND_BATCH_SIZE: usize = 1;
 
 /// Builder for configuring a `PeerManager`.
 #[allow(clippy::module_name_repetitions)]
@@ -20,6 +23,10 @@ pub struct PeerManagerBuilder {
     stream_buffer_capacity: usize,
     heartbeat_interval: Duration,
     heartbeat_timeout: Duration,
+    violation_policy: ViolationPolicy,
+    choke_request_window: Duration,
+    send_batch_size: usize,
+    idle_park_timeout: Option<Duration>,
 }
 
 impl PeerManagerBuilder {
@@ -32,6 +39,10 @@ impl PeerManagerBuilder {
             stream_buffer_capacity: DEFAULT_STREAM_BUFFER_CAPACITY,
             heartbeat_interval: Duration::from_millis(DEFAULT_HEARTBEAT_INTERVAL_MILLIS),
             heartbeat_timeout: Duration::from_millis(DEFAULT_HEARTBEAT_TIMEOUT_MILLIS),
+            violation_policy: ViolationPolicy::default(),
+            choke_request_window: Duration::from_millis(DEFAULT_CHOKE_REQUEST_WINDOW_MILLIS),
+            send_batch_size: DEFAULT_SEND_BATCH_SIZE,
+            idle_park_timeout: None,
         }
     }
 
@@ -70,6 +81,43 @@ impl PeerManagerBuilder {
         self
     }
 
+    /// Sets the action taken when a peer violates the protocol.
+    #[must_use]
+    pub fn with_violation_policy(mut self, policy: ViolationPolicy) -> PeerManagerBuilder {
+        self.violation_policy = policy;
+        self
+    }
+
+    /// Sets how long after we choke a peer its requests are still tolerated, as they may already be in flight.
+    #[must_use]
+    pub fn with_choke_request_window(mut self, window: Duration) -> PeerManagerBuilder {
+        self.choke_request_window = window;
+        self
+    }
+
+    /// Sets the maximum number of queued messages that are written to a peer before it is flushed.
+    ///
+    /// Messages that are already queued for a peer, such as a burst of block requests, are then written
+    /// together and flushed at once, which saves system calls and packets. Messages are reported as sent
+    /// once written, before they are flushed. Defaults to one, which flushes every message by itself.
+    #[must_use]
+    pub fn with_send_batch_size(mut self, size: usize) -> PeerManagerBuilder {
+        self.send_batch_size = size.max(1);
+        self
+    }
+
+    /// Sets how long a connection has to go without any message other than keep-alives, while neither
+    /// side is interested in the other, before it is parked.
+    ///
+    /// Parked connections send their keep-alive messages together with the other parked connections, so
+    /// that they are woken up less often, and are revived as soon as any other message is sent or received.
+    /// Defaults to `None`, which never parks connections.
+    #[must_use]
+    pub fn with_idle_park_timeout(mut self, timeout: Option<Duration>) -> PeerManagerBuilder {
+        self.idle_park_timeout = timeout;
+        self
+    }
+
     /// Retrieves the peer capacity.
     #[must_use]
     pub fn peer_capacity(&self) -> usize {
@@ -100,6 +148,30 @@ impl PeerManagerBuilder {
         self.heartbeat_timeout
     }
 
+    /// Retrieves the protocol `ViolationPolicy`.
+    #[must_use]
+    pub fn violation_policy(&self) -> ViolationPolicy {
+        self.violation_policy
+    }
+
+    /// Retrieves the window in which requests are tolerated after choking a peer.
+    #[must_use]
+    pub fn choke_request_window(&self) -> Duration {
+        self.choke_request_window
+    }
+
+    /// Retrieves the maximum number of messages written to a peer before it is flushed.
+    #[must_use]
+    pub fn send_batch_size(&self) -> usize {
+        self.send_batch_size
+    }
+
+    /// Retrieves the timeout after which idle connections are parked, if any.
+    #[must_use]
+    pub fn idle_park_timeout(&self) -> Option<Duration> {
+        self.idle_park_timeout
+    }
+
     /// Builds a `PeerManager` from the current `PeerManagerBuilder` configuration.
     #[must_use]
     pub fn build<Peer, Message>(self) -> PeerManager<Peer, Message>
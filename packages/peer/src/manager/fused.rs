@@ -1,10 +1,12 @@
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::stream::{Fuse, Stream};
-use futures::{StreamExt, TryStream};
-use tokio::time::Instant;
+use futures::{FutureExt as _, StreamExt, TryStream};
+use tokio::time::{Instant, Sleep};
 
 /// Error type for `PersistentStream`.
 pub enum PersistentError<Err> {
@@ -68,6 +70,9 @@ where
 
 /// A stream wrapper that enforces a recurring timeout. If the underlying stream does not yield
 /// an item within the specified duration, a timeout error is returned.
+///
+/// While the coalescing flag is set, deadlines are rounded up to a grid shared by all streams, so
+/// that the timeouts of many idle streams fire together instead of each waking up on its own.
 pub struct RecurringTimeoutStream<St, Ty, Err>
 where
     St: Stream<Item = Result<Ty, Err>>,
@@ -75,7 +80,8 @@ where
 {
     stream: Fuse<St>,
     timeout: Duration,
-    deadline: Instant,
+    sleep: Pin<Box<Sleep>>,
+    coalesce: Option<Arc<AtomicBool>>,
 }
 
 impl<St, Ty, Err> RecurringTimeoutStream<St, Ty, Err>
@@ -88,9 +94,56 @@ where
         RecurringTimeoutStream {
             stream: stream.fuse(),
             timeout,
-            deadline: Instant::now() + timeout,
+            sleep: Box::pin(tokio::time::sleep(timeout)),
+            coalesce: None,
         }
     }
+
+    /// Coalesces the timeouts with those of other streams while `flag` is set.
+    pub fn with_coalescing(mut self, flag: Arc<AtomicBool>) -> RecurringTimeoutStream<St, Ty, Err> {
+        self.coalesce = Some(flag);
+        self
+    }
+
+    fn reset_deadline(&mut self) {
+        let now = Instant::now();
+        let coalesced = self.coalesce.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed));
+
+        let deadline = if coalesced {
+            coalesced_deadline(now, self.timeout)
+        } else {
+            now + self.timeout
+        };
+
+        self.sleep.as_mut().reset(deadline);
+    }
+}
+
+/// Rounds `now + timeout / 2` up to the next multiple of `timeout / 2` since a process wide epoch.
+fn coalesced_deadline(now: Instant, timeout: Duration) -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+    grid_deadline(*EPOCH.get_or_init(|| now), now, timeout)
+}
+
+/// Rounds `now + timeout / 2` up to the next multiple of `timeout / 2` since `epoch`.
+///
+/// The deadline is never earlier than half the timeout, nor later than the full timeout.
+#[allow(clippy::manual_div_ceil)] // `u128::div_ceil` is newer than our minimum supported rust version
+fn grid_deadline(epoch: Instant, now: Instant, timeout: Duration) -> Instant {
+    let tick = timeout / 2;
+    if tick.is_zero() || now < epoch {
+        return now + timeout;
+    }
+
+    let earliest = now.duration_since(epoch) + tick;
+    let ticks = (earliest.as_nanos() + tick.as_nanos() - 1) / tick.as_nanos();
+
+    // Only overflows for timeouts of centuries
+    match u32::try_from(ticks).ok().and_then(|ticks| tick.checked_mul(ticks)) {
+        Some(offset) => epoch + offset,
+        None => now + timeout,
+    }
 }
 
 impl<St, Ty, Err> Stream for RecurringTimeoutStream<St, Ty, Err>
@@ -104,9 +157,8 @@ where
         let ready = match self.stream.poll_next_unpin(cx) {
             Poll::Ready(ready) => ready,
             Poll::Pending => {
-                let now = Instant::now();
-                if now > self.deadline {
-                    self.deadline = now + self.timeout;
+                if self.sleep.poll_unpin(cx).is_ready() {
+                    self.reset_deadline();
 
                     return Poll::Ready(Some(Err(RecurringTimeoutError::Timeout)));
                 }
@@ -121,7 +173,7 @@ where
         match item {
             Ok(message) => {
                 // Reset the timeout
-                self.deadline = Instant::now() + self.timeout;
+                self.reset_deadline();
 
                 Poll::Ready(Some(Ok(message)))
             }
@@ -129,3 +181,38 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::grid_deadline;
+
+    #[test]
+    fn positive_coalesced_deadline_within_bounds() {
+        let timeout = Duration::from_secs(120);
+        let epoch = Instant::now();
+
+        for offset in [0, 1, 59, 60, 61, 119, 120, 500] {
+            let now = epoch + Duration::from_secs(offset);
+            let deadline = grid_deadline(epoch, now, timeout);
+
+            assert!(deadline >= now + timeout / 2);
+            assert!(deadline <= now + timeout);
+        }
+    }
+
+    #[test]
+    fn positive_coalesced_deadline_shared_grid() {
+        let timeout = Duration::from_secs(120);
+        let epoch = Instant::now();
+
+        let first = grid_deadline(epoch, epoch + Duration::from_secs(10), timeout);
+        let second = grid_deadline(epoch, epoch + Duration::from_secs(11), timeout);
+
+        assert_eq!(first, second);
+        assert_eq!(first, epoch + timeout);
+    }
+}
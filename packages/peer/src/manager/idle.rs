@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Tracks whether a single connection has gone idle, so that it can be parked.
+///
+/// A connection is idle when neither side is interested in the other, and no message other than
+/// keep-alives was exchanged for the park timeout. Any such message revives a parked connection.
+pub struct IdleTracker {
+    park_timeout: Option<Duration>,
+    last_activity: Instant,
+    we_interested: bool,
+    peer_interested: bool,
+    parked: Arc<AtomicBool>,
+}
+
+impl IdleTracker {
+    /// Creates a new `IdleTracker`, which never parks the connection if `park_timeout` is `None`.
+    pub fn new(park_timeout: Option<Duration>) -> IdleTracker {
+        IdleTracker {
+            park_timeout,
+            last_activity: Instant::now(),
+            we_interested: false,
+            peer_interested: false,
+            parked: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Shared flag that is set while the connection is parked.
+    pub fn parked_flag(&self) -> Arc<AtomicBool> {
+        self.parked.clone()
+    }
+
+    /// Whether the connection is currently parked.
+    pub fn is_parked(&self) -> bool {
+        self.parked.load(Ordering::Relaxed)
+    }
+
+    /// Records a message, other than a keep-alive, received from the peer.
+    ///
+    /// Returns `true` if this revived the connection.
+    pub fn received(&mut self, interest: Option<bool>) -> bool {
+        if let Some(interested) = interest {
+            self.peer_interested = interested;
+        }
+
+        self.activity()
+    }
+
+    /// Records a message, other than a keep-alive, sent to the peer.
+    ///
+    /// Returns `true` if this revived the connection.
+    pub fn sent(&mut self, interest: Option<bool>) -> bool {
+        if let Some(interested) = interest {
+            self.we_interested = interested;
+        }
+
+        self.activity()
+    }
+
+    /// Parks the connection if it has gone idle.
+    ///
+    /// Returns `true` if the connection was parked by this call.
+    pub fn park_if_idle(&mut self, now: Instant) -> bool {
+        let Some(park_timeout) = self.park_timeout else {
+            return false;
+        };
+
+        if self.is_parked() || self.we_interested || self.peer_interested {
+            return false;
+        }
+
+        if now.saturating_duration_since(self.last_activity) < park_timeout {
+            return false;
+        }
+
+        self.parked.store(true, Ordering::Relaxed);
+        true
+    }
+
+    fn activity(&mut self) -> bool {
+        self.last_activity = Instant::now();
+
+        self.parked.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::IdleTracker;
+
+    const PARK_TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn positive_park_after_timeout() {
+        let mut tracker = IdleTracker::new(Some(PARK_TIMEOUT));
+        let now = Instant::now();
+
+        assert!(!tracker.park_if_idle(now));
+        assert!(tracker.park_if_idle(now + PARK_TIMEOUT));
+        assert!(tracker.is_parked());
+
+        // Already parked
+        assert!(!tracker.park_if_idle(now + PARK_TIMEOUT * 2));
+    }
+
+    #[test]
+    fn positive_revive_on_activity() {
+        let mut tracker = IdleTracker::new(Some(PARK_TIMEOUT));
+        let flag = tracker.parked_flag();
+
+        assert!(tracker.park_if_idle(Instant::now() + PARK_TIMEOUT));
+        assert!(flag.load(std::sync::atomic::Ordering::Relaxed));
+
+        assert!(tracker.received(None));
+        assert!(!tracker.is_parked());
+        assert!(!flag.load(std::sync::atomic::Ordering::Relaxed));
+
+        // Not parked anymore, so nothing to revive
+        assert!(!tracker.sent(None));
+    }
+
+    #[test]
+    fn negative_park_while_interested() {
+        let mut tracker = IdleTracker::new(Some(PARK_TIMEOUT));
+        tracker.sent(Some(true));
+        assert!(!tracker.park_if_idle(Instant::now() + PARK_TIMEOUT));
+
+        tracker.sent(Some(false));
+        tracker.received(Some(true));
+        assert!(!tracker.park_if_idle(Instant::now() + PARK_TIMEOUT));
+
+        tracker.received(Some(false));
+        assert!(tracker.park_if_idle(Instant::now() + PARK_TIMEOUT));
+    }
+
+    #[test]
+    fn negative_park_disabled() {
+        let mut tracker = IdleTracker::new(None);
+
+        assert!(!tracker.park_if_idle(Instant::now() + PARK_TIMEOUT * 100));
+    }
+}
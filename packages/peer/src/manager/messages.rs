@@ -2,6 +2,8 @@ use futures::{Sink, Stream, TryStream};
 use thiserror::Error;
 
 use crate::manager::peer_info::PeerInfo;
+use crate::manager::validation::{MessageKind, ProtocolViolation};
+use crate::slots::ConnectionSlot;
 
 /// Trait for providing `PeerManager` with necessary message information.
 ///
@@ -13,6 +15,38 @@ pub trait ManagedMessage: std::fmt::Debug {
 
     /// Checks whether this message is a keep-alive message.
     fn is_keep_alive(&self) -> bool;
+
+    /// Retrieves the connection state this message transitions to, if any.
+    ///
+    /// Used to emit tracing events for changes such as choking or interest.
+    fn state_transition(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Retrieves the classification of this message.
+    ///
+    /// Used to detect protocol violations when a `ViolationPolicy` other than `Ignore` is configured.
+    fn kind(&self) -> MessageKind {
+        MessageKind::Other
+    }
+
+    /// Retrieves the interest this message declares, `true` for interested and `false` for not interested.
+    ///
+    /// Used to only park idle connections that neither side is interested in.
+    fn interest(&self) -> Option<bool> {
+        None
+    }
+
+    /// Retrieves the messages sent to a peer right before it is gracefully shut down.
+    ///
+    /// Used to let the peer know that we are no longer choking or interested in it.
+    #[must_use]
+    fn farewell() -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
 }
 
 //----------------------------------------------------------------------------//
@@ -35,12 +69,59 @@ where
 {
     /// Adds a peer to the peer manager.
     AddPeer(PeerInfo, Peer),
-    /// Removes a peer from the peer manager.
-    RemovePeer(PeerInfo),
+    /// Adds a peer to the peer manager, holding the given `ConnectionSlot` until the peer is removed.
+    AddSlottedPeer(PeerInfo, Peer, ConnectionSlot),
+    /// Removes a peer from the peer manager, for the given reason.
+    ///
+    /// The reason is reported back in the `PeerRemoved` message, so an application dropping a peer for
+    /// being banned or pruned, for example, sees the same reason as whoever else handles the event.
+    RemovePeer(PeerInfo, DisconnectReason),
+    /// Gracefully shuts down a peer, removing it from the peer manager.
+    ///
+    /// Messages queued for the peer before this one are sent first, followed by the
+    /// `ManagedMessage::farewell` messages and then the given messages (for example an
+    /// `lt_donthave`), before the connection is closed.
+    ShutdownPeer(PeerInfo, Vec<Message>),
     /// Sends a message to a peer.
     SendMessage(PeerInfo, MessageId, Message), // TODO: Support querying for statistics
 }
 
+/// Reason that a peer was dropped, reported when it is removed from the `PeerManager`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Peer was removed without a more specific reason.
+    Removed,
+    /// Peer was gracefully shut down.
+    Shutdown,
+    /// Peer closed the connection.
+    Closed,
+    /// Connection to the peer failed with an error of the given kind.
+    Io(std::io::ErrorKind),
+    /// Peer violated the protocol, under `ViolationPolicy::Disconnect`.
+    ProtocolViolation(ProtocolViolation),
+    /// Peer did not respond in time, for example to our requests.
+    Timeout,
+    /// Peer was banned, for example for sending us bad data.
+    Banned,
+    /// Connection was pruned to make room for a more useful one.
+    Pruned,
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::Removed => write!(f, "Peer Removed"),
+            DisconnectReason::Shutdown => write!(f, "Peer Shut Down"),
+            DisconnectReason::Closed => write!(f, "Peer Closed The Connection"),
+            DisconnectReason::Io(kind) => write!(f, "Connection Error: {kind}"),
+            DisconnectReason::ProtocolViolation(violation) => write!(f, "Protocol Violation: {violation:?}"),
+            DisconnectReason::Timeout => write!(f, "Peer Timed Out"),
+            DisconnectReason::Banned => write!(f, "Peer Banned"),
+            DisconnectReason::Pruned => write!(f, "Peer Pruned"),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum PeerManagerOutputError {
     #[error("Peer Disconnected, but Missing")]
@@ -61,14 +142,25 @@ pub enum PeerManagerOutputError {
 pub enum PeerManagerOutputMessage<Message> {
     /// Indicates a peer has been added to the peer manager.
     PeerAdded(PeerInfo),
-    /// Indicates a peer has been removed from the peer manager.
-    PeerRemoved(PeerInfo),
+    /// Indicates a peer has been removed from the peer manager, and why.
+    PeerRemoved(PeerInfo, DisconnectReason),
     /// Indicates a message has been sent to the given peer.
     SentMessage(PeerInfo, MessageId),
     /// Indicates a message has been received from a peer.
     ReceivedMessage(PeerInfo, Message),
-    /// Indicates a peer has disconnected.
+    /// Indicates a peer has disconnected, and why.
     ///
     /// Same semantics as `PeerRemoved`, but the peer is not returned.
-    PeerDisconnect(PeerInfo),
+    PeerDisconnect(PeerInfo, DisconnectReason),
+    /// Indicates an idle peer was parked, as neither side was interested in the other for a while.
+    ///
+    /// Keep-alive messages of parked peers are sent together with those of the other parked peers,
+    /// until the peer is revived.
+    PeerParked(PeerInfo),
+    /// Indicates a parked peer was revived, as a message other than a keep-alive was sent or received.
+    PeerRevived(PeerInfo),
+    /// Indicates a peer violated the protocol.
+    ///
+    /// If the `ViolationPolicy` is `Disconnect`, this is followed by the peer being removed.
+    ProtocolViolation(PeerInfo, ProtocolViolation),
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 
@@ -11,7 +12,9 @@ use futures::{SinkExt as _, StreamExt, TryStream};
 use sink::PeerManagerSink;
 
 use super::ManagedMessage;
-use crate::{PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage, PeerManagerStream};
+use crate::{
+    PeerInfo, PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage, PeerManagerStream,
+};
 
 pub mod builder;
 pub mod error;
@@ -19,8 +22,10 @@ pub mod messages;
 pub mod peer_info;
 pub mod sink;
 pub mod stream;
+pub mod validation;
 
 mod fused;
+mod idle;
 mod task;
 
 /// Manages a set of peers with beating hearts.
@@ -75,6 +80,33 @@ where
     pub fn into_parts(self) -> (PeerManagerSink<Peer, Message>, PeerManagerStream<Peer, Message>) {
         (self.sink, self.stream)
     }
+
+    /// Gracefully shut down the peer with the given `PeerInfo`.
+    ///
+    /// See `PeerManagerSink::shutdown`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the peer was not found, or if the shut down could
+    /// not be sent to the peer.
+    pub fn shutdown(
+        &self,
+        info: PeerInfo,
+        messages: Vec<Message>,
+    ) -> impl Future<Output = Result<(), PeerManagerError<SendError>>> {
+        self.sink.shutdown(info, messages)
+    }
+
+    /// Gracefully shut down all peers.
+    ///
+    /// See `PeerManagerSink::shutdown_all`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error with the first error returned when shutting down a peer.
+    pub fn shutdown_all(&self) -> impl Future<Output = Result<(), PeerManagerError<SendError>>> {
+        self.sink.shutdown_all()
+    }
 }
 
 impl<Peer, Message> Sink<std::io::Result<PeerManagerInputMessage<Peer, Message>>> for PeerManager<Peer, Message>
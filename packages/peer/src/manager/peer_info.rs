@@ -52,6 +52,20 @@ impl PeerInfo {
     pub fn extensions(&self) -> &Extensions {
         &self.ext
     }
+
+    /// Create a tracing span identifying this peer.
+    ///
+    /// Uses the same `peer_addr`, `peer_id` and `info_hash` fields as the handshaker, so
+    /// that logs for a single connection can be filtered on from handshake to disconnect.
+    #[must_use]
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "peer",
+            peer_addr = %self.addr,
+            peer_id = %self.pid.short(),
+            info_hash = %self.hash.short()
+        )
+    }
 }
 
 impl PartialEq for PeerInfo {
@@ -1,20 +1,24 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 
 use crossbeam::queue::SegQueue;
 use futures::channel::mpsc::{self, SendError};
+use futures::channel::oneshot;
+use futures::future::{self, Either};
 use futures::sink::Sink;
 use futures::task::{Context, Poll};
 use futures::{SinkExt as _, Stream, TryStream};
 
-use super::messages::{PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage};
+use super::messages::{DisconnectReason, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage};
 use super::task::run_peer;
 use crate::manager::builder::PeerManagerBuilder;
 use crate::manager::error::PeerManagerError;
 use crate::manager::peer_info::PeerInfo;
 use crate::manager::ManagedMessage;
+use crate::slots::ConnectionSlot;
 
 /// Sink half of a `PeerManager`.
 #[allow(clippy::module_name_repetitions)]
@@ -34,6 +38,7 @@ where
     #[allow(clippy::type_complexity)]
     peers: Arc<Mutex<HashMap<PeerInfo, mpsc::Sender<PeerManagerInputMessage<Peer, Message>>>>>,
     task_queue: Arc<SegQueue<tokio::task::JoinHandle<()>>>,
+    shutdown_waiters: Arc<Mutex<HashMap<PeerInfo, Vec<oneshot::Sender<()>>>>>,
 }
 
 impl<Peer, Message> Clone for PeerManagerSink<Peer, Message>
@@ -53,6 +58,7 @@ where
             sender: self.sender.clone(),
             peers: self.peers.clone(),
             task_queue: self.task_queue.clone(),
+            shutdown_waiters: self.shutdown_waiters.clone(),
         }
     }
 }
@@ -80,8 +86,90 @@ where
             sender,
             peers,
             task_queue,
+            shutdown_waiters: Arc::default(),
         }
     }
+
+    /// Gracefully shut down the peer with the given `PeerInfo`.
+    ///
+    /// Messages already queued for the peer are sent first, followed by the
+    /// `ManagedMessage::farewell` messages and then the given messages, before the
+    /// connection is closed. The returned future completes once the peer has been
+    /// closed and removed; the `PeerManagerStream` must be polled for this to happen.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the peer was not found, or if the shut down could
+    /// not be sent to the peer.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    pub fn shutdown(
+        &self,
+        info: PeerInfo,
+        messages: Vec<Message>,
+    ) -> impl Future<Output = Result<(), PeerManagerError<SendError>>> {
+        let sender = if let Ok(guard) = self.peers.try_lock() {
+            guard.get(&info).cloned().ok_or(PeerManagerError::PeerNotFound(info))
+        } else {
+            tracing::debug!("failed to get peers lock");
+            Err(PeerManagerError::LockFailed)
+        };
+
+        let (waiter, closed) = oneshot::channel();
+        if sender.is_ok() {
+            self.shutdown_waiters.lock().unwrap().entry(info).or_default().push(waiter);
+        }
+
+        let shutdown_waiters = self.shutdown_waiters.clone();
+        async move {
+            tracing::trace!("shutting down peer, with info: {info:?}");
+
+            let mut sender = sender?;
+            if let Err(e) = sender.send(PeerManagerInputMessage::ShutdownPeer(info, messages)).await {
+                shutdown_waiters.lock().unwrap().remove(&info);
+                return Err(PeerManagerError::SendFailed(e));
+            }
+
+            // The waiter is dropped without a signal only if the peer task was aborted
+            let _closed = closed.await;
+
+            Ok(())
+        }
+    }
+
+    /// Gracefully shut down all peers, as with `shutdown` without any extra messages.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the peers could not be locked, or with the first
+    /// error returned when shutting down a peer.
+    pub fn shutdown_all(&self) -> impl Future<Output = Result<(), PeerManagerError<SendError>>> {
+        let Ok(guard) = self.peers.try_lock() else {
+            tracing::debug!("failed to get peers lock");
+            return Either::Left(future::ready(Err(PeerManagerError::LockFailed)));
+        };
+        let infos = guard.keys().copied().collect::<Vec<_>>();
+        drop(guard);
+
+        let shutdowns = infos
+            .into_iter()
+            .map(|info| self.shutdown(info, Vec::new()))
+            .collect::<Vec<_>>();
+
+        Either::Right(async move {
+            for result in future::join_all(shutdowns).await {
+                match result {
+                    // Peer went away before we got to it
+                    Ok(()) | Err(PeerManagerError::PeerNotFound(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Ok(())
+        })
+    }
 }
 
 impl<Peer, Message> PeerManagerSink<Peer, Message>
@@ -108,13 +196,15 @@ where
         };
 
         match message {
-            PeerManagerInputMessage::AddPeer(info, peer) => self.add_peer(info, peer),
-            PeerManagerInputMessage::RemovePeer(info) => self.remove_peer(info),
+            PeerManagerInputMessage::AddPeer(info, peer) => self.add_peer(info, peer, None),
+            PeerManagerInputMessage::AddSlottedPeer(info, peer, slot) => self.add_peer(info, peer, Some(slot)),
+            PeerManagerInputMessage::RemovePeer(info, reason) => self.remove_peer(info, reason),
+            PeerManagerInputMessage::ShutdownPeer(info, messages) => self.shutdown_peer(info, messages),
             PeerManagerInputMessage::SendMessage(info, mid, peer_message) => self.send_message(info, mid, peer_message),
         }
     }
 
-    fn add_peer(&self, info: PeerInfo, peer: Peer) -> Result<(), PeerManagerError<SendError>> {
+    fn add_peer(&self, info: PeerInfo, peer: Peer, opt_slot: Option<ConnectionSlot>) -> Result<(), PeerManagerError<SendError>> {
         tracing::trace!("adding peer: {peer:?}, with info: {info:?}");
 
         let Ok(mut guard) = self.peers.try_lock() else {
@@ -136,7 +226,14 @@ where
                 return Err(PeerManagerError::PeerAlreadyExists(info));
             }
             Entry::Vacant(vac) => {
-                let (sender, task) = run_peer(peer, info, self.sender.clone(), &self.builder);
+                let (sender, task) = run_peer(
+                    peer,
+                    info,
+                    opt_slot,
+                    self.sender.clone(),
+                    self.shutdown_waiters.clone(),
+                    &self.builder,
+                );
                 vac.insert(sender);
                 self.task_queue.push(task); // Add the task to the task queue
             }
@@ -145,7 +242,7 @@ where
         Ok(())
     }
 
-    fn remove_peer(&self, info: PeerInfo) -> Result<(), PeerManagerError<SendError>> {
+    fn remove_peer(&self, info: PeerInfo, reason: DisconnectReason) -> Result<(), PeerManagerError<SendError>> {
         tracing::trace!("removing peer, with info: {info:?}");
 
         let Ok(mut guard) = self.peers.try_lock() else {
@@ -156,7 +253,24 @@ where
         let peer_sender = guard.get_mut(&info).ok_or(PeerManagerError::PeerNotFound(info))?;
 
         peer_sender
-            .start_send(PeerManagerInputMessage::RemovePeer(info))
+            .start_send(PeerManagerInputMessage::RemovePeer(info, reason))
+            .map_err(PeerManagerError::SendFailed)?;
+
+        Ok(())
+    }
+
+    fn shutdown_peer(&self, info: PeerInfo, messages: Vec<Message>) -> Result<(), PeerManagerError<SendError>> {
+        tracing::trace!("shutting down peer, with info: {info:?}");
+
+        let Ok(mut guard) = self.peers.try_lock() else {
+            tracing::debug!("failed to get peers lock");
+            return Err(PeerManagerError::LockFailed);
+        };
+
+        let peer_sender = guard.get_mut(&info).ok_or(PeerManagerError::PeerNotFound(info))?;
+
+        peer_sender
+            .start_send(PeerManagerInputMessage::ShutdownPeer(info, messages))
             .map_err(PeerManagerError::SendFailed)?;
 
         Ok(())
@@ -7,7 +7,9 @@ use futures::stream::Stream;
 use futures::{Sink, StreamExt, TryStream};
 use pin_project::pin_project;
 
-use super::messages::{ManagedMessage, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage};
+use super::messages::{
+    DisconnectReason, ManagedMessage, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage,
+};
 use crate::manager::peer_info::PeerInfo;
 
 /// Stream half of a `PeerManager`.
@@ -79,7 +81,9 @@ where
 
         let ready = match next_message {
             Err(err) => match err {
-                PeerManagerOutputError::PeerError(info, _) => {
+                PeerManagerOutputError::PeerError(info, ref e) => {
+                    let reason = DisconnectReason::Io(e.kind());
+
                     let Ok(mut peers) = self.peers.try_lock() else {
                         cx.waker().wake_by_ref();
                         return Poll::Pending;
@@ -88,7 +92,7 @@ where
                     match peers.remove(&info) {
                         Some(peer) => {
                             drop(peer);
-                            Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info))))
+                            Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info, reason))))
                         }
                         None => Poll::Ready(Some(Err(PeerManagerOutputError::PeerErrorAndMissing(
                             info,
@@ -100,7 +104,7 @@ where
                 | PeerManagerOutputError::PeerDisconnectedAndMissing(_)
                 | PeerManagerOutputError::PeerRemovedAndMissing(_) => Poll::Ready(Some(Err(err))),
             },
-            Ok(PeerManagerOutputMessage::PeerRemoved(info)) => {
+            Ok(PeerManagerOutputMessage::PeerRemoved(info, reason)) => {
                 let Ok(mut peers) = self.peers.try_lock() else {
                     cx.waker().wake_by_ref();
                     return Poll::Pending;
@@ -109,13 +113,13 @@ where
                 match peers.remove(&info) {
                     Some(peer) => {
                         drop(peer);
-                        Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info))))
+                        Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info, reason))))
                     }
                     None => Poll::Ready(Some(Err(PeerManagerOutputError::PeerRemovedAndMissing(info)))),
                 }
             }
 
-            Ok(PeerManagerOutputMessage::PeerDisconnect(info)) => {
+            Ok(PeerManagerOutputMessage::PeerDisconnect(info, reason)) => {
                 let Ok(mut peers) = self.peers.try_lock() else {
                     cx.waker().wake_by_ref();
                     return Poll::Pending;
@@ -124,7 +128,7 @@ where
                 match peers.remove(&info) {
                     Some(peer) => {
                         drop(peer);
-                        Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info))))
+                        Poll::Ready(Some(Ok(PeerManagerOutputMessage::PeerRemoved(info, reason))))
                     }
                     None => Poll::Ready(Some(Err(PeerManagerOutputError::PeerDisconnectedAndMissing(info)))),
                 }
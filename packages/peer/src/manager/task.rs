@@ -1,14 +1,23 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
 use futures::channel::mpsc::{self, SendError};
+use futures::channel::oneshot;
 use futures::stream::SplitSink;
-use futures::{Sink, SinkExt, Stream, StreamExt, TryStream, TryStreamExt};
+use futures::{FutureExt as _, Sink, SinkExt, Stream, StreamExt, TryStream, TryStreamExt};
 use thiserror::Error;
 use tokio::task::{self, JoinHandle};
+use tokio::time::Instant;
+use tracing::Instrument as _;
 
 use super::fused::{PersistentError, PersistentStream, RecurringTimeoutError, RecurringTimeoutStream};
-use super::messages::{PeerManagerInputMessage, PeerManagerOutputMessage};
+use super::idle::IdleTracker;
+use super::messages::{DisconnectReason, PeerManagerInputMessage, PeerManagerOutputMessage};
 use crate::manager::builder::PeerManagerBuilder;
 use crate::manager::peer_info::PeerInfo;
+use crate::manager::validation::{ProtocolValidator, ProtocolViolation, ViolationPolicy};
 use crate::manager::ManagedMessage;
+use crate::slots::ConnectionSlot;
 use crate::PeerManagerOutputError;
 
 #[derive(Error, Debug)]
@@ -21,6 +30,10 @@ enum PeerError<PeerSendErr, ManagerSendErr> {
     PeerDisconnect(PeerSendErr),
     #[error("Peer Removed")]
     PeerRemoved(PeerInfo),
+    #[error("Peer Shut Down")]
+    PeerShutdown(PeerInfo),
+    #[error("Protocol Violation: {0:?}")]
+    ProtocolViolation(ProtocolViolation),
 }
 
 enum UnifiedError<Err> {
@@ -30,6 +43,7 @@ enum UnifiedError<Err> {
 
 enum MergedError<Err> {
     Disconnect,
+    PeerClosed,
     StreamError(Err),
     Timeout,
 }
@@ -37,9 +51,8 @@ enum MergedError<Err> {
 impl<Err> From<UnifiedError<Err>> for MergedError<Err> {
     fn from(err: UnifiedError<Err>) -> Self {
         match err {
-            UnifiedError::Peer(PersistentError::Disconnect) | UnifiedError::Manager(RecurringTimeoutError::Disconnect) => {
-                Self::Disconnect
-            }
+            UnifiedError::Peer(PersistentError::Disconnect) => Self::PeerClosed,
+            UnifiedError::Manager(RecurringTimeoutError::Disconnect) => Self::Disconnect,
             UnifiedError::Peer(PersistentError::StreamError(err))
             | UnifiedError::Manager(RecurringTimeoutError::StreamError(err)) => Self::StreamError(err),
             UnifiedError::Manager(RecurringTimeoutError::Timeout) => Self::Timeout,
@@ -47,6 +60,12 @@ impl<Err> From<UnifiedError<Err>> for MergedError<Err> {
     }
 }
 
+/// State of a single connection, tracked across the messages exchanged with the peer.
+struct ConnectionState {
+    validator: ProtocolValidator,
+    idle: IdleTracker,
+}
+
 enum UnifiedItem<Peer, Message>
 where
     Peer: Sink<std::io::Result<Message>>
@@ -65,7 +84,9 @@ where
 pub fn run_peer<Peer, Message>(
     peer: Peer,
     info: PeerInfo,
+    opt_slot: Option<ConnectionSlot>,
     mut send: mpsc::Sender<Result<PeerManagerOutputMessage<Message>, PeerManagerOutputError>>,
+    shutdown_waiters: Arc<Mutex<HashMap<PeerInfo, Vec<oneshot::Sender<()>>>>>,
     builder: &PeerManagerBuilder,
 ) -> (mpsc::Sender<PeerManagerInputMessage<Peer, Message>>, JoinHandle<()>)
 where
@@ -83,6 +104,12 @@ where
     let (mut peer_send, peer_recv) = peer.split();
 
     let heartbeat_interval = builder.heartbeat_interval();
+    let send_batch_size = builder.send_batch_size();
+    let policy = builder.violation_policy();
+    let mut state = ConnectionState {
+        validator: ProtocolValidator::new(builder.choke_request_window()),
+        idle: IdleTracker::new(builder.idle_park_timeout()),
+    };
 
     let peer_stream = Box::pin(
         PersistentStream::new(peer_recv)
@@ -92,26 +119,80 @@ where
 
     let manager_stream = Box::pin(
         RecurringTimeoutStream::new(manager_recv.map(Ok), heartbeat_interval)
+            .with_coalescing(state.idle.parked_flag())
             .map_err(UnifiedError::Manager)
             .map_ok(|i| UnifiedItem::Manager(i)),
     );
 
     let mut merged_stream = Box::pin(futures::stream::select(peer_stream, manager_stream).map_err(MergedError::from));
 
-    let task = task::spawn(async move {
-        if send.send(Ok(PeerManagerOutputMessage::PeerAdded(info))).await.is_err() {
-            return;
-        }
+    let task = task::spawn(
+        async move {
+            if send.send(Ok(PeerManagerOutputMessage::PeerAdded(info))).await.is_err() {
+                return;
+            }
+            tracing::debug!("peer added");
+
+            // Number of messages written to the peer since it was last flushed
+            let mut unflushed = 0;
 
-        while let Some(result) = merged_stream.as_mut().next().await {
-            if handle_stream_result::<Peer, Message>(result, &mut peer_send, &mut send, &info)
+            loop {
+                let result = if let Some(result) = merged_stream.as_mut().next().now_or_never() {
+                    result
+                } else {
+                    // Nothing else is queued to be sent along with the written messages
+                    if unflushed > 0 {
+                        if let Err(err) = peer_send.flush().await.map_err(PeerError::<_, SendError>::PeerDisconnect) {
+                            tracing::debug!("peer finished: {err}");
+                            break;
+                        }
+                        unflushed = 0;
+                    }
+
+                    merged_stream.as_mut().next().await
+                };
+
+                let Some(result) = result else {
+                    break;
+                };
+
+                if let Err(err) = handle_stream_result::<Peer, Message>(
+                    result,
+                    &mut peer_send,
+                    &mut send,
+                    &info,
+                    policy,
+                    &mut state,
+                    &mut unflushed,
+                )
                 .await
-                .is_err()
-            {
-                break;
+                {
+                    tracing::debug!("peer finished: {err}");
+                    break;
+                }
+
+                if unflushed >= send_batch_size {
+                    if let Err(err) = peer_send.flush().await.map_err(PeerError::<_, SendError>::PeerDisconnect) {
+                        tracing::debug!("peer finished: {err}");
+                        break;
+                    }
+                    unflushed = 0;
+                }
+            }
+
+            // Free up the slot for another connection before anyone waiting on the shut down is woken
+            drop(opt_slot);
+
+            // Waiters are also woken if the peer went away before it could be shut down
+            let waiters = shutdown_waiters.lock().unwrap().remove(&info).unwrap_or_default();
+            for waiter in waiters {
+                if waiter.send(()).is_err() {
+                    tracing::trace!("shutdown waiter was dropped");
+                }
             }
         }
-    });
+        .instrument(info.span()),
+    );
 
     (manager_send, task)
 }
@@ -121,6 +202,9 @@ async fn handle_stream_result<Peer, Message>(
     peer_send: &mut SplitSink<Peer, std::io::Result<Message>>,
     manager_send: &mut mpsc::Sender<Result<PeerManagerOutputMessage<Message>, PeerManagerOutputError>>,
     info: &PeerInfo,
+    policy: ViolationPolicy,
+    state: &mut ConnectionState,
+    unflushed: &mut usize,
 ) -> Result<(), PeerError<<Peer as Sink<std::io::Result<Message>>>::Error, SendError>>
 where
     Peer: Sink<std::io::Result<Message>>
@@ -134,6 +218,37 @@ where
 {
     match result {
         Ok(UnifiedItem::Peer(message)) => {
+            if let Some(state) = message.state_transition() {
+                tracing::debug!(state, direction = "inbound", "peer state changed");
+            }
+
+            if !message.is_keep_alive() && state.idle.received(message.interest()) {
+                report_idle_change(manager_send, *info, false).await?;
+            }
+
+            if policy != ViolationPolicy::Ignore && !message.is_keep_alive() {
+                if let Some(violation) = state.validator.received(message.kind()) {
+                    tracing::debug!(?violation, "protocol violation");
+
+                    manager_send
+                        .send(Ok(PeerManagerOutputMessage::ProtocolViolation(*info, violation)))
+                        .await
+                        .map_err(PeerError::ManagerDisconnect)?;
+
+                    if policy == ViolationPolicy::Disconnect {
+                        manager_send
+                            .send(Ok(PeerManagerOutputMessage::PeerDisconnect(
+                                *info,
+                                DisconnectReason::ProtocolViolation(violation),
+                            )))
+                            .await
+                            .map_err(PeerError::ManagerDisconnect)?;
+
+                        return Err(PeerError::ProtocolViolation(violation));
+                    }
+                }
+            }
+
             // Handle peer message
             manager_send
                 .send(Ok(PeerManagerOutputMessage::ReceivedMessage(*info, message)))
@@ -142,17 +257,43 @@ where
 
             Ok(())
         }
-        Ok(UnifiedItem::Manager(PeerManagerInputMessage::AddPeer(_, _))) => panic!("invalid message"),
-        Ok(UnifiedItem::Manager(PeerManagerInputMessage::RemovePeer(info))) => {
+        Ok(UnifiedItem::Manager(PeerManagerInputMessage::AddPeer(_, _) | PeerManagerInputMessage::AddSlottedPeer(_, _, _))) => {
+            panic!("invalid message")
+        }
+        Ok(UnifiedItem::Manager(PeerManagerInputMessage::RemovePeer(info, reason))) => {
             manager_send
-                .send(Ok(PeerManagerOutputMessage::PeerRemoved(info)))
+                .send(Ok(PeerManagerOutputMessage::PeerRemoved(info, reason)))
                 .await
                 .map_err(PeerError::ManagerDisconnect)?;
 
             Err(PeerError::PeerRemoved(info))
         }
+        Ok(UnifiedItem::Manager(PeerManagerInputMessage::ShutdownPeer(info, messages))) => {
+            for message in Message::farewell().into_iter().chain(messages) {
+                peer_send.feed(Ok(message)).await.map_err(PeerError::PeerDisconnect)?;
+            }
+            peer_send.close().await.map_err(PeerError::PeerDisconnect)?;
+
+            manager_send
+                .send(Ok(PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::Shutdown)))
+                .await
+                .map_err(PeerError::ManagerDisconnect)?;
+
+            Err(PeerError::PeerShutdown(info))
+        }
         Ok(UnifiedItem::Manager(PeerManagerInputMessage::SendMessage(info, id, message))) => {
-            peer_send.send(Ok(message)).await.map_err(PeerError::PeerDisconnect)?;
+            if let Some(state) = message.state_transition() {
+                tracing::debug!(state, direction = "outbound", "peer state changed");
+            }
+            state.validator.sent(message.kind());
+
+            if !message.is_keep_alive() && state.idle.sent(message.interest()) {
+                report_idle_change(manager_send, info, false).await?;
+            }
+
+            peer_send.feed(Ok(message)).await.map_err(PeerError::PeerDisconnect)?;
+            *unflushed += 1;
+
             manager_send
                 .send(Ok(PeerManagerOutputMessage::SentMessage(info, id)))
                 .await
@@ -161,6 +302,14 @@ where
             Ok(())
         }
         Err(MergedError::Disconnect) => Err(PeerError::Disconnected),
+        Err(MergedError::PeerClosed) => {
+            manager_send
+                .send(Ok(PeerManagerOutputMessage::PeerDisconnect(*info, DisconnectReason::Closed)))
+                .await
+                .map_err(PeerError::ManagerDisconnect)?;
+
+            Err(PeerError::Disconnected)
+        }
         Err(MergedError::StreamError(e)) => {
             // Handle stream error
             manager_send
@@ -175,8 +324,31 @@ where
                 .send(Ok(Message::keep_alive()))
                 .await
                 .map_err(PeerError::PeerDisconnect)?;
+            // Sending flushed any messages that were already written
+            *unflushed = 0;
+
+            if state.idle.park_if_idle(Instant::now()) {
+                report_idle_change(manager_send, *info, true).await?;
+            }
 
             Ok(())
         }
     }
 }
+
+/// Notify the manager that the peer was parked, or revived if not.
+async fn report_idle_change<Message, PeerSendErr>(
+    manager_send: &mut mpsc::Sender<Result<PeerManagerOutputMessage<Message>, PeerManagerOutputError>>,
+    info: PeerInfo,
+    parked: bool,
+) -> Result<(), PeerError<PeerSendErr, SendError>> {
+    let message = if parked {
+        tracing::debug!("peer parked");
+        PeerManagerOutputMessage::PeerParked(info)
+    } else {
+        tracing::debug!("peer revived");
+        PeerManagerOutputMessage::PeerRevived(info)
+    };
+
+    manager_send.send(Ok(message)).await.map_err(PeerError::ManagerDisconnect)
+}
@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Classification of a message, used to validate the order of messages exchanged with a peer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageKind {
+    /// Message that establishes the connection, and must only be sent once.
+    Handshake,
+    /// Message telling the receiver that its requests will not be answered.
+    Choke,
+    /// Message telling the receiver that its requests will now be answered.
+    UnChoke,
+    /// Message revealing all pieces the sender has.
+    BitField,
+    /// Message requesting a block.
+    Request {
+        piece_index: u32,
+        block_offset: u32,
+        block_length: usize,
+    },
+    /// Message containing a block.
+    Piece {
+        piece_index: u32,
+        block_offset: u32,
+        block_length: usize,
+    },
+    /// Any message that is not validated.
+    Other,
+}
+
+/// Violation of the peer wire protocol committed by a remote peer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolViolation {
+    /// Peer requested a block while we were choking them, after the allowed window.
+    RequestWhileChoked {
+        piece_index: u32,
+        block_offset: u32,
+        block_length: usize,
+    },
+    /// Peer sent a block that we never requested.
+    UnrequestedPiece {
+        piece_index: u32,
+        block_offset: u32,
+        block_length: usize,
+    },
+    /// Peer sent a bitfield that was not the first message of the connection.
+    BitFieldNotFirst,
+    /// Peer sent a second handshake.
+    DuplicateHandshake,
+}
+
+/// Action taken by the `PeerManager` when a peer violates the protocol.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ViolationPolicy {
+    /// Messages are not validated.
+    #[default]
+    Ignore,
+    /// Violations are reported, and the peer is kept.
+    Tolerate,
+    /// Violations are reported, and the peer is disconnected.
+    Disconnect,
+}
+
+#[derive(Copy, Clone)]
+enum ChokeState {
+    /// We have never unchoked the peer, so it has no reason to request anything.
+    NeverUnChoked,
+    /// We choked the peer at the given time.
+    Choked(Instant),
+    UnChoked,
+}
+
+/// Tracks the state of a single connection to detect protocol violations by the peer.
+pub struct ProtocolValidator {
+    choke_window: Duration,
+    choke_state: ChokeState,
+    received_any: bool,
+    received_handshake: bool,
+    requested: HashSet<(u32, u32, usize)>,
+}
+
+impl ProtocolValidator {
+    /// Create a new `ProtocolValidator`, tolerating requests for `choke_window` after we choke the peer.
+    pub fn new(choke_window: Duration) -> ProtocolValidator {
+        ProtocolValidator {
+            choke_window,
+            choke_state: ChokeState::NeverUnChoked,
+            received_any: false,
+            received_handshake: false,
+            requested: HashSet::new(),
+        }
+    }
+
+    /// Record a message that we sent to the peer.
+    pub fn sent(&mut self, kind: MessageKind) {
+        match kind {
+            MessageKind::Choke => {
+                if let ChokeState::UnChoked = self.choke_state {
+                    self.choke_state = ChokeState::Choked(Instant::now());
+                }
+            }
+            MessageKind::UnChoke => self.choke_state = ChokeState::UnChoked,
+            MessageKind::Request {
+                piece_index,
+                block_offset,
+                block_length,
+            } => {
+                self.requested.insert((piece_index, block_offset, block_length));
+            }
+            MessageKind::Handshake | MessageKind::BitField | MessageKind::Piece { .. } | MessageKind::Other => (),
+        }
+    }
+
+    /// Record a message that we received from the peer, returning the violation it commits, if any.
+    ///
+    /// Keep alive messages should not be recorded, as they may be sent at any time.
+    pub fn received(&mut self, kind: MessageKind) -> Option<ProtocolViolation> {
+        let first_message = !self.received_any;
+        self.received_any = true;
+
+        match kind {
+            MessageKind::Handshake if self.received_handshake => Some(ProtocolViolation::DuplicateHandshake),
+            MessageKind::Handshake => {
+                self.received_handshake = true;
+                None
+            }
+            MessageKind::BitField if !first_message => Some(ProtocolViolation::BitFieldNotFirst),
+            MessageKind::Request {
+                piece_index,
+                block_offset,
+                block_length,
+            } => match self.choke_state {
+                ChokeState::UnChoked => None,
+                ChokeState::Choked(since) if since.elapsed() <= self.choke_window => None,
+                ChokeState::Choked(_) | ChokeState::NeverUnChoked => Some(ProtocolViolation::RequestWhileChoked {
+                    piece_index,
+                    block_offset,
+                    block_length,
+                }),
+            },
+            MessageKind::Piece {
+                piece_index,
+                block_offset,
+                block_length,
+            } if !self.requested.remove(&(piece_index, block_offset, block_length)) => {
+                Some(ProtocolViolation::UnrequestedPiece {
+                    piece_index,
+                    block_offset,
+                    block_length,
+                })
+            }
+            MessageKind::Choke => {
+                // Outstanding requests are discarded by a peer when it chokes us
+                self.requested.clear();
+                None
+            }
+            MessageKind::UnChoke | MessageKind::BitField | MessageKind::Piece { .. } | MessageKind::Other => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{MessageKind, ProtocolValidator, ProtocolViolation};
+
+    const REQUEST: MessageKind = MessageKind::Request {
+        piece_index: 0,
+        block_offset: 0,
+        block_length: 16,
+    };
+    const PIECE: MessageKind = MessageKind::Piece {
+        piece_index: 0,
+        block_offset: 0,
+        block_length: 16,
+    };
+
+    #[test]
+    fn positive_request_after_unchoke() {
+        let mut validator = ProtocolValidator::new(Duration::ZERO);
+
+        validator.sent(MessageKind::UnChoke);
+
+        assert_eq!(validator.received(REQUEST), None);
+    }
+
+    #[test]
+    fn positive_request_within_choke_window() {
+        let mut validator = ProtocolValidator::new(Duration::from_secs(30));
+
+        validator.sent(MessageKind::UnChoke);
+        validator.sent(MessageKind::Choke);
+
+        assert_eq!(validator.received(REQUEST), None);
+    }
+
+    #[test]
+    fn negative_request_never_unchoked() {
+        let mut validator = ProtocolValidator::new(Duration::from_secs(30));
+
+        assert!(matches!(
+            validator.received(REQUEST),
+            Some(ProtocolViolation::RequestWhileChoked { .. })
+        ));
+    }
+
+    #[test]
+    fn positive_piece_requested_once() {
+        let mut validator = ProtocolValidator::new(Duration::ZERO);
+
+        validator.sent(REQUEST);
+
+        assert_eq!(validator.received(PIECE), None);
+        assert!(matches!(
+            validator.received(PIECE),
+            Some(ProtocolViolation::UnrequestedPiece { .. })
+        ));
+    }
+
+    #[test]
+    fn negative_piece_after_choke_discards_requests() {
+        let mut validator = ProtocolValidator::new(Duration::ZERO);
+
+        validator.sent(REQUEST);
+
+        assert_eq!(validator.received(MessageKind::Choke), None);
+        assert!(matches!(
+            validator.received(PIECE),
+            Some(ProtocolViolation::UnrequestedPiece { .. })
+        ));
+    }
+
+    #[test]
+    fn negative_bitfield_not_first() {
+        let mut validator = ProtocolValidator::new(Duration::ZERO);
+
+        assert_eq!(validator.received(MessageKind::BitField), None);
+        assert_eq!(
+            validator.received(MessageKind::BitField),
+            Some(ProtocolViolation::BitFieldNotFirst)
+        );
+    }
+
+    #[test]
+    fn negative_duplicate_handshake() {
+        let mut validator = ProtocolValidator::new(Duration::ZERO);
+
+        assert_eq!(validator.received(MessageKind::Handshake), None);
+        assert_eq!(
+            validator.received(MessageKind::Handshake),
+            Some(ProtocolViolation::DuplicateHandshake)
+        );
+    }
+}
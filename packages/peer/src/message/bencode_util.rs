@@ -6,6 +6,7 @@ use bencode::{BConvert, BDictAccess, BRefAccess, BencodeConvertError};
 use util::convert;
 
 use crate::message::bits_ext::ExtendedType;
+use crate::message::prot_ext::MetadataCompression;
 
 pub const CONVERT: IoErrorBencodeConvert = IoErrorBencodeConvert;
 
@@ -29,6 +30,7 @@ pub const CLIENT_IPV6_ADDR_KEY: &[u8] = b"ipv6";
 pub const CLIENT_IPV4_ADDR_KEY: &[u8] = b"ipv4";
 pub const CLIENT_MAX_REQUESTS_KEY: &[u8] = b"reqq";
 pub const METADATA_SIZE_KEY: &[u8] = b"metadata_size";
+pub const METADATA_COMPRESSION_KEY: &[u8] = b"metadata_compression";
 
 pub fn parse_id_map<K, V>(root: &dyn BDictAccess<K, V>) -> HashMap<ExtendedType, u8>
 where
@@ -133,6 +135,24 @@ where
     CONVERT.lookup_and_convert_int(root, METADATA_SIZE_KEY).ok()
 }
 
+pub fn parse_metadata_compression<K, V>(root: &dyn BDictAccess<K, V>) -> Vec<MetadataCompression>
+where
+    V: BRefAccess,
+{
+    let mut compression = Vec::new();
+
+    if let Ok(ben_list) = CONVERT.lookup_and_convert_list(root, METADATA_COMPRESSION_KEY) {
+        for index in 0..ben_list.len() {
+            // Unrecognized algorithms are skipped, they will never be negotiated
+            if let Some(id) = ben_list.get(index).and_then(BRefAccess::bytes) {
+                compression.extend(MetadataCompression::from_id(id));
+            }
+        }
+    }
+
+    compression
+}
+
 fn parse_ipv4_addr(ipv4_bytes: &[u8]) -> Ipv4Addr {
     convert::bytes_be_to_ipv4([ipv4_bytes[0], ipv4_bytes[1], ipv4_bytes[2], ipv4_bytes[3]])
 }
@@ -163,6 +183,7 @@ fn parse_ipv6_addr(ipv6_bytes: &[u8]) -> Ipv6Addr {
 pub const MESSAGE_TYPE_KEY: &[u8] = b"msg_type";
 pub const PIECE_INDEX_KEY: &[u8] = b"piece";
 pub const TOTAL_SIZE_KEY: &[u8] = b"total_size";
+pub const COMPRESSION_KEY: &[u8] = b"compression";
 
 pub fn parse_message_type<K, V>(root: &dyn BDictAccess<K, V>) -> std::io::Result<u8>
 where
@@ -186,3 +207,13 @@ where
 {
     CONVERT.lookup_and_convert_int(root, TOTAL_SIZE_KEY)
 }
+
+pub fn parse_compression<K, V>(root: &dyn BDictAccess<K, V>) -> Option<Vec<u8>>
+where
+    V: BRefAccess,
+{
+    CONVERT
+        .lookup_and_convert_bytes(root, COMPRESSION_KEY)
+        .ok()
+        .map(<[u8]>::to_vec)
+}
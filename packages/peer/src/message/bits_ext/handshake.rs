@@ -8,7 +8,7 @@ use nom::{IResult, Needed};
 use util::convert;
 
 use crate::message;
-use crate::message::{bencode_util, bits_ext};
+use crate::message::{bencode_util, bits_ext, MetadataCompression};
 
 /// Builder type for an `ExtendedMessage`.
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
@@ -21,6 +21,7 @@ pub struct ExtendedMessageBuilder {
     our_ipv4_addr: Option<Ipv4Addr>,
     our_max_requests: Option<i64>,
     metadata_size: Option<i64>,
+    metadata_compression: Vec<MetadataCompression>,
     custom_entries: HashMap<String, BencodeMut<'static>>,
 }
 
@@ -41,6 +42,7 @@ impl ExtendedMessageBuilder {
             our_ipv4_addr: None,
             our_max_requests: None,
             metadata_size: None,
+            metadata_compression: Vec::new(),
             custom_entries: HashMap::new(),
         }
     }
@@ -170,6 +172,21 @@ impl ExtendedMessageBuilder {
         self
     }
 
+    /// Sets the compression algorithms we support for metadata payloads, in order of preference.
+    ///
+    /// # Parameters
+    ///
+    /// - `compression`: The supported compression algorithms, empty if we do not support compression.
+    ///
+    /// # Returns
+    ///
+    /// The updated `ExtendedMessageBuilder`.
+    #[must_use]
+    pub fn with_metadata_compression(mut self, compression: Vec<MetadataCompression>) -> ExtendedMessageBuilder {
+        self.metadata_compression = compression;
+        self
+    }
+
     /// Sets a custom entry in the message with the given dictionary key.
     ///
     /// # Parameters
@@ -258,6 +275,18 @@ fn bencode_from_builder(builder: &ExtendedMessageBuilder, mut custom_entries: Ha
         builder
             .metadata_size
             .map(|metadata_size| root_map_access.insert(bencode_util::METADATA_SIZE_KEY.into(), ben_int!(metadata_size)));
+
+        if !builder.metadata_compression.is_empty() {
+            let mut ben_compression = BencodeMut::new_list();
+            {
+                let ben_compression_access = ben_compression.list_mut().unwrap();
+                for compression in &builder.metadata_compression {
+                    ben_compression_access.push(ben_bytes!(compression.id()));
+                }
+            }
+
+            root_map_access.insert(bencode_util::METADATA_COMPRESSION_KEY.into(), ben_compression);
+        }
     }
 
     root_map.encode()
@@ -273,12 +302,14 @@ const ROOT_ERROR_KEY: &str = "ExtendedMessage";
 
 const UT_METADATA_ID: &str = "ut_metadata";
 const UT_PEX_ID: &str = "ut_pex";
+const UT_HOLEPUNCH_ID: &str = "ut_holepunch";
 
 /// Enumeration of extended types activated via `ExtendedMessage`.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum ExtendedType {
     UtMetadata,
     UtPex,
+    UtHolepunch,
     Custom(String),
 }
 
@@ -297,6 +328,7 @@ impl ExtendedType {
         match id {
             UT_METADATA_ID => ExtendedType::UtMetadata,
             UT_PEX_ID => ExtendedType::UtPex,
+            UT_HOLEPUNCH_ID => ExtendedType::UtHolepunch,
             custom => ExtendedType::Custom(custom.to_string()),
         }
     }
@@ -311,6 +343,7 @@ impl ExtendedType {
         match self {
             ExtendedType::UtMetadata => UT_METADATA_ID,
             ExtendedType::UtPex => UT_PEX_ID,
+            ExtendedType::UtHolepunch => UT_HOLEPUNCH_ID,
             ExtendedType::Custom(id) => id,
         }
     }
@@ -329,6 +362,7 @@ pub struct ExtendedMessage {
     our_ipv4_addr: Option<Ipv4Addr>,
     our_max_requests: Option<i64>,
     metadata_size: Option<i64>,
+    metadata_compression: Box<[MetadataCompression]>,
     raw_bencode: Bytes,
 }
 
@@ -360,6 +394,7 @@ impl ExtendedMessage {
             our_ipv4_addr: builder.our_ipv4_addr,
             our_max_requests: builder.our_max_requests,
             metadata_size: builder.metadata_size,
+            metadata_compression: builder.metadata_compression.into(),
             raw_bencode: raw_bencode.freeze(),
         }
     }
@@ -397,6 +432,7 @@ impl ExtendedMessage {
                     let our_ipv4_addr = bencode_util::parse_client_ipv4_addr(ben_dict);
                     let our_max_requests = bencode_util::parse_client_max_requests(ben_dict);
                     let metadata_size = bencode_util::parse_metadata_size(ben_dict);
+                    let metadata_compression = bencode_util::parse_metadata_compression(ben_dict);
 
                     Ok(ExtendedMessage {
                         id_map,
@@ -407,6 +443,7 @@ impl ExtendedMessage {
                         our_ipv4_addr,
                         our_max_requests,
                         metadata_size,
+                        metadata_compression: metadata_compression.into(),
                         raw_bencode: Bytes::copy_from_slice(raw_bencode),
                     })
                 });
@@ -468,6 +505,15 @@ impl ExtendedMessage {
         self.id_map.get(ext_type).copied()
     }
 
+    /// Retrieves the mapping of every extended type to its id.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the map of extended types to ids.
+    pub fn id_map(&self) -> &HashMap<ExtendedType, u8> {
+        &self.id_map
+    }
+
     /// Retrieves our id from the message.
     ///
     /// # Returns
@@ -531,6 +577,15 @@ impl ExtendedMessage {
         self.metadata_size
     }
 
+    /// Retrieves the compression algorithms supported for metadata payloads, in order of preference.
+    ///
+    /// # Returns
+    ///
+    /// A slice of the supported algorithms, empty if compression is not supported.
+    pub fn metadata_compression(&self) -> &[MetadataCompression] {
+        &self.metadata_compression
+    }
+
     /// Retrieves a raw `BencodeRef` representing the current message.
     ///
     /// # Panics
@@ -20,7 +20,7 @@ use crate::message::bencode_util;
 const PORT_MESSAGE_LEN: u32 = 3;
 const BASE_EXTENDED_MESSAGE_LEN: u32 = 6;
 
-const PORT_MESSAGE_ID: u8 = 9;
+pub const PORT_MESSAGE_ID: u8 = 9;
 pub const EXTENDED_MESSAGE_ID: u8 = 20;
 
 const EXTENDED_MESSAGE_HANDSHAKE_ID: u8 = 0;
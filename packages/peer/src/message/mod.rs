@@ -51,17 +51,20 @@ mod null;
 mod prot_ext;
 mod standard;
 
+use crate::manager::validation::MessageKind;
 #[allow(clippy::module_name_repetitions)]
 pub use crate::message::bits_ext::{BitsExtensionMessage, ExtendedMessage, ExtendedMessageBuilder, ExtendedType, PortMessage};
 #[allow(clippy::module_name_repetitions)]
 pub use crate::message::null::NullProtocolMessage;
 #[allow(clippy::module_name_repetitions)]
 pub use crate::message::prot_ext::{
-    PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError, UtMetadataDataMessage, UtMetadataMessage,
-    UtMetadataRejectMessage, UtMetadataRequestMessage,
+    MetadataCompression, PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError, UtHolepunchErrorCode,
+    UtHolepunchMessage, UtHolepunchMessageError, UtMetadataDataMessage, UtMetadataMessage, UtMetadataRejectMessage,
+    UtMetadataRequestMessage,
 };
 #[allow(clippy::module_name_repetitions)]
 pub use crate::message::standard::{BitFieldIter, BitFieldMessage, CancelMessage, HaveMessage, PieceMessage, RequestMessage};
+use crate::stats::WireMessageType;
 use crate::ManagedMessage;
 
 #[derive(Error, Debug, Clone)]
@@ -128,6 +131,48 @@ where
     fn is_keep_alive(&self) -> bool {
         matches!(self, &PeerWireProtocolMessage::KeepAlive)
     }
+
+    fn farewell() -> Vec<PeerWireProtocolMessage<P>> {
+        vec![PeerWireProtocolMessage::Choke, PeerWireProtocolMessage::UnInterested]
+    }
+
+    fn state_transition(&self) -> Option<&'static str> {
+        match self {
+            PeerWireProtocolMessage::Choke => Some("choked"),
+            PeerWireProtocolMessage::UnChoke => Some("unchoked"),
+            PeerWireProtocolMessage::Interested => Some("interested"),
+            PeerWireProtocolMessage::UnInterested => Some("uninterested"),
+            _ => None,
+        }
+    }
+
+    fn interest(&self) -> Option<bool> {
+        match self {
+            PeerWireProtocolMessage::Interested => Some(true),
+            PeerWireProtocolMessage::UnInterested => Some(false),
+            _ => None,
+        }
+    }
+
+    // Extended handshakes are not reported, as BEP 10 allows them to be sent more than once
+    fn kind(&self) -> MessageKind {
+        match self {
+            PeerWireProtocolMessage::Choke => MessageKind::Choke,
+            PeerWireProtocolMessage::UnChoke => MessageKind::UnChoke,
+            PeerWireProtocolMessage::BitField(_) => MessageKind::BitField,
+            PeerWireProtocolMessage::Request(msg) => MessageKind::Request {
+                piece_index: msg.piece_index(),
+                block_offset: msg.block_offset(),
+                block_length: msg.block_length(),
+            },
+            PeerWireProtocolMessage::Piece(msg) => MessageKind::Piece {
+                piece_index: msg.piece_index(),
+                block_offset: msg.block_offset(),
+                block_length: msg.block_length(),
+            },
+            _ => MessageKind::Other,
+        }
+    }
 }
 
 impl<P> PeerWireProtocolMessage<P>
@@ -217,6 +262,29 @@ where
     }
 }
 
+/// Classify a whole message frame, returning its type along with the number of bytes of piece data it carries.
+pub(crate) fn frame_message_type(frame: &[u8]) -> (WireMessageType, usize) {
+    let piece_header_len = MESSAGE_LENGTH_LEN_BYTES + u32_to_usize(BASE_PIECE_MESSAGE_LEN);
+
+    let message_type = match (frame.len(), frame.get(MESSAGE_LENGTH_LEN_BYTES)) {
+        (MESSAGE_LENGTH_LEN_BYTES, None) => WireMessageType::KeepAlive,
+        (_, Some(&CHOKE_MESSAGE_ID)) => WireMessageType::Choke,
+        (_, Some(&UNCHOKE_MESSAGE_ID)) => WireMessageType::UnChoke,
+        (_, Some(&INTERESTED_MESSAGE_ID)) => WireMessageType::Interested,
+        (_, Some(&UNINTERESTED_MESSAGE_ID)) => WireMessageType::UnInterested,
+        (_, Some(&HAVE_MESSAGE_ID)) => WireMessageType::Have,
+        (_, Some(&BITFIELD_MESSAGE_ID)) => WireMessageType::BitField,
+        (_, Some(&REQUEST_MESSAGE_ID)) => WireMessageType::Request,
+        (len, Some(&PIECE_MESSAGE_ID)) if len >= piece_header_len => return (WireMessageType::Piece, len - piece_header_len),
+        (_, Some(&CANCEL_MESSAGE_ID)) => WireMessageType::Cancel,
+        (_, Some(&bits_ext::PORT_MESSAGE_ID)) => WireMessageType::Port,
+        (_, Some(&bits_ext::EXTENDED_MESSAGE_ID)) => WireMessageType::Extended,
+        _ => WireMessageType::Other,
+    };
+
+    (message_type, 0)
+}
+
 /// Write a length and optional id out to the given writer.
 fn write_length_id_pair<W>(mut writer: W, length: u32, opt_id: Option<u8>) -> std::io::Result<usize>
 where
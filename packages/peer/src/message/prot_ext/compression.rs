@@ -0,0 +1,156 @@
+use std::io::{Read as _, Write as _};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::message::bits_ext::ExtendedMessage;
+
+const GZIP_ID: &str = "gzip";
+const ZSTD_ID: &str = "zstd";
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Maximum size of a single decompressed metadata piece.
+///
+/// Matches the 16 KiB piece size mandated by `BEP 9`, anything larger is treated as an error so
+/// that a malicious peer can not use a small payload to make us allocate a large amount of memory.
+pub const MAX_DECOMPRESSED_PIECE_LEN: usize = 16 * 1024;
+
+/// Compression algorithm applied to `ut_metadata` data payloads.
+///
+/// Support is advertised in the extended handshake, and only used when both peers support it.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum MetadataCompression {
+    Gzip,
+    Zstd,
+}
+
+impl MetadataCompression {
+    /// Creates a `MetadataCompression` from the given identifier, if recognized.
+    #[must_use]
+    pub fn from_id(id: &[u8]) -> Option<MetadataCompression> {
+        match id {
+            id if id == GZIP_ID.as_bytes() => Some(MetadataCompression::Gzip),
+            id if id == ZSTD_ID.as_bytes() => Some(MetadataCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Retrieves the identifier used on the wire for the given `MetadataCompression`.
+    #[must_use]
+    pub fn id(&self) -> &'static str {
+        match self {
+            MetadataCompression::Gzip => GZIP_ID,
+            MetadataCompression::Zstd => ZSTD_ID,
+        }
+    }
+
+    /// Picks the first of our supported algorithms that the peer also supports.
+    ///
+    /// Returns `None` if either side does not support compression, in which case metadata should be sent uncompressed.
+    #[must_use]
+    pub fn negotiate(ours: &ExtendedMessage, theirs: &ExtendedMessage) -> Option<MetadataCompression> {
+        ours.metadata_compression()
+            .iter()
+            .find(|compression| theirs.metadata_compression().contains(compression))
+            .copied()
+    }
+
+    /// Compress the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the compressor fails.
+    pub fn compress(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            MetadataCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+
+                encoder.finish()
+            }
+            MetadataCompression::Zstd => zstd::encode_all(bytes, ZSTD_LEVEL),
+        }
+    }
+
+    /// Decompress the given bytes, failing if the output would exceed `MAX_DECOMPRESSED_PIECE_LEN`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes are not valid or decompress to more than one piece.
+    pub fn decompress(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let limit = (MAX_DECOMPRESSED_PIECE_LEN + 1) as u64;
+        let mut decompressed = Vec::new();
+
+        match self {
+            MetadataCompression::Gzip => GzDecoder::new(bytes).take(limit).read_to_end(&mut decompressed)?,
+            MetadataCompression::Zstd => zstd::Decoder::new(bytes)?.take(limit).read_to_end(&mut decompressed)?,
+        };
+
+        if decompressed.len() > MAX_DECOMPRESSED_PIECE_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Decompressed Metadata Piece Exceeds Maximum Length",
+            ));
+        }
+
+        Ok(decompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetadataCompression, MAX_DECOMPRESSED_PIECE_LEN};
+    use crate::message::bits_ext::ExtendedMessageBuilder;
+
+    #[test]
+    fn positive_round_trip() {
+        let data: Vec<u8> = (0..MAX_DECOMPRESSED_PIECE_LEN)
+            .map(|index| u8::try_from(index % 7).unwrap())
+            .collect();
+
+        for compression in [MetadataCompression::Gzip, MetadataCompression::Zstd] {
+            let compressed = compression.compress(&data).unwrap();
+
+            assert!(compressed.len() < data.len());
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn positive_negotiate_prefers_our_order() {
+        let ours = ExtendedMessageBuilder::new()
+            .with_metadata_compression(vec![MetadataCompression::Zstd, MetadataCompression::Gzip])
+            .build();
+        let theirs = ExtendedMessageBuilder::new()
+            .with_metadata_compression(vec![MetadataCompression::Gzip, MetadataCompression::Zstd])
+            .build();
+
+        assert_eq!(
+            MetadataCompression::negotiate(&ours, &theirs),
+            Some(MetadataCompression::Zstd)
+        );
+    }
+
+    #[test]
+    fn negative_negotiate_peer_without_support() {
+        let ours = ExtendedMessageBuilder::new()
+            .with_metadata_compression(vec![MetadataCompression::Zstd])
+            .build();
+        let theirs = ExtendedMessageBuilder::new().build();
+
+        assert_eq!(MetadataCompression::negotiate(&ours, &theirs), None);
+    }
+
+    #[test]
+    fn negative_decompress_exceeds_piece_len() {
+        let data = vec![0u8; MAX_DECOMPRESSED_PIECE_LEN + 1];
+
+        for compression in [MetadataCompression::Gzip, MetadataCompression::Zstd] {
+            let compressed = compression.compress(&data).unwrap();
+
+            assert!(compression.decompress(&compressed).is_err());
+        }
+    }
+}
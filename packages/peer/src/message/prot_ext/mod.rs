@@ -22,8 +22,12 @@ use crate::protocol::PeerProtocol;
 
 const EXTENSION_HEADER_LEN: usize = message::HEADER_LEN + 1;
 
+mod compression;
+mod ut_holepunch;
 mod ut_metadata;
 
+pub use self::compression::MetadataCompression;
+pub use self::ut_holepunch::{UtHolepunchErrorCode, UtHolepunchMessage, UtHolepunchMessageError};
 pub use self::ut_metadata::{UtMetadataDataMessage, UtMetadataMessage, UtMetadataRejectMessage, UtMetadataRequestMessage};
 
 #[derive(Debug, Clone)]
@@ -49,6 +53,9 @@ pub enum PeerExtensionProtocolMessageError {
     #[error("Error from UtMetadata")]
     UtMetadataError(UtMetadataMessageError),
 
+    #[error("Error from UtHolepunch")]
+    UtHolepunchError(UtHolepunchMessageError),
+
     #[error("Error from UtMetadata")]
     UnknownId(),
 
@@ -65,6 +72,12 @@ impl From<UtMetadataMessageError> for PeerExtensionProtocolMessageError {
     }
 }
 
+impl From<UtHolepunchMessageError> for PeerExtensionProtocolMessageError {
+    fn from(value: UtHolepunchMessageError) -> Self {
+        Self::UtHolepunchError(value)
+    }
+}
+
 /// Enumeration of `BEP 10` extension protocol compatible messages.
 #[derive(Debug)]
 pub enum PeerExtensionProtocolMessage<P>
@@ -72,6 +85,7 @@ where
     P: PeerProtocol,
 {
     UtMetadata(UtMetadataMessage),
+    UtHolepunch(UtHolepunchMessage),
     //UtPex(UtPexMessage),
     Custom(Result<P::ProtocolMessage, P::ProtocolMessageError>),
 }
@@ -122,6 +136,7 @@ where
     /// # Panics
     ///
     /// This function will panic if the message is too long.
+    #[allow(clippy::io_other_error)] // `io::Error::other` is newer than our minimum supported rust version
     pub fn write_bytes<W>(&self, mut writer: W, extended: &ExtendedMessage, custom_prot: &mut P) -> std::io::Result<usize>
     where
         W: std::io::Write,
@@ -148,6 +163,27 @@ where
 
                 Ok(id_length + total_len)
             }
+            PeerExtensionProtocolMessage::UtHolepunch(msg) => {
+                let Some(ext_id) = extended.query_id(&ExtendedType::UtHolepunch) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        "Can't Send UtHolepunchMessage As We Have No Id Mapping",
+                    ));
+                };
+
+                let total_len = 2 + msg.message_size();
+
+                let id_length = message::write_length_id_pair(
+                    &mut writer,
+                    total_len.try_into().unwrap(),
+                    Some(bits_ext::EXTENDED_MESSAGE_ID),
+                )?;
+                writer.write_u8(ext_id)?;
+
+                let () = msg.write_bytes(writer)?;
+
+                Ok(id_length + total_len)
+            }
             PeerExtensionProtocolMessage::Custom(msg) => custom_prot.write_bytes(msg, writer),
         }
     }
@@ -160,6 +196,7 @@ where
     pub fn message_size(&self, custom_prot: &mut P) -> std::io::Result<usize> {
         match self {
             PeerExtensionProtocolMessage::UtMetadata(msg) => Ok(msg.message_size()),
+            PeerExtensionProtocolMessage::UtHolepunch(msg) => Ok(msg.message_size()),
             PeerExtensionProtocolMessage::Custom(msg) => custom_prot.message_size(msg),
         }
     }
@@ -220,6 +257,14 @@ fn parse_extensions_with_id<P>(
 where
     P: PeerProtocol,
 {
+    if extended.query_id(&ExtendedType::UtHolepunch) == Some(id) {
+        let item = UtHolepunchMessage::parse_bytes(&bytes)
+            .map(PeerExtensionProtocolMessage::UtHolepunch)
+            .map_err(PeerExtensionProtocolMessageError::UtHolepunchError);
+
+        return Ok(item);
+    }
+
     let Some(lt_metadata_id) = extended.query_id(&ExtendedType::UtMetadata) else {
         return Ok(Err(PeerExtensionProtocolMessageError::UnknownId()));
     };
@@ -0,0 +1,221 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+const RENDEZVOUS_MESSAGE_TYPE_ID: u8 = 0;
+const CONNECT_MESSAGE_TYPE_ID: u8 = 1;
+const ERROR_MESSAGE_TYPE_ID: u8 = 2;
+
+const IPV4_ADDR_TYPE_ID: u8 = 0;
+const IPV6_ADDR_TYPE_ID: u8 = 1;
+
+const NO_SUCH_PEER_ERROR_CODE: u32 = 1;
+const NOT_CONNECTED_ERROR_CODE: u32 = 2;
+const NO_SUPPORT_ERROR_CODE: u32 = 3;
+const NO_SELF_ERROR_CODE: u32 = 4;
+
+/// Length of the message type, address type, port and error code fields.
+const FIXED_FIELDS_LEN: usize = 1 + 1 + 2 + 4;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug, Clone)]
+pub enum UtHolepunchMessageError {
+    #[error("Failed to match message type: {0}")]
+    UnknownMessageType(u8),
+
+    #[error("Failed to match address type: {0}")]
+    UnknownAddressType(u8),
+
+    #[error("Failed to match error code: {0}")]
+    UnknownErrorCode(u32),
+
+    #[error("Message is truncated")]
+    Truncated,
+}
+
+/// Reason that a relaying peer could not forward a `UtHolepunchMessage::Rendezvous`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum UtHolepunchErrorCode {
+    /// Target endpoint is invalid.
+    NoSuchPeer,
+    /// Relaying peer is not connected to the target peer.
+    NotConnected,
+    /// Target peer does not support the holepunch extension.
+    NoSupport,
+    /// Target endpoint belongs to the peer that sent the rendezvous.
+    NoSelf,
+}
+
+impl UtHolepunchErrorCode {
+    fn from_code(code: u32) -> Result<UtHolepunchErrorCode, UtHolepunchMessageError> {
+        match code {
+            NO_SUCH_PEER_ERROR_CODE => Ok(UtHolepunchErrorCode::NoSuchPeer),
+            NOT_CONNECTED_ERROR_CODE => Ok(UtHolepunchErrorCode::NotConnected),
+            NO_SUPPORT_ERROR_CODE => Ok(UtHolepunchErrorCode::NoSupport),
+            NO_SELF_ERROR_CODE => Ok(UtHolepunchErrorCode::NoSelf),
+            other => Err(UtHolepunchMessageError::UnknownErrorCode(other)),
+        }
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            UtHolepunchErrorCode::NoSuchPeer => NO_SUCH_PEER_ERROR_CODE,
+            UtHolepunchErrorCode::NotConnected => NOT_CONNECTED_ERROR_CODE,
+            UtHolepunchErrorCode::NoSupport => NO_SUPPORT_ERROR_CODE,
+            UtHolepunchErrorCode::NoSelf => NO_SELF_ERROR_CODE,
+        }
+    }
+}
+
+/// Enumeration of messages for `PeerExtensionProtocolMessage::UtHolepunch`.
+///
+/// See `http://www.bittorrent.org/beps/bep_0055.html`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum UtHolepunchMessage {
+    /// Ask the receiving peer to relay a connect message between us and the target peer.
+    Rendezvous(SocketAddr),
+    /// Sent by a relaying peer, asking us to connect to the given peer.
+    Connect(SocketAddr),
+    /// Sent by a relaying peer that could not relay a rendezvous for the given target peer.
+    Error(SocketAddr, UtHolepunchErrorCode),
+}
+
+impl UtHolepunchMessage {
+    /// Create a new [`UtHolepunchMessage`] from bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unable to parse given bytes into type.
+    pub fn parse_bytes(mut bytes: &[u8]) -> Result<UtHolepunchMessage, UtHolepunchMessageError> {
+        let msg_type = bytes.read_u8().map_err(|_| UtHolepunchMessageError::Truncated)?;
+        let addr_type = bytes.read_u8().map_err(|_| UtHolepunchMessageError::Truncated)?;
+
+        let ip = match addr_type {
+            IPV4_ADDR_TYPE_ID => bytes.read_u32::<BigEndian>().map(|ip| IpAddr::V4(Ipv4Addr::from(ip))),
+            IPV6_ADDR_TYPE_ID => bytes.read_u128::<BigEndian>().map(|ip| IpAddr::V6(Ipv6Addr::from(ip))),
+            other => return Err(UtHolepunchMessageError::UnknownAddressType(other)),
+        }
+        .map_err(|_| UtHolepunchMessageError::Truncated)?;
+        let port = bytes
+            .read_u16::<BigEndian>()
+            .map_err(|_| UtHolepunchMessageError::Truncated)?;
+        let err_code = bytes
+            .read_u32::<BigEndian>()
+            .map_err(|_| UtHolepunchMessageError::Truncated)?;
+
+        let addr = SocketAddr::new(ip, port);
+        match msg_type {
+            RENDEZVOUS_MESSAGE_TYPE_ID => Ok(UtHolepunchMessage::Rendezvous(addr)),
+            CONNECT_MESSAGE_TYPE_ID => Ok(UtHolepunchMessage::Connect(addr)),
+            ERROR_MESSAGE_TYPE_ID => Ok(UtHolepunchMessage::Error(addr, UtHolepunchErrorCode::from_code(err_code)?)),
+            other => Err(UtHolepunchMessageError::UnknownMessageType(other)),
+        }
+    }
+
+    /// Writes bytes from the current state.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unable to write the bytes.
+    pub fn write_bytes<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let (msg_type, err_code) = match self {
+            UtHolepunchMessage::Rendezvous(_) => (RENDEZVOUS_MESSAGE_TYPE_ID, 0),
+            UtHolepunchMessage::Connect(_) => (CONNECT_MESSAGE_TYPE_ID, 0),
+            UtHolepunchMessage::Error(_, code) => (ERROR_MESSAGE_TYPE_ID, code.code()),
+        };
+        let addr = self.addr();
+
+        writer.write_u8(msg_type)?;
+        match addr.ip() {
+            IpAddr::V4(ip) => {
+                writer.write_u8(IPV4_ADDR_TYPE_ID)?;
+                writer.write_all(&ip.octets())?;
+            }
+            IpAddr::V6(ip) => {
+                writer.write_u8(IPV6_ADDR_TYPE_ID)?;
+                writer.write_all(&ip.octets())?;
+            }
+        }
+        writer.write_u16::<BigEndian>(addr.port())?;
+        writer.write_u32::<BigEndian>(err_code)
+    }
+
+    #[must_use]
+    pub fn message_size(&self) -> usize {
+        let addr_len = match self.addr() {
+            SocketAddr::V4(_) => 4,
+            SocketAddr::V6(_) => 16,
+        };
+
+        FIXED_FIELDS_LEN + addr_len
+    }
+
+    /// Address of the peer that the message refers to.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        match *self {
+            UtHolepunchMessage::Rendezvous(addr) | UtHolepunchMessage::Connect(addr) | UtHolepunchMessage::Error(addr, _) => addr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::{UtHolepunchErrorCode, UtHolepunchMessage, UtHolepunchMessageError};
+
+    #[test]
+    fn positive_round_trip() {
+        let v4: SocketAddr = "1.2.3.4:6881".parse().unwrap();
+        let v6: SocketAddr = "[::1]:6881".parse().unwrap();
+
+        for message in [
+            UtHolepunchMessage::Rendezvous(v4),
+            UtHolepunchMessage::Connect(v6),
+            UtHolepunchMessage::Error(v4, UtHolepunchErrorCode::NoSupport),
+        ] {
+            let mut bytes = Vec::new();
+            message.write_bytes(&mut bytes).unwrap();
+
+            assert_eq!(message.message_size(), bytes.len());
+            assert_eq!(message, UtHolepunchMessage::parse_bytes(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn positive_parse_connect_v4() {
+        let bytes = [1, 0, 1, 2, 3, 4, 0x1A, 0xE1, 0, 0, 0, 0];
+
+        assert_eq!(
+            UtHolepunchMessage::Connect("1.2.3.4:6881".parse().unwrap()),
+            UtHolepunchMessage::parse_bytes(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    fn negative_parse_truncated() {
+        let bytes = [0, 1, 0, 0, 0, 0];
+
+        assert!(matches!(
+            UtHolepunchMessage::parse_bytes(&bytes),
+            Err(UtHolepunchMessageError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn negative_parse_unknown_error_code() {
+        let bytes = [2, 0, 1, 2, 3, 4, 0x1A, 0xE1, 0, 0, 0, 9];
+
+        assert!(matches!(
+            UtHolepunchMessage::parse_bytes(&bytes),
+            Err(UtHolepunchMessageError::UnknownErrorCode(9))
+        ));
+    }
+}
@@ -1,9 +1,10 @@
 use std::io::Write as _;
 
-use bencode::{ben_int, ben_map, BConvert, BDecodeOpt, BencodeRef};
+use bencode::{ben_bytes, ben_int, ben_map, BConvert, BDecodeOpt, BMutAccess, BencodeRef};
 use bytes::Bytes;
 use thiserror::Error;
 
+use super::compression::MetadataCompression;
 use super::PeerExtensionProtocolMessageError;
 use crate::message::bencode_util;
 
@@ -18,6 +19,12 @@ const ROOT_ERROR_KEY: &str = "PeerExtensionProtocolMessage";
 pub enum UtMetadataMessageError {
     #[error("Failed to match message type: {0}")]
     UnknownMessageType(u8),
+
+    #[error("Failed to match compression: {0}")]
+    UnknownCompression(String),
+
+    #[error("Failed to decompress data: {0}")]
+    InvalidCompressedData(String),
 }
 
 /// Enumeration of messages for `PeerExtensionProtocolMessage::UtMetadata`.
@@ -61,12 +68,18 @@ impl UtMetadataMessage {
                     DATA_MESSAGE_TYPE_ID => {
                         let total_size = bencode_util::parse_total_size(bencode_dict)?;
 
-                        Ok(UtMetadataMessage::Data(UtMetadataDataMessage::with_bytes(
-                            piece,
-                            total_size,
-                            extra_bytes,
-                            &bencode_bytes,
-                        )))
+                        match bencode_util::parse_compression(bencode_dict) {
+                            None => Ok(UtMetadataMessage::Data(UtMetadataDataMessage::with_bytes(
+                                piece,
+                                total_size,
+                                extra_bytes,
+                                &bencode_bytes,
+                            ))),
+                            Some(id) => {
+                                UtMetadataDataMessage::with_compressed_bytes(piece, total_size, &id, extra_bytes, &bencode_bytes)
+                                    .map(UtMetadataMessage::Data)
+                            }
+                        }
                     }
                     other => Err(UtMetadataMessageError::UnknownMessageType(other)),
                 };
@@ -168,29 +181,30 @@ impl UtMetadataRequestMessage {
 }
 
 /// Message for sending a piece of metadata from a peer.
+///
+/// If a `MetadataCompression` was negotiated, the payload is compressed on the wire, while
+/// `UtMetadataDataMessage::data` always returns the uncompressed piece.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct UtMetadataDataMessage {
     piece: i64,
     total_size: i64,
     data: Bytes,
+    compression: Option<MetadataCompression>,
+    wire_data: Bytes,
     bencode_size: usize,
 }
 
 impl UtMetadataDataMessage {
     pub fn new(piece: i64, total_size: i64, data: Bytes) -> UtMetadataDataMessage {
-        let encoded_bytes_len = (ben_map! {
-            bencode_util::MESSAGE_TYPE_KEY => ben_int!(i64::from(DATA_MESSAGE_TYPE_ID)),
-            bencode_util::PIECE_INDEX_KEY  => ben_int!(piece),
-            bencode_util::TOTAL_SIZE_KEY   => ben_int!(total_size)
-        })
-        .encode()
-        .len();
+        let encoded_bytes_len = encode_data_header(piece, total_size, None).len();
 
         UtMetadataDataMessage {
             piece,
             total_size,
+            wire_data: data.clone(),
             data,
+            compression: None,
             bencode_size: encoded_bytes_len,
         }
     }
@@ -199,11 +213,59 @@ impl UtMetadataDataMessage {
         UtMetadataDataMessage {
             piece,
             total_size,
+            wire_data: data.clone(),
             data,
+            compression: None,
             bencode_size: bytes.len(),
         }
     }
 
+    fn with_compressed_bytes(
+        piece: i64,
+        total_size: i64,
+        compression_id: &[u8],
+        wire_data: Bytes,
+        bytes: &Bytes,
+    ) -> Result<UtMetadataDataMessage, UtMetadataMessageError> {
+        let Some(compression) = MetadataCompression::from_id(compression_id) else {
+            return Err(UtMetadataMessageError::UnknownCompression(
+                String::from_utf8_lossy(compression_id).into_owned(),
+            ));
+        };
+
+        let data = compression
+            .decompress(&wire_data)
+            .map_err(|err| UtMetadataMessageError::InvalidCompressedData(err.to_string()))?;
+
+        Ok(UtMetadataDataMessage {
+            piece,
+            total_size,
+            data: data.into(),
+            compression: Some(compression),
+            wire_data,
+            bencode_size: bytes.len(),
+        })
+    }
+
+    /// Compress the payload of this message with the given `MetadataCompression`.
+    ///
+    /// Should only be used if `MetadataCompression::negotiate` selected the algorithm for the peer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if unable to compress the payload.
+    pub fn with_compression(self, compression: MetadataCompression) -> std::io::Result<UtMetadataDataMessage> {
+        let wire_data = compression.compress(&self.data)?;
+        let encoded_bytes_len = encode_data_header(self.piece, self.total_size, Some(compression)).len();
+
+        Ok(UtMetadataDataMessage {
+            compression: Some(compression),
+            wire_data: wire_data.into(),
+            bencode_size: encoded_bytes_len,
+            ..self
+        })
+    }
+
     /// Write Bytes from current state.
     ///
     /// # Errors
@@ -213,20 +275,15 @@ impl UtMetadataDataMessage {
     where
         W: std::io::Write,
     {
-        let encoded_bytes = (ben_map! {
-            bencode_util::MESSAGE_TYPE_KEY => ben_int!(i64::from(DATA_MESSAGE_TYPE_ID)),
-            bencode_util::PIECE_INDEX_KEY  => ben_int!(self.piece),
-            bencode_util::TOTAL_SIZE_KEY   => ben_int!(self.total_size)
-        })
-        .encode();
+        let encoded_bytes = encode_data_header(self.piece, self.total_size, self.compression);
 
         writer.write_all(encoded_bytes.as_ref())?;
 
-        writer.write_all(self.data.as_ref())
+        writer.write_all(self.wire_data.as_ref())
     }
 
     pub fn message_size(&self) -> usize {
-        self.bencode_size + self.data.len()
+        self.bencode_size + self.wire_data.len()
     }
 
     pub fn piece(&self) -> i64 {
@@ -240,6 +297,27 @@ impl UtMetadataDataMessage {
     pub fn data(&self) -> &Bytes {
         &self.data
     }
+
+    pub fn compression(&self) -> Option<MetadataCompression> {
+        self.compression
+    }
+}
+
+fn encode_data_header(piece: i64, total_size: i64, compression: Option<MetadataCompression>) -> Vec<u8> {
+    let mut header = ben_map! {
+        bencode_util::MESSAGE_TYPE_KEY => ben_int!(i64::from(DATA_MESSAGE_TYPE_ID)),
+        bencode_util::PIECE_INDEX_KEY  => ben_int!(piece),
+        bencode_util::TOTAL_SIZE_KEY   => ben_int!(total_size)
+    };
+
+    if let Some(compression) = compression {
+        header
+            .dict_mut()
+            .unwrap()
+            .insert(bencode_util::COMPRESSION_KEY.into(), ben_bytes!(compression.id()));
+    }
+
+    header.encode()
 }
 
 /// Message for rejecting a request for metadata from a peer.
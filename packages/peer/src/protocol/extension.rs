@@ -1,6 +1,91 @@
-use crate::message::{ExtendedMessage, PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::message::{ExtendedMessage, ExtendedType, PeerExtensionProtocolMessage, PeerExtensionProtocolMessageError};
 use crate::protocol::{NestedPeerProtocol, PeerProtocol};
 
+/// Extension ids negotiated in the extended handshakes of a connection.
+#[derive(Debug, Default)]
+struct NegotiatedIds {
+    ours: HashMap<ExtendedType, u8>,
+    theirs: HashMap<ExtendedType, u8>,
+}
+
+/// Shared handle to the extension ids of a connection, updated as extended handshakes are sent and received.
+///
+/// Ids of zero, which disable an extension, are never exposed.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionIds {
+    ids: Arc<RwLock<NegotiatedIds>>,
+}
+
+impl ExtensionIds {
+    /// Id that the peer should use when sending us a message of the given extension.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn our_id(&self, ext_type: &ExtendedType) -> Option<u8> {
+        self.ids.read().unwrap().ours.get(ext_type).copied()
+    }
+
+    /// Id that we should use when sending the peer a message of the given extension.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn their_id(&self, ext_type: &ExtendedType) -> Option<u8> {
+        self.ids.read().unwrap().theirs.get(ext_type).copied()
+    }
+
+    /// Whether both us and the peer support the given extension.
+    #[must_use]
+    pub fn is_negotiated(&self, ext_type: &ExtendedType) -> bool {
+        self.our_id(ext_type).is_some() && self.their_id(ext_type).is_some()
+    }
+
+    /// Take a snapshot of the ids from our extended handshake.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn ours(&self) -> HashMap<ExtendedType, u8> {
+        self.ids.read().unwrap().ours.clone()
+    }
+
+    /// Take a snapshot of the ids from the extended handshake of the peer.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn theirs(&self) -> HashMap<ExtendedType, u8> {
+        self.ids.read().unwrap().theirs.clone()
+    }
+
+    fn set_ours(&self, message: &ExtendedMessage) {
+        self.ids.write().unwrap().ours = enabled_ids(message);
+    }
+
+    fn set_theirs(&self, message: &ExtendedMessage) {
+        self.ids.write().unwrap().theirs = enabled_ids(message);
+    }
+}
+
+/// Ids of the extensions enabled by the given extended handshake.
+fn enabled_ids(message: &ExtendedMessage) -> HashMap<ExtendedType, u8> {
+    message
+        .id_map()
+        .iter()
+        .filter(|(_, &id)| id != 0)
+        .map(|(ext_type, &id)| (ext_type.clone(), id))
+        .collect()
+}
+
 /// Protocol for `BEP 10` peer extensions.
 
 #[derive(Debug, Clone)]
@@ -10,6 +95,7 @@ where
 {
     our_extended_msg: Option<ExtendedMessage>,
     their_extended_msg: Option<ExtendedMessage>,
+    ids: ExtensionIds,
     custom_protocol: P,
 }
 
@@ -24,9 +110,18 @@ where
         PeerExtensionProtocol {
             our_extended_msg: None,
             their_extended_msg: None,
+            ids: ExtensionIds::default(),
             custom_protocol,
         }
     }
+
+    /// Handle to the extension ids negotiated over the connection.
+    ///
+    /// The handle can be kept around after the protocol has been moved into a codec, so that
+    /// the application and custom extensions can address extended messages to the peer.
+    pub fn extension_ids(&self) -> ExtensionIds {
+        self.ids.clone()
+    }
 }
 
 impl<P> PeerProtocol for PeerExtensionProtocol<P>
@@ -95,13 +190,50 @@ where
 {
     fn received_message(&mut self, message: &ExtendedMessage) -> usize {
         self.their_extended_msg = Some(message.clone());
+        self.ids.set_theirs(message);
 
         self.custom_protocol.received_message(message)
     }
 
     fn sent_message(&mut self, message: &ExtendedMessage) -> usize {
         self.our_extended_msg = Some(message.clone());
+        self.ids.set_ours(message);
 
         self.custom_protocol.sent_message(message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PeerExtensionProtocol;
+    use crate::message::{ExtendedMessageBuilder, ExtendedType};
+    use crate::protocol::null::NullProtocol;
+    use crate::protocol::NestedPeerProtocol as _;
+
+    #[test]
+    fn positive_extension_ids_track_handshakes() {
+        let mut protocol = PeerExtensionProtocol::new(NullProtocol::new());
+        let ids = protocol.extension_ids();
+
+        let ours = ExtendedMessageBuilder::new()
+            .with_extended_type(ExtendedType::UtMetadata, Some(1))
+            .with_extended_type(ExtendedType::UtPex, Some(2))
+            .build();
+        let theirs = ExtendedMessageBuilder::new()
+            .with_extended_type(ExtendedType::UtMetadata, Some(3))
+            .with_extended_type(ExtendedType::UtPex, Some(0))
+            .build();
+
+        protocol.sent_message(&ours);
+        protocol.received_message(&theirs);
+
+        assert_eq!(Some(1), ids.our_id(&ExtendedType::UtMetadata));
+        assert_eq!(Some(3), ids.their_id(&ExtendedType::UtMetadata));
+        assert!(ids.is_negotiated(&ExtendedType::UtMetadata));
+
+        assert_eq!(None, ids.their_id(&ExtendedType::UtPex));
+        assert!(!ids.is_negotiated(&ExtendedType::UtPex));
+        assert_eq!(2, ids.ours().len());
+        assert_eq!(1, ids.theirs().len());
+    }
+}
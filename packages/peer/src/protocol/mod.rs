@@ -51,6 +51,15 @@ pub trait PeerProtocol {
     ///
     /// This function will return an error if unable to calculate the message length.
     fn message_size(&mut self, message: &Result<Self::ProtocolMessage, Self::ProtocolMessageError>) -> std::io::Result<usize>;
+
+    /// Whether every message is framed by a 4 byte (`u32`) big endian length prefix, as in the peer wire protocol.
+    ///
+    /// If true, `bytes_needed` must return the length prefix plus the length it encodes. This
+    /// allows codecs to find all of the complete messages that are buffered in a single pass,
+    /// rather than calling `bytes_needed` once for every message.
+    fn is_length_prefixed(&self) -> bool {
+        false
+    }
 }
 
 /// Trait for nested peer protocols to see higher level peer protocol messages.
@@ -0,0 +1,322 @@
+//! Timing out of block requests that peers are slow to deliver.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::message::{CancelMessage, PieceMessage, RequestMessage};
+use crate::PeerInfo;
+
+/// Shortest time a peer is given to deliver a requested block.
+const DEFAULT_MIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest time a peer is given to deliver a requested block, and the time given to peers with an unknown rate.
+const DEFAULT_MAX_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many times longer than expected at its current rate a peer may take to deliver a block.
+const TIMEOUT_FACTOR: u64 = 4;
+
+struct OutstandingRequest {
+    message: RequestMessage,
+    deadline: Instant,
+}
+
+#[derive(Default)]
+struct PeerRequests {
+    outstanding: VecDeque<OutstandingRequest>,
+    received_bytes: u64,
+    rate: u64,
+}
+
+impl PeerRequests {
+    fn outstanding_bytes(&self) -> u64 {
+        self.outstanding
+            .iter()
+            .map(|request| request.message.block_length() as u64)
+            .sum()
+    }
+}
+
+/// Block request that a peer did not deliver in time, see `RequestTracker::poll_timeouts`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimedOutRequest {
+    peer: PeerInfo,
+    message: RequestMessage,
+    cancel: bool,
+}
+
+impl TimedOutRequest {
+    /// Peer that the block was requested from.
+    #[must_use]
+    pub fn peer(&self) -> PeerInfo {
+        self.peer
+    }
+
+    /// Request for the block, which should be re-queued so that it is requested from another peer.
+    #[must_use]
+    pub fn request(&self) -> &RequestMessage {
+        &self.message
+    }
+
+    /// Message canceling the request with the slow peer, if the `RequestTracker` is configured to send one.
+    #[must_use]
+    pub fn cancel(&self) -> Option<CancelMessage> {
+        self.cancel.then(|| {
+            CancelMessage::new(
+                self.message.piece_index(),
+                self.message.block_offset(),
+                self.message.block_length(),
+            )
+        })
+    }
+}
+
+/// Tracks the `RequestMessage`(s) sent to the peers of a torrent, timing out the blocks that are not delivered in time.
+///
+/// Each request is given a timeout derived from the rate at which the peer has been delivering blocks
+/// and the number of bytes already requested from it, so a fast peer with a deep queue is not timed
+/// out early, while a stalled peer does not hold up a piece for long. Timed out requests are handed
+/// back to be re-queued for other peers, so that a single slow peer cannot keep a piece from completing.
+///
+/// Like the `UploadScheduler`, the tracker does not send anything itself; requests are pushed as they
+/// are sent, and blocks are recorded as they are received.
+#[allow(clippy::module_name_repetitions)]
+pub struct RequestTracker {
+    peers: HashMap<PeerInfo, PeerRequests>,
+    min_timeout: Duration,
+    max_timeout: Duration,
+    send_cancel: bool,
+}
+
+impl RequestTracker {
+    /// Create a new `RequestTracker` with default timeouts.
+    #[must_use]
+    pub fn new() -> RequestTracker {
+        RequestTracker::with_timeouts(DEFAULT_MIN_TIMEOUT, DEFAULT_MAX_TIMEOUT)
+    }
+
+    /// Create a new `RequestTracker` giving peers between `min_timeout` and `max_timeout` to deliver a block.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if `min_timeout` is greater than `max_timeout`.
+    #[must_use]
+    pub fn with_timeouts(min_timeout: Duration, max_timeout: Duration) -> RequestTracker {
+        assert!(min_timeout <= max_timeout, "min_timeout must not be greater than max_timeout");
+
+        RequestTracker {
+            peers: HashMap::new(),
+            min_timeout,
+            max_timeout,
+            send_cancel: false,
+        }
+    }
+
+    /// Whether timed out requests should be canceled with the slow peer, see `TimedOutRequest::cancel`.
+    ///
+    /// Canceling saves the peer from uploading a block that will be downloaded from another peer,
+    /// at the cost of discarding it if it was already on its way. Disabled by default.
+    #[must_use]
+    pub fn with_cancel(mut self, send_cancel: bool) -> RequestTracker {
+        self.send_cancel = send_cancel;
+        self
+    }
+
+    /// Record that a `RequestMessage` was sent to the given peer at the given time.
+    pub fn push(&mut self, peer: PeerInfo, message: RequestMessage, now: Instant) {
+        let (min_timeout, max_timeout) = (self.min_timeout, self.max_timeout);
+        let requests = self.peers.entry(peer).or_default();
+
+        let timeout = match requests.rate {
+            0 => max_timeout,
+            rate => {
+                let queued_bytes = requests.outstanding_bytes() + message.block_length() as u64;
+                let expected_millis = queued_bytes.saturating_mul(TIMEOUT_FACTOR * 1000) / rate;
+
+                Duration::from_millis(expected_millis).clamp(min_timeout, max_timeout)
+            }
+        };
+
+        requests.outstanding.push_back(OutstandingRequest {
+            message,
+            deadline: now + timeout,
+        });
+    }
+
+    /// Record that a block was received from the given peer.
+    ///
+    /// Returns true if the block was requested from the peer and had not timed out yet.
+    pub fn received(&mut self, peer: &PeerInfo, message: &PieceMessage) -> bool {
+        let Some(requests) = self.peers.get_mut(peer) else {
+            return false;
+        };
+        requests.received_bytes += message.block_length() as u64;
+
+        let opt_position = requests.outstanding.iter().position(|request| {
+            request.message.piece_index() == message.piece_index()
+                && request.message.block_offset() == message.block_offset()
+                && request.message.block_length() == message.block_length()
+        });
+
+        opt_position
+            .and_then(|position| requests.outstanding.remove(position))
+            .is_some()
+    }
+
+    /// Recompute the rate of every peer from the bytes received over the elapsed period.
+    ///
+    /// This should be called periodically; rates apply to requests pushed after the update.
+    pub fn update_rates(&mut self, elapsed: Duration) {
+        let elapsed_millis = elapsed.as_millis().max(1);
+
+        for requests in self.peers.values_mut() {
+            let rate = u128::from(requests.received_bytes) * 1000 / elapsed_millis;
+
+            requests.rate = u64::try_from(rate).unwrap_or(u64::MAX);
+            requests.received_bytes = 0;
+        }
+    }
+
+    /// Rate, in bytes per second, at which the given peer delivered blocks over the last period.
+    #[must_use]
+    pub fn rate(&self, peer: &PeerInfo) -> u64 {
+        self.peers.get(peer).map_or(0, |requests| requests.rate)
+    }
+
+    /// Remove and return the requests that were not delivered by their deadline, oldest first for each peer.
+    pub fn poll_timeouts(&mut self, now: Instant) -> Vec<TimedOutRequest> {
+        let mut timed_out = Vec::new();
+
+        for (peer, requests) in &mut self.peers {
+            let (expired, pending): (VecDeque<_>, VecDeque<_>) =
+                requests.outstanding.drain(..).partition(|request| request.deadline <= now);
+            requests.outstanding = pending;
+
+            timed_out.extend(expired.into_iter().map(|request| TimedOutRequest {
+                peer: *peer,
+                message: request.message,
+                cancel: self.send_cancel,
+            }));
+        }
+
+        timed_out
+    }
+
+    /// Remove a peer, for example when it chokes us or disconnects, returning the requests still outstanding with it.
+    pub fn remove_peer(&mut self, peer: &PeerInfo) -> Vec<RequestMessage> {
+        self.peers
+            .remove(peer)
+            .map(|requests| requests.outstanding.into_iter().map(|request| request.message).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of requests outstanding across all peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peers.values().map(|requests| requests.outstanding.len()).sum()
+    }
+
+    /// Whether or not there are no requests outstanding.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.peers.values().all(|requests| requests.outstanding.is_empty())
+    }
+}
+
+impl Default for RequestTracker {
+    fn default() -> RequestTracker {
+        RequestTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use handshake::Extensions;
+
+    use super::RequestTracker;
+    use crate::message::{CancelMessage, PieceMessage, RequestMessage};
+    use crate::PeerInfo;
+
+    const BLOCK_LEN: usize = 16 * 1024;
+
+    fn peer_info(port: u16) -> PeerInfo {
+        PeerInfo::new(
+            format!("127.0.0.1:{port}").parse().unwrap(),
+            [port.to_be_bytes()[1]; 20].into(),
+            [0u8; 20].into(),
+            Extensions::new(),
+        )
+    }
+
+    fn block(index: u32) -> PieceMessage {
+        PieceMessage::new(index, 0, Bytes::from(vec![0u8; BLOCK_LEN]))
+    }
+
+    #[test]
+    fn positive_received_block_not_timed_out() {
+        let mut tracker = RequestTracker::new();
+        let peer = peer_info(1);
+        let now = Instant::now();
+
+        tracker.push(peer, RequestMessage::new(0, 0, BLOCK_LEN), now);
+
+        assert!(tracker.received(&peer, &block(0)));
+        assert!(!tracker.received(&peer, &block(0)));
+        assert!(tracker.poll_timeouts(now + Duration::from_secs(3600)).is_empty());
+    }
+
+    #[test]
+    fn positive_timeout_follows_peer_rate() {
+        let mut tracker = RequestTracker::with_timeouts(Duration::from_secs(1), Duration::from_secs(60)).with_cancel(true);
+        let (fast, slow) = (peer_info(1), peer_info(2));
+        let now = Instant::now();
+
+        // Fast peer delivers a block a second, slow peer a block every ten seconds
+        for index in 0..10 {
+            tracker.push(fast, RequestMessage::new(index, 0, BLOCK_LEN), now);
+            tracker.received(&fast, &block(index));
+        }
+        tracker.push(slow, RequestMessage::new(1, 0, BLOCK_LEN), now);
+        tracker.received(&slow, &block(1));
+        tracker.update_rates(Duration::from_secs(10));
+
+        tracker.push(fast, RequestMessage::new(2, 0, BLOCK_LEN), now);
+        tracker.push(slow, RequestMessage::new(3, 0, BLOCK_LEN), now);
+
+        // Fast peer stalled, while the slow peer is still given time at its rate
+        let timed_out = tracker.poll_timeouts(now + Duration::from_secs(5));
+
+        assert_eq!(1, timed_out.len());
+        assert_eq!(fast, timed_out[0].peer());
+        assert_eq!(&RequestMessage::new(2, 0, BLOCK_LEN), timed_out[0].request());
+        assert_eq!(Some(CancelMessage::new(2, 0, BLOCK_LEN)), timed_out[0].cancel());
+        assert_eq!(1, tracker.len());
+    }
+
+    #[test]
+    fn negative_unknown_rate_uses_max_timeout() {
+        let mut tracker = RequestTracker::with_timeouts(Duration::from_secs(1), Duration::from_secs(60));
+        let peer = peer_info(1);
+        let now = Instant::now();
+
+        tracker.push(peer, RequestMessage::new(0, 0, BLOCK_LEN), now);
+
+        assert!(tracker.poll_timeouts(now + Duration::from_secs(59)).is_empty());
+
+        let timed_out = tracker.poll_timeouts(now + Duration::from_secs(60));
+        assert_eq!(1, timed_out.len());
+        assert_eq!(None, timed_out[0].cancel());
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn positive_remove_peer_returns_outstanding() {
+        let mut tracker = RequestTracker::new();
+        let peer = peer_info(1);
+
+        tracker.push(peer, RequestMessage::new(0, 0, BLOCK_LEN), Instant::now());
+
+        assert_eq!(vec![RequestMessage::new(0, 0, BLOCK_LEN)], tracker.remove_peer(&peer));
+        assert!(tracker.is_empty());
+    }
+}
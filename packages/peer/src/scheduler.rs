@@ -0,0 +1,291 @@
+//! Fair scheduling of uploads across the unchoked peers of a torrent.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::message::{CancelMessage, PieceMessage};
+use crate::PeerInfo;
+
+/// Reciprocation rate, in bytes per second, that is worth one unit of weight.
+const DEFAULT_RATE_PER_WEIGHT: u64 = 16 * 1024;
+/// Maximum weight a single peer can earn through reciprocation.
+const DEFAULT_MAX_WEIGHT: u64 = 16;
+
+struct QueuedPiece {
+    finish: f64,
+    sequence: u64,
+    message: PieceMessage,
+}
+
+#[derive(Default)]
+struct PeerUploads {
+    queue: VecDeque<QueuedPiece>,
+    last_finish: f64,
+    received_bytes: u64,
+    weight: u64,
+}
+
+/// Weighted fair queuing scheduler for outgoing `PieceMessage`(s) across the peers of a torrent.
+///
+/// Each peer is given a weight derived from the rate at which it uploads to us, so peers that
+/// reciprocate receive a proportionally larger share of our upload bandwidth, while a single fast
+/// peer with a deep request queue cannot starve the others. Every peer has a weight of at least one.
+///
+/// The scheduler does not send anything itself; pieces are pushed as requests are served from
+/// disk and popped, in order, as the connections are able to accept more data.
+#[allow(clippy::module_name_repetitions)]
+pub struct UploadScheduler {
+    peers: HashMap<PeerInfo, PeerUploads>,
+    virtual_time: f64,
+    next_sequence: u64,
+    rate_per_weight: u64,
+    max_weight: u64,
+}
+
+impl UploadScheduler {
+    /// Create a new `UploadScheduler` with default weighting.
+    #[must_use]
+    pub fn new() -> UploadScheduler {
+        UploadScheduler::with_weighting(DEFAULT_RATE_PER_WEIGHT, DEFAULT_MAX_WEIGHT)
+    }
+
+    /// Create a new `UploadScheduler` giving a peer one unit of weight for every `rate_per_weight`
+    /// bytes per second it uploads to us, up to `max_weight`.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if `rate_per_weight` or `max_weight` is zero.
+    #[must_use]
+    pub fn with_weighting(rate_per_weight: u64, max_weight: u64) -> UploadScheduler {
+        assert!(rate_per_weight != 0, "rate_per_weight must be non zero");
+        assert!(max_weight != 0, "max_weight must be non zero");
+
+        UploadScheduler {
+            peers: HashMap::new(),
+            virtual_time: 0.0,
+            next_sequence: 0,
+            rate_per_weight,
+            max_weight,
+        }
+    }
+
+    /// Record that we received the given number of payload bytes from a peer.
+    pub fn record_received(&mut self, peer: PeerInfo, bytes: u64) {
+        self.peer_mut(peer).received_bytes += bytes;
+    }
+
+    /// Recompute the weight of every peer from the bytes received over the elapsed period.
+    ///
+    /// This should be called periodically, for example on every choking round. Weights apply
+    /// to pieces pushed after the update.
+    pub fn update_weights(&mut self, elapsed: Duration) {
+        let elapsed_millis = elapsed.as_millis().max(1);
+
+        for uploads in self.peers.values_mut() {
+            let rate = u128::from(uploads.received_bytes) * 1000 / elapsed_millis;
+            let earned = u64::try_from(rate / u128::from(self.rate_per_weight)).unwrap_or(u64::MAX);
+
+            uploads.weight = earned.clamp(1, self.max_weight);
+            uploads.received_bytes = 0;
+        }
+    }
+
+    /// Current weight of the given peer.
+    #[must_use]
+    pub fn weight(&self, peer: &PeerInfo) -> u64 {
+        self.peers.get(peer).map_or(1, |uploads| uploads.weight.max(1))
+    }
+
+    /// Queue a `PieceMessage` to be uploaded to the given peer.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn push(&mut self, peer: PeerInfo, message: PieceMessage) {
+        let virtual_time = self.virtual_time;
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let uploads = self.peer_mut(peer);
+
+        let start = uploads.last_finish.max(virtual_time);
+        let finish = start + message.block_length() as f64 / uploads.weight.max(1) as f64;
+
+        uploads.last_finish = finish;
+        uploads.queue.push_back(QueuedPiece {
+            finish,
+            sequence,
+            message,
+        });
+    }
+
+    /// Take the next `PieceMessage` that should be uploaded, and the peer it should be uploaded to.
+    ///
+    /// Pieces with the same virtual finish time are taken in the order they were pushed.
+    pub fn pop(&mut self) -> Option<(PeerInfo, PieceMessage)> {
+        let peer = self
+            .peers
+            .iter()
+            .filter_map(|(peer, uploads)| uploads.queue.front().map(|queued| (*peer, queued.finish, queued.sequence)))
+            .min_by(|(_, lhs_finish, lhs_sequence), (_, rhs_finish, rhs_sequence)| {
+                lhs_finish.total_cmp(rhs_finish).then(lhs_sequence.cmp(rhs_sequence))
+            })
+            .map(|(peer, _, _)| peer)?;
+
+        let queued = self.peers.get_mut(&peer)?.queue.pop_front()?;
+        self.virtual_time = queued.finish;
+
+        Some((peer, queued.message))
+    }
+
+    /// Remove a queued `PieceMessage` for a request that the peer has canceled.
+    ///
+    /// Returns true if a matching piece was queued.
+    pub fn cancel(&mut self, peer: &PeerInfo, message: &CancelMessage) -> bool {
+        let Some(uploads) = self.peers.get_mut(peer) else {
+            return false;
+        };
+
+        let opt_position = uploads.queue.iter().position(|queued| {
+            queued.message.piece_index() == message.piece_index()
+                && queued.message.block_offset() == message.block_offset()
+                && queued.message.block_length() == message.block_length()
+        });
+
+        opt_position.and_then(|position| uploads.queue.remove(position)).is_some()
+    }
+
+    /// Remove a peer, for example when it is choked or disconnected, returning any pieces still queued for it.
+    pub fn remove_peer(&mut self, peer: &PeerInfo) -> Vec<PieceMessage> {
+        self.peers
+            .remove(peer)
+            .map(|uploads| uploads.queue.into_iter().map(|queued| queued.message).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of pieces queued across all peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peers.values().map(|uploads| uploads.queue.len()).sum()
+    }
+
+    /// Whether or not there are no pieces queued.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.peers.values().all(|uploads| uploads.queue.is_empty())
+    }
+
+    fn peer_mut(&mut self, peer: PeerInfo) -> &mut PeerUploads {
+        self.peers.entry(peer).or_insert_with(|| PeerUploads {
+            weight: 1,
+            ..PeerUploads::default()
+        })
+    }
+}
+
+impl Default for UploadScheduler {
+    fn default() -> UploadScheduler {
+        UploadScheduler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use handshake::Extensions;
+
+    use super::UploadScheduler;
+    use crate::message::{CancelMessage, PieceMessage};
+    use crate::PeerInfo;
+
+    const BLOCK_LEN: usize = 16 * 1024;
+
+    fn peer_info(port: u16) -> PeerInfo {
+        PeerInfo::new(
+            format!("127.0.0.1:{port}").parse().unwrap(),
+            [port.to_be_bytes()[1]; 20].into(),
+            [0u8; 20].into(),
+            Extensions::new(),
+        )
+    }
+
+    fn block(index: u32) -> PieceMessage {
+        PieceMessage::new(index, 0, Bytes::from(vec![0u8; BLOCK_LEN]))
+    }
+
+    #[test]
+    fn positive_equal_weights_round_robin() {
+        let mut scheduler = UploadScheduler::new();
+        let (fast, slow) = (peer_info(1), peer_info(2));
+
+        // Fast peer requested many blocks before the slow peer requested any
+        for index in 0..4 {
+            scheduler.push(fast, block(index));
+        }
+        scheduler.push(slow, block(0));
+        scheduler.push(slow, block(1));
+
+        let order: Vec<PeerInfo> = std::iter::from_fn(|| scheduler.pop()).map(|(peer, _)| peer).collect();
+
+        assert_eq!(vec![fast, slow, fast, slow, fast, fast], order);
+    }
+
+    #[test]
+    fn positive_weight_from_reciprocation() {
+        let mut scheduler = UploadScheduler::with_weighting(1024, 16);
+        let (giving, taking) = (peer_info(1), peer_info(2));
+
+        scheduler.record_received(giving, 4 * 1024 * 10);
+        scheduler.record_received(taking, 0);
+        scheduler.update_weights(Duration::from_secs(10));
+
+        assert_eq!(4, scheduler.weight(&giving));
+        assert_eq!(1, scheduler.weight(&taking));
+
+        for index in 0..6 {
+            scheduler.push(giving, block(index));
+            scheduler.push(taking, block(index));
+        }
+
+        let first_four: Vec<PeerInfo> = std::iter::from_fn(|| scheduler.pop()).map(|(peer, _)| peer).take(4).collect();
+
+        assert_eq!(vec![giving, giving, giving, taking], first_four);
+    }
+
+    #[test]
+    fn positive_weight_capped() {
+        let mut scheduler = UploadScheduler::with_weighting(1, 4);
+        let peer = peer_info(1);
+
+        scheduler.record_received(peer, u64::MAX);
+        scheduler.update_weights(Duration::ZERO);
+
+        assert_eq!(4, scheduler.weight(&peer));
+    }
+
+    #[test]
+    fn positive_cancel_queued_piece() {
+        let mut scheduler = UploadScheduler::new();
+        let peer = peer_info(1);
+
+        scheduler.push(peer, block(0));
+        scheduler.push(peer, block(1));
+
+        assert!(scheduler.cancel(&peer, &CancelMessage::new(0, 0, BLOCK_LEN)));
+        assert!(!scheduler.cancel(&peer, &CancelMessage::new(0, 0, BLOCK_LEN)));
+
+        assert_eq!(1, scheduler.len());
+        assert_eq!(Some(1), scheduler.pop().map(|(_, message)| message.piece_index()));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn positive_remove_peer_returns_queued() {
+        let mut scheduler = UploadScheduler::new();
+        let peer = peer_info(1);
+
+        scheduler.push(peer, block(0));
+
+        assert_eq!(1, scheduler.remove_peer(&peer).len());
+        assert!(scheduler.pop().is_none());
+    }
+}
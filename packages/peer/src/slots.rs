@@ -0,0 +1,348 @@
+//! Connection slots shared between incoming and outgoing peer connections.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use util::bt::InfoHash;
+
+const DEFAULT_MAX_CONNECTIONS: usize = 200;
+const DEFAULT_MAX_TORRENT_CONNECTIONS: usize = 50;
+const DEFAULT_INCOMING_RESERVE: f64 = 0.2;
+const DEFAULT_OUTGOING_RESERVE: f64 = 0.0;
+
+/// Direction in which a peer connection was established.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConnectionDirection {
+    /// Connection accepted from a remote peer.
+    Incoming,
+    /// Connection dialed by us.
+    Outgoing,
+}
+
+/// Configures the number of connection slots, globally and for each torrent.
+///
+/// Part of each limit may be reserved for one direction, so that for example an aggressive
+/// dialer can not take the slots needed to accept connections from remote peers.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SlotConfig {
+    max_connections: usize,
+    max_torrent_connections: usize,
+    incoming_reserve: f64,
+    outgoing_reserve: f64,
+}
+
+impl SlotConfig {
+    /// Sets the maximum number of connections across all torrents.
+    #[must_use]
+    pub fn with_max_connections(mut self, max: usize) -> SlotConfig {
+        self.max_connections = max;
+        self
+    }
+
+    /// Sets the maximum number of connections for a single torrent.
+    #[must_use]
+    pub fn with_max_torrent_connections(mut self, max: usize) -> SlotConfig {
+        self.max_torrent_connections = max;
+        self
+    }
+
+    /// Sets the ratio of each limit that only incoming connections may use.
+    ///
+    /// The ratio is clamped between zero and one, minus the outgoing reserve.
+    #[must_use]
+    pub fn with_incoming_reserve(mut self, ratio: f64) -> SlotConfig {
+        self.incoming_reserve = ratio.clamp(0.0, 1.0 - self.outgoing_reserve);
+        self
+    }
+
+    /// Sets the ratio of each limit that only outgoing connections may use.
+    ///
+    /// The ratio is clamped between zero and one, minus the incoming reserve.
+    #[must_use]
+    pub fn with_outgoing_reserve(mut self, ratio: f64) -> SlotConfig {
+        self.outgoing_reserve = ratio.clamp(0.0, 1.0 - self.incoming_reserve);
+        self
+    }
+
+    /// Retrieves the maximum number of connections across all torrents.
+    #[must_use]
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Retrieves the maximum number of connections for a single torrent.
+    #[must_use]
+    pub fn max_torrent_connections(&self) -> usize {
+        self.max_torrent_connections
+    }
+
+    /// Retrieves the ratio of each limit that only incoming connections may use.
+    #[must_use]
+    pub fn incoming_reserve(&self) -> f64 {
+        self.incoming_reserve
+    }
+
+    /// Retrieves the ratio of each limit that only outgoing connections may use.
+    #[must_use]
+    pub fn outgoing_reserve(&self) -> f64 {
+        self.outgoing_reserve
+    }
+
+    /// Number of connections in the given direction that fit within the given limit.
+    fn direction_limit(&self, limit: usize, direction: ConnectionDirection) -> usize {
+        let other_reserve = match direction {
+            ConnectionDirection::Incoming => self.outgoing_reserve,
+            ConnectionDirection::Outgoing => self.incoming_reserve,
+        };
+
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let reserved = (limit as f64 * other_reserve).round() as usize;
+
+        limit.saturating_sub(reserved)
+    }
+}
+
+impl Default for SlotConfig {
+    fn default() -> SlotConfig {
+        SlotConfig {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            max_torrent_connections: DEFAULT_MAX_TORRENT_CONNECTIONS,
+            incoming_reserve: DEFAULT_INCOMING_RESERVE,
+            outgoing_reserve: DEFAULT_OUTGOING_RESERVE,
+        }
+    }
+}
+
+//----------------------------------------------------------------------------//
+
+/// Number of connections held in each direction.
+#[derive(Debug, Copy, Clone, Default)]
+struct SlotCount {
+    incoming: usize,
+    outgoing: usize,
+}
+
+impl SlotCount {
+    fn total(self) -> usize {
+        self.incoming + self.outgoing
+    }
+
+    fn get(self, direction: ConnectionDirection) -> usize {
+        match direction {
+            ConnectionDirection::Incoming => self.incoming,
+            ConnectionDirection::Outgoing => self.outgoing,
+        }
+    }
+
+    fn get_mut(&mut self, direction: ConnectionDirection) -> &mut usize {
+        match direction {
+            ConnectionDirection::Incoming => &mut self.incoming,
+            ConnectionDirection::Outgoing => &mut self.outgoing,
+        }
+    }
+
+    /// Whether another connection in the given direction fits within the given limit.
+    fn has_room(self, config: &SlotConfig, limit: usize, direction: ConnectionDirection) -> bool {
+        self.total() < limit && self.get(direction) < config.direction_limit(limit, direction)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    global: SlotCount,
+    torrents: HashMap<InfoHash, SlotCount>,
+}
+
+/// Connection slots shared between the incoming accepts and outgoing dials of all torrents.
+///
+/// A slot is acquired before dialing a peer, or after accepting one, and is released once the
+/// returned `ConnectionSlot` is dropped, for example by adding the peer to a `PeerManager` with
+/// `PeerManagerInputMessage::AddSlottedPeer`.
+///
+/// Cloning the `ConnectionSlots` shares the slots between the clones.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone)]
+pub struct ConnectionSlots {
+    config: SlotConfig,
+    state: Arc<Mutex<SlotState>>,
+}
+
+impl ConnectionSlots {
+    /// Create new `ConnectionSlots` with the given configuration.
+    #[must_use]
+    pub fn new(config: SlotConfig) -> ConnectionSlots {
+        ConnectionSlots {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    /// Retrieves the configuration of the slots.
+    #[must_use]
+    pub fn config(&self) -> SlotConfig {
+        self.config
+    }
+
+    /// Acquire a slot for a connection in the given direction for the given torrent.
+    ///
+    /// Returns `None` if either the global or the torrent limit for that direction was reached.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn try_acquire(&self, direction: ConnectionDirection, hash: InfoHash) -> Option<ConnectionSlot> {
+        let mut state = self.state.lock().unwrap();
+        let torrent = state.torrents.get(&hash).copied().unwrap_or_default();
+
+        if !state.global.has_room(&self.config, self.config.max_connections, direction)
+            || !torrent.has_room(&self.config, self.config.max_torrent_connections, direction)
+        {
+            return None;
+        }
+
+        *state.global.get_mut(direction) += 1;
+        *state.torrents.entry(hash).or_default().get_mut(direction) += 1;
+
+        Some(ConnectionSlot {
+            direction,
+            hash,
+            state: self.state.clone(),
+        })
+    }
+
+    /// Whether a slot is available for a connection in the given direction for the given torrent.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn is_available(&self, direction: ConnectionDirection, hash: InfoHash) -> bool {
+        let state = self.state.lock().unwrap();
+        let torrent = state.torrents.get(&hash).copied().unwrap_or_default();
+
+        state.global.has_room(&self.config, self.config.max_connections, direction)
+            && torrent.has_room(&self.config, self.config.max_torrent_connections, direction)
+    }
+
+    /// Number of slots held in the given direction across all torrents.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn connections(&self, direction: ConnectionDirection) -> usize {
+        self.state.lock().unwrap().global.get(direction)
+    }
+
+    /// Number of slots held in the given direction for the given torrent.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn torrent_connections(&self, direction: ConnectionDirection, hash: InfoHash) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .torrents
+            .get(&hash)
+            .map_or(0, |count| count.get(direction))
+    }
+}
+
+/// Slot held by a single connection, released when dropped.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    direction: ConnectionDirection,
+    hash: InfoHash,
+    state: Arc<Mutex<SlotState>>,
+}
+
+impl ConnectionSlot {
+    /// Direction of the connection holding the slot.
+    #[must_use]
+    pub fn direction(&self) -> ConnectionDirection {
+        self.direction
+    }
+
+    /// `InfoHash` of the torrent the slot was acquired for.
+    #[must_use]
+    pub fn hash(&self) -> &InfoHash {
+        &self.hash
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        *state.global.get_mut(self.direction) -= 1;
+        if let Some(torrent) = state.torrents.get_mut(&self.hash) {
+            *torrent.get_mut(self.direction) -= 1;
+
+            if torrent.total() == 0 {
+                state.torrents.remove(&self.hash);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use util::bt::{self, InfoHash};
+
+    use super::{ConnectionDirection, ConnectionSlots, SlotConfig};
+
+    fn hash(byte: u8) -> InfoHash {
+        [byte; bt::INFO_HASH_LEN].into()
+    }
+
+    #[test]
+    fn positive_release_slot_on_drop() {
+        let slots = ConnectionSlots::new(SlotConfig::default().with_max_connections(1));
+
+        let slot = slots.try_acquire(ConnectionDirection::Incoming, hash(0)).unwrap();
+        assert!(slots.try_acquire(ConnectionDirection::Incoming, hash(1)).is_none());
+        assert_eq!(1, slots.torrent_connections(ConnectionDirection::Incoming, hash(0)));
+
+        drop(slot);
+        assert_eq!(0, slots.connections(ConnectionDirection::Incoming));
+        assert!(slots.try_acquire(ConnectionDirection::Incoming, hash(1)).is_some());
+    }
+
+    #[test]
+    fn positive_reserve_slots_for_incoming() {
+        let config = SlotConfig::default().with_max_connections(10).with_incoming_reserve(0.3);
+        let slots = ConnectionSlots::new(config);
+
+        let outgoing: Vec<_> = (0..10)
+            .map_while(|index| slots.try_acquire(ConnectionDirection::Outgoing, hash(index)))
+            .collect();
+        assert_eq!(7, outgoing.len());
+
+        let incoming: Vec<_> = (0..10)
+            .map_while(|index| slots.try_acquire(ConnectionDirection::Incoming, hash(index)))
+            .collect();
+        assert_eq!(3, incoming.len());
+    }
+
+    #[test]
+    fn negative_torrent_limit_reached() {
+        let config = SlotConfig::default()
+            .with_max_torrent_connections(2)
+            .with_incoming_reserve(0.0);
+        let slots = ConnectionSlots::new(config);
+
+        let _first = slots.try_acquire(ConnectionDirection::Outgoing, hash(0)).unwrap();
+        let _second = slots.try_acquire(ConnectionDirection::Incoming, hash(0)).unwrap();
+
+        assert!(!slots.is_available(ConnectionDirection::Incoming, hash(0)));
+        assert!(slots.try_acquire(ConnectionDirection::Outgoing, hash(0)).is_none());
+        assert!(slots.try_acquire(ConnectionDirection::Outgoing, hash(1)).is_some());
+    }
+}
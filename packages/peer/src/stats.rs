@@ -0,0 +1,221 @@
+//! Protocol overhead statistics for peer wire connections.
+
+use std::sync::{Arc, Mutex};
+
+use crate::message;
+
+const WIRE_MESSAGE_TYPES: usize = 13;
+
+/// Type of a message framed with the peer wire protocol length prefix.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WireMessageType {
+    /// Message with a length of zero.
+    KeepAlive,
+    /// Message telling the receiver that its requests will not be answered.
+    Choke,
+    /// Message telling the receiver that its requests will now be answered.
+    UnChoke,
+    /// Message telling the receiver that we want to download from it.
+    Interested,
+    /// Message telling the receiver that we no longer want to download from it.
+    UnInterested,
+    /// Message announcing a single piece.
+    Have,
+    /// Message announcing all pieces.
+    BitField,
+    /// Message requesting a block.
+    Request,
+    /// Message containing a block.
+    Piece,
+    /// Message canceling a block request.
+    Cancel,
+    /// Message containing the port of a DHT node.
+    Port,
+    /// Message of the extension protocol.
+    Extended,
+    /// Message with an unknown id, or that does not follow the peer wire protocol framing.
+    Other,
+}
+
+impl WireMessageType {
+    fn index(self) -> usize {
+        match self {
+            WireMessageType::KeepAlive => 0,
+            WireMessageType::Choke => 1,
+            WireMessageType::UnChoke => 2,
+            WireMessageType::Interested => 3,
+            WireMessageType::UnInterested => 4,
+            WireMessageType::Have => 5,
+            WireMessageType::BitField => 6,
+            WireMessageType::Request => 7,
+            WireMessageType::Piece => 8,
+            WireMessageType::Cancel => 9,
+            WireMessageType::Port => 10,
+            WireMessageType::Extended => 11,
+            WireMessageType::Other => 12,
+        }
+    }
+}
+
+/// Statistics for the messages flowing in a single direction of a connection.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct DirectionStats {
+    messages: [u64; WIRE_MESSAGE_TYPES],
+    overhead_bytes: u64,
+    payload_bytes: u64,
+}
+
+impl DirectionStats {
+    /// Number of messages of the given type.
+    #[must_use]
+    pub fn messages(&self, message_type: WireMessageType) -> u64 {
+        self.messages[message_type.index()]
+    }
+
+    /// Number of messages of any type, including keep alives.
+    #[must_use]
+    pub fn total_messages(&self) -> u64 {
+        self.messages.iter().sum()
+    }
+
+    /// Number of keep alive messages.
+    #[must_use]
+    pub fn keep_alives(&self) -> u64 {
+        self.messages(WireMessageType::KeepAlive)
+    }
+
+    /// Number of bytes spent on the protocol itself.
+    ///
+    /// Every byte that is not piece data is considered overhead, including the headers of piece messages.
+    #[must_use]
+    pub fn overhead_bytes(&self) -> u64 {
+        self.overhead_bytes
+    }
+
+    /// Number of bytes of piece data.
+    #[must_use]
+    pub fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+
+    /// Number of bytes of both overhead and payload.
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.overhead_bytes + self.payload_bytes
+    }
+
+    fn record(&mut self, frame: &[u8]) {
+        let (message_type, payload_len) = message::frame_message_type(frame);
+
+        let payload_len = payload_len as u64;
+        let frame_len = frame.len() as u64;
+
+        self.messages[message_type.index()] += 1;
+        self.payload_bytes += payload_len;
+        self.overhead_bytes += frame_len - payload_len;
+    }
+}
+
+/// Snapshot of the statistics for a connection.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct WireStats {
+    sent: DirectionStats,
+    received: DirectionStats,
+}
+
+impl WireStats {
+    /// Statistics for messages sent to the peer.
+    #[must_use]
+    pub fn sent(&self) -> &DirectionStats {
+        &self.sent
+    }
+
+    /// Statistics for messages received from the peer.
+    #[must_use]
+    pub fn received(&self) -> &DirectionStats {
+        &self.received
+    }
+}
+
+/// Shared handle to the statistics of a connection, updated as the codec encodes and decodes messages.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default)]
+pub struct WireStatsHandle {
+    stats: Arc<Mutex<WireStats>>,
+}
+
+impl WireStatsHandle {
+    /// Take a snapshot of the current statistics.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the lock is poisoned.
+    #[must_use]
+    pub fn snapshot(&self) -> WireStats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub(crate) fn record_sent(&self, frame: &[u8]) {
+        self.stats.lock().unwrap().sent.record(frame);
+    }
+
+    pub(crate) fn record_received(&self, frame: &[u8]) {
+        self.stats.lock().unwrap().received.record(frame);
+    }
+
+    /// Record several received frames, taking the lock only once.
+    pub(crate) fn record_received_all<'a>(&self, frames: impl IntoIterator<Item = &'a [u8]>) {
+        let mut stats = self.stats.lock().unwrap();
+
+        for frame in frames {
+            stats.received.record(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WireMessageType, WireStatsHandle};
+
+    #[test]
+    fn positive_record_keep_alive() {
+        let stats = WireStatsHandle::default();
+
+        stats.record_received(&[0, 0, 0, 0]);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(1, snapshot.received().keep_alives());
+        assert_eq!(4, snapshot.received().overhead_bytes());
+        assert_eq!(0, snapshot.received().payload_bytes());
+        assert_eq!(0, snapshot.sent().total_messages());
+    }
+
+    #[test]
+    fn positive_record_piece_payload() {
+        let stats = WireStatsHandle::default();
+
+        let mut frame = vec![0, 0, 0, 13, 7, 0, 0, 0, 1, 0, 0, 0, 0];
+        frame.extend_from_slice(&[0xAB; 4]);
+
+        stats.record_sent(&frame);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(1, snapshot.sent().messages(WireMessageType::Piece));
+        assert_eq!(13, snapshot.sent().overhead_bytes());
+        assert_eq!(4, snapshot.sent().payload_bytes());
+    }
+
+    #[test]
+    fn positive_record_unknown_message() {
+        let stats = WireStatsHandle::default();
+
+        stats.record_received(&[0, 0, 0, 1, 200]);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(1, snapshot.received().messages(WireMessageType::Other));
+        assert_eq!(5, snapshot.received().overhead_bytes());
+    }
+}
@@ -6,7 +6,9 @@ use futures::sink::Sink;
 use futures::stream::Stream;
 use futures::{SinkExt as _, StreamExt as _, TryStream};
 use peer::error::PeerManagerError;
-use peer::{ManagedMessage, PeerInfo, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage};
+use peer::{
+    DisconnectReason, ManagedMessage, PeerInfo, PeerManagerInputMessage, PeerManagerOutputError, PeerManagerOutputMessage,
+};
 use thiserror::Error;
 use tokio::time::error::Elapsed;
 use tracing::level_filters::LevelFilter;
@@ -58,6 +60,7 @@ pub fn tracing_stderr_init(filter: LevelFilter) {
     tracing::info!("Logging initialized");
 }
 
+#[allow(dead_code)]
 pub async fn add_peer<Si, St, Peer, Message>(
     send: &mut Si,
     recv: &mut St,
@@ -96,6 +99,7 @@ where
     }
 }
 
+#[allow(dead_code)]
 pub async fn remove_peer<Si, St, Peer, Message>(send: &mut Si, recv: &mut St, info: PeerInfo) -> Result<(), Error<Message>>
 where
     Si: Sink<std::io::Result<PeerManagerInputMessage<Peer, Message>>, Error = PeerManagerError<SendError>> + Unpin,
@@ -109,16 +113,19 @@ where
         + 'static,
     Message: ManagedMessage + Send + 'static,
 {
-    let () = tokio::time::timeout(DEFAULT_TIMEOUT, send.send(Ok(PeerManagerInputMessage::RemovePeer(info))))
-        .await
-        .map_err(|e| Error::SendTimedOut(e))??;
+    let () = tokio::time::timeout(
+        DEFAULT_TIMEOUT,
+        send.send(Ok(PeerManagerInputMessage::RemovePeer(info, DisconnectReason::Removed))),
+    )
+    .await
+    .map_err(|e| Error::SendTimedOut(e))??;
 
     let response = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
         .await
         .map(|res| res.ok_or(Error::ReceiverClosed()))
         .map_err(|e| Error::ReceiveTimedOut(e))???;
 
-    if let PeerManagerOutputMessage::PeerRemoved(info_recv) = response {
+    if let PeerManagerOutputMessage::PeerRemoved(info_recv, DisconnectReason::Removed) = response {
         if info_recv == info {
             Ok(())
         } else {
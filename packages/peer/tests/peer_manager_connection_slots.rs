@@ -0,0 +1,65 @@
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::PeerWireProtocolMessage;
+use peer::protocols::NullProtocol;
+use peer::{
+    ConnectionDirection, ConnectionSlots, PeerInfo, PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputMessage,
+    SlotConfig,
+};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Peer = ConnectedChannel<
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+>;
+
+#[tokio::test]
+async fn positive_slot_released_when_peer_removed() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let slots = ConnectionSlots::new(SlotConfig::default().with_max_connections(1));
+
+    let (peer_one, _peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = PeerInfo::new(
+        "127.0.0.1:0".parse().unwrap(),
+        [0u8; bt::PEER_ID_LEN].into(),
+        hash,
+        Extensions::new(),
+    );
+
+    let slot = slots.try_acquire(ConnectionDirection::Incoming, hash).unwrap();
+    send.send(Ok(PeerManagerInputMessage::AddSlottedPeer(peer_one_info, peer_one, slot)))
+        .await
+        .unwrap();
+
+    let added = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(added, PeerManagerOutputMessage::PeerAdded(info) if info == peer_one_info));
+
+    // The slot is held for as long as the peer is managed
+    assert_eq!(1, slots.connections(ConnectionDirection::Incoming));
+    assert!(slots.try_acquire(ConnectionDirection::Outgoing, hash).is_none());
+
+    tokio::time::timeout(DEFAULT_TIMEOUT, send.shutdown(peer_one_info, Vec::new()))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(0, slots.connections(ConnectionDirection::Incoming));
+    assert!(slots.try_acquire(ConnectionDirection::Incoming, hash).is_some());
+}
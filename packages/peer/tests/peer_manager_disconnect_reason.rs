@@ -0,0 +1,87 @@
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{add_peer, tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::PeerWireProtocolMessage;
+use peer::protocols::NullProtocol;
+use peer::{DisconnectReason, PeerInfo, PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputMessage};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Peer = ConnectedChannel<
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+>;
+
+fn peer_info(id: u8) -> PeerInfo {
+    PeerInfo::new(
+        format!("127.0.0.1:{id}").parse().unwrap(),
+        [id; bt::PEER_ID_LEN].into(),
+        [0u8; bt::INFO_HASH_LEN].into(),
+        Extensions::new(),
+    )
+}
+
+#[tokio::test]
+async fn positive_removed_peer_reports_given_reason() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, _peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = peer_info(1);
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    send.send(Ok(PeerManagerInputMessage::RemovePeer(
+        peer_one_info,
+        DisconnectReason::Banned,
+    )))
+    .await
+    .unwrap();
+
+    let removed = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        removed,
+        PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::Banned) if info == peer_one_info
+    ));
+}
+
+#[tokio::test]
+async fn positive_closed_peer_reports_closed() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = peer_info(1);
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    // Dropping the remote end closes the connection from their side
+    drop(peer_two);
+
+    let removed = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        removed,
+        PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::Closed) if info == peer_one_info
+    ));
+}
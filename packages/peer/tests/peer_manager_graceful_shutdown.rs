@@ -0,0 +1,136 @@
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{add_peer, tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::{HaveMessage, PeerWireProtocolMessage};
+use peer::protocols::NullProtocol;
+use peer::{DisconnectReason, PeerInfo, PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputMessage};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Peer = ConnectedChannel<
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+>;
+
+fn peer_info(id: u8) -> PeerInfo {
+    PeerInfo::new(
+        format!("127.0.0.1:{id}").parse().unwrap(),
+        [id; bt::PEER_ID_LEN].into(),
+        [0u8; bt::INFO_HASH_LEN].into(),
+        Extensions::new(),
+    )
+}
+
+#[tokio::test]
+async fn positive_shutdown_flushes_and_says_farewell() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, mut peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = peer_info(1);
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    send.send(Ok(PeerManagerInputMessage::SendMessage(
+        peer_one_info,
+        0,
+        PeerWireProtocolMessage::KeepAlive,
+    )))
+    .await
+    .unwrap();
+
+    let shutdown = tokio::spawn(send.shutdown(peer_one_info, vec![PeerWireProtocolMessage::Have(HaveMessage::new(7))]));
+
+    let sent = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(sent, PeerManagerOutputMessage::SentMessage(info, 0) if info == peer_one_info));
+
+    let removed = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        removed,
+        PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::Shutdown) if info == peer_one_info
+    ));
+
+    tokio::time::timeout(DEFAULT_TIMEOUT, shutdown)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    let received: Vec<_> = tokio::time::timeout(DEFAULT_TIMEOUT, (&mut peer_two).map(Result::unwrap).collect())
+        .await
+        .unwrap();
+    // Queued messages are flushed before the farewell and the extra messages
+    assert!(
+        matches!(
+            received.as_slice(),
+            [
+                PeerWireProtocolMessage::KeepAlive,
+                PeerWireProtocolMessage::Choke,
+                PeerWireProtocolMessage::UnInterested,
+                PeerWireProtocolMessage::Have(have),
+            ] if have.piece_index() == 7
+        ),
+        "unexpected messages: {received:?}"
+    );
+}
+
+#[tokio::test]
+async fn positive_shutdown_all_removes_every_peer() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, _peer_one_remote): (Peer, Peer) = connected_channel(5);
+    let (peer_two, _peer_two_remote): (Peer, Peer) = connected_channel(5);
+    add_peer(&mut send, &mut recv, peer_info(1), peer_one).await.unwrap();
+    add_peer(&mut send, &mut recv, peer_info(2), peer_two).await.unwrap();
+
+    let shutdown = tokio::spawn(send.shutdown_all());
+
+    let mut removed = Vec::new();
+    for _ in 0..2 {
+        let message = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::Shutdown) = message else {
+            panic!("it should be a peer removed, but got: {message:?}")
+        };
+        removed.push(info);
+    }
+    removed.sort_by_key(|info| *info.addr());
+
+    assert_eq!(vec![peer_info(1), peer_info(2)], removed);
+
+    tokio::time::timeout(DEFAULT_TIMEOUT, shutdown)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    assert!(matches!(
+        send.shutdown(peer_info(1), Vec::new()).await,
+        Err(peer::error::PeerManagerError::PeerNotFound(_))
+    ));
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{add_peer, tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::PeerWireProtocolMessage;
+use peer::protocols::NullProtocol;
+use peer::{PeerInfo, PeerManagerBuilder, PeerManagerOutputMessage};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Peer = ConnectedChannel<
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+>;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(50);
+const IDLE_PARK_TIMEOUT: Duration = Duration::from_millis(100);
+
+fn peer_info() -> PeerInfo {
+    PeerInfo::new(
+        "127.0.0.1:0".parse().unwrap(),
+        [0u8; bt::PEER_ID_LEN].into(),
+        [0u8; bt::INFO_HASH_LEN].into(),
+        Extensions::new(),
+    )
+}
+
+#[tokio::test]
+async fn positive_peer_manager_parks_and_revives_idle_peer() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .with_heartbeat_interval(HEARTBEAT_INTERVAL)
+        .with_idle_park_timeout(Some(IDLE_PARK_TIMEOUT))
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = peer_info();
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    // Drain the keep-alive messages, so the heartbeat never blocks on a full channel
+    let (mut peer_two_send, mut peer_two_recv) = peer_two.split();
+    tokio::spawn(async move { while peer_two_recv.next().await.is_some() {} });
+
+    let parked = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(parked, PeerManagerOutputMessage::PeerParked(info) if info == peer_one_info));
+
+    peer_two_send.send(Ok(PeerWireProtocolMessage::Interested)).await.unwrap();
+
+    let revived = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(revived, PeerManagerOutputMessage::PeerRevived(info) if info == peer_one_info));
+
+    let received = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        received,
+        PeerManagerOutputMessage::ReceivedMessage(info, PeerWireProtocolMessage::Interested) if info == peer_one_info
+    ));
+
+    // The peer is interested, so the connection is no longer idle
+    let res = tokio::time::timeout(IDLE_PARK_TIMEOUT * 3, recv.next()).await;
+    assert!(res.is_err(), "it should not park an interested peer, but got: {res:?}");
+}
+
+#[tokio::test]
+async fn negative_peer_manager_does_not_park_by_default() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .with_heartbeat_interval(HEARTBEAT_INTERVAL)
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = peer_info();
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    let (_peer_two_send, mut peer_two_recv) = peer_two.split();
+    tokio::spawn(async move { while peer_two_recv.next().await.is_some() {} });
+
+    let res = tokio::time::timeout(IDLE_PARK_TIMEOUT * 3, recv.next()).await;
+    assert!(res.is_err(), "it should not park the peer, but got: {res:?}");
+}
@@ -0,0 +1,74 @@
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{add_peer, tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::{PeerWireProtocolMessage, RequestMessage};
+use peer::protocols::NullProtocol;
+use peer::{DisconnectReason, PeerInfo, PeerManagerBuilder, PeerManagerOutputMessage, ProtocolViolation, ViolationPolicy};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Peer = ConnectedChannel<
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+    Result<PeerWireProtocolMessage<NullProtocol>, std::io::Error>,
+>;
+
+#[tokio::test]
+async fn positive_peer_manager_disconnects_on_violation() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .with_violation_policy(ViolationPolicy::Disconnect)
+        .build::<Peer, PeerWireProtocolMessage<NullProtocol>>()
+        .into_parts();
+
+    let (peer_one, mut peer_two): (Peer, Peer) = connected_channel(5);
+    let peer_one_info = PeerInfo::new(
+        "127.0.0.1:0".parse().unwrap(),
+        [0u8; bt::PEER_ID_LEN].into(),
+        [0u8; bt::INFO_HASH_LEN].into(),
+        Extensions::new(),
+    );
+
+    add_peer(&mut send, &mut recv, peer_one_info, peer_one).await.unwrap();
+
+    // We never unchoked the peer, so it has no business requesting anything
+    peer_two
+        .send(Ok(PeerWireProtocolMessage::Request(RequestMessage::new(0, 0, 16))))
+        .await
+        .unwrap();
+
+    let violation = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let PeerManagerOutputMessage::ProtocolViolation(info, violation) = violation else {
+        panic!("it should be a protocol violation, but got: {violation:?}")
+    };
+
+    assert_eq!(info, peer_one_info);
+    assert_eq!(
+        violation,
+        ProtocolViolation::RequestWhileChoked {
+            piece_index: 0,
+            block_offset: 0,
+            block_length: 16
+        }
+    );
+
+    let removed = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        removed,
+        PeerManagerOutputMessage::PeerRemoved(info, DisconnectReason::ProtocolViolation(removed_violation))
+            if info == peer_one_info && removed_violation == violation
+    ));
+}
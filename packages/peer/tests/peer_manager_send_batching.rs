@@ -0,0 +1,130 @@
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use common::connected_channel::{connected_channel, ConnectedChannel};
+use common::{add_peer, tracing_stderr_init, DEFAULT_TIMEOUT, INIT};
+use futures::{Sink, SinkExt as _, Stream, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::{PeerWireProtocolMessage, RequestMessage};
+use peer::protocols::NullProtocol;
+use peer::{PeerInfo, PeerManagerBuilder, PeerManagerInputMessage, PeerManagerOutputMessage};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+type Message = PeerWireProtocolMessage<NullProtocol>;
+type Channel = ConnectedChannel<std::io::Result<Message>, std::io::Result<Message>>;
+
+const NUM_REQUESTS: usize = 20;
+
+/// Peer that counts how many times it was flushed.
+#[derive(Debug)]
+struct FlushCountingPeer {
+    channel: Channel,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl Sink<std::io::Result<Message>> for FlushCountingPeer {
+    type Error = std::io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.channel.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: std::io::Result<Message>) -> Result<(), Self::Error> {
+        self.channel.start_send_unpin(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let result = self.channel.poll_flush_unpin(cx);
+        if result.is_ready() {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.channel.poll_close_unpin(cx)
+    }
+}
+
+impl Stream for FlushCountingPeer {
+    type Item = std::io::Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.channel.poll_next_unpin(cx)
+    }
+}
+
+#[tokio::test]
+async fn positive_peer_manager_send_batching_flushes_once() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    assert_eq!(1, send_requests_and_count_flushes(NUM_REQUESTS).await);
+}
+
+#[tokio::test]
+async fn positive_peer_manager_send_unbatched_flushes_every_message() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    assert_eq!(NUM_REQUESTS, send_requests_and_count_flushes(1).await);
+}
+
+/// Queue a burst of requests for a peer, and count how many times the peer was flushed to send them.
+async fn send_requests_and_count_flushes(send_batch_size: usize) -> usize {
+    let (mut send, mut recv) = PeerManagerBuilder::new()
+        .with_send_batch_size(send_batch_size)
+        .build::<FlushCountingPeer, Message>()
+        .into_parts();
+
+    let (channel, mut remote): (Channel, Channel) = connected_channel(NUM_REQUESTS);
+    let flushes = Arc::new(AtomicUsize::new(0));
+    let peer = FlushCountingPeer {
+        channel,
+        flushes: flushes.clone(),
+    };
+    let info = PeerInfo::new(
+        "127.0.0.1:0".parse().unwrap(),
+        [0u8; bt::PEER_ID_LEN].into(),
+        [0u8; bt::INFO_HASH_LEN].into(),
+        Extensions::new(),
+    );
+
+    add_peer(&mut send, &mut recv, info, peer).await.unwrap();
+
+    for mid in 0..NUM_REQUESTS {
+        let request = PeerWireProtocolMessage::Request(RequestMessage::new(0, mid as u32 * 16 * 1024, 16 * 1024));
+        send.feed(Ok(PeerManagerInputMessage::SendMessage(info, mid as u64, request)))
+            .await
+            .unwrap();
+    }
+    send.flush().await.unwrap();
+
+    for mid in 0..NUM_REQUESTS {
+        let sent = tokio::time::timeout(DEFAULT_TIMEOUT, recv.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(sent, PeerManagerOutputMessage::SentMessage(_, id) if id == mid as u64));
+
+        let received = tokio::time::timeout(DEFAULT_TIMEOUT, remote.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(received, PeerWireProtocolMessage::Request(_)));
+    }
+
+    // Wait for the peer to go idle, at which point the written messages are flushed
+    tokio::time::sleep(DEFAULT_TIMEOUT / 5).await;
+
+    flushes.load(Ordering::SeqCst)
+}
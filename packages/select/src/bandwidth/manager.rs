@@ -0,0 +1,252 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::{Sink, Stream};
+use handshake::InfoHash;
+use metainfo::Metainfo;
+use peer::PeerInfo;
+use tracing::instrument;
+
+use crate::bandwidth::error::BandwidthError;
+use crate::bandwidth::{IBandwidthMessage, OBandwidthMessage};
+use crate::priority::{self, TorrentPriority};
+use crate::ControlMessage;
+
+/// Builder for configuring the rate limit of a `BandwidthModule`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BandwidthModuleBuilder {
+    rate_limit: Option<usize>,
+}
+
+impl BandwidthModuleBuilder {
+    #[must_use]
+    pub fn new() -> BandwidthModuleBuilder {
+        BandwidthModuleBuilder::default()
+    }
+
+    /// Bytes per second granted across all torrents, if limited.
+    ///
+    /// Up to a second worth of bytes that were not asked for is saved up for later requests.
+    ///
+    /// Defaults to `None`, which grants every request as soon as it is made.
+    #[must_use]
+    pub fn with_rate_limit(mut self, bytes_per_second: Option<usize>) -> BandwidthModuleBuilder {
+        self.rate_limit = bytes_per_second;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> BandwidthModule {
+        BandwidthModule::from_builder(self)
+    }
+}
+
+#[derive(Default)]
+struct TorrentState {
+    priority: TorrentPriority,
+    /// Requests waiting to be granted, oldest first, along with the bytes still to be granted.
+    requests: VecDeque<(PeerInfo, usize)>,
+}
+
+impl TorrentState {
+    fn pending_bytes(&self) -> usize {
+        self.requests.iter().map(|(_, bytes)| bytes).sum()
+    }
+}
+
+/// Module for limiting the rate of bytes transferred across all torrents.
+///
+/// Bytes become available on every tick, in proportion to the time that passed. While requests
+/// ask for more bytes than are available, the bytes are divided between the torrents by their
+/// `TorrentPriority`, and granted to the requests of each torrent in the order they were made.
+#[allow(clippy::module_name_repetitions)]
+pub struct BandwidthModule {
+    config: BandwidthModuleBuilder,
+    torrents: HashMap<InfoHash, TorrentState>,
+    available: usize,
+    out_queue: VecDeque<OBandwidthMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl BandwidthModule {
+    #[must_use]
+    pub fn from_builder(builder: BandwidthModuleBuilder) -> BandwidthModule {
+        BandwidthModule {
+            config: builder,
+            torrents: HashMap::new(),
+            available: 0,
+            out_queue: VecDeque::new(),
+            opt_stream_waker: None,
+        }
+    }
+
+    fn handle_message(&mut self, message: IBandwidthMessage) -> Result<(), BandwidthError> {
+        match message {
+            IBandwidthMessage::Control(control) => match *control {
+                ControlMessage::AddTorrent(metainfo) => self.add_torrent(&metainfo),
+                ControlMessage::RemoveTorrent(metainfo) => self.remove_torrent(&metainfo),
+                ControlMessage::PeerConnected(_) => Ok(()),
+                ControlMessage::PeerDisconnected(info) => {
+                    self.remove_peer(info);
+                    Ok(())
+                }
+                ControlMessage::Tick(duration) => {
+                    self.tick(duration);
+                    Ok(())
+                }
+            },
+            IBandwidthMessage::SetPriority(hash, priority) => self.set_priority(hash, priority),
+            IBandwidthMessage::Request(info, bytes) => self.request(info, bytes),
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), BandwidthError> {
+        let info_hash = metainfo.info().info_hash();
+
+        match self.torrents.entry(info_hash) {
+            Entry::Occupied(_) => Err(BandwidthError::InvalidMetainfoExists { hash: info_hash }),
+            Entry::Vacant(vac) => {
+                vac.insert(TorrentState::default());
+
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn remove_torrent(&mut self, metainfo: &Metainfo) -> Result<(), BandwidthError> {
+        let info_hash = metainfo.info().info_hash();
+
+        if self.torrents.remove(&info_hash).is_none() {
+            Err(BandwidthError::InvalidMetainfoNotExists { hash: info_hash })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn remove_peer(&mut self, peer: PeerInfo) {
+        if let Some(torrent) = self.torrents.get_mut(peer.hash()) {
+            torrent.requests.retain(|(info, _)| *info != peer);
+        }
+    }
+
+    fn set_priority(&mut self, hash: InfoHash, priority: TorrentPriority) -> Result<(), BandwidthError> {
+        let Some(torrent) = self.torrents.get_mut(&hash) else {
+            return Err(BandwidthError::InvalidMetainfoNotExists { hash });
+        };
+
+        torrent.priority = priority;
+
+        Ok(())
+    }
+
+    fn request(&mut self, peer: PeerInfo, bytes: usize) -> Result<(), BandwidthError> {
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(BandwidthError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        if self.config.rate_limit.is_none() {
+            self.queue_message(OBandwidthMessage::Grant(peer, bytes));
+        } else {
+            torrent.requests.push_back((peer, bytes));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn tick(&mut self, duration: Duration) {
+        let Some(rate_limit) = self.config.rate_limit else {
+            return;
+        };
+
+        let refill = usize::try_from(duration.as_millis().saturating_mul(rate_limit as u128) / 1000).unwrap_or(usize::MAX);
+        self.available = self.available.saturating_add(refill);
+
+        let (hashes, demands): (Vec<InfoHash>, Vec<(TorrentPriority, usize)>) = self
+            .torrents
+            .iter()
+            .map(|(hash, torrent)| (*hash, (torrent.priority, torrent.pending_bytes())))
+            .unzip();
+        let shares: HashMap<InfoHash, usize> = hashes
+            .into_iter()
+            .zip(priority::weighted_shares(self.available, &demands))
+            .collect();
+
+        let mut messages = Vec::new();
+        for (hash, torrent) in &mut self.torrents {
+            let mut share = shares[hash];
+            self.available -= share;
+
+            while let Some((peer, remaining)) = torrent.requests.front_mut().filter(|_| share != 0) {
+                let granted = share.min(*remaining);
+                messages.push(OBandwidthMessage::Grant(*peer, granted));
+                share -= granted;
+                *remaining -= granted;
+
+                if *remaining == 0 {
+                    torrent.requests.pop_front();
+                }
+            }
+        }
+        // Bytes that were not asked for are only saved up to a second worth
+        self.available = self.available.min(rate_limit);
+
+        for message in messages {
+            self.queue_message(message);
+        }
+    }
+
+    fn queue_message(&mut self, message: OBandwidthMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<OBandwidthMessage, BandwidthError>>> {
+        if let Some(message) = self.out_queue.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Sink<IBandwidthMessage> for BandwidthModule {
+    type Error = BandwidthError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IBandwidthMessage) -> Result<(), Self::Error> {
+        self.handle_message(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Stream for BandwidthModule {
+    type Item = Result<OBandwidthMessage, BandwidthError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_next_message(cx)
+    }
+}
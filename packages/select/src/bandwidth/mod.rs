@@ -0,0 +1,32 @@
+//! Module for limiting bandwidth across torrents.
+
+use handshake::InfoHash;
+use peer::PeerInfo;
+
+use crate::{ControlMessage, TorrentPriority};
+
+pub mod error;
+
+mod manager;
+
+pub use self::manager::{BandwidthModule, BandwidthModuleBuilder};
+
+/// Enumeration of bandwidth messages that can be sent to a bandwidth module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IBandwidthMessage {
+    /// Control message.
+    Control(Box<ControlMessage>),
+    /// Use the given `TorrentPriority` for the torrent with the given `InfoHash`.
+    SetPriority(InfoHash, TorrentPriority),
+    /// Ask for the given number of bytes to be transferred with the peer.
+    Request(PeerInfo, usize),
+}
+
+/// Enumeration of bandwidth messages that can be received from a bandwidth module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OBandwidthMessage {
+    /// The given number of bytes may be transferred with the peer.
+    ///
+    /// A request may be granted in several parts, which add up to the bytes requested.
+    Grant(PeerInfo, usize),
+}
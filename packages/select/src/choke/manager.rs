@@ -0,0 +1,433 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::{Sink, Stream};
+use handshake::InfoHash;
+use metainfo::Metainfo;
+use peer::PeerInfo;
+use tracing::instrument;
+
+use crate::choke::error::ChokeError;
+use crate::choke::{ChokeAlgorithm, IChokeMessage, OChokeMessage};
+use crate::priority::{self, TorrentPriority};
+use crate::ControlMessage;
+
+const DEFAULT_UPLOAD_SLOTS: usize = 4;
+const DEFAULT_RECHOKE_INTERVAL_SECS: u64 = 10;
+const DEFAULT_ROTATION_INTERVAL_SECS: u64 = 30;
+
+/// Builder for configuring the upload slots of a `ChokeModule`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug)]
+pub struct ChokeModuleBuilder {
+    algorithm: ChokeAlgorithm,
+    upload_slots: usize,
+    global_upload_slots: Option<usize>,
+    rechoke_interval: Duration,
+    rotation_interval: Duration,
+}
+
+impl Default for ChokeModuleBuilder {
+    fn default() -> Self {
+        ChokeModuleBuilder {
+            algorithm: ChokeAlgorithm::default(),
+            upload_slots: DEFAULT_UPLOAD_SLOTS,
+            global_upload_slots: None,
+            rechoke_interval: Duration::from_secs(DEFAULT_RECHOKE_INTERVAL_SECS),
+            rotation_interval: Duration::from_secs(DEFAULT_ROTATION_INTERVAL_SECS),
+        }
+    }
+}
+
+impl ChokeModuleBuilder {
+    #[must_use]
+    pub fn new() -> ChokeModuleBuilder {
+        ChokeModuleBuilder::default()
+    }
+
+    /// Algorithm used for newly added torrents, until changed with `IChokeMessage::SetAlgorithm`.
+    #[must_use]
+    pub fn with_algorithm(mut self, algorithm: ChokeAlgorithm) -> ChokeModuleBuilder {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Number of peers unchoked at once per torrent, including the rotating slot.
+    #[must_use]
+    pub fn with_upload_slots(mut self, slots: usize) -> ChokeModuleBuilder {
+        self.upload_slots = slots;
+        self
+    }
+
+    /// Number of peers unchoked at once across all torrents, if capped.
+    ///
+    /// While the torrents want more slots than the cap, the slots are divided between them by
+    /// their `TorrentPriority`, set with `IChokeMessage::SetPriority`. No torrent gets more than
+    /// its upload slots.
+    ///
+    /// Defaults to `None`, which leaves every torrent with its upload slots.
+    #[must_use]
+    pub fn with_global_upload_slots(mut self, slots: Option<usize>) -> ChokeModuleBuilder {
+        self.global_upload_slots = slots;
+        self
+    }
+
+    /// Time between re-ranking the peers of each torrent by their transfer rate.
+    #[must_use]
+    pub fn with_rechoke_interval(mut self, interval: Duration) -> ChokeModuleBuilder {
+        self.rechoke_interval = interval;
+        self
+    }
+
+    /// Minimum time a peer keeps the rotating slot before it moves on to the next peer.
+    #[must_use]
+    pub fn with_rotation_interval(mut self, interval: Duration) -> ChokeModuleBuilder {
+        self.rotation_interval = interval;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> ChokeModule {
+        ChokeModule::from_builder(self)
+    }
+}
+
+#[derive(Default)]
+struct PeerState {
+    interested: bool,
+    unchoked: bool,
+    received: usize,
+    sent: usize,
+}
+
+struct TorrentState {
+    algorithm: ChokeAlgorithm,
+    priority: TorrentPriority,
+    peers: HashMap<PeerInfo, PeerState>,
+    /// Order in which peers are given the rotating slot, next peer first.
+    rotation: VecDeque<PeerInfo>,
+    opt_rotating: Option<PeerInfo>,
+    since_rotation: Option<Duration>,
+}
+
+impl TorrentState {
+    fn new(algorithm: ChokeAlgorithm) -> TorrentState {
+        TorrentState {
+            algorithm,
+            priority: TorrentPriority::default(),
+            peers: HashMap::new(),
+            rotation: VecDeque::new(),
+            opt_rotating: None,
+            since_rotation: None,
+        }
+    }
+}
+
+/// Module for choosing which peers are unchoked, per torrent.
+///
+/// Peers are re-ranked every rechoke interval, by the bytes transferred since the previous
+/// rechoke, according to the `ChokeAlgorithm` of their torrent.
+#[allow(clippy::module_name_repetitions)]
+pub struct ChokeModule {
+    config: ChokeModuleBuilder,
+    torrents: HashMap<InfoHash, TorrentState>,
+    since_rechoke: Duration,
+    out_queue: VecDeque<OChokeMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl ChokeModule {
+    #[must_use]
+    pub fn from_builder(builder: ChokeModuleBuilder) -> ChokeModule {
+        ChokeModule {
+            config: builder,
+            torrents: HashMap::new(),
+            since_rechoke: Duration::ZERO,
+            out_queue: VecDeque::new(),
+            opt_stream_waker: None,
+        }
+    }
+
+    fn handle_message(&mut self, message: IChokeMessage) -> Result<(), ChokeError> {
+        match message {
+            IChokeMessage::Control(control) => match *control {
+                ControlMessage::AddTorrent(metainfo) => self.add_torrent(&metainfo),
+                ControlMessage::RemoveTorrent(metainfo) => self.remove_torrent(&metainfo),
+                ControlMessage::PeerConnected(info) => self.add_peer(info),
+                ControlMessage::PeerDisconnected(info) => self.remove_peer(info),
+                ControlMessage::Tick(duration) => {
+                    self.tick(duration);
+                    Ok(())
+                }
+            },
+            IChokeMessage::SetAlgorithm(hash, algorithm) => self.set_algorithm(hash, algorithm),
+            IChokeMessage::SetPriority(hash, priority) => self.set_priority(hash, priority),
+            IChokeMessage::PeerInterested(info, interested) => {
+                self.update_peer(info, |peer| peer.interested = interested);
+                Ok(())
+            }
+            IChokeMessage::ReceivedBlock(info, length) => {
+                self.update_peer(info, |peer| peer.received += length);
+                Ok(())
+            }
+            IChokeMessage::SentBlock(info, length) => {
+                self.update_peer(info, |peer| peer.sent += length);
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), ChokeError> {
+        let info_hash = metainfo.info().info_hash();
+
+        match self.torrents.entry(info_hash) {
+            Entry::Occupied(_) => Err(ChokeError::InvalidMetainfoExists { hash: info_hash }),
+            Entry::Vacant(vac) => {
+                vac.insert(TorrentState::new(self.config.algorithm));
+
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn remove_torrent(&mut self, metainfo: &Metainfo) -> Result<(), ChokeError> {
+        let info_hash = metainfo.info().info_hash();
+
+        if self.torrents.remove(&info_hash).is_none() {
+            Err(ChokeError::InvalidMetainfoNotExists { hash: info_hash })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_peer(&mut self, peer: PeerInfo) -> Result<(), ChokeError> {
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(ChokeError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        // Peer connected may be sent multiple times, keep any existing state
+        if let Entry::Vacant(vac) = torrent.peers.entry(peer) {
+            vac.insert(PeerState::default());
+            torrent.rotation.push_back(peer);
+        }
+
+        Ok(())
+    }
+
+    fn remove_peer(&mut self, peer: PeerInfo) -> Result<(), ChokeError> {
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(ChokeError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        torrent.peers.remove(&peer);
+        torrent.rotation.retain(|info| *info != peer);
+        if torrent.opt_rotating == Some(peer) {
+            torrent.opt_rotating = None;
+        }
+
+        Ok(())
+    }
+
+    fn set_algorithm(&mut self, hash: InfoHash, algorithm: ChokeAlgorithm) -> Result<(), ChokeError> {
+        let Some(torrent) = self.torrents.get_mut(&hash) else {
+            return Err(ChokeError::InvalidMetainfoNotExists { hash });
+        };
+
+        torrent.algorithm = algorithm;
+
+        Ok(())
+    }
+
+    fn set_priority(&mut self, hash: InfoHash, priority: TorrentPriority) -> Result<(), ChokeError> {
+        let Some(torrent) = self.torrents.get_mut(&hash) else {
+            return Err(ChokeError::InvalidMetainfoNotExists { hash });
+        };
+
+        torrent.priority = priority;
+
+        Ok(())
+    }
+
+    fn update_peer<F>(&mut self, info: PeerInfo, update: F)
+    where
+        F: FnOnce(&mut PeerState),
+    {
+        if let Some(peer) = self
+            .torrents
+            .get_mut(info.hash())
+            .and_then(|torrent| torrent.peers.get_mut(&info))
+        {
+            update(peer);
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn tick(&mut self, duration: Duration) {
+        self.since_rechoke += duration;
+        for torrent in self.torrents.values_mut() {
+            torrent.since_rotation = torrent.since_rotation.map(|since| since + duration);
+        }
+
+        if self.since_rechoke < self.config.rechoke_interval {
+            return;
+        }
+        self.since_rechoke = Duration::ZERO;
+
+        let slots = self.torrent_slots();
+
+        let mut messages = Vec::new();
+        for (hash, torrent) in &mut self.torrents {
+            messages.extend(rechoke(&self.config, slots[hash], torrent));
+        }
+
+        for message in messages {
+            self.queue_message(message);
+        }
+    }
+
+    /// Number of upload slots each torrent gets, dividing any global cap by priority.
+    fn torrent_slots(&self) -> HashMap<InfoHash, usize> {
+        let upload_slots = self.config.upload_slots;
+        let Some(global_slots) = self.config.global_upload_slots else {
+            return self.torrents.keys().map(|hash| (*hash, upload_slots)).collect();
+        };
+
+        let (hashes, demands): (Vec<InfoHash>, Vec<(TorrentPriority, usize)>) = self
+            .torrents
+            .iter()
+            .map(|(hash, torrent)| {
+                let interested = torrent.peers.values().filter(|peer| peer.interested).count();
+
+                (*hash, (torrent.priority, interested.min(upload_slots)))
+            })
+            .unzip();
+
+        hashes
+            .into_iter()
+            .zip(priority::weighted_shares(global_slots, &demands))
+            .collect()
+    }
+
+    fn queue_message(&mut self, message: OChokeMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<OChokeMessage, ChokeError>>> {
+        if let Some(message) = self.out_queue.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Bytes transferred with the peer since the last rechoke that the algorithm ranks peers by.
+fn ranked_bytes(algorithm: ChokeAlgorithm, peer: &PeerState) -> usize {
+    match algorithm {
+        ChokeAlgorithm::TitForTat => peer.received,
+        ChokeAlgorithm::FastestUpload => peer.sent,
+    }
+}
+
+/// Choose the peers of the torrent to unchoke within its upload slots, returning the messages for peers whose state changed.
+#[allow(clippy::unnecessary_map_or)] // `Option::is_none_or` is newer than our minimum supported rust version
+fn rechoke(config: &ChokeModuleBuilder, upload_slots: usize, torrent: &mut TorrentState) -> Vec<OChokeMessage> {
+    // Ties go to peers that are already unchoked, so that slots are not needlessly swapped
+    let mut ranked: Vec<(usize, bool, PeerInfo)> = torrent
+        .peers
+        .iter()
+        .filter(|(_, peer)| peer.interested)
+        .map(|(info, peer)| (ranked_bytes(torrent.algorithm, peer), peer.unchoked, *info))
+        .collect();
+    ranked.sort_by_key(|(bytes, unchoked, info)| (Reverse(*bytes), !*unchoked, *info.addr()));
+
+    let regular_slots = upload_slots.saturating_sub(1);
+    let mut unchoke: HashSet<PeerInfo> = ranked.iter().take(regular_slots).map(|(_, _, info)| *info).collect();
+
+    if upload_slots != 0 {
+        let rotation_due = torrent.since_rotation.map_or(true, |since| since >= config.rotation_interval);
+        let is_eligible =
+            |info: &PeerInfo| torrent.peers.get(info).is_some_and(|peer| peer.interested) && !unchoke.contains(info);
+
+        let keep_rotating = !rotation_due && torrent.opt_rotating.as_ref().is_some_and(is_eligible);
+        if !keep_rotating {
+            torrent.opt_rotating = torrent.rotation.iter().position(is_eligible).and_then(|index| {
+                let info = torrent.rotation.remove(index)?;
+                torrent.rotation.push_back(info);
+
+                Some(info)
+            });
+            torrent.since_rotation = Some(Duration::ZERO);
+        }
+
+        unchoke.extend(torrent.opt_rotating);
+    }
+
+    let mut choked = Vec::new();
+    let mut unchoked = Vec::new();
+    for (info, peer) in &mut torrent.peers {
+        let should_unchoke = unchoke.contains(info);
+
+        if should_unchoke && !peer.unchoked {
+            unchoked.push(*info);
+        } else if !should_unchoke && peer.unchoked {
+            choked.push(*info);
+        }
+
+        peer.unchoked = should_unchoke;
+        peer.received = 0;
+        peer.sent = 0;
+    }
+    choked.sort_by_key(|info| *info.addr());
+    unchoked.sort_by_key(|info| *info.addr());
+
+    choked
+        .into_iter()
+        .map(OChokeMessage::Choke)
+        .chain(unchoked.into_iter().map(OChokeMessage::Unchoke))
+        .collect()
+}
+
+impl Sink<IChokeMessage> for ChokeModule {
+    type Error = ChokeError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IChokeMessage) -> Result<(), Self::Error> {
+        self.handle_message(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Stream for ChokeModule {
+    type Item = Result<OChokeMessage, ChokeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_next_message(cx)
+    }
+}
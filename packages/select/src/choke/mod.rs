@@ -0,0 +1,56 @@
+//! Module for upload choking.
+
+use handshake::InfoHash;
+use peer::PeerInfo;
+
+use crate::{ControlMessage, TorrentPriority};
+
+pub mod error;
+
+mod manager;
+
+pub use self::manager::{ChokeModule, ChokeModuleBuilder};
+
+/// Enumeration of choke messages that can be sent to a choke module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IChokeMessage {
+    /// Control message.
+    Control(Box<ControlMessage>),
+    /// Use the given `ChokeAlgorithm` for the torrent with the given `InfoHash`.
+    SetAlgorithm(InfoHash, ChokeAlgorithm),
+    /// Use the given `TorrentPriority` for the torrent with the given `InfoHash`, see `ChokeModuleBuilder::with_global_upload_slots`.
+    SetPriority(InfoHash, TorrentPriority),
+    /// The peer is (or is no longer) interested in pieces we have.
+    PeerInterested(PeerInfo, bool),
+    /// Received a block of the given length from the peer.
+    ReceivedBlock(PeerInfo, usize),
+    /// Sent a block of the given length to the peer.
+    SentBlock(PeerInfo, usize),
+}
+
+/// Enumeration of choke messages that can be received from a choke module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OChokeMessage {
+    /// Choke the given peer.
+    Choke(PeerInfo),
+    /// Unchoke the given peer.
+    Unchoke(PeerInfo),
+}
+
+/// Algorithm used to decide which interested peers are unchoked.
+///
+/// With either algorithm, all but one upload slot go to the best ranked peers, while the
+/// last slot rotates between the remaining peers so that better peers can be discovered.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChokeAlgorithm {
+    /// Rank peers by the rate they upload to us, reciprocating the fastest.
+    ///
+    /// Suited to leeching, where peers that upload to us are rewarded.
+    #[default]
+    TitForTat,
+    /// Rank peers by the rate they download from us, serving the fastest first.
+    ///
+    /// Suited to seeding, where peers do not upload to us, so our upload capacity is
+    /// best spent on the peers that can take it.
+    FastestUpload,
+}
@@ -0,0 +1,13 @@
+//! Module for connection error types.
+
+use handshake::InfoHash;
+use thiserror::Error;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug)]
+pub enum ConnectionError {
+    #[error("Metainfo With Hash {hash:?} Has Already Been Added")]
+    InvalidMetainfoExists { hash: InfoHash },
+    #[error("Metainfo With Hash {hash:?} Was Not Already Added")]
+    InvalidMetainfoNotExists { hash: InfoHash },
+}
@@ -0,0 +1,456 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::{Sink, Stream};
+use handshake::InfoHash;
+use metainfo::Metainfo;
+use peer::PeerInfo;
+use tracing::instrument;
+
+use crate::connection::error::ConnectionError;
+use crate::connection::{IConnectionMessage, OConnectionMessage};
+use crate::ControlMessage;
+
+const DEFAULT_TORRENT_TARGET_PEERS: usize = 40;
+const DEFAULT_TORRENT_MAX_PEERS: usize = 60;
+const DEFAULT_GLOBAL_TARGET_PEERS: usize = 200;
+const DEFAULT_GLOBAL_MAX_PEERS: usize = 300;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_SNUB_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_REQUEST_INTERVAL_SECS: u64 = 30;
+
+/// Builder for configuring the peer count targets of a `ConnectionModule`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug)]
+pub struct ConnectionModuleBuilder {
+    torrent_target: usize,
+    torrent_max: usize,
+    global_target: usize,
+    global_max: usize,
+    idle_timeout: Duration,
+    snub_timeout: Duration,
+    request_interval: Duration,
+}
+
+impl Default for ConnectionModuleBuilder {
+    fn default() -> Self {
+        ConnectionModuleBuilder {
+            torrent_target: DEFAULT_TORRENT_TARGET_PEERS,
+            torrent_max: DEFAULT_TORRENT_MAX_PEERS,
+            global_target: DEFAULT_GLOBAL_TARGET_PEERS,
+            global_max: DEFAULT_GLOBAL_MAX_PEERS,
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            snub_timeout: Duration::from_secs(DEFAULT_SNUB_TIMEOUT_SECS),
+            request_interval: Duration::from_secs(DEFAULT_REQUEST_INTERVAL_SECS),
+        }
+    }
+}
+
+impl ConnectionModuleBuilder {
+    #[must_use]
+    pub fn new() -> ConnectionModuleBuilder {
+        ConnectionModuleBuilder::default()
+    }
+
+    /// Number of peers we want connected per torrent.
+    ///
+    /// Below this, more peers are requested from discovery sources; above this, idle,
+    /// snubbed, and seed-to-seed connections are pruned.
+    #[must_use]
+    pub fn with_torrent_target_peers(mut self, target: usize) -> ConnectionModuleBuilder {
+        self.torrent_target = target;
+        self
+    }
+
+    /// Number of peers allowed per torrent before even useful connections are pruned.
+    #[must_use]
+    pub fn with_torrent_max_peers(mut self, max: usize) -> ConnectionModuleBuilder {
+        self.torrent_max = max;
+        self
+    }
+
+    /// Number of peers we want connected across all torrents.
+    #[must_use]
+    pub fn with_global_target_peers(mut self, target: usize) -> ConnectionModuleBuilder {
+        self.global_target = target;
+        self
+    }
+
+    /// Number of peers allowed across all torrents before even useful connections are pruned.
+    #[must_use]
+    pub fn with_global_max_peers(mut self, max: usize) -> ConnectionModuleBuilder {
+        self.global_max = max;
+        self
+    }
+
+    /// Time without sending or receiving a block after which a peer is considered idle.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> ConnectionModuleBuilder {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Time without receiving a block after which a peer is considered to be snubbing us.
+    #[must_use]
+    pub fn with_snub_timeout(mut self, timeout: Duration) -> ConnectionModuleBuilder {
+        self.snub_timeout = timeout;
+        self
+    }
+
+    /// Minimum time between requests for more peers for the same torrent.
+    #[must_use]
+    pub fn with_request_interval(mut self, interval: Duration) -> ConnectionModuleBuilder {
+        self.request_interval = interval;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> ConnectionModule {
+        ConnectionModule::from_builder(self)
+    }
+}
+
+/// Ordering of how useful a connection is to us, least useful first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Usefulness {
+    /// We are seeding and so is the peer.
+    SeedToSeed,
+    /// Peer has not sent us a block in a while.
+    Snubbed,
+    /// No blocks have been exchanged in a while.
+    Idle,
+    Active,
+}
+
+#[derive(Default)]
+struct PeerState {
+    seeding: bool,
+    since_received: Duration,
+    since_activity: Duration,
+}
+
+#[derive(Default)]
+struct TorrentState {
+    seeding: bool,
+    peers: HashMap<PeerInfo, PeerState>,
+    since_request: Option<Duration>,
+}
+
+/// Module for keeping the number of connected peers between targets, per torrent and globally.
+#[allow(clippy::module_name_repetitions)]
+pub struct ConnectionModule {
+    config: ConnectionModuleBuilder,
+    torrents: HashMap<InfoHash, TorrentState>,
+    out_queue: VecDeque<OConnectionMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl ConnectionModule {
+    #[must_use]
+    pub fn from_builder(builder: ConnectionModuleBuilder) -> ConnectionModule {
+        ConnectionModule {
+            config: builder,
+            torrents: HashMap::new(),
+            out_queue: VecDeque::new(),
+            opt_stream_waker: None,
+        }
+    }
+
+    fn handle_message(&mut self, message: IConnectionMessage) -> Result<(), ConnectionError> {
+        match message {
+            IConnectionMessage::Control(control) => match *control {
+                ControlMessage::AddTorrent(metainfo) => self.add_torrent(&metainfo),
+                ControlMessage::RemoveTorrent(metainfo) => self.remove_torrent(&metainfo),
+                ControlMessage::PeerConnected(info) => self.add_peer(info),
+                ControlMessage::PeerDisconnected(info) => self.remove_peer(info),
+                ControlMessage::Tick(duration) => {
+                    self.tick(duration);
+                    Ok(())
+                }
+            },
+            IConnectionMessage::SetSeeding(hash, seeding) => self.set_seeding(hash, seeding),
+            IConnectionMessage::PeerSeeding(info) => {
+                self.update_peer(info, |peer| peer.seeding = true);
+                Ok(())
+            }
+            IConnectionMessage::ReceivedBlock(info, _) => {
+                let snub_timeout = self.config.snub_timeout;
+
+                self.update_peer(info, |peer| {
+                    if peer.since_received >= snub_timeout {
+                        info.span()
+                            .in_scope(|| tracing::debug!(state = "unsnubbed", "peer state changed"));
+                    }
+
+                    peer.since_received = Duration::ZERO;
+                    peer.since_activity = Duration::ZERO;
+                });
+                Ok(())
+            }
+            IConnectionMessage::SentBlock(info, _) => {
+                self.update_peer(info, |peer| peer.since_activity = Duration::ZERO);
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), ConnectionError> {
+        let info_hash = metainfo.info().info_hash();
+
+        match self.torrents.entry(info_hash) {
+            Entry::Occupied(_) => Err(ConnectionError::InvalidMetainfoExists { hash: info_hash }),
+            Entry::Vacant(vac) => {
+                vac.insert(TorrentState::default());
+
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn remove_torrent(&mut self, metainfo: &Metainfo) -> Result<(), ConnectionError> {
+        let info_hash = metainfo.info().info_hash();
+
+        if self.torrents.remove(&info_hash).is_none() {
+            Err(ConnectionError::InvalidMetainfoNotExists { hash: info_hash })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_peer(&mut self, peer: PeerInfo) -> Result<(), ConnectionError> {
+        let _entered = peer.span().entered();
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(ConnectionError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        // Peer connected may be sent multiple times, keep any existing state
+        torrent.peers.entry(peer).or_default();
+        tracing::debug!("peer connected");
+
+        Ok(())
+    }
+
+    fn remove_peer(&mut self, peer: PeerInfo) -> Result<(), ConnectionError> {
+        let _entered = peer.span().entered();
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(ConnectionError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        // Peers we pruned have already been removed
+        torrent.peers.remove(&peer);
+        tracing::debug!("peer disconnected");
+
+        Ok(())
+    }
+
+    fn set_seeding(&mut self, hash: InfoHash, seeding: bool) -> Result<(), ConnectionError> {
+        let Some(torrent) = self.torrents.get_mut(&hash) else {
+            return Err(ConnectionError::InvalidMetainfoNotExists { hash });
+        };
+
+        torrent.seeding = seeding;
+
+        Ok(())
+    }
+
+    fn update_peer<F>(&mut self, info: PeerInfo, update: F)
+    where
+        F: FnOnce(&mut PeerState),
+    {
+        if let Some(peer) = self
+            .torrents
+            .get_mut(info.hash())
+            .and_then(|torrent| torrent.peers.get_mut(&info))
+        {
+            update(peer);
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn tick(&mut self, duration: Duration) {
+        for torrent in self.torrents.values_mut() {
+            torrent.since_request = torrent.since_request.map(|since| since + duration);
+
+            for (info, peer) in &mut torrent.peers {
+                let was_snubbing = peer.since_received >= self.config.snub_timeout;
+
+                peer.since_received += duration;
+                peer.since_activity += duration;
+
+                if !torrent.seeding && !was_snubbing && peer.since_received >= self.config.snub_timeout {
+                    info.span()
+                        .in_scope(|| tracing::debug!(state = "snubbed", "peer state changed"));
+                }
+            }
+        }
+
+        // Prune each torrent down to its own targets first, then across all torrents
+        let mut pruned = Vec::new();
+        for torrent in self.torrents.values() {
+            let candidates = self.ranked_peers(std::iter::once(torrent));
+
+            pruned.extend(select_pruned(
+                candidates,
+                torrent.peers.len(),
+                self.config.torrent_target,
+                self.config.torrent_max,
+            ));
+        }
+        self.disconnect_peers(&pruned);
+
+        let total_peers = self.total_peers();
+        let candidates = self.ranked_peers(self.torrents.values());
+        let pruned = select_pruned(candidates, total_peers, self.config.global_target, self.config.global_max);
+        self.disconnect_peers(&pruned);
+
+        self.request_peers();
+    }
+
+    /// All peers of the given torrents, ordered from least to most useful.
+    fn ranked_peers<'a, I>(&self, torrents: I) -> Vec<(Usefulness, PeerInfo)>
+    where
+        I: Iterator<Item = &'a TorrentState>,
+    {
+        let mut ranked: Vec<(Usefulness, Duration, PeerInfo)> = torrents
+            .flat_map(|torrent| {
+                torrent
+                    .peers
+                    .iter()
+                    .map(move |(info, peer)| (self.usefulness(torrent, peer), peer.since_activity, *info))
+            })
+            .collect();
+
+        ranked.sort_by_key(|(usefulness, since_activity, _)| (*usefulness, Reverse(*since_activity)));
+
+        ranked.into_iter().map(|(usefulness, _, info)| (usefulness, info)).collect()
+    }
+
+    fn usefulness(&self, torrent: &TorrentState, peer: &PeerState) -> Usefulness {
+        if torrent.seeding && peer.seeding {
+            Usefulness::SeedToSeed
+        } else if !torrent.seeding && peer.since_received >= self.config.snub_timeout {
+            Usefulness::Snubbed
+        } else if peer.since_activity >= self.config.idle_timeout {
+            Usefulness::Idle
+        } else {
+            Usefulness::Active
+        }
+    }
+
+    fn disconnect_peers(&mut self, peers: &[PeerInfo]) {
+        for info in peers {
+            if let Some(torrent) = self.torrents.get_mut(info.hash()) {
+                torrent.peers.remove(info);
+            }
+            info.span().in_scope(|| tracing::debug!("peer pruned"));
+
+            self.queue_message(OConnectionMessage::DisconnectPeer(*info));
+        }
+    }
+
+    /// Request more peers for torrents under their target, without going over the global target.
+    fn request_peers(&mut self) {
+        let mut total_peers = self.total_peers();
+        let mut requests = Vec::new();
+
+        for (hash, torrent) in &mut self.torrents {
+            let interval_pending = torrent
+                .since_request
+                .is_some_and(|since| since < self.config.request_interval);
+            let wanted = self
+                .config
+                .torrent_target
+                .saturating_sub(torrent.peers.len())
+                .min(self.config.global_target.saturating_sub(total_peers));
+
+            if !interval_pending && wanted != 0 {
+                torrent.since_request = Some(Duration::ZERO);
+                total_peers += wanted;
+
+                requests.push(OConnectionMessage::RequestPeers(*hash, wanted));
+            }
+        }
+
+        for message in requests {
+            self.queue_message(message);
+        }
+    }
+
+    fn total_peers(&self) -> usize {
+        self.torrents.values().map(|torrent| torrent.peers.len()).sum()
+    }
+
+    fn queue_message(&mut self, message: OConnectionMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<OConnectionMessage, ConnectionError>>> {
+        if let Some(message) = self.out_queue.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Select which of the ranked peers should be pruned.
+///
+/// Peers that are not active are pruned while over the target, any peer is pruned while over the max.
+fn select_pruned(ranked: Vec<(Usefulness, PeerInfo)>, count: usize, target: usize, max: usize) -> Vec<PeerInfo> {
+    let mut remaining = count;
+
+    ranked
+        .into_iter()
+        .take_while(|(usefulness, _)| {
+            let prune = remaining > max || (remaining > target && *usefulness != Usefulness::Active);
+            remaining -= usize::from(prune);
+
+            prune
+        })
+        .map(|(_, info)| info)
+        .collect()
+}
+
+impl Sink<IConnectionMessage> for ConnectionModule {
+    type Error = ConnectionError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IConnectionMessage) -> Result<(), Self::Error> {
+        self.handle_message(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Stream for ConnectionModule {
+    type Item = Result<OConnectionMessage, ConnectionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_next_message(cx)
+    }
+}
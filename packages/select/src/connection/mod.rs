@@ -0,0 +1,36 @@
+//! Module for connection management.
+
+use handshake::InfoHash;
+use peer::PeerInfo;
+
+use crate::ControlMessage;
+
+pub mod error;
+
+mod manager;
+
+pub use self::manager::{ConnectionModule, ConnectionModuleBuilder};
+
+/// Enumeration of connection messages that can be sent to a connection module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IConnectionMessage {
+    /// Control message.
+    Control(Box<ControlMessage>),
+    /// We are (or are no longer) seeding the torrent for the given `InfoHash`.
+    SetSeeding(InfoHash, bool),
+    /// The peer has all pieces for its torrent.
+    PeerSeeding(PeerInfo),
+    /// Received a block of the given length from the peer.
+    ReceivedBlock(PeerInfo, usize),
+    /// Sent a block of the given length to the peer.
+    SentBlock(PeerInfo, usize),
+}
+
+/// Enumeration of connection messages that can be received from a connection module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OConnectionMessage {
+    /// Disconnect from the given peer, it is the least useful connection.
+    DisconnectPeer(PeerInfo),
+    /// Request (about) the given number of additional peers for the `InfoHash` from discovery sources.
+    RequestPeers(InfoHash, usize),
+}
@@ -1,3 +1 @@
-struct DhtModule {
-    
-}
\ No newline at end of file
+struct DhtModule {}
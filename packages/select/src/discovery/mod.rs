@@ -2,9 +2,9 @@
 
 use std::net::SocketAddr;
 
-use handshake::InfoHash;
+use handshake::{AttemptEvent, AttemptFailure, AttemptStage, InfoHash};
 use metainfo::Metainfo;
-use peer::messages::UtMetadataMessage;
+use peer::messages::{UtHolepunchMessage, UtMetadataMessage};
 use peer::PeerInfo;
 use utracker::announce::ClientState;
 
@@ -12,8 +12,14 @@ use crate::ControlMessage;
 
 pub mod error;
 
+mod tiers;
+mod udp_tracker;
+mod ut_holepunch;
 mod ut_metadata;
 
+pub use self::tiers::TrackerTiers;
+pub use self::udp_tracker::UdpTrackerModule;
+pub use self::ut_holepunch::UtHolepunchModule;
 pub use self::ut_metadata::UtMetadataModule;
 
 /// Enumeration of discovery messages that can be sent to a discovery module.
@@ -25,6 +31,33 @@ pub enum IDiscoveryMessage {
     DownloadMetainfo(InfoHash),
     /// Received a `UtMetadata` message.
     ReceivedUtMetadataMessage(PeerInfo, UtMetadataMessage),
+    /// Add the resolved udp tracker tiers of the announce list for the `InfoHash`.
+    AddUdpTrackers(InfoHash, Vec<Vec<SocketAddr>>),
+    /// Our `ClientState` for the `InfoHash` changed, and should be announced.
+    UpdateClientState(InfoHash, ClientState),
+    /// Received an announce response from the udp tracker.
+    ReceivedUdpTrackerResponse(InfoHash, SocketAddr),
+    /// Announce to the udp tracker failed or timed out.
+    FailedUdpTrackerAnnounce(InfoHash, SocketAddr),
+    /// Received a `UtHolepunch` message.
+    ReceivedUtHolepunchMessage(PeerInfo, UtHolepunchMessage),
+    /// Our outgoing connection to the peer for the `InfoHash` could not be established.
+    FailedConnection(InfoHash, SocketAddr),
+}
+
+impl IDiscoveryMessage {
+    /// Create a `FailedConnection` message from an `AttemptEvent` of the handshaker.
+    ///
+    /// Returns None unless the attempt failed to establish a connection to the peer.
+    #[must_use]
+    pub fn from_attempt_event(event: &AttemptEvent) -> Option<IDiscoveryMessage> {
+        match event.stage() {
+            AttemptStage::Failed(AttemptFailure::TimedOut | AttemptFailure::Refused | AttemptFailure::Connect(_)) => {
+                Some(IDiscoveryMessage::FailedConnection(*event.hash(), *event.address()))
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Enumeration of discovery messages that can be received from a discovery module.
@@ -38,4 +71,10 @@ pub enum ODiscoveryMessage {
     SendUtMetadataMessage(PeerInfo, UtMetadataMessage),
     /// We have finished downloading the given `Metainfo`.
     DownloadedMetainfo(Metainfo),
+    /// Send a `UtHolepunch` message.
+    SendUtHolepunchMessage(PeerInfo, UtHolepunchMessage),
+    /// Initiate a connection to the peer for the `InfoHash`, such as with an `InitiateMessage` to the handshaker.
+    InitiateConnection(InfoHash, SocketAddr),
+    /// Ban the peer, which sent us bad data, such as by disconnecting it and filtering its future connections.
+    BanPeer(PeerInfo),
 }
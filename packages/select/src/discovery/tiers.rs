@@ -0,0 +1,217 @@
+use rand::seq::SliceRandom;
+
+struct Tier<T> {
+    trackers: Vec<T>,
+    next: Option<usize>,
+    working: bool,
+}
+
+/// Tracker tiers of an announce list, with failover handled per [BEP 12](http://www.bittorrent.org/beps/bep_0012.html).
+///
+/// Trackers within a tier are tried in order, with the first tracker that responds being
+/// promoted to the front of its tier. We only fall through to the next tier once every tracker
+/// in the current tier has failed.
+#[allow(clippy::module_name_repetitions)]
+pub struct TrackerTiers<T> {
+    tiers: Vec<Tier<T>>,
+}
+
+impl<T> TrackerTiers<T>
+where
+    T: PartialEq + Clone,
+{
+    /// Create a new `TrackerTiers` from the given tiers, keeping the order of trackers within each tier.
+    ///
+    /// Empty tiers are ignored.
+    #[must_use]
+    pub fn new(tiers: Vec<Vec<T>>) -> TrackerTiers<T> {
+        let tiers = tiers
+            .into_iter()
+            .filter(|trackers| !trackers.is_empty())
+            .map(|trackers| Tier {
+                trackers,
+                next: Some(0),
+                working: false,
+            })
+            .collect();
+
+        TrackerTiers { tiers }
+    }
+
+    /// Randomly shuffle the trackers within each tier, as is done when first loading an announce list.
+    pub fn shuffle(&mut self) {
+        let mut rng = rand::thread_rng();
+
+        for tier in &mut self.tiers {
+            tier.trackers.shuffle(&mut rng);
+        }
+    }
+
+    /// Whether or not there are any trackers.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Tracker that should be tried next when no tracker is known to be working.
+    ///
+    /// Once every tracker in every tier has failed, we start over from the first tier.
+    pub fn candidate(&mut self) -> Option<T> {
+        if self.tiers.iter().all(|tier| tier.next.is_none()) {
+            for tier in &mut self.tiers {
+                tier.next = Some(0);
+            }
+        }
+
+        self.tiers
+            .iter()
+            .find_map(|tier| tier.next.map(|index| tier.trackers[index].clone()))
+    }
+
+    /// Trackers at the front of each tier that have responded to us.
+    pub fn working(&self) -> impl Iterator<Item = &T> {
+        self.tiers.iter().filter(|tier| tier.working).map(|tier| &tier.trackers[0])
+    }
+
+    /// Trackers that should be announced to, on a state change for example.
+    ///
+    /// This is every working tracker across all tiers or, if none are working, the next candidate.
+    pub fn announce_targets(&mut self) -> Vec<T> {
+        let working: Vec<T> = self.working().cloned().collect();
+
+        if working.is_empty() {
+            self.candidate().into_iter().collect()
+        } else {
+            working
+        }
+    }
+
+    /// Record that the given tracker responded, promoting it to the front of its tier.
+    ///
+    /// Returns false if the tracker is unknown.
+    pub fn succeeded(&mut self, tracker: &T) -> bool {
+        let Some((tier_index, index)) = self.position(tracker) else {
+            return false;
+        };
+        let tier = &mut self.tiers[tier_index];
+
+        let promoted = tier.trackers.remove(index);
+        tier.trackers.insert(0, promoted);
+        tier.next = Some(0);
+        tier.working = true;
+
+        true
+    }
+
+    /// Record that the given tracker failed, returning the tracker that should be tried in its place.
+    ///
+    /// The next tracker in the same tier is preferred, falling through to later tiers only
+    /// once the tier has been exhausted.
+    #[allow(clippy::unnecessary_map_or)] // `Option::is_none_or` is newer than our minimum supported rust version
+    pub fn failed(&mut self, tracker: &T) -> Option<T> {
+        let (tier_index, index) = self.position(tracker)?;
+        let tier = &mut self.tiers[tier_index];
+
+        if index == 0 {
+            tier.working = false;
+        } else if tier.working {
+            return None;
+        }
+
+        // A stale failure for a tracker we have already moved past should not rewind the tier
+        if tier.next.map_or(true, |next| next <= index) {
+            tier.next = Some(index + 1).filter(|&next| next < tier.trackers.len());
+        }
+
+        self.candidate()
+    }
+
+    fn position(&self, tracker: &T) -> Option<(usize, usize)> {
+        self.tiers.iter().enumerate().find_map(|(tier_index, tier)| {
+            tier.trackers
+                .iter()
+                .position(|t| t == tracker)
+                .map(|index| (tier_index, index))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackerTiers;
+
+    fn two_tiers() -> TrackerTiers<&'static str> {
+        TrackerTiers::new(vec![vec!["a1", "a2", "a3"], vec![], vec!["b1", "b2"]])
+    }
+
+    #[test]
+    fn positive_candidate_first_tracker_first_tier() {
+        let mut tiers = two_tiers();
+
+        assert_eq!(Some("a1"), tiers.candidate());
+        assert_eq!(vec!["a1"], tiers.announce_targets());
+    }
+
+    #[test]
+    fn positive_failover_within_tier_before_next_tier() {
+        let mut tiers = two_tiers();
+
+        assert_eq!(Some("a2"), tiers.failed(&"a1"));
+        assert_eq!(Some("a3"), tiers.failed(&"a2"));
+        assert_eq!(Some("b1"), tiers.failed(&"a3"));
+        assert_eq!(Some("b2"), tiers.failed(&"b1"));
+    }
+
+    #[test]
+    fn positive_all_failed_starts_over() {
+        let mut tiers = two_tiers();
+
+        for tracker in ["a1", "a2", "a3", "b1"] {
+            tiers.failed(&tracker);
+        }
+
+        assert_eq!(Some("a1"), tiers.failed(&"b2"));
+    }
+
+    #[test]
+    fn positive_succeeded_promotes_to_front_of_tier() {
+        let mut tiers = two_tiers();
+
+        tiers.failed(&"a1");
+        assert!(tiers.succeeded(&"a2"));
+
+        assert_eq!(Some("a2"), tiers.candidate());
+        assert_eq!(vec![&"a2"], tiers.working().collect::<Vec<_>>());
+
+        // Promoted tracker failing moves on to the old front of the tier
+        assert_eq!(Some("a1"), tiers.failed(&"a2"));
+    }
+
+    #[test]
+    fn positive_announce_targets_working_in_every_tier() {
+        let mut tiers = two_tiers();
+
+        tiers.succeeded(&"a3");
+        tiers.succeeded(&"b2");
+
+        assert_eq!(vec!["a3", "b2"], tiers.announce_targets());
+    }
+
+    #[test]
+    fn positive_failed_non_front_tracker_keeps_working_tier() {
+        let mut tiers = two_tiers();
+
+        tiers.succeeded(&"a1");
+
+        assert_eq!(None, tiers.failed(&"a3"));
+        assert_eq!(vec!["a1"], tiers.announce_targets());
+    }
+
+    #[test]
+    fn negative_unknown_tracker() {
+        let mut tiers = two_tiers();
+
+        assert!(!tiers.succeeded(&"c1"));
+        assert_eq!(None, tiers.failed(&"c1"));
+    }
+}
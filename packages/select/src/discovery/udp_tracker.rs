@@ -1,3 +1,163 @@
-struct UdpTrackerModule {
-    
-}
\ No newline at end of file
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use futures::sink::Sink;
+use futures::stream::Stream;
+use handshake::InfoHash;
+use utracker::announce::ClientState;
+
+use crate::discovery::error::DiscoveryError;
+use crate::discovery::tiers::TrackerTiers;
+use crate::discovery::{IDiscoveryMessage, ODiscoveryMessage};
+use crate::extended::ExtendedListener;
+use crate::ControlMessage;
+
+struct ActiveTorrent {
+    tiers: TrackerTiers<SocketAddr>,
+    opt_state: Option<ClientState>,
+}
+
+/// Discovery module announcing to udp trackers, failing over between trackers per BEP 12.
+///
+/// Trackers are added as resolved tiers of an announce list. On a state change, we announce
+/// to the working tracker of every tier, or to the next candidate tracker if none are working.
+/// Failed announces are retried against the next tracker in the tier, then the next tier.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Default)]
+pub struct UdpTrackerModule {
+    active_torrents: HashMap<InfoHash, ActiveTorrent>,
+    pending_announces: VecDeque<ODiscoveryMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl UdpTrackerModule {
+    #[must_use]
+    pub fn new() -> UdpTrackerModule {
+        UdpTrackerModule::default()
+    }
+
+    fn add_trackers(&mut self, hash: InfoHash, trackers: Vec<Vec<SocketAddr>>) -> Result<(), DiscoveryError> {
+        match self.active_torrents.entry(hash) {
+            Entry::Occupied(_) => Err(DiscoveryError::InvalidMetainfoExists { hash }),
+            Entry::Vacant(vacant) => {
+                let mut tiers = TrackerTiers::new(trackers);
+                tiers.shuffle();
+
+                vacant.insert(ActiveTorrent { tiers, opt_state: None });
+
+                Ok(())
+            }
+        }
+    }
+
+    fn remove_trackers(&mut self, hash: InfoHash) {
+        self.active_torrents.remove(&hash);
+    }
+
+    fn update_state(&mut self, hash: InfoHash, state: ClientState) -> Result<(), DiscoveryError> {
+        let torrent = self
+            .active_torrents
+            .get_mut(&hash)
+            .ok_or(DiscoveryError::InvalidMetainfoNotExists { hash })?;
+        torrent.opt_state = Some(state);
+
+        for addr in torrent.tiers.announce_targets() {
+            self.pending_announces
+                .push_back(ODiscoveryMessage::SendUdpTrackerAnnounce(hash, addr, state));
+        }
+
+        Ok(())
+    }
+
+    fn recv_response(&mut self, hash: InfoHash, addr: SocketAddr) -> Result<(), DiscoveryError> {
+        let torrent = self
+            .active_torrents
+            .get_mut(&hash)
+            .ok_or(DiscoveryError::InvalidMetainfoNotExists { hash })?;
+
+        if !torrent.tiers.succeeded(&addr) {
+            tracing::debug!("udp tracker {addr} responded but is not a tracker for {hash:?}");
+        }
+
+        Ok(())
+    }
+
+    fn recv_failure(&mut self, hash: InfoHash, addr: SocketAddr) -> Result<(), DiscoveryError> {
+        let torrent = self
+            .active_torrents
+            .get_mut(&hash)
+            .ok_or(DiscoveryError::InvalidMetainfoNotExists { hash })?;
+
+        // Retry with the last state we announced, there is nothing to retry without one
+        if let (Some(next_addr), Some(state)) = (torrent.tiers.failed(&addr), torrent.opt_state) {
+            self.pending_announces
+                .push_back(ODiscoveryMessage::SendUdpTrackerAnnounce(hash, next_addr, state));
+        }
+
+        Ok(())
+    }
+
+    fn check_stream_unblock(&mut self) {
+        if !self.pending_announces.is_empty() {
+            if let Some(waker) = self.opt_stream_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl ExtendedListener for UdpTrackerModule {}
+
+impl Sink<IDiscoveryMessage> for UdpTrackerModule {
+    type Error = DiscoveryError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IDiscoveryMessage) -> Result<(), Self::Error> {
+        let result = match item {
+            IDiscoveryMessage::Control(ControlMessage::RemoveTorrent(metainfo)) => {
+                self.remove_trackers(metainfo.info().info_hash());
+                Ok(())
+            }
+            IDiscoveryMessage::AddUdpTrackers(hash, trackers) => self.add_trackers(hash, trackers),
+            IDiscoveryMessage::UpdateClientState(hash, state) => self.update_state(hash, state),
+            IDiscoveryMessage::ReceivedUdpTrackerResponse(hash, addr) => self.recv_response(hash, addr),
+            IDiscoveryMessage::FailedUdpTrackerAnnounce(hash, addr) => self.recv_failure(hash, addr),
+            IDiscoveryMessage::Control(_)
+            | IDiscoveryMessage::DownloadMetainfo(_)
+            | IDiscoveryMessage::ReceivedUtMetadataMessage(_, _)
+            | IDiscoveryMessage::ReceivedUtHolepunchMessage(_, _)
+            | IDiscoveryMessage::FailedConnection(_, _) => Ok(()),
+        };
+
+        self.check_stream_unblock();
+
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for UdpTrackerModule {
+    type Item = Result<ODiscoveryMessage, DiscoveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(message) = self.pending_announces.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::sink::Sink;
+use futures::stream::Stream;
+use handshake::InfoHash;
+use peer::messages::builders::ExtendedMessageBuilder;
+use peer::messages::{ExtendedMessage, ExtendedType, UtHolepunchErrorCode, UtHolepunchMessage};
+use peer::PeerInfo;
+use rand::seq::IteratorRandom;
+
+use crate::discovery::error::DiscoveryError;
+use crate::discovery::{IDiscoveryMessage, ODiscoveryMessage};
+use crate::extended::{ExtendedListener, ExtendedPeerInfo};
+use crate::ControlMessage;
+
+const UT_HOLEPUNCH_EXTENSION_ID: u8 = 4;
+/// Time before we will holepunch to (or be asked to connect to) the same peer again.
+const HOLEPUNCH_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_QUEUED_MESSAGES: usize = 256;
+
+struct HolepunchPeer {
+    // Address the peer accepts connections on, which differs from its address for incoming connections
+    listen_addr: SocketAddr,
+    supported: bool,
+}
+
+/// Discovery module for the `ut_holepunch` extension, see `http://www.bittorrent.org/beps/bep_0055.html`.
+///
+/// When an outgoing connection to a peer fails, a rendezvous is sent to a random connected peer that
+/// supports the extension, which relays a connect message to both sides so that each initiates a
+/// connection to the other at the same time, punching a hole through their NATs. Rendezvous messages
+/// from our peers are relayed in the same way.
+///
+/// Failed connections should be given to the module from the `AttemptEvent`(s) of the handshaker, see
+/// `IDiscoveryMessage::from_attempt_event`, and connections it asks for should be initiated with the handshaker.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Default)]
+pub struct UtHolepunchModule {
+    peers: HashMap<PeerInfo, HolepunchPeer>,
+    // Targets we sent a rendezvous for, and peers we were told to connect to, with their cooldown left
+    rendezvous: HashMap<(InfoHash, SocketAddr), Duration>,
+    initiated: HashMap<(InfoHash, SocketAddr), Duration>,
+    queued: VecDeque<ODiscoveryMessage>,
+    opt_sink_waker: Option<Waker>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl UtHolepunchModule {
+    #[must_use]
+    pub fn new() -> UtHolepunchModule {
+        UtHolepunchModule::default()
+    }
+
+    fn add_peer(&mut self, info: PeerInfo) {
+        self.peers.entry(info).or_insert(HolepunchPeer {
+            listen_addr: *info.addr(),
+            supported: false,
+        });
+    }
+
+    fn update_peer(&mut self, info: PeerInfo, ext_info: &ExtendedPeerInfo) {
+        let our_support = ext_info
+            .our_message()
+            .and_then(|msg| msg.query_id(&ExtendedType::UtHolepunch))
+            .is_some();
+        let they_support = ext_info
+            .their_message()
+            .and_then(|msg| msg.query_id(&ExtendedType::UtHolepunch))
+            .is_some();
+        let listen_addr = ext_info
+            .their_message()
+            .and_then(ExtendedMessage::our_tcp_port)
+            .map_or(*info.addr(), |port| SocketAddr::new(info.addr().ip(), port));
+
+        self.peers.insert(
+            info,
+            HolepunchPeer {
+                listen_addr,
+                supported: our_support && they_support,
+            },
+        );
+    }
+
+    fn remove_peer(&mut self, info: &PeerInfo) {
+        self.peers.remove(info);
+    }
+
+    fn remove_torrent(&mut self, hash: InfoHash) {
+        self.rendezvous.retain(|&(recent_hash, _), _| recent_hash != hash);
+        self.initiated.retain(|&(recent_hash, _), _| recent_hash != hash);
+    }
+
+    fn apply_tick(&mut self, duration: Duration) {
+        for recent in [&mut self.rendezvous, &mut self.initiated] {
+            recent.retain(|_, left| match left.checked_sub(duration) {
+                Some(remaining) => {
+                    *left = remaining;
+                    true
+                }
+                None => false,
+            });
+        }
+    }
+
+    /// Connected peer of the given torrent, that either connected from or listens on the given address.
+    fn find_peer(&self, hash: &InfoHash, addr: SocketAddr) -> Option<(&PeerInfo, &HolepunchPeer)> {
+        self.peers
+            .iter()
+            .find(|(info, peer)| info.hash() == hash && (*info.addr() == addr || peer.listen_addr == addr))
+    }
+
+    fn failed_connection(&mut self, hash: InfoHash, addr: SocketAddr) {
+        let key = (hash, addr);
+        if self.find_peer(&hash, addr).is_some() || self.rendezvous.contains_key(&key) || self.initiated.contains_key(&key) {
+            return;
+        }
+
+        let opt_relay = self
+            .peers
+            .iter()
+            .filter(|(info, peer)| *info.hash() == hash && peer.supported && peer.listen_addr != addr)
+            .map(|(info, _)| *info)
+            .choose(&mut rand::thread_rng());
+
+        if let Some(relay) = opt_relay {
+            tracing::debug!("Holepunching To {addr:?} Through {:?}", relay.addr());
+
+            self.rendezvous.insert(key, HOLEPUNCH_COOLDOWN);
+            self.queue(ODiscoveryMessage::SendUtHolepunchMessage(
+                relay,
+                UtHolepunchMessage::Rendezvous(addr),
+            ));
+        }
+    }
+
+    fn recv_rendezvous(&mut self, from: PeerInfo, target: SocketAddr) {
+        let Some(from_addr) = self
+            .peers
+            .get(&from)
+            .filter(|peer| peer.supported)
+            .map(|peer| peer.listen_addr)
+        else {
+            tracing::debug!("Ignoring Rendezvous From {:?} Without UtHolepunch Support", from.addr());
+            return;
+        };
+
+        let relay = if target.ip().is_unspecified() || target.port() == 0 {
+            Err(UtHolepunchErrorCode::NoSuchPeer)
+        } else if target == *from.addr() || target == from_addr {
+            Err(UtHolepunchErrorCode::NoSelf)
+        } else {
+            match self.find_peer(from.hash(), target) {
+                None => Err(UtHolepunchErrorCode::NotConnected),
+                Some((_, peer)) if !peer.supported => Err(UtHolepunchErrorCode::NoSupport),
+                Some((&info, _)) => Ok(info),
+            }
+        };
+
+        match relay {
+            Ok(info) => {
+                self.queue(ODiscoveryMessage::SendUtHolepunchMessage(
+                    info,
+                    UtHolepunchMessage::Connect(from_addr),
+                ));
+                self.queue(ODiscoveryMessage::SendUtHolepunchMessage(
+                    from,
+                    UtHolepunchMessage::Connect(target),
+                ));
+            }
+            Err(code) => self.queue(ODiscoveryMessage::SendUtHolepunchMessage(
+                from,
+                UtHolepunchMessage::Error(target, code),
+            )),
+        }
+    }
+
+    fn recv_connect(&mut self, from: &PeerInfo, addr: SocketAddr) {
+        let hash = *from.hash();
+        if self.find_peer(&hash, addr).is_some() || self.initiated.contains_key(&(hash, addr)) {
+            return;
+        }
+
+        self.initiated.insert((hash, addr), HOLEPUNCH_COOLDOWN);
+        self.queue(ODiscoveryMessage::InitiateConnection(hash, addr));
+    }
+
+    fn recv_error(from: &PeerInfo, addr: SocketAddr, code: UtHolepunchErrorCode) {
+        from.span().in_scope(|| {
+            tracing::debug!("Peer Could Not Relay Rendezvous To {addr:?}: {code:?}");
+        });
+    }
+
+    fn queue(&mut self, message: ODiscoveryMessage) {
+        if self.queued.len() < MAX_QUEUED_MESSAGES {
+            self.queued.push_back(message);
+
+            if let Some(waker) = self.opt_stream_waker.take() {
+                waker.wake();
+            }
+        } else {
+            tracing::warn!("Dropping UtHolepunch Message As The Queue Is Full: {message:?}");
+        }
+    }
+}
+
+impl ExtendedListener for UtHolepunchModule {
+    fn extend(&self, _info: &PeerInfo, builder: ExtendedMessageBuilder) -> ExtendedMessageBuilder {
+        builder.with_extended_type(ExtendedType::UtHolepunch, Some(UT_HOLEPUNCH_EXTENSION_ID))
+    }
+
+    fn on_update(&mut self, info: &PeerInfo, extended: &ExtendedPeerInfo) {
+        self.update_peer(*info, extended);
+    }
+}
+
+impl Sink<IDiscoveryMessage> for UtHolepunchModule {
+    type Error = DiscoveryError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.queued.len() < MAX_QUEUED_MESSAGES {
+            Poll::Ready(Ok(()))
+        } else {
+            self.opt_sink_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IDiscoveryMessage) -> Result<(), Self::Error> {
+        match item {
+            IDiscoveryMessage::Control(ControlMessage::PeerConnected(info)) => self.add_peer(info),
+            IDiscoveryMessage::Control(ControlMessage::PeerDisconnected(info)) => self.remove_peer(&info),
+            IDiscoveryMessage::Control(ControlMessage::RemoveTorrent(metainfo)) => {
+                self.remove_torrent(metainfo.info().info_hash());
+            }
+            IDiscoveryMessage::Control(ControlMessage::Tick(duration)) => self.apply_tick(duration),
+            IDiscoveryMessage::FailedConnection(hash, addr) => self.failed_connection(hash, addr),
+            IDiscoveryMessage::ReceivedUtHolepunchMessage(info, UtHolepunchMessage::Rendezvous(target)) => {
+                self.recv_rendezvous(info, target);
+            }
+            IDiscoveryMessage::ReceivedUtHolepunchMessage(info, UtHolepunchMessage::Connect(addr)) => {
+                self.recv_connect(&info, addr);
+            }
+            IDiscoveryMessage::ReceivedUtHolepunchMessage(info, UtHolepunchMessage::Error(addr, code)) => {
+                UtHolepunchModule::recv_error(&info, addr, code);
+            }
+            IDiscoveryMessage::Control(ControlMessage::AddTorrent(_))
+            | IDiscoveryMessage::DownloadMetainfo(_)
+            | IDiscoveryMessage::ReceivedUtMetadataMessage(..)
+            | IDiscoveryMessage::AddUdpTrackers(..)
+            | IDiscoveryMessage::UpdateClientState(..)
+            | IDiscoveryMessage::ReceivedUdpTrackerResponse(..)
+            | IDiscoveryMessage::FailedUdpTrackerAnnounce(..) => (),
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for UtHolepunchModule {
+    type Item = Result<ODiscoveryMessage, DiscoveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(message) = self.queued.pop_front() {
+            if let Some(waker) = self.opt_sink_waker.take() {
+                waker.wake();
+            }
+
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
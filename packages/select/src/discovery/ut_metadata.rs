@@ -1,21 +1,21 @@
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::io::Write as _;
+use std::ops::Range;
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
 use std::time::Duration;
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use futures::sink::Sink;
 use futures::stream::Stream;
 use handshake::InfoHash;
 use metainfo::{Info, Metainfo};
 use peer::messages::builders::ExtendedMessageBuilder;
 use peer::messages::{
-    ExtendedMessage, ExtendedType, UtMetadataDataMessage, UtMetadataMessage, UtMetadataRejectMessage, UtMetadataRequestMessage,
+    ExtendedMessage, ExtendedType, MetadataCompression, UtMetadataDataMessage, UtMetadataMessage, UtMetadataRejectMessage,
+    UtMetadataRequestMessage,
 };
 use peer::PeerInfo;
-use rand::{self, Rng};
 
 use crate::discovery::error::DiscoveryError;
 use crate::discovery::{IDiscoveryMessage, ODiscoveryMessage};
@@ -26,11 +26,44 @@ const REQUEST_TIMEOUT_MILLIS: u64 = 2000;
 const MAX_REQUEST_SIZE: usize = 16 * 1024;
 const MAX_ACTIVE_REQUESTS: usize = 100;
 const MAX_PEER_REQUESTS: usize = 100;
+/// Number of peers that must send matching copies of a metadata piece before the piece is trusted.
+const CROSS_CHECK_COPIES: usize = 2;
 
 struct PendingInfo {
-    messages: Vec<UtMetadataRequestMessage>,
-    left: usize,
-    bytes: Vec<u8>,
+    metadata_size: usize,
+    pieces: Vec<PendingPiece>,
+}
+
+#[derive(Default)]
+struct PendingPiece {
+    /// Copies of the piece received so far, along with the peer that sent each of them.
+    copies: Vec<(PeerInfo, Bytes)>,
+    /// Peers whose copies were discarded after the assembled metadata failed verification.
+    discarded: HashSet<PeerInfo>,
+}
+
+impl PendingPiece {
+    /// Most common copy of the piece, along with the number of peers that sent it.
+    ///
+    /// Ties are broken in favour of the copy that was received first.
+    fn most_common(&self) -> Option<(&Bytes, usize)> {
+        self.copies
+            .iter()
+            .map(|(_, copy)| (copy, self.copies.iter().filter(|(_, other)| other == copy).count()))
+            .rev()
+            .max_by_key(|(_, count)| *count)
+    }
+
+    /// Copy of the piece that enough peers agree on to be trusted.
+    fn agreed(&self) -> Option<&Bytes> {
+        self.most_common()
+            .filter(|(_, count)| *count >= CROSS_CHECK_COPIES)
+            .map(|(copy, _)| copy)
+    }
+
+    fn copy_from(&self, peer: &PeerInfo) -> Option<&Bytes> {
+        self.copies.iter().find(|(from, _)| from == peer).map(|(_, copy)| copy)
+    }
 }
 
 struct ActiveRequest {
@@ -57,6 +90,11 @@ pub struct UtMetadataModule {
     active_peers: HashMap<InfoHash, ActivePeers>,
     active_requests: Vec<ActiveRequest>,
     peer_requests: VecDeque<PeerRequest>,
+    metadata_compression: Vec<MetadataCompression>,
+    peer_compression: HashMap<PeerInfo, MetadataCompression>,
+    banned_peers: HashSet<PeerInfo>,
+    ban_queue: VecDeque<PeerInfo>,
+    downloaded: VecDeque<Vec<u8>>,
     opt_sink_waker: Option<Waker>,
     opt_stream_waker: Option<Waker>,
 }
@@ -70,11 +108,25 @@ impl UtMetadataModule {
             active_peers: HashMap::new(),
             active_requests: Vec::new(),
             peer_requests: VecDeque::new(),
+            metadata_compression: Vec::new(),
+            peer_compression: HashMap::new(),
+            banned_peers: HashSet::new(),
+            ban_queue: VecDeque::new(),
+            downloaded: VecDeque::new(),
             opt_sink_waker: None,
             opt_stream_waker: None,
         }
     }
 
+    /// Advertise support for compressing metadata payloads with the given algorithms, in order of preference.
+    ///
+    /// Payloads we send are only compressed for peers that advertise support for one of the algorithms.
+    #[must_use]
+    pub fn with_metadata_compression(mut self, compression: Vec<MetadataCompression>) -> UtMetadataModule {
+        self.metadata_compression = compression;
+        self
+    }
+
     fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), DiscoveryError> {
         let info_hash = metainfo.info().info_hash();
         match self.completed_map.entry(info_hash) {
@@ -98,6 +150,12 @@ impl UtMetadataModule {
     }
 
     fn add_peer(&mut self, info: PeerInfo, ext_info: &ExtendedPeerInfo) {
+        let _entered = info.span().entered();
+
+        if self.banned_peers.contains(&info) {
+            return;
+        }
+
         let our_support = ext_info
             .our_message()
             .and_then(|msg| msg.query_id(&ExtendedType::UtMetadata))
@@ -108,6 +166,14 @@ impl UtMetadataModule {
             .is_some();
         let opt_metadata_size = ext_info.their_message().and_then(ExtendedMessage::metadata_size);
 
+        match (ext_info.our_message(), ext_info.their_message()) {
+            (Some(ours), Some(theirs)) => match MetadataCompression::negotiate(ours, theirs) {
+                Some(compression) => self.peer_compression.insert(info, compression),
+                None => self.peer_compression.remove(&info),
+            },
+            _ => self.peer_compression.remove(&info),
+        };
+
         tracing::info!(
             "Our Support For UtMetadata Is {:?} And {:?} Support For UtMetadata Is {:?} With Metadata Size {:?}",
             our_support,
@@ -129,6 +195,7 @@ impl UtMetadataModule {
     }
 
     fn remove_peer(&mut self, info: PeerInfo) {
+        self.peer_compression.remove(&info);
         if let Some(active_peers) = self.active_peers.get_mut(info.hash()) {
             active_peers.peers.remove(&info);
             if active_peers.peers.is_empty() {
@@ -137,16 +204,34 @@ impl UtMetadataModule {
         }
     }
 
+    /// Stop downloading metadata from the peer, and tell the client to ban it, as it sent us bad metadata.
+    fn ban_peer(&mut self, info: PeerInfo) {
+        if !self.banned_peers.insert(info) {
+            return;
+        }
+
+        info.span()
+            .in_scope(|| tracing::warn!("Banning Peer For Sending Bad Metadata"));
+
+        self.remove_peer(info);
+        self.active_requests.retain(|request| request.sent_to != info);
+        if let Some(Some(pending)) = self.pending_map.get_mut(info.hash()) {
+            for piece in &mut pending.pieces {
+                piece.copies.retain(|(from, _)| *from != info);
+            }
+        }
+
+        self.ban_queue.push_back(info);
+    }
+
     fn apply_tick(&mut self, duration: Duration) {
+        // Pieces of expired requests are requested again, as they have no outstanding request
         self.active_requests.retain(|request| {
             let is_expired = request.left.checked_sub(duration).is_none();
             if is_expired {
                 if let Some(active) = self.active_peers.get_mut(request.sent_to.hash()) {
                     active.peers.remove(&request.sent_to);
                 }
-                if let Some(Some(pending)) = self.pending_map.get_mut(request.sent_to.hash()) {
-                    pending.messages.push(request.message);
-                }
             }
             !is_expired
         });
@@ -167,19 +252,38 @@ impl UtMetadataModule {
     }
 
     fn recv_data(&mut self, info: PeerInfo, data: &UtMetadataDataMessage) {
-        if let Some(index) = self
+        let Some(index) = self
             .active_requests
             .iter()
             .position(|request| request.sent_to == info && request.message.piece() == data.piece())
-        {
-            self.active_requests.swap_remove(index);
-            if let Some(Some(pending)) = self.pending_map.get_mut(info.hash()) {
-                let piece: usize = data.piece().try_into().unwrap();
-                let data_offset = piece.checked_mul(MAX_REQUEST_SIZE).unwrap();
-                pending.left -= 1;
-                (&mut pending.bytes.as_mut_slice()[data_offset..])
-                    .write_all(data.data().as_ref())
-                    .unwrap();
+        else {
+            return;
+        };
+        self.active_requests.swap_remove(index);
+
+        let Some(Some(pending)) = self.pending_map.get_mut(info.hash()) else {
+            return;
+        };
+        let piece_index: usize = data.piece().try_into().unwrap();
+        if data.data().len() != piece_range(pending.metadata_size, piece_index).len() {
+            self.ban_peer(info);
+            return;
+        }
+
+        let piece = &mut pending.pieces[piece_index];
+        piece.copies.push((info, data.data().clone()));
+
+        // Once enough peers agree on the piece, any peer that sent a different copy sent a bad piece
+        if let Some(agreed) = piece.agreed() {
+            let bad_peers: Vec<PeerInfo> = piece
+                .copies
+                .iter()
+                .filter(|(_, copy)| copy != agreed)
+                .map(|(from, _)| *from)
+                .collect();
+
+            for bad_peer in bad_peers {
+                self.ban_peer(bad_peer);
             }
         }
     }
@@ -188,53 +292,108 @@ impl UtMetadataModule {
         // TODO: Remove any requests after receiving a reject, for now, we will just timeout
     }
 
+    fn retrieve_banned_peer(&mut self) -> Option<Result<ODiscoveryMessage, DiscoveryError>> {
+        self.ban_queue.pop_front().map(|info| Ok(ODiscoveryMessage::BanPeer(info)))
+    }
+
     fn retrieve_completed_download(&mut self) -> Option<Result<ODiscoveryMessage, DiscoveryError>> {
-        let opt_completed_hash = self
-            .pending_map
-            .iter()
-            .find(|(_, opt_pending)| opt_pending.as_ref().map_or(false, |pending| pending.left == 0))
-            .map(|(hash, _)| *hash);
-
-        opt_completed_hash.and_then(|completed_hash| {
-            let completed = self.pending_map.remove(&completed_hash).unwrap().unwrap();
-            self.active_peers.remove(&completed_hash);
-            match Info::from_bytes(&completed.bytes[..]) {
-                Ok(info) => Some(Ok(ODiscoveryMessage::DownloadedMetainfo(info.into()))),
-                Err(_) => self.retrieve_completed_download(),
+        while let Some(bytes) = self.downloaded.pop_front() {
+            if let Ok(info) = Info::from_bytes(&bytes[..]) {
+                return Some(Ok(ODiscoveryMessage::DownloadedMetainfo(info.into())));
             }
-        })
+        }
+        None
     }
 
     fn retrieve_piece_request(&mut self) -> Option<Result<ODiscoveryMessage, DiscoveryError>> {
-        for (hash, opt_pending) in &mut self.pending_map {
-            if let Some(pending) = opt_pending {
-                if !pending.messages.is_empty() {
-                    if let Some(active_peers) = self.active_peers.get(hash) {
-                        if !active_peers.peers.is_empty() {
-                            let mut active_peers_iter = active_peers.peers.iter();
-                            let num_active_peers = active_peers_iter.len();
-                            let selected_peer_num = rand::thread_rng().gen::<usize>() % num_active_peers;
-                            let selected_peer = active_peers_iter.nth(selected_peer_num).unwrap();
-                            let selected_message = pending.messages.pop().unwrap();
-                            self.active_requests
-                                .push(generate_active_request(selected_message, *selected_peer));
-                            tracing::info!(
-                                "Requesting Piece {:?} For Hash {:?}",
-                                selected_message.piece(),
-                                selected_peer.hash()
-                            );
-                            return Some(Ok(ODiscoveryMessage::SendUtMetadataMessage(
-                                *selected_peer,
-                                UtMetadataMessage::Request(selected_message),
-                            )));
-                        }
-                    }
+        let (selected_peer, selected_message) = self.next_piece_request()?;
+
+        self.active_requests
+            .push(generate_active_request(selected_message, selected_peer));
+        tracing::info!(
+            "Requesting Piece {:?} For Hash {:?}",
+            selected_message.piece(),
+            selected_peer.hash()
+        );
+
+        Some(Ok(ODiscoveryMessage::SendUtMetadataMessage(
+            selected_peer,
+            UtMetadataMessage::Request(selected_message),
+        )))
+    }
+
+    /// Peer that the next piece should be requested from, if any.
+    ///
+    /// Pieces are requested from as many peers as needed to cross check them, and each
+    /// request goes to the peer with the fewest active requests, so that different pieces
+    /// are downloaded from different peers in parallel.
+    fn next_piece_request(&self) -> Option<(PeerInfo, UtMetadataRequestMessage)> {
+        if self.active_requests.len() >= MAX_ACTIVE_REQUESTS {
+            return None;
+        }
+
+        for (hash, opt_pending) in &self.pending_map {
+            let Some(pending) = opt_pending else {
+                continue;
+            };
+
+            for (piece_index, piece) in pending.pieces.iter().enumerate() {
+                let matching = piece.most_common().map_or(0, |(_, count)| count);
+                if matching + self.requested_from(hash, piece_index).count() >= CROSS_CHECK_COPIES {
+                    continue;
+                }
+
+                let opt_selected_peer = self.piece_candidates(hash, piece_index, piece).min_by_key(|peer| {
+                    self.active_requests
+                        .iter()
+                        .filter(|request| request.sent_to == **peer)
+                        .count()
+                });
+
+                if let Some(selected_peer) = opt_selected_peer {
+                    return Some((*selected_peer, UtMetadataRequestMessage::new(piece_index.try_into().unwrap())));
                 }
             }
         }
         None
     }
 
+    /// Peers with an active request for the given piece.
+    fn requested_from<'a>(&'a self, hash: &'a InfoHash, piece_index: usize) -> impl Iterator<Item = &'a PeerInfo> {
+        self.active_requests
+            .iter()
+            .filter(move |request| request.sent_to.hash() == hash && usize::try_from(request.message.piece()) == Ok(piece_index))
+            .map(|request| &request.sent_to)
+    }
+
+    /// Peers that could still be asked for a copy of the given piece.
+    fn piece_candidates<'a>(
+        &'a self,
+        hash: &'a InfoHash,
+        piece_index: usize,
+        piece: &'a PendingPiece,
+    ) -> impl Iterator<Item = &'a PeerInfo> {
+        self.active_peers
+            .get(hash)
+            .into_iter()
+            .flat_map(|active_peers| active_peers.peers.iter())
+            .filter(move |peer| {
+                piece.copy_from(peer).is_none()
+                    && !piece.discarded.contains(*peer)
+                    && self.requested_from(hash, piece_index).all(|requested| requested != *peer)
+            })
+    }
+
+    /// Whether every piece is either agreed on, or has a copy and no other peer to cross check it with.
+    fn is_assembled(&self, hash: &InfoHash, pending: &PendingInfo) -> bool {
+        pending.pieces.iter().enumerate().all(|(piece_index, piece)| {
+            piece.agreed().is_some()
+                || (!piece.copies.is_empty()
+                    && self.requested_from(hash, piece_index).next().is_none()
+                    && self.piece_candidates(hash, piece_index, piece).next().is_none())
+        })
+    }
+
     fn retrieve_piece_response(&mut self) -> Option<Result<ODiscoveryMessage, DiscoveryError>> {
         while let Some(request) = self.peer_requests.pop_front() {
             let hash = request.send_to.hash();
@@ -246,11 +405,20 @@ impl UtMetadataModule {
                     let info_slice = &data[start..end];
                     let mut info_payload = BytesMut::with_capacity(info_slice.len());
                     info_payload.extend_from_slice(info_slice);
-                    let message = UtMetadataDataMessage::new(
+                    let mut message = UtMetadataDataMessage::new(
                         piece.try_into().unwrap(),
                         info_slice.len().try_into().unwrap(),
                         info_payload.freeze(),
                     );
+                    if let Some(&compression) = self.peer_compression.get(&request.send_to) {
+                        // Fall back to sending the payload uncompressed if compression fails
+                        match message.clone().with_compression(compression) {
+                            Ok(compressed) => message = compressed,
+                            Err(err) => request.send_to.span().in_scope(|| {
+                                tracing::warn!("Failed To Compress Metadata Piece With {compression:?}: {err}");
+                            }),
+                        }
+                    }
                     return Some(Ok(ODiscoveryMessage::SendUtMetadataMessage(
                         request.send_to,
                         UtMetadataMessage::Data(message),
@@ -261,43 +429,95 @@ impl UtMetadataModule {
         None
     }
 
-    fn initialize_pending(&mut self) -> bool {
-        let mut pending_tasks_available = false;
+    fn initialize_pending(&mut self) {
         for (hash, opt_pending) in &mut self.pending_map {
             if opt_pending.is_none() {
                 if let Some(active_peers) = self.active_peers.get(hash) {
                     *opt_pending = Some(pending_info_from_metadata_size(active_peers.metadata_size));
                 }
             }
-            pending_tasks_available |= opt_pending.as_ref().map_or(false, |pending| !pending.messages.is_empty());
         }
-        pending_tasks_available
-    }
-
-    fn validate_downloaded(&mut self) -> bool {
-        let mut completed_downloads_available = false;
-        for (&expected_hash, opt_pending) in &mut self.pending_map {
-            if let Some(pending) = opt_pending {
-                if pending.left == 0 {
-                    let real_hash = InfoHash::from_bytes(&pending.bytes[..]);
-                    if real_hash == expected_hash {
-                        completed_downloads_available = true;
-                    } else {
-                        *opt_pending = None;
-                    }
-                }
+    }
+
+    fn validate_downloaded(&mut self) {
+        let assembled_hashes: Vec<InfoHash> = self
+            .pending_map
+            .iter()
+            .filter(|(hash, opt_pending)| opt_pending.as_ref().is_some_and(|pending| self.is_assembled(hash, pending)))
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        for hash in assembled_hashes {
+            self.validate_assembled(hash);
+        }
+    }
+
+    /// Check the assembled metadata against the `InfoHash`, banning the peers that sent bad pieces.
+    fn validate_assembled(&mut self, expected_hash: InfoHash) {
+        let Some(Some(pending)) = self.pending_map.get_mut(&expected_hash) else {
+            return;
+        };
+
+        // Peers that sent copies of pieces which were not agreed on may have sent bad pieces
+        let suspects: HashSet<PeerInfo> = pending
+            .pieces
+            .iter()
+            .filter(|piece| piece.agreed().is_none())
+            .flat_map(|piece| piece.copies.iter().map(|(from, _)| *from))
+            .collect();
+
+        // Try the most common copies first, then the copies of each suspect in turn
+        let opt_verified = std::iter::once(None)
+            .chain(suspects.iter().map(Some))
+            .map(|opt_preferred| assemble_metadata(pending, opt_preferred))
+            .find(|bytes| InfoHash::from_bytes(&bytes[..]) == expected_hash);
+
+        if let Some(bytes) = opt_verified {
+            let bad_peers: HashSet<PeerInfo> = pending
+                .pieces
+                .iter()
+                .enumerate()
+                .flat_map(|(piece_index, piece)| {
+                    let verified = &bytes[piece_range(pending.metadata_size, piece_index)];
+
+                    piece
+                        .copies
+                        .iter()
+                        .filter(move |(_, copy)| copy.as_ref() != verified)
+                        .map(|(from, _)| *from)
+                })
+                .collect();
+
+            self.pending_map.remove(&expected_hash);
+            self.active_peers.remove(&expected_hash);
+            self.active_requests
+                .retain(|request| *request.sent_to.hash() != expected_hash);
+            self.downloaded.push_back(bytes);
+
+            for bad_peer in bad_peers {
+                self.ban_peer(bad_peer);
+            }
+        } else if suspects.len() == 1 {
+            self.ban_peer(suspects.into_iter().next().unwrap());
+        } else if suspects.is_empty() {
+            // Peers agreed on every piece, so start over in case the metadata size was wrong
+            self.pending_map.insert(expected_hash, None);
+        } else {
+            // Ask other peers for the pieces that could not be cross checked
+            for piece in pending.pieces.iter_mut().filter(|piece| piece.agreed().is_none()) {
+                piece.discarded.extend(piece.copies.drain(..).map(|(from, _)| from));
             }
         }
-        completed_downloads_available
     }
 
     fn check_stream_unblock(&mut self) {
-        let downloads_available = self.validate_downloaded();
-        let tasks_available = self.initialize_pending();
-        let free_task_queue_space = self.active_requests.len() != MAX_ACTIVE_REQUESTS;
+        self.validate_downloaded();
+        self.initialize_pending();
+        let tasks_available = self.next_piece_request().is_some();
         let peer_requests_available = !self.peer_requests.is_empty();
-        let should_unblock = self.opt_stream_waker.is_some()
-            && ((free_task_queue_space && tasks_available) || peer_requests_available || downloads_available);
+        let downloads_available = !self.downloaded.is_empty() || !self.ban_queue.is_empty();
+        let should_unblock =
+            self.opt_stream_waker.is_some() && (tasks_available || peer_requests_available || downloads_available);
         if should_unblock {
             if let Some(waker) = self.opt_stream_waker.take() {
                 waker.wake();
@@ -325,26 +545,49 @@ fn generate_active_request(message: UtMetadataRequestMessage, peer: PeerInfo) ->
 
 fn pending_info_from_metadata_size(metadata_size: i64) -> PendingInfo {
     let cast_metadata_size: usize = metadata_size.try_into().unwrap();
-    let bytes = vec![0u8; cast_metadata_size];
-    let mut messages = Vec::new();
     let num_pieces = if cast_metadata_size % MAX_REQUEST_SIZE != 0 {
         cast_metadata_size / MAX_REQUEST_SIZE + 1
     } else {
         cast_metadata_size / MAX_REQUEST_SIZE
     };
-    for index in 0..num_pieces {
-        messages.push(UtMetadataRequestMessage::new(index.try_into().unwrap()));
-    }
     PendingInfo {
-        messages,
-        left: num_pieces,
-        bytes,
+        metadata_size: cast_metadata_size,
+        pieces: (0..num_pieces).map(|_| PendingPiece::default()).collect(),
+    }
+}
+
+/// Range of the metadata covered by the given piece.
+fn piece_range(metadata_size: usize, piece_index: usize) -> Range<usize> {
+    let start = piece_index.saturating_mul(MAX_REQUEST_SIZE).min(metadata_size);
+    let end = start.saturating_add(MAX_REQUEST_SIZE).min(metadata_size);
+
+    start..end
+}
+
+/// Assemble the metadata from the agreed copies of each piece, falling back to the copies of
+/// the preferred peer, and then to the most common copies, for pieces that were not agreed on.
+fn assemble_metadata(pending: &PendingInfo, opt_preferred: Option<&PeerInfo>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(pending.metadata_size);
+
+    for piece in &pending.pieces {
+        let opt_copy = piece
+            .agreed()
+            .or_else(|| opt_preferred.and_then(|preferred| piece.copy_from(preferred)))
+            .or_else(|| piece.most_common().map(|(copy, _)| copy));
+
+        if let Some(copy) = opt_copy {
+            bytes.extend_from_slice(copy);
+        }
     }
+
+    bytes
 }
 
 impl ExtendedListener for UtMetadataModule {
     fn extend(&self, _info: &PeerInfo, builder: ExtendedMessageBuilder) -> ExtendedMessageBuilder {
-        builder.with_extended_type(ExtendedType::UtMetadata, Some(5))
+        builder
+            .with_extended_type(ExtendedType::UtMetadata, Some(5))
+            .with_metadata_compression(self.metadata_compression.clone())
     }
 
     fn on_update(&mut self, info: &PeerInfo, extended: &ExtendedPeerInfo) {
@@ -366,7 +609,7 @@ impl Sink<IDiscoveryMessage> for UtMetadataModule {
     }
 
     fn start_send(mut self: Pin<&mut Self>, item: IDiscoveryMessage) -> Result<(), Self::Error> {
-        match item {
+        let result = match item {
             IDiscoveryMessage::Control(ControlMessage::AddTorrent(metainfo)) => self.add_torrent(&metainfo),
             IDiscoveryMessage::Control(ControlMessage::RemoveTorrent(metainfo)) => self.remove_torrent(&metainfo),
             IDiscoveryMessage::Control(ControlMessage::PeerConnected(_)) => Ok(()),
@@ -394,7 +637,16 @@ impl Sink<IDiscoveryMessage> for UtMetadataModule {
                 UtMetadataModule::recv_reject(info, msg);
                 Ok(())
             }
-        }
+            IDiscoveryMessage::AddUdpTrackers(..)
+            | IDiscoveryMessage::UpdateClientState(..)
+            | IDiscoveryMessage::ReceivedUdpTrackerResponse(..)
+            | IDiscoveryMessage::FailedUdpTrackerAnnounce(..)
+            | IDiscoveryMessage::ReceivedUtHolepunchMessage(..)
+            | IDiscoveryMessage::FailedConnection(..) => Ok(()),
+        };
+
+        self.check_stream_unblock();
+        result
     }
 
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -410,8 +662,12 @@ impl Stream for UtMetadataModule {
     type Item = Result<ODiscoveryMessage, DiscoveryError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.validate_downloaded();
+        self.initialize_pending();
+
         let opt_result = self
-            .retrieve_completed_download()
+            .retrieve_banned_peer()
+            .or_else(|| self.retrieve_completed_download())
             .or_else(|| self.retrieve_piece_request())
             .or_else(|| self.retrieve_piece_response());
 
@@ -3,16 +3,26 @@ use std::time::Duration;
 use metainfo::Metainfo;
 use peer::PeerInfo;
 
+pub mod bandwidth;
+pub mod choke;
+pub mod connection;
 pub mod discovery;
 pub mod error;
+pub mod pause;
+pub mod picker;
+pub mod queue;
 pub mod revelation;
+pub mod selection;
+pub mod shutdown;
 
 mod extended;
+mod priority;
 mod uber;
 
 pub use uber::{DiscoveryTrait, IUberMessage, OUberMessage, UberModule, UberModuleBuilder};
 
 pub use crate::extended::{ExtendedListener, ExtendedPeerInfo, IExtendedMessage, OExtendedMessage};
+pub use crate::priority::TorrentPriority;
 
 /// Enumeration of control messages most modules will be interested in.
 #[derive(Clone, Debug, PartialEq, Eq)]
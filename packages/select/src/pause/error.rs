@@ -0,0 +1,13 @@
+//! Module for pause error types.
+
+use handshake::InfoHash;
+use thiserror::Error;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug)]
+pub enum PauseError {
+    #[error("Metainfo With Hash {hash:?} Has Already Been Added")]
+    InvalidMetainfoExists { hash: InfoHash },
+    #[error("Metainfo With Hash {hash:?} Was Not Already Added")]
+    InvalidMetainfoNotExists { hash: InfoHash },
+}
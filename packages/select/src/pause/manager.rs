@@ -0,0 +1,312 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use futures::{Sink, Stream};
+use handshake::InfoHash;
+use metainfo::Metainfo;
+use peer::PeerInfo;
+use tracing::instrument;
+use utracker::announce::AnnounceEvent;
+
+use crate::pause::error::PauseError;
+use crate::pause::{IPauseMessage, OPauseMessage, PeerPausePolicy, TrackerPausePolicy};
+use crate::ControlMessage;
+
+/// Builder for configuring how a `PauseModule` pauses torrents.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PauseModuleBuilder {
+    peer_policy: PeerPausePolicy,
+    tracker_policy: TrackerPausePolicy,
+}
+
+impl PauseModuleBuilder {
+    #[must_use]
+    pub fn new() -> PauseModuleBuilder {
+        PauseModuleBuilder::default()
+    }
+
+    /// What happens to the connected peers of a torrent when it is paused.
+    ///
+    /// Defaults to `PeerPausePolicy::Choke`.
+    #[must_use]
+    pub fn with_peer_policy(mut self, policy: PeerPausePolicy) -> PauseModuleBuilder {
+        self.peer_policy = policy;
+        self
+    }
+
+    /// What the trackers of a torrent are told when it is paused.
+    ///
+    /// Defaults to `TrackerPausePolicy::AnnounceStopped`.
+    #[must_use]
+    pub fn with_tracker_policy(mut self, policy: TrackerPausePolicy) -> PauseModuleBuilder {
+        self.tracker_policy = policy;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> PauseModule {
+        PauseModule::from_builder(self)
+    }
+}
+
+#[derive(Default)]
+struct TorrentState {
+    // Connected peers, along with whether or not they are unchoked
+    peers: HashMap<PeerInfo, bool>,
+    // Peers to unchoke on resume, set while the torrent is paused
+    opt_paused: Option<Vec<PeerInfo>>,
+}
+
+/// Module for pausing torrents, telling the peers, trackers, and disk of a torrent to stop, and restoring them on resume.
+///
+/// Pausing a torrent sends, in order, `TorrentPaused`, then a `Choke` for every unchoked peer (or a
+/// `DisconnectPeer` for every peer), then a `Stopped` announce, and finally a `SyncDisk`. Resuming sends
+/// `TorrentResumed`, an `Unchoke` for every peer that was choked by the pause and is still connected, and
+/// a `Started` announce. Which messages are sent for the peers and trackers is configured with the
+/// `PauseModuleBuilder`.
+///
+/// Messages from other modules for a paused torrent, such as an unchoke from a `ChokeModule`, should be
+/// dropped while `PauseModule::is_paused` is true.
+#[allow(clippy::module_name_repetitions)]
+pub struct PauseModule {
+    config: PauseModuleBuilder,
+    torrents: HashMap<InfoHash, TorrentState>,
+    out_queue: VecDeque<OPauseMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl PauseModule {
+    #[must_use]
+    pub fn from_builder(builder: PauseModuleBuilder) -> PauseModule {
+        PauseModule {
+            config: builder,
+            torrents: HashMap::new(),
+            out_queue: VecDeque::new(),
+            opt_stream_waker: None,
+        }
+    }
+
+    /// Whether or not the torrent for the given `InfoHash` is paused.
+    #[must_use]
+    pub fn is_paused(&self, hash: &InfoHash) -> bool {
+        self.torrents.get(hash).is_some_and(|torrent| torrent.opt_paused.is_some())
+    }
+
+    /// Pause the torrent for the given `InfoHash`, pausing a paused torrent does nothing.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the torrent was not added.
+    #[instrument(skip(self))]
+    pub fn pause(&mut self, hash: InfoHash) -> Result<(), PauseError> {
+        let torrent = self
+            .torrents
+            .get_mut(&hash)
+            .ok_or(PauseError::InvalidMetainfoNotExists { hash })?;
+        if torrent.opt_paused.is_some() {
+            return Ok(());
+        }
+
+        let mut messages = vec![OPauseMessage::TorrentPaused(hash)];
+        let mut unchoked = Vec::new();
+
+        match self.config.peer_policy {
+            PeerPausePolicy::Choke => {
+                for (peer, is_unchoked) in &mut torrent.peers {
+                    if *is_unchoked {
+                        *is_unchoked = false;
+                        unchoked.push(*peer);
+                        messages.push(OPauseMessage::Choke(*peer));
+                    }
+                }
+            }
+            PeerPausePolicy::Disconnect => {
+                messages.extend(torrent.peers.keys().map(|peer| OPauseMessage::DisconnectPeer(*peer)));
+            }
+        }
+        torrent.opt_paused = Some(unchoked);
+
+        if self.config.tracker_policy == TrackerPausePolicy::AnnounceStopped {
+            messages.push(OPauseMessage::Announce(hash, AnnounceEvent::Stopped));
+        }
+        messages.push(OPauseMessage::SyncDisk(hash));
+
+        for message in messages {
+            self.queue_message(message);
+        }
+
+        Ok(())
+    }
+
+    /// Resume the torrent for the given `InfoHash`, resuming a torrent that is not paused does nothing.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the torrent was not added.
+    #[instrument(skip(self))]
+    pub fn resume(&mut self, hash: InfoHash) -> Result<(), PauseError> {
+        let torrent = self
+            .torrents
+            .get_mut(&hash)
+            .ok_or(PauseError::InvalidMetainfoNotExists { hash })?;
+        let Some(unchoked) = torrent.opt_paused.take() else {
+            return Ok(());
+        };
+
+        let mut messages = vec![OPauseMessage::TorrentResumed(hash)];
+
+        for peer in unchoked {
+            if let Some(is_unchoked) = torrent.peers.get_mut(&peer) {
+                *is_unchoked = true;
+                messages.push(OPauseMessage::Unchoke(peer));
+            }
+        }
+
+        if self.config.tracker_policy == TrackerPausePolicy::AnnounceStopped {
+            messages.push(OPauseMessage::Announce(hash, AnnounceEvent::Started));
+        }
+
+        for message in messages {
+            self.queue_message(message);
+        }
+
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: IPauseMessage) -> Result<(), PauseError> {
+        match message {
+            IPauseMessage::Control(control) => match *control {
+                ControlMessage::AddTorrent(metainfo) => self.add_torrent(&metainfo),
+                ControlMessage::RemoveTorrent(metainfo) => self.remove_torrent(&metainfo),
+                ControlMessage::PeerConnected(info) => self.add_peer(info),
+                ControlMessage::PeerDisconnected(info) => self.remove_peer(info),
+                ControlMessage::Tick(_) => Ok(()),
+            },
+            IPauseMessage::PeerChoked(info, choked) => {
+                self.choke_peer(info, choked);
+                Ok(())
+            }
+            IPauseMessage::Pause(hash) => self.pause(hash),
+            IPauseMessage::Resume(hash) => self.resume(hash),
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), PauseError> {
+        let info_hash = metainfo.info().info_hash();
+
+        match self.torrents.entry(info_hash) {
+            Entry::Occupied(_) => Err(PauseError::InvalidMetainfoExists { hash: info_hash }),
+            Entry::Vacant(vac) => {
+                vac.insert(TorrentState::default());
+
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    fn remove_torrent(&mut self, metainfo: &Metainfo) -> Result<(), PauseError> {
+        let info_hash = metainfo.info().info_hash();
+
+        if self.torrents.remove(&info_hash).is_none() {
+            Err(PauseError::InvalidMetainfoNotExists { hash: info_hash })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_peer(&mut self, peer: PeerInfo) -> Result<(), PauseError> {
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(PauseError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        // Peer connected may be sent multiple times, keep any existing state
+        torrent.peers.entry(peer).or_insert(false);
+
+        if torrent.opt_paused.is_some() && self.config.peer_policy == PeerPausePolicy::Disconnect {
+            self.queue_message(OPauseMessage::DisconnectPeer(peer));
+        }
+
+        Ok(())
+    }
+
+    fn remove_peer(&mut self, peer: PeerInfo) -> Result<(), PauseError> {
+        let info_hash = *peer.hash();
+
+        let Some(torrent) = self.torrents.get_mut(&info_hash) else {
+            return Err(PauseError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        torrent.peers.remove(&peer);
+        if let Some(unchoked) = &mut torrent.opt_paused {
+            unchoked.retain(|info| *info != peer);
+        }
+
+        Ok(())
+    }
+
+    /// Record the choke state of a peer, which is left as is while its torrent is paused.
+    fn choke_peer(&mut self, peer: PeerInfo, choked: bool) {
+        let Some(torrent) = self.torrents.get_mut(peer.hash()) else {
+            return;
+        };
+
+        if torrent.opt_paused.is_none() {
+            if let Some(is_unchoked) = torrent.peers.get_mut(&peer) {
+                *is_unchoked = !choked;
+            }
+        }
+    }
+
+    fn queue_message(&mut self, message: OPauseMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<OPauseMessage, PauseError>>> {
+        if let Some(message) = self.out_queue.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Sink<IPauseMessage> for PauseModule {
+    type Error = PauseError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IPauseMessage) -> Result<(), Self::Error> {
+        self.handle_message(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Stream for PauseModule {
+    type Item = Result<OPauseMessage, PauseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_next_message(cx)
+    }
+}
@@ -0,0 +1,67 @@
+//! Module for pausing and resuming torrents.
+
+use handshake::InfoHash;
+use peer::PeerInfo;
+use utracker::announce::AnnounceEvent;
+
+use crate::ControlMessage;
+
+pub mod error;
+
+mod manager;
+
+pub use self::manager::{PauseModule, PauseModuleBuilder};
+
+/// Enumeration of pause messages that can be sent to a pause module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IPauseMessage {
+    /// Control message.
+    Control(Box<ControlMessage>),
+    /// We choked (or unchoked) the given peer.
+    ///
+    /// Peers that were unchoked when their torrent is paused are unchoked again when it is resumed.
+    PeerChoked(PeerInfo, bool),
+    /// Pause the torrent for the given `InfoHash`, see `PauseModule::pause`.
+    Pause(InfoHash),
+    /// Resume the torrent for the given `InfoHash`, see `PauseModule::resume`.
+    Resume(InfoHash),
+}
+
+/// Enumeration of pause messages that can be received from a pause module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OPauseMessage {
+    /// The torrent for the given `InfoHash` was paused, no more blocks should be requested for it.
+    TorrentPaused(InfoHash),
+    /// The torrent for the given `InfoHash` was resumed, blocks may be requested for it again.
+    TorrentResumed(InfoHash),
+    /// Choke the given peer.
+    Choke(PeerInfo),
+    /// Unchoke the given peer.
+    Unchoke(PeerInfo),
+    /// Disconnect from the given peer.
+    DisconnectPeer(PeerInfo),
+    /// Announce the given event to the trackers of the torrent for the given `InfoHash`.
+    Announce(InfoHash, AnnounceEvent),
+    /// Sync the torrent for the given `InfoHash` to disk, after which no more disk IO is done for it until it is resumed.
+    SyncDisk(InfoHash),
+}
+
+/// What happens to the connected peers of a torrent when it is paused.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PeerPausePolicy {
+    /// Choke every unchoked peer, keeping the connections open so that the torrent resumes quickly.
+    #[default]
+    Choke,
+    /// Disconnect from every peer, including peers connecting while the torrent is paused.
+    Disconnect,
+}
+
+/// What the trackers of a torrent are told when it is paused.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TrackerPausePolicy {
+    /// Announce `Stopped` when paused, and `Started` when resumed, so that we are no longer handed out to peers.
+    #[default]
+    AnnounceStopped,
+    /// Do not announce anything; regular announces should be skipped while the torrent is paused.
+    Silent,
+}
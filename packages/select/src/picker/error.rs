@@ -0,0 +1,10 @@
+//! Module for picker error types.
+
+use thiserror::Error;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PickerError {
+    #[error("Piece Index {index:?} Was Out Of Range For {num_pieces:?} Pieces")]
+    InvalidPieceOutOfRange { index: u64, num_pieces: u64 },
+}
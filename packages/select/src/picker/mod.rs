@@ -0,0 +1,518 @@
+//! Module for picking which piece of a torrent to download next.
+
+use std::cmp::Reverse;
+
+use rand::seq::SliceRandom as _;
+
+use crate::picker::error::PickerError;
+use crate::selection::FileSelection;
+
+pub mod error;
+
+/// Download state of a single piece.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PieceState {
+    Missing,
+    Pending,
+    Partial,
+    Complete,
+}
+
+/// How the first pieces of a download are picked, before switching to rarest first.
+///
+/// Rare pieces are slow to download, as few peers can serve them. A fresh download has nothing to
+/// offer peers in return until it completes a piece, so it is better off completing a few pieces
+/// quickly first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InitialPick {
+    /// Pick pieces at random, so that peers starting a download at the same time end up with different pieces.
+    Random,
+    /// Pick the most available pieces, which can be requested from the most peers.
+    MostAvailable,
+}
+
+/// Picks the next piece of a torrent to download, rarest first.
+///
+/// Optionally, the picker can be made aware of the disk layout of the torrent. Pieces are laid out
+/// back to back in the files of a torrent, so preferring pieces that directly follow pieces that were
+/// already downloaded (or are being downloaded) turns the writes of a torrent into a few sequential
+/// streams. This avoids the seek storms caused by interleaving random writes of multiple torrents
+/// on the same spinning disk, at the cost of picking slightly less rare pieces.
+#[derive(Clone, Debug)]
+pub struct PiecePicker {
+    states: Vec<PieceState>,
+    wanted: Vec<bool>,
+    availability: Vec<u32>,
+    rarity_slack: Option<u32>,
+    initial_pick: Option<(InitialPick, u64)>,
+    max_in_flight: Option<u64>,
+    num_complete: u64,
+}
+
+impl PiecePicker {
+    /// Create a new `PiecePicker` for a torrent with the given number of pieces, all of which are wanted.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn new(num_pieces: u64) -> PiecePicker {
+        let num_pieces = num_pieces as usize;
+
+        PiecePicker {
+            states: vec![PieceState::Missing; num_pieces],
+            wanted: vec![true; num_pieces],
+            availability: vec![0; num_pieces],
+            rarity_slack: None,
+            initial_pick: None,
+            max_in_flight: None,
+            num_complete: 0,
+        }
+    }
+
+    /// Create a new `PiecePicker` that only picks the pieces that have to be downloaded for the given `FileSelection`.
+    #[must_use]
+    pub fn from_selection(selection: &FileSelection) -> PiecePicker {
+        let mut picker = PiecePicker::new(selection.num_pieces());
+
+        for (index, wanted) in picker.wanted.iter_mut().enumerate() {
+            *wanted = selection.should_download(index as u64);
+        }
+
+        picker
+    }
+
+    /// Prefer pieces that continue a contiguous run of downloaded pieces, as long as they are at
+    /// most `rarity_slack` peers more available than the rarest piece that could be picked.
+    ///
+    /// A slack of zero only breaks ties between the rarest pieces by disk layout. Disabled by default.
+    #[must_use]
+    pub fn with_disk_affinity(mut self, rarity_slack: u32) -> PiecePicker {
+        self.rarity_slack = Some(rarity_slack);
+        self
+    }
+
+    /// Pick pieces according to the given `InitialPick` until `num_pieces` pieces are complete, and rarest
+    /// first from then on. Disk affinity only applies once the picker has switched to rarest first.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn with_initial_pick(mut self, initial_pick: InitialPick, num_pieces: u64) -> PiecePicker {
+        self.initial_pick = Some((initial_pick, num_pieces));
+        self
+    }
+
+    /// Pick partially downloaded pieces (see `abort_partial`) before any new piece, regardless of rarity,
+    /// and do not start new pieces while `max_in_flight` pieces are already started but not yet complete.
+    ///
+    /// Incomplete pieces hold memory until they are completed and can only be verified once all of their
+    /// blocks are downloaded, so keeping their number low frees memory and verifies pieces sooner.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub fn with_partial_priority(mut self, max_in_flight: u64) -> PiecePicker {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    /// Number of pieces in the torrent.
+    #[must_use]
+    pub fn num_pieces(&self) -> u64 {
+        self.states.len() as u64
+    }
+
+    /// Number of pieces that were started, either pending or partially downloaded, but not yet complete.
+    #[must_use]
+    pub fn num_in_flight(&self) -> u64 {
+        self.states
+            .iter()
+            .filter(|&&state| state == PieceState::Pending || state == PieceState::Partial)
+            .count() as u64
+    }
+
+    /// Set whether or not the piece at the given index should be picked.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece index is out of range.
+    pub fn set_wanted(&mut self, index: u64, wanted: bool) -> Result<(), PickerError> {
+        let slot = self.slot(index)?;
+        self.wanted[slot] = wanted;
+
+        Ok(())
+    }
+
+    /// Number of connected peers that have the piece at the given index.
+    #[must_use]
+    pub fn availability(&self, index: u64) -> u32 {
+        self.slot(index).map_or(0, |slot| self.availability[slot])
+    }
+
+    /// Record that a peer has the piece at the given index, such as after receiving a have message.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece index is out of range.
+    pub fn add_peer_piece(&mut self, index: u64) -> Result<(), PickerError> {
+        self.add_peer_pieces([index])
+    }
+
+    /// Record that a peer has the pieces at the given indices, such as after receiving a bitfield message.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if any piece index is out of range, in which case no availability is recorded.
+    pub fn add_peer_pieces<I>(&mut self, indices: I) -> Result<(), PickerError>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        for slot in self.slots(indices)? {
+            self.availability[slot] = self.availability[slot].saturating_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// Record that a peer with the pieces at the given indices has disconnected.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if any piece index is out of range, in which case no availability is removed.
+    pub fn remove_peer_pieces<I>(&mut self, indices: I) -> Result<(), PickerError>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        for slot in self.slots(indices)? {
+            self.availability[slot] = self.availability[slot].saturating_sub(1);
+        }
+
+        Ok(())
+    }
+
+    /// Pick the next piece to download from a peer that has the pieces for which `peer_has` returns true.
+    ///
+    /// The picked piece is pending until it is either completed or aborted, and will not be picked again
+    /// in the meantime. Returns None if the peer has no piece that we still need, or if partial priority
+    /// is enabled and no new piece may be started.
+    pub fn pick<F>(&mut self, peer_has: F) -> Option<u64>
+    where
+        F: Fn(u64) -> bool,
+    {
+        if let Some(max_in_flight) = self.max_in_flight {
+            let partial = (0..self.states.len())
+                .filter(|&slot| self.wanted[slot] && self.states[slot] == PieceState::Partial && peer_has(slot as u64))
+                .min_by_key(|&slot| (self.availability[slot], slot));
+
+            if let Some(slot) = partial {
+                self.states[slot] = PieceState::Pending;
+
+                return Some(slot as u64);
+            } else if self.num_in_flight() >= max_in_flight {
+                return None;
+            }
+        }
+
+        let is_candidate = |slot: usize| {
+            self.wanted[slot] && matches!(self.states[slot], PieceState::Missing | PieceState::Partial) && peer_has(slot as u64)
+        };
+
+        let initial_pick = self
+            .initial_pick
+            .filter(|&(_, num_pieces)| self.num_complete < num_pieces)
+            .map(|(initial_pick, _)| initial_pick);
+
+        let slot = match initial_pick {
+            Some(InitialPick::Random) => {
+                let candidates: Vec<usize> = (0..self.states.len()).filter(|&slot| is_candidate(slot)).collect();
+
+                *candidates.choose(&mut rand::thread_rng())?
+            }
+            Some(InitialPick::MostAvailable) => (0..self.states.len())
+                .filter(|&slot| is_candidate(slot))
+                .max_by_key(|&slot| (self.availability[slot], Reverse(slot)))?,
+            None => self.pick_rarest(is_candidate)?,
+        };
+
+        self.states[slot] = PieceState::Pending;
+
+        Some(slot as u64)
+    }
+
+    /// Pick the rarest candidate piece, taking disk affinity into account if enabled.
+    fn pick_rarest<F>(&self, is_candidate: F) -> Option<usize>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let rarest = (0..self.states.len())
+            .filter(|&slot| is_candidate(slot))
+            .map(|slot| self.availability[slot])
+            .min()?;
+
+        let slot = match self.rarity_slack {
+            None => (0..self.states.len()).find(|&slot| is_candidate(slot) && self.availability[slot] == rarest)?,
+            Some(rarity_slack) => {
+                let max_availability = rarest.saturating_add(rarity_slack);
+
+                // Number of missing pieces since the last piece that was started, if any
+                let mut gap: Option<u64> = None;
+                let mut best: Option<(u64, u32, usize)> = None;
+
+                for slot in 0..self.states.len() {
+                    if is_candidate(slot) && self.availability[slot] <= max_availability {
+                        let key = (gap.unwrap_or(u64::MAX), self.availability[slot], slot);
+                        best = Some(best.map_or(key, |best| best.min(key)));
+                    }
+
+                    gap = match self.states[slot] {
+                        PieceState::Missing => gap.map(|gap| gap + 1),
+                        PieceState::Pending | PieceState::Partial | PieceState::Complete => Some(0),
+                    };
+                }
+
+                best?.2
+            }
+        };
+
+        Some(slot)
+    }
+
+    /// Record that the piece at the given index was downloaded and verified.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece index is out of range.
+    pub fn complete(&mut self, index: u64) -> Result<(), PickerError> {
+        let slot = self.slot(index)?;
+        if self.states[slot] != PieceState::Complete {
+            self.states[slot] = PieceState::Complete;
+            self.num_complete += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Record that the download of a pending piece was abandoned, or failed verification, so that it can be picked again.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece index is out of range.
+    pub fn abort(&mut self, index: u64) -> Result<(), PickerError> {
+        let slot = self.slot(index)?;
+        if matches!(self.states[slot], PieceState::Pending | PieceState::Partial) {
+            self.states[slot] = PieceState::Missing;
+        }
+
+        Ok(())
+    }
+
+    /// Record that the download of a pending piece was abandoned after some of its blocks were downloaded,
+    /// such as when the peer it was picked for disconnected, so that it can be picked again.
+    ///
+    /// With partial priority enabled, the piece is picked again before any new piece.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece index is out of range.
+    pub fn abort_partial(&mut self, index: u64) -> Result<(), PickerError> {
+        let slot = self.slot(index)?;
+        if self.states[slot] == PieceState::Pending {
+            self.states[slot] = PieceState::Partial;
+        }
+
+        Ok(())
+    }
+
+    /// Whether or not every wanted piece has been completed.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.states
+            .iter()
+            .zip(&self.wanted)
+            .all(|(&state, &wanted)| !wanted || state == PieceState::Complete)
+    }
+
+    fn slot(&self, index: u64) -> Result<usize, PickerError> {
+        usize::try_from(index)
+            .ok()
+            .filter(|&slot| slot < self.states.len())
+            .ok_or(PickerError::InvalidPieceOutOfRange {
+                index,
+                num_pieces: self.num_pieces(),
+            })
+    }
+
+    fn slots<I>(&self, indices: I) -> Result<Vec<usize>, PickerError>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        indices.into_iter().map(|index| self.slot(index)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InitialPick, PiecePicker};
+    use crate::picker::error::PickerError;
+    use crate::selection::FileSelection;
+
+    /// Eight pieces that every peer has twice, except for piece 6 which only a single peer has.
+    fn eight_pieces() -> PiecePicker {
+        let mut picker = PiecePicker::new(8);
+
+        picker.add_peer_pieces(0..8).unwrap();
+        picker.add_peer_pieces((0..8).filter(|&index| index != 6)).unwrap();
+
+        picker
+    }
+
+    #[test]
+    fn positive_pick_rarest_first() {
+        let mut picker = eight_pieces();
+
+        assert_eq!(Some(6), picker.pick(|_| true));
+        assert_eq!(Some(0), picker.pick(|_| true));
+        assert_eq!(Some(1), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_pick_only_pieces_of_peer() {
+        let mut picker = eight_pieces();
+
+        assert_eq!(Some(3), picker.pick(|index| index == 3));
+        assert_eq!(None, picker.pick(|index| index == 3));
+
+        picker.abort(3).unwrap();
+        assert_eq!(Some(3), picker.pick(|index| index == 3));
+
+        picker.complete(3).unwrap();
+        picker.abort(3).unwrap();
+        assert_eq!(None, picker.pick(|index| index == 3));
+    }
+
+    #[test]
+    fn positive_pick_disk_affinity_continues_run() {
+        let mut picker = eight_pieces().with_disk_affinity(1);
+        picker.complete(2).unwrap();
+
+        // Less rare pieces directly following the completed piece are preferred over the rarest piece
+        assert_eq!(Some(3), picker.pick(|_| true));
+        assert_eq!(Some(4), picker.pick(|_| true));
+        assert_eq!(Some(5), picker.pick(|_| true));
+        assert_eq!(Some(6), picker.pick(|_| true));
+        assert_eq!(Some(7), picker.pick(|_| true));
+        assert_eq!(Some(0), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_pick_disk_affinity_within_rarity_slack() {
+        let mut picker = eight_pieces().with_disk_affinity(0);
+        picker.complete(2).unwrap();
+
+        // Piece 3 is more available than the rarest piece, beyond the slack
+        assert_eq!(Some(6), picker.pick(|_| true));
+        // Among equally rare pieces, the ones continuing a run are preferred
+        assert_eq!(Some(3), picker.pick(|_| true));
+        assert_eq!(Some(4), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_initial_pick_most_available() {
+        let mut picker = PiecePicker::new(8).with_initial_pick(InitialPick::MostAvailable, 1);
+        picker.add_peer_pieces(0..8).unwrap();
+        picker.add_peer_pieces([4, 5]).unwrap();
+        picker.add_peer_pieces([5]).unwrap();
+
+        assert_eq!(Some(5), picker.pick(|_| true));
+        assert_eq!(Some(4), picker.pick(|_| true));
+
+        // Rarest first once enough pieces are complete, a piece completed twice counts once
+        picker.complete(5).unwrap();
+        picker.complete(5).unwrap();
+        picker.remove_peer_pieces([3]).unwrap();
+        assert_eq!(Some(3), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_initial_pick_random_until_threshold() {
+        let mut picker = eight_pieces().with_initial_pick(InitialPick::Random, 2);
+
+        let first = picker.pick(|index| index != 6).unwrap();
+        let second = picker.pick(|index| index != 6).unwrap();
+        assert!(first != 6 && second != 6 && first != second);
+
+        picker.complete(first).unwrap();
+        picker.complete(second).unwrap();
+        assert_eq!(Some(6), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_partial_priority_completes_partial_pieces_first() {
+        let mut picker = eight_pieces().with_partial_priority(8);
+
+        assert_eq!(Some(6), picker.pick(|_| true));
+        assert_eq!(Some(0), picker.pick(|_| true));
+        picker.abort_partial(0).unwrap();
+        picker.abort_partial(6).unwrap();
+        assert_eq!(2, picker.num_in_flight());
+
+        // Partial pieces are picked before the rarest new piece, rarest partial piece first
+        picker.remove_peer_pieces([1]).unwrap();
+        assert_eq!(Some(6), picker.pick(|_| true));
+        assert_eq!(Some(0), picker.pick(|_| true));
+        assert_eq!(Some(1), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn negative_partial_priority_limits_in_flight_pieces() {
+        let mut picker = eight_pieces().with_partial_priority(2);
+
+        assert_eq!(Some(6), picker.pick(|_| true));
+        assert_eq!(Some(0), picker.pick(|_| true));
+        picker.abort_partial(0).unwrap();
+
+        // No new piece is started while two pieces are in flight, only the partial piece can be picked
+        assert_eq!(None, picker.pick(|index| index != 0));
+        assert_eq!(Some(0), picker.pick(|_| true));
+
+        picker.complete(6).unwrap();
+        assert_eq!(Some(1), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_remove_peer_pieces() {
+        let mut picker = eight_pieces();
+
+        picker.remove_peer_pieces(0..8).unwrap();
+
+        assert_eq!(0, picker.availability(6));
+        assert_eq!(1, picker.availability(0));
+        assert_eq!(Some(6), picker.pick(|_| true));
+    }
+
+    #[test]
+    fn positive_from_selection_skips_unwanted_pieces() {
+        let mut selection = FileSelection::new(10, vec![5, 30, 5]).unwrap();
+        selection.set_wanted(1, false).unwrap();
+
+        let mut picker = PiecePicker::from_selection(&selection);
+        picker.add_peer_pieces(0..4).unwrap();
+
+        assert_eq!(Some(0), picker.pick(|_| true));
+        assert_eq!(Some(3), picker.pick(|_| true));
+        assert_eq!(None, picker.pick(|_| true));
+
+        picker.complete(0).unwrap();
+        assert!(!picker.is_complete());
+        picker.complete(3).unwrap();
+        assert!(picker.is_complete());
+    }
+
+    #[test]
+    fn negative_piece_out_of_range() {
+        let mut picker = eight_pieces();
+
+        assert_eq!(
+            Err(PickerError::InvalidPieceOutOfRange { index: 8, num_pieces: 8 }),
+            picker.add_peer_pieces(6..9)
+        );
+        assert_eq!(1, picker.availability(6));
+        assert!(picker.complete(8).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Priority classes for sharing global resources between torrents.
+
+/// Priority class of a torrent, weighting its share of resources that are capped across all torrents.
+///
+/// Priorities only matter while a global cap is saturated; until then, every torrent gets what it asks for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TorrentPriority {
+    /// Gets half the share of a normal priority torrent.
+    Low,
+    /// Share that torrents get unless told otherwise.
+    #[default]
+    Normal,
+    /// Gets twice the share of a normal priority torrent.
+    High,
+}
+
+impl TorrentPriority {
+    /// Relative weight of the priority class.
+    #[must_use]
+    pub fn weight(self) -> usize {
+        match self {
+            TorrentPriority::Low => 1,
+            TorrentPriority::Normal => 2,
+            TorrentPriority::High => 4,
+        }
+    }
+}
+
+/// Divide the total between the demands, in proportion to the weight of their priorities.
+///
+/// No demand is given more than it asked for; whatever it does not need is divided between the
+/// others. Units left over from rounding go to the higher priorities first.
+pub(crate) fn weighted_shares(total: usize, demands: &[(TorrentPriority, usize)]) -> Vec<usize> {
+    let mut shares = vec![0; demands.len()];
+    let mut remaining = total;
+
+    loop {
+        let mut unmet: Vec<usize> = (0..demands.len()).filter(|&index| shares[index] < demands[index].1).collect();
+        if unmet.is_empty() || remaining == 0 {
+            return shares;
+        }
+
+        let total_weight: usize = unmet.iter().map(|&index| demands[index].0.weight()).sum();
+        let mut given = 0;
+        for &index in &unmet {
+            let (priority, demand) = demands[index];
+            let share = remaining.saturating_mul(priority.weight()) / total_weight;
+            let give = share.min(demand - shares[index]);
+
+            shares[index] += give;
+            given += give;
+        }
+
+        if given == 0 {
+            // Every share rounded down to nothing, so hand out the rest one unit at a time
+            unmet.sort_by_key(|&index| std::cmp::Reverse(demands[index].0));
+            for index in unmet.into_iter().take(remaining) {
+                shares[index] += 1;
+                given += 1;
+            }
+        }
+
+        remaining -= given;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TorrentPriority;
+
+    #[test]
+    fn positive_shares_weighted_by_priority() {
+        let demands = [(TorrentPriority::Normal, 10), (TorrentPriority::High, 10)];
+
+        assert_eq!(vec![2, 4], super::weighted_shares(6, &demands));
+        // Rounding leftovers go to the higher priority
+        assert_eq!(vec![1, 3], super::weighted_shares(4, &demands));
+    }
+
+    #[test]
+    fn positive_unused_share_redistributed() {
+        let demands = [
+            (TorrentPriority::High, 1),
+            (TorrentPriority::Normal, 10),
+            (TorrentPriority::Low, 10),
+        ];
+
+        assert_eq!(vec![1, 6, 3], super::weighted_shares(10, &demands));
+    }
+
+    #[test]
+    fn positive_unsaturated_demands_met() {
+        let demands = [(TorrentPriority::Low, 3), (TorrentPriority::High, 2)];
+
+        assert_eq!(vec![3, 2], super::weighted_shares(100, &demands));
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use futures::{Sink, Stream};
+use handshake::InfoHash;
+use metainfo::Metainfo;
+use tracing::instrument;
+
+use crate::queue::error::QueueError;
+use crate::queue::{IQueueMessage, OQueueMessage, QueueState};
+use crate::ControlMessage;
+
+const DEFAULT_MAX_ACTIVE_TORRENTS: usize = 5;
+
+/// Builder for configuring the number of active slots of a `QueueModule`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug)]
+pub struct QueueModuleBuilder {
+    max_active: usize,
+}
+
+impl Default for QueueModuleBuilder {
+    fn default() -> Self {
+        QueueModuleBuilder {
+            max_active: DEFAULT_MAX_ACTIVE_TORRENTS,
+        }
+    }
+}
+
+impl QueueModuleBuilder {
+    #[must_use]
+    pub fn new() -> QueueModuleBuilder {
+        QueueModuleBuilder::default()
+    }
+
+    /// Number of torrents, downloading or seeding, that may be active at the same time.
+    ///
+    /// Force started torrents are not counted against this limit.
+    #[must_use]
+    pub fn with_max_active_torrents(mut self, max: usize) -> QueueModuleBuilder {
+        self.max_active = max;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> QueueModule {
+        QueueModule::from_builder(self)
+    }
+}
+
+/// Module for limiting the number of active torrents, starting queued torrents in priority order as slots free up.
+#[allow(clippy::module_name_repetitions)]
+pub struct QueueModule {
+    config: QueueModuleBuilder,
+    // Ordered from highest to lowest priority
+    order: Vec<InfoHash>,
+    forced: HashSet<InfoHash>,
+    states: HashMap<InfoHash, QueueState>,
+    out_queue: VecDeque<OQueueMessage>,
+    opt_stream_waker: Option<Waker>,
+}
+
+impl QueueModule {
+    #[must_use]
+    pub fn from_builder(builder: QueueModuleBuilder) -> QueueModule {
+        QueueModule {
+            config: builder,
+            order: Vec::new(),
+            forced: HashSet::new(),
+            states: HashMap::new(),
+            out_queue: VecDeque::new(),
+            opt_stream_waker: None,
+        }
+    }
+
+    /// Current state of the torrent for the given `InfoHash`, if it was added.
+    #[must_use]
+    pub fn state(&self, hash: &InfoHash) -> Option<QueueState> {
+        self.states.get(hash).copied()
+    }
+
+    /// Current position of the torrent for the given `InfoHash` in the queue, if it was added.
+    #[must_use]
+    pub fn position(&self, hash: &InfoHash) -> Option<usize> {
+        self.order.iter().position(|queued| queued == hash)
+    }
+
+    fn handle_message(&mut self, message: IQueueMessage) -> Result<(), QueueError> {
+        match message {
+            IQueueMessage::Control(control) => match *control {
+                ControlMessage::AddTorrent(metainfo) => self.add_torrent(&metainfo)?,
+                ControlMessage::RemoveTorrent(metainfo) => self.remove_torrent(&metainfo)?,
+                _ => return Ok(()),
+            },
+            IQueueMessage::SetPosition(hash, position) => self.set_position(hash, position)?,
+            IQueueMessage::ForceStart(hash, forced) => self.force_start(hash, forced)?,
+        }
+
+        self.schedule();
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn add_torrent(&mut self, metainfo: &Metainfo) -> Result<(), QueueError> {
+        let info_hash = metainfo.info().info_hash();
+
+        if self.order.contains(&info_hash) {
+            return Err(QueueError::InvalidMetainfoExists { hash: info_hash });
+        }
+        self.order.push(info_hash);
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn remove_torrent(&mut self, metainfo: &Metainfo) -> Result<(), QueueError> {
+        let info_hash = metainfo.info().info_hash();
+        let position = self.existing_position(info_hash)?;
+
+        self.order.remove(position);
+        self.forced.remove(&info_hash);
+        self.states.remove(&info_hash);
+
+        Ok(())
+    }
+
+    fn set_position(&mut self, hash: InfoHash, position: usize) -> Result<(), QueueError> {
+        let current = self.existing_position(hash)?;
+
+        self.order.remove(current);
+        self.order.insert(position.min(self.order.len()), hash);
+
+        Ok(())
+    }
+
+    fn force_start(&mut self, hash: InfoHash, forced: bool) -> Result<(), QueueError> {
+        self.existing_position(hash)?;
+
+        if forced {
+            self.forced.insert(hash);
+        } else {
+            self.forced.remove(&hash);
+        }
+
+        Ok(())
+    }
+
+    fn existing_position(&self, hash: InfoHash) -> Result<usize, QueueError> {
+        self.position(&hash).ok_or(QueueError::InvalidMetainfoNotExists { hash })
+    }
+
+    /// Assign active slots in priority order, sending a message for every torrent whose state changed.
+    fn schedule(&mut self) {
+        let mut free_slots = self.config.max_active;
+        let mut changed = Vec::new();
+
+        for hash in &self.order {
+            let state = if self.forced.contains(hash) {
+                QueueState::ForceStarted
+            } else if free_slots != 0 {
+                free_slots -= 1;
+                QueueState::Active
+            } else {
+                QueueState::Queued
+            };
+
+            if self.states.insert(*hash, state) != Some(state) {
+                changed.push(OQueueMessage::StateChanged(*hash, state));
+            }
+        }
+
+        // Torrents leaving an active slot are reported before the torrents taking them over
+        changed.sort_by_key(|OQueueMessage::StateChanged(_, state)| *state != QueueState::Queued);
+
+        for message in changed {
+            self.queue_message(message);
+        }
+    }
+
+    fn queue_message(&mut self, message: OQueueMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<OQueueMessage, QueueError>>> {
+        if let Some(message) = self.out_queue.pop_front() {
+            Poll::Ready(Some(Ok(message)))
+        } else {
+            self.opt_stream_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Sink<IQueueMessage> for QueueModule {
+    type Error = QueueError;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IQueueMessage) -> Result<(), Self::Error> {
+        self.handle_message(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Stream for QueueModule {
+    type Item = Result<OQueueMessage, QueueError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_next_message(cx)
+    }
+}
@@ -0,0 +1,44 @@
+//! Module for scheduling which torrents are active.
+
+use handshake::InfoHash;
+
+use crate::ControlMessage;
+
+pub mod error;
+
+mod manager;
+
+pub use self::manager::{QueueModule, QueueModuleBuilder};
+
+/// Enumeration of queue messages that can be sent to a queue module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IQueueMessage {
+    /// Control message.
+    ///
+    /// Added torrents are placed at the end of the queue.
+    Control(Box<ControlMessage>),
+    /// Move the torrent for the given `InfoHash` to the given position in the queue, zero being the highest priority.
+    SetPosition(InfoHash, usize),
+    /// Start (or stop force starting) the torrent for the given `InfoHash` regardless of the active limit.
+    ///
+    /// Force started torrents do not take up an active slot.
+    ForceStart(InfoHash, bool),
+}
+
+/// Enumeration of queue messages that can be received from a queue module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OQueueMessage {
+    /// The torrent for the given `InfoHash` transitioned to the given state.
+    StateChanged(InfoHash, QueueState),
+}
+
+/// State of a torrent within the queue.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum QueueState {
+    /// Torrent is waiting for an active slot to free up.
+    Queued,
+    /// Torrent is downloading or seeding in an active slot.
+    Active,
+    /// Torrent is downloading or seeding without taking up an active slot.
+    ForceStarted,
+}
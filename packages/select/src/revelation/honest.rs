@@ -1,19 +1,18 @@
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, VecDeque};
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
-use bit_set::BitSet;
-use bytes::{BufMut, BytesMut};
 use futures::{Sink, Stream};
 use handshake::InfoHash;
 use metainfo::Metainfo;
-use peer::messages::{BitFieldMessage, HaveMessage};
-use peer::PeerInfo;
+use peer::messages::HaveMessage;
+use peer::{Bitfield, PeerInfo};
 use tracing::instrument;
 
 use crate::revelation::error::RevealError;
-use crate::revelation::{IRevealMessage, ORevealMessage};
+use crate::revelation::{IRevealMessage, ORevealMessage, RevealConfig};
 use crate::ControlMessage;
 
 #[allow(clippy::module_name_repetitions)]
@@ -21,7 +20,7 @@ use crate::ControlMessage;
 pub struct HonestRevealModuleBuilder {
     torrents: HashMap<InfoHash, PeersInfo>,
     out_queue: VecDeque<ORevealMessage>,
-    out_bytes: BytesMut,
+    config: RevealConfig,
 }
 
 impl HonestRevealModuleBuilder {
@@ -30,10 +29,17 @@ impl HonestRevealModuleBuilder {
         HonestRevealModuleBuilder {
             torrents: HashMap::new(),
             out_queue: VecDeque::new(),
-            out_bytes: BytesMut::new(),
+            config: RevealConfig::default(),
         }
     }
 
+    /// Sets the `RevealConfig` used by torrents that have not been given their own.
+    #[must_use]
+    pub fn with_default_config(mut self, config: RevealConfig) -> HonestRevealModuleBuilder {
+        self.config = config;
+        self
+    }
+
     #[must_use]
     pub fn build(self) -> HonestRevealModule {
         HonestRevealModule::from_builder(self)
@@ -41,16 +47,70 @@ impl HonestRevealModuleBuilder {
 }
 
 struct PeersInfo {
-    num_pieces: usize,
-    status: BitSet<u8>,
-    peers: HashSet<PeerInfo>,
+    status: Bitfield,
+    config: RevealConfig,
+    peers: HashMap<PeerInfo, PeerPieces>,
+}
+
+/// Pieces a peer has revealed to us, and whether we told them we are interested.
+struct PeerPieces {
+    pieces: Bitfield,
+    interested: bool,
+    // Time we have been uninterested for, while still waiting to send a `NotInterested` message
+    uninterested_for: Option<Duration>,
 }
 
+impl PeerPieces {
+    /// Re-evaluate our interest in the peer given the pieces we have, returning a message if it changed.
+    ///
+    /// Losing interest is only reported right away if there is no hysteresis, otherwise it is left to `tick_interest`.
+    fn update_interest(&mut self, info: PeerInfo, status: &Bitfield, hysteresis: Duration) -> Option<ORevealMessage> {
+        let interested = !self.pieces.is_subset(status);
+
+        if interested {
+            self.uninterested_for = None;
+
+            if self.interested {
+                None
+            } else {
+                self.interested = true;
+                Some(ORevealMessage::SendInterested(info))
+            }
+        } else if !self.interested {
+            None
+        } else if hysteresis.is_zero() {
+            self.interested = false;
+            Some(ORevealMessage::SendNotInterested(info))
+        } else {
+            self.uninterested_for.get_or_insert(Duration::ZERO);
+            None
+        }
+    }
+
+    /// Advance the time we have been uninterested for, returning a message once it reaches the hysteresis.
+    fn tick_interest(&mut self, info: PeerInfo, elapsed: Duration, hysteresis: Duration) -> Option<ORevealMessage> {
+        let uninterested_for = self.uninterested_for.as_mut()?;
+        *uninterested_for += elapsed;
+
+        if *uninterested_for < hysteresis {
+            return None;
+        }
+        self.uninterested_for = None;
+        self.interested = false;
+
+        Some(ORevealMessage::SendNotInterested(info))
+    }
+}
+
+/// Module that honestly reveals our pieces to peers.
+///
+/// Pieces revealed by peers are tracked as well, so that `Interested` and `NotInterested` messages are sent
+/// whenever a peer starts or stops having pieces that we are missing.
 #[allow(clippy::module_name_repetitions)]
 pub struct HonestRevealModule {
     torrents: HashMap<InfoHash, PeersInfo>,
     out_queue: VecDeque<ORevealMessage>,
-    out_bytes: BytesMut,
+    config: RevealConfig,
     opt_stream_waker: Option<Waker>,
 }
 
@@ -60,7 +120,7 @@ impl HonestRevealModule {
         HonestRevealModule {
             torrents: builder.torrents,
             out_queue: builder.out_queue,
-            out_bytes: builder.out_bytes,
+            config: builder.config,
             opt_stream_waker: None,
         }
     }
@@ -72,9 +132,22 @@ impl HonestRevealModule {
             IRevealMessage::Control(ControlMessage::PeerConnected(info)) => self.add_peer(info),
             IRevealMessage::Control(ControlMessage::PeerDisconnected(info)) => self.remove_peer(info),
             IRevealMessage::FoundGoodPiece(hash, index) => self.insert_piece(hash, index),
-            IRevealMessage::Control(ControlMessage::Tick(_))
-            | IRevealMessage::ReceivedBitField(_, _)
-            | IRevealMessage::ReceivedHave(_, _) => Ok(()),
+            IRevealMessage::ReceivedBitField(info, bitfield) => self.insert_peer_pieces(info, |pieces| {
+                // Spare bits at the end of a bitfield are ignored
+                pieces.union_with(&Bitfield::from_message(&bitfield, pieces.len()));
+            }),
+            IRevealMessage::ReceivedHave(info, have) => self.insert_peer_pieces(info, |pieces| {
+                let index = have.piece_index() as usize;
+
+                if index < pieces.len() {
+                    pieces.set(index);
+                }
+            }),
+            IRevealMessage::SetConfig(hash, config) => self.set_config(hash, config),
+            IRevealMessage::Control(ControlMessage::Tick(duration)) => {
+                self.tick(duration);
+                Ok(())
+            }
         }
     }
 
@@ -92,13 +165,10 @@ impl HonestRevealModule {
             Entry::Vacant(vac) => {
                 let num_pieces = metainfo.info().pieces().count();
 
-                let mut piece_set = BitSet::default();
-                piece_set.reserve_len_exact(num_pieces);
-
                 let peers_info = PeersInfo {
-                    num_pieces,
-                    status: piece_set,
-                    peers: HashSet::new(),
+                    status: Bitfield::new(num_pieces),
+                    config: self.config,
+                    peers: HashMap::new(),
                 };
                 vac.insert(peers_info);
 
@@ -125,23 +195,22 @@ impl HonestRevealModule {
         tracing::trace!("adding peer");
         let info_hash = *peer.hash();
 
-        let out_bytes = &mut self.out_bytes;
         let out_queue = &mut self.out_queue;
         let Some(peers_info) = self.torrents.get_mut(&info_hash) else {
             tracing::error!("adding peer error");
             return Err(RevealError::InvalidMetainfoNotExists { hash: info_hash });
         };
 
-        peers_info.peers.insert(peer);
-
-        if !peers_info.status.is_empty() {
-            let bitfield_slice = peers_info.status.get_ref().storage();
-            insert_reversed_bits(out_bytes, bitfield_slice);
-
-            let bitfield_bytes = out_bytes.split_off(0).freeze();
-            let bitfield = BitFieldMessage::new(bitfield_bytes);
+        // Peer connected may be sent multiple times, keep any pieces they already revealed
+        let num_pieces = peers_info.status.len();
+        peers_info.peers.entry(peer).or_insert_with(|| PeerPieces {
+            pieces: Bitfield::new(num_pieces),
+            interested: false,
+            uninterested_for: None,
+        });
 
-            let message = ORevealMessage::SendBitField(peer, bitfield);
+        if !peers_info.status.is_clear() {
+            let message = ORevealMessage::SendBitField(peer, peers_info.status.to_message());
             tracing::trace!("sending message: {message:?}");
 
             out_queue.push_back(message);
@@ -170,36 +239,108 @@ impl HonestRevealModule {
     fn insert_piece(&mut self, hash: InfoHash, index: u64) -> Result<(), RevealError> {
         tracing::trace!("inserting piece");
 
-        let out_queue = &mut self.out_queue;
         let Some(peers_info) = self.torrents.get_mut(&hash) else {
             return Err(RevealError::InvalidMetainfoNotExists { hash });
         };
 
         let index: usize = index.try_into().unwrap();
 
-        if index >= peers_info.num_pieces {
+        if index >= peers_info.status.len() {
             Err(RevealError::InvalidPieceOutOfRange {
                 index: index.try_into().unwrap(),
                 hash,
             })
         } else {
-            for peer in &peers_info.peers {
-                let message = ORevealMessage::SendHave(*peer, HaveMessage::new(index.try_into().unwrap()));
-                tracing::trace!("sending message: {message:?}");
+            let config = peers_info.config;
+
+            let mut messages = Vec::new();
+            for (info, peer) in &peers_info.peers {
+                if config.have_suppression() && peer.pieces.get(index) {
+                    continue;
+                }
 
-                out_queue.push_back(message);
+                messages.push(ORevealMessage::SendHave(*info, HaveMessage::new(index.try_into().unwrap())));
+            }
 
-                if let Some(waker) = self.opt_stream_waker.take() {
-                    waker.wake();
+            peers_info.status.set(index);
+
+            // Peers that only had pieces we now have are no longer interesting
+            for (info, peer) in &mut peers_info.peers {
+                if peer.interested && peer.pieces.get(index) {
+                    messages.extend(peer.update_interest(*info, &peers_info.status, config.interest_hysteresis()));
                 }
             }
 
-            peers_info.status.insert(index);
+            for message in messages {
+                self.queue_message(message);
+            }
 
             Ok(())
         }
     }
 
+    #[instrument(skip(self, insert))]
+    fn insert_peer_pieces<F>(&mut self, info: PeerInfo, insert: F) -> Result<(), RevealError>
+    where
+        F: FnOnce(&mut Bitfield),
+    {
+        let info_hash = *info.hash();
+
+        let Some(peers_info) = self.torrents.get_mut(&info_hash) else {
+            return Err(RevealError::InvalidMetainfoNotExists { hash: info_hash });
+        };
+
+        // Pieces may be revealed before we are told about the peer, in which case they are dropped
+        let Some(peer) = peers_info.peers.get_mut(&info) else {
+            tracing::trace!("ignoring pieces from unknown peer");
+            return Ok(());
+        };
+
+        insert(&mut peer.pieces);
+
+        if let Some(message) = peer.update_interest(info, &peers_info.status, peers_info.config.interest_hysteresis()) {
+            self.queue_message(message);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn set_config(&mut self, hash: InfoHash, config: RevealConfig) -> Result<(), RevealError> {
+        let Some(peers_info) = self.torrents.get_mut(&hash) else {
+            return Err(RevealError::InvalidMetainfoNotExists { hash });
+        };
+
+        peers_info.config = config;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, elapsed: Duration) {
+        let mut messages = Vec::new();
+
+        for peers_info in self.torrents.values_mut() {
+            let hysteresis = peers_info.config.interest_hysteresis();
+
+            for (info, peer) in &mut peers_info.peers {
+                messages.extend(peer.tick_interest(*info, elapsed, hysteresis));
+            }
+        }
+
+        for message in messages {
+            self.queue_message(message);
+        }
+    }
+
+    fn queue_message(&mut self, message: ORevealMessage) {
+        tracing::trace!("sending message: {message:?}");
+
+        self.out_queue.push_back(message);
+        if let Some(waker) = self.opt_stream_waker.take() {
+            waker.wake();
+        }
+    }
+
     #[instrument(skip(self))]
     fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<ORevealMessage, RevealError>>> {
         tracing::trace!("polling for next message");
@@ -244,17 +385,3 @@ impl Stream for HonestRevealModule {
         self.poll_next_message(cx)
     }
 }
-
-fn insert_reversed_bits(bytes: &mut BytesMut, slice: &[u8]) {
-    for mut byte in slice.iter().copied() {
-        let mut reversed_byte = 0;
-
-        for _ in 0..8 {
-            reversed_byte <<= 1;
-            reversed_byte |= byte & 0x01;
-            byte >>= 1;
-        }
-
-        bytes.put_u8(reversed_byte);
-    }
-}
@@ -1,5 +1,7 @@
 //! Module for piece revelation.
 
+use std::time::Duration;
+
 use handshake::InfoHash;
 use peer::messages::{BitFieldMessage, HaveMessage};
 use peer::PeerInfo;
@@ -23,6 +25,8 @@ pub enum IRevealMessage {
     ReceivedBitField(PeerInfo, BitFieldMessage),
     /// Received a `HaveMessage`.
     ReceivedHave(PeerInfo, HaveMessage),
+    /// Set the `RevealConfig` for the torrent with the given `InfoHash`, replacing the default of the module.
+    SetConfig(InfoHash, RevealConfig),
 }
 
 /// Enumeration of revelation messages that can be received from a revelation module.
@@ -32,4 +36,48 @@ pub enum ORevealMessage {
     SendBitField(PeerInfo, BitFieldMessage),
     /// Send a `HaveMessage`.
     SendHave(PeerInfo, HaveMessage),
+    /// Send an `Interested` message, the peer has pieces that we are missing.
+    SendInterested(PeerInfo),
+    /// Send a `NotInterested` message, the peer no longer has pieces that we are missing.
+    SendNotInterested(PeerInfo),
+}
+
+/// Policies for cutting down on redundant messages sent to peers.
+///
+/// By default, every message is sent.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RevealConfig {
+    have_suppression: bool,
+    interest_hysteresis: Duration,
+}
+
+impl RevealConfig {
+    /// Whether or not to skip sending a `HaveMessage` to peers that already have the piece.
+    #[must_use]
+    pub fn with_have_suppression(mut self, suppress: bool) -> RevealConfig {
+        self.have_suppression = suppress;
+        self
+    }
+
+    /// Sets how long we have to stay uninterested in a peer before a `NotInterested` message is sent.
+    ///
+    /// If the peer becomes interesting again within this time, neither message is sent.
+    #[must_use]
+    pub fn with_interest_hysteresis(mut self, hysteresis: Duration) -> RevealConfig {
+        self.interest_hysteresis = hysteresis;
+        self
+    }
+
+    /// Whether or not `HaveMessage`s are suppressed for peers that already have the piece.
+    #[must_use]
+    pub fn have_suppression(&self) -> bool {
+        self.have_suppression
+    }
+
+    /// How long we have to stay uninterested in a peer before a `NotInterested` message is sent.
+    #[must_use]
+    pub fn interest_hysteresis(&self) -> Duration {
+        self.interest_hysteresis
+    }
 }
@@ -0,0 +1,12 @@
+//! Module for selection error types.
+
+use thiserror::Error;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SelectionError {
+    #[error("File Index {index:?} Was Out Of Range For {num_files:?} Files")]
+    InvalidFileOutOfRange { index: usize, num_files: usize },
+    #[error("Piece Length Of Zero Is Invalid")]
+    InvalidPieceLength,
+}
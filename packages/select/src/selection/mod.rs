@@ -0,0 +1,360 @@
+//! Module for downloading a subset of the files in a torrent.
+
+use std::ops::Range;
+
+use metainfo::Metainfo;
+
+use crate::selection::error::SelectionError;
+
+pub mod error;
+
+/// How a piece relates to the files that were selected for download.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PieceSelection {
+    /// Piece only contains bytes of skipped files, and does not need to be downloaded.
+    Skipped,
+    /// Piece only contains bytes of wanted files.
+    Wanted,
+    /// Piece overlaps both wanted and skipped files.
+    ///
+    /// The piece still has to be downloaded in full to be verified against its hash, but only
+    /// the given ranges, relative to the start of the piece, belong to wanted files.
+    Boundary(Vec<Range<u64>>),
+}
+
+/// What should be done with a block received for a piece.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Write the whole block.
+    Write,
+    /// Discard the whole block.
+    Discard,
+    /// Write only the given ranges, relative to the start of the block, and discard the rest.
+    WriteRanges(Vec<Range<u64>>),
+}
+
+/// Selection of the files of a torrent that should be downloaded.
+///
+/// Pieces at the boundary between a wanted and a skipped file are still downloaded, as
+/// pieces can only be verified as a whole, but only bytes belonging to wanted files are
+/// counted as required for completion. Optionally, the bytes of skipped files within
+/// boundary pieces can be discarded instead of written to disk; they then have to be
+/// downloaded again if the file is selected later on.
+#[derive(Clone, Debug)]
+pub struct FileSelection {
+    piece_length: u64,
+    total_length: u64,
+    // Byte range of each file within the torrent, along with whether it is wanted
+    files: Vec<(Range<u64>, bool)>,
+    discard_unwanted: bool,
+}
+
+impl FileSelection {
+    /// Create a new `FileSelection` from the piece length and the lengths of the files, in torrent order.
+    ///
+    /// All files are initially wanted.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece length is zero.
+    pub fn new<I>(piece_length: u64, file_lengths: I) -> Result<FileSelection, SelectionError>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        if piece_length == 0 {
+            return Err(SelectionError::InvalidPieceLength);
+        }
+
+        let mut total_length = 0;
+        let files = file_lengths
+            .into_iter()
+            .map(|length| {
+                let start = total_length;
+                total_length += length;
+
+                (start..total_length, true)
+            })
+            .collect();
+
+        Ok(FileSelection {
+            piece_length,
+            total_length,
+            files,
+            discard_unwanted: false,
+        })
+    }
+
+    /// Create a new `FileSelection` for the files of the given `Metainfo`, with all files initially wanted.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the piece length of the `Metainfo` is zero.
+    pub fn from_metainfo(metainfo: &Metainfo) -> Result<FileSelection, SelectionError> {
+        let info = metainfo.info();
+
+        FileSelection::new(info.piece_length(), info.files().map(metainfo::File::length))
+    }
+
+    /// Whether or not bytes of skipped files within boundary pieces should be discarded instead of written.
+    ///
+    /// Defaults to false.
+    #[must_use]
+    pub fn with_discard_unwanted(mut self, discard: bool) -> FileSelection {
+        self.discard_unwanted = discard;
+        self
+    }
+
+    /// Set whether or not the file at the given index, in torrent order, is wanted.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the file index is out of range.
+    pub fn set_wanted(&mut self, index: usize, wanted: bool) -> Result<(), SelectionError> {
+        let num_files = self.files.len();
+        let file = self
+            .files
+            .get_mut(index)
+            .ok_or(SelectionError::InvalidFileOutOfRange { index, num_files })?;
+
+        file.1 = wanted;
+
+        Ok(())
+    }
+
+    /// Whether or not the file at the given index is wanted.
+    #[must_use]
+    pub fn is_wanted(&self, index: usize) -> bool {
+        self.files.get(index).is_some_and(|(_, wanted)| *wanted)
+    }
+
+    /// Number of pieces in the torrent.
+    #[must_use]
+    #[allow(clippy::manual_div_ceil)] // `u64::div_ceil` is newer than our minimum supported rust version
+    pub fn num_pieces(&self) -> u64 {
+        (self.total_length + self.piece_length - 1) / self.piece_length
+    }
+
+    /// How the piece at the given index relates to the wanted files.
+    ///
+    /// Pieces out of range are reported as skipped.
+    #[must_use]
+    pub fn piece(&self, index: u64) -> PieceSelection {
+        let piece = self.piece_range(index);
+        let wanted = self.wanted_ranges(&piece);
+
+        match &wanted[..] {
+            [] => PieceSelection::Skipped,
+            [only] if *only == piece => PieceSelection::Wanted,
+            _ => PieceSelection::Boundary(
+                wanted
+                    .into_iter()
+                    .map(|range| (range.start - piece.start)..(range.end - piece.start))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether or not the piece at the given index has to be downloaded.
+    #[must_use]
+    pub fn should_download(&self, index: u64) -> bool {
+        self.piece(index) != PieceSelection::Skipped
+    }
+
+    /// Indices of every piece that has to be downloaded, including boundary pieces.
+    pub fn wanted_pieces(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.num_pieces()).filter(|&index| self.should_download(index))
+    }
+
+    /// Number of bytes in the piece at the given index that belong to wanted files.
+    #[must_use]
+    pub fn required_bytes(&self, index: u64) -> u64 {
+        let piece = self.piece_range(index);
+
+        self.wanted_ranges(&piece).iter().map(|range| range.end - range.start).sum()
+    }
+
+    /// Number of bytes, across all pieces, that belong to wanted files.
+    #[must_use]
+    pub fn total_required_bytes(&self) -> u64 {
+        self.wanted_ranges(&(0..self.total_length))
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    /// Number of required bytes covered by the given completed pieces.
+    ///
+    /// Completion of the selection is reached once this equals `total_required_bytes`.
+    pub fn completed_bytes<I>(&self, completed_pieces: I) -> u64
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        completed_pieces.into_iter().map(|index| self.required_bytes(index)).sum()
+    }
+
+    /// What should be done with the given block of the piece at the given index.
+    ///
+    /// Unless discarding unwanted bytes was enabled, every block of a piece that is downloaded is written in full.
+    #[must_use]
+    pub fn block_action(&self, index: u64, block_offset: u64, block_length: u64) -> BlockAction {
+        if !self.discard_unwanted {
+            return BlockAction::Write;
+        }
+
+        let block_start = self.piece_range(index).start + block_offset;
+        let block = block_start..(block_start + block_length).min(self.total_length);
+        let wanted = self.wanted_ranges(&block);
+
+        match &wanted[..] {
+            [] => BlockAction::Discard,
+            [only] if *only == block => BlockAction::Write,
+            _ => BlockAction::WriteRanges(
+                wanted
+                    .into_iter()
+                    .map(|range| (range.start - block.start)..(range.end - block.start))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn piece_range(&self, index: u64) -> Range<u64> {
+        let start = index.saturating_mul(self.piece_length).min(self.total_length);
+        let end = start.saturating_add(self.piece_length).min(self.total_length);
+
+        start..end
+    }
+
+    /// Ranges of bytes within the given range that belong to wanted files, with adjacent ranges merged.
+    fn wanted_ranges(&self, range: &Range<u64>) -> Vec<Range<u64>> {
+        let first_file = self.files.partition_point(|(file, _)| file.end <= range.start);
+
+        let mut wanted: Vec<Range<u64>> = Vec::new();
+        for (file, _) in self.files[first_file..]
+            .iter()
+            .take_while(|(file, _)| file.start < range.end)
+            .filter(|(file, wanted)| *wanted && !file.is_empty())
+        {
+            let overlap = file.start.max(range.start)..file.end.min(range.end);
+
+            match wanted.last_mut() {
+                Some(last) if last.end == overlap.start => last.end = overlap.end,
+                _ => wanted.push(overlap),
+            }
+        }
+
+        wanted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::{BlockAction, FileSelection, PieceSelection};
+    use crate::selection::error::SelectionError;
+
+    /// Three files over four pieces of 10 bytes, with both boundaries falling inside a piece.
+    ///
+    /// ```text
+    /// pieces: |0         |1         |2         |3     |
+    /// files:  |0            |1           |2           |
+    ///         0            13           25           36
+    /// ```
+    fn three_files() -> FileSelection {
+        FileSelection::new(10, vec![13, 12, 11]).unwrap()
+    }
+
+    #[test]
+    fn positive_all_wanted() {
+        let selection = three_files();
+
+        assert_eq!(4, selection.num_pieces());
+        assert_eq!(vec![0, 1, 2, 3], selection.wanted_pieces().collect::<Vec<_>>());
+        assert_eq!(PieceSelection::Wanted, selection.piece(1));
+        assert_eq!(36, selection.total_required_bytes());
+        assert_eq!(6, selection.required_bytes(3));
+    }
+
+    #[test]
+    fn positive_skipped_middle_file_boundary_pieces() {
+        let mut selection = three_files();
+        selection.set_wanted(1, false).unwrap();
+
+        assert_eq!(PieceSelection::Wanted, selection.piece(0));
+        assert_eq!(PieceSelection::Boundary(vec![Range { start: 0, end: 3 }]), selection.piece(1));
+        assert_eq!(
+            PieceSelection::Boundary(vec![Range { start: 5, end: 10 }]),
+            selection.piece(2)
+        );
+        assert_eq!(PieceSelection::Wanted, selection.piece(3));
+
+        // Boundary pieces are still downloaded, but only count their wanted bytes
+        assert_eq!(vec![0, 1, 2, 3], selection.wanted_pieces().collect::<Vec<_>>());
+        assert_eq!(3, selection.required_bytes(1));
+        assert_eq!(24, selection.total_required_bytes());
+        assert_eq!(24, selection.completed_bytes(0..4));
+    }
+
+    #[test]
+    fn positive_piece_within_skipped_file() {
+        let mut selection = FileSelection::new(10, vec![5, 30, 5]).unwrap();
+        selection.set_wanted(1, false).unwrap();
+
+        assert_eq!(PieceSelection::Boundary(vec![Range { start: 0, end: 5 }]), selection.piece(0));
+        assert_eq!(PieceSelection::Skipped, selection.piece(1));
+        assert_eq!(PieceSelection::Skipped, selection.piece(2));
+        assert_eq!(
+            PieceSelection::Boundary(vec![Range { start: 5, end: 10 }]),
+            selection.piece(3)
+        );
+        assert_eq!(vec![0, 3], selection.wanted_pieces().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn positive_adjacent_wanted_files_merged() {
+        let selection = FileSelection::new(10, vec![4, 0, 6, 10]).unwrap();
+
+        assert_eq!(PieceSelection::Wanted, selection.piece(0));
+        assert_eq!(20, selection.total_required_bytes());
+    }
+
+    #[test]
+    fn positive_block_action_keeps_writing_by_default() {
+        let mut selection = three_files();
+        selection.set_wanted(1, false).unwrap();
+
+        assert_eq!(BlockAction::Write, selection.block_action(1, 0, 10));
+    }
+
+    #[test]
+    fn positive_block_action_discards_unwanted() {
+        let mut selection = three_files().with_discard_unwanted(true);
+        selection.set_wanted(1, false).unwrap();
+
+        assert_eq!(BlockAction::Write, selection.block_action(0, 0, 10));
+        assert_eq!(
+            BlockAction::WriteRanges(vec![Range { start: 0, end: 3 }]),
+            selection.block_action(1, 0, 10)
+        );
+        assert_eq!(BlockAction::Discard, selection.block_action(1, 5, 5));
+        assert_eq!(
+            BlockAction::WriteRanges(vec![Range { start: 3, end: 8 }]),
+            selection.block_action(2, 2, 8)
+        );
+    }
+
+    #[test]
+    fn negative_invalid_file_index() {
+        let mut selection = three_files();
+
+        assert_eq!(
+            Err(SelectionError::InvalidFileOutOfRange { index: 3, num_files: 3 }),
+            selection.set_wanted(3, false)
+        );
+    }
+
+    #[test]
+    fn negative_zero_piece_length() {
+        assert_eq!(Some(SelectionError::InvalidPieceLength), FileSelection::new(0, vec![1]).err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Module for shutting down the components of a client gracefully.
+
+use std::future::Future;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt as _, StreamExt as _};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Step of a `GracefulShutdown`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShutdownStep {
+    /// Stop accepting and initiating connections, such as by shutting down the tasks of the `Handshaker`.
+    StopAccepting,
+    /// Close connected peers gracefully, such as with `PeerManagerSink::shutdown_all`.
+    ClosePeers,
+    /// Announce the `Stopped` event to the trackers of every torrent.
+    AnnounceStopped,
+    /// Sync every torrent to disk, and save its resume data.
+    Flush,
+}
+
+/// Shuts down the components of a client in order, bounded by a timeout.
+///
+/// Connections are no longer accepted, then connected peers are closed, so that no more blocks
+/// are transferred. Only then are the `Stopped` events announced, with final transfer totals, and
+/// the disk flushed, with final resume data. Announcing and flushing run concurrently, so that
+/// an unresponsive tracker does not hold up the disk.
+///
+/// Each step completes once all of the futures added for it have completed. Steps without any
+/// futures complete immediately.
+#[allow(clippy::module_name_repetitions)]
+pub struct GracefulShutdown {
+    timeout: Duration,
+    steps: Vec<(ShutdownStep, BoxFuture<'static, ()>)>,
+}
+
+impl GracefulShutdown {
+    /// Create a new `GracefulShutdown` without any steps.
+    #[must_use]
+    pub fn new() -> GracefulShutdown {
+        GracefulShutdown {
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Sets how long the whole shutdown may take, after which any remaining steps are abandoned.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> GracefulShutdown {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a future that has to complete as part of the given step, its output is discarded.
+    #[must_use]
+    pub fn with_step<F>(mut self, step: ShutdownStep, future: F) -> GracefulShutdown
+    where
+        F: Future + Send + 'static,
+    {
+        self.steps.push((step, future.map(drop).boxed()));
+        self
+    }
+
+    /// Run the shutdown, resolving once every step has completed or the timeout has passed.
+    pub async fn run(self) -> ShutdownReport {
+        let GracefulShutdown { timeout, mut steps } = self;
+        let mut completed = Vec::new();
+
+        let shutdown = async {
+            for step in [ShutdownStep::StopAccepting, ShutdownStep::ClosePeers] {
+                future::join_all(take_step(&mut steps, step)).await;
+                completed.push(step);
+            }
+
+            let mut remaining: FuturesUnordered<_> = [ShutdownStep::AnnounceStopped, ShutdownStep::Flush]
+                .into_iter()
+                .map(|step| future::join_all(take_step(&mut steps, step)).map(move |_| step))
+                .collect();
+            while let Some(step) = remaining.next().await {
+                completed.push(step);
+            }
+        };
+
+        if tokio::time::timeout(timeout, shutdown).await.is_err() {
+            tracing::warn!(?completed, "graceful shutdown timed out");
+        }
+
+        ShutdownReport { completed }
+    }
+}
+
+impl Default for GracefulShutdown {
+    fn default() -> GracefulShutdown {
+        GracefulShutdown::new()
+    }
+}
+
+/// Outcome of running a `GracefulShutdown`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShutdownReport {
+    completed: Vec<ShutdownStep>,
+}
+
+impl ShutdownReport {
+    /// Steps that completed before the timeout, in the order they completed.
+    #[must_use]
+    pub fn completed(&self) -> &[ShutdownStep] {
+        &self.completed
+    }
+
+    /// Whether or not the given step completed before the timeout.
+    #[must_use]
+    pub fn is_completed(&self, step: ShutdownStep) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// Whether or not every step completed before the timeout.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        [
+            ShutdownStep::StopAccepting,
+            ShutdownStep::ClosePeers,
+            ShutdownStep::AnnounceStopped,
+            ShutdownStep::Flush,
+        ]
+        .into_iter()
+        .all(|step| self.is_completed(step))
+    }
+}
+
+/// Remove the futures of the given step.
+fn take_step(steps: &mut Vec<(ShutdownStep, BoxFuture<'static, ()>)>, step: ShutdownStep) -> Vec<BoxFuture<'static, ()>> {
+    let (taken, kept) = std::mem::take(steps).into_iter().partition(|(other, _)| *other == step);
+    *steps = kept;
+
+    taken.into_iter().map(|(_, future)| future).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{GracefulShutdown, ShutdownStep};
+
+    fn record(log: &Arc<Mutex<Vec<&'static str>>>, entry: &'static str) -> impl std::future::Future<Output = ()> {
+        let log = log.clone();
+
+        async move {
+            tokio::task::yield_now().await;
+            log.lock().unwrap().push(entry);
+        }
+    }
+
+    #[tokio::test]
+    async fn positive_run_steps_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let report = GracefulShutdown::new()
+            .with_step(ShutdownStep::Flush, record(&log, "flush"))
+            .with_step(ShutdownStep::ClosePeers, record(&log, "close peers"))
+            .with_step(ShutdownStep::StopAccepting, record(&log, "stop accepting"))
+            .run()
+            .await;
+
+        assert_eq!(vec!["stop accepting", "close peers", "flush"], *log.lock().unwrap());
+        assert!(report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn positive_flush_despite_unresponsive_tracker() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let report = GracefulShutdown::new()
+            .with_timeout(Duration::from_millis(50))
+            .with_step(ShutdownStep::AnnounceStopped, futures::future::pending::<()>())
+            .with_step(ShutdownStep::Flush, record(&log, "flush"))
+            .run()
+            .await;
+
+        assert_eq!(vec!["flush"], *log.lock().unwrap());
+        assert_eq!(
+            &[ShutdownStep::StopAccepting, ShutdownStep::ClosePeers, ShutdownStep::Flush],
+            report.completed()
+        );
+        assert!(!report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn negative_timeout_abandons_remaining_steps() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let report = GracefulShutdown::new()
+            .with_timeout(Duration::from_millis(50))
+            .with_step(ShutdownStep::ClosePeers, futures::future::pending::<()>())
+            .with_step(ShutdownStep::Flush, record(&log, "flush"))
+            .run()
+            .await;
+
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(&[ShutdownStep::StopAccepting], report.completed());
+        assert!(!report.is_completed(ShutdownStep::Flush));
+    }
+}
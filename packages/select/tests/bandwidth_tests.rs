@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use common::{metainfo, peer_info, tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use peer::PeerInfo;
+use select::bandwidth::{BandwidthModule, BandwidthModuleBuilder, IBandwidthMessage, OBandwidthMessage};
+use select::{ControlMessage, TorrentPriority};
+use tracing::level_filters::LevelFilter;
+use util::bt::InfoHash;
+
+mod common;
+
+async fn add_torrent(module: &mut BandwidthModule, num_pieces: usize) -> InfoHash {
+    let metainfo = metainfo(num_pieces);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IBandwidthMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+
+    info_hash
+}
+
+/// Bytes granted to each peer after the given time passes.
+async fn granted_after(module: &mut BandwidthModule, duration: Duration) -> HashMap<PeerInfo, usize> {
+    module
+        .send(IBandwidthMessage::Control(Box::new(ControlMessage::Tick(duration))))
+        .await
+        .unwrap();
+
+    let mut granted = HashMap::new();
+    while let Some(Some(message)) = module.next().now_or_never() {
+        let OBandwidthMessage::Grant(info, bytes) = message.unwrap();
+        *granted.entry(info).or_default() += bytes;
+    }
+
+    granted
+}
+
+#[tokio::test]
+async fn positive_unlimited_grants_immediately() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = BandwidthModuleBuilder::new().build();
+    let info_hash = add_torrent(&mut module, 1).await;
+    let peer = peer_info(info_hash, 0);
+
+    module.send(IBandwidthMessage::Request(peer, 1000)).await.unwrap();
+
+    let message = module.next().now_or_never().unwrap().unwrap().unwrap();
+    assert_eq!(OBandwidthMessage::Grant(peer, 1000), message);
+}
+
+#[tokio::test]
+async fn positive_saturated_rate_divided_by_priority() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = BandwidthModuleBuilder::new().with_rate_limit(Some(6000)).build();
+    let high_hash = add_torrent(&mut module, 1).await;
+    let normal_hash = add_torrent(&mut module, 2).await;
+    module
+        .send(IBandwidthMessage::SetPriority(high_hash, TorrentPriority::High))
+        .await
+        .unwrap();
+
+    let (high_peer, normal_peer) = (peer_info(high_hash, 0), peer_info(normal_hash, 1));
+    module.send(IBandwidthMessage::Request(high_peer, 10_000)).await.unwrap();
+    module.send(IBandwidthMessage::Request(normal_peer, 10_000)).await.unwrap();
+
+    // Nothing is granted until time passes
+    assert!(module.next().now_or_never().is_none());
+
+    let granted = granted_after(&mut module, Duration::from_secs(1)).await;
+    assert_eq!(Some(&4000), granted.get(&high_peer));
+    assert_eq!(Some(&2000), granted.get(&normal_peer));
+
+    // Once the high priority request is done, the rest goes to the normal priority one
+    let granted = granted_after(&mut module, Duration::from_secs(1)).await;
+    assert_eq!(Some(&4000), granted.get(&high_peer));
+    assert_eq!(Some(&2000), granted.get(&normal_peer));
+
+    let granted = granted_after(&mut module, Duration::from_secs(1)).await;
+    assert_eq!(Some(&2000), granted.get(&high_peer));
+    assert_eq!(Some(&4000), granted.get(&normal_peer));
+}
+
+#[tokio::test]
+async fn positive_disconnected_peer_requests_dropped() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = BandwidthModuleBuilder::new().with_rate_limit(Some(1000)).build();
+    let info_hash = add_torrent(&mut module, 1).await;
+
+    let (gone_peer, peer) = (peer_info(info_hash, 0), peer_info(info_hash, 1));
+    module.send(IBandwidthMessage::Request(gone_peer, 500)).await.unwrap();
+    module.send(IBandwidthMessage::Request(peer, 500)).await.unwrap();
+    module
+        .send(IBandwidthMessage::Control(Box::new(ControlMessage::PeerDisconnected(
+            gone_peer,
+        ))))
+        .await
+        .unwrap();
+
+    let granted = granted_after(&mut module, Duration::from_millis(500)).await;
+    assert_eq!(HashMap::from([(peer, 500)]), granted);
+}
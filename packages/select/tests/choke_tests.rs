@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use common::{metainfo, peer_info, tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use peer::PeerInfo;
+use select::choke::{ChokeAlgorithm, ChokeModule, ChokeModuleBuilder, IChokeMessage, OChokeMessage};
+use select::{ControlMessage, TorrentPriority};
+use tracing::level_filters::LevelFilter;
+use util::bt::InfoHash;
+
+mod common;
+
+const RECHOKE_INTERVAL: Duration = Duration::from_secs(10);
+
+async fn add_torrent(module: &mut ChokeModule) -> InfoHash {
+    add_torrent_with_pieces(module, 1).await
+}
+
+async fn add_torrent_with_pieces(module: &mut ChokeModule, num_pieces: usize) -> InfoHash {
+    let metainfo = metainfo(num_pieces);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IChokeMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+
+    info_hash
+}
+
+async fn connect_interested_peers(module: &mut ChokeModule, hash: InfoHash, ports: std::ops::Range<u16>) -> Vec<PeerInfo> {
+    let mut peers = Vec::new();
+
+    for port in ports {
+        let info = peer_info(hash, port);
+
+        module
+            .send(IChokeMessage::Control(Box::new(ControlMessage::PeerConnected(info))))
+            .await
+            .unwrap();
+        module.send(IChokeMessage::PeerInterested(info, true)).await.unwrap();
+        peers.push(info);
+    }
+
+    peers
+}
+
+async fn rechoke(module: &mut ChokeModule) -> Vec<OChokeMessage> {
+    module
+        .send(IChokeMessage::Control(Box::new(ControlMessage::Tick(RECHOKE_INTERVAL))))
+        .await
+        .unwrap();
+
+    let mut messages = Vec::new();
+    while let Some(Some(message)) = module.next().now_or_never() {
+        messages.push(message.unwrap());
+    }
+
+    messages
+}
+
+#[tokio::test]
+async fn positive_fastest_upload_unchokes_fastest_downloaders() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ChokeModuleBuilder::new()
+        .with_algorithm(ChokeAlgorithm::FastestUpload)
+        .with_upload_slots(3)
+        .with_rechoke_interval(RECHOKE_INTERVAL)
+        .build();
+    let info_hash = add_torrent(&mut module).await;
+    let peers = connect_interested_peers(&mut module, info_hash, 0..5).await;
+
+    // Blocks received from peers do not count towards seeding
+    module.send(IChokeMessage::ReceivedBlock(peers[0], 5000)).await.unwrap();
+    for (index, sent) in [(1, 100), (2, 300), (3, 200)] {
+        module.send(IChokeMessage::SentBlock(peers[index], sent)).await.unwrap();
+    }
+
+    // Two fastest peers, plus the first peer in the rotation
+    assert_eq!(
+        vec![
+            OChokeMessage::Unchoke(peers[0]),
+            OChokeMessage::Unchoke(peers[2]),
+            OChokeMessage::Unchoke(peers[3])
+        ],
+        rechoke(&mut module).await
+    );
+
+    // Peer four overtakes peer three, while the rotating slot is kept
+    module.send(IChokeMessage::SentBlock(peers[2], 300)).await.unwrap();
+    module.send(IChokeMessage::SentBlock(peers[4], 250)).await.unwrap();
+    module.send(IChokeMessage::SentBlock(peers[3], 200)).await.unwrap();
+
+    assert_eq!(
+        vec![OChokeMessage::Choke(peers[3]), OChokeMessage::Unchoke(peers[4])],
+        rechoke(&mut module).await
+    );
+}
+
+#[tokio::test]
+async fn positive_rotating_slot_moves_through_peers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ChokeModuleBuilder::new()
+        .with_algorithm(ChokeAlgorithm::FastestUpload)
+        .with_upload_slots(2)
+        .with_rechoke_interval(RECHOKE_INTERVAL)
+        .with_rotation_interval(RECHOKE_INTERVAL * 2)
+        .build();
+    let info_hash = add_torrent(&mut module).await;
+    let peers = connect_interested_peers(&mut module, info_hash, 0..3).await;
+
+    let mut rotated = Vec::new();
+    for _ in 0..4 {
+        module.send(IChokeMessage::SentBlock(peers[2], 100)).await.unwrap();
+
+        rotated.push(rechoke(&mut module).await);
+    }
+
+    assert_eq!(
+        vec![
+            vec![OChokeMessage::Unchoke(peers[0]), OChokeMessage::Unchoke(peers[2])],
+            vec![],
+            vec![OChokeMessage::Choke(peers[0]), OChokeMessage::Unchoke(peers[1])],
+            vec![],
+        ],
+        rotated
+    );
+}
+
+#[tokio::test]
+async fn positive_set_algorithm_switches_ranking() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ChokeModuleBuilder::new()
+        .with_upload_slots(2)
+        .with_rechoke_interval(RECHOKE_INTERVAL)
+        .with_rotation_interval(RECHOKE_INTERVAL * 10)
+        .build();
+    let info_hash = add_torrent(&mut module).await;
+    let peers = connect_interested_peers(&mut module, info_hash, 0..3).await;
+
+    // Leeching, peer two uploads to us
+    module.send(IChokeMessage::ReceivedBlock(peers[2], 100)).await.unwrap();
+    module.send(IChokeMessage::SentBlock(peers[1], 100)).await.unwrap();
+    assert_eq!(
+        vec![OChokeMessage::Unchoke(peers[0]), OChokeMessage::Unchoke(peers[2])],
+        rechoke(&mut module).await
+    );
+
+    // Seeding, peer one downloads from us the fastest
+    module
+        .send(IChokeMessage::SetAlgorithm(info_hash, ChokeAlgorithm::FastestUpload))
+        .await
+        .unwrap();
+    module.send(IChokeMessage::ReceivedBlock(peers[2], 100)).await.unwrap();
+    module.send(IChokeMessage::SentBlock(peers[1], 100)).await.unwrap();
+    assert_eq!(
+        vec![OChokeMessage::Choke(peers[2]), OChokeMessage::Unchoke(peers[1])],
+        rechoke(&mut module).await
+    );
+}
+
+#[tokio::test]
+async fn positive_uninterested_peers_choked() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ChokeModuleBuilder::new()
+        .with_algorithm(ChokeAlgorithm::FastestUpload)
+        .with_upload_slots(2)
+        .with_rechoke_interval(RECHOKE_INTERVAL)
+        .build();
+    let info_hash = add_torrent(&mut module).await;
+    let peers = connect_interested_peers(&mut module, info_hash, 0..2).await;
+
+    assert_eq!(
+        vec![OChokeMessage::Unchoke(peers[0]), OChokeMessage::Unchoke(peers[1])],
+        rechoke(&mut module).await
+    );
+
+    module.send(IChokeMessage::PeerInterested(peers[1], false)).await.unwrap();
+    assert_eq!(vec![OChokeMessage::Choke(peers[1])], rechoke(&mut module).await);
+}
+
+#[tokio::test]
+async fn positive_global_slots_divided_by_priority() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ChokeModuleBuilder::new()
+        .with_algorithm(ChokeAlgorithm::FastestUpload)
+        .with_upload_slots(4)
+        .with_global_upload_slots(Some(4))
+        .with_rechoke_interval(RECHOKE_INTERVAL)
+        .build();
+    let high_hash = add_torrent_with_pieces(&mut module, 1).await;
+    let normal_hash = add_torrent_with_pieces(&mut module, 2).await;
+    module
+        .send(IChokeMessage::SetPriority(high_hash, TorrentPriority::High))
+        .await
+        .unwrap();
+
+    let high_peers = connect_interested_peers(&mut module, high_hash, 0..4).await;
+    let normal_peers = connect_interested_peers(&mut module, normal_hash, 4..8).await;
+
+    let unchoked = rechoke(&mut module).await;
+    let count_unchoked = |peers: &[PeerInfo]| {
+        peers
+            .iter()
+            .filter(|info| unchoked.contains(&OChokeMessage::Unchoke(**info)))
+            .count()
+    };
+
+    // With every slot wanted, the high priority torrent gets the larger share
+    assert_eq!(4, unchoked.len());
+    assert_eq!(3, count_unchoked(&high_peers));
+    assert_eq!(1, count_unchoked(&normal_peers));
+
+    // Slots the high priority torrent does not need go to the other torrent, which now has three
+    for info in &high_peers[1..] {
+        module.send(IChokeMessage::PeerInterested(*info, false)).await.unwrap();
+    }
+    let messages = rechoke(&mut module).await;
+    assert_eq!(
+        2,
+        messages
+            .iter()
+            .filter(|message| matches!(message, OChokeMessage::Unchoke(info) if normal_peers.contains(info)))
+            .count()
+    );
+}
@@ -2,7 +2,11 @@
 
 use std::sync::Once;
 
+use handshake::Extensions;
+use metainfo::{DirectAccessor, Metainfo, MetainfoBuilder, PieceLength};
+use peer::PeerInfo;
 use tracing::level_filters::LevelFilter;
+use util::bt::{self, InfoHash};
 
 #[allow(dead_code)]
 pub static INIT: Once = Once::new();
@@ -25,3 +29,28 @@ pub fn tracing_stderr_init(filter: LevelFilter) {
 
     tracing::info!("Logging initialized");
 }
+
+/// Metainfo for a torrent with the given number of one byte pieces.
+#[allow(dead_code)]
+pub fn metainfo(num_pieces: usize) -> Metainfo {
+    let data = vec![0u8; num_pieces];
+
+    let accessor = DirectAccessor::new("MyFile.txt", &data);
+    let bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1))
+        .build(1, accessor, |_| ())
+        .unwrap();
+
+    Metainfo::from_bytes(bytes).unwrap()
+}
+
+/// Peer of the torrent with the given hash, told apart from other peers by its port.
+#[allow(dead_code)]
+pub fn peer_info(hash: InfoHash, port: u16) -> PeerInfo {
+    PeerInfo::new(
+        format!("1.2.3.4:{port}").parse().unwrap(),
+        [0u8; bt::PEER_ID_LEN].into(),
+        hash,
+        Extensions::new(),
+    )
+}
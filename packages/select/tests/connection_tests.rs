@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use common::{metainfo, peer_info, tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use peer::PeerInfo;
+use select::connection::{ConnectionModule, ConnectionModuleBuilder, IConnectionMessage, OConnectionMessage};
+use select::ControlMessage;
+use tracing::level_filters::LevelFilter;
+use util::bt::InfoHash;
+
+mod common;
+
+async fn connect_peers(module: &mut ConnectionModule, hash: InfoHash, ports: std::ops::Range<u16>) -> Vec<PeerInfo> {
+    let mut peers = Vec::new();
+
+    for port in ports {
+        let info = peer_info(hash, port);
+
+        module
+            .send(IConnectionMessage::Control(Box::new(ControlMessage::PeerConnected(info))))
+            .await
+            .unwrap();
+        peers.push(info);
+    }
+
+    peers
+}
+
+fn drain_messages(module: &mut ConnectionModule) -> Vec<OConnectionMessage> {
+    let mut messages = Vec::new();
+
+    while let Some(Some(message)) = module.next().now_or_never() {
+        messages.push(message.unwrap());
+    }
+
+    messages
+}
+
+#[tokio::test]
+async fn positive_request_peers_under_target() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new()
+        .with_torrent_target_peers(10)
+        .with_request_interval(Duration::from_secs(30))
+        .build();
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    connect_peers(&mut module, info_hash, 0..4).await;
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![OConnectionMessage::RequestPeers(info_hash, 6)]
+    );
+
+    // Should not request again until the interval has passed
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+    assert!(drain_messages(&mut module).is_empty());
+}
+
+#[tokio::test]
+async fn positive_request_peers_limited_by_global_target() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new()
+        .with_torrent_target_peers(10)
+        .with_global_target_peers(5)
+        .build();
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    connect_peers(&mut module, info_hash, 0..3).await;
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![OConnectionMessage::RequestPeers(info_hash, 2)]
+    );
+}
+
+#[tokio::test]
+async fn positive_prune_seed_to_seed_over_target() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new()
+        .with_torrent_target_peers(2)
+        .with_torrent_max_peers(10)
+        .build();
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    module.send(IConnectionMessage::SetSeeding(info_hash, true)).await.unwrap();
+    let peers = connect_peers(&mut module, info_hash, 0..3).await;
+    module.send(IConnectionMessage::PeerSeeding(peers[1])).await.unwrap();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![OConnectionMessage::DisconnectPeer(peers[1])]
+    );
+}
+
+#[tokio::test]
+async fn positive_prune_snubbed_over_target() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new()
+        .with_torrent_target_peers(1)
+        .with_torrent_max_peers(10)
+        .with_snub_timeout(Duration::from_secs(30))
+        .build();
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    let peers = connect_peers(&mut module, info_hash, 0..3).await;
+
+    // No peer is snubbing us yet, so nothing is pruned even though we are over target
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(29),
+        ))))
+        .await
+        .unwrap();
+    assert!(drain_messages(&mut module).is_empty());
+
+    module
+        .send(IConnectionMessage::ReceivedBlock(peers[2], 16 * 1024))
+        .await
+        .unwrap();
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+
+    let messages = drain_messages(&mut module);
+    assert_eq!(messages.len(), 2);
+    assert!(!messages.contains(&OConnectionMessage::DisconnectPeer(peers[2])));
+}
+
+#[tokio::test]
+async fn positive_prune_active_over_max() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new()
+        .with_torrent_target_peers(1)
+        .with_torrent_max_peers(2)
+        .build();
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    connect_peers(&mut module, info_hash, 0..3).await;
+
+    module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::Tick(
+            Duration::from_secs(1),
+        ))))
+        .await
+        .unwrap();
+
+    let messages = drain_messages(&mut module);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(messages[0], OConnectionMessage::DisconnectPeer(_)));
+}
+
+#[tokio::test]
+async fn negative_peer_connected_without_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = ConnectionModuleBuilder::new().build();
+    let info_hash = metainfo(1).info().info_hash();
+
+    let result = module
+        .send(IConnectionMessage::Control(Box::new(ControlMessage::PeerConnected(
+            peer_info(info_hash, 0),
+        ))))
+        .await;
+
+    assert!(result.is_err());
+}
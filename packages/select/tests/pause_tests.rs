@@ -0,0 +1,136 @@
+use common::{metainfo, peer_info, tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use peer::PeerInfo;
+use select::pause::{IPauseMessage, OPauseMessage, PauseModule, PauseModuleBuilder, PeerPausePolicy, TrackerPausePolicy};
+use select::ControlMessage;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self, InfoHash};
+use utracker::announce::AnnounceEvent;
+
+mod common;
+
+/// Add a torrent with a choked and an unchoked peer, returning its hash along with both peers.
+async fn add_torrent_with_peers(module: &mut PauseModule) -> (InfoHash, PeerInfo, PeerInfo) {
+    let metainfo = metainfo(1);
+    let info_hash = metainfo.info().info_hash();
+    let (choked, unchoked) = (peer_info(info_hash, 1), peer_info(info_hash, 2));
+
+    module
+        .send(IPauseMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo))))
+        .await
+        .unwrap();
+    for peer in [choked, unchoked] {
+        module
+            .send(IPauseMessage::Control(Box::new(ControlMessage::PeerConnected(peer))))
+            .await
+            .unwrap();
+    }
+    module.send(IPauseMessage::PeerChoked(unchoked, false)).await.unwrap();
+
+    (info_hash, choked, unchoked)
+}
+
+fn drain_messages(module: &mut PauseModule) -> Vec<OPauseMessage> {
+    let mut messages = Vec::new();
+
+    while let Some(Some(message)) = module.next().now_or_never() {
+        messages.push(message.unwrap());
+    }
+
+    messages
+}
+
+#[tokio::test]
+async fn positive_pause_and_resume_restores_unchoked_peers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = PauseModuleBuilder::new().build();
+    let (hash, _, unchoked) = add_torrent_with_peers(&mut module).await;
+
+    module.send(IPauseMessage::Pause(hash)).await.unwrap();
+
+    assert!(module.is_paused(&hash));
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![
+            OPauseMessage::TorrentPaused(hash),
+            OPauseMessage::Choke(unchoked),
+            OPauseMessage::Announce(hash, AnnounceEvent::Stopped),
+            OPauseMessage::SyncDisk(hash),
+        ]
+    );
+
+    // Pausing again does nothing, and choking the peer ourselves does not change what is restored
+    module.pause(hash).unwrap();
+    module.send(IPauseMessage::PeerChoked(unchoked, true)).await.unwrap();
+    assert!(drain_messages(&mut module).is_empty());
+
+    module.resume(hash).unwrap();
+
+    assert!(!module.is_paused(&hash));
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![
+            OPauseMessage::TorrentResumed(hash),
+            OPauseMessage::Unchoke(unchoked),
+            OPauseMessage::Announce(hash, AnnounceEvent::Started),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn positive_pause_disconnects_peers_silently() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = PauseModuleBuilder::new()
+        .with_peer_policy(PeerPausePolicy::Disconnect)
+        .with_tracker_policy(TrackerPausePolicy::Silent)
+        .build();
+    let (hash, choked, unchoked) = add_torrent_with_peers(&mut module).await;
+
+    module.pause(hash).unwrap();
+
+    let messages = drain_messages(&mut module);
+    assert_eq!(4, messages.len());
+    assert_eq!(OPauseMessage::TorrentPaused(hash), messages[0]);
+    assert!(messages.contains(&OPauseMessage::DisconnectPeer(choked)));
+    assert!(messages.contains(&OPauseMessage::DisconnectPeer(unchoked)));
+    assert_eq!(OPauseMessage::SyncDisk(hash), messages[3]);
+
+    // Peers connecting while paused are disconnected as well
+    let late = peer_info(hash, 3);
+    module
+        .send(IPauseMessage::Control(Box::new(ControlMessage::PeerConnected(late))))
+        .await
+        .unwrap();
+    assert_eq!(drain_messages(&mut module), vec![OPauseMessage::DisconnectPeer(late)]);
+
+    for peer in [choked, unchoked, late] {
+        module
+            .send(IPauseMessage::Control(Box::new(ControlMessage::PeerDisconnected(peer))))
+            .await
+            .unwrap();
+    }
+    module.resume(hash).unwrap();
+
+    assert_eq!(drain_messages(&mut module), vec![OPauseMessage::TorrentResumed(hash)]);
+}
+
+#[tokio::test]
+async fn negative_pause_unknown_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = PauseModuleBuilder::new().build();
+
+    assert!(module.pause([0u8; bt::INFO_HASH_LEN].into()).is_err());
+    assert!(module
+        .send(IPauseMessage::Resume([0u8; bt::INFO_HASH_LEN].into()))
+        .await
+        .is_err());
+}
@@ -0,0 +1,145 @@
+use common::{metainfo, tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use metainfo::Metainfo;
+use select::queue::{IQueueMessage, OQueueMessage, QueueModule, QueueModuleBuilder, QueueState};
+use select::ControlMessage;
+use tracing::level_filters::LevelFilter;
+use util::bt::InfoHash;
+
+mod common;
+
+async fn add_torrents(module: &mut QueueModule, count: usize) -> Vec<Metainfo> {
+    let mut torrents = Vec::new();
+
+    for num_pieces in 1..=count {
+        let metainfo = metainfo(num_pieces);
+
+        module
+            .send(IQueueMessage::Control(Box::new(ControlMessage::AddTorrent(metainfo.clone()))))
+            .await
+            .unwrap();
+        torrents.push(metainfo);
+    }
+
+    torrents
+}
+
+fn hash(metainfo: &Metainfo) -> InfoHash {
+    metainfo.info().info_hash()
+}
+
+fn drain_messages(module: &mut QueueModule) -> Vec<OQueueMessage> {
+    let mut messages = Vec::new();
+
+    while let Some(Some(message)) = module.next().now_or_never() {
+        messages.push(message.unwrap());
+    }
+
+    messages
+}
+
+#[tokio::test]
+async fn positive_queue_when_slots_full() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = QueueModuleBuilder::new().with_max_active_torrents(2).build();
+    let torrents = add_torrents(&mut module, 3).await;
+
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![
+            OQueueMessage::StateChanged(hash(&torrents[0]), QueueState::Active),
+            OQueueMessage::StateChanged(hash(&torrents[1]), QueueState::Active),
+            OQueueMessage::StateChanged(hash(&torrents[2]), QueueState::Queued),
+        ]
+    );
+
+    // Removing an active torrent frees up its slot
+    module
+        .send(IQueueMessage::Control(Box::new(ControlMessage::RemoveTorrent(
+            torrents[0].clone(),
+        ))))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![OQueueMessage::StateChanged(hash(&torrents[2]), QueueState::Active)]
+    );
+}
+
+#[tokio::test]
+async fn positive_set_position_swaps_active_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = QueueModuleBuilder::new().with_max_active_torrents(1).build();
+    let torrents = add_torrents(&mut module, 2).await;
+    drain_messages(&mut module);
+
+    module.send(IQueueMessage::SetPosition(hash(&torrents[1]), 0)).await.unwrap();
+
+    assert_eq!(module.position(&hash(&torrents[1])), Some(0));
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![
+            OQueueMessage::StateChanged(hash(&torrents[0]), QueueState::Queued),
+            OQueueMessage::StateChanged(hash(&torrents[1]), QueueState::Active),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn positive_force_start_ignores_limit() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = QueueModuleBuilder::new().with_max_active_torrents(1).build();
+    let torrents = add_torrents(&mut module, 3).await;
+    drain_messages(&mut module);
+
+    module
+        .send(IQueueMessage::ForceStart(hash(&torrents[2]), true))
+        .await
+        .unwrap();
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![OQueueMessage::StateChanged(hash(&torrents[2]), QueueState::ForceStarted)]
+    );
+
+    // Force starting the active torrent frees up its slot for the next queued torrent
+    module
+        .send(IQueueMessage::ForceStart(hash(&torrents[0]), true))
+        .await
+        .unwrap();
+    assert_eq!(
+        drain_messages(&mut module),
+        vec![
+            OQueueMessage::StateChanged(hash(&torrents[0]), QueueState::ForceStarted),
+            OQueueMessage::StateChanged(hash(&torrents[1]), QueueState::Active),
+        ]
+    );
+
+    module
+        .send(IQueueMessage::ForceStart(hash(&torrents[2]), false))
+        .await
+        .unwrap();
+    assert_eq!(module.state(&hash(&torrents[2])), Some(QueueState::Queued));
+}
+
+#[tokio::test]
+async fn negative_set_position_without_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = QueueModuleBuilder::new().build();
+
+    let result = module.send(IQueueMessage::SetPosition(hash(&metainfo(1)), 0)).await;
+
+    assert!(result.is_err());
+}
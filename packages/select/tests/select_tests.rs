@@ -1,12 +1,14 @@
 use std::time::Duration;
 
+use bytes::Bytes;
 use common::{tracing_stderr_init, INIT};
-use futures::{SinkExt as _, StreamExt as _};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
 use handshake::Extensions;
 use metainfo::{DirectAccessor, Metainfo, MetainfoBuilder, PieceLength};
+use peer::messages::{BitFieldMessage, HaveMessage};
 use peer::PeerInfo;
 use select::revelation::error::RevealError;
-use select::revelation::{HonestRevealModuleBuilder, IRevealMessage, ORevealMessage};
+use select::revelation::{HonestRevealModule, HonestRevealModuleBuilder, IRevealMessage, ORevealMessage, RevealConfig};
 use select::ControlMessage;
 use tracing::level_filters::LevelFilter;
 use util::bt;
@@ -220,3 +222,214 @@ async fn negative_all_pieces_good_found_good_piece_out_of_range() {
         }
     }
 }
+
+async fn next_message(module: &mut HonestRevealModule) -> ORevealMessage {
+    tokio::time::timeout(Duration::from_millis(50), module.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn positive_interest_follows_missing_pieces() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let builder = HonestRevealModuleBuilder::new();
+    let mut module = builder.build();
+    let metainfo = metainfo(8);
+    let info_hash = metainfo.info().info_hash();
+    let peer_info = peer_info(info_hash);
+
+    module
+        .send(IRevealMessage::Control(ControlMessage::AddTorrent(metainfo)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::Control(ControlMessage::PeerConnected(peer_info)))
+        .await
+        .unwrap();
+
+    // Peer has pieces 0 and 1, both of which we are missing
+    let bitfield = BitFieldMessage::new(Bytes::from_static(&[0xC0]));
+    module
+        .send(IRevealMessage::ReceivedBitField(peer_info, bitfield))
+        .await
+        .unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendInterested(info) if info == peer_info));
+
+    // Still missing piece 1
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 0)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendHave(_, have) if have.piece_index() == 0));
+    assert!(module.next().now_or_never().is_none());
+
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 1)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendHave(_, have) if have.piece_index() == 1));
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendNotInterested(info) if info == peer_info));
+
+    // Peer gets a piece we are missing
+    module
+        .send(IRevealMessage::ReceivedHave(peer_info, HaveMessage::new(5)))
+        .await
+        .unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendInterested(info) if info == peer_info));
+}
+
+#[tokio::test]
+async fn negative_not_interested_in_pieces_we_have() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let builder = HonestRevealModuleBuilder::new();
+    let mut module = builder.build();
+    let metainfo = metainfo(8);
+    let info_hash = metainfo.info().info_hash();
+    let peer_info = peer_info(info_hash);
+
+    module
+        .send(IRevealMessage::Control(ControlMessage::AddTorrent(metainfo)))
+        .await
+        .unwrap();
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 3)).await.unwrap();
+    module
+        .send(IRevealMessage::Control(ControlMessage::PeerConnected(peer_info)))
+        .await
+        .unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendBitField(_, _)));
+
+    // Peer only has the piece we already have, plus a spare bit past the last piece
+    module
+        .send(IRevealMessage::ReceivedHave(peer_info, HaveMessage::new(3)))
+        .await
+        .unwrap();
+    let bitfield = BitFieldMessage::new(Bytes::from_static(&[0x10, 0x80]));
+    module
+        .send(IRevealMessage::ReceivedBitField(peer_info, bitfield))
+        .await
+        .unwrap();
+
+    assert!(module.next().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn positive_have_suppressed_for_peers_with_piece() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let builder = HonestRevealModuleBuilder::new();
+    let mut module = builder.build();
+    let metainfo = metainfo(8);
+    let info_hash = metainfo.info().info_hash();
+    let peer_info = peer_info(info_hash);
+
+    module
+        .send(IRevealMessage::Control(ControlMessage::AddTorrent(metainfo)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::SetConfig(
+            info_hash,
+            RevealConfig::default().with_have_suppression(true),
+        ))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::Control(ControlMessage::PeerConnected(peer_info)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::ReceivedHave(peer_info, HaveMessage::new(0)))
+        .await
+        .unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendInterested(_)));
+
+    // Peer already has piece 0, so only our loss of interest is sent
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 0)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendNotInterested(info) if info == peer_info));
+
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 1)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendHave(_, have) if have.piece_index() == 1));
+    assert!(module.next().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn negative_set_config_torrent_not_exists() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let builder = HonestRevealModuleBuilder::new();
+    let mut module = builder.build();
+    let info_hash = metainfo(1).info().info_hash();
+
+    let error = module
+        .send(IRevealMessage::SetConfig(info_hash, RevealConfig::default()))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, RevealError::InvalidMetainfoNotExists { hash } if hash == info_hash));
+}
+
+#[tokio::test]
+async fn positive_interest_hysteresis_suppresses_flapping() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let config = RevealConfig::default().with_interest_hysteresis(Duration::from_secs(10));
+    let builder = HonestRevealModuleBuilder::new().with_default_config(config);
+    let mut module = builder.build();
+    let metainfo = metainfo(8);
+    let info_hash = metainfo.info().info_hash();
+    let peer_info = peer_info(info_hash);
+
+    module
+        .send(IRevealMessage::Control(ControlMessage::AddTorrent(metainfo)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::Control(ControlMessage::PeerConnected(peer_info)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::ReceivedHave(peer_info, HaveMessage::new(0)))
+        .await
+        .unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendInterested(_)));
+
+    // Losing interest is held back
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 0)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendHave(_, _)));
+    module
+        .send(IRevealMessage::Control(ControlMessage::Tick(Duration::from_secs(5))))
+        .await
+        .unwrap();
+    assert!(module.next().now_or_never().is_none());
+
+    // Regaining interest before the hysteresis elapsed sends nothing
+    module
+        .send(IRevealMessage::ReceivedHave(peer_info, HaveMessage::new(1)))
+        .await
+        .unwrap();
+    module
+        .send(IRevealMessage::Control(ControlMessage::Tick(Duration::from_secs(10))))
+        .await
+        .unwrap();
+    assert!(module.next().now_or_never().is_none());
+
+    // Staying uninterested for the whole hysteresis sends the message
+    module.send(IRevealMessage::FoundGoodPiece(info_hash, 1)).await.unwrap();
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendHave(_, _)));
+    for _ in 0..2 {
+        module
+            .send(IRevealMessage::Control(ControlMessage::Tick(Duration::from_secs(5))))
+            .await
+            .unwrap();
+    }
+    assert!(matches!(next_message(&mut module).await, ORevealMessage::SendNotInterested(info) if info == peer_info));
+    assert!(module.next().now_or_never().is_none());
+}
@@ -0,0 +1,127 @@
+use std::net::SocketAddr;
+
+use common::{tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use select::discovery::{IDiscoveryMessage, ODiscoveryMessage, UdpTrackerModule};
+use tracing::level_filters::LevelFilter;
+use util::bt::{self, InfoHash};
+use utracker::announce::{AnnounceEvent, ClientState};
+
+mod common;
+
+fn any_info_hash() -> InfoHash {
+    [55u8; bt::INFO_HASH_LEN].into()
+}
+
+fn tracker(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+fn started() -> ClientState {
+    ClientState::new(0, 100, 0, AnnounceEvent::Started)
+}
+
+fn drain_announces(module: &mut UdpTrackerModule) -> Vec<SocketAddr> {
+    let mut announces = Vec::new();
+
+    while let Some(Some(message)) = module.next().now_or_never() {
+        match message.unwrap() {
+            ODiscoveryMessage::SendUdpTrackerAnnounce(_, addr, _) => announces.push(addr),
+            other => panic!("unexpected discovery message: {other:?}"),
+        }
+    }
+
+    announces
+}
+
+#[tokio::test]
+async fn positive_failover_within_tier_then_next_tier() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let hash = any_info_hash();
+    let mut module = UdpTrackerModule::new();
+
+    module
+        .send(IDiscoveryMessage::AddUdpTrackers(
+            hash,
+            vec![vec![tracker(1), tracker(2)], vec![tracker(3)]],
+        ))
+        .await
+        .unwrap();
+    module
+        .send(IDiscoveryMessage::UpdateClientState(hash, started()))
+        .await
+        .unwrap();
+
+    // Trackers within a tier are shuffled, but we should stay within the first tier
+    let first = drain_announces(&mut module);
+    assert_eq!(first.len(), 1);
+    assert!(first[0] == tracker(1) || first[0] == tracker(2));
+
+    module
+        .send(IDiscoveryMessage::FailedUdpTrackerAnnounce(hash, first[0]))
+        .await
+        .unwrap();
+
+    let second = drain_announces(&mut module);
+    assert_eq!(second.len(), 1);
+    assert!(second[0] != first[0] && second[0] != tracker(3));
+
+    module
+        .send(IDiscoveryMessage::FailedUdpTrackerAnnounce(hash, second[0]))
+        .await
+        .unwrap();
+
+    assert_eq!(drain_announces(&mut module), vec![tracker(3)]);
+}
+
+#[tokio::test]
+async fn positive_state_change_announces_to_working_trackers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let hash = any_info_hash();
+    let mut module = UdpTrackerModule::new();
+
+    module
+        .send(IDiscoveryMessage::AddUdpTrackers(
+            hash,
+            vec![vec![tracker(1)], vec![tracker(2)]],
+        ))
+        .await
+        .unwrap();
+
+    module
+        .send(IDiscoveryMessage::ReceivedUdpTrackerResponse(hash, tracker(1)))
+        .await
+        .unwrap();
+    module
+        .send(IDiscoveryMessage::ReceivedUdpTrackerResponse(hash, tracker(2)))
+        .await
+        .unwrap();
+
+    module
+        .send(IDiscoveryMessage::UpdateClientState(hash, started()))
+        .await
+        .unwrap();
+
+    assert_eq!(drain_announces(&mut module), vec![tracker(1), tracker(2)]);
+}
+
+#[tokio::test]
+async fn negative_response_without_trackers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = UdpTrackerModule::new();
+
+    let result = module
+        .send(IDiscoveryMessage::ReceivedUdpTrackerResponse(any_info_hash(), tracker(1)))
+        .await;
+
+    assert!(result.is_err());
+}
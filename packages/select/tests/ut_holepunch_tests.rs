@@ -0,0 +1,181 @@
+use std::net::SocketAddr;
+
+use common::{tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use peer::messages::builders::ExtendedMessageBuilder;
+use peer::messages::{ExtendedType, UtHolepunchErrorCode, UtHolepunchMessage};
+use peer::PeerInfo;
+use select::discovery::{IDiscoveryMessage, ODiscoveryMessage, UtHolepunchModule};
+use select::{ControlMessage, ExtendedListener as _, ExtendedPeerInfo};
+use tracing::level_filters::LevelFilter;
+use util::bt::{self, InfoHash};
+
+mod common;
+
+fn any_info_hash() -> InfoHash {
+    [55u8; bt::INFO_HASH_LEN].into()
+}
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([1, 2, 3, 4], port))
+}
+
+fn extended_info(holepunch: bool, listen_port: Option<u16>) -> ExtendedPeerInfo {
+    let ours = ExtendedMessageBuilder::new()
+        .with_extended_type(ExtendedType::UtHolepunch, Some(4))
+        .build();
+    let mut theirs = ExtendedMessageBuilder::new().with_our_tcp_port(listen_port);
+    if holepunch {
+        theirs = theirs.with_extended_type(ExtendedType::UtHolepunch, Some(9));
+    }
+
+    ExtendedPeerInfo::new(Some(ours), Some(theirs.build()))
+}
+
+async fn connect_peer(module: &mut UtHolepunchModule, port: u16, holepunch: bool, listen_port: Option<u16>) -> PeerInfo {
+    let info = PeerInfo::new(addr(port), [0u8; bt::PEER_ID_LEN].into(), any_info_hash(), Extensions::new());
+
+    module
+        .send(IDiscoveryMessage::Control(ControlMessage::PeerConnected(info)))
+        .await
+        .unwrap();
+    module.on_update(&info, &extended_info(holepunch, listen_port));
+
+    info
+}
+
+fn drain(module: &mut UtHolepunchModule) -> Vec<ODiscoveryMessage> {
+    let mut messages = Vec::new();
+
+    while let Some(Some(message)) = module.next().now_or_never() {
+        messages.push(message.unwrap());
+    }
+
+    messages
+}
+
+#[tokio::test]
+async fn positive_relay_rendezvous() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = UtHolepunchModule::new();
+    let initiator = connect_peer(&mut module, 1, true, None).await;
+    // Target connected to us, so is reached through its listen port
+    let target = connect_peer(&mut module, 50000, true, Some(2)).await;
+
+    module
+        .send(IDiscoveryMessage::ReceivedUtHolepunchMessage(
+            initiator,
+            UtHolepunchMessage::Rendezvous(addr(2)),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            ODiscoveryMessage::SendUtHolepunchMessage(target, UtHolepunchMessage::Connect(addr(1))),
+            ODiscoveryMessage::SendUtHolepunchMessage(initiator, UtHolepunchMessage::Connect(addr(2))),
+        ],
+        drain(&mut module)
+    );
+}
+
+#[tokio::test]
+async fn negative_rendezvous_errors() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = UtHolepunchModule::new();
+    let initiator = connect_peer(&mut module, 1, true, None).await;
+    connect_peer(&mut module, 2, false, None).await;
+
+    for (target, code) in [
+        (addr(1), UtHolepunchErrorCode::NoSelf),
+        (addr(2), UtHolepunchErrorCode::NoSupport),
+        (addr(3), UtHolepunchErrorCode::NotConnected),
+        (addr(0), UtHolepunchErrorCode::NoSuchPeer),
+    ] {
+        module
+            .send(IDiscoveryMessage::ReceivedUtHolepunchMessage(
+                initiator,
+                UtHolepunchMessage::Rendezvous(target),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![ODiscoveryMessage::SendUtHolepunchMessage(
+                initiator,
+                UtHolepunchMessage::Error(target, code)
+            )],
+            drain(&mut module)
+        );
+    }
+}
+
+#[tokio::test]
+async fn positive_holepunch_failed_connection() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = UtHolepunchModule::new();
+    connect_peer(&mut module, 1, false, None).await;
+    let relay = connect_peer(&mut module, 2, true, None).await;
+
+    for _ in 0..2 {
+        module
+            .send(IDiscoveryMessage::FailedConnection(any_info_hash(), addr(3)))
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        vec![ODiscoveryMessage::SendUtHolepunchMessage(
+            relay,
+            UtHolepunchMessage::Rendezvous(addr(3))
+        )],
+        drain(&mut module)
+    );
+
+    for _ in 0..2 {
+        module
+            .send(IDiscoveryMessage::ReceivedUtHolepunchMessage(
+                relay,
+                UtHolepunchMessage::Connect(addr(3)),
+            ))
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        vec![ODiscoveryMessage::InitiateConnection(any_info_hash(), addr(3))],
+        drain(&mut module)
+    );
+
+    // A holepunched connection that fails is not holepunched again
+    module
+        .send(IDiscoveryMessage::FailedConnection(any_info_hash(), addr(3)))
+        .await
+        .unwrap();
+    assert!(drain(&mut module).is_empty());
+}
+
+#[tokio::test]
+async fn negative_failed_connection_without_relay() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let mut module = UtHolepunchModule::new();
+    connect_peer(&mut module, 1, false, None).await;
+
+    module
+        .send(IDiscoveryMessage::FailedConnection(any_info_hash(), addr(3)))
+        .await
+        .unwrap();
+
+    assert!(drain(&mut module).is_empty());
+}
@@ -0,0 +1,172 @@
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use common::{tracing_stderr_init, INIT};
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _};
+use handshake::Extensions;
+use metainfo::{DirectAccessor, Metainfo, MetainfoBuilder, PieceLength};
+use peer::messages::builders::ExtendedMessageBuilder;
+use peer::messages::{ExtendedType, UtMetadataDataMessage, UtMetadataMessage};
+use peer::PeerInfo;
+use select::discovery::{IDiscoveryMessage, ODiscoveryMessage, UtMetadataModule};
+use select::{ControlMessage, ExtendedListener as _, ExtendedPeerInfo};
+use tracing::level_filters::LevelFilter;
+use util::bt;
+
+mod common;
+
+const METADATA_PIECE_SIZE: usize = 16 * 1024;
+
+/// Metainfo whose info dictionary spans multiple metadata pieces.
+fn any_metainfo() -> Metainfo {
+    // A long file name is a cheap way to pad out the info dictionary
+    let file_name = "a".repeat(METADATA_PIECE_SIZE * 2);
+    let file_contents = [55u8; 1024];
+    let accessor = DirectAccessor::new(&file_name, &file_contents);
+
+    let metainfo_bytes = MetainfoBuilder::new()
+        .set_piece_length(PieceLength::Custom(1024))
+        .build(1, accessor, |_| ())
+        .unwrap();
+
+    Metainfo::from_bytes(metainfo_bytes).unwrap()
+}
+
+async fn connect_peer(module: &mut UtMetadataModule, metainfo: &Metainfo, port: u16) -> PeerInfo {
+    let addr = SocketAddr::from(([1, 2, 3, 4], port));
+    let info = PeerInfo::new(
+        addr,
+        [0u8; bt::PEER_ID_LEN].into(),
+        metainfo.info().info_hash(),
+        Extensions::new(),
+    );
+
+    let ours = ExtendedMessageBuilder::new()
+        .with_extended_type(ExtendedType::UtMetadata, Some(5))
+        .build();
+    let theirs = ExtendedMessageBuilder::new()
+        .with_extended_type(ExtendedType::UtMetadata, Some(9))
+        .with_metadata_size(Some(metainfo.info().to_bytes().len().try_into().unwrap()))
+        .build();
+
+    module
+        .send(IDiscoveryMessage::Control(ControlMessage::PeerConnected(info)))
+        .await
+        .unwrap();
+    module.on_update(&info, &ExtendedPeerInfo::new(Some(ours), Some(theirs)));
+
+    info
+}
+
+/// Answer every batch of metadata requests until the module has nothing left to send, with the bad peers sending
+/// corrupted pieces, and return the messages other than requests.
+async fn download(module: &mut UtMetadataModule, metainfo: &Metainfo, bad_peers: &[PeerInfo]) -> Vec<ODiscoveryMessage> {
+    let info_bytes = metainfo.info().to_bytes();
+    let mut messages = Vec::new();
+
+    loop {
+        let mut requests = Vec::new();
+        while let Some(Some(message)) = module.next().now_or_never() {
+            match message.unwrap() {
+                ODiscoveryMessage::SendUtMetadataMessage(info, UtMetadataMessage::Request(request)) => {
+                    requests.push((info, request));
+                }
+                other => messages.push(other),
+            }
+        }
+
+        if requests.is_empty() {
+            return messages;
+        }
+
+        for (info, request) in requests {
+            let piece: usize = request.piece().try_into().unwrap();
+            let start = piece * METADATA_PIECE_SIZE;
+            let end = (start + METADATA_PIECE_SIZE).min(info_bytes.len());
+
+            let mut data = info_bytes[start..end].to_vec();
+            if bad_peers.contains(&info) {
+                for byte in &mut data {
+                    *byte = !*byte;
+                }
+            }
+
+            let data = UtMetadataDataMessage::new(request.piece(), info_bytes.len().try_into().unwrap(), Bytes::from(data));
+            module
+                .send(IDiscoveryMessage::ReceivedUtMetadataMessage(
+                    info,
+                    UtMetadataMessage::Data(data),
+                ))
+                .await
+                .unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn positive_cross_check_bans_outvoted_peer() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let metainfo = any_metainfo();
+    let mut module = UtMetadataModule::new();
+    module
+        .send(IDiscoveryMessage::DownloadMetainfo(metainfo.info().info_hash()))
+        .await
+        .unwrap();
+
+    let good_one = connect_peer(&mut module, &metainfo, 1).await;
+    let good_two = connect_peer(&mut module, &metainfo, 2).await;
+    let bad = connect_peer(&mut module, &metainfo, 3).await;
+
+    let messages = download(&mut module, &metainfo, &[bad]).await;
+
+    assert!(messages.contains(&ODiscoveryMessage::BanPeer(bad)));
+    assert!(!messages.contains(&ODiscoveryMessage::BanPeer(good_one)));
+    assert!(!messages.contains(&ODiscoveryMessage::BanPeer(good_two)));
+    assert!(messages.contains(&ODiscoveryMessage::DownloadedMetainfo(metainfo.info().clone().into())));
+}
+
+#[tokio::test]
+async fn positive_info_hash_breaks_tie_between_peers() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let metainfo = any_metainfo();
+    let mut module = UtMetadataModule::new();
+    module
+        .send(IDiscoveryMessage::DownloadMetainfo(metainfo.info().info_hash()))
+        .await
+        .unwrap();
+
+    let good = connect_peer(&mut module, &metainfo, 1).await;
+    let bad = connect_peer(&mut module, &metainfo, 2).await;
+
+    let messages = download(&mut module, &metainfo, &[bad]).await;
+
+    assert!(messages.contains(&ODiscoveryMessage::BanPeer(bad)));
+    assert!(!messages.contains(&ODiscoveryMessage::BanPeer(good)));
+    assert!(messages.contains(&ODiscoveryMessage::DownloadedMetainfo(metainfo.info().clone().into())));
+}
+
+#[tokio::test]
+async fn negative_bans_only_peer_with_bad_metadata() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::INFO);
+    });
+
+    let metainfo = any_metainfo();
+    let mut module = UtMetadataModule::new();
+    module
+        .send(IDiscoveryMessage::DownloadMetainfo(metainfo.info().info_hash()))
+        .await
+        .unwrap();
+
+    let bad = connect_peer(&mut module, &metainfo, 1).await;
+
+    let messages = download(&mut module, &metainfo, &[bad]).await;
+
+    assert_eq!(vec![ODiscoveryMessage::BanPeer(bad)], messages);
+}
@@ -10,6 +10,9 @@ pub use crate::sha::builder::ShaHashBuilder;
 /// Length of a SHA-1 hash.
 pub const SHA_HASH_LEN: usize = 20;
 
+/// Number of leading bytes shown by the short form of a `ShaHash`.
+pub const SHORT_SHA_HASH_LEN: usize = 4;
+
 /// SHA-1 hash wrapper type for performing operations on the hash.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
@@ -50,6 +53,15 @@ impl ShaHash {
     pub fn len() -> usize {
         SHA_HASH_LEN
     }
+
+    /// Abbreviated form of the hash, for identifying peers and torrents in logs.
+    #[must_use]
+    pub fn short(&self) -> ShortShaHash {
+        let mut short = [0u8; SHORT_SHA_HASH_LEN];
+        short.copy_from_slice(&self.hash[..SHORT_SHA_HASH_LEN]);
+
+        ShortShaHash { short }
+    }
 }
 
 impl std::fmt::Display for ShaHash {
@@ -63,6 +75,22 @@ impl std::fmt::Display for ShaHash {
     }
 }
 
+/// Leading bytes of a `ShaHash`, displayed as lowercase hex.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ShortShaHash {
+    short: [u8; SHORT_SHA_HASH_LEN],
+}
+
+impl std::fmt::Display for ShortShaHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in &self.short {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
 impl AsRef<[u8]> for ShaHash {
     fn as_ref(&self) -> &[u8] {
         &self.hash
@@ -170,6 +198,14 @@ impl<'a> Iterator for Bits<'a> {
 mod tests {
     use super::{ShaHash, XorRep};
 
+    #[test]
+    fn positive_short_display() {
+        let mut bytes = [0u8; super::SHA_HASH_LEN];
+        bytes[..5].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0xff]);
+
+        assert_eq!(ShaHash::from(bytes).short().to_string(), "deadbeef");
+    }
+
     #[test]
     fn positive_no_leading_zeroes() {
         let zero_bits = ShaHash::from([0u8; super::SHA_HASH_LEN]);
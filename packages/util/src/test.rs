@@ -1,4 +1,6 @@
+use std::alloc::{GlobalAlloc, Layout, System};
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use chrono::{DateTime, Duration, Utc};
 
@@ -88,3 +90,34 @@ pub fn dummy_block_node_ids(num_ids: u8) -> Vec<NodeId> {
 
     id_block
 }
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator counting every allocation, so benchmarks can check that a hot path does not allocate.
+///
+/// Install it with `#[global_allocator]`, then measure with `allocations_during`.
+#[derive(Debug)]
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+/// Number of allocations made by the `CountingAllocator` while running the given function.
+///
+/// Always zero if the `CountingAllocator` is not the global allocator.
+pub fn allocations_during<F>(f: F) -> usize
+where
+    F: FnOnce(),
+{
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
@@ -0,0 +1,66 @@
+use std::hint::black_box;
+use std::net::SocketAddr;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use util::test::{allocations_during, CountingAllocator};
+use utracker::announce::{AnnounceResponse, AnnounceResponseView, MAX_ANNOUNCE_PEERS_V4};
+use utracker::contact::{CompactPeers, CompactPeersV4};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Announce response holding as many IPv4 peers as fit in a single datagram.
+fn full_response_bytes() -> Vec<u8> {
+    let mut peers = CompactPeersV4::new();
+    for index in 0..MAX_ANNOUNCE_PEERS_V4 {
+        let [a, b] = u16::try_from(index).unwrap().to_be_bytes();
+
+        peers.insert(format!("10.0.{a}.{b}:6881").parse().unwrap());
+    }
+
+    let mut bytes = Vec::new();
+    AnnounceResponse::new(1800, 50, 50, CompactPeers::V4(peers))
+        .write_bytes(&mut bytes)
+        .unwrap();
+
+    bytes
+}
+
+fn bench_parse(bytes: &[u8]) -> AnnounceResponse<'_> {
+    AnnounceResponse::from_bytes_v4(bytes).unwrap().1
+}
+
+fn bench_parse_view(bytes: &[u8]) -> AnnounceResponseView<'_> {
+    AnnounceResponseView::from_bytes_v4(bytes).unwrap().1
+}
+
+fn bench_iter_view(bytes: &[u8]) -> Option<SocketAddr> {
+    bench_parse_view(bytes).peers().last()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let bytes = full_response_bytes();
+
+    // Guard the hot path, a regression here should fail loudly rather than show up as noise
+    assert_eq!(
+        0,
+        allocations_during(|| {
+            black_box(bench_iter_view(black_box(&bytes)));
+        })
+    );
+
+    c.bench_function("announce response parse", |b| {
+        b.iter(|| bench_parse(black_box(&bytes)));
+    });
+
+    c.bench_function("announce response view parse", |b| {
+        b.iter(|| bench_parse_view(black_box(&bytes)));
+    });
+
+    c.bench_function("announce response view peers", |b| {
+        b.iter(|| bench_iter_view(black_box(&bytes)));
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
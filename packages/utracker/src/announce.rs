@@ -12,12 +12,13 @@ use nom::multi::count;
 use nom::number::complete::{be_i32, be_i64, be_u16, be_u32, be_u8};
 use nom::sequence::tuple;
 use nom::IResult;
+use thiserror::Error;
 use tracing::instrument;
 use util::bt::{self, InfoHash, PeerId};
 use util::convert;
 
-use crate::contact::CompactPeers;
-use crate::option::AnnounceOptions;
+use crate::contact::{self, CompactPeers, CompactPeersIter};
+use crate::option::{AnnounceOptions, URLDataOption};
 
 const IMPLIED_IPV4_ID: [u8; 4] = [0u8; 4];
 const IMPLIED_IPV6_ID: [u8; 16] = [0u8; 16];
@@ -29,6 +30,24 @@ const ANNOUNCE_COMPLETED_EVENT: i32 = 1;
 const ANNOUNCE_STARTED_EVENT: i32 = 2;
 const ANNOUNCE_STOPPED_EVENT: i32 = 3;
 
+/// Largest announce response that fits in a single datagram without being fragmented over an ethernet link.
+const MAX_ANNOUNCE_RESPONSE_BYTES: usize = 1472;
+
+/// Bytes of an announce response that precede its peers, including the action and transaction id.
+pub(crate) const ANNOUNCE_RESPONSE_HEADER_BYTES: usize = 20;
+
+/// Maximum number of IPv4 peers in an announce response that fits in a single datagram.
+///
+/// Servers truncate the peers of larger responses to this many.
+pub const MAX_ANNOUNCE_PEERS_V4: usize =
+    (MAX_ANNOUNCE_RESPONSE_BYTES - ANNOUNCE_RESPONSE_HEADER_BYTES) / contact::SOCKET_ADDR_V4_BYTES;
+
+/// Maximum number of IPv6 peers in an announce response that fits in a single datagram.
+///
+/// Servers truncate the peers of larger responses to this many.
+pub const MAX_ANNOUNCE_PEERS_V6: usize =
+    (MAX_ANNOUNCE_RESPONSE_BYTES - ANNOUNCE_RESPONSE_HEADER_BYTES) / contact::SOCKET_ADDR_V6_BYTES;
+
 /// Announce request sent from the client to the server.
 ///
 /// IPv6 is supported but is [not standard](http://opentracker.blog.h3q.com/2007/12/28/the-ipv6-situation/).
@@ -169,6 +188,14 @@ impl<'a> AnnounceRequest<'a> {
         &self.options
     }
 
+    /// `URLDataOption` supplied in the request, containing the path and query of the tracker url.
+    ///
+    /// Allows trackers multiplexing by path to see which path was announced to.
+    #[must_use]
+    pub fn url_data(&self) -> Option<URLDataOption<'_>> {
+        self.options.get()
+    }
+
     /// Create an owned version of `AnnounceRequest`.
     #[must_use]
     pub fn to_owned(&self) -> AnnounceRequest<'static> {
@@ -190,6 +217,144 @@ impl<'a> AnnounceRequest<'a> {
     }
 }
 
+/// Errors occurring when building an `AnnounceRequest` with an `AnnounceRequestBuilder`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnounceRequestError {
+    #[error("Port must be non zero unless the client is stopping")]
+    ZeroPort,
+
+    #[error("Byte counts reported in the client state must not be negative")]
+    NegativeBytes,
+
+    #[error("Client reported a completed event with {bytes_left} bytes left")]
+    CompletedWithBytesLeft { bytes_left: i64 },
+}
+
+/// Builder for an `AnnounceRequest`, validating that the request is coherent before it is sent.
+///
+/// Unless set, the client state reports no progress and no event, the source address is
+/// implied from the sender, the tracker picks the number of peers, and the key is randomly
+/// generated.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug)]
+pub struct AnnounceRequestBuilder<'a> {
+    info_hash: InfoHash,
+    peer_id: PeerId,
+    state: ClientState,
+    ip: SourceIP,
+    key: Option<u32>,
+    num_want: DesiredPeers,
+    port: u16,
+    options: AnnounceOptions<'a>,
+}
+
+impl<'a> AnnounceRequestBuilder<'a> {
+    /// Create a new `AnnounceRequestBuilder` for the given `InfoHash` and `PeerId`.
+    #[must_use]
+    pub fn new(hash: InfoHash, peer_id: PeerId) -> AnnounceRequestBuilder<'a> {
+        AnnounceRequestBuilder {
+            info_hash: hash,
+            peer_id,
+            state: ClientState::new(0, 0, 0, AnnounceEvent::None),
+            ip: SourceIP::ImpliedV4,
+            key: None,
+            num_want: DesiredPeers::Default,
+            port: 0,
+            options: AnnounceOptions::new(),
+        }
+    }
+
+    /// State of the client to report to the tracker.
+    #[must_use]
+    pub fn with_state(mut self, state: ClientState) -> AnnounceRequestBuilder<'a> {
+        self.state = state;
+        self
+    }
+
+    /// Address that the tracker should send the response to.
+    #[must_use]
+    pub fn with_source_ip(mut self, ip: SourceIP) -> AnnounceRequestBuilder<'a> {
+        self.ip = ip;
+        self
+    }
+
+    /// Key identifying the client to the tracker across address changes.
+    ///
+    /// Defaults to a randomly generated key.
+    #[must_use]
+    pub fn with_key(mut self, key: u32) -> AnnounceRequestBuilder<'a> {
+        self.key = Some(key);
+        self
+    }
+
+    /// Number of peers desired by the client.
+    #[must_use]
+    pub fn with_num_want(mut self, num_want: DesiredPeers) -> AnnounceRequestBuilder<'a> {
+        self.num_want = num_want;
+        self
+    }
+
+    /// Port that peers should connect to the client on.
+    #[must_use]
+    pub fn with_port(mut self, port: u16) -> AnnounceRequestBuilder<'a> {
+        self.port = port;
+        self
+    }
+
+    /// Set of `AnnounceOptions` to send with the request.
+    #[must_use]
+    pub fn with_options(mut self, options: AnnounceOptions<'a>) -> AnnounceRequestBuilder<'a> {
+        self.options = options;
+        self
+    }
+
+    /// Path and query of the tracker url, sent as a `URLDataOption` so that trackers
+    /// multiplexing by path know which path was announced to.
+    ///
+    /// Replaces any `URLDataOption` already present in the options.
+    #[must_use]
+    pub fn with_url_data(mut self, url_data: &'a [u8]) -> AnnounceRequestBuilder<'a> {
+        self.options.insert(&URLDataOption::new(url_data));
+        self
+    }
+
+    /// Build the `AnnounceRequest`.
+    ///
+    /// # Errors
+    ///
+    /// It would return an error if the port is zero for an event other than stopped, if any
+    /// byte count is negative, or if a completed event is reported with bytes left.
+    pub fn build(self) -> Result<AnnounceRequest<'a>, AnnounceRequestError> {
+        let state = self.state;
+
+        if self.port == 0 && state.event() != AnnounceEvent::Stopped {
+            return Err(AnnounceRequestError::ZeroPort);
+        }
+
+        if state.bytes_downloaded() < 0 || state.bytes_left() < 0 || state.bytes_uploaded() < 0 {
+            return Err(AnnounceRequestError::NegativeBytes);
+        }
+
+        if state.event() == AnnounceEvent::Completed && state.bytes_left() != 0 {
+            return Err(AnnounceRequestError::CompletedWithBytesLeft {
+                bytes_left: state.bytes_left(),
+            });
+        }
+
+        Ok(AnnounceRequest::new(
+            self.info_hash,
+            self.peer_id,
+            state,
+            self.ip,
+            self.key.unwrap_or_else(rand::random),
+            self.num_want,
+            self.port,
+            self.options,
+        ))
+    }
+}
+
 /// Parse an `AnnounceRequest` with the given `SourceIP` type constructor.
 fn parse_request(bytes: &[u8], ip_type: fn(bytes: &[u8]) -> IResult<&[u8], SourceIP>) -> IResult<&[u8], AnnounceRequest<'_>> {
     let (bytes, (info_hash, peer_id, state, ip, key, num_want, port, options)) = tuple((
@@ -269,6 +434,14 @@ impl<'a> AnnounceResponse<'a> {
         Ok(())
     }
 
+    /// Replace the interval in seconds that clients should wait before re-announcing.
+    #[must_use]
+    pub fn with_interval(mut self, interval: i32) -> AnnounceResponse<'a> {
+        self.interval = interval;
+
+        self
+    }
+
     /// Interval in seconds that clients should wait before re-announcing.
     #[must_use]
     pub fn interval(&self) -> i32 {
@@ -293,6 +466,27 @@ impl<'a> AnnounceResponse<'a> {
         &self.peers
     }
 
+    /// Maximum number of peers that this response can hold while fitting in a single datagram.
+    #[must_use]
+    pub fn max_peers(&self) -> usize {
+        match self.peers {
+            CompactPeers::V4(_) => MAX_ANNOUNCE_PEERS_V4,
+            CompactPeers::V6(_) => MAX_ANNOUNCE_PEERS_V6,
+        }
+    }
+
+    /// Drop the peers that do not fit in a single datagram, keeping the first `max_peers` in their given order.
+    ///
+    /// Returns true if any peers were dropped.
+    pub fn truncate_peers(&mut self) -> bool {
+        let max_peers = self.max_peers();
+        let truncated = self.peers.len() > max_peers;
+
+        self.peers.truncate(max_peers);
+
+        truncated
+    }
+
     /// Create an owned version of `AnnounceResponse`.
     #[must_use]
     pub fn to_owned(&self) -> AnnounceResponse<'static> {
@@ -317,6 +511,124 @@ fn parse_response<'a>(
     Ok((bytes, AnnounceResponse::new(interval, leechers, seeders, peers)))
 }
 
+/// Borrow-only view of an announce response, for parsing many responses without allocating.
+///
+/// Unlike an `AnnounceResponse`, which may own its peers, an `AnnounceResponseView` only ever
+/// borrows the buffer it was parsed from, so parsing it and iterating over its peers never
+/// allocates. Use `AnnounceResponseView::to_response` where an `AnnounceResponse` is needed.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AnnounceResponseView<'a> {
+    interval: i32,
+    leechers: i32,
+    seeders: i32,
+    peers: &'a [u8],
+    is_ipv6: bool,
+}
+
+impl<'a> AnnounceResponseView<'a> {
+    /// Construct an IPv4 `AnnounceResponseView` from the given bytes, without allocating.
+    ///
+    /// # Errors
+    ///
+    /// It will return an error when unable to parse the bytes.
+    pub fn from_bytes_v4(bytes: &'a [u8]) -> IResult<&'a [u8], AnnounceResponseView<'a>> {
+        parse_response_view(bytes, false)
+    }
+
+    /// Construct an IPv6 `AnnounceResponseView` from the given bytes, without allocating.
+    ///
+    /// # Errors
+    ///
+    /// It will return an error when unable to parse the bytes.
+    pub fn from_bytes_v6(bytes: &'a [u8]) -> IResult<&'a [u8], AnnounceResponseView<'a>> {
+        parse_response_view(bytes, true)
+    }
+
+    /// Interval in seconds that clients should wait before re-announcing.
+    #[must_use]
+    pub fn interval(&self) -> i32 {
+        self.interval
+    }
+
+    /// Number of leechers the tracker knows about for the torrent.
+    #[must_use]
+    pub fn leechers(&self) -> i32 {
+        self.leechers
+    }
+
+    /// Number of seeders the tracker knows about for the torrent.
+    #[must_use]
+    pub fn seeders(&self) -> i32 {
+        self.seeders
+    }
+
+    /// Whether or not the peers are IPv6 addresses.
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        self.is_ipv6
+    }
+
+    /// Raw compact bytes of the peers, as sent by the tracker.
+    #[must_use]
+    pub fn raw_peers(&self) -> &'a [u8] {
+        self.peers
+    }
+
+    /// Iterator over the peers, decoded from their raw compact bytes as they are iterated.
+    #[must_use]
+    pub fn peers(&self) -> CompactPeersIter<'a> {
+        if self.is_ipv6 {
+            CompactPeersIter::from_raw_v6(self.peers)
+        } else {
+            CompactPeersIter::from_raw_v4(self.peers)
+        }
+    }
+
+    /// Create an `AnnounceResponse` borrowing the same peers.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the peers failed to parse, which they do not as they were validated with the view.
+    #[must_use]
+    pub fn to_response(&self) -> AnnounceResponse<'a> {
+        let parsed = if self.is_ipv6 {
+            CompactPeers::from_bytes_v6(self.peers)
+        } else {
+            CompactPeers::from_bytes_v4(self.peers)
+        };
+        let (_, peers) = parsed.expect("bip_utracker: Peers Of AnnounceResponseView Already Validated");
+
+        AnnounceResponse::new(self.interval, self.leechers, self.seeders, peers)
+    }
+}
+
+/// Parse an `AnnounceResponseView`, taking the rest of the bytes as peers.
+fn parse_response_view(bytes: &[u8], is_ipv6: bool) -> IResult<&[u8], AnnounceResponseView<'_>> {
+    let (peers, (interval, leechers, seeders)) = tuple((be_i32, be_i32, be_i32))(bytes)?;
+
+    let peer_bytes = if is_ipv6 {
+        contact::SOCKET_ADDR_V6_BYTES
+    } else {
+        contact::SOCKET_ADDR_V4_BYTES
+    };
+    let remainder_bytes = peers.len() % peer_bytes;
+    if remainder_bytes != 0 {
+        return Err(nom::Err::Incomplete(nom::Needed::new(peer_bytes - remainder_bytes)));
+    }
+
+    Ok((
+        &peers[peers.len()..],
+        AnnounceResponseView {
+            interval,
+            leechers,
+            seeders,
+            peers,
+            is_ipv6,
+        },
+    ))
+}
+
 // ----------------------------------------------------------------------------//
 
 /// Announce state of a client reported to the server.
@@ -342,6 +654,21 @@ impl ClientState {
         }
     }
 
+    /// Create a new `ClientState` from unsigned byte totals, as tracked by the client.
+    ///
+    /// Totals too large to be reported to the tracker are saturated.
+    #[must_use]
+    pub fn from_totals(bytes_downloaded: u64, bytes_left: u64, bytes_uploaded: u64, event: AnnounceEvent) -> ClientState {
+        let saturate = |bytes: u64| i64::try_from(bytes).unwrap_or(i64::MAX);
+
+        ClientState::new(
+            saturate(bytes_downloaded),
+            saturate(bytes_left),
+            saturate(bytes_uploaded),
+            event,
+        )
+    }
+
     /// Construct the `ClientState` from the given bytes.
     ///
     /// # Errors
@@ -467,7 +794,7 @@ fn parse_event(bytes: &[u8]) -> IResult<&[u8], AnnounceEvent> {
 // ----------------------------------------------------------------------------//
 
 /// Client specified IP address to send the response to.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub enum SourceIP {
     /// Infer the IPv4 address from the sender address.
     ImpliedV4,
@@ -623,14 +950,17 @@ fn parse_desired(bytes: &[u8]) -> IResult<&[u8], DesiredPeers> {
 #[cfg(test)]
 mod tests {
     use std::io::Write as _;
-    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
 
     use byteorder::{BigEndian, WriteBytesExt};
     use nom::IResult;
     use util::bt::{InfoHash, PeerId};
     use util::convert;
 
-    use super::{AnnounceEvent, AnnounceRequest, AnnounceResponse, ClientState, DesiredPeers, SourceIP};
+    use super::{
+        AnnounceEvent, AnnounceRequest, AnnounceRequestBuilder, AnnounceRequestError, AnnounceResponse, AnnounceResponseView,
+        ClientState, DesiredPeers, SourceIP, MAX_ANNOUNCE_PEERS_V4,
+    };
     use crate::announce::{parse_ipv4, parse_ipv6};
     use crate::contact::{CompactPeers, CompactPeersV4, CompactPeersV6};
     use crate::option::AnnounceOptions;
@@ -677,6 +1007,100 @@ mod tests {
         assert_eq!(&received[..], &expected[..]);
     }
 
+    #[test]
+    fn positive_build_request() {
+        let info_hash = [3u8; 20].into();
+        let peer_id = [4u8; 20].into();
+        let state = ClientState::from_totals(10, 20, 30, AnnounceEvent::Started);
+
+        let request = AnnounceRequestBuilder::new(info_hash, peer_id)
+            .with_state(state)
+            .with_key(234_234)
+            .with_num_want(DesiredPeers::Specified(34))
+            .with_port(6969)
+            .build()
+            .unwrap();
+
+        let expected = AnnounceRequest::new(
+            info_hash,
+            peer_id,
+            state,
+            SourceIP::ImpliedV4,
+            234_234,
+            DesiredPeers::Specified(34),
+            6969,
+            AnnounceOptions::new(),
+        );
+
+        assert_eq!(expected, request);
+    }
+
+    #[test]
+    fn positive_build_stopped_request_zero_port() {
+        let request = AnnounceRequestBuilder::new([3u8; 20].into(), [4u8; 20].into())
+            .with_state(ClientState::new(0, 0, 0, AnnounceEvent::Stopped))
+            .build();
+
+        assert!(request.is_ok());
+    }
+
+    #[test]
+    fn positive_build_request_url_data_round_trip() {
+        let request = AnnounceRequestBuilder::new([3u8; 20].into(), [4u8; 20].into())
+            .with_port(6969)
+            .with_url_data(b"/announce?passkey=abc")
+            .build()
+            .unwrap();
+
+        let mut bytes = Vec::new();
+        request.write_bytes(&mut bytes).unwrap();
+
+        let received = AnnounceRequest::from_bytes_v4(&bytes).unwrap().1;
+
+        assert_eq!(
+            Some(&b"/announce?passkey=abc"[..]),
+            received.url_data().map(|url_data| url_data.url_data())
+        );
+        assert_eq!(request, received);
+    }
+
+    #[test]
+    fn negative_build_request_zero_port() {
+        let request = AnnounceRequestBuilder::new([3u8; 20].into(), [4u8; 20].into())
+            .with_state(ClientState::new(0, 0, 0, AnnounceEvent::Started))
+            .build();
+
+        assert_eq!(Err(AnnounceRequestError::ZeroPort), request);
+    }
+
+    #[test]
+    fn negative_build_request_negative_bytes() {
+        let request = AnnounceRequestBuilder::new([3u8; 20].into(), [4u8; 20].into())
+            .with_state(ClientState::new(0, -1, 0, AnnounceEvent::None))
+            .with_port(6969)
+            .build();
+
+        assert_eq!(Err(AnnounceRequestError::NegativeBytes), request);
+    }
+
+    #[test]
+    fn negative_build_completed_request_bytes_left() {
+        let request = AnnounceRequestBuilder::new([3u8; 20].into(), [4u8; 20].into())
+            .with_state(ClientState::new(100, 5, 0, AnnounceEvent::Completed))
+            .with_port(6969)
+            .build();
+
+        assert_eq!(Err(AnnounceRequestError::CompletedWithBytesLeft { bytes_left: 5 }), request);
+    }
+
+    #[test]
+    fn positive_client_state_from_totals_saturates() {
+        let state = ClientState::from_totals(u64::MAX, 0, 1, AnnounceEvent::None);
+
+        assert_eq!(i64::MAX, state.bytes_downloaded());
+        assert_eq!(1, state.bytes_uploaded());
+    }
+
     #[test]
     fn positive_write_response() {
         let mut received = Vec::new();
@@ -699,6 +1123,30 @@ mod tests {
         assert_eq!(&received[..], &expected[..]);
     }
 
+    #[test]
+    fn positive_truncate_response_to_datagram() {
+        let mut peers = CompactPeersV4::new();
+        for port in 0..1000 {
+            peers.insert(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port));
+        }
+
+        let mut response = AnnounceResponse::new(1800, 1000, 0, CompactPeers::V4(peers));
+        assert!(response.truncate_peers());
+        assert!(!response.truncate_peers());
+
+        let mut received = Vec::new();
+        response.write_bytes(&mut received).unwrap();
+
+        // Action and transaction id are written in front of the response
+        assert!(received.len() + 8 <= 1472);
+        assert_eq!(MAX_ANNOUNCE_PEERS_V4, response.peers().len());
+        assert!(response
+            .peers()
+            .iter()
+            .zip(0..)
+            .all(|(peer, port)| peer == SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))));
+    }
+
     #[test]
     fn positive_write_state() {
         let mut received = Vec::new();
@@ -955,6 +1403,25 @@ mod tests {
 
         assert_eq!(received_v4, expected_v4);
         assert_eq!(received_v6, expected_v6);
+
+        let view_v4 = AnnounceResponseView::from_bytes_v4(&bytes_v4).unwrap().1;
+        let view_v6 = AnnounceResponseView::from_bytes_v6(&bytes_v6).unwrap().1;
+
+        assert_eq!(view_v4.to_response(), expected_v4);
+        assert_eq!(view_v6.to_response(), expected_v6);
+        assert!(view_v4.peers().eq(expected_v4.peers().iter()));
+        assert!(view_v6.peers().eq(expected_v6.peers().iter()));
+    }
+
+    #[test]
+    fn negative_parse_response_view_partial_peer() {
+        let mut bytes = Vec::new();
+        bytes.write_i32::<BigEndian>(34).unwrap();
+        bytes.write_i32::<BigEndian>(234).unwrap();
+        bytes.write_i32::<BigEndian>(0).unwrap();
+        bytes.extend_from_slice(&[127, 0, 0, 1, 0]);
+
+        assert!(AnnounceResponseView::from_bytes_v4(&bytes).is_err());
     }
 
     #[test]
@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 use std::sync::mpsc;
@@ -13,21 +13,28 @@ use handshake::{DiscoveryInfo, InitiateMessage, Protocol};
 use nom::IResult;
 use tracing::{instrument, Level};
 use umio::{Dispatcher, ELoopBuilder, MessageSender, Provider, ShutdownHandle};
-use util::bt::PeerId;
+use util::bt::{InfoHash, PeerId};
 
 use super::HandshakerMessage;
-use crate::announce::{AnnounceRequest, DesiredPeers, SourceIP};
+use crate::announce::{
+    AnnounceRequestBuilder, ClientState, SourceIP, ANNOUNCE_RESPONSE_HEADER_BYTES, MAX_ANNOUNCE_PEERS_V4, MAX_ANNOUNCE_PEERS_V6,
+};
 use crate::client::error::{ClientError, ClientResult};
-use crate::client::{ClientMetadata, ClientRequest, ClientResponse, ClientToken, RequestLimiter};
-use crate::option::AnnounceOptions;
+use crate::client::health::TrackerHealthMap;
+use crate::client::transaction::{OutstandingTransactions, TransactionIdGenerator};
+use crate::client::{AnnounceWarning, ClientMetadata, ClientRequest, ClientResponse, ClientToken, RequestLimiter};
+use crate::contact::{SOCKET_ADDR_V4_BYTES, SOCKET_ADDR_V6_BYTES};
+use crate::error::RetryClass;
 use crate::request::{self, RequestType, TrackerRequest};
 use crate::response::{ResponseType, TrackerResponse};
-use crate::scrape::ScrapeRequest;
+use crate::scrape::{ScrapeRequest, MAX_SCRAPE_HASHES};
 
 const EXPECTED_PACKET_LENGTH: usize = 1500;
 
 const CONNECTION_ID_VALID_DURATION_MILLIS: i64 = 60000;
 const MAXIMUM_REQUEST_RETRANSMIT_ATTEMPTS: u64 = 8;
+/// Consecutive timeouts with a working connection id before it is assumed the tracker lost track of us.
+const CONNECTION_ID_REFRESH_TIMEOUTS: usize = 2;
 
 /// Internal dispatch timeout.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -88,10 +95,26 @@ impl std::hash::Hash for TimeoutToken {
     }
 }
 
+/// Internal request, covering the requests made through `TrackerClient` methods other than `request`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DispatchRequest {
+    Client(ClientRequest),
+    /// Announce with the given url data, the path and query of the tracker url.
+    AnnounceWithUrlData(InfoHash, ClientState, Vec<u8>),
+    /// Scrape of at least one, and at most `MAX_SCRAPE_HASHES`, hashes in a single packet.
+    ScrapeBatch(Vec<InfoHash>),
+}
+
+impl From<ClientRequest> for DispatchRequest {
+    fn from(request: ClientRequest) -> Self {
+        Self::Client(request)
+    }
+}
+
 /// Internal dispatch message for clients.
 #[derive(Debug)]
 pub enum DispatchMessage {
-    Request(SocketAddr, ClientToken, ClientRequest),
+    Request(SocketAddr, ClientToken, DispatchRequest),
     StartTimer,
     Shutdown(mpsc::SyncSender<std::io::Result<()>>),
 }
@@ -100,12 +123,14 @@ pub enum DispatchMessage {
 ///
 /// Assumes `msg_capacity` is less than `usize::max_value`().
 #[allow(clippy::module_name_repetitions)]
-#[instrument(skip())]
+#[instrument(skip(transaction_ids))]
 pub fn create_dispatcher<H>(
     bind: SocketAddr,
     handshaker: H,
     msg_capacity: usize,
     limiter: RequestLimiter,
+    health: TrackerHealthMap,
+    transaction_ids: Box<dyn TransactionIdGenerator>,
 ) -> std::io::Result<(MessageSender<DispatchMessage>, SocketAddr, ShutdownHandle)>
 where
     H: Sink<std::io::Result<HandshakerMessage>> + std::fmt::Debug + DiscoveryInfo + Send + Unpin + 'static,
@@ -123,7 +148,7 @@ where
     let (mut eloop, socket, shutdown) = builder.build()?;
     let channel = eloop.channel();
 
-    let dispatcher = ClientDispatcher::new(handshaker, bind, limiter);
+    let dispatcher = ClientDispatcher::new(handshaker, bind, limiter, health, transaction_ids);
 
     let handle = {
         let (started_eloop_sender, started_eloop_receiver) = mpsc::sync_channel(0);
@@ -156,8 +181,12 @@ struct ClientDispatcher<H> {
     port: u16,
     bound_addr: SocketAddr,
     active_requests: HashMap<ClientToken, ConnectTimer>,
+    // Requests that refreshed the connection id after a probable NAT rebinding
+    rebound_requests: HashSet<ClientToken>,
     id_cache: ConnectIdCache,
+    transactions: OutstandingTransactions,
     limiter: RequestLimiter,
+    health: TrackerHealthMap,
 }
 
 impl<H> ClientDispatcher<H>
@@ -166,8 +195,14 @@ where
     H::Error: std::fmt::Display,
 {
     /// Create a new `ClientDispatcher`.
-    #[instrument(skip(), ret(level = Level::TRACE))]
-    pub fn new(handshaker: H, bind: SocketAddr, limiter: RequestLimiter) -> ClientDispatcher<H> {
+    #[instrument(skip(transaction_ids), ret(level = Level::TRACE))]
+    pub fn new(
+        handshaker: H,
+        bind: SocketAddr,
+        limiter: RequestLimiter,
+        health: TrackerHealthMap,
+        transaction_ids: Box<dyn TransactionIdGenerator>,
+    ) -> ClientDispatcher<H> {
         tracing::debug!("new client dispatcher");
 
         let peer_id = handshaker.peer_id();
@@ -179,8 +214,11 @@ where
             port,
             bound_addr: bind,
             active_requests: HashMap::new(),
+            rebound_requests: HashSet::new(),
             id_cache: ConnectIdCache::new(),
+            transactions: OutstandingTransactions::new(transaction_ids),
             limiter,
+            health,
         }
     }
 
@@ -189,6 +227,8 @@ where
     pub fn shutdown(&mut self, provider: &mut Provider<'_, ClientDispatcher<H>>) {
         tracing::debug!("shuting down...");
 
+        self.transactions.clear();
+
         let mut active_requests = std::mem::take(&mut self.active_requests);
         let mut unfinished_requests = active_requests.drain();
 
@@ -202,15 +242,26 @@ where
 
             self.notify_client(client_token, Err(ClientError::ClientShutdown));
         }
+        self.rebound_requests.clear();
         provider.shutdown();
     }
 
     /// Finish a request by sending the result back to the client.
     #[instrument(skip(self))]
     pub fn notify_client(&mut self, token: ClientToken, result: ClientResult<ClientResponse>) {
+        self.notify_client_metadata(ClientMetadata::new(token, result));
+    }
+
+    /// Finish a request by sending the given metadata back to the client.
+    #[instrument(skip(self))]
+    pub fn notify_client_metadata(&mut self, mut metadata: ClientMetadata) {
         tracing::trace!("notifying clients");
 
-        match block_on(self.handshaker.send(Ok(ClientMetadata::new(token, result).into()))) {
+        if self.rebound_requests.remove(&metadata.token()) {
+            metadata = metadata.with_nat_rebinding();
+        }
+
+        match block_on(self.handshaker.send(Ok(metadata.into()))) {
             Ok(()) => tracing::debug!("client metadata sent"),
             Err(e) => tracing::error!("sending client metadata failed with error: {e}"),
         }
@@ -225,7 +276,7 @@ where
         provider: &mut Provider<'_, ClientDispatcher<H>>,
         addr: SocketAddr,
         token: ClientToken,
-        request: ClientRequest,
+        request: DispatchRequest,
     ) {
         tracing::debug!(?addr, ?token, ?request, "sending request");
 
@@ -242,36 +293,54 @@ where
             }
             _ => (),
         };
+
+        if let DispatchRequest::ScrapeBatch(hashes) = &request {
+            if hashes.is_empty() || hashes.len() > MAX_SCRAPE_HASHES {
+                tracing::error!(num_hashes = hashes.len(), "scrape batch size out of range");
+
+                self.notify_client(token, Err(ClientError::InvalidScrapeBatch(hashes.len())));
+
+                return;
+            }
+        }
+
+        // Stop announcing torrents that the tracker permanently rejected
+        if let Some(err) = announce_hash(&request).and_then(|hash| self.health.rejection(addr, &hash)) {
+            tracing::debug!(%addr, %err, "announce skipped for rejected torrent");
+
+            self.notify_client(token, Err(ClientError::ServerMessage(err)));
+
+            return;
+        }
         self.active_requests.insert(token, ConnectTimer::new(addr, request));
 
         self.process_request(provider, token, false);
     }
 
     /// Process a response received from some tracker and match it up against our sent requests.
+    ///
+    /// The warning is passed on to the client if the response is an announce response.
     #[instrument(skip(self, provider, response, addr))]
     pub fn recv_response(
         &mut self,
         provider: &mut Provider<'_, ClientDispatcher<H>>,
         response: &TrackerResponse<'_>,
         addr: SocketAddr,
+        opt_warning: Option<AnnounceWarning>,
     ) {
         tracing::debug!(?response, ?addr, "receiving response");
 
-        let token = ClientToken(response.transaction_id());
-
-        let conn_timer = if let Some(conn_timer) = self.active_requests.remove(&token) {
-            if conn_timer.message_params().0 == addr {
-                conn_timer
-            } else {
-                tracing::error!(?conn_timer, %addr, "different message prams");
+        // Drop spoofed and late responses, leaving the request to wait for the genuine one
+        let Some(token) = self.transactions.accept(response.transaction_id(), addr) else {
+            return;
+        };
 
-                return;
-            }
-        } else {
+        let Some(mut conn_timer) = self.active_requests.remove(&token) else {
             tracing::error!(?token, "token not in active requests");
 
             return;
         };
+        conn_timer.clear_transaction_id();
 
         if let Some(clear_timeout_token) = conn_timer.timeout_id().map(TimeoutToken::cleanup) {
             provider
@@ -279,16 +348,33 @@ where
                 .expect("bip_utracker: Failed To Clear Request Timeout");
         };
 
+        if let Some(sent_at) = conn_timer.sent_at() {
+            self.health.record_round_trip(addr, sent_at.elapsed());
+        }
+
         // Check if the response requires us to update the connection timer
         if let &ResponseType::Connect(id) = response.response_type() {
             self.id_cache.put(addr, id);
 
+            if conn_timer.take_refreshing() {
+                tracing::warn!(%addr, "tracker responded once the connection id was refreshed, our NAT mapping probably changed");
+
+                self.health.record_nat_rebinding(addr);
+                self.rebound_requests.insert(token);
+            }
+
             self.active_requests.insert(token, conn_timer);
             self.process_request(provider, token, false);
         } else {
+            self.id_cache.confirm(addr);
+
             // Match the request type against the response type and update our client
             match (conn_timer.message_params().1, response.response_type()) {
-                (&ClientRequest::Announce(hash, _), ResponseType::Announce(res)) => {
+                (
+                    &DispatchRequest::Client(ClientRequest::Announce(hash, _) | ClientRequest::AnnounceWithSource(hash, _, _))
+                    | &DispatchRequest::AnnounceWithUrlData(hash, _, _),
+                    ResponseType::Announce(res),
+                ) if res.peers().is_ipv6() == source_ip(addr, conn_timer.message_params().1).is_ipv6() => {
                     // Forward contact information on to the handshaker
                     for addr in res.peers().iter() {
                         tracing::debug!("sending will block if unable to send!");
@@ -301,15 +387,37 @@ where
                         }
                     }
 
-                    self.notify_client(token, Ok(ClientResponse::Announce(res.to_owned())));
+                    self.health.record_success(addr, true);
+
+                    let mut metadata = ClientMetadata::new(token, Ok(ClientResponse::Announce(res.to_owned())));
+                    if let Some(warning) = opt_warning {
+                        tracing::warn!(?warning, %addr, "announce response peers may be incomplete");
+                        metadata = metadata.with_warning(warning);
+                    }
+                    self.notify_client_metadata(metadata);
                 }
-                (&ClientRequest::Scrape(..), ResponseType::Scrape(res)) => {
+                (&DispatchRequest::Client(ClientRequest::Scrape(..)), ResponseType::Scrape(res)) => {
+                    self.health.record_success(addr, false);
                     self.notify_client(token, Ok(ClientResponse::Scrape(res.to_owned())));
                 }
-                (_, ResponseType::Error(res)) => {
+                (DispatchRequest::ScrapeBatch(hashes), ResponseType::Scrape(res)) if res.iter().len() == hashes.len() => {
+                    let stats = hashes.iter().copied().zip(res.iter()).collect();
+
+                    self.health.record_success(addr, false);
+                    self.notify_client(token, Ok(ClientResponse::ScrapeBatch(stats)));
+                }
+                (request, ResponseType::Error(res)) => {
+                    self.health.record_failure(addr);
+
+                    if let Some(hash) = announce_hash(request).filter(|_| res.retry() == RetryClass::Permanent) {
+                        tracing::warn!(%addr, %hash, reason = ?res.reason(), "tracker permanently rejected torrent");
+
+                        self.health.record_rejection(addr, hash, res.to_owned());
+                    }
                     self.notify_client(token, Err(ClientError::ServerMessage(res.to_owned())));
                 }
                 _ => {
+                    self.health.record_failure(addr);
                     self.notify_client(token, Err(ClientError::ServerError));
                 }
             }
@@ -331,50 +439,89 @@ where
 
         // Resolve the duration of the current timeout to use
         let Some(next_timeout) = conn_timer.current_timeout(timed_out) else {
+            if let Some(id) = conn_timer.transaction_id() {
+                self.transactions.finish(id, token);
+            }
+
             let err = ClientError::MaxTimeout;
 
             tracing::error!("error reached timeout: {err}");
 
+            self.health.record_failure(conn_timer.message_params().0);
             self.notify_client(token, Err(err));
 
             return;
         };
 
         let addr = conn_timer.message_params().0;
-        let opt_conn_id = self.id_cache.get(conn_timer.message_params().0);
+
+        // A tracker that stops responding to a connection id it used to accept has most likely
+        // lost track of us, such as after our NAT mapping changed, so get a new connection id.
+        // A single lost datagram is not evidence of that, so wait for several timeouts in a row
+        if timed_out && conn_timer.sent_with_conn_id() && self.id_cache.record_timeout(addr) {
+            tracing::debug!(%addr, "requests keep timing out with a working connection id, refreshing it");
+
+            self.id_cache.remove(addr);
+            conn_timer.set_refreshing();
+        }
+        let opt_conn_id = self.id_cache.get(addr);
 
         // Resolve the type of request we need to make
         let (conn_id, request_type) = match (opt_conn_id, conn_timer.message_params().1) {
-            (Some(id), &ClientRequest::Announce(hash, state)) => {
-                let source_ip = match addr {
-                    SocketAddr::V4(_) => SourceIP::ImpliedV4,
-                    SocketAddr::V6(_) => SourceIP::ImpliedV6,
-                };
-                let key = rand::random::<u32>();
-
-                (
-                    id,
-                    RequestType::Announce(AnnounceRequest::new(
-                        hash,
-                        self.pid,
-                        state,
-                        source_ip,
-                        key,
-                        DesiredPeers::Default,
-                        self.port,
-                        AnnounceOptions::new(),
-                    )),
+            (
+                Some(id),
+                request @ (&DispatchRequest::Client(
+                    ClientRequest::Announce(hash, state) | ClientRequest::AnnounceWithSource(hash, state, _),
                 )
+                | &DispatchRequest::AnnounceWithUrlData(hash, state, _)),
+            ) => {
+                let mut builder = AnnounceRequestBuilder::new(hash, self.pid)
+                    .with_state(state)
+                    .with_source_ip(source_ip(addr, request))
+                    .with_port(self.port);
+
+                if let DispatchRequest::AnnounceWithUrlData(_, _, url_data) = request {
+                    builder = builder.with_url_data(url_data);
+                }
+
+                let announce_result = builder.build();
+
+                match announce_result {
+                    Ok(announce) => (id, RequestType::Announce(announce)),
+                    Err(err) => {
+                        tracing::error!("error building announce: {err}");
+
+                        self.notify_client(token, Err(err.into()));
+
+                        return;
+                    }
+                }
             }
-            (Some(id), &ClientRequest::Scrape(hash)) => {
+            (Some(id), &DispatchRequest::Client(ClientRequest::Scrape(hash))) => {
                 let mut scrape_request = ScrapeRequest::new();
                 scrape_request.insert(hash);
 
                 (id, RequestType::Scrape(scrape_request))
             }
+            (Some(id), DispatchRequest::ScrapeBatch(hashes)) => {
+                let mut scrape_request = ScrapeRequest::new();
+                for hash in hashes {
+                    scrape_request.insert(*hash);
+                }
+
+                (id, RequestType::Scrape(scrape_request))
+            }
             (None, _) => (request::CONNECT_ID_PROTOCOL_ID, RequestType::Connect),
         };
-        let tracker_request = TrackerRequest::new(conn_id, token.0, request_type);
+
+        // Responses to any previous packet for this request are no longer accepted
+        if let Some(id) = conn_timer.transaction_id() {
+            self.transactions.finish(id, token);
+        }
+        let transaction_id = self.transactions.start(token, addr);
+
+        let sent_with_conn_id = !matches!(request_type, RequestType::Connect);
+        let tracker_request = TrackerRequest::new(conn_id, transaction_id, request_type);
 
         // Try to write the request out to the server
         let mut write_success = false;
@@ -402,9 +549,14 @@ where
         // If message was not sent (too long to fit) then end the request
         if write_success {
             conn_timer.set_timeout_id(timeout_id);
+            conn_timer.set_transaction_id(transaction_id);
+            conn_timer.set_sent_at(Instant::now());
+            conn_timer.set_sent_with_conn_id(sent_with_conn_id);
 
             self.active_requests.insert(token, conn_timer);
         } else {
+            self.transactions.finish(transaction_id, token);
+
             let e = ClientError::MaxLength;
             tracing::warn!(?e, "notifying client with error");
 
@@ -425,11 +577,13 @@ where
     fn incoming(&mut self, mut provider: Provider<'_, Self>, message: &[u8], addr: SocketAddr) {
         tracing::debug!(?message, %addr, "received incoming");
 
+        let (message, opt_warning) = trim_announce_response(message);
+
         let () = match TrackerResponse::from_bytes(message) {
             IResult::Ok((_, response)) => {
                 tracing::trace!(?response, %addr, "received an incoming response");
 
-                self.recv_response(&mut provider, &response, addr);
+                self.recv_response(&mut provider, &response, addr, opt_warning);
             }
             Err(e) => {
                 tracing::error!(%e, "received an incoming error message");
@@ -512,22 +666,30 @@ impl DerefMut for TimeoutId {
 
 /// Contains logic for making sure a valid connection id is present
 /// and correctly timing out when sending requests to the server.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct ConnectTimer {
     addr: SocketAddr,
     attempt: u64,
-    request: ClientRequest,
+    request: DispatchRequest,
     timeout_id: Option<TimeoutId>,
+    transaction_id: Option<u32>,
+    sent_at: Option<Instant>,
+    sent_with_conn_id: bool,
+    refreshing: bool,
 }
 
 impl ConnectTimer {
     /// Create a new `ConnectTimer`.
-    pub fn new(addr: SocketAddr, request: ClientRequest) -> ConnectTimer {
+    pub fn new(addr: SocketAddr, request: DispatchRequest) -> ConnectTimer {
         ConnectTimer {
             addr,
             attempt: 0,
             request,
             timeout_id: None,
+            transaction_id: None,
+            sent_at: None,
+            sent_with_conn_id: false,
+            refreshing: false,
         }
     }
 
@@ -557,13 +719,102 @@ impl ConnectTimer {
         self.timeout_id = Some(id);
     }
 
+    /// Yields the transaction id of the last packet sent for the request, if it awaits a response.
+    pub fn transaction_id(&self) -> Option<u32> {
+        self.transaction_id
+    }
+
+    /// Sets the transaction id of the last packet sent for the request.
+    pub fn set_transaction_id(&mut self, id: u32) {
+        self.transaction_id = Some(id);
+    }
+
+    /// Clears the transaction id, once a response to the last packet has been received.
+    pub fn clear_transaction_id(&mut self) {
+        self.transaction_id = None;
+    }
+
+    /// Yields the time that the last packet for the request was sent, if one was sent.
+    pub fn sent_at(&self) -> Option<Instant> {
+        self.sent_at
+    }
+
+    /// Sets the time that the last packet for the request was sent.
+    pub fn set_sent_at(&mut self, sent_at: Instant) {
+        self.sent_at = Some(sent_at);
+    }
+
+    /// Whether the last packet sent for the request used a connection id, rather than asking for one.
+    pub fn sent_with_conn_id(&self) -> bool {
+        self.sent_with_conn_id
+    }
+
+    /// Sets whether the last packet sent for the request used a connection id.
+    pub fn set_sent_with_conn_id(&mut self, sent_with_conn_id: bool) {
+        self.sent_with_conn_id = sent_with_conn_id;
+    }
+
+    /// Marks the request as refreshing a connection id that the tracker stopped responding to.
+    pub fn set_refreshing(&mut self) {
+        self.refreshing = true;
+    }
+
+    /// Yields whether the request was refreshing the connection id, clearing it.
+    pub fn take_refreshing(&mut self) -> bool {
+        std::mem::take(&mut self.refreshing)
+    }
+
     /// Yields the message parameters for the current connection.
     #[instrument(skip(self), ret(level = Level::TRACE))]
-    pub fn message_params(&self) -> (SocketAddr, &ClientRequest) {
+    pub fn message_params(&self) -> (SocketAddr, &DispatchRequest) {
         (self.addr, &self.request)
     }
 }
 
+/// Source ip for an announce request, implied from the tracker address unless given by the caller.
+fn source_ip(addr: SocketAddr, request: &DispatchRequest) -> SourceIP {
+    match (request, addr) {
+        (&DispatchRequest::Client(ClientRequest::AnnounceWithSource(_, _, source_ip)), _) => source_ip,
+        (_, SocketAddr::V4(_)) => SourceIP::ImpliedV4,
+        (_, SocketAddr::V6(_)) => SourceIP::ImpliedV6,
+    }
+}
+
+/// Torrent announced by the request, None for scrape requests.
+fn announce_hash(request: &DispatchRequest) -> Option<InfoHash> {
+    match request {
+        &DispatchRequest::Client(ClientRequest::Announce(hash, _) | ClientRequest::AnnounceWithSource(hash, _, _))
+        | &DispatchRequest::AnnounceWithUrlData(hash, _, _) => Some(hash),
+        DispatchRequest::Client(ClientRequest::Scrape(_)) | DispatchRequest::ScrapeBatch(_) => None,
+    }
+}
+
+/// Drop any partial peer from the end of an announce response, which was cut short by our receive buffer.
+///
+/// Returns the warning for the client if the peers of the response may be incomplete, other responses are left as is.
+fn trim_announce_response(message: &[u8]) -> (&[u8], Option<AnnounceWarning>) {
+    let action_id = message.get(..4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()));
+    let (peer_bytes, max_peers) = match action_id {
+        Some(crate::ANNOUNCE_IPV4_ACTION_ID) => (SOCKET_ADDR_V4_BYTES, MAX_ANNOUNCE_PEERS_V4),
+        Some(crate::ANNOUNCE_IPV6_ACTION_ID) => (SOCKET_ADDR_V6_BYTES, MAX_ANNOUNCE_PEERS_V6),
+        _ => return (message, None),
+    };
+    let Some(peers_len) = message.len().checked_sub(ANNOUNCE_RESPONSE_HEADER_BYTES) else {
+        return (message, None);
+    };
+
+    let trimmed = &message[..message.len() - peers_len % peer_bytes];
+    let opt_warning = if message.len() >= EXPECTED_PACKET_LENGTH || trimmed.len() != message.len() {
+        Some(AnnounceWarning::Truncated)
+    } else if peers_len / peer_bytes >= max_peers {
+        Some(AnnounceWarning::DatagramFull)
+    } else {
+        None
+    };
+
+    (trimmed, opt_warning)
+}
+
 /// Calculates the timeout for the request given the attempt count.
 #[instrument(skip())]
 fn calculate_message_timeout_millis(attempt: u64) -> u64 {
@@ -578,15 +829,22 @@ fn calculate_message_timeout_millis(attempt: u64) -> u64 {
 // ----------------------------------------------------------------------------//
 
 /// Cache for storing connection ids associated with a specific server address.
+///
+/// Connection ids are confirmed once the server responds to a request made with them, after which
+/// the requests made with them that timed out in a row are counted.
 #[derive(Debug)]
 struct ConnectIdCache {
     cache: HashMap<SocketAddr, (u64, Instant)>,
+    confirmed: HashMap<SocketAddr, usize>,
 }
 
 impl ConnectIdCache {
     /// Create a new connect id cache.
     fn new() -> ConnectIdCache {
-        ConnectIdCache { cache: HashMap::new() }
+        ConnectIdCache {
+            cache: HashMap::new(),
+            confirmed: HashMap::new(),
+        }
     }
 
     /// Get an active connection id for the given addr.
@@ -604,6 +862,7 @@ impl ConnectIdCache {
 
                 if is_expired(curr_time, prev_time) {
                     occ.remove();
+                    self.confirmed.remove(&addr);
 
                     tracing::warn!("connection id was already expired");
 
@@ -623,6 +882,31 @@ impl ConnectIdCache {
         let curr_time = Instant::now();
 
         self.cache.insert(addr, (connect_id, curr_time));
+        self.confirmed.remove(&addr);
+    }
+
+    /// Mark the connection id for the given addr as one that the server responds to.
+    fn confirm(&mut self, addr: SocketAddr) {
+        if self.cache.contains_key(&addr) {
+            self.confirmed.insert(addr, 0);
+        }
+    }
+
+    /// Record that a request made with the connection id for the given addr timed out.
+    ///
+    /// Returns true if the server used to respond to the connection id, but has not for the
+    /// last `CONNECTION_ID_REFRESH_TIMEOUTS` requests made with it.
+    fn record_timeout(&mut self, addr: SocketAddr) -> bool {
+        self.confirmed.get_mut(&addr).is_some_and(|timeouts| {
+            *timeouts += 1;
+            *timeouts >= CONNECTION_ID_REFRESH_TIMEOUTS
+        })
+    }
+
+    /// Remove the connection id for the given addr.
+    fn remove(&mut self, addr: SocketAddr) {
+        self.cache.remove(&addr);
+        self.confirmed.remove(&addr);
     }
 
     /// Removes all entries that have expired.
@@ -635,7 +919,7 @@ impl ConnectIdCache {
         let mut opt_curr_entry = self.cache.iter().skip(curr_index).map(|(&k, &v)| (k, v)).next();
         while let Some((addr, (_, prev_time))) = opt_curr_entry.take() {
             if is_expired(curr_time, prev_time) {
-                self.cache.remove(&addr);
+                self.remove(addr);
             }
 
             curr_index += 1;
@@ -1,6 +1,7 @@
 use thiserror::Error;
 
-use crate::error::ErrorResponse;
+use crate::announce::AnnounceRequestError;
+use crate::error::{ErrorResponse, RetryClass};
 
 /// Result type for a `ClientRequest`.
 pub type ClientResult<T> = Result<T, ClientError>;
@@ -24,6 +25,30 @@ pub enum ClientError {
     #[error("Requested to send from IPv4 to IPv6 or vice versa")]
     IPVersionMismatch,
 
+    #[error("Requested a scrape batch of {0} hashes, expected 1 to {max}", max = crate::scrape::MAX_SCRAPE_HASHES)]
+    InvalidScrapeBatch(usize),
+
+    #[error("Requested an invalid announce : {0}")]
+    InvalidAnnounce(#[from] AnnounceRequestError),
+
     #[error("Server returned an error message : {0}")]
     ServerMessage(#[from] ErrorResponse<'static>),
 }
+
+impl ClientError {
+    /// Whether the request should be retried.
+    ///
+    /// Error messages from the server are classified by their `ErrorReason`, timeouts and invalid
+    /// messages may be temporary, while requests that could not be made at all are permanent.
+    #[must_use]
+    pub fn retry(&self) -> RetryClass {
+        match self {
+            ClientError::ServerMessage(err) => err.retry(),
+            ClientError::MaxTimeout | ClientError::ClientShutdown | ClientError::ServerError => RetryClass::Transient,
+            ClientError::MaxLength
+            | ClientError::IPVersionMismatch
+            | ClientError::InvalidScrapeBatch(_)
+            | ClientError::InvalidAnnounce(_) => RetryClass::Permanent,
+        }
+    }
+}
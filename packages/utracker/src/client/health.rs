@@ -0,0 +1,249 @@
+//! Health of the trackers requested by a `TrackerClient`.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use util::bt::InfoHash;
+
+use crate::error::ErrorResponse;
+
+/// Number of recent requests (and round trips) that health statistics are calculated over.
+const HEALTH_WINDOW_LEN: usize = 20;
+
+/// Snapshot of the health of a single tracker, as seen by a `TrackerClient`.
+///
+/// Success rate and round trip time are calculated over the most recent requests only, so
+/// they reflect the current state of the tracker rather than its entire history.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackerHealth {
+    outcomes: VecDeque<bool>,
+    round_trips: VecDeque<Duration>,
+    consecutive_failures: u32,
+    last_announce: Option<Instant>,
+    nat_rebindings: u32,
+    rejected: HashMap<InfoHash, ErrorResponse<'static>>,
+}
+
+impl TrackerHealth {
+    /// Fraction of recent requests that the tracker responded to successfully.
+    ///
+    /// Returns None if no request to the tracker has completed yet.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn success_rate(&self) -> Option<f64> {
+        if self.outcomes.is_empty() {
+            return None;
+        }
+
+        let successes = self.outcomes.iter().filter(|&&success| success).count();
+
+        Some(successes as f64 / self.outcomes.len() as f64)
+    }
+
+    /// Number of requests to the tracker that have failed since the last successful request.
+    #[must_use]
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Time that the tracker last responded successfully to an announce.
+    #[must_use]
+    pub fn last_announce(&self) -> Option<Instant> {
+        self.last_announce
+    }
+
+    /// Number of times the tracker stopped responding to our connection id, and responded again once it was
+    /// refreshed, which most likely means that the NAT mapping of our socket changed.
+    #[must_use]
+    pub fn nat_rebindings(&self) -> u32 {
+        self.nat_rebindings
+    }
+
+    /// Error the tracker permanently rejected announces for the given torrent with, such as an unregistered torrent.
+    ///
+    /// Announces for rejected torrents fail with this error without being sent, until the rejection is cleared
+    /// with `TrackerClient::clear_rejected_torrent`.
+    #[must_use]
+    pub fn rejection(&self, hash: &InfoHash) -> Option<&ErrorResponse<'static>> {
+        self.rejected.get(hash)
+    }
+
+    /// Average time between sending a packet to the tracker and receiving its response, over recent packets.
+    ///
+    /// Returns None if the tracker has not responded to any packet yet.
+    #[must_use]
+    pub fn average_rtt(&self) -> Option<Duration> {
+        let num_round_trips = u32::try_from(self.round_trips.len()).ok().filter(|&len| len != 0)?;
+
+        Some(self.round_trips.iter().sum::<Duration>() / num_round_trips)
+    }
+
+    fn record_round_trip(&mut self, rtt: Duration) {
+        push_bounded(&mut self.round_trips, rtt);
+    }
+
+    fn record_outcome(&mut self, success: bool) {
+        push_bounded(&mut self.outcomes, success);
+
+        if success {
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        }
+    }
+}
+
+fn push_bounded<T>(window: &mut VecDeque<T>, value: T) {
+    if window.len() == HEALTH_WINDOW_LEN {
+        window.pop_front();
+    }
+
+    window.push_back(value);
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Health of every tracker, shared between the `TrackerClient` and its dispatcher.
+#[derive(Clone, Debug, Default)]
+pub struct TrackerHealthMap {
+    trackers: Arc<Mutex<HashMap<SocketAddr, TrackerHealth>>>,
+}
+
+impl TrackerHealthMap {
+    pub fn new() -> TrackerHealthMap {
+        TrackerHealthMap::default()
+    }
+
+    /// Record a response from the tracker, received the given amount of time after our packet was sent.
+    pub fn record_round_trip(&self, addr: SocketAddr, rtt: Duration) {
+        self.trackers.lock().unwrap().entry(addr).or_default().record_round_trip(rtt);
+    }
+
+    /// Record a request that the tracker responded to successfully.
+    pub fn record_success(&self, addr: SocketAddr, announced: bool) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let health = trackers.entry(addr).or_default();
+
+        health.record_outcome(true);
+        if announced {
+            health.last_announce = Some(Instant::now());
+        }
+    }
+
+    /// Record a request that failed, because the tracker timed out or responded with an error.
+    pub fn record_failure(&self, addr: SocketAddr) {
+        self.trackers.lock().unwrap().entry(addr).or_default().record_outcome(false);
+    }
+
+    /// Record that the tracker responded once our connection id was refreshed, after it stopped responding to it.
+    pub fn record_nat_rebinding(&self, addr: SocketAddr) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let health = trackers.entry(addr).or_default();
+
+        health.nat_rebindings = health.nat_rebindings.saturating_add(1);
+    }
+
+    /// Record that the tracker permanently rejected announces for the given torrent.
+    pub fn record_rejection(&self, addr: SocketAddr, hash: InfoHash, err: ErrorResponse<'static>) {
+        self.trackers
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_default()
+            .rejected
+            .insert(hash, err);
+    }
+
+    /// Error the tracker permanently rejected announces for the given torrent with, if any.
+    pub fn rejection(&self, addr: SocketAddr, hash: &InfoHash) -> Option<ErrorResponse<'static>> {
+        self.trackers
+            .lock()
+            .unwrap()
+            .get(&addr)
+            .and_then(|health| health.rejection(hash).cloned())
+    }
+
+    /// Clear the rejection of the given torrent by the tracker, returns false if it was not rejected.
+    pub fn clear_rejection(&self, addr: SocketAddr, hash: &InfoHash) -> bool {
+        self.trackers
+            .lock()
+            .unwrap()
+            .get_mut(&addr)
+            .is_some_and(|health| health.rejected.remove(hash).is_some())
+    }
+
+    /// Health of the given tracker, if it has been requested.
+    pub fn get(&self, addr: SocketAddr) -> Option<TrackerHealth> {
+        self.trackers.lock().unwrap().get(&addr).cloned()
+    }
+
+    /// Health of every tracker that has been requested.
+    pub fn snapshot(&self) -> Vec<(SocketAddr, TrackerHealth)> {
+        self.trackers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&addr, health)| (addr, health.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use super::{TrackerHealthMap, HEALTH_WINDOW_LEN};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn positive_success_rate_and_consecutive_failures() {
+        let health_map = TrackerHealthMap::new();
+
+        health_map.record_success(addr(1), true);
+        health_map.record_failure(addr(1));
+        health_map.record_failure(addr(1));
+        health_map.record_success(addr(1), false);
+        health_map.record_failure(addr(1));
+
+        let health = health_map.get(addr(1)).unwrap();
+        assert_eq!(Some(0.4), health.success_rate());
+        assert_eq!(1, health.consecutive_failures());
+        assert!(health.last_announce().is_some());
+
+        assert!(health_map.get(addr(2)).is_none());
+    }
+
+    #[test]
+    fn positive_success_rate_over_recent_requests() {
+        let health_map = TrackerHealthMap::new();
+
+        health_map.record_failure(addr(1));
+        for _ in 0..HEALTH_WINDOW_LEN {
+            health_map.record_success(addr(1), false);
+        }
+
+        let health = health_map.get(addr(1)).unwrap();
+        assert_eq!(Some(1.0), health.success_rate());
+        assert_eq!(0, health.consecutive_failures());
+        assert!(health.last_announce().is_none());
+    }
+
+    #[test]
+    fn positive_average_rtt() {
+        let health_map = TrackerHealthMap::new();
+
+        health_map.record_round_trip(addr(1), Duration::from_millis(100));
+        health_map.record_round_trip(addr(1), Duration::from_millis(300));
+
+        let health = health_map.get(addr(1)).unwrap();
+        assert_eq!(Some(Duration::from_millis(200)), health.average_rtt());
+        assert_eq!(None, health.success_rate());
+    }
+}
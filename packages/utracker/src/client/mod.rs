@@ -10,13 +10,19 @@ use umio::{MessageSender, ShutdownHandle};
 use util::bt::InfoHash;
 use util::trans::{LocallyShuffledIds, TransactionIds};
 
-use crate::announce::{AnnounceResponse, ClientState};
-use crate::client::dispatcher::DispatchMessage;
+use crate::announce::{AnnounceResponse, ClientState, SourceIP};
+use crate::client::dispatcher::{DispatchMessage, DispatchRequest};
 use crate::client::error::ClientResult;
-use crate::scrape::ScrapeResponse;
+use crate::client::health::{TrackerHealth, TrackerHealthMap};
+use crate::client::multi::MultiAnnounce;
+use crate::client::transaction::{RandomTransactionIds, TransactionIdGenerator};
+use crate::scrape::{self, ScrapeResponse, ScrapeStats};
 
 mod dispatcher;
 pub mod error;
+pub mod health;
+pub mod multi;
+pub mod transaction;
 
 /// Capacity of outstanding requests (assuming each request uses at most 1 timer at any time)
 const DEFAULT_CAPACITY: usize = 4096;
@@ -43,23 +49,57 @@ impl From<ClientMetadata> for HandshakerMessage {
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ClientRequest {
+    /// Announce using the address family of the tracker to select the IPv4 or IPv6 action.
     Announce(InfoHash, ClientState),
+    /// Announce with the given source ip, an IPv6 source selects the IPv6 action,
+    /// and so a peer list of IPv6 addresses, regardless of the tracker address family.
+    AnnounceWithSource(InfoHash, ClientState, SourceIP),
     Scrape(InfoHash),
 }
 
+/// Warning that the peers of an announce response may be incomplete.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AnnounceWarning {
+    /// The response was larger than our receive buffer and was cut short, so the peers at the end were lost.
+    Truncated,
+    /// The response holds as many peers as fit in a single datagram, so the tracker may have left some out.
+    DatagramFull,
+}
+
 /// Response metadata from a request.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct ClientMetadata {
     token: ClientToken,
     result: ClientResult<ClientResponse>,
+    warning: Option<AnnounceWarning>,
+    nat_rebinding: bool,
 }
 
 impl ClientMetadata {
     /// Create a new `ClientMetadata` container.
     #[must_use]
     pub fn new(token: ClientToken, result: ClientResult<ClientResponse>) -> ClientMetadata {
-        ClientMetadata { token, result }
+        ClientMetadata {
+            token,
+            result,
+            warning: None,
+            nat_rebinding: false,
+        }
+    }
+
+    /// Attach a warning that the peers of the announce response may be incomplete.
+    #[must_use]
+    pub fn with_warning(mut self, warning: AnnounceWarning) -> ClientMetadata {
+        self.warning = Some(warning);
+        self
+    }
+
+    /// Note that the request refreshed the connection id after the tracker stopped responding to it.
+    #[must_use]
+    pub fn with_nat_rebinding(mut self) -> ClientMetadata {
+        self.nat_rebinding = true;
+        self
     }
 
     /// Access the request token corresponding to this metadata.
@@ -72,6 +112,22 @@ impl ClientMetadata {
     pub fn result(&self) -> &ClientResult<ClientResponse> {
         &self.result
     }
+
+    /// Warning that the peers of the announce response may be incomplete, if any.
+    #[must_use]
+    pub fn warning(&self) -> Option<AnnounceWarning> {
+        self.warning
+    }
+
+    /// Whether the NAT mapping of our socket probably changed during the request.
+    ///
+    /// The tracker stopped responding to a connection id that it used to accept, and responded again
+    /// once a new connection id was requested, after which the request was sent again. Any announce
+    /// made before should be assumed lost to the tracker, as it was made from the old mapping.
+    #[must_use]
+    pub fn nat_rebinding(&self) -> bool {
+        self.nat_rebinding
+    }
 }
 
 /// Response received by the `TrackerClient`.
@@ -82,6 +138,8 @@ pub enum ClientResponse {
     Announce(AnnounceResponse<'static>),
     /// Scrape response.
     Scrape(ScrapeResponse<'static>),
+    /// Batched scrape response, with the stats for each requested hash.
+    ScrapeBatch(Vec<(InfoHash, ScrapeStats)>),
 }
 
 impl ClientResponse {
@@ -94,7 +152,7 @@ impl ClientResponse {
     pub fn announce_response(&self) -> Option<&AnnounceResponse<'static>> {
         match self {
             ClientResponse::Announce(res) => Some(res),
-            &ClientResponse::Scrape(_) => None,
+            ClientResponse::Scrape(_) | ClientResponse::ScrapeBatch(_) => None,
         }
     }
 
@@ -106,10 +164,23 @@ impl ClientResponse {
     #[must_use]
     pub fn scrape_response(&self) -> Option<&ScrapeResponse<'static>> {
         match self {
-            &ClientResponse::Announce(_) => None,
+            ClientResponse::Announce(_) | ClientResponse::ScrapeBatch(_) => None,
             ClientResponse::Scrape(res) => Some(res),
         }
     }
+
+    /// Optionally return a reference to the per hash stats of a batched scrape.
+    ///
+    /// If you know that the token associated with the response was retrieved
+    /// from `TrackerClient::scrape`, then unwrapping this value is guaranteed to
+    /// succeed.
+    #[must_use]
+    pub fn scrape_batch_response(&self) -> Option<&[(InfoHash, ScrapeStats)]> {
+        match self {
+            ClientResponse::Announce(_) | ClientResponse::Scrape(_) => None,
+            ClientResponse::ScrapeBatch(stats) => Some(stats),
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------//
@@ -123,6 +194,7 @@ pub struct TrackerClient {
     // We are in charge of incrementing this, background worker is in charge of decrementing
     limiter: RequestLimiter,
     generator: TokenGenerator,
+    health: TrackerHealthMap,
     bound_socket: SocketAddr,
     shutdown_handle: ShutdownHandle,
 }
@@ -144,6 +216,33 @@ impl TrackerClient {
     where
         H: Sink<std::io::Result<HandshakerMessage>> + std::fmt::Debug + DiscoveryInfo + Send + Unpin + 'static,
         H::Error: std::fmt::Display,
+    {
+        TrackerClient::run_with_transaction_ids(bind, handshaker, capacity_or_default, RandomTransactionIds)
+    }
+
+    /// Run a new `TrackerClient` that sends packets with transaction ids from the given generator.
+    ///
+    /// Responses from an address other than the one the request was sent to, or with a transaction
+    /// id that is unknown or belongs to an earlier (retransmitted) packet, are dropped.
+    ///
+    /// # Errors
+    ///
+    /// It would return a IO error if unable build a new client.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if the desired capacity is too large.
+    #[instrument(skip(transaction_ids))]
+    pub fn run_with_transaction_ids<H, T>(
+        bind: SocketAddr,
+        handshaker: H,
+        capacity_or_default: Option<usize>,
+        transaction_ids: T,
+    ) -> std::io::Result<TrackerClient>
+    where
+        H: Sink<std::io::Result<HandshakerMessage>> + std::fmt::Debug + DiscoveryInfo + Send + Unpin + 'static,
+        H::Error: std::fmt::Display,
+        T: TransactionIdGenerator + 'static,
     {
         let capacity = if let Some(capacity) = capacity_or_default {
             tracing::trace!("with capacity {capacity}");
@@ -164,9 +263,16 @@ impl TrackerClient {
         );
         // Limit the capacity of messages (channel capacity - 1)
         let limiter = RequestLimiter::new(capacity);
+        let health = TrackerHealthMap::new();
 
-        let (dispatcher, bound_socket, shutdown_handle) =
-            dispatcher::create_dispatcher(bind, handshaker, chan_capacity, limiter.clone())?;
+        let (dispatcher, bound_socket, shutdown_handle) = dispatcher::create_dispatcher(
+            bind,
+            handshaker,
+            chan_capacity,
+            limiter.clone(),
+            health.clone(),
+            Box::new(transaction_ids),
+        )?;
 
         tracing::info!(?bound_socket, "running client");
 
@@ -174,6 +280,7 @@ impl TrackerClient {
             send: dispatcher,
             limiter,
             generator: TokenGenerator::new(),
+            health,
             bound_socket,
             shutdown_handle,
         })
@@ -188,6 +295,10 @@ impl TrackerClient {
     /// It would panic if unable to send request message.
     #[instrument(skip(self))]
     pub fn request(&mut self, addr: SocketAddr, request: ClientRequest) -> Option<ClientToken> {
+        self.dispatch(addr, request.into())
+    }
+
+    fn dispatch(&mut self, addr: SocketAddr, request: DispatchRequest) -> Option<ClientToken> {
         if self.limiter.can_initiate() {
             let token = self.generator.generate();
 
@@ -207,6 +318,71 @@ impl TrackerClient {
         }
     }
 
+    /// Execute an asynchronous announce request to the given tracker, sending the given url data.
+    ///
+    /// The url data is the path and query of the tracker url, for trackers that multiplex by path.
+    ///
+    /// If the maximum number of requests are currently in progress, return None.
+    pub fn announce_with_url_data(
+        &mut self,
+        addr: SocketAddr,
+        hash: InfoHash,
+        state: ClientState,
+        url_data: &[u8],
+    ) -> Option<ClientToken> {
+        self.dispatch(addr, DispatchRequest::AnnounceWithUrlData(hash, state, url_data.to_vec()))
+    }
+
+    /// Execute asynchronous scrape requests for all of the given hashes to the given tracker.
+    ///
+    /// Hashes are split into batches of at most `MAX_SCRAPE_HASHES`, each sent as a single
+    /// packet, with one token returned per batch (in order).
+    ///
+    /// If the maximum number of requests are currently in progress, fewer tokens than
+    /// batches will be returned; the hashes of the remaining batches were not requested.
+    pub fn scrape(&mut self, addr: SocketAddr, hashes: &[InfoHash]) -> Vec<ClientToken> {
+        hashes
+            .chunks(scrape::MAX_SCRAPE_HASHES)
+            .map_while(|batch| self.dispatch(addr, DispatchRequest::ScrapeBatch(batch.to_vec())))
+            .collect()
+    }
+
+    /// Execute asynchronous announce requests for the given hash and state to all of the given trackers.
+    ///
+    /// The returned `MultiAnnounce` aggregates the responses, as they are passed to it from
+    /// the handshaker. Trackers that could not be requested, because the maximum number of
+    /// requests are currently in progress, are marked as `TrackerStatus::Limited`.
+    pub fn announce_all(&mut self, addrs: &[SocketAddr], hash: InfoHash, state: ClientState) -> MultiAnnounce {
+        let mut multi = MultiAnnounce::new(hash);
+
+        for &addr in addrs {
+            let opt_token = self.request(addr, ClientRequest::Announce(hash, state));
+
+            multi.push_tracker(addr, opt_token);
+        }
+
+        multi
+    }
+
+    /// Snapshot of the health of the given tracker, if it has been requested.
+    #[must_use]
+    pub fn tracker_health(&self, addr: SocketAddr) -> Option<TrackerHealth> {
+        self.health.get(addr)
+    }
+
+    /// Allow announcing the given torrent to the given tracker again, after the tracker permanently rejected it.
+    ///
+    /// Returns false if the torrent was not rejected by the tracker, see `TrackerHealth::rejection`.
+    pub fn clear_rejected_torrent(&self, addr: SocketAddr, hash: InfoHash) -> bool {
+        self.health.clear_rejection(addr, &hash)
+    }
+
+    /// Snapshot of the health of every tracker that has been requested.
+    #[must_use]
+    pub fn trackers_health(&self) -> Vec<(SocketAddr, TrackerHealth)> {
+        self.health.snapshot()
+    }
+
     #[must_use]
     pub fn local_addr(&self) -> SocketAddr {
         self.bound_socket
@@ -234,7 +410,7 @@ impl Drop for TrackerClient {
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ClientToken(u32);
 
-/// Generates tokens which double as transaction ids.
+/// Generates tokens that identify requests to the caller, independent of their transaction ids.
 struct TokenGenerator {
     generator: LocallyShuffledIds<u32>,
 }
@@ -0,0 +1,175 @@
+//! Announcing to multiple trackers at once.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+use util::bt::InfoHash;
+
+use crate::announce::AnnounceResponse;
+use crate::client::error::ClientError;
+use crate::client::{ClientMetadata, ClientToken};
+
+/// Status of the announce to a single tracker, as part of a `MultiAnnounce`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrackerStatus {
+    /// Waiting on a response from the tracker.
+    Pending,
+    /// Announce was not sent, because the maximum number of requests were already in progress.
+    Limited,
+    /// Tracker responded to the announce.
+    Announced(AnnounceResponse<'static>),
+    /// Announce to the tracker failed.
+    Failed(ClientError),
+}
+
+/// Aggregated outcome of announcing a single torrent to multiple trackers.
+///
+/// Created by `TrackerClient::announce_all`, every `ClientMetadata` received by the
+/// handshaker should be passed to `MultiAnnounce::recv_metadata` until it is complete.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct MultiAnnounce {
+    hash: InfoHash,
+    trackers: Vec<(SocketAddr, TrackerStatus)>,
+    tokens: HashMap<ClientToken, usize>,
+}
+
+impl MultiAnnounce {
+    pub(crate) fn new(hash: InfoHash) -> MultiAnnounce {
+        MultiAnnounce {
+            hash,
+            trackers: Vec::new(),
+            tokens: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn push_tracker(&mut self, addr: SocketAddr, opt_token: Option<ClientToken>) {
+        let status = match opt_token {
+            Some(token) => {
+                self.tokens.insert(token, self.trackers.len());
+                TrackerStatus::Pending
+            }
+            None => TrackerStatus::Limited,
+        };
+
+        self.trackers.push((addr, status));
+    }
+
+    /// Record the given metadata, returning false if it was not for one of our announces.
+    pub fn recv_metadata(&mut self, metadata: &ClientMetadata) -> bool {
+        let Some(index) = self.tokens.remove(&metadata.token()) else {
+            return false;
+        };
+
+        self.trackers[index].1 = match metadata.result() {
+            Ok(response) => match response.announce_response() {
+                Some(announce) => TrackerStatus::Announced(announce.clone()),
+                None => TrackerStatus::Failed(ClientError::ServerError),
+            },
+            Err(err) => TrackerStatus::Failed(err.clone()),
+        };
+
+        true
+    }
+
+    /// `InfoHash` that was announced.
+    #[must_use]
+    pub fn hash(&self) -> InfoHash {
+        self.hash
+    }
+
+    /// Whether or not every tracker has either responded, or failed.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Status of each tracker, in the order they were given.
+    #[must_use]
+    pub fn trackers(&self) -> &[(SocketAddr, TrackerStatus)] {
+        &self.trackers
+    }
+
+    /// Number of trackers that responded to the announce.
+    #[must_use]
+    pub fn num_announced(&self) -> usize {
+        self.trackers
+            .iter()
+            .filter(|(_, status)| matches!(status, TrackerStatus::Announced(_)))
+            .count()
+    }
+
+    /// Union of the peers returned by every tracker that responded, in the order they were received.
+    #[must_use]
+    pub fn peers(&self) -> Vec<SocketAddr> {
+        let mut seen = HashSet::new();
+
+        self.trackers
+            .iter()
+            .filter_map(|(_, status)| match status {
+                TrackerStatus::Announced(response) => Some(response.peers().iter()),
+                TrackerStatus::Pending | TrackerStatus::Limited | TrackerStatus::Failed(_) => None,
+            })
+            .flatten()
+            .filter(|addr| seen.insert(*addr))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+    use util::bt;
+
+    use super::{MultiAnnounce, TrackerStatus};
+    use crate::announce::AnnounceResponse;
+    use crate::client::error::ClientError;
+    use crate::client::{ClientMetadata, ClientResponse, ClientToken};
+    use crate::contact::{CompactPeers, CompactPeersV4};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn announce_response(ports: &[u16]) -> ClientResponse {
+        let mut peers = CompactPeersV4::new();
+        for &port in ports {
+            peers.insert(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), port));
+        }
+
+        ClientResponse::Announce(AnnounceResponse::new(1800, 1, 2, CompactPeers::V4(peers)))
+    }
+
+    #[test]
+    fn positive_aggregate_peers_and_statuses() {
+        let mut multi = MultiAnnounce::new([0u8; bt::INFO_HASH_LEN].into());
+        multi.push_tracker(addr(1), Some(ClientToken(1)));
+        multi.push_tracker(addr(2), Some(ClientToken(2)));
+        multi.push_tracker(addr(3), Some(ClientToken(3)));
+        multi.push_tracker(addr(4), None);
+
+        assert!(!multi.recv_metadata(&ClientMetadata::new(ClientToken(5), Err(ClientError::MaxTimeout))));
+
+        assert!(multi.recv_metadata(&ClientMetadata::new(ClientToken(1), Ok(announce_response(&[1, 2])))));
+        assert!(multi.recv_metadata(&ClientMetadata::new(ClientToken(3), Ok(announce_response(&[2, 3])))));
+        assert!(!multi.is_complete());
+
+        assert!(multi.recv_metadata(&ClientMetadata::new(ClientToken(2), Err(ClientError::MaxTimeout))));
+        assert!(!multi.recv_metadata(&ClientMetadata::new(ClientToken(2), Err(ClientError::MaxTimeout))));
+        assert!(multi.is_complete());
+
+        assert_eq!(2, multi.num_announced());
+        assert_eq!(
+            vec![
+                SocketAddr::from(([10, 0, 0, 1], 1)),
+                SocketAddr::from(([10, 0, 0, 1], 2)),
+                SocketAddr::from(([10, 0, 0, 1], 3))
+            ],
+            multi.peers()
+        );
+        assert_eq!(TrackerStatus::Failed(ClientError::MaxTimeout), multi.trackers()[1].1);
+        assert_eq!(TrackerStatus::Limited, multi.trackers()[3].1);
+    }
+}
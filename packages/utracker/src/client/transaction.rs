@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::client::ClientToken;
+
+/// Number of ids generated for a request before an id that is still outstanding is reused.
+const MAX_GENERATE_ATTEMPTS: usize = 8;
+
+/// Generates the transaction ids that packets to trackers are sent with.
+///
+/// Responses are only accepted if they echo the transaction id of an outstanding packet and come
+/// from the tracker it was sent to, so ids should be hard to predict for an off-path attacker to
+/// be unable to spoof responses. A fresh id is generated for every packet, including retransmits.
+#[allow(clippy::module_name_repetitions)]
+pub trait TransactionIdGenerator: Send {
+    /// Generate a new transaction id.
+    fn generate(&mut self) -> u32;
+}
+
+impl<F> TransactionIdGenerator for F
+where
+    F: FnMut() -> u32 + Send,
+{
+    fn generate(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// Generates transaction ids with a cryptographically secure random number generator.
+///
+/// This is the default generator of a `TrackerClient`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RandomTransactionIds;
+
+impl TransactionIdGenerator for RandomTransactionIds {
+    fn generate(&mut self) -> u32 {
+        rand::random()
+    }
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Tracks the transaction ids of packets that are awaiting a response.
+pub struct OutstandingTransactions {
+    generator: Box<dyn TransactionIdGenerator>,
+    outstanding: HashMap<u32, (ClientToken, SocketAddr)>,
+}
+
+impl OutstandingTransactions {
+    /// Create a new `OutstandingTransactions` with the given generator.
+    pub fn new(generator: Box<dyn TransactionIdGenerator>) -> OutstandingTransactions {
+        OutstandingTransactions {
+            generator,
+            outstanding: HashMap::new(),
+        }
+    }
+
+    /// Start a transaction for the request with the given token, to be sent to the given address.
+    ///
+    /// Ids that are already outstanding are skipped, unless the generator keeps returning them.
+    pub fn start(&mut self, token: ClientToken, addr: SocketAddr) -> u32 {
+        let mut id = self.generator.generate();
+        for _ in 1..MAX_GENERATE_ATTEMPTS {
+            if !self.outstanding.contains_key(&id) {
+                break;
+            }
+            id = self.generator.generate();
+        }
+
+        if let Some((displaced, _)) = self.outstanding.insert(id, (token, addr)) {
+            tracing::warn!(id, ?displaced, "transaction id generator reused an outstanding id");
+        }
+
+        id
+    }
+
+    /// Finish the transaction with the given id, if it is still outstanding for the given token.
+    pub fn finish(&mut self, id: u32, token: ClientToken) {
+        if self
+            .outstanding
+            .get(&id)
+            .is_some_and(|&(outstanding, _)| outstanding == token)
+        {
+            self.outstanding.remove(&id);
+        }
+    }
+
+    /// Finish the transaction with the given id, if it was sent to the given address, yielding its token.
+    ///
+    /// Returns None, and leaves any outstanding transaction untouched, if the id is unknown (or has
+    /// expired) or the response came from a different address.
+    pub fn accept(&mut self, id: u32, addr: SocketAddr) -> Option<ClientToken> {
+        match self.outstanding.get(&id) {
+            Some(&(token, expected)) if expected == addr => {
+                self.outstanding.remove(&id);
+
+                Some(token)
+            }
+            Some(&(_, expected)) => {
+                tracing::warn!(id, %addr, %expected, "dropping response from an unexpected address");
+
+                None
+            }
+            None => {
+                tracing::debug!(id, %addr, "dropping response with an unknown or expired transaction id");
+
+                None
+            }
+        }
+    }
+
+    /// Forget every outstanding transaction.
+    pub fn clear(&mut self) {
+        self.outstanding.clear();
+    }
+}
+
+impl std::fmt::Debug for OutstandingTransactions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutstandingTransactions")
+            .field("outstanding", &self.outstanding)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::OutstandingTransactions;
+    use crate::client::ClientToken;
+
+    fn tracker(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn sequential() -> OutstandingTransactions {
+        let mut next = 0;
+
+        OutstandingTransactions::new(Box::new(move || {
+            next += 1;
+            next
+        }))
+    }
+
+    #[test]
+    fn positive_accept_outstanding_transaction() {
+        let mut transactions = sequential();
+
+        let id = transactions.start(ClientToken(7), tracker(1));
+
+        assert_eq!(Some(ClientToken(7)), transactions.accept(id, tracker(1)));
+        assert_eq!(None, transactions.accept(id, tracker(1)));
+    }
+
+    #[test]
+    fn positive_skip_outstanding_ids() {
+        let mut transactions = OutstandingTransactions::new(Box::new({
+            let mut ids = [1, 1, 2].into_iter();
+            move || ids.next().unwrap()
+        }));
+
+        assert_eq!(1, transactions.start(ClientToken(0), tracker(1)));
+        assert_eq!(2, transactions.start(ClientToken(1), tracker(1)));
+    }
+
+    #[test]
+    fn negative_accept_unexpected_address() {
+        let mut transactions = sequential();
+
+        let id = transactions.start(ClientToken(7), tracker(1));
+
+        assert_eq!(None, transactions.accept(id, tracker(2)));
+        assert_eq!(Some(ClientToken(7)), transactions.accept(id, tracker(1)));
+    }
+
+    #[test]
+    fn negative_accept_expired_transaction() {
+        let mut transactions = sequential();
+
+        let expired = transactions.start(ClientToken(7), tracker(1));
+        transactions.finish(expired, ClientToken(7));
+        let id = transactions.start(ClientToken(7), tracker(1));
+
+        assert_eq!(None, transactions.accept(expired, tracker(1)));
+        assert_eq!(Some(ClientToken(7)), transactions.accept(id, tracker(1)));
+    }
+}
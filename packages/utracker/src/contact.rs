@@ -7,8 +7,8 @@ use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 use nom::{IResult, Needed};
 use util::convert;
 
-const SOCKET_ADDR_V4_BYTES: usize = 6;
-const SOCKET_ADDR_V6_BYTES: usize = 18;
+pub(crate) const SOCKET_ADDR_V4_BYTES: usize = 6;
+pub(crate) const SOCKET_ADDR_V6_BYTES: usize = 18;
 
 /// Container for peers to be sent/received from a tracker.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -59,6 +59,44 @@ impl<'a> CompactPeers<'a> {
         }
     }
 
+    /// Whether or not the peers are IPv6 addresses.
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        match self {
+            CompactPeers::V6(_) => true,
+            CompactPeers::V4(_) => false,
+        }
+    }
+
+    /// Whether or not the peers are IPv4 addresses.
+    #[must_use]
+    pub fn is_ipv4(&self) -> bool {
+        !self.is_ipv6()
+    }
+
+    /// Number of peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self {
+            CompactPeers::V4(peers) => peers.len(),
+            CompactPeers::V6(peers) => peers.len(),
+        }
+    }
+
+    /// Whether or not there are no peers.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keep only the first `len` peers, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            CompactPeers::V4(peers) => peers.truncate(len),
+            CompactPeers::V6(peers) => peers.truncate(len),
+        }
+    }
+
     /// Iterator over all of the contact information.
     #[allow(clippy::iter_without_into_iter)]
     #[must_use]
@@ -99,6 +137,16 @@ impl<'a> CompactPeersIter<'a> {
     fn new(iter: CompactPeersIterType<'a>) -> CompactPeersIter<'a> {
         CompactPeersIter { iter }
     }
+
+    /// Create a new `CompactPeersIter` over raw IPv4 peers, whose length must be a multiple of their size.
+    pub(crate) fn from_raw_v4(peers: &'a [u8]) -> CompactPeersIter<'a> {
+        CompactPeersIter::new(CompactPeersIterType::V4(CompactPeersV4Iter::new(peers)))
+    }
+
+    /// Create a new `CompactPeersIter` over raw IPv6 peers, whose length must be a multiple of their size.
+    pub(crate) fn from_raw_v6(peers: &'a [u8]) -> CompactPeersIter<'a> {
+        CompactPeersIter::new(CompactPeersIterType::V6(CompactPeersV6Iter::new(peers)))
+    }
 }
 
 #[allow(clippy::copy_iterator)]
@@ -160,6 +208,23 @@ impl<'a> CompactPeersV4<'a> {
         self.peers.to_mut().extend_from_slice(&peer_bytes);
     }
 
+    /// Number of peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peers.len() / SOCKET_ADDR_V4_BYTES
+    }
+
+    /// Whether or not there are no peers.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Keep only the first `len` peers, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        truncate_peers(&mut self.peers, len.saturating_mul(SOCKET_ADDR_V4_BYTES));
+    }
+
     /// Iterator over all of the contact information.
     #[allow(clippy::iter_without_into_iter)]
     #[must_use]
@@ -176,6 +241,14 @@ impl<'a> CompactPeersV4<'a> {
     }
 }
 
+/// Truncate the bytes of some compact peers to the given length, without copying borrowed bytes.
+fn truncate_peers(peers: &mut Cow<'_, [u8]>, len: usize) {
+    match peers {
+        Cow::Borrowed(bytes) => *bytes = &bytes[..len.min(bytes.len())],
+        Cow::Owned(bytes) => bytes.truncate(len),
+    }
+}
+
 fn parse_peers_v4(bytes: &[u8]) -> IResult<&[u8], CompactPeersV4<'_>> {
     let remainder_bytes = bytes.len() % SOCKET_ADDR_V4_BYTES;
 
@@ -282,6 +355,23 @@ impl<'a> CompactPeersV6<'a> {
         self.peers.to_mut().extend_from_slice(&peer_bytes);
     }
 
+    /// Number of peers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peers.len() / SOCKET_ADDR_V6_BYTES
+    }
+
+    /// Whether or not there are no peers.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Keep only the first `len` peers, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        truncate_peers(&mut self.peers, len.saturating_mul(SOCKET_ADDR_V6_BYTES));
+    }
+
     /// Iterator over all of the contact information.
     #[allow(clippy::iter_without_into_iter)]
     #[must_use]
@@ -415,6 +505,23 @@ mod tests {
         assert_eq!(received, IResult::Ok((&b""[..], expected)));
     }
 
+    #[test]
+    fn positive_truncate_parsed_peers_v4() {
+        let bytes = [127, 0, 0, 1, 0, 15, 127, 0, 0, 1, 1, 0];
+
+        let (_, mut received) = CompactPeersV4::from_bytes(&bytes).unwrap();
+        let mut expected = CompactPeersV4::new();
+
+        expected.insert("127.0.0.1:15".parse().unwrap());
+
+        received.truncate(1);
+        assert_eq!(received, expected);
+        assert_eq!(received.len(), 1);
+
+        received.truncate(5);
+        assert_eq!(received, expected);
+    }
+
     #[test]
     fn positive_write_empty_v4() {
         let mut received = Vec::new();
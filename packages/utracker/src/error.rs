@@ -10,6 +10,62 @@ use nom::sequence::terminated;
 use nom::IResult;
 use thiserror::Error;
 
+/// Messages sent by trackers for torrents that they do not track.
+const UNREGISTERED_MESSAGES: [&str; 5] = [
+    "unregistered torrent",
+    "torrent not registered",
+    "torrent not found",
+    "unknown torrent",
+    "info_hash not found",
+];
+
+/// Messages sent by trackers for clients that are not allowed to announce.
+const UNAUTHORIZED_MESSAGES: [&str; 5] = ["unauthorized", "not authorized", "access denied", "passkey", "banned"];
+
+/// Messages sent by trackers for clients that announce too often.
+const RATE_LIMITED_MESSAGES: [&str; 5] = [
+    "rate limit",
+    "too many requests",
+    "slow down",
+    "announce interval",
+    "try again",
+];
+
+/// Reason for an `ErrorResponse`, recognized from its message.
+///
+/// Trackers send free form messages, so only the most common ones are recognized.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorReason {
+    /// The tracker does not track the torrent.
+    UnregisteredTorrent,
+    /// The client (or its passkey) is not allowed to announce to the tracker.
+    Unauthorized,
+    /// The client announced too often.
+    RateLimited,
+    /// Any other message.
+    Message,
+}
+
+impl ErrorReason {
+    /// Whether the request should be retried, see `RetryClass`.
+    #[must_use]
+    pub fn retry(self) -> RetryClass {
+        match self {
+            ErrorReason::UnregisteredTorrent | ErrorReason::Unauthorized => RetryClass::Permanent,
+            ErrorReason::RateLimited | ErrorReason::Message => RetryClass::Transient,
+        }
+    }
+}
+
+/// Whether a failed request should be retried.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RetryClass {
+    /// The request will keep failing until something changes on the tracker, so it should not be retried.
+    Permanent,
+    /// The request may succeed later, so it should be retried after backing off.
+    Transient,
+}
+
 /// Error reported by the server and sent to the client.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -62,6 +118,29 @@ impl<'a> ErrorResponse<'a> {
         &self.message
     }
 
+    /// Reason for the error, recognized from the message.
+    #[must_use]
+    pub fn reason(&self) -> ErrorReason {
+        let message = self.message.to_lowercase();
+        let matches_any = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+
+        if matches_any(&UNREGISTERED_MESSAGES) {
+            ErrorReason::UnregisteredTorrent
+        } else if matches_any(&UNAUTHORIZED_MESSAGES) {
+            ErrorReason::Unauthorized
+        } else if matches_any(&RATE_LIMITED_MESSAGES) {
+            ErrorReason::RateLimited
+        } else {
+            ErrorReason::Message
+        }
+    }
+
+    /// Whether the request should be retried, based on the reason for the error.
+    #[must_use]
+    pub fn retry(&self) -> RetryClass {
+        self.reason().retry()
+    }
+
     /// Create an owned version of the `ErrorResponse`.
     #[must_use]
     pub fn to_owned(&self) -> ErrorResponse<'static> {
@@ -70,3 +149,30 @@ impl<'a> ErrorResponse<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorReason, ErrorResponse, RetryClass};
+
+    #[test]
+    fn positive_classify_error_reasons() {
+        let reasons = [
+            ("Unregistered torrent", ErrorReason::UnregisteredTorrent),
+            ("Torrent Not Found", ErrorReason::UnregisteredTorrent),
+            ("Invalid passkey", ErrorReason::Unauthorized),
+            ("Rate limited, slow down", ErrorReason::RateLimited),
+            ("Tracker is down for maintenance", ErrorReason::Message),
+        ];
+
+        for (message, reason) in reasons {
+            assert_eq!(reason, ErrorResponse::new(message).reason(), "{message}");
+        }
+    }
+
+    #[test]
+    fn positive_retry_transient_errors() {
+        assert_eq!(RetryClass::Permanent, ErrorResponse::new("unregistered torrent").retry());
+        assert_eq!(RetryClass::Transient, ErrorResponse::new("too many requests").retry());
+        assert_eq!(RetryClass::Transient, ErrorResponse::new("").retry());
+    }
+}
@@ -28,6 +28,11 @@ mod server;
 pub use util::bt::{InfoHash, PeerId};
 
 pub use crate::client::error::{ClientError, ClientResult};
-pub use crate::client::{ClientMetadata, ClientRequest, ClientResponse, ClientToken, HandshakerMessage, TrackerClient};
-pub use crate::server::handler::{ServerHandler, ServerResult};
-pub use crate::server::TrackerServer;
+pub use crate::client::health::TrackerHealth;
+pub use crate::client::multi::{MultiAnnounce, TrackerStatus};
+pub use crate::client::transaction::{RandomTransactionIds, TransactionIdGenerator};
+pub use crate::client::{
+    AnnounceWarning, ClientMetadata, ClientRequest, ClientResponse, ClientToken, HandshakerMessage, TrackerClient,
+};
+pub use crate::server::handler::{AsyncServerHandler, AsyncServerResult, ServerFuture, ServerHandler, ServerResult};
+pub use crate::server::{AsyncServerConfig, TrackerServer, DEFAULT_MAX_PENDING_REQUESTS, DEFAULT_REQUEST_TIMEOUT};
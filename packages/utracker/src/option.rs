@@ -108,9 +108,9 @@ impl<'a> AnnounceOptions<'a> {
     ///
     /// Returns None if the option is not found or it failed to read from the given bytes.
     #[must_use]
-    pub fn get<O>(&'a self) -> Option<O>
+    pub fn get<'b, O>(&'b self) -> Option<O>
     where
-        O: AnnounceOption<'a>,
+        O: AnnounceOption<'b>,
     {
         self.raw_options
             .get(&O::option_byte())
@@ -210,6 +210,12 @@ impl<'a> URLDataOption<'a> {
     pub fn new(url_data: &'a [u8]) -> URLDataOption<'a> {
         URLDataOption { url_data }
     }
+
+    /// Concatenated PATH and QUERY of the tracker URL.
+    #[must_use]
+    pub fn url_data(&self) -> &'a [u8] {
+        self.url_data
+    }
 }
 
 impl<'a> AnnounceOption<'a> for URLDataOption<'a> {
@@ -501,6 +507,18 @@ mod tests {
         assert_eq!(received, IResult::Ok((&b""[..], expected)));
     }
 
+    #[test]
+    fn positive_get_url_data_from_owned() {
+        let bytes = [super::URL_DATA_BYTE, 3, b'/', b'a', b'b', super::END_OF_OPTIONS_BYTE];
+
+        let (_, options) = AnnounceOptions::from_bytes(&bytes).unwrap();
+        let owned = options.to_owned();
+
+        let url_data = owned.get::<URLDataOption<'_>>().unwrap();
+
+        assert_eq!(b"/ab", url_data.url_data());
+    }
+
     #[test]
     fn negative_parse_url_data_incomplete() {
         let bytes = [super::URL_DATA_BYTE, 5, 0, 0];
@@ -15,6 +15,11 @@ use util::convert;
 
 const SCRAPE_STATS_BYTES: usize = 12;
 
+/// Maximum number of `InfoHash` that fit in a single scrape request packet.
+///
+/// Keeps the request within a typical 1500 byte MTU (16 byte header plus 20 bytes per hash).
+pub const MAX_SCRAPE_HASHES: usize = 74;
+
 /// Status for a given `InfoHash`.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -0,0 +1,238 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nom::IResult;
+use tokio::runtime::Handle;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{instrument, Level};
+use umio::{Dispatcher, MessageSender, Provider, ShutdownHandle};
+
+use crate::error::ErrorResponse;
+use crate::request::{self, RequestType, TrackerRequest};
+use crate::response::{ResponseType, TrackerResponse};
+use crate::server::dispatcher::{self, DispatchMessage};
+use crate::server::handler::{AsyncServerHandler, AsyncServerResult, ServerFuture};
+use crate::server::throttle::AnnounceThrottle;
+use crate::server::AsyncServerConfig;
+
+const REQUEST_TIMED_OUT: &str = "Request Timed Out While Being Serviced";
+
+/// Create a new background dispatcher to service requests with an `AsyncServerHandler`.
+#[allow(clippy::module_name_repetitions)]
+#[instrument(skip(runtime))]
+pub fn create_async_dispatcher<H>(
+    bind: SocketAddr,
+    handler: H,
+    config: AsyncServerConfig,
+    runtime: Handle,
+) -> std::io::Result<(MessageSender<DispatchMessage>, SocketAddr, ShutdownHandle)>
+where
+    H: AsyncServerHandler + std::fmt::Debug + 'static,
+{
+    tracing::trace!("create async dispatcher");
+
+    dispatcher::spawn_dispatcher(bind, |channel| AsyncServerDispatcher::new(handler, config, runtime, channel))
+}
+
+// ----------------------------------------------------------------------------//
+
+/// Dispatcher that services requests on a tokio runtime, writing responses once their futures complete.
+#[derive(Debug)]
+struct AsyncServerDispatcher<H>
+where
+    H: AsyncServerHandler + std::fmt::Debug,
+{
+    handler: H,
+    runtime: Handle,
+    channel: MessageSender<DispatchMessage>,
+    pending: Arc<Semaphore>,
+    request_timeout: Duration,
+    throttle: AnnounceThrottle,
+}
+
+impl<H> AsyncServerDispatcher<H>
+where
+    H: AsyncServerHandler + std::fmt::Debug,
+{
+    /// Create a new `AsyncServerDispatcher`.
+    #[instrument(skip(runtime), ret(level = Level::TRACE))]
+    fn new(
+        handler: H,
+        config: AsyncServerConfig,
+        runtime: Handle,
+        channel: MessageSender<DispatchMessage>,
+    ) -> AsyncServerDispatcher<H> {
+        AsyncServerDispatcher {
+            handler,
+            runtime,
+            channel,
+            pending: Arc::new(Semaphore::new(config.max_pending_requests())),
+            request_timeout: config.request_timeout(),
+            throttle: AnnounceThrottle::new(),
+        }
+    }
+
+    /// Forward the request on to the appropriate handler method.
+    #[instrument(skip(self, provider))]
+    fn process_request(&mut self, provider: &mut Provider<'_, Self>, request: &TrackerRequest<'_>, addr: SocketAddr) {
+        tracing::trace!("process request");
+
+        let conn_id = request.connection_id();
+        let trans_id = request.transaction_id();
+
+        if let &RequestType::Connect = request.request_type() {
+            if conn_id != request::CONNECT_ID_PROTOCOL_ID {
+                tracing::warn!(
+                    "request was not `CONNECT_ID_PROTOCOL_ID`, i.e. {}, but {conn_id}.",
+                    request::CONNECT_ID_PROTOCOL_ID
+                );
+
+                return;
+            }
+        }
+
+        // Requests are dropped rather than queued, the client will retry them
+        let Ok(permit) = self.pending.clone().try_acquire_owned() else {
+            tracing::warn!("too many pending requests, dropping request");
+
+            return;
+        };
+
+        match request.request_type() {
+            &RequestType::Connect => {
+                let future = self.handler.connect(addr);
+
+                self.spawn_response(permit, addr, trans_id, future, ResponseType::Connect);
+            }
+            RequestType::Announce(req) => {
+                if let Some(min_interval) = self.handler.min_announce_interval(addr, req) {
+                    let event = req.state().event();
+
+                    if !self
+                        .throttle
+                        .admit(addr, req.info_hash(), event, min_interval, Instant::now())
+                    {
+                        tracing::debug!(?min_interval, "client announced too frequently");
+
+                        let response = TrackerResponse::new(
+                            trans_id,
+                            ResponseType::Error(ErrorResponse::new(dispatcher::ANNOUNCED_TOO_FREQUENTLY)),
+                        );
+                        dispatcher::write_response(provider, &response, addr);
+
+                        return;
+                    }
+                }
+                let opt_interval = self.handler.announce_interval(addr, req);
+
+                let request_is_ipv6 = req.source_ip().is_ipv6();
+                let future = self.handler.announce(addr, conn_id, req.to_owned());
+
+                self.spawn_response(permit, addr, trans_id, future, move |response| {
+                    let response = match opt_interval {
+                        Some(interval) => response.with_interval(interval),
+                        None => response,
+                    };
+
+                    dispatcher::announce_response_type(response, request_is_ipv6)
+                });
+            }
+            RequestType::Scrape(req) => {
+                let future = self.handler.scrape(addr, conn_id, req.to_owned());
+
+                self.spawn_response(permit, addr, trans_id, future, ResponseType::Scrape);
+            }
+        }
+    }
+
+    /// Drive the handler future on the runtime, sending the response back to the event loop once it completes.
+    ///
+    /// The permit is held until the future completes or times out.
+    fn spawn_response<T, F>(
+        &self,
+        permit: OwnedSemaphorePermit,
+        addr: SocketAddr,
+        trans_id: u32,
+        future: ServerFuture<T>,
+        into_response: F,
+    ) where
+        T: Send + 'static,
+        F: FnOnce(T) -> ResponseType<'static> + Send + 'static,
+    {
+        let channel = self.channel.clone();
+        let request_timeout = self.request_timeout;
+
+        self.runtime.spawn(async move {
+            let attempt: AsyncServerResult<T> = match tokio::time::timeout(request_timeout, future).await {
+                Ok(Some(attempt)) => attempt,
+                Ok(None) => {
+                    tracing::warn!("request attempt canceled");
+
+                    return;
+                }
+                Err(_) => {
+                    tracing::warn!("request attempt timed out");
+
+                    Err(REQUEST_TIMED_OUT.to_owned())
+                }
+            };
+            drop(permit);
+
+            let response_type = match attempt {
+                Ok(response) => into_response(response),
+                Err(err_msg) => ResponseType::Error(ErrorResponse::new(&err_msg).to_owned()),
+            };
+
+            let response = TrackerResponse::new(trans_id, response_type);
+
+            tracing::trace!(?response, "forward response");
+
+            if channel.send(DispatchMessage::Response(addr, response)).is_err() {
+                tracing::debug!("server shut down before the response was written");
+            }
+        });
+    }
+}
+
+impl<H> Dispatcher for AsyncServerDispatcher<H>
+where
+    H: AsyncServerHandler + std::fmt::Debug,
+{
+    type TimeoutToken = ();
+    type Message = DispatchMessage;
+
+    #[instrument(skip(self, provider))]
+    fn incoming(&mut self, mut provider: Provider<'_, Self>, message: &[u8], addr: SocketAddr) {
+        let () = match TrackerRequest::from_bytes(message) {
+            IResult::Ok((_, request)) => {
+                tracing::debug!("received an incoming request: {request:?}");
+
+                self.process_request(&mut provider, &request, addr);
+            }
+            Err(e) => {
+                tracing::error!(%e, "received an incoming error message");
+            }
+        };
+    }
+
+    #[instrument(skip(self, provider))]
+    fn notify(&mut self, mut provider: Provider<'_, Self>, message: DispatchMessage) {
+        let () = match message {
+            DispatchMessage::Shutdown(shutdown_finished_sender) => {
+                tracing::debug!("received a shutdown notification");
+
+                provider.shutdown();
+
+                let () = shutdown_finished_sender.send(Ok(())).unwrap();
+            }
+            DispatchMessage::Response(addr, response) => {
+                dispatcher::write_response(&mut provider, &response, addr);
+            }
+        };
+    }
+
+    fn timeout(&mut self, _: Provider<'_, Self>, (): ()) {
+        unreachable!("async server sets no timeouts");
+    }
+}
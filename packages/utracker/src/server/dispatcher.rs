@@ -1,23 +1,30 @@
 use std::net::SocketAddr;
 use std::sync::mpsc;
+use std::time::Instant;
 
 use nom::IResult;
 use tracing::{instrument, Level};
 use umio::{Dispatcher, ELoopBuilder, MessageSender, Provider, ShutdownHandle};
 
-use crate::announce::AnnounceRequest;
+use crate::announce::{AnnounceRequest, AnnounceResponse};
 use crate::error::ErrorResponse;
 use crate::request::{self, RequestType, TrackerRequest};
 use crate::response::{ResponseType, TrackerResponse};
 use crate::scrape::ScrapeRequest;
 use crate::server::handler::ServerHandler;
+use crate::server::throttle::AnnounceThrottle;
 
 const EXPECTED_PACKET_LENGTH: usize = 1500;
 
+pub const PEERS_ADDRESS_FAMILY_MISMATCH: &str = "Announce Response Peers Do Not Match The Requested Address Family";
+
+pub const ANNOUNCED_TOO_FREQUENTLY: &str = "Rate Limited, Announced Before The Minimum Announce Interval Passed";
+
 /// Internal dispatch message for servers.
 #[derive(Debug)]
 pub enum DispatchMessage {
     Shutdown(mpsc::SyncSender<std::io::Result<()>>),
+    Response(SocketAddr, TrackerResponse<'static>),
 }
 
 /// Create a new background dispatcher to service requests.
@@ -32,6 +39,21 @@ where
 {
     tracing::trace!("create dispatcher");
 
+    spawn_dispatcher(bind, |_| ServerDispatcher::new(handler))
+}
+
+/// Run the dispatcher built by `new_dispatcher` on a new background event loop.
+///
+/// The dispatcher is given a channel back into its own event loop.
+#[instrument(skip(new_dispatcher))]
+pub fn spawn_dispatcher<D, F>(
+    bind: SocketAddr,
+    new_dispatcher: F,
+) -> std::io::Result<(MessageSender<DispatchMessage>, SocketAddr, ShutdownHandle)>
+where
+    D: Dispatcher<TimeoutToken = (), Message = DispatchMessage> + Send + 'static,
+    F: FnOnce(MessageSender<DispatchMessage>) -> D,
+{
     let builder = ELoopBuilder::new()
         .channel_capacity(1)
         .timer_capacity(0)
@@ -41,7 +63,7 @@ where
     let (mut eloop, socket, shutdown) = builder.build()?;
     let channel = eloop.channel();
 
-    let dispatcher = ServerDispatcher::new(handler);
+    let dispatcher = new_dispatcher(channel.clone());
 
     let handle = {
         let (started_eloop_sender, started_eloop_receiver) = mpsc::sync_channel(0);
@@ -69,6 +91,7 @@ where
     H: ServerHandler + std::fmt::Debug,
 {
     handler: H,
+    throttle: AnnounceThrottle,
 }
 
 impl<H> ServerDispatcher<H>
@@ -78,7 +101,10 @@ where
     /// Create a new `ServerDispatcher`.
     #[instrument(skip(), ret(level = Level::TRACE))]
     fn new(handler: H) -> ServerDispatcher<H> {
-        ServerDispatcher { handler }
+        ServerDispatcher {
+            handler,
+            throttle: AnnounceThrottle::new(),
+        }
     }
 
     /// Forward the request on to the appropriate handler method.
@@ -145,15 +171,35 @@ where
         request: &AnnounceRequest<'_>,
         addr: SocketAddr,
     ) {
+        if let Some(min_interval) = self.handler.min_announce_interval(addr, request) {
+            let event = request.state().event();
+
+            if !self
+                .throttle
+                .admit(addr, request.info_hash(), event, min_interval, Instant::now())
+            {
+                tracing::debug!(?min_interval, "client announced too frequently");
+
+                let response = TrackerResponse::new(trans_id, ResponseType::Error(ErrorResponse::new(ANNOUNCED_TOO_FREQUENTLY)));
+                write_response(provider, &response, addr);
+
+                return;
+            }
+        }
+        let opt_interval = self.handler.announce_interval(addr, request);
+
         let Some(attempt) = self.handler.announce(addr, conn_id, request) else {
             tracing::warn!("announce attempt canceled");
 
             return;
         };
 
-        let response_type = match attempt {
-            Ok(response) => ResponseType::Announce(response),
-            Err(err_msg) => ResponseType::Error(ErrorResponse::new(err_msg)),
+        let response_type = match (attempt, opt_interval) {
+            (Ok(response), Some(interval)) => {
+                announce_response_type(response.with_interval(interval), request.source_ip().is_ipv6())
+            }
+            (Ok(response), None) => announce_response_type(response, request.source_ip().is_ipv6()),
+            (Err(err_msg), _) => ResponseType::Error(ErrorResponse::new(err_msg)),
         };
         let response = TrackerResponse::new(trans_id, response_type);
 
@@ -191,11 +237,34 @@ where
     }
 }
 
+/// Check the response of an announce handler before it is sent to the client.
+///
+/// The action of the response is chosen by the peers, so they must match the address family of the request.
+/// Peers that do not fit in a single datagram are dropped, keeping those that the handler listed first.
+pub fn announce_response_type(mut response: AnnounceResponse<'_>, request_is_ipv6: bool) -> ResponseType<'_> {
+    if response.peers().is_ipv6() != request_is_ipv6 {
+        tracing::warn!("announce handler responded with peers of the wrong address family");
+
+        return ResponseType::Error(ErrorResponse::new(PEERS_ADDRESS_FAMILY_MISMATCH));
+    }
+
+    let num_peers = response.peers().len();
+    if response.truncate_peers() {
+        tracing::warn!(
+            num_peers,
+            max_peers = response.max_peers(),
+            "announce handler responded with more peers than fit in a datagram, truncating"
+        );
+    }
+
+    ResponseType::Announce(response)
+}
+
 /// Write the given tracker response through to the given provider.
 #[instrument(skip(provider))]
-fn write_response<H>(provider: &mut Provider<'_, ServerDispatcher<H>>, response: &TrackerResponse<'_>, addr: SocketAddr)
+pub fn write_response<D>(provider: &mut Provider<'_, D>, response: &TrackerResponse<'_>, addr: SocketAddr)
 where
-    H: ServerHandler + std::fmt::Debug,
+    D: Dispatcher,
 {
     tracing::debug!("write response");
 
@@ -240,6 +309,9 @@ where
 
                 let () = shutdown_finished_sender.send(Ok(())).unwrap();
             }
+            DispatchMessage::Response(addr, response) => {
+                write_response(&mut provider, &response, addr);
+            }
         };
     }
 
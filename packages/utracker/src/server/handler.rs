@@ -1,4 +1,7 @@
 use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
 
 use crate::announce::{AnnounceRequest, AnnounceResponse};
 use crate::scrape::{ScrapeRequest, ScrapeResponse};
@@ -8,6 +11,16 @@ use crate::scrape::{ScrapeRequest, ScrapeResponse};
 /// Either the response T or an error message.
 pub type ServerResult<'a, T> = Result<T, &'a str>;
 
+/// Result type for an `AsyncServerHandler`.
+///
+/// Either the response T or an owned error message.
+pub type AsyncServerResult<T> = Result<T, String>;
+
+/// Future returned by an `AsyncServerHandler`.
+///
+/// Resolves to `None` if no response should be sent for the request.
+pub type ServerFuture<T> = BoxFuture<'static, Option<AsyncServerResult<T>>>;
+
 /// Trait for providing a `TrackerServer` with methods to service `TrackerRequests`.
 #[allow(clippy::module_name_repetitions)]
 
@@ -25,4 +38,52 @@ pub trait ServerHandler: Send {
 
     /// Service a scrape request with the given connect id.
     fn scrape(&mut self, addr: SocketAddr, id: u64, req: &ScrapeRequest<'_>) -> Option<ServerResult<'_, ScrapeResponse<'_>>>;
+
+    /// Interval in seconds that the client at the given address should wait before announcing again.
+    ///
+    /// Called before each announce request is serviced, so that the interval can depend on the torrent
+    /// or the client, and overrides the interval of the response. Defaults to keeping the interval
+    /// given by `announce`.
+    fn announce_interval(&mut self, _addr: SocketAddr, _req: &AnnounceRequest<'_>) -> Option<i32> {
+        None
+    }
+
+    /// Minimum time that the client at the given address must wait between regular announces for a torrent.
+    ///
+    /// Announces arriving sooner are answered with an error instead of being serviced; announces
+    /// reporting an event are never rejected. Defaults to not limiting clients.
+    fn min_announce_interval(&mut self, _addr: SocketAddr, _req: &AnnounceRequest<'_>) -> Option<Duration> {
+        None
+    }
+}
+
+/// Trait for providing a `TrackerServer` with methods that service `TrackerRequests` asynchronously.
+///
+/// Each method is called from the packet loop and should return quickly; any slow work, such as
+/// consulting a database, belongs in the returned future, which is driven on the tokio runtime
+/// the server was started from.
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncServerHandler: Send {
+    /// Service a connection id request from the given address.
+    fn connect(&mut self, addr: SocketAddr) -> ServerFuture<u64>;
+
+    /// Service an announce request with the given connect id.
+    fn announce(&mut self, addr: SocketAddr, id: u64, req: AnnounceRequest<'static>) -> ServerFuture<AnnounceResponse<'static>>;
+
+    /// Service a scrape request with the given connect id.
+    fn scrape(&mut self, addr: SocketAddr, id: u64, req: ScrapeRequest<'static>) -> ServerFuture<ScrapeResponse<'static>>;
+
+    /// Interval in seconds that the client at the given address should wait before announcing again.
+    ///
+    /// Same as `ServerHandler::announce_interval`. Defaults to keeping the interval given by `announce`.
+    fn announce_interval(&mut self, _addr: SocketAddr, _req: &AnnounceRequest<'_>) -> Option<i32> {
+        None
+    }
+
+    /// Minimum time that the client at the given address must wait between regular announces for a torrent.
+    ///
+    /// Same as `ServerHandler::min_announce_interval`. Defaults to not limiting clients.
+    fn min_announce_interval(&mut self, _addr: SocketAddr, _req: &AnnounceRequest<'_>) -> Option<Duration> {
+        None
+    }
 }
@@ -1,14 +1,75 @@
 use std::net::SocketAddr;
 use std::sync::mpsc;
+use std::time::Duration;
 
 use tracing::{instrument, Level};
 use umio::{MessageSender, ShutdownHandle};
 
 use crate::server::dispatcher::DispatchMessage;
-use crate::server::handler::ServerHandler;
+use crate::server::handler::{AsyncServerHandler, ServerHandler};
 
+mod async_dispatcher;
 mod dispatcher;
 pub mod handler;
+mod throttle;
+
+/// Default maximum number of requests an `AsyncServerHandler` may be servicing at once.
+pub const DEFAULT_MAX_PENDING_REQUESTS: usize = 1024;
+/// Default amount of time an `AsyncServerHandler` has to service a request.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configures how a `TrackerServer` drives an `AsyncServerHandler`.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AsyncServerConfig {
+    max_pending_requests: usize,
+    request_timeout: Duration,
+}
+
+impl AsyncServerConfig {
+    /// Create a new `AsyncServerConfig` with default values.
+    #[must_use]
+    pub fn new() -> AsyncServerConfig {
+        AsyncServerConfig {
+            max_pending_requests: DEFAULT_MAX_PENDING_REQUESTS,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Maximum number of requests being serviced at once; further requests are dropped until one completes.
+    #[must_use]
+    pub fn with_max_pending_requests(mut self, max_pending_requests: usize) -> AsyncServerConfig {
+        self.max_pending_requests = max_pending_requests;
+
+        self
+    }
+
+    /// Amount of time a request may take before an error response is sent in its place.
+    #[must_use]
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> AsyncServerConfig {
+        self.request_timeout = request_timeout;
+
+        self
+    }
+
+    /// Maximum number of requests being serviced at once.
+    #[must_use]
+    pub fn max_pending_requests(&self) -> usize {
+        self.max_pending_requests
+    }
+
+    /// Amount of time a request may take before an error response is sent in its place.
+    #[must_use]
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+}
+
+impl Default for AsyncServerConfig {
+    fn default() -> AsyncServerConfig {
+        AsyncServerConfig::new()
+    }
+}
 
 /// Tracker server that executes responses asynchronously.
 ///
@@ -43,6 +104,32 @@ impl TrackerServer {
         })
     }
 
+    /// Run a new `TrackerServer` that services requests with an `AsyncServerHandler`.
+    ///
+    /// Handler futures are spawned onto the tokio runtime this is called from.
+    ///
+    /// # Errors
+    ///
+    /// It would return an IO Error if unable to run the server, or if not called from within a tokio runtime.
+    #[instrument(skip(), ret(level = Level::TRACE))]
+    pub fn run_async<H>(bind: SocketAddr, handler: H, config: AsyncServerConfig) -> std::io::Result<TrackerServer>
+    where
+        H: AsyncServerHandler + std::fmt::Debug + 'static,
+    {
+        let runtime = tokio::runtime::Handle::try_current().map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, e))?;
+
+        let (dispatcher, bound_socket, shutdown_handle) =
+            async_dispatcher::create_async_dispatcher(bind, handler, config, runtime)?;
+
+        tracing::info!(?bound_socket, "running async server");
+
+        Ok(TrackerServer {
+            dispatcher,
+            bound_socket,
+            shutdown_handle,
+        })
+    }
+
     #[must_use]
     pub fn local_addr(&self) -> SocketAddr {
         self.bound_socket
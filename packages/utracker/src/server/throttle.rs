@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use util::bt::InfoHash;
+
+use crate::announce::AnnounceEvent;
+
+/// Number of clients tracked before the first time expired entries are pruned.
+const MIN_PRUNE_LEN: usize = 1024;
+
+/// Time of the last announce of each client for each torrent, used to enforce a minimum announce interval.
+#[derive(Debug)]
+pub struct AnnounceThrottle {
+    last_announces: HashMap<(SocketAddr, InfoHash), (Instant, Duration)>,
+    prune_len: usize,
+}
+
+impl AnnounceThrottle {
+    /// Create a new `AnnounceThrottle`.
+    pub fn new() -> AnnounceThrottle {
+        AnnounceThrottle {
+            last_announces: HashMap::new(),
+            prune_len: MIN_PRUNE_LEN,
+        }
+    }
+
+    /// Record an announce, returning false if it should be rejected for arriving too soon after the previous one.
+    ///
+    /// Only regular announces are limited; announces reporting an event always go through, and a stopped
+    /// client is forgotten.
+    pub fn admit(
+        &mut self,
+        addr: SocketAddr,
+        info_hash: InfoHash,
+        event: AnnounceEvent,
+        min_interval: Duration,
+        now: Instant,
+    ) -> bool {
+        let key = (addr, info_hash);
+
+        match event {
+            AnnounceEvent::Stopped => {
+                self.last_announces.remove(&key);
+                return true;
+            }
+            AnnounceEvent::None => {
+                if let Some(&(last, interval)) = self.last_announces.get(&key) {
+                    if now.saturating_duration_since(last) < interval {
+                        return false;
+                    }
+                }
+            }
+            AnnounceEvent::Started | AnnounceEvent::Completed => (),
+        }
+
+        self.last_announces.insert(key, (now, min_interval));
+        self.prune(now);
+
+        true
+    }
+
+    /// Drop the clients whose minimum interval has passed, once enough of them are tracked.
+    fn prune(&mut self, now: Instant) {
+        if self.last_announces.len() < self.prune_len {
+            return;
+        }
+
+        self.last_announces
+            .retain(|_, &mut (last, interval)| now.saturating_duration_since(last) < interval);
+        self.prune_len = std::cmp::max(self.last_announces.len() * 2, MIN_PRUNE_LEN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use util::bt;
+
+    use super::AnnounceThrottle;
+    use crate::announce::AnnounceEvent;
+
+    #[test]
+    fn positive_admit_after_min_interval() {
+        let mut throttle = AnnounceThrottle::new();
+        let addr = "127.0.0.1:6969".parse().unwrap();
+        let hash = [0u8; bt::INFO_HASH_LEN].into();
+        let (now, min_interval) = (Instant::now(), Duration::from_secs(60));
+
+        assert!(throttle.admit(addr, hash, AnnounceEvent::Started, min_interval, now));
+        assert!(throttle.admit(addr, hash, AnnounceEvent::None, min_interval, now + min_interval));
+        assert!(throttle.admit(addr, hash, AnnounceEvent::Stopped, min_interval, now + min_interval));
+        assert!(throttle.admit(addr, hash, AnnounceEvent::None, min_interval, now + min_interval));
+    }
+
+    #[test]
+    fn negative_reject_before_min_interval() {
+        let mut throttle = AnnounceThrottle::new();
+        let addr = "127.0.0.1:6969".parse().unwrap();
+        let hash = [0u8; bt::INFO_HASH_LEN].into();
+        let (now, min_interval) = (Instant::now(), Duration::from_secs(60));
+
+        assert!(throttle.admit(addr, hash, AnnounceEvent::None, min_interval, now));
+        assert!(!throttle.admit(addr, hash, AnnounceEvent::None, min_interval, now + Duration::from_secs(59)));
+        assert!(throttle.admit(addr, [1u8; bt::INFO_HASH_LEN].into(), AnnounceEvent::None, min_interval, now));
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::{HashMap, HashSet};
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::sync::{Arc, Mutex, Once};
 use std::time::Duration;
 
@@ -12,10 +12,10 @@ use tracing::level_filters::LevelFilter;
 use tracing::{instrument, Level};
 use util::bt::{InfoHash, PeerId};
 use util::trans::{LocallyShuffledIds, TransactionIds};
-use utracker::announce::{AnnounceEvent, AnnounceRequest, AnnounceResponse};
+use utracker::announce::{AnnounceEvent, AnnounceRequest, AnnounceResponse, SourceIP};
 use utracker::contact::{CompactPeers, CompactPeersV4, CompactPeersV6};
 use utracker::scrape::{ScrapeRequest, ScrapeResponse, ScrapeStats};
-use utracker::{HandshakerMessage, ServerHandler, ServerResult};
+use utracker::{AsyncServerHandler, HandshakerMessage, ServerFuture, ServerHandler, ServerResult};
 
 #[allow(dead_code)]
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
@@ -23,6 +23,9 @@ pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
 #[allow(dead_code)]
 pub const LOOPBACK_IPV4: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
 
+#[allow(dead_code)]
+pub const LOOPBACK_IPV6: SocketAddr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0, 0, 0));
+
 const NUM_PEERS_RETURNED: usize = 20;
 
 #[allow(dead_code)]
@@ -57,6 +60,11 @@ pub struct InnerMockTrackerHandler {
     cids: HashSet<u64>,
     cid_generator: LocallyShuffledIds<u64>,
     peers_map: HashMap<InfoHash, HashSet<SocketAddr>>,
+    url_data: Vec<Vec<u8>>,
+    unregistered: HashSet<InfoHash>,
+    num_announces: usize,
+    announce_interval: Option<i32>,
+    min_announce_interval: Option<Duration>,
 }
 
 #[allow(dead_code)]
@@ -70,6 +78,11 @@ impl MockTrackerHandler {
                 cids: HashSet::new(),
                 cid_generator: LocallyShuffledIds::<u64>::new(),
                 peers_map: HashMap::new(),
+                url_data: Vec::new(),
+                unregistered: HashSet::new(),
+                num_announces: 0,
+                announce_interval: None,
+                min_announce_interval: None,
             })),
         }
     }
@@ -77,6 +90,31 @@ impl MockTrackerHandler {
     pub fn num_active_connect_ids(&self) -> usize {
         self.inner.lock().unwrap().cids.len()
     }
+
+    /// Url data of every announce that supplied it, in the order received.
+    pub fn announced_url_data(&self) -> Vec<Vec<u8>> {
+        self.inner.lock().unwrap().url_data.clone()
+    }
+
+    /// Respond to announces for the given torrent with an unregistered torrent error.
+    pub fn unregister(&self, hash: InfoHash) {
+        self.inner.lock().unwrap().unregistered.insert(hash);
+    }
+
+    /// Number of announces received with a valid connection id.
+    pub fn num_announces(&self) -> usize {
+        self.inner.lock().unwrap().num_announces
+    }
+
+    /// Respond to announces with the given interval, instead of the default one.
+    pub fn set_announce_interval(&self, interval: i32) {
+        self.inner.lock().unwrap().announce_interval = Some(interval);
+    }
+
+    /// Reject regular announces of a client arriving sooner than the given interval after its previous one.
+    pub fn set_min_announce_interval(&self, min_interval: Duration) {
+        self.inner.lock().unwrap().min_announce_interval = Some(min_interval);
+    }
 }
 
 impl ServerHandler for MockTrackerHandler {
@@ -104,11 +142,22 @@ impl ServerHandler for MockTrackerHandler {
         let mut inner_lock = self.inner.lock().unwrap();
 
         if inner_lock.cids.contains(&id) {
+            inner_lock.num_announces += 1;
+            if inner_lock.unregistered.contains(&req.info_hash()) {
+                return Some(Err("Unregistered torrent"));
+            }
+
+            if let Some(url_data) = req.url_data() {
+                inner_lock.url_data.push(url_data.url_data().to_vec());
+            }
+
             let peers = inner_lock.peers_map.entry(req.info_hash()).or_default();
-            // Ignore any source ip directives in the request
-            let store_addr = match addr {
-                SocketAddr::V4(v4_addr) => SocketAddr::V4(SocketAddrV4::new(*v4_addr.ip(), req.port())),
-                SocketAddr::V6(v6_addr) => SocketAddr::V6(SocketAddrV6::new(*v6_addr.ip(), req.port(), 0, 0)),
+            // Use an explicit source ip from the request, otherwise the address it was sent from
+            let store_addr = match (req.source_ip(), addr) {
+                (SourceIP::ExplicitV4(ip), _) => SocketAddr::V4(SocketAddrV4::new(ip, req.port())),
+                (SourceIP::ExplicitV6(ip), _) => SocketAddr::V6(SocketAddrV6::new(ip, req.port(), 0, 0)),
+                (_, SocketAddr::V4(v4_addr)) => SocketAddr::V4(SocketAddrV4::new(*v4_addr.ip(), req.port())),
+                (_, SocketAddr::V6(v6_addr)) => SocketAddr::V6(SocketAddrV6::new(*v6_addr.ip(), req.port(), 0, 0)),
             };
 
             // Resolve what to do with the event
@@ -161,6 +210,14 @@ impl ServerHandler for MockTrackerHandler {
         }
     }
 
+    fn announce_interval(&mut self, _: SocketAddr, _: &AnnounceRequest<'_>) -> Option<i32> {
+        self.inner.lock().unwrap().announce_interval
+    }
+
+    fn min_announce_interval(&mut self, _: SocketAddr, _: &AnnounceRequest<'_>) -> Option<Duration> {
+        self.inner.lock().unwrap().min_announce_interval
+    }
+
     #[instrument(skip(self), ret(level = Level::TRACE))]
     fn scrape(&mut self, _: SocketAddr, id: u64, req: &ScrapeRequest<'_>) -> Option<ServerResult<'_, ScrapeResponse<'_>>> {
         tracing::debug!("mock scrape");
@@ -187,6 +244,88 @@ impl ServerHandler for MockTrackerHandler {
     }
 }
 
+/// Services requests with a `MockTrackerHandler` from within the returned futures.
+#[derive(Debug, Clone)]
+pub struct MockAsyncTrackerHandler {
+    inner: MockTrackerHandler,
+    stalled: bool,
+}
+
+#[allow(dead_code)]
+impl MockAsyncTrackerHandler {
+    pub fn new() -> MockAsyncTrackerHandler {
+        MockAsyncTrackerHandler {
+            inner: MockTrackerHandler::new(),
+            stalled: false,
+        }
+    }
+
+    /// Handler whose futures never complete.
+    pub fn stalled() -> MockAsyncTrackerHandler {
+        MockAsyncTrackerHandler {
+            inner: MockTrackerHandler::new(),
+            stalled: true,
+        }
+    }
+
+    /// Handler servicing the requests once the futures are polled.
+    pub fn handler(&self) -> &MockTrackerHandler {
+        &self.inner
+    }
+
+    async fn wait(stalled: bool) {
+        if stalled {
+            futures::future::pending::<()>().await;
+        }
+
+        tokio::task::yield_now().await;
+    }
+}
+
+impl AsyncServerHandler for MockAsyncTrackerHandler {
+    fn connect(&mut self, addr: SocketAddr) -> ServerFuture<u64> {
+        let (mut inner, stalled) = (self.inner.clone(), self.stalled);
+
+        Box::pin(async move {
+            MockAsyncTrackerHandler::wait(stalled).await;
+
+            inner.connect(addr).map(|attempt| attempt.map_err(ToOwned::to_owned))
+        })
+    }
+
+    fn announce(&mut self, addr: SocketAddr, id: u64, req: AnnounceRequest<'static>) -> ServerFuture<AnnounceResponse<'static>> {
+        let (mut inner, stalled) = (self.inner.clone(), self.stalled);
+
+        Box::pin(async move {
+            MockAsyncTrackerHandler::wait(stalled).await;
+
+            inner
+                .announce(addr, id, &req)
+                .map(|attempt| attempt.map(|response| response.to_owned()).map_err(ToOwned::to_owned))
+        })
+    }
+
+    fn scrape(&mut self, addr: SocketAddr, id: u64, req: ScrapeRequest<'static>) -> ServerFuture<ScrapeResponse<'static>> {
+        let (mut inner, stalled) = (self.inner.clone(), self.stalled);
+
+        Box::pin(async move {
+            MockAsyncTrackerHandler::wait(stalled).await;
+
+            inner
+                .scrape(addr, id, &req)
+                .map(|attempt| attempt.map(|response| response.to_owned()).map_err(ToOwned::to_owned))
+        })
+    }
+
+    fn announce_interval(&mut self, addr: SocketAddr, req: &AnnounceRequest<'_>) -> Option<i32> {
+        ServerHandler::announce_interval(&mut self.inner, addr, req)
+    }
+
+    fn min_announce_interval(&mut self, addr: SocketAddr, req: &AnnounceRequest<'_>) -> Option<Duration> {
+        ServerHandler::min_announce_interval(&mut self.inner, addr, req)
+    }
+}
+
 //----------------------------------------------------------------------------//
 
 #[allow(dead_code)]
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::error::ErrorReason;
+use utracker::{ClientError, ClientMetadata, ClientRequest, HandshakerMessage, TrackerClient, TrackerServer};
+
+mod common;
+
+async fn next_metadata(handshaker_receiver: &mut common::MockHandshakerStream) -> ClientMetadata {
+    loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => return metadata,
+        }
+    }
+}
+
+#[tokio::test]
+async fn positive_announce_interval_override() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    mock_handler.set_announce_interval(3600);
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+
+    client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Started)),
+        )
+        .unwrap();
+
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+    let response = metadata.result().as_ref().unwrap().announce_response().unwrap();
+
+    assert_eq!(3600, response.interval());
+}
+
+#[tokio::test]
+async fn negative_announce_before_min_interval() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    mock_handler.set_min_announce_interval(Duration::from_secs(60));
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler.clone()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let request = ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::None));
+
+    client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+
+    // Announcing again right away is rejected without reaching the handler
+    client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+
+    let Err(ClientError::ServerMessage(response)) = metadata.result() else {
+        panic!("expected an error message, got: {:?}", metadata.result());
+    };
+    assert_eq!(ErrorReason::RateLimited, response.reason());
+    assert_eq!(1, mock_handler.num_announces());
+
+    // Events are never rejected
+    client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Stopped)),
+        )
+        .unwrap();
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+    assert_eq!(2, mock_handler.num_announces());
+}
@@ -0,0 +1,59 @@
+use std::net::SocketAddr;
+
+use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::{HandshakerMessage, TrackerClient, TrackerServer, TrackerStatus};
+
+mod common;
+
+#[tokio::test]
+async fn positive_announce_all() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let server_one = TrackerServer::run(LOOPBACK_IPV4, MockTrackerHandler::new()).unwrap();
+    let server_two = TrackerServer::run(LOOPBACK_IPV4, MockTrackerHandler::new()).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+
+    tracing::debug!("sending announces");
+    let mut multi = client.announce_all(
+        &[server_one.local_addr(), server_two.local_addr()],
+        hash,
+        ClientState::new(0, 0, 0, AnnounceEvent::Started),
+    );
+    assert!(!multi.is_complete());
+
+    tracing::debug!("receiving client metadata");
+    while !multi.is_complete() {
+        let message = tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        if let HandshakerMessage::ClientMetadata(metadata) = message {
+            assert!(multi.recv_metadata(&metadata));
+        }
+    }
+
+    let exp_peer_addr: SocketAddr = "127.0.0.1:6969".parse().unwrap();
+
+    assert_eq!(hash, multi.hash());
+    assert_eq!(2, multi.num_announced());
+    assert_eq!(vec![exp_peer_addr], multi.peers());
+    assert_eq!(server_one.local_addr(), multi.trackers()[0].0);
+    assert_eq!(server_two.local_addr(), multi.trackers()[1].0);
+    assert!(multi
+        .trackers()
+        .iter()
+        .all(|(_, status)| matches!(status, TrackerStatus::Announced(_))));
+}
@@ -0,0 +1,67 @@
+use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::error::{ErrorReason, RetryClass};
+use utracker::{ClientError, ClientMetadata, ClientRequest, HandshakerMessage, TrackerClient, TrackerServer};
+
+mod common;
+
+async fn next_metadata(handshaker_receiver: &mut common::MockHandshakerStream) -> ClientMetadata {
+    loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => return metadata,
+        }
+    }
+}
+
+#[tokio::test]
+async fn positive_stop_announcing_unregistered_torrent() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    mock_handler.unregister(hash);
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler.clone()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+    let request = ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Started));
+
+    let send_token = client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+    assert_eq!(send_token, metadata.token());
+
+    let Err(err @ ClientError::ServerMessage(response)) = metadata.result() else {
+        panic!("expected an error message, got: {:?}", metadata.result());
+    };
+    assert_eq!(ErrorReason::UnregisteredTorrent, response.reason());
+    assert_eq!(RetryClass::Permanent, err.retry());
+    assert_eq!(1, mock_handler.num_announces());
+
+    let health = client.tracker_health(server.local_addr()).unwrap();
+    assert_eq!(Some(response), health.rejection(&hash));
+
+    // The re-announce fails without reaching the tracker
+    let send_token = client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut handshaker_receiver).await;
+    assert_eq!(send_token, metadata.token());
+    assert!(matches!(metadata.result(), Err(ClientError::ServerMessage(_))));
+    assert_eq!(1, mock_handler.num_announces());
+
+    // Until the rejection is cleared
+    assert!(client.clear_rejected_torrent(server.local_addr(), hash));
+    client.request(server.local_addr(), request).unwrap();
+    next_metadata(&mut handshaker_receiver).await;
+    assert_eq!(2, mock_handler.num_announces());
+}
@@ -0,0 +1,154 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use common::{handshaker, tracing_stderr_init, MockHandshakerStream, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tokio::net::UdpSocket;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, AnnounceRequest, AnnounceResponse, ClientState, MAX_ANNOUNCE_PEERS_V4};
+use utracker::contact::{CompactPeers, CompactPeersV4};
+use utracker::scrape::{ScrapeRequest, ScrapeResponse};
+use utracker::{
+    AnnounceWarning, ClientMetadata, ClientRequest, HandshakerMessage, ServerHandler, ServerResult, TrackerClient, TrackerServer,
+};
+
+mod common;
+
+const NUM_PEERS: u16 = 1000;
+
+fn peer(port: u16) -> SocketAddrV4 {
+    SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), port)
+}
+
+/// Handler that responds to every announce with more peers than fit in a datagram.
+#[derive(Debug)]
+struct ManyPeersHandler;
+
+impl ServerHandler for ManyPeersHandler {
+    fn connect(&mut self, _addr: SocketAddr) -> Option<ServerResult<'_, u64>> {
+        Some(Ok(1))
+    }
+
+    fn announce(
+        &mut self,
+        _addr: SocketAddr,
+        _id: u64,
+        _req: &AnnounceRequest<'_>,
+    ) -> Option<ServerResult<'_, AnnounceResponse<'_>>> {
+        let mut peers = CompactPeersV4::new();
+        for port in 0..NUM_PEERS {
+            peers.insert(peer(port));
+        }
+
+        Some(Ok(AnnounceResponse::new(1800, NUM_PEERS.into(), 0, CompactPeers::V4(peers))))
+    }
+
+    fn scrape(&mut self, _addr: SocketAddr, _id: u64, _req: &ScrapeRequest<'_>) -> Option<ServerResult<'_, ScrapeResponse<'_>>> {
+        None
+    }
+}
+
+/// Receive messages from the client until the metadata of the request arrives.
+async fn recv_metadata(handshaker_receiver: &mut MockHandshakerStream) -> ClientMetadata {
+    loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => break metadata,
+        }
+    }
+}
+
+#[tokio::test]
+async fn positive_server_truncates_peers_to_datagram() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, ManyPeersHandler).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Started)),
+        )
+        .unwrap();
+
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    let response = metadata.result().as_ref().unwrap().announce_response().unwrap();
+
+    // The peers listed first by the handler are kept
+    let expected: Vec<SocketAddr> = (0..)
+        .map(|port| SocketAddr::V4(peer(port)))
+        .take(MAX_ANNOUNCE_PEERS_V4)
+        .collect();
+    assert_eq!(expected, response.peers().iter().collect::<Vec<_>>());
+    assert_eq!(Some(AnnounceWarning::DatagramFull), metadata.warning());
+}
+
+#[tokio::test]
+async fn positive_client_warns_of_cut_short_response() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    // Tracker that does not truncate its responses, so they are cut short by the receive buffer of the client
+    let tracker = UdpSocket::bind(LOOPBACK_IPV4).await.unwrap();
+    let tracker_addr = tracker.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buffer = [0u8; 1500];
+
+        loop {
+            let (len, addr) = tracker.recv_from(&mut buffer).await.unwrap();
+            let request = &buffer[..len];
+
+            // Connect requests start with the protocol id, other requests with our connection id
+            let mut response = Vec::new();
+            if request[..8] == 0x0417_2710_1980_u64.to_be_bytes() {
+                response.extend_from_slice(&0u32.to_be_bytes());
+                response.extend_from_slice(&request[12..16]);
+                response.extend_from_slice(&1u64.to_be_bytes());
+            } else {
+                let mut peers = CompactPeersV4::new();
+                for port in 0..NUM_PEERS {
+                    peers.insert(peer(port));
+                }
+
+                response.extend_from_slice(&1u32.to_be_bytes());
+                response.extend_from_slice(&request[12..16]);
+                AnnounceResponse::new(1800, NUM_PEERS.into(), 0, CompactPeers::V4(peers))
+                    .write_bytes(&mut response)
+                    .unwrap();
+            }
+
+            tracker.send_to(&response, addr).await.unwrap();
+        }
+    });
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    client
+        .request(
+            tracker_addr,
+            ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Started)),
+        )
+        .unwrap();
+
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    let response = metadata.result().as_ref().unwrap().announce_response().unwrap();
+
+    // Whole peers that made it in to the buffer are kept
+    assert_eq!((1500 - 20) / 6, response.peers().len());
+    assert_eq!(Some(AnnounceWarning::Truncated), metadata.warning());
+}
@@ -0,0 +1,51 @@
+use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::{HandshakerMessage, TrackerClient, TrackerServer};
+
+mod common;
+
+#[tokio::test]
+async fn positive_announce_url_data() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler.clone()).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+
+    tracing::debug!("sending announce");
+    let send_token = client
+        .announce_with_url_data(
+            server.local_addr(),
+            hash,
+            ClientState::new(0, 0, 0, AnnounceEvent::Started),
+            b"/announce?passkey=abc",
+        )
+        .unwrap();
+
+    tracing::debug!("receiving client metadata");
+    let metadata = loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => break metadata,
+        }
+    };
+
+    assert_eq!(send_token, metadata.token());
+    assert!(metadata.result().as_ref().unwrap().announce_response().is_some());
+    assert_eq!(vec![b"/announce?passkey=abc".to_vec()], mock_handler.announced_url_data());
+}
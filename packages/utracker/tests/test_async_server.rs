@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use common::{handshaker, tracing_stderr_init, MockAsyncTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::error::ErrorReason;
+use utracker::{AsyncServerConfig, ClientError, ClientMetadata, ClientRequest, HandshakerMessage, TrackerClient, TrackerServer};
+
+mod common;
+
+async fn next_metadata(handshaker_receiver: &mut common::MockHandshakerStream) -> ClientMetadata {
+    loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => return metadata,
+        }
+    }
+}
+
+#[tokio::test]
+async fn positive_async_receive_announce() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (sink, mut stream) = handshaker();
+
+    let server = TrackerServer::run_async(LOOPBACK_IPV4, MockAsyncTrackerHandler::new(), AsyncServerConfig::new()).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, sink, None).unwrap();
+
+    let send_token = client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(
+                [0u8; bt::INFO_HASH_LEN].into(),
+                ClientState::new(0, 0, 0, AnnounceEvent::Started),
+            ),
+        )
+        .unwrap();
+
+    let _init_msg = match tokio::time::timeout(DEFAULT_TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        HandshakerMessage::InitiateMessage(message) => message,
+        HandshakerMessage::ClientMetadata(_) => unreachable!(),
+    };
+
+    let metadata = match tokio::time::timeout(DEFAULT_TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        HandshakerMessage::InitiateMessage(_) => unreachable!(),
+        HandshakerMessage::ClientMetadata(metadata) => metadata,
+    };
+
+    assert_eq!(send_token, metadata.token());
+
+    let response = metadata.result().as_ref().unwrap().announce_response().unwrap();
+    assert_eq!(response.leechers(), 1);
+    assert_eq!(response.seeders(), 1);
+}
+
+#[tokio::test]
+async fn negative_async_request_timeout() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (sink, mut stream) = handshaker();
+
+    let config = AsyncServerConfig::new().with_request_timeout(Duration::from_millis(100));
+    let server = TrackerServer::run_async(LOOPBACK_IPV4, MockAsyncTrackerHandler::stalled(), config).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, sink, None).unwrap();
+
+    let send_token = client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(
+                [0u8; bt::INFO_HASH_LEN].into(),
+                ClientState::new(0, 0, 0, AnnounceEvent::None),
+            ),
+        )
+        .unwrap();
+
+    let metadata = match tokio::time::timeout(DEFAULT_TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        HandshakerMessage::InitiateMessage(_) => unreachable!(),
+        HandshakerMessage::ClientMetadata(metadata) => metadata,
+    };
+
+    assert_eq!(send_token, metadata.token());
+    assert!(matches!(metadata.result(), Err(ClientError::ServerMessage(_))));
+}
+
+#[tokio::test]
+async fn positive_async_announce_interval_override() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (sink, mut stream) = handshaker();
+
+    let mock_handler = MockAsyncTrackerHandler::new();
+    mock_handler.handler().set_announce_interval(3600);
+
+    let server = TrackerServer::run_async(LOOPBACK_IPV4, mock_handler, AsyncServerConfig::new()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, sink, None).unwrap();
+
+    client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(
+                [0u8; bt::INFO_HASH_LEN].into(),
+                ClientState::new(0, 0, 0, AnnounceEvent::Started),
+            ),
+        )
+        .unwrap();
+
+    let metadata = next_metadata(&mut stream).await;
+    let response = metadata.result().as_ref().unwrap().announce_response().unwrap();
+
+    assert_eq!(3600, response.interval());
+}
+
+#[tokio::test]
+async fn negative_async_announce_before_min_interval() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (sink, mut stream) = handshaker();
+
+    let mock_handler = MockAsyncTrackerHandler::new();
+    mock_handler.handler().set_min_announce_interval(Duration::from_secs(60));
+
+    let server = TrackerServer::run_async(LOOPBACK_IPV4, mock_handler.clone(), AsyncServerConfig::new()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, sink, None).unwrap();
+    let request = ClientRequest::Announce(
+        [0u8; bt::INFO_HASH_LEN].into(),
+        ClientState::new(0, 0, 0, AnnounceEvent::None),
+    );
+
+    client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut stream).await;
+    assert!(metadata.result().is_ok());
+
+    // Announcing again right away is rejected without reaching the handler
+    client.request(server.local_addr(), request).unwrap();
+    let metadata = next_metadata(&mut stream).await;
+
+    let Err(ClientError::ServerMessage(response)) = metadata.result() else {
+        panic!("expected an error message, got: {:?}", metadata.result());
+    };
+    assert_eq!(ErrorReason::RateLimited, response.reason());
+    assert_eq!(1, mock_handler.handler().num_announces());
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{handshaker, tracing_stderr_init, MockHandshakerStream, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, AnnounceRequest, AnnounceResponse, ClientState};
+use utracker::contact::{CompactPeers, CompactPeersV4};
+use utracker::scrape::{ScrapeRequest, ScrapeResponse};
+use utracker::{ClientMetadata, ClientRequest, HandshakerMessage, ServerHandler, ServerResult, TrackerClient, TrackerServer};
+
+mod common;
+
+/// Longer than the first two retransmit timeouts of a request.
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Handler that loses track of every connection id after answering a single announce with it,
+/// as a tracker would once the NAT mapping of the client changed.
+#[derive(Debug, Default)]
+struct ForgetfulHandler {
+    next_id: u64,
+    answered: HashSet<u64>,
+}
+
+impl ServerHandler for ForgetfulHandler {
+    fn connect(&mut self, _addr: SocketAddr) -> Option<ServerResult<'_, u64>> {
+        self.next_id += 1;
+
+        Some(Ok(self.next_id))
+    }
+
+    fn announce(
+        &mut self,
+        _addr: SocketAddr,
+        id: u64,
+        _req: &AnnounceRequest<'_>,
+    ) -> Option<ServerResult<'_, AnnounceResponse<'_>>> {
+        if !self.answered.insert(id) {
+            return None;
+        }
+
+        Some(Ok(AnnounceResponse::new(1800, 0, 0, CompactPeers::V4(CompactPeersV4::new()))))
+    }
+
+    fn scrape(&mut self, _addr: SocketAddr, _id: u64, _req: &ScrapeRequest<'_>) -> Option<ServerResult<'_, ScrapeResponse<'_>>> {
+        None
+    }
+}
+
+/// Handler that loses a single announce, as if the datagram was dropped, and only ever
+/// answers to the first connection id it gave out.
+#[derive(Debug, Default)]
+struct LossyHandler {
+    opt_id: Option<u64>,
+    num_announces: usize,
+}
+
+impl ServerHandler for LossyHandler {
+    fn connect(&mut self, _addr: SocketAddr) -> Option<ServerResult<'_, u64>> {
+        Some(Ok(*self.opt_id.get_or_insert(1)))
+    }
+
+    fn announce(
+        &mut self,
+        _addr: SocketAddr,
+        id: u64,
+        _req: &AnnounceRequest<'_>,
+    ) -> Option<ServerResult<'_, AnnounceResponse<'_>>> {
+        self.num_announces += 1;
+        if self.num_announces == 2 || self.opt_id != Some(id) {
+            return None;
+        }
+
+        Some(Ok(AnnounceResponse::new(1800, 0, 0, CompactPeers::V4(CompactPeersV4::new()))))
+    }
+
+    fn scrape(&mut self, _addr: SocketAddr, _id: u64, _req: &ScrapeRequest<'_>) -> Option<ServerResult<'_, ScrapeResponse<'_>>> {
+        None
+    }
+}
+
+async fn recv_metadata(handshaker_receiver: &mut MockHandshakerStream) -> ClientMetadata {
+    match tokio::time::timeout(RETRANSMIT_TIMEOUT, handshaker_receiver.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        HandshakerMessage::ClientMetadata(metadata) => metadata,
+        HandshakerMessage::InitiateMessage(_) => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn positive_refresh_connection_id_after_nat_rebinding() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, ForgetfulHandler::default()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let state = ClientState::new(0, 0, 0, AnnounceEvent::None);
+
+    client
+        .request(server.local_addr(), ClientRequest::Announce(hash, state))
+        .unwrap();
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+    assert!(!metadata.nat_rebinding());
+
+    // The tracker no longer answers to the cached connection id, so once the announce has timed out
+    // a few times in a row a new one is requested and the announce is sent again
+    client
+        .request(server.local_addr(), ClientRequest::Announce(hash, state))
+        .unwrap();
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+    assert!(metadata.nat_rebinding());
+
+    assert_eq!(1, client.tracker_health(server.local_addr()).unwrap().nat_rebindings());
+}
+
+#[tokio::test]
+async fn negative_single_lost_datagram_keeps_connection_id() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let server = TrackerServer::run(LOOPBACK_IPV4, LossyHandler::default()).unwrap();
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let state = ClientState::new(0, 0, 0, AnnounceEvent::None);
+
+    client
+        .request(server.local_addr(), ClientRequest::Announce(hash, state))
+        .unwrap();
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+
+    // The first send of this announce is lost, the retransmit with the same connection id is answered
+    client
+        .request(server.local_addr(), ClientRequest::Announce(hash, state))
+        .unwrap();
+    let metadata = recv_metadata(&mut handshaker_receiver).await;
+    assert!(metadata.result().is_ok());
+    assert!(!metadata.nat_rebinding());
+
+    assert_eq!(0, client.tracker_health(server.local_addr()).unwrap().nat_rebindings());
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+
 use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
 use futures::StreamExt as _;
 use tracing::level_filters::LevelFilter;
-use util::bt::{self};
+use util::bt::{self, InfoHash};
+use utracker::scrape::MAX_SCRAPE_HASHES;
 use utracker::{ClientRequest, HandshakerMessage, TrackerClient, TrackerServer};
 
 mod common;
@@ -43,3 +46,56 @@ async fn positive_scrape() {
     assert_eq!(stats.num_downloads(), 0);
     assert_eq!(stats.num_leechers(), 0);
 }
+
+#[tokio::test]
+async fn positive_scrape_batch() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (sink, mut stream) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, sink, None).unwrap();
+
+    // Nothing to scrape, so no empty batch is sent
+    assert!(client.scrape(server.local_addr(), &[]).is_empty());
+
+    let hashes: Vec<InfoHash> = (0..=100u8).map(|i| [i; bt::INFO_HASH_LEN].into()).collect();
+
+    let send_tokens = client.scrape(server.local_addr(), &hashes);
+    assert_eq!(send_tokens.len(), 2);
+
+    let mut responses = HashMap::new();
+    for _ in 0..send_tokens.len() {
+        let metadata = match tokio::time::timeout(DEFAULT_TIMEOUT, stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => unreachable!(),
+            HandshakerMessage::ClientMetadata(metadata) => metadata,
+        };
+
+        let response = metadata.result().as_ref().unwrap().scrape_batch_response().unwrap().to_vec();
+        responses.insert(metadata.token(), response);
+    }
+
+    assert_eq!(responses[&send_tokens[0]].len(), MAX_SCRAPE_HASHES);
+
+    let stats: Vec<_> = send_tokens
+        .iter()
+        .flat_map(|token| responses.remove(token).unwrap())
+        .collect();
+
+    assert_eq!(stats.len(), hashes.len());
+    for ((hash, stats), expected_hash) in stats.iter().zip(hashes.iter()) {
+        assert_eq!(hash, expected_hash);
+        assert_eq!(stats.num_seeders(), 0);
+        assert_eq!(stats.num_downloads(), 0);
+        assert_eq!(stats.num_leechers(), 0);
+    }
+}
@@ -0,0 +1,57 @@
+use common::{handshaker, tracing_stderr_init, MockTrackerHandler, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::announce::{AnnounceEvent, ClientState};
+use utracker::{ClientRequest, HandshakerMessage, TrackerClient, TrackerServer};
+
+mod common;
+
+#[tokio::test]
+async fn positive_tracker_health_after_announce() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let mock_handler = MockTrackerHandler::new();
+    let server = TrackerServer::run(LOOPBACK_IPV4, mock_handler).unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    assert!(client.tracker_health(server.local_addr()).is_none());
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let send_token = client
+        .request(
+            server.local_addr(),
+            ClientRequest::Announce(hash, ClientState::new(0, 0, 0, AnnounceEvent::Started)),
+        )
+        .unwrap();
+
+    // Skip the initiate message for our own peer, health is recorded before the metadata is sent
+    let metadata = loop {
+        match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+        {
+            HandshakerMessage::InitiateMessage(_) => (),
+            HandshakerMessage::ClientMetadata(metadata) => break metadata,
+        }
+    };
+    assert_eq!(send_token, metadata.token());
+    assert!(metadata.result().is_ok());
+
+    let health = client.tracker_health(server.local_addr()).unwrap();
+
+    assert_eq!(Some(1.0), health.success_rate());
+    assert_eq!(0, health.consecutive_failures());
+    assert!(health.last_announce().is_some());
+    assert!(health.average_rtt().is_some());
+
+    let trackers = client.trackers_health();
+    assert_eq!(vec![(server.local_addr(), health)], trackers);
+}
@@ -0,0 +1,142 @@
+use std::net::SocketAddr;
+
+use common::{handshaker, tracing_stderr_init, DEFAULT_TIMEOUT, INIT, LOOPBACK_IPV4};
+use futures::StreamExt as _;
+use tokio::net::UdpSocket;
+use tracing::level_filters::LevelFilter;
+use util::bt::{self};
+use utracker::request::{RequestType, TrackerRequest};
+use utracker::response::{ResponseType, TrackerResponse};
+use utracker::scrape::{ScrapeResponse, ScrapeStats};
+use utracker::{ClientRequest, HandshakerMessage, TrackerClient};
+
+mod common;
+
+/// Receive the next request sent to the tracker, yielding its connection id and transaction id.
+async fn recv_request(tracker: &UdpSocket) -> (SocketAddr, u64, u32) {
+    let mut buffer = [0u8; 1500];
+    let (len, addr) = tokio::time::timeout(DEFAULT_TIMEOUT, tracker.recv_from(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let (_, request) = TrackerRequest::from_bytes(&buffer[..len]).unwrap();
+    assert!(matches!(
+        request.request_type(),
+        RequestType::Connect | RequestType::Scrape(_)
+    ));
+
+    (addr, request.connection_id(), request.transaction_id())
+}
+
+async fn send_response(socket: &UdpSocket, addr: SocketAddr, response: TrackerResponse<'_>) {
+    let mut bytes = Vec::new();
+    response.write_bytes(&mut bytes).unwrap();
+
+    socket.send_to(&bytes, addr).await.unwrap();
+}
+
+fn scrape_response(seeders: i32) -> ResponseType<'static> {
+    let mut response = ScrapeResponse::new();
+    response.insert(ScrapeStats::new(seeders, 0, 0));
+
+    ResponseType::Scrape(response)
+}
+
+#[tokio::test]
+async fn positive_drop_spoofed_responses() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, mut handshaker_receiver) = handshaker();
+
+    let tracker = UdpSocket::bind(LOOPBACK_IPV4).await.unwrap();
+    let spoofer = UdpSocket::bind(LOOPBACK_IPV4).await.unwrap();
+
+    let mut client = TrackerClient::run(LOOPBACK_IPV4, handshaker_sender, None).unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    let send_token = client
+        .request(tracker.local_addr().unwrap(), ClientRequest::Scrape(hash))
+        .unwrap();
+
+    // Responses from the wrong address, or with the wrong transaction id, are dropped
+    let (client_addr, _, connect_id) = recv_request(&tracker).await;
+    send_response(
+        &spoofer,
+        client_addr,
+        TrackerResponse::new(connect_id, ResponseType::Connect(1)),
+    )
+    .await;
+    send_response(
+        &tracker,
+        client_addr,
+        TrackerResponse::new(!connect_id, ResponseType::Connect(2)),
+    )
+    .await;
+    send_response(
+        &tracker,
+        client_addr,
+        TrackerResponse::new(connect_id, ResponseType::Connect(3)),
+    )
+    .await;
+
+    let (_, conn_id, scrape_id) = recv_request(&tracker).await;
+    assert_eq!(3, conn_id);
+
+    // Late responses to the connect request are no longer accepted either
+    send_response(&spoofer, client_addr, TrackerResponse::new(scrape_id, scrape_response(1))).await;
+    send_response(&tracker, client_addr, TrackerResponse::new(connect_id, scrape_response(2))).await;
+    send_response(&tracker, client_addr, TrackerResponse::new(scrape_id, scrape_response(3))).await;
+
+    let metadata = match tokio::time::timeout(DEFAULT_TIMEOUT, handshaker_receiver.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        HandshakerMessage::ClientMetadata(metadata) => metadata,
+        HandshakerMessage::InitiateMessage(_) => unreachable!(),
+    };
+    assert_eq!(send_token, metadata.token());
+
+    let response = metadata.result().as_ref().unwrap().scrape_response().unwrap();
+    assert_eq!(3, response.iter().next().unwrap().num_seeders());
+}
+
+#[tokio::test]
+async fn positive_custom_transaction_ids() {
+    INIT.call_once(|| {
+        tracing_stderr_init(LevelFilter::ERROR);
+    });
+
+    let (handshaker_sender, _handshaker_receiver) = handshaker();
+
+    let tracker = UdpSocket::bind(LOOPBACK_IPV4).await.unwrap();
+
+    let mut next_id = 100;
+    let mut client = TrackerClient::run_with_transaction_ids(LOOPBACK_IPV4, handshaker_sender, None, move || {
+        next_id += 1;
+        next_id
+    })
+    .unwrap();
+
+    let hash = [0u8; bt::INFO_HASH_LEN].into();
+    client
+        .request(tracker.local_addr().unwrap(), ClientRequest::Scrape(hash))
+        .unwrap();
+
+    let (client_addr, _, connect_id) = recv_request(&tracker).await;
+    assert_eq!(101, connect_id);
+
+    send_response(
+        &tracker,
+        client_addr,
+        TrackerResponse::new(connect_id, ResponseType::Connect(1)),
+    )
+    .await;
+
+    let (_, _, scrape_id) = recv_request(&tracker).await;
+    assert_eq!(102, scrape_id);
+}